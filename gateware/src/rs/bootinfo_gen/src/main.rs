@@ -9,7 +9,7 @@
 use clap::Parser;
 use std::fs;
 use std::path::PathBuf;
-use tiliqua_lib::bootinfo::BootInfo;
+use tiliqua_lib::bootinfo::{BootInfo, ClockInfo};
 use tiliqua_manifest::BitstreamManifest;
 use tiliqua_hal::dma_framebuffer::DVIModeline;
 
@@ -37,6 +37,21 @@ struct Args {
     #[arg(long)]
     fixed_pclk_hz: u32,
 
+    /// Refresh rate in Hz. If set, a CVT reduced blanking modeline with
+    /// real timings is generated instead of a fixed one.
+    #[arg(long)]
+    refresh_hz: Option<f32>,
+
+    /// Audio clock in Hz, as if programmed into the external PLL. If not
+    /// set, no clocks are passed on (like a bitstream without one).
+    #[arg(long)]
+    audio_hz: Option<u32>,
+
+    /// Pixel clock in Hz passed on with `audio_hz`. Defaults to that of
+    /// the modeline.
+    #[arg(long)]
+    pixel_hz: Option<u32>,
+
     /// Output bootinfo binary file
     #[arg(short, long)]
     output: PathBuf,
@@ -51,23 +66,36 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let manifest: BitstreamManifest = serde_json::from_str(&manifest_str)?;
     println!("Manifest parsed successfully");
 
-    let modeline = DVIModeline::default().maybe_override_fixed(
-        Some((args.h_active, args.v_active)), 
-        args.fixed_pclk_hz
-    );
+    let modeline = match args.refresh_hz {
+        Some(refresh_hz) => DVIModeline::cvt_reduced_blanking(
+            args.h_active, args.v_active, refresh_hz
+        ).ok_or("no CVT modeline within the supported pixel clock range")?,
+        None => DVIModeline::default().maybe_override_fixed(
+            Some((args.h_active, args.v_active)),
+            args.fixed_pclk_hz
+        ),
+    };
+    println!("Modeline: {:?}", modeline);
+
+    let clocks = args.audio_hz.map(|audio_hz| ClockInfo {
+        audio_hz,
+        pixel_hz: Some(args.pixel_hz.unwrap_or(modeline.pixel_clk_hz())),
+    });
+    println!("Clocks: {:?}", clocks);
 
     let bootinfo = BootInfo {
         manifest,
         modeline,
+        last_panic: None,
+        clocks,
     };
 
-    // Serialize to binary using postcard (WARN: exactly match bootloader procedure!)
+    // Serialize exactly as the bootloader does, including the extension
+    // record that follows the original fields.
     let mut buffer = [0u8; 1024]; // BOOTINFO_MAX_SIZE
-    let crc_checker = crc::Crc::<u32>::new(&crc::CRC_32_BZIP2);
-    let digest = crc_checker.digest();
-    let serialized = postcard::to_slice_crc32(&bootinfo, &mut buffer, digest)?;
-    let serialized_len = serialized.len();
-    fs::write(&args.output, serialized)?;
+    let serialized_len = unsafe { bootinfo.to_addr(buffer.as_mut_ptr() as usize) }
+        .ok_or("BootInfo does not fit in BOOTINFO_MAX_SIZE")?;
+    fs::write(&args.output, &buffer[..serialized_len])?;
     println!("Generated bootinfo: {} bytes -> {:?}", serialized_len, args.output);
     Ok(())
 }
@@ -16,6 +16,13 @@ const CY8CMBR3XXX_CCITT16_POLYNOM: u16 = 0x1021;
 const REG_COMMAND: u8           = 0x86;
 const REG_CONFIG_CRC0: u8       = 0x7e;
 const REG_TOTAL_WORKING_SNS: u8 = 0x97;
+const REG_FINGER_THRESHOLD0: usize = 0x0C;
+// 16-bit little-endian registers. Difference counts are reported for
+// every sensor, the rest only for the one selected in SENSOR_ID.
+const REG_SENSOR_ID: u8         = 0x82;
+const REG_DIFFERENCE_COUNT0: u8 = 0xBA;
+const REG_DEBUG_BASELINE0: u8   = 0xE0;
+const REG_DEBUG_RAW_COUNT0: u8  = 0xE2;
 const CMD_SAVE_CHECK_CRC: u8    = 0x02;
 const CMD_WRITE_RESET: u8       = 0xFF;
 
@@ -150,9 +157,20 @@ const DEFAULT_CONFIG: [u8; CY8CMBR3XXX_CONFIG_DATA_LENGTH] = [
     0x00,
 ];
 
+/// Counts of a single sensor, for tuning. Noise margin is how far `diff`
+/// stays below the finger threshold when the electrode is not touched.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SensorCounts {
+    pub raw: u16,
+    pub baseline: u16,
+    pub diff: u16,
+}
+
 pub struct Cy8cmbr3108Driver<I2C> {
     i2c: I2C,
     pub config: [u8; CY8CMBR3XXX_CONFIG_DATA_LENGTH],
+    // Sensor last written to SENSOR_ID, if any.
+    debug_sensor: Option<u8>,
 }
 
 impl<I2C: I2c> Cy8cmbr3108Driver<I2C> {
@@ -167,7 +185,7 @@ impl<I2C: I2c> Cy8cmbr3108Driver<I2C> {
                 config[1] |= 1<<(*n_sensor-8);
             }
         }
-        Self { i2c, config }
+        Self { i2c, config, debug_sensor: None }
     }
 
     pub fn calculate_crc(&self) -> u16 {
@@ -234,6 +252,49 @@ impl<I2C: I2c> Cy8cmbr3108Driver<I2C> {
         Ok(total_working_sns & 0x1f)
     }
 
+    /// Configured finger threshold of sensor `index`, in difference counts.
+    pub fn finger_threshold(&self, index: u8) -> u8 {
+        self.config[REG_FINGER_THRESHOLD0 + index as usize]
+    }
+
+    /// Difference count (raw count minus baseline) of sensor `index`. This is
+    /// what is compared against the finger threshold.
+    pub fn read_sensor_diff(&mut self, index: u8) -> Result<u16, I2C::Error> {
+        self.read_register_u16(REG_DIFFERENCE_COUNT0 + 2*index)
+    }
+
+    /// Raw count of sensor `index`. The chip only reports raw counts and
+    /// baselines for one sensor at a time, so switching sensors takes until
+    /// the next scan to show up: the first read after a switch may still be
+    /// of the previous sensor.
+    pub fn read_sensor_raw(&mut self, index: u8) -> Result<u16, I2C::Error> {
+        self.select_debug_sensor(index)?;
+        self.read_register_u16(REG_DEBUG_RAW_COUNT0)
+    }
+
+    /// Baseline of sensor `index`, see `read_sensor_raw`.
+    pub fn read_sensor_baseline(&mut self, index: u8) -> Result<u16, I2C::Error> {
+        self.select_debug_sensor(index)?;
+        self.read_register_u16(REG_DEBUG_BASELINE0)
+    }
+
+    fn select_debug_sensor(&mut self, index: u8) -> Result<(), I2C::Error> {
+        if self.debug_sensor != Some(index) {
+            self.write_register(REG_SENSOR_ID, index)?;
+            self.debug_sensor = Some(index);
+        }
+        Ok(())
+    }
+
+    fn read_register_u16(&mut self, register: u8) -> Result<u16, I2C::Error> {
+        let mut buffer = [0u8; 2];
+        self.i2c.transaction(
+            CY8CMBR3108_ADDR,
+            &mut [Operation::Write(&[register]), Operation::Read(&mut buffer)]
+        )?;
+        Ok(u16::from_le_bytes(buffer))
+    }
+
     fn read_register(&mut self, register: u8) -> Result<u8, I2C::Error> {
         let mut buffer = [0u8];
         self.i2c.transaction(
@@ -262,6 +323,8 @@ impl<I2C: I2c> Cy8cmbr3108Driver<I2C> {
     }
 
     pub fn reset(&mut self) -> Result<(), I2C::Error> {
+        // Also resets the sensor selected for debug data.
+        self.debug_sensor = None;
         self.write_register(REG_COMMAND, CMD_WRITE_RESET)
     }
 }
@@ -288,4 +351,67 @@ mod tests {
         let mut cy8 = Cy8cmbr3108Driver::new(MockI2c, &[]);
         cy8.write_config_to_sram();
     }
+
+    /// Register file behind an auto-incrementing address pointer.
+    struct RegisterI2c {
+        regs: [u8; 256],
+        n_writes: usize,
+    }
+
+    impl embedded_hal::i2c::ErrorType for RegisterI2c {
+        type Error = core::convert::Infallible;
+    }
+
+    impl I2c for RegisterI2c {
+        fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>])
+            -> Result<(), Self::Error> {
+            assert_eq!(address, CY8CMBR3108_ADDR);
+            let mut ptr = 0usize;
+            for op in operations {
+                match op {
+                    Operation::Write(bytes) => {
+                        ptr = bytes[0] as usize;
+                        for b in &bytes[1..] {
+                            self.regs[ptr] = *b;
+                            ptr += 1;
+                            self.n_writes += 1;
+                        }
+                    }
+                    Operation::Read(buffer) => {
+                        for b in buffer.iter_mut() {
+                            *b = self.regs[ptr];
+                            ptr += 1;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_read_sensor_counts() {
+        let mut regs = [0u8; 256];
+        // Difference counts of sensors 0 and 5.
+        regs[0xBA..0xBC].copy_from_slice(&[0x34, 0x12]);
+        regs[0xC4..0xC6].copy_from_slice(&[0xFF, 0x00]);
+        // Debug baseline / raw count.
+        regs[0xE0..0xE2].copy_from_slice(&[0x00, 0x08]);
+        regs[0xE2..0xE4].copy_from_slice(&[0x80, 0x09]);
+        let mut cy8 = Cy8cmbr3108Driver::new(RegisterI2c { regs, n_writes: 0 }, &[]);
+
+        assert_eq!(cy8.read_sensor_diff(0), Ok(0x1234));
+        assert_eq!(cy8.read_sensor_diff(5), Ok(0x00FF));
+        assert_eq!(cy8.i2c.n_writes, 0);
+
+        // Raw count and baseline select the sensor for debug data first,
+        // but only when it changes.
+        assert_eq!(cy8.read_sensor_raw(5), Ok(0x0980));
+        assert_eq!(cy8.i2c.regs[0x82], 5);
+        assert_eq!(cy8.read_sensor_baseline(5), Ok(0x0800));
+        assert_eq!(cy8.i2c.n_writes, 1);
+        cy8.read_sensor_baseline(7).unwrap();
+        assert_eq!(cy8.i2c.regs[0x82], 7);
+        assert_eq!(cy8.i2c.n_writes, 2);
+    }
 }
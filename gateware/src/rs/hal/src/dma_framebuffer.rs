@@ -11,6 +11,33 @@ pub enum Rotate {
     Right = 3,
 }
 
+/// Full-screen patterns for diagnosing video problems, drawn without any
+/// application drawing code (see `draw_test_pattern` on the framebuffer).
+#[derive(Default, Debug, PartialEq, Clone, Copy, EnumIter, IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TestPattern {
+    /// A bar for each of the 16 hues, above a 16-step intensity ramp.
+    #[default]
+    ColorBars,
+    /// Grid with a border on the outermost pixels, a center cross, a
+    /// diagonal from the origin and a square marking the origin. Checks
+    /// the active area geometry and the rotation.
+    Crosshatch,
+    /// Solid fields, for dead pixels and color channels.
+    White,
+    Red,
+    Green,
+    Blue,
+}
+
+impl TestPattern {
+    /// Next pattern, wrapping around after the last one.
+    pub fn next(self) -> Self {
+        use strum::IntoEnumIterator;
+        TestPattern::iter().cycle().skip_while(|p| *p != self).nth(1).unwrap()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct DVIModeline {
    pub h_active:      u16,
@@ -27,11 +54,154 @@ pub struct DVIModeline {
    pub rotate:        Rotate,
 }
 
+/// Individually editable fields of a `DVIModeline`.
+#[derive(Debug, Clone, Copy, PartialEq, EnumIter, IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ModelineField {
+    HActive,
+    HSyncStart,
+    HSyncEnd,
+    HTotal,
+    HSyncInvert,
+    VActive,
+    VSyncStart,
+    VSyncEnd,
+    VTotal,
+    VSyncInvert,
+    /// Adjusted in kHz.
+    PixelClk,
+}
+
+/// Pixel clocks the dynamic DVI PLL can lock to. The lower bound is out of
+/// the ECP5 VCO spec, see `create_dynamic_dvi_pll` in `pll.py`, and the
+/// upper bound is the `PCLK_FMAX_DYNAMIC` timing constraint.
+pub const PIXEL_CLK_MIN_KHZ: u32 = 24_000;
+pub const PIXEL_CLK_MAX_KHZ: u32 = 74_250;
+
+/// Pixel clocks are rounded to steps the external PLL hits exactly.
+const PIXEL_CLK_STEP_KHZ: u32 = 250;
+
+/// Lowest pixel clock `DVIModeline::adjust` will go to.
+const PIXEL_CLK_ADJUST_MIN_KHZ: i32 = 1000;
+
+/// Adjust `timings[ix]` (one of active, sync start, sync end, total) by `delta`,
+/// pushing the other timings along such that they stay strictly increasing.
+fn adjust_ordered(timings: &mut [&mut u16; 4], ix: usize, delta: i32) {
+    let n = timings.len();
+    let lo = (ix + 1) as i32;
+    let hi = (u16::MAX as usize - (n - 1 - ix)) as i32;
+    *timings[ix] = (*timings[ix] as i32 + delta).clamp(lo, hi) as u16;
+    for i in ix+1..n {
+        if *timings[i] <= *timings[i-1] {
+            *timings[i] = *timings[i-1] + 1;
+        }
+    }
+    for i in (0..ix).rev() {
+        if *timings[i] >= *timings[i+1] {
+            *timings[i] = *timings[i+1] - 1;
+        }
+    }
+}
+
 impl DVIModeline {
     pub fn refresh_rate(&self) -> f32 {
         1e6f32 * self.pixel_clk_mhz / (self.h_total as u32 * self.v_total as u32) as f32
     }
 
+    /// VESA CVT reduced blanking (v2) timings for the given resolution and
+    /// refresh rate. The pixel clock is rounded to `PIXEL_CLK_STEP_KHZ`, so
+    /// the actual `refresh_rate()` may differ slightly from `refresh_hz`.
+    /// Returns `None` if the pixel clock is outside what the PLL supports.
+    pub fn cvt_reduced_blanking(h_active: u16, v_active: u16, refresh_hz: f32) -> Option<DVIModeline> {
+        const H_BLANK: u16 = 80;
+        const H_FRONT_PORCH: u16 = 8;
+        const H_SYNC: u16 = 32;
+        const V_SYNC: u16 = 8;
+        const V_BACK_PORCH: u16 = 6;
+        const V_FRONT_PORCH_MIN: u16 = 1;
+        const MIN_VBLANK_US: f32 = 460.0;
+        if h_active == 0 || v_active == 0 || refresh_hz <= 0.0f32 {
+            return None;
+        }
+        let h_period_us = (1e6f32 / refresh_hz - MIN_VBLANK_US) / v_active as f32;
+        if h_period_us <= 0.0f32 {
+            return None;
+        }
+        let vbi_lines = ((MIN_VBLANK_US / h_period_us) as u16 + 1)
+            .max(V_FRONT_PORCH_MIN + V_SYNC + V_BACK_PORCH);
+        let v_front_porch = vbi_lines - V_SYNC - V_BACK_PORCH;
+        let h_total = h_active.checked_add(H_BLANK)?;
+        let v_total = v_active.checked_add(vbi_lines)?;
+        let exact_khz = refresh_hz * h_total as f32 * v_total as f32 / 1e3f32;
+        let pixel_clk_khz = (exact_khz / PIXEL_CLK_STEP_KHZ as f32 + 0.5f32) as u32 * PIXEL_CLK_STEP_KHZ;
+        if !(PIXEL_CLK_MIN_KHZ..=PIXEL_CLK_MAX_KHZ).contains(&pixel_clk_khz) {
+            return None;
+        }
+        Some(DVIModeline {
+            h_active,
+            h_sync_start  : h_active + H_FRONT_PORCH,
+            h_sync_end    : h_active + H_FRONT_PORCH + H_SYNC,
+            h_total,
+            h_sync_invert : false,
+            v_active,
+            v_sync_start  : v_active + v_front_porch,
+            v_sync_end    : v_active + v_front_porch + V_SYNC,
+            v_total,
+            v_sync_invert : true,
+            pixel_clk_mhz : pixel_clk_khz as f32 / 1e3f32,
+            rotate        : Rotate::Normal,
+        })
+    }
+
+    pub fn pixel_clk_hz(&self) -> u32 {
+        (self.pixel_clk_mhz * 1e6f32) as u32
+    }
+
+    pub fn pixel_clk_khz(&self) -> u32 {
+        (self.pixel_clk_hz() + 500) / 1000
+    }
+
+    /// Nudge a single field by `delta` (any nonzero `delta` toggles polarities).
+    ///
+    /// Neighbouring timings are pushed along where needed, so the result
+    /// always satisfies `is_consistent()` and can be handed to the PLL and
+    /// framebuffer as-is.
+    pub fn adjust(&mut self, field: ModelineField, delta: i32) {
+        use ModelineField::*;
+        match field {
+            HActive | HSyncStart | HSyncEnd | HTotal => {
+                let ix = field as usize - HActive as usize;
+                adjust_ordered(&mut [&mut self.h_active, &mut self.h_sync_start,
+                                     &mut self.h_sync_end, &mut self.h_total], ix, delta);
+            }
+            VActive | VSyncStart | VSyncEnd | VTotal => {
+                let ix = field as usize - VActive as usize;
+                adjust_ordered(&mut [&mut self.v_active, &mut self.v_sync_start,
+                                     &mut self.v_sync_end, &mut self.v_total], ix, delta);
+            }
+            HSyncInvert => self.h_sync_invert ^= delta != 0,
+            VSyncInvert => self.v_sync_invert ^= delta != 0,
+            PixelClk => {
+                let khz = (self.pixel_clk_khz() as i32 + delta).max(PIXEL_CLK_ADJUST_MIN_KHZ);
+                self.pixel_clk_mhz = khz as f32 / 1e3f32;
+            }
+        }
+    }
+
+    /// Timings are strictly increasing in both directions and there is a
+    /// pixel clock, i.e. this describes a real (not fixed) mode.
+    pub fn is_consistent(&self) -> bool {
+        0 < self.h_active &&
+        self.h_active < self.h_sync_start &&
+        self.h_sync_start < self.h_sync_end &&
+        self.h_sync_end < self.h_total &&
+        0 < self.v_active &&
+        self.v_active < self.v_sync_start &&
+        self.v_sync_start < self.v_sync_end &&
+        self.v_sync_end < self.v_total &&
+        self.pixel_clk_mhz > 0.0f32
+    }
+
     pub fn fixed(&self) -> bool {
         self.v_total == 0
     }
@@ -81,10 +251,198 @@ impl Default for DVIModeline {
     }
 }
 
+/// Part of the active area that drawing maps into, in drawing (that is,
+/// rotated) coordinates. Shrinking it leaves a border, to compensate for
+/// displays that crop the edges of the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Viewport {
+    /// The whole active area of `mode`.
+    pub fn full(mode: &DVIModeline) -> Self {
+        let (width, height) = match mode.rotate {
+            Rotate::Normal | Rotate::Inverted => (mode.h_active, mode.v_active),
+            Rotate::Left | Rotate::Right => (mode.v_active, mode.h_active),
+        };
+        Self { x: 0, y: 0, width, height }
+    }
+
+    /// Viewport at (`x`, `y`) of `width` x `height`, shifted and shrunk
+    /// as needed to fit in the active area of `mode`, and at least 1x1.
+    pub fn clamped(mode: &DVIModeline, x: u16, y: u16, width: u16, height: u16) -> Self {
+        let full = Self::full(mode);
+        let width = width.clamp(1, full.width.max(1));
+        let height = height.clamp(1, full.height.max(1));
+        Self {
+            x: x.min(full.width.saturating_sub(width)),
+            y: y.min(full.height.saturating_sub(height)),
+            width,
+            height,
+        }
+    }
+
+    /// (width, height), as reported to `embedded-graphics`.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width as u32, self.height as u32)
+    }
+}
+
+/// Default time HPD must hold a new level before it is reported.
+pub const HPD_DEBOUNCE_MS: u64 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HpdEvent {
+    Connected,
+    Disconnected,
+}
+
+/// Debounced hotplug detect. A flaky connector can bounce HPD for a while,
+/// which should not be seen as several reconnects.
+pub struct VideoHpd {
+    /// HPD must be stable for this long before a change is reported.
+    pub debounce_ms: u64,
+    stable: bool,
+    raw: bool,
+    raw_since_ms: u64,
+}
+
+impl VideoHpd {
+    /// Start from an already stable `hpd`, which is not reported as an event.
+    pub fn new(hpd: bool, now_ms: u64) -> Self {
+        Self {
+            debounce_ms: HPD_DEBOUNCE_MS,
+            stable: hpd,
+            raw: hpd,
+            raw_since_ms: now_ms,
+        }
+    }
+
+    /// Feed the current raw HPD level, returning an event once a new level
+    /// has been held for `debounce_ms`.
+    pub fn poll(&mut self, hpd: bool, now_ms: u64) -> Option<HpdEvent> {
+        if hpd != self.raw {
+            self.raw = hpd;
+            self.raw_since_ms = now_ms;
+        }
+        if self.raw == self.stable || now_ms.saturating_sub(self.raw_since_ms) < self.debounce_ms {
+            return None;
+        }
+        self.stable = self.raw;
+        Some(if self.stable { HpdEvent::Connected } else { HpdEvent::Disconnected })
+    }
+
+    /// Last debounced HPD level.
+    pub fn connected(&self) -> bool {
+        self.stable
+    }
+}
+
+/// Default window over which `ScanoutMeter` counts frames.
+pub const SCANOUT_WINDOW_MS: u64 = 1000;
+
+/// Free-running scanout counters from the framebuffer gateware.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScanoutStats {
+    /// Frames scanned out since the bitstream started (wraps).
+    pub frames: u32,
+}
+
+/// Measured refresh rate, from the frame counter read across a window of
+/// known length. A flaky cable or failed PLL lock shows up as a refresh
+/// rate far from the modeline's, or no frames at all.
+pub struct ScanoutMeter {
+    /// Length of each measurement window.
+    pub window_ms: u64,
+    start: Option<(u32, u64)>,
+    refresh_hz: Option<f32>,
+}
+
+impl ScanoutMeter {
+    pub fn new() -> Self {
+        Self {
+            window_ms: SCANOUT_WINDOW_MS,
+            start: None,
+            refresh_hz: None,
+        }
+    }
+
+    /// Feed counters read at `now_ms`, returning the refresh rate measured
+    /// over the window just completed, if any.
+    pub fn poll(&mut self, stats: ScanoutStats, now_ms: u64) -> Option<f32> {
+        let (frames, since_ms) = match self.start {
+            Some(start) => start,
+            None => {
+                self.start = Some((stats.frames, now_ms));
+                return None;
+            }
+        };
+        let elapsed_ms = now_ms.saturating_sub(since_ms);
+        if elapsed_ms < self.window_ms {
+            return None;
+        }
+        let hz = stats.frames.wrapping_sub(frames) as f32 * 1000.0 / elapsed_ms as f32;
+        self.start = Some((stats.frames, now_ms));
+        self.refresh_hz = Some(hz);
+        self.refresh_hz
+    }
+
+    /// Refresh rate over the last complete window, `Some(0.0)` if nothing
+    /// was scanned out, or `None` until the first window completes.
+    pub fn refresh_hz(&self) -> Option<f32> {
+        self.refresh_hz
+    }
+
+    /// Line rate over the last complete window. There is no line counter,
+    /// but every frame is `v_total` lines of the modeline being scanned out.
+    pub fn line_hz(&self, modeline: &DVIModeline) -> Option<f32> {
+        self.refresh_hz.map(|hz| hz * modeline.v_total as f32)
+    }
+}
+
+impl Default for ScanoutMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub trait DMAFramebuffer {
     fn update_fb_base(&mut self, fb_base: u32);
     fn set_palette_rgb(&mut self, intensity: u8, hue: u8, r: u8, g: u8, b: u8);
     fn get_hpd(&mut self) -> bool;
+
+    /// Whether scanout is currently in vertical blanking. Always true while
+    /// the framebuffer is disabled.
+    fn in_vblank(&self) -> bool;
+
+    /// Whether scanout is enabled, i.e. blanking intervals come and go.
+    fn scanout_enabled(&self) -> bool;
+
+    /// Block until the start of the next vertical blanking interval.
+    ///
+    /// There is a single framebuffer, which is scanned out while it is drawn
+    /// to. Drawing only during blanking avoids tearing, at the cost of
+    /// waiting up to a frame and only having the blanking lines to draw in.
+    /// Heavy redraws that overrun it will still tear, only less often.
+    ///
+    /// Returns immediately while scanout is disabled, as there is nothing to
+    /// tear and `in_vblank` never falls.
+    fn wait_for_vblank(&mut self) {
+        while self.in_vblank() {
+            if !self.scanout_enabled() {
+                return;
+            }
+        }
+        while !self.in_vblank() { }
+    }
+
+    /// Current scanout counters. See `ScanoutMeter` for turning these into
+    /// a measured refresh rate.
+    fn scanout_stats(&self) -> ScanoutStats;
 }
 
 #[macro_export]
@@ -97,8 +455,9 @@ macro_rules! impl_dma_framebuffer {
         $LINEX:ident: $PACLINEX:ty,
     )+) => {
         $(
-            use tiliqua_hal::dma_framebuffer::{DVIModeline, Rotate};
-            use tiliqua_hal::embedded_graphics::prelude::{Pixel, Size, OriginDimensions, DrawTarget};
+            use tiliqua_hal::dma_framebuffer::{DVIModeline, Rotate, TestPattern, Viewport};
+            use tiliqua_hal::embedded_graphics::prelude::{Pixel, Point, Size, OriginDimensions, Dimensions, DrawTarget};
+            use tiliqua_hal::embedded_graphics::primitives::Rectangle;
             use tiliqua_lib::color::HI8;
 
             pub struct $DMA_FRAMEBUFFERX {
@@ -108,9 +467,18 @@ macro_rules! impl_dma_framebuffer {
                 registers_pixel_plot: $PACPIXEL_PLOTX,
                 registers_line: $PACLINEX,
                 mode: DVIModeline,
+                viewport: Viewport,
                 framebuffer_base: *mut u32,
                 blitter_mem_base: *mut u32,
                 current_spritesheet_key: u32,
+                auto_flush: bool,
+                // See `begin_batch`. Free FIFO entries we know about, so writes
+                // need no status read until these run out, and the end point of
+                // the last line, not yet enqueued in case the next line continues it.
+                batch: bool,
+                line_credits: u8,
+                plot_credits: u8,
+                line_pending: Option<(u16, u16, u8)>,
             }
 
             impl $DMA_FRAMEBUFFERX {
@@ -154,21 +522,201 @@ macro_rules! impl_dma_framebuffer {
                         registers_blitter,
                         registers_pixel_plot,
                         registers_line,
+                        viewport: Viewport::full(&mode),
                         mode,
                         framebuffer_base: fb_base as *mut u32,
                         blitter_mem_base: blitter_mem_base as *mut u32,
                         current_spritesheet_key: 0, // No spritesheet loaded initially
+                        auto_flush: false,
+                        batch: false,
+                        line_credits: 0,
+                        plot_credits: 0,
+                        line_pending: None,
+                    }
+                }
+
+                /// Flush the CPU data cache on every `end_frame`, for applications
+                /// that write framebuffer memory directly (see `flush`).
+                pub fn with_auto_flush(mut self, auto_flush: bool) -> Self {
+                    self.auto_flush = auto_flush;
+                    self
+                }
+
+                /// Flush the CPU data cache, so the CPU and the scanout agree on
+                /// the contents of framebuffer memory.
+                ///
+                /// Only needed after raw CPU accesses to framebuffer memory (through
+                /// `fb_base`), which go through the data cache. Everything drawn
+                /// through `DrawTarget` (pixel plot, line, blit and the fills built
+                /// on them) is written to memory by the accelerators, bypassing the
+                /// cache, and never needs a flush.
+                pub fn flush(&mut self) {
+                    pac::cpu::vexriscv::flush_dcache();
+                }
+
+                /// Start a batch of accelerated draws (pixels, lines and the fills
+                /// built on them), until `end_batch`.
+                ///
+                /// Outside a batch, every enqueued command first polls the FIFO
+                /// status, which for dense drawing costs as much as the command
+                /// itself. Inside a batch, the free space in each FIFO is read once
+                /// and spent without polling, so the status is only read again when
+                /// it runs out (and the CPU only spins if the FIFO is really full).
+                /// Consecutive lines of the same color sharing an end point are also
+                /// joined into a single line strip, saving a command per line.
+                ///
+                /// Ordering against blits and other draws is the same as without a
+                /// batch, and `end_frame` also ends the current batch.
+                pub fn begin_batch(&mut self) {
+                    self.batch = true;
+                }
+
+                /// End a batch started with `begin_batch`, enqueueing anything still
+                /// held back to join line strips.
+                pub fn end_batch(&mut self) {
+                    self.flush_line_strip();
+                    self.batch = false;
+                }
+
+                /// Wait for space in the line FIFO. In a batch, only poll the
+                /// status when the space known from the last poll has been used.
+                fn wait_line_fifo(&mut self) {
+                    if !self.batch {
+                        while self.registers_line.status().read().full().bit() {
+                            riscv::asm::nop();
+                        }
+                        return;
+                    }
+                    while self.line_credits == 0 {
+                        let status = self.registers_line.status().read();
+                        self.line_credits = status.fifo_depth().bits()
+                            .saturating_sub(status.fifo_level().bits());
+                    }
+                    self.line_credits -= 1;
+                }
+
+                fn wait_plot_fifo(&mut self) {
+                    if !self.batch {
+                        while self.registers_pixel_plot.status().read().busy().bit() {
+                            // Plotting FIFO is full. Spin.
+                            riscv::asm::nop();
+                        }
+                        return;
+                    }
+                    while self.plot_credits == 0 {
+                        let status = self.registers_pixel_plot.status().read();
+                        self.plot_credits = (status.fifo_depth().bits() as u16)
+                            .saturating_sub(status.fifo_level().bits()) as u8;
+                    }
+                    self.plot_credits -= 1;
+                }
+
+                fn write_line_point(&mut self, x: u16, y: u16, pixel: u8, end: bool) {
+                    self.wait_line_fifo();
+                    self.registers_line.point().write(|w| unsafe {
+                        w.x().bits(x);
+                        w.y().bits(y);
+                        w.pixel().bits(pixel);
+                        w.cmd().bit(end) // CONTINUE (0) or END (1) line strip
+                    });
+                }
+
+                /// Terminate the line strip held open by a batch, if any.
+                fn flush_line_strip(&mut self) {
+                    if let Some((x, y, pixel)) = self.line_pending.take() {
+                        self.write_line_point(x, y, pixel, true);
                     }
                 }
 
                 pub fn rotate(&mut self, rotation: &Rotate) {
+                    self.flush_line_strip();
                     self.registers_fb.flags().write(|w| unsafe {
                         w.enable().bit(true);
                         w.rotation().bits(rotation.clone() as u8)
                     });
                     self.mode.rotate = rotation.clone();
+                    let v = self.viewport;
+                    self.viewport = Viewport::clamped(&self.mode, v.x, v.y, v.width, v.height);
+                }
+
+                /// Draw into a `w` x `h` region at (`x_off`, `y_off`) of the active
+                /// area, rather than all of it, to compensate for displays that
+                /// crop (overscan) the image. The modeline is unchanged, the rest of
+                /// the active area is simply never drawn to. The viewport is clamped
+                /// to the active area, and `size()` reports its size, so layouts
+                /// based on it stay on screen.
+                ///
+                /// Pixels outside the viewport are dropped and accelerated lines are
+                /// clipped to it. Blits crossing its edges fall back to pixel plots.
+                pub fn set_viewport(&mut self, x_off: u16, y_off: u16, w: u16, h: u16) {
+                    self.flush_line_strip();
+                    self.viewport = Viewport::clamped(&self.mode, x_off, y_off, w, h);
+                }
+
+                /// Draw into the whole active area again.
+                pub fn reset_viewport(&mut self) {
+                    self.flush_line_strip();
+                    self.viewport = Viewport::full(&self.mode);
                 }
 
+                pub fn viewport(&self) -> Viewport {
+                    self.viewport
+                }
+
+                /// Fill a solid rectangle, clipped to the display.
+                ///
+                /// There is no dedicated fill in the hardware, so this enqueues one
+                /// accelerated line per row (or per column, if that is fewer lines).
+                /// Like other accelerated draws, the fill is executed asynchronously.
+                pub fn fill_rect_solid(&mut self, x: i32, y: i32, w: u32, h: u32, color: HI8) {
+                    let size = self.size();
+                    let x0 = x.max(0);
+                    let y0 = y.max(0);
+                    let x1 = (x as i64 + w as i64).min(size.width as i64) as i32;
+                    let y1 = (y as i64 + h as i64).min(size.height as i64) as i32;
+                    if x0 >= x1 || y0 >= y1 {
+                        // Entirely off-screen, nothing to draw.
+                        return;
+                    }
+                    if x1 - x0 >= y1 - y0 {
+                        for row in y0..y1 {
+                            self.draw_line_solid(x0, row, x1 - 1, row, 1, color);
+                        }
+                    } else {
+                        for col in x0..x1 {
+                            self.draw_line_solid(col, y0, col, y1 - 1, 1, color);
+                        }
+                    }
+                }
+
+                /// Fill the whole framebuffer with a test pattern, using only the
+                /// accelerated line and fill paths, so that a working pattern
+                /// proves the video pipeline independent of any other drawing.
+                pub fn draw_test_pattern(&mut self, pattern: TestPattern) {
+                    tiliqua_lib::draw::draw_test_pattern(self, pattern).ok();
+                }
+
+                /// Start drawing a frame at the start of vertical blanking, see
+                /// `DMAFramebuffer::wait_for_vblank` for the tradeoffs.
+                pub fn begin_frame(&mut self) {
+                    use hal::dma_framebuffer::DMAFramebuffer;
+                    self.wait_for_vblank();
+                }
+
+                /// Wait for queued accelerated (line, blit, pixel plot) draws to be
+                /// accepted by the plotter, so a frame bracketed by `begin_frame`
+                /// doesn't spill into the active area from the queues alone.
+                ///
+                /// With `with_auto_flush`, this also flushes the data cache.
+                pub fn end_frame(&mut self) {
+                    self.end_batch();
+                    while !self.registers_line.status().read().empty().bit() { }
+                    while !self.registers_blitter.status().read().empty().bit() { }
+                    while self.registers_pixel_plot.status().read().busy().bit() { }
+                    if self.auto_flush {
+                        self.flush();
+                    }
+                }
             }
 
 
@@ -194,20 +742,26 @@ macro_rules! impl_dma_framebuffer {
                 fn get_hpd(&mut self) -> bool  {
                     self.registers_fb.hpd().read().hpd().bit()
                 }
+
+                fn in_vblank(&self) -> bool {
+                    self.registers_fb.hpd().read().vblank().bit()
+                }
+
+                fn scanout_enabled(&self) -> bool {
+                    self.registers_fb.hpd().read().enabled().bit()
+                }
+
+                fn scanout_stats(&self) -> hal::dma_framebuffer::ScanoutStats {
+                    hal::dma_framebuffer::ScanoutStats {
+                        frames: self.registers_fb.frames().read().frames().bits(),
+                    }
+                }
             }
 
             impl OriginDimensions for $DMA_FRAMEBUFFERX {
                 fn size(&self) -> Size {
-                    match self.mode.rotate {
-                        Rotate::Normal | Rotate::Inverted => {
-                            Size::new(self.mode.h_active as u32,
-                                      self.mode.v_active as u32)
-                        }
-                        Rotate::Left | Rotate::Right => {
-                            Size::new(self.mode.v_active as u32,
-                                      self.mode.h_active as u32)
-                        }
-                    }
+                    let (width, height) = self.viewport.size();
+                    Size::new(width, height)
                 }
             }
 
@@ -228,20 +782,32 @@ macro_rules! impl_dma_framebuffer {
                 where
                     I: IntoIterator<Item = Pixel<Self::Color>>,
                 {
+                    self.flush_line_strip();
+                    let (width, height) = self.viewport.size();
                     for Pixel(coord, color) in pixels.into_iter() {
-                        while self.registers_pixel_plot.status().read().busy().bit() {
-                            // Plotting FIFO is full. Spin.
-                            riscv::asm::nop();
+                        if coord.x < 0 || coord.y < 0 ||
+                           coord.x as u32 >= width || coord.y as u32 >= height {
+                            continue;
                         }
+                        let x = coord.x + self.viewport.x as i32;
+                        let y = coord.y + self.viewport.y as i32;
+                        self.wait_plot_fifo();
                         self.registers_pixel_plot.plot().write(|w| unsafe {
-                            w.x().bits(coord.x as u16);
-                            w.y().bits(coord.y as u16);
+                            w.x().bits(x as u16);
+                            w.y().bits(y as u16);
                             w.pixel().bits(color.to_raw())
                         });
                     }
                     Ok(())
                 }
 
+                /// Solid fills (including `clear`) are accelerated by `fill_rect_solid`.
+                fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+                    self.fill_rect_solid(area.top_left.x, area.top_left.y,
+                                         area.size.width, area.size.height, color);
+                    Ok(())
+                }
+
                 // *** ACCELERATED DRAWING EXTENSIONS ***
                 //
                 // `draw_iter` above is a normal `embedded-graphics` DrawTarget method.
@@ -320,6 +886,15 @@ macro_rules! impl_dma_framebuffer {
                         return false;
                     }
 
+                    // Like `ClippedDrawTarget`, blits that aren't entirely inside the
+                    // viewport are left to the (clipped) pixel plot fallback.
+                    let dst = Rectangle::new(Point::new(dst_x, dst_y), Size::new(width, height));
+                    if self.bounding_box().intersection(&dst) != dst {
+                        return false;
+                    }
+
+                    self.flush_line_strip();
+
                     // Spin if command FIFO is full (too many blits already enqueued)
                     while self.registers_blitter.status().read().full().bit() {
                         riscv::asm::nop();
@@ -339,8 +914,8 @@ macro_rules! impl_dma_framebuffer {
 
                     // Enqueue new blit operation from the last source sub-rectangle.
                     self.registers_blitter.blit().write(|w| unsafe {
-                        w.dst_x().bits(dst_x as u16);
-                        w.dst_y().bits(dst_y as u16);
+                        w.dst_x().bits((dst_x + self.viewport.x as i32) as u16);
+                        w.dst_y().bits((dst_y + self.viewport.y as i32) as u16);
                         w.pixel().bits(color.to_raw())
                     });
 
@@ -362,40 +937,44 @@ macro_rules! impl_dma_framebuffer {
                 fn draw_line_solid(&mut self, start_x: i32, start_y: i32, end_x: i32, end_y: i32,
                                    stroke_width: u32, color: Self::Color) -> bool {
 
-                    // TODO: Check bounds? Bresenham hardware might do wierd stuff
-                    // or stall forever if the line endpoints are off the screen...
-
                     if stroke_width != 1 {
                         // Only support 1-pixel wide solid lines for now.
                         // Fall back to `embedded-graphics` software implementation.
                         return false;
                     }
 
-                    // No space for new line commands?
-                    while self.registers_line.status().read().full().bit() {
-                        riscv::asm::nop();
-                    }
+                    // The Bresenham hardware might do weird stuff or stall forever if
+                    // the line endpoints are off the screen, so clip to the viewport.
+                    let (p0, p1) = match tiliqua_lib::clip::clip_line(&self.bounding_box(),
+                            Point::new(start_x, start_y), Point::new(end_x, end_y)) {
+                        Some(clipped) => clipped,
+                        // Entirely outside, nothing to draw.
+                        None => return true,
+                    };
 
                     let pixel_data = color.to_raw();
+                    let start = ((p0.x + self.viewport.x as i32) as u16,
+                                 (p0.y + self.viewport.y as i32) as u16, pixel_data);
+                    let end = ((p1.x + self.viewport.x as i32) as u16,
+                               (p1.y + self.viewport.y as i32) as u16, pixel_data);
 
-                    self.registers_line.point().write(|w| unsafe {
-                        w.x().bits(start_x as u16);
-                        w.y().bits(start_y as u16);
-                        w.pixel().bits(pixel_data);
-                        w.cmd().bit(false) // CONTINUE line strip (0)
-                    });
-
-                    while self.registers_line.status().read().full().bit() {
-                        riscv::asm::nop();
+                    if self.batch {
+                        // Continue the open strip if this line starts where it left
+                        // off, otherwise terminate it and start a new one. Either way
+                        // the end of this line is held back, in case the next one
+                        // continues from it.
+                        match self.line_pending.take() {
+                            Some(pending) if pending == start => {},
+                            Some((x, y, pixel)) => self.write_line_point(x, y, pixel, true),
+                            None => {},
+                        }
+                        self.write_line_point(start.0, start.1, pixel_data, false);
+                        self.line_pending = Some(end);
+                    } else {
+                        self.write_line_point(start.0, start.1, pixel_data, false);
+                        self.write_line_point(end.0, end.1, pixel_data, true);
                     }
 
-                    self.registers_line.point().write(|w| unsafe {
-                        w.x().bits(end_x as u16);
-                        w.y().bits(end_y as u16);
-                        w.pixel().bits(pixel_data);
-                        w.cmd().bit(true) // END line strip (1)
-                    });
-
                     // Line was enqueued and will be drawn asynchronously.
                     // `Some(Ok())` indicates the software line drawing fallback is not needed.
                     true
@@ -404,3 +983,181 @@ macro_rules! impl_dma_framebuffer {
         )+
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn test_modeline_adjust() {
+        let mut m = DVIModeline::default();
+        assert!(m.is_consistent());
+        assert!(approx(m.refresh_rate(), 60.0));
+        assert_eq!(m.pixel_clk_hz(), 74_250_000);
+
+        // Totals and pixel clock feed straight into the refresh rate.
+        m.adjust(ModelineField::HTotal, 1650);
+        assert_eq!(m.h_total, 3300);
+        assert!(approx(m.refresh_rate(), 30.0));
+        m.adjust(ModelineField::PixelClk, 74250);
+        assert_eq!(m.pixel_clk_hz(), 148_500_000);
+        assert!(approx(m.refresh_rate(), 60.0));
+        m.adjust(ModelineField::VTotal, -50);
+        assert_eq!(m.v_total, 700);
+        assert!(approx(m.refresh_rate(), 148.5e6 / (3300.0 * 700.0)));
+
+        // Growing the active area pushes later timings along with it...
+        let mut m = DVIModeline::default();
+        m.adjust(ModelineField::HActive, 500);
+        assert_eq!((m.h_active, m.h_sync_start, m.h_sync_end, m.h_total),
+                   (1780, 1781, 1782, 1783));
+        // ...and shrinking the total pulls earlier timings back.
+        m.adjust(ModelineField::VTotal, -740);
+        assert_eq!((m.v_active, m.v_sync_start, m.v_sync_end, m.v_total),
+                   (7, 8, 9, 10));
+        assert!(m.is_consistent());
+
+        // Nothing can be pushed out of range.
+        m.adjust(ModelineField::VActive, -10000);
+        assert_eq!(m.v_active, 1);
+        m.adjust(ModelineField::HTotal, 100000);
+        assert_eq!(m.h_total, u16::MAX);
+        m.adjust(ModelineField::PixelClk, -1_000_000);
+        assert_eq!(m.pixel_clk_hz(), 1_000_000);
+        assert!(m.is_consistent());
+
+        // Polarity fields toggle.
+        let mut m = DVIModeline::default();
+        m.adjust(ModelineField::HSyncInvert, 1);
+        m.adjust(ModelineField::VSyncInvert, -1);
+        assert!(m.h_sync_invert && m.v_sync_invert);
+        m.adjust(ModelineField::HSyncInvert, 0);
+        assert!(m.h_sync_invert);
+        m.adjust(ModelineField::HSyncInvert, 1);
+        assert!(!m.h_sync_invert);
+
+        // Fixed modelines (no timings) are not consistent.
+        let m = DVIModeline::default().maybe_override_fixed(Some((720, 720)), 60_000_000);
+        assert!(!m.is_consistent());
+    }
+
+    #[test]
+    fn test_cvt_reduced_blanking() {
+        // Reference CVT-RB v2 timings (before pixel clock rounding).
+        let m = DVIModeline::cvt_reduced_blanking(1280, 720, 60.0).unwrap();
+        assert_eq!((m.h_active, m.h_sync_start, m.h_sync_end, m.h_total),
+                   (1280, 1288, 1320, 1360));
+        assert_eq!((m.v_active, m.v_sync_start, m.v_sync_end, m.v_total),
+                   (720, 727, 735, 741));
+        assert!(!m.h_sync_invert && m.v_sync_invert);
+        // 60.466MHz exactly, rounded to the nearest 250kHz.
+        assert_eq!(m.pixel_clk_khz(), 60_500);
+        assert!(approx(m.refresh_rate(), 60.03));
+        assert!(m.is_consistent() && !m.fixed());
+
+        // Vertical blanking never goes below the CVT minimum.
+        let m = DVIModeline::cvt_reduced_blanking(1024, 1024, 30.0).unwrap();
+        assert_eq!(m.v_total - m.v_active, 15);
+
+        // Outside the PLL range.
+        assert_eq!(DVIModeline::cvt_reduced_blanking(1920, 1080, 60.0), None);
+        assert_eq!(DVIModeline::cvt_reduced_blanking(640, 480, 60.0), None);
+        // Nonsense.
+        assert_eq!(DVIModeline::cvt_reduced_blanking(0, 480, 60.0), None);
+        assert_eq!(DVIModeline::cvt_reduced_blanking(640, 480, 0.0), None);
+        assert_eq!(DVIModeline::cvt_reduced_blanking(640, 480, 10000.0), None);
+    }
+
+    #[test]
+    fn test_test_pattern_next() {
+        use strum::IntoEnumIterator;
+        let mut p = TestPattern::default();
+        for expect in TestPattern::iter().skip(1) {
+            p = p.next();
+            assert_eq!(p, expect);
+        }
+        assert_eq!(p.next(), TestPattern::default());
+    }
+
+    #[test]
+    fn test_viewport() {
+        let mode = DVIModeline::default();
+        assert_eq!(Viewport::full(&mode).size(), (1280, 720));
+
+        // Inset by 5% on each side.
+        let v = Viewport::clamped(&mode, 64, 36, 1152, 648);
+        assert_eq!((v.x, v.y), (64, 36));
+        assert_eq!(v.size(), (1152, 648));
+
+        // Shifted past the edge: moved back inside, keeping its size.
+        let v = Viewport::clamped(&mode, 400, 100, 1000, 700);
+        assert_eq!((v.x, v.y, v.width, v.height), (280, 20, 1000, 700));
+        // Too large, or empty: clamped to the active area, and at least 1x1.
+        let v = Viewport::clamped(&mode, 10, 10, 2000, 0);
+        assert_eq!((v.x, v.y, v.width, v.height), (0, 10, 1280, 1));
+        assert_eq!(Viewport::clamped(&mode, u16::MAX, u16::MAX, u16::MAX, u16::MAX),
+                   Viewport::full(&mode));
+
+        // Sizes are in drawing coordinates, so follow the rotation.
+        let rotated = DVIModeline { rotate: Rotate::Left, ..DVIModeline::default() };
+        assert_eq!(Viewport::full(&rotated).size(), (720, 1280));
+        let v = Viewport::clamped(&rotated, 0, 0, 1152, 648);
+        assert_eq!(v.size(), (720, 648));
+    }
+
+    #[test]
+    fn test_scanout_meter() {
+        let modeline = DVIModeline::default();
+        let mut meter = ScanoutMeter::new();
+        assert_eq!(meter.poll(ScanoutStats { frames: 100 }, 0), None);
+        assert_eq!(meter.poll(ScanoutStats { frames: 130 }, 500), None);
+        assert_eq!(meter.refresh_hz(), None);
+        assert_eq!(meter.line_hz(&modeline), None);
+        // 60 frames in 1s
+        assert_eq!(meter.poll(ScanoutStats { frames: 160 }, 1000), Some(60.0));
+        assert_eq!(meter.line_hz(&modeline), Some(60.0 * modeline.v_total as f32));
+        // Polled late: measure over the actual window, across the counter wrapping.
+        meter.poll(ScanoutStats { frames: u32::MAX - 9 }, 2000);
+        assert_eq!(meter.poll(ScanoutStats { frames: 110 }, 4000), Some(60.0));
+        // Scanout stopped
+        assert_eq!(meter.poll(ScanoutStats { frames: 110 }, 5000), Some(0.0));
+        assert_eq!(meter.refresh_hz(), Some(0.0));
+    }
+
+    #[test]
+    fn test_hpd_debounce() {
+        let mut hpd = VideoHpd::new(false, 0);
+        let mut events = Vec::new();
+        // Connector wiggled in: bounces for a while, then settles high.
+        let trace = [
+            (10, true), (15, false), (40, true), (45, false), (90, true),
+            (100, true), (150, false), (160, true), (200, true), (300, true),
+            (359, true), (360, true), (361, true), (1000, true),
+        ];
+        for (now_ms, level) in trace {
+            if let Some(event) = hpd.poll(level, now_ms) {
+                events.push((now_ms, event));
+            }
+        }
+        // Reported once, 200ms after the last edge at 160ms.
+        assert_eq!(events, [(360, HpdEvent::Connected)]);
+        assert!(hpd.connected());
+
+        // Short dropouts are ignored.
+        assert_eq!(hpd.poll(false, 1100), None);
+        assert_eq!(hpd.poll(true, 1250), None);
+        assert_eq!(hpd.poll(true, 2000), None);
+
+        // A real disconnect with a custom debounce.
+        hpd.debounce_ms = 50;
+        assert_eq!(hpd.poll(false, 3000), None);
+        assert_eq!(hpd.poll(false, 3049), None);
+        assert_eq!(hpd.poll(false, 3050), Some(HpdEvent::Disconnected));
+        assert_eq!(hpd.poll(false, 4000), None);
+        assert!(!hpd.connected());
+    }
+}
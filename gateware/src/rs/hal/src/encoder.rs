@@ -1,7 +1,307 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PressKind {
+    Short,
+    Long,
+    Double,
+}
+
+/// Button timing thresholds, in calls to `update()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PressConfig {
+    /// Button must be stable for this many updates before a change is accepted.
+    pub debounce: u16,
+    /// Presses held at least this long are `Long`.
+    pub long_press: u16,
+    /// A second press starting within this many updates of the first
+    /// release is a `Double`. Zero disables double press detection, so
+    /// that `Short` presses are reported as soon as they are released.
+    pub double_gap: u16,
+}
+
+impl PressConfig {
+    pub fn from_ms(period_ms: u32, debounce_ms: u32, long_press_ms: u32,
+                   double_gap_ms: u32) -> Self {
+        let to_updates = |ms: u32| (ms / period_ms.max(1)).min(u16::MAX as u32) as u16;
+        Self {
+            debounce: to_updates(debounce_ms),
+            long_press: to_updates(long_press_ms),
+            double_gap: to_updates(double_gap_ms),
+        }
+    }
+}
+
+impl Default for PressConfig {
+    /// Assumes a 10ms update period.
+    fn default() -> Self {
+        Self::from_ms(10, 20, 500, 0)
+    }
+}
+
+/// A classified button press, see [`GestureDetector`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    Click,
+    DoubleClick,
+    /// Reported on release, with how long the button was held (ms).
+    LongPress(u32),
+}
+
+impl From<Gesture> for PressKind {
+    fn from(gesture: Gesture) -> Self {
+        match gesture {
+            Gesture::Click => PressKind::Short,
+            Gesture::DoubleClick => PressKind::Double,
+            Gesture::LongPress(_) => PressKind::Long,
+        }
+    }
+}
+
+/// Gesture timing thresholds, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GestureConfig {
+    /// Button must be stable this long before a change is accepted.
+    pub debounce_ms: u32,
+    /// Presses held at least this long are a `LongPress`.
+    pub long_press_ms: u32,
+    /// A second press starting within this long of the first release
+    /// is a `DoubleClick`. Zero disables double-click detection, so
+    /// that a `Click` is reported as soon as the button is released.
+    pub double_click_ms: u32,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            debounce_ms: 20,
+            long_press_ms: 500,
+            double_click_ms: 250,
+        }
+    }
+}
+
+/// Debounces a raw button signal and classifies each press, from
+/// timestamps rather than a fixed polling period.
+///
+/// A press is classified once, so a `LongPress` never also fires a
+/// `Click` on release, and the two presses of a `DoubleClick` are not
+/// reported separately.
+#[derive(Debug)]
+pub struct GestureDetector {
+    config: GestureConfig,
+    raw: bool,
+    raw_since: u32,
+    pressed: bool,
+    pressed_at: u32,
+    released_at: u32,
+    pending_click: bool,
+    second_press: bool,
+}
+
+impl GestureDetector {
+    pub fn new(config: GestureConfig, pressed: bool, now_ms: u32) -> Self {
+        Self {
+            config,
+            raw: pressed,
+            raw_since: now_ms,
+            pressed,
+            pressed_at: now_ms,
+            released_at: now_ms,
+            pending_click: false,
+            second_press: false,
+        }
+    }
+
+    pub fn set_config(&mut self, config: GestureConfig) {
+        self.config = config;
+    }
+
+    /// Debounced button state.
+    pub fn pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Time the (debounced) button has been held down for, in ms.
+    pub fn held(&self, now_ms: u32) -> u32 {
+        if self.pressed { now_ms.wrapping_sub(self.pressed_at) } else { 0 }
+    }
+
+    /// Feed one raw button sample, returning a gesture once it is classified.
+    pub fn update(&mut self, raw: bool, now_ms: u32) -> Option<Gesture> {
+        if raw != self.raw {
+            self.raw = raw;
+            self.raw_since = now_ms;
+        }
+
+        let mut gesture = None;
+        if self.raw != self.pressed &&
+           now_ms.wrapping_sub(self.raw_since) >= self.config.debounce_ms {
+            self.pressed = self.raw;
+            if self.pressed {
+                self.pressed_at = now_ms;
+                if self.pending_click {
+                    self.pending_click = false;
+                    self.second_press = true;
+                }
+            } else {
+                let held = now_ms.wrapping_sub(self.pressed_at);
+                if self.second_press {
+                    self.second_press = false;
+                    gesture = Some(Gesture::DoubleClick);
+                } else if held >= self.config.long_press_ms {
+                    gesture = Some(Gesture::LongPress(held));
+                } else if self.config.double_click_ms == 0 {
+                    gesture = Some(Gesture::Click);
+                } else {
+                    self.pending_click = true;
+                    self.released_at = now_ms;
+                }
+            }
+        }
+
+        if self.pending_click && !self.pressed &&
+           now_ms.wrapping_sub(self.released_at) >= self.config.double_click_ms {
+            self.pending_click = false;
+            gesture = Some(Gesture::Click);
+        }
+
+        gesture
+    }
+}
+
+/// [`GestureDetector`] driven at a fixed rate, with thresholds counted
+/// in calls to `update()`.
+#[derive(Debug)]
+pub struct PressDetector {
+    gestures: GestureDetector,
+    updates: u32,
+}
+
+impl PressDetector {
+    pub fn new(config: PressConfig, pressed: bool) -> Self {
+        Self {
+            gestures: GestureDetector::new(config.into(), pressed, 0),
+            updates: 0,
+        }
+    }
+
+    pub fn set_config(&mut self, config: PressConfig) {
+        self.gestures.set_config(config.into());
+    }
+
+    /// Debounced button state.
+    pub fn pressed(&self) -> bool {
+        self.gestures.pressed()
+    }
+
+    /// Updates the (debounced) button has been held down for.
+    pub fn held(&self) -> u16 {
+        self.gestures.held(self.updates).min(u16::MAX as u32) as u16
+    }
+
+    /// Feed one raw button sample, returning a press once it is classified.
+    pub fn update(&mut self, raw: bool) -> Option<PressKind> {
+        self.updates = self.updates.wrapping_add(1);
+        self.gestures.update(raw, self.updates).map(PressKind::from)
+    }
+}
+
+impl From<PressConfig> for GestureConfig {
+    // Timestamps are in updates, so thresholds carry over as-is.
+    fn from(config: PressConfig) -> Self {
+        Self {
+            debounce_ms: config.debounce as u32,
+            long_press_ms: config.long_press as u32,
+            double_click_ms: config.double_gap as u32,
+        }
+    }
+}
+
+/// Without a tick for this long (ms), the encoder is at rest.
+pub const VELOCITY_REST_MS: u32 = 250;
+
+/// Accumulates detent ticks into a position, and estimates velocity
+/// from the time between them.
+///
+/// The velocity is averaged over successive ticks, but restarts when the
+/// direction reverses. Once ticks stop, it decays as it can be no faster
+/// than one tick in the time since the last, reaching zero after
+/// `VELOCITY_REST_MS`.
+#[derive(Debug)]
+pub struct MotionTracker {
+    position: i32,
+    // Ticks per second, with 8 fractional bits.
+    velocity: i32,
+    last_tick_ms: u32,
+}
+
+impl MotionTracker {
+    pub fn new(now_ms: u32) -> Self {
+        Self {
+            position: 0,
+            velocity: 0,
+            // First tick is from rest.
+            last_tick_ms: now_ms.wrapping_sub(VELOCITY_REST_MS),
+        }
+    }
+
+    /// Accumulated ticks (wrapping).
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Smoothed velocity in ticks per second, positive for increasing position.
+    pub fn velocity(&self) -> i32 {
+        self.velocity / 256
+    }
+
+    /// Feed the ticks since the last update.
+    pub fn update(&mut self, ticks: i32, now_ms: u32) {
+        let since = now_ms.wrapping_sub(self.last_tick_ms).clamp(1, VELOCITY_REST_MS);
+        if ticks != 0 {
+            self.position = self.position.wrapping_add(ticks);
+            self.last_tick_ms = now_ms;
+            let rate = (ticks as i64 * (1000 << 8) / since as i64)
+                .clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+            self.velocity = if self.velocity != 0 && (rate > 0) == (self.velocity > 0) {
+                // Average, rounding away from zero so steady rates are reached exactly.
+                let sum = self.velocity as i64 + rate as i64;
+                ((sum + sum.signum()) / 2) as i32
+            } else {
+                rate
+            };
+        } else if since >= VELOCITY_REST_MS {
+            self.velocity = 0;
+        } else {
+            let bound = (1000 << 8) / since as i32;
+            self.velocity = self.velocity.clamp(-bound, bound);
+        }
+    }
+}
+
 pub trait Encoder {
     fn poke_ticks(&mut self) -> i8;
-    fn poke_btn(&mut self) -> bool;
+    fn poke_press(&mut self) -> Option<PressKind>;
+    fn set_press_config(&mut self, config: PressConfig);
     fn update(&mut self);
+
+    /// Sample the button and return a gesture once one is classified.
+    /// Independent of `update()` and `poke_press()`, which classify
+    /// presses separately at a fixed rate.
+    fn poll_gesture(&mut self, now_ms: u32) -> Option<Gesture>;
+    fn set_gesture_config(&mut self, config: GestureConfig);
+
+    /// Ticks accumulated by `update()` since startup. Unlike `poke_ticks()`,
+    /// reading this does not consume anything.
+    fn position(&self) -> i32;
+    /// Smoothed ticks per second, see [`MotionTracker`].
+    fn velocity(&self) -> i32;
+    /// Time between calls to `update()`, used for `velocity()`. Defaults to 10ms.
+    fn set_update_period_ms(&mut self, period_ms: u32);
+
+    /// Check for any kind of pending press and clear it.
+    fn poke_btn(&mut self) -> bool {
+        self.poke_press().is_some()
+    }
 }
 
 #[macro_export]
@@ -16,13 +316,14 @@ macro_rules! impl_encoder {
 
                 rot: i16,
                 lrot: i16,
-                lbtn: bool,
-                btn_held: u16,
+                press: hal::encoder::PressDetector,
+                gestures: hal::encoder::GestureDetector,
+                motion: hal::encoder::MotionTracker,
+                period_ms: u32,
+                now_ms: u32,
 
                 pending_ticks: i8,
-                pending_release: bool,
-                pending_press:   bool,
-
+                pending_press: Option<hal::encoder::PressKind>,
             }
 
             impl $ENCODERX {
@@ -31,11 +332,15 @@ macro_rules! impl_encoder {
                     Self { registers,
                            rot: 0,
                            lrot: 0,
-                           lbtn: btn,
-                           btn_held: 0,
+                           press: hal::encoder::PressDetector::new(
+                               hal::encoder::PressConfig::default(), btn),
+                           gestures: hal::encoder::GestureDetector::new(
+                               hal::encoder::GestureConfig::default(), btn, 0),
+                           motion: hal::encoder::MotionTracker::new(0),
+                           period_ms: 10,
+                           now_ms: 0,
                            pending_ticks: 0,
-                           pending_release: false,
-                           pending_press: false,
+                           pending_press: None,
                     }
                 }
 
@@ -52,14 +357,34 @@ macro_rules! impl_encoder {
                     ticks
                 }
 
-                /// Check for pending clicks and erase it.
-                fn poke_btn(&mut self) -> bool {
-                    let btn = self.pending_press && self.pending_release;
-                    if btn {
-                        self.pending_press = false;
-                        self.pending_release = false;
-                    }
-                    btn
+                /// Check for a pending (classified) press and clear it.
+                fn poke_press(&mut self) -> Option<hal::encoder::PressKind> {
+                    self.pending_press.take()
+                }
+
+                fn set_press_config(&mut self, config: hal::encoder::PressConfig) {
+                    self.press.set_config(config);
+                }
+
+                fn poll_gesture(&mut self, now_ms: u32) -> Option<hal::encoder::Gesture> {
+                    let btn = self.registers.button().read().bits() != 0;
+                    self.gestures.update(btn, now_ms)
+                }
+
+                fn set_gesture_config(&mut self, config: hal::encoder::GestureConfig) {
+                    self.gestures.set_config(config);
+                }
+
+                fn position(&self) -> i32 {
+                    self.motion.position()
+                }
+
+                fn velocity(&self) -> i32 {
+                    self.motion.velocity()
+                }
+
+                fn set_update_period_ms(&mut self, period_ms: u32) {
+                    self.period_ms = period_ms;
                 }
 
                 fn update(&mut self) {
@@ -68,35 +393,29 @@ macro_rules! impl_encoder {
                     let btn = self.registers.button().read().bits() != 0;
                     let mut delta_ticks = self.rot - self.lrot;
 
-                    if btn {
-                        self.btn_held += 1;
-                    } else {
-                        self.btn_held = 0;
-                    }
-
                     // This logic is dumb. Move it into RTL.
 
+                    let mut ticks: i8 = 0;
+
                     while delta_ticks > 1 {
-                        self.pending_ticks += 1;
+                        ticks += 1;
                         delta_ticks -= 2;
                     }
 
                     while delta_ticks < -1 {
-                        self.pending_ticks -= 1;
+                        ticks -= 1;
                         delta_ticks += 2;
                     }
 
-                    // button just released
-                    if self.lbtn != btn {
-                        if btn {
-                            self.pending_press = true;
-                        } else {
-                            self.pending_release = true;
-                        }
+                    self.pending_ticks += ticks;
+                    self.now_ms = self.now_ms.wrapping_add(self.period_ms);
+                    self.motion.update(ticks as i32, self.now_ms);
+
+                    if let Some(press) = self.press.update(btn) {
+                        self.pending_press = Some(press);
                     }
 
                     self.lrot = self.rot - delta_ticks;
-                    self.lbtn = btn;
                 }
             }
 
@@ -108,3 +427,155 @@ macro_rules! impl_encoder {
         )+
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed (button level, number of updates) pairs, collecting presses.
+    fn classify(detector: &mut PressDetector, levels: &[(bool, u16)]) -> Vec<PressKind> {
+        let mut presses = Vec::new();
+        for &(level, n) in levels {
+            for _ in 0..n {
+                presses.extend(detector.update(level));
+            }
+        }
+        presses
+    }
+
+    #[test]
+    fn test_press_classification() {
+        let config = PressConfig { debounce: 2, long_press: 50, double_gap: 20 };
+
+        let mut d = PressDetector::new(config, false);
+        assert_eq!(classify(&mut d, &[(true, 10), (false, 40)]), [PressKind::Short]);
+        assert_eq!(classify(&mut d, &[(true, 80), (false, 40)]), [PressKind::Long]);
+        assert_eq!(classify(&mut d, &[(true, 10), (false, 10), (true, 10), (false, 40)]),
+                   [PressKind::Double]);
+        // Second press after the gap is two separate short presses.
+        assert_eq!(classify(&mut d, &[(true, 10), (false, 30), (true, 10), (false, 40)]),
+                   [PressKind::Short, PressKind::Short]);
+
+        // Contact bounce shorter than the debounce time is ignored.
+        let bouncy = [(true, 1), (false, 1), (true, 1), (false, 1), (true, 10),
+                      (false, 1), (true, 1), (false, 40)];
+        assert_eq!(classify(&mut d, &bouncy), [PressKind::Short]);
+        assert_eq!(classify(&mut d, &[(true, 1), (false, 40)]), []);
+
+        // Without double press detection, short presses are reported on release.
+        let mut d = PressDetector::new(PressConfig { double_gap: 0, ..config }, false);
+        let mut presses = Vec::new();
+        for level in [true; 10].into_iter().chain([false; 3]) {
+            presses.extend(d.update(level));
+        }
+        assert_eq!(presses, [PressKind::Short]);
+        assert!(!d.pressed());
+    }
+
+    /// Feed `ticks` per update, every `period_ms`, for `n` updates.
+    fn run(m: &mut MotionTracker, now: &mut u32, ticks: &[i32], period_ms: u32, n: usize) {
+        for t in ticks.iter().cycle().take(n) {
+            *now += period_ms;
+            m.update(*t, *now);
+        }
+    }
+
+    #[test]
+    fn test_motion() {
+        let mut now = 0;
+        let mut m = MotionTracker::new(now);
+        assert_eq!((m.position(), m.velocity()), (0, 0));
+
+        // One tick every 10ms settles on 100 ticks/sec.
+        run(&mut m, &mut now, &[1], 10, 50);
+        assert_eq!(m.position(), 50);
+        assert_eq!(m.velocity(), 100);
+
+        // Reversing flips the sign straight away.
+        run(&mut m, &mut now, &[-1], 10, 1);
+        assert_eq!(m.position(), 49);
+        assert!(m.velocity() < 0);
+        run(&mut m, &mut now, &[-2], 10, 20);
+        assert_eq!(m.position(), 9);
+        assert_eq!(m.velocity(), -200);
+
+        // Slow steady turning holds its rate between ticks.
+        run(&mut m, &mut now, &[1, 0, 0, 0, 0], 10, 100);
+        assert_eq!(m.position(), 29);
+        assert_eq!(m.velocity(), 20);
+        run(&mut m, &mut now, &[1, 0, 0, 0], 10, 4);
+        assert_eq!(m.velocity(), 20);
+
+        // Once ticks stop, the velocity decays to rest.
+        run(&mut m, &mut now, &[0], 10, 7);
+        assert!(m.velocity() > 0 && m.velocity() < 20);
+        run(&mut m, &mut now, &[0], 10, 20);
+        assert_eq!(m.velocity(), 0);
+        assert_eq!(m.position(), 30);
+
+        // The first tick from rest is slow, whatever the update rate.
+        run(&mut m, &mut now, &[1], 1, 1);
+        assert_eq!(m.velocity(), 1000 / VELOCITY_REST_MS as i32);
+    }
+
+    /// Feed (button level, ms) edges, sampling every 5ms until `end_ms`.
+    fn gestures(detector: &mut GestureDetector, edges: &[(bool, u32)], end_ms: u32)
+        -> Vec<(u32, Gesture)> {
+        let mut out = Vec::new();
+        let mut level = false;
+        for now in (0..end_ms).step_by(5) {
+            for &(l, at) in edges {
+                if at <= now {
+                    level = l;
+                }
+            }
+            if let Some(g) = detector.update(level, now) {
+                out.push((now, g));
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_gestures() {
+        let config = GestureConfig { debounce_ms: 20, long_press_ms: 500, double_click_ms: 250 };
+        let d = || GestureDetector::new(config, false, 0);
+
+        // Click is only reported once the double-click window has passed.
+        assert_eq!(gestures(&mut d(), &[(true, 100), (false, 200)], 1000),
+                   [(470, Gesture::Click)]);
+
+        // Long press fires once on release with the held time, never a click.
+        assert_eq!(gestures(&mut d(), &[(true, 100), (false, 3120)], 5000),
+                   [(3140, Gesture::LongPress(3020))]);
+        let mut long = d();
+        assert_eq!(gestures(&mut long, &[(true, 100)], 1000), []);
+        assert!(long.pressed());
+        assert_eq!(long.held(1000), 880);
+
+        // Second press inside the window is a double click, not two clicks.
+        assert_eq!(gestures(&mut d(), &[(true, 100), (false, 200), (true, 300), (false, 400)], 1000),
+                   [(420, Gesture::DoubleClick)]);
+        assert_eq!(gestures(&mut d(), &[(true, 100), (false, 200), (true, 500), (false, 600)], 1500),
+                   [(470, Gesture::Click), (870, Gesture::Click)]);
+
+        // Bounces shorter than the debounce time are ignored.
+        assert_eq!(gestures(&mut d(), &[(true, 100), (false, 105), (true, 110), (false, 115)], 1000),
+                   []);
+        assert_eq!(gestures(&mut d(), &[(true, 100), (false, 105), (true, 110), (false, 300)], 1000),
+                   [(570, Gesture::Click)]);
+
+        // No double-click window: clicks are reported on release.
+        let mut d = GestureDetector::new(GestureConfig { double_click_ms: 0, ..config }, false, 0);
+        assert_eq!(gestures(&mut d, &[(true, 100), (false, 200)], 1000),
+                   [(220, Gesture::Click)]);
+
+        // Timestamps may wrap around.
+        let mut d = GestureDetector::new(config, false, u32::MAX - 50);
+        assert_eq!(d.update(true, u32::MAX - 10), None);
+        assert_eq!(d.update(true, 10), None);
+        assert_eq!(d.update(false, 100), None);
+        assert_eq!(d.update(false, 120), None);
+        assert_eq!(d.update(false, 400), Some(Gesture::Click));
+    }
+}
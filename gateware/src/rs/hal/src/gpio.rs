@@ -0,0 +1,126 @@
+/// 8-pin GPIO bank, e.g. one of the EX0/EX1 expansion headers. Bit N of
+/// every mask is pin N. All pins are inputs after reset.
+pub trait Gpio {
+    /// Current level of every pin, including those driven as outputs.
+    fn read_inputs(&self) -> u8;
+    /// Levels driven on pins configured as outputs. Bits of input pins
+    /// are latched, and take effect if the pin later becomes an output.
+    fn set_outputs(&mut self, mask: u8);
+    /// Set bits make push-pull outputs, clear bits make inputs.
+    fn set_direction(&mut self, mask: u8);
+}
+
+/// Pin modes of the amaranth_soc `gpio.Peripheral`, 2 bits per pin in
+/// its `mode` register.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u16)]
+pub enum PinMode {
+    InputOnly = 0b00,
+    PushPull  = 0b01,
+    OpenDrain = 0b10,
+    Alternate = 0b11,
+}
+
+/// `mode` register value for a `Gpio::set_direction` mask.
+pub fn mode_bits(direction: u8) -> u16 {
+    (0..8).fold(0u16, |mode, pin| {
+        let m = if direction & (1 << pin) != 0 {
+            PinMode::PushPull
+        } else {
+            PinMode::InputOnly
+        };
+        mode | ((m as u16) << (2 * pin))
+    })
+}
+
+#[macro_export]
+macro_rules! impl_gpio {
+    ($(
+        $GPIOX:ident: $PACGPIOX:ty,
+    )+) => {
+        $(
+            #[derive(Debug)]
+            pub struct $GPIOX {
+                registers: $PACGPIOX,
+            }
+
+            impl $GPIOX {
+                pub fn new(registers: $PACGPIOX) -> Self {
+                    Self { registers }
+                }
+            }
+
+            impl hal::gpio::Gpio for $GPIOX {
+                fn read_inputs(&self) -> u8 {
+                    self.registers.input().read().bits()
+                }
+
+                fn set_outputs(&mut self, mask: u8) {
+                    self.registers.output().write(|w| unsafe { w.bits(mask) });
+                }
+
+                fn set_direction(&mut self, mask: u8) {
+                    self.registers.mode().write(|w| unsafe {
+                        w.bits(hal::gpio::mode_bits(mask))
+                    });
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Registers of a `gpio.Peripheral`, with pins looped back so that
+    /// outputs are read back on the input register.
+    struct MockGpio {
+        mode: u16,
+        output: u8,
+        external: u8,
+    }
+
+    impl Gpio for MockGpio {
+        fn read_inputs(&self) -> u8 {
+            let mut inputs = self.external;
+            for pin in 0..8 {
+                if (self.mode >> (2 * pin)) & 0b11 == PinMode::PushPull as u16 {
+                    inputs = (inputs & !(1 << pin)) | (self.output & (1 << pin));
+                }
+            }
+            inputs
+        }
+
+        fn set_outputs(&mut self, mask: u8) {
+            self.output = mask;
+        }
+
+        fn set_direction(&mut self, mask: u8) {
+            self.mode = mode_bits(mask);
+        }
+    }
+
+    #[test]
+    fn test_mode_bits() {
+        assert_eq!(mode_bits(0x00), 0x0000);
+        assert_eq!(mode_bits(0xFF), 0x5555);
+        assert_eq!(mode_bits(0b1000_0001), 0x4001);
+        assert_eq!(mode_bits(0b0000_0110), 0x0014);
+    }
+
+    #[test]
+    fn test_gpio_directions() {
+        let mut gpio = MockGpio { mode: 0, output: 0, external: 0b1010_1010 };
+        assert_eq!(gpio.read_inputs(), 0b1010_1010);
+
+        // Outputs latched while the pins are still inputs.
+        gpio.set_outputs(0b0000_1111);
+        assert_eq!(gpio.read_inputs(), 0b1010_1010);
+
+        // Low nibble driven, high nibble still follows the header.
+        gpio.set_direction(0x0F);
+        assert_eq!(gpio.mode, 0x0055);
+        assert_eq!(gpio.read_inputs(), 0b1010_1111);
+    }
+}
@@ -7,9 +7,24 @@ pub enum PlaybackMode {
     Scrub = 4,
 }
 
+/// Split a signed 8.8 fixed-point playback rate into the `speed` register
+/// value and the `reverse` flag. Saturates at the fastest rate the
+/// register can hold, and never stops playback entirely.
+pub fn rate_to_speed(ratio_fixed: i32) -> (u16, bool) {
+    let speed = ratio_fixed.unsigned_abs().clamp(1, u16::MAX as u32) as u16;
+    (speed, ratio_fixed < 0)
+}
+
 pub trait GrainPlayer {
-    fn set_params(&mut self, speed: u16, start: u32, length: u32);
-    fn set_control(&mut self, mode: PlaybackMode, gate: bool, hw_gate_enable: bool, reverse: bool);
+    fn set_params(&mut self, start: u32, length: u32);
+    /// Playback rate in 8.8 fixed point (0x100 plays at the recorded pitch).
+    /// Negative rates play the grain in reverse. The crossfade and grain
+    /// boundaries are the same in both directions.
+    fn set_rate(&mut self, ratio_fixed: i32);
+    fn set_control(&mut self, mode: PlaybackMode, gate: bool, hw_gate_enable: bool);
+    /// Crossfade the last `samples` of a looping grain into its start.
+    /// Must not exceed the grain length. 0 disables the crossfade.
+    fn set_crossfade(&mut self, samples: u32);
     fn position(&self) -> usize;
 }
 
@@ -30,18 +45,33 @@ macro_rules! impl_grain_player {
             }
 
             impl hal::grain_player::GrainPlayer for $GRAINX {
-                fn set_params(&mut self, speed: u16, start: u32, length: u32) {
-                    self.registers.speed().write(|w| unsafe { w.speed().bits(speed) });
+                fn set_params(&mut self, start: u32, length: u32) {
                     self.registers.start().write(|w| unsafe { w.start().bits(start) });
                     self.registers.length().write(|w| unsafe { w.length().bits(length) });
                 }
 
-                fn set_control(&mut self, mode: hal::grain_player::PlaybackMode, gate: bool, hw_gate_enable: bool, reverse: bool) {
-                    self.registers.control().write(|w| unsafe {
+                fn set_rate(&mut self, ratio_fixed: i32) {
+                    let (speed, reverse) = hal::grain_player::rate_to_speed(ratio_fixed);
+                    self.registers.speed().write(|w| unsafe { w.speed().bits(speed) });
+                    self.registers.control().modify(|_, w| w.reverse().bit(reverse));
+                }
+
+                fn set_control(&mut self, mode: hal::grain_player::PlaybackMode, gate: bool, hw_gate_enable: bool) {
+                    // `reverse` is left as set by `set_rate`.
+                    self.registers.control().modify(|_, w| unsafe {
                         w.mode().bits(mode as u8);
                         w.gate().bit(gate);
-                        w.hw_gate_enable().bit(hw_gate_enable);
-                        w.reverse().bit(reverse)
+                        w.hw_gate_enable().bit(hw_gate_enable)
+                    });
+                }
+
+                fn set_crossfade(&mut self, samples: u32) {
+                    let samples = samples.min(u16::MAX as u32);
+                    // The gateware doesn't divide, so it needs the per-sample gain step (UQ0.16).
+                    let gain_step = (1u32 << 16).checked_div(samples).unwrap_or(0).min(u16::MAX as u32);
+                    self.registers.crossfade().write(|w| unsafe {
+                        w.length().bits(samples as u16);
+                        w.gain_step().bits(gain_step as u16)
                     });
                 }
 
@@ -52,3 +82,18 @@ macro_rules! impl_grain_player {
         )+
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_to_speed() {
+        assert_eq!(rate_to_speed(0x100), (0x100, false));
+        assert_eq!(rate_to_speed(-0x100), (0x100, true));
+        assert_eq!(rate_to_speed(-0x80), (0x80, true));
+        assert_eq!(rate_to_speed(0), (1, false));
+        assert_eq!(rate_to_speed(i32::MIN), (u16::MAX, true));
+        assert_eq!(rate_to_speed(0x1_0000), (u16::MAX, false));
+    }
+}
@@ -1,3 +1,63 @@
+use core::ops::RangeInclusive;
+use embedded_hal::i2c::I2c;
+use heapless::Vec;
+
+/// Addresses outside this range are reserved by the I2C specification.
+pub const SCAN_ADDRESSES: RangeInclusive<u8> = 0x08..=0x77;
+
+/// Probe every 7-bit address in `range` with a zero-length write,
+/// returning those that ACK. Only the address is sent (see the
+/// `addr_only` transaction bit of the I2C core), so this is safe to run
+/// against any device. Only the first 16 responders are returned.
+pub fn scan<I2C: I2c>(dev: &mut I2C, range: RangeInclusive<u8>) -> Vec<u8, 16> {
+    let mut found = Vec::new();
+    for address in range {
+        if address > 0x7F {
+            break;
+        }
+        if dev.write(address, &[]).is_ok() && found.push(address).is_err() {
+            break;
+        }
+    }
+    found
+}
+
+/// Implements `recover_bus()` for an I2C peripheral. Expands to nothing
+/// unless the `i2c-recovery` feature is enabled on this crate.
+#[cfg(feature = "i2c-recovery")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! impl_i2c_recovery {
+    ($I2CX:ident) => {
+        impl $I2CX {
+            /// Clock a slave out of a partially completed transaction, in
+            /// case it is holding SDA low (e.g. after a reset or an interrupted
+            /// read). The core issues 9 SCL pulses with SDA released, followed
+            /// by a STOP. This is harmless on an idle bus, so it is worth
+            /// calling before critical transactions like EDID reads.
+            ///
+            /// Returns `ErrorKind::Bus` if SDA is still held low afterwards.
+            pub fn recover_bus(&mut self) -> Result<(), $crate::hal::i2c::ErrorKind> {
+                while self.registers.status().read().busy().bit() { }
+                self.registers.recover().write(|w| w.start().bit(true));
+                while self.registers.status().read().busy().bit() { }
+                if self.registers.status().read().sda().bit() {
+                    Ok(())
+                } else {
+                    Err($crate::hal::i2c::ErrorKind::Bus)
+                }
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "i2c-recovery"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! impl_i2c_recovery {
+    ($I2CX:ident) => {};
+}
+
 #[macro_export]
 macro_rules! impl_i2c {
     ($(
@@ -30,6 +90,8 @@ macro_rules! impl_i2c {
                 }
             }
 
+            $crate::impl_i2c_recovery!($I2CX);
+
             impl From<$PACI2CX> for $I2CX {
                 fn from(registers: $PACI2CX) -> $I2CX {
                     $I2CX::new(registers)
@@ -59,6 +121,22 @@ macro_rules! impl_i2c {
 
                     self.registers.address().write(|w| unsafe { w.address().bits(address) } );
 
+                    if total_bytes == 0 {
+                        // The core only starts once an entry is enqueued, so send
+                        // an address-only write. Its data byte is never sent.
+                        while self.registers.status().read().busy().bit() { }
+                        self.registers.transaction_reg().write( |w| {
+                            w.rw().bit(false);
+                            w.addr_only().bit(true);
+                            w.last().bit(true)
+                        } );
+                        while self.registers.status().read().busy().bit() { }
+                        if self.registers.status().read().error().bit() {
+                            return Err($crate::hal::i2c::ErrorKind::Other);
+                        }
+                        return Ok(());
+                    }
+
                     let mut sent_bytes = 0;
                     for op in operations.iter() {
                         match op {
@@ -112,3 +190,64 @@ macro_rules! impl_i2c {
         )+
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::i2c::{ErrorKind, ErrorType, Operation};
+
+    /// ACKs only the addresses in `present`, and fails if anything
+    /// other than an empty write (an address-only probe) is issued.
+    struct MockBus {
+        present: &'static [u8],
+        probed: std::vec::Vec<u8>,
+    }
+
+    impl MockBus {
+        fn new(present: &'static [u8]) -> Self {
+            Self { present, probed: std::vec::Vec::new() }
+        }
+    }
+
+    impl ErrorType for MockBus {
+        type Error = ErrorKind;
+    }
+
+    impl I2c for MockBus {
+        fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>])
+            -> Result<(), Self::Error> {
+            for op in operations.iter() {
+                match op {
+                    Operation::Write(bytes) => assert!(bytes.is_empty()),
+                    Operation::Read(_) => panic!("scan should not read"),
+                }
+            }
+            self.probed.push(address);
+            if self.present.contains(&address) {
+                Ok(())
+            } else {
+                Err(ErrorKind::Other)
+            }
+        }
+    }
+
+    #[test]
+    fn test_scan() {
+        let mut bus = MockBus::new(&[0x05, 0x08, 0x3D, 0x47, 0x52, 0x77, 0x78]);
+        assert_eq!(scan(&mut bus, SCAN_ADDRESSES), [0x08, 0x3D, 0x47, 0x52, 0x77]);
+        // Every address is probed once, and only 7-bit addresses.
+        assert!(bus.probed.iter().copied().eq(SCAN_ADDRESSES));
+        bus.probed.clear();
+        assert_eq!(scan(&mut bus, 0x00..=0xFF), [0x05, 0x08, 0x3D, 0x47, 0x52, 0x77, 0x78]);
+        assert!(bus.probed.iter().copied().eq(0x00..=0x7F));
+        assert_eq!(scan(&mut bus, 0x40..=0x50), [0x47]);
+        assert!(scan(&mut MockBus::new(&[]), SCAN_ADDRESSES).is_empty());
+
+        // Stops at 16 responders.
+        let mut full = MockBus::new(&[0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17,
+                                      0x18, 0x19, 0x1A, 0x1B, 0x1C, 0x1D, 0x1E, 0x1F, 0x20]);
+        let found = scan(&mut full, SCAN_ADDRESSES);
+        assert_eq!(found.len(), 16);
+        assert_eq!(found.last(), Some(&0x1F));
+    }
+}
@@ -25,6 +25,8 @@ pub mod spiflash;
 pub mod eeprom;
 pub mod scope;
 pub mod vector;
+pub mod gpio;
+pub mod watchdog;
 
 pub use embedded_hal as hal;
 pub use embedded_hal_nb as hal_nb;
@@ -3,6 +3,14 @@ use embedded_hal::i2c::Operation;
 
 const PCA9635_ADDR: u8 = 0x05;
 
+// MODE2: totem-pole outputs, plus DMBLNK to switch the group
+// control from dimming to blinking.
+const MODE2: u8 = 0x01;
+const MODE2_DMBLNK: u8 = 0x20;
+
+// Power-on group dimming used before group control was exposed.
+const GRPPWM_DEFAULT: u8 = 0x40;
+
 pub trait Pca9635<I2C: I2c> {
     fn push(&mut self) -> Result<(), I2C::Error>;
 }
@@ -10,11 +18,62 @@ pub trait Pca9635<I2C: I2c> {
 pub struct Pca9635Driver<I2C> {
     i2c: I2C,
     pub leds: [u8; 16],
+    group_pwm: u8,
+    group_freq: u8,
+    group_blink: bool,
+    // LEDs (bit per index) that follow the group dimming / blinking.
+    // The rest only follow their own `leds` PWM value.
+    group_mask: u16,
 }
 
 impl<I2C: I2c> Pca9635Driver<I2C> {
     pub fn new(i2c: I2C) -> Self {
-        Self { i2c, leds: [0u8; 16] }
+        Self {
+            i2c,
+            leds: [0u8; 16],
+            group_pwm: GRPPWM_DEFAULT,
+            group_freq: 0,
+            group_blink: false,
+            group_mask: 0xFFFF,
+        }
+    }
+
+    /// Dim all LEDs in the group by `duty`/256 on top of their own PWM value.
+    pub fn set_group_pwm(&mut self, duty: u8) {
+        self.group_pwm = duty;
+        self.group_blink = false;
+    }
+
+    /// Blink all LEDs in the group, on for `duty`/256 of each period. The
+    /// chip supports periods from ~42ms to ~10.7s, others are clamped.
+    pub fn set_group_blink(&mut self, period_ms: u32, duty: u8) {
+        // Period is (GRPFREQ + 1) / 24 seconds.
+        let steps = period_ms.saturating_mul(24).saturating_add(500) / 1000;
+        self.group_freq = (steps.clamp(1, 256) - 1) as u8;
+        self.group_pwm = duty;
+        self.group_blink = true;
+    }
+
+    /// Whether LED `index` follows the group dimming / blinking (the
+    /// default for all LEDs), or only its own PWM value.
+    pub fn set_blink_group(&mut self, index: usize, enable: bool) {
+        if index > 15 {
+            panic!("bad index");
+        }
+        if enable {
+            self.group_mask |= 1 << index;
+        } else {
+            self.group_mask &= !(1 << index);
+        }
+    }
+
+    // LEDOUTx packs 4 LEDs, 2 bits each: 0b10 for individual PWM
+    // only, 0b11 for individual and group PWM.
+    fn ledout(&self, reg: usize) -> u8 {
+        (0..4).fold(0u8, |acc, n| {
+            let in_group = (self.group_mask >> (reg * 4 + n)) & 1 != 0;
+            acc | (if in_group { 0b11 } else { 0b10 }) << (n * 2)
+        })
     }
 }
 
@@ -23,7 +82,7 @@ impl<I2C: I2c> Pca9635<I2C> for Pca9635Driver<I2C> {
         let pca9635_bytes = [
            0x80u8, // Auto-increment starting from MODE1
            0x81u8, // MODE1
-           0x01u8, // MODE2
+           if self.group_blink { MODE2 | MODE2_DMBLNK } else { MODE2 }, // MODE2
            self.leds[0x0], // PWM0
            self.leds[0x1], // PWM1
            self.leds[0x2], // PWM2
@@ -40,13 +99,87 @@ impl<I2C: I2c> Pca9635<I2C> for Pca9635Driver<I2C> {
            self.leds[0xD], // PWM13
            self.leds[0xE], // PWM14
            self.leds[0xF], // PWM15
-           0x40u8, // GRPPWM
-           0x00u8, // GRPFREQ
-           0xFFu8, // LEDOUT0
-           0xFFu8, // LEDOUT1
-           0xFFu8, // LEDOUT2
-           0xFFu8, // LEDOUT3
+           self.group_pwm, // GRPPWM
+           self.group_freq, // GRPFREQ
+           self.ledout(0), // LEDOUT0
+           self.ledout(1), // LEDOUT1
+           self.ledout(2), // LEDOUT2
+           self.ledout(3), // LEDOUT3
         ];
         self.i2c.transaction(PCA9635_ADDR, &mut [Operation::Write(&pca9635_bytes)])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::i2c::ErrorType;
+
+    /// Keeps the bytes of the last write, as `push()` does a single one.
+    struct RecordingI2c {
+        last_write: [u8; 25],
+    }
+
+    impl ErrorType for RecordingI2c {
+        type Error = core::convert::Infallible;
+    }
+
+    impl I2c for RecordingI2c {
+        fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>])
+            -> Result<(), Self::Error> {
+            assert_eq!(address, PCA9635_ADDR);
+            for op in operations {
+                if let Operation::Write(bytes) = op {
+                    self.last_write.copy_from_slice(bytes);
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn pushed(pca: &mut Pca9635Driver<RecordingI2c>) -> [u8; 25] {
+        pca.push().unwrap();
+        pca.i2c.last_write
+    }
+
+    #[test]
+    fn test_group_blink() {
+        let mut pca = Pca9635Driver::new(RecordingI2c { last_write: [0u8; 25] });
+        pca.leds[3] = 0x7F;
+
+        // Defaults match the original fixed register values.
+        let bytes = pushed(&mut pca);
+        assert_eq!(bytes[..3], [0x80, 0x81, 0x01]);
+        assert_eq!(bytes[3 + 3], 0x7F);
+        assert_eq!(bytes[19..], [0x40, 0x00, 0xFF, 0xFF, 0xFF, 0xFF]);
+
+        // 1sec period: GRPFREQ = 24 - 1, DMBLNK set.
+        pca.set_group_blink(1000, 0x80);
+        let bytes = pushed(&mut pca);
+        assert_eq!(bytes[2], 0x21);
+        assert_eq!(bytes[19..21], [0x80, 23]);
+
+        // Period is rounded to the nearest 1/24 sec and clamped.
+        pca.set_group_blink(500, 0x80);
+        assert_eq!(pushed(&mut pca)[20], 11);
+        pca.set_group_blink(0, 0x80);
+        assert_eq!(pushed(&mut pca)[20], 0);
+        pca.set_group_blink(60_000, 0x80);
+        assert_eq!(pushed(&mut pca)[20], 0xFF);
+        pca.set_group_blink(u32::MAX, 0x80);
+        assert_eq!(pushed(&mut pca)[20], 0xFF);
+
+        // LEDs taken out of the group drop to individual PWM only.
+        pca.set_blink_group(0, false);
+        pca.set_blink_group(5, false);
+        pca.set_blink_group(15, false);
+        let bytes = pushed(&mut pca);
+        assert_eq!(bytes[21..], [0b11111110, 0b11111011, 0xFF, 0b10111111]);
+
+        // Back to dimming.
+        pca.set_group_pwm(0x10);
+        let bytes = pushed(&mut pca);
+        assert_eq!(bytes[2], 0x01);
+        assert_eq!(bytes[19], 0x10);
+    }
+}
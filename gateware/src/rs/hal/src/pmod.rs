@@ -1,3 +1,47 @@
+/// Output gain of 1.0, as written to the gain stage ahead of the DAC.
+pub const DAC_GAIN_UNITY: u16 = 1 << 15;
+
+/// Output gain ramp, for muting and unmuting without a click. Each
+/// `step` moves the gain toward silence (or unity), without overshoot.
+#[derive(Debug, Clone, Copy)]
+pub struct MuteRamp {
+    gain: u16,
+    mute: bool,
+}
+
+impl MuteRamp {
+    pub const fn new() -> Self {
+        Self { gain: DAC_GAIN_UNITY, mute: false }
+    }
+
+    pub fn gain(&self) -> u16 {
+        self.gain
+    }
+
+    /// Move `step` (at least 1) closer to `mute`, returning the new gain.
+    pub fn step(&mut self, mute: bool, step: i16) -> u16 {
+        let step = step.unsigned_abs().max(1);
+        self.mute = mute;
+        self.gain = if mute {
+            self.gain.saturating_sub(step)
+        } else {
+            self.gain.saturating_add(step).min(DAC_GAIN_UNITY)
+        };
+        self.gain
+    }
+
+    /// The gain reached the target of the last `step`.
+    pub fn done(&self) -> bool {
+        self.gain == if self.mute { 0 } else { DAC_GAIN_UNITY }
+    }
+}
+
+impl Default for MuteRamp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub trait EurorackPmod {
     fn jack(&self) -> u8;
     fn touch_err(&self) -> u8;
@@ -7,8 +51,22 @@ pub trait EurorackPmod {
     fn led_set_auto(&mut self, index: usize);
     fn led_all_auto(&mut self);
     fn led_all_manual(&mut self);
-    fn write_calibration_constant(&mut self, ch: u8, a: i32, b: i32);
+    /// Start writing a calibration constant. The previous write (if any)
+    /// must have completed, see `calibration_write_done`.
+    fn write_calibration_constant_start(&mut self, ch: u8, a: i32, b: i32);
+    /// True once the last started calibration write has been committed.
+    fn calibration_write_done(&self) -> bool;
+    fn write_calibration_constant(&mut self, ch: u8, a: i32, b: i32) {
+        self.write_calibration_constant_start(ch, a, b);
+        while !self.calibration_write_done() {}
+    }
+    /// Instantly (soft) mute the CODEC. May click, see `mute_ramp`.
     fn mute(&mut self, mute: bool);
+    /// Step the output gain toward muted (or unmuted) by `step`, out of
+    /// `DAC_GAIN_UNITY`. Call repeatedly (e.g. from a timer ISR) until
+    /// `is_mute_ramp_done`. This leaves the CODEC mute as it was.
+    fn mute_ramp(&mut self, target: bool, step: i16);
+    fn is_mute_ramp_done(&self) -> bool;
     fn hard_reset(&mut self);
     fn set_aclk_unstable(&mut self);
     fn f_bits(&self) -> u8;
@@ -25,11 +83,12 @@ macro_rules! impl_eurorack_pmod {
             pub struct $PMODX {
                 pub registers: $PACPMODX,
                 led_mode: u8,
+                mute_ramp: $crate::pmod::MuteRamp,
             }
 
             impl $PMODX {
                 pub fn new(registers: $PACPMODX) -> Self {
-                    Self { registers, led_mode: 0xff }
+                    Self { registers, led_mode: 0xff, mute_ramp: $crate::pmod::MuteRamp::new() }
                 }
             }
 
@@ -104,20 +163,32 @@ macro_rules! impl_eurorack_pmod {
                     self.registers.led_mode().write(|w| unsafe { w.led().bits(self.led_mode) } );
                 }
 
-                fn write_calibration_constant(&mut self, ch: u8, a: i32, b: i32) {
+                fn write_calibration_constant_start(&mut self, ch: u8, a: i32, b: i32) {
                     self.registers.cal_a().write(|w| unsafe { w.value().bits(a as u32) });
                     self.registers.cal_b().write(|w| unsafe { w.value().bits(b as u32) });
                     self.registers.cal_reg().write(|w| unsafe {
                         w.write().bit(true);
                         w.channel().bits(ch)
                     });
-                    while !self.registers.cal_reg().read().done().bit() {}
+                }
+
+                fn calibration_write_done(&self) -> bool {
+                    self.registers.cal_reg().read().done().bit()
                 }
 
                 fn mute(&mut self, mute: bool) {
                     self.registers.flags().write(|w| w.mute().bit(mute) );
                 }
 
+                fn mute_ramp(&mut self, target: bool, step: i16) {
+                    let gain = self.mute_ramp.step(target, step);
+                    self.registers.dac_gain().write(|w| unsafe { w.gain().bits(gain) });
+                }
+
+                fn is_mute_ramp_done(&self) -> bool {
+                    self.mute_ramp.done()
+                }
+
                 fn hard_reset(&mut self) {
                     self.registers.flags().write(|w| w.hard_reset().bit(true) );
                 }
@@ -139,3 +210,38 @@ macro_rules! impl_eurorack_pmod {
         )+
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mute_ramp() {
+        let mut ramp = MuteRamp::new();
+        assert!(ramp.done());
+        assert_eq!(ramp.gain(), DAC_GAIN_UNITY);
+
+        // Reaches silence in ceil(32768 / 5000) steps, clamping the last one.
+        let mut steps = 0;
+        while !ramp.done() || steps == 0 {
+            let gain = ramp.step(true, 5000);
+            steps += 1;
+            assert_eq!(gain, DAC_GAIN_UNITY.saturating_sub(5000 * steps));
+        }
+        assert_eq!(steps, 7);
+        assert_eq!(ramp.step(true, 5000), 0);
+
+        // Reversing partway, and clamping at unity.
+        ramp.step(false, 20000);
+        assert!(!ramp.done());
+        assert_eq!(ramp.step(false, 20000), DAC_GAIN_UNITY);
+        assert!(ramp.done());
+        assert_eq!(ramp.step(false, i16::MAX), DAC_GAIN_UNITY);
+
+        // Negative steps count by magnitude, zero steps still move.
+        assert_eq!(ramp.step(true, -10000), DAC_GAIN_UNITY - 10000);
+        assert_eq!(ramp.step(true, 0), DAC_GAIN_UNITY - 10001);
+        assert_eq!(ramp.step(true, i16::MIN), 0);
+        assert!(ramp.done());
+    }
+}
@@ -1,3 +1,67 @@
+use serde_derive::{Serialize, Deserialize};
+use strum_macros::{EnumIter, IntoStaticStr};
+
+/// Envelope rate for a full-scale ramp lasting `time_ms`.
+///
+/// Envelopes are a 16-bit level (0..65535), which each rate register
+/// increments or decrements once per audio sample at `fs`. So a rate of
+/// `r` takes `65536 / r` samples to sweep the whole range. Decay and
+/// release times are for the full range as well, so a decay to a high
+/// sustain level finishes early. Clamped to the register range, with
+/// zero treated as the fastest rate.
+pub fn adsr_rate(time_ms: u32, fs: u32) -> u16 {
+    let samples = (time_ms as u64 * fs as u64 / 1000).max(1);
+    (65536u64 / samples).clamp(1, u16::MAX as u64) as u16
+}
+
+/// Which voice a new note takes over when all voices are occupied.
+#[derive(Default, Debug, PartialEq, Clone, Copy, Serialize, Deserialize, EnumIter, IntoStaticStr)]
+#[strum(serialize_all = "kebab-case")]
+pub enum StealPolicy {
+    /// The longest-playing note.
+    #[default]
+    Oldest,
+    /// The note with the lowest filter envelope.
+    Quietest,
+    /// The lowest note number.
+    Lowest,
+    /// The highest note number.
+    Highest,
+}
+
+/// Voice slot state as read back from the synth.
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+pub struct VoiceState {
+    pub note: u8,
+    pub cutoff: u8,
+    pub gate: bool,
+    /// Key released, but held by the sustain pedal.
+    pub sustained: bool,
+}
+
+/// Voice slot to steal under `policy`. `ages` holds when each slot last
+/// started a note (larger is newer). Voices held only by the sustain pedal
+/// are considered only if every voice is sustained. Ties go to the lowest
+/// slot index.
+pub fn steal_voice(policy: StealPolicy, voices: &[VoiceState], ages: &[u32]) -> usize {
+    let any_keyed = voices.iter().any(|v| !v.sustained);
+    let key = |ix: usize| -> i32 {
+        let v = &voices[ix];
+        match policy {
+            StealPolicy::Oldest   => -(ages[ix] as i32),
+            StealPolicy::Quietest => -(v.cutoff as i32),
+            StealPolicy::Lowest   => -(v.note as i32),
+            StealPolicy::Highest  => v.note as i32,
+        }
+    };
+    (0..voices.len())
+        .filter(|&ix| !any_keyed || !voices[ix].sustained)
+        // `max_by_key` keeps the last maximum, so walk backwards.
+        .rev()
+        .max_by_key(|&ix| key(ix))
+        .unwrap_or(0)
+}
+
 #[macro_export]
 macro_rules! impl_polysynth {
     ($(
@@ -8,11 +72,22 @@ macro_rules! impl_polysynth {
             #[derive(Debug)]
             pub struct $POLYSYNTHX {
                 registers: $PACPOLYSYNTHX,
+                steal_policy: $crate::polysynth::StealPolicy,
+                // Voice states and note start times as of the last `update_steal`.
+                last_voices: [$crate::polysynth::VoiceState; $N_VOICES],
+                voice_ages: [u32; $N_VOICES],
+                age: u32,
             }
 
             impl $POLYSYNTHX {
                 pub fn new(registers: $PACPOLYSYNTHX) -> Self {
-                    Self { registers }
+                    Self {
+                        registers,
+                        steal_policy: $crate::polysynth::StealPolicy::default(),
+                        last_voices: [$crate::polysynth::VoiceState::default(); $N_VOICES],
+                        voice_ages: [0; $N_VOICES],
+                        age: 0,
+                    }
                 }
             }
 
@@ -44,6 +119,50 @@ macro_rules! impl_polysynth {
                     ]
                 }
 
+                pub fn voice_states(&self) -> [$crate::polysynth::VoiceState; $N_VOICES] {
+                    let read = |r: u32| $crate::polysynth::VoiceState {
+                        note:      (r & 0xFF) as u8,
+                        cutoff:    ((r >> 8) & 0xFF) as u8,
+                        gate:      (r >> 16) & 1 != 0,
+                        sustained: (r >> 17) & 1 != 0,
+                    };
+                    [
+                        read(self.registers.voices0().read().bits()),
+                        read(self.registers.voices1().read().bits()),
+                        read(self.registers.voices2().read().bits()),
+                        read(self.registers.voices3().read().bits()),
+                        read(self.registers.voices4().read().bits()),
+                        read(self.registers.voices5().read().bits()),
+                        read(self.registers.voices6().read().bits()),
+                        read(self.registers.voices7().read().bits()),
+                    ]
+                }
+
+                /// Policy for picking the voice a new note takes over when
+                /// all voices are occupied. Applied by `update_steal`.
+                pub fn set_steal_policy(&mut self, policy: $crate::polysynth::StealPolicy)  {
+                    self.steal_policy = policy;
+                }
+
+                /// Track when each voice started its note, and nominate the
+                /// voice to steal under the current policy. Call this
+                /// periodically: note ages are only as precise as the call
+                /// rate, and notes arriving between calls while all voices
+                /// are busy share the same nominated voice.
+                pub fn update_steal(&mut self)  {
+                    let voices = self.voice_states();
+                    for (ix, (v, last)) in voices.iter().zip(self.last_voices.iter()).enumerate() {
+                        if v.gate && (!last.gate || v.note != last.note) {
+                            self.age = self.age.wrapping_add(1);
+                            self.voice_ages[ix] = self.age;
+                        }
+                    }
+                    self.last_voices = voices;
+                    let steal = $crate::polysynth::steal_voice(
+                        self.steal_policy, &voices, &self.voice_ages);
+                    self.registers.steal_voice().write(|w| unsafe { w.voice().bits(steal as u8) } );
+                }
+
                 pub fn set_matrix_coefficient(&mut self, o_x: u32, i_y: u32, value: i32)  {
                     // TODO: statically verify x_o, y_i both < 16. Should be true for any normal use case
                     // as matrices larger than this won't be able to process things at audio rate.
@@ -63,6 +182,17 @@ macro_rules! impl_polysynth {
                     self.registers.reso().write(|w| unsafe { w.value().bits(value) } );
                 }
 
+                /// Set all envelope parameters at once. Rates are as described
+                /// in `polysynth::adsr_rate`, and `sustain` is a level (0..65535). These
+                /// are latched by each voice on note-on, so changes apply from
+                /// the next note played.
+                pub fn set_adsr(&mut self, attack: u16, decay: u16, sustain: u16, release: u16)  {
+                    self.set_attack_rate(attack);
+                    self.set_decay_rate(decay);
+                    self.set_sustain_level(sustain);
+                    self.set_release_rate(release);
+                }
+
                 pub fn set_attack_rate(&mut self, value: u16)  {
                     self.registers.attack_rate().write(|w| unsafe { w.value().bits(value) } );
                 }
@@ -83,6 +213,17 @@ macro_rules! impl_polysynth {
                     self.registers.lfo().write(|w| unsafe { w.value().bits(value as u16) } );
                 }
 
+                /// Stereo position of a voice slot, from -32768 (hard left) to
+                /// 32767 (hard right). This belongs to the slot rather than
+                /// the note, so it survives voice stealing.
+                pub fn set_voice_pan(&mut self, voice: usize, pan: i16)  {
+                    assert!(voice < $N_VOICES);
+                    self.registers.voice_pan().write(|w| unsafe {
+                        w.voice().bits(voice as u8);
+                        w.value().bits(pan as u16)
+                    } );
+                }
+
                 pub fn write_wavetable_sample(&mut self, addr: u16, data: i16)  {
                     self.registers.wt_addr().write(|w| unsafe { w.value().bits(addr) } );
                     self.registers.wt_data().write(|w| unsafe { w.value().bits(data as u16) } );
@@ -114,3 +255,69 @@ macro_rules! impl_polysynth {
         )+
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voice(note: u8, cutoff: u8, sustained: bool) -> VoiceState {
+        VoiceState { note, cutoff, gate: true, sustained }
+    }
+
+    #[test]
+    fn test_steal_voice() {
+        let voices = [
+            voice(60, 200, false),
+            voice(48, 40,  false),
+            voice(72, 90,  false),
+            voice(55, 40,  false),
+        ];
+        let ages = [3, 2, 4, 5];
+        assert_eq!(steal_voice(StealPolicy::Oldest,   &voices, &ages), 1);
+        // Slots 1 and 3 are equally quiet, the lower slot wins.
+        assert_eq!(steal_voice(StealPolicy::Quietest, &voices, &ages), 1);
+        assert_eq!(steal_voice(StealPolicy::Lowest,   &voices, &ages), 1);
+        assert_eq!(steal_voice(StealPolicy::Highest,  &voices, &ages), 2);
+    }
+
+    #[test]
+    fn test_steal_voice_sustained() {
+        // Sustained voices are passed over while any voice is keyed ...
+        let voices = [
+            voice(60, 10,  true),
+            voice(48, 200, false),
+            voice(72, 90,  true),
+            voice(55, 150, false),
+        ];
+        let ages = [1, 4, 2, 3];
+        assert_eq!(steal_voice(StealPolicy::Oldest,   &voices, &ages), 3);
+        assert_eq!(steal_voice(StealPolicy::Quietest, &voices, &ages), 3);
+        assert_eq!(steal_voice(StealPolicy::Lowest,   &voices, &ages), 1);
+        assert_eq!(steal_voice(StealPolicy::Highest,  &voices, &ages), 3);
+        // ... but still stolen once every voice is sustained.
+        let voices = voices.map(|v| VoiceState { sustained: true, ..v });
+        assert_eq!(steal_voice(StealPolicy::Oldest,   &voices, &ages), 0);
+        assert_eq!(steal_voice(StealPolicy::Quietest, &voices, &ages), 0);
+        assert_eq!(steal_voice(StealPolicy::Highest,  &voices, &ages), 2);
+    }
+
+    #[test]
+    fn test_adsr_rate() {
+        // 1ms at 48kHz is 48 samples.
+        assert_eq!(adsr_rate(1, 48000), 1365);
+        assert_eq!(adsr_rate(1000, 48000), 1);
+        assert_eq!(adsr_rate(100, 48000), 13);
+        // Longer times never reach zero (which would hang the envelope).
+        assert_eq!(adsr_rate(60_000, 48000), 1);
+        // Zero time, or under a sample, is as fast as the register allows.
+        assert_eq!(adsr_rate(0, 48000), u16::MAX);
+        assert_eq!(adsr_rate(1, 500), u16::MAX);
+        // Monotonic in time.
+        let mut last = u16::MAX;
+        for ms in 0..2000 {
+            let rate = adsr_rate(ms, 48000);
+            assert!(rate <= last);
+            last = rate;
+        }
+    }
+}
@@ -19,13 +19,15 @@ use log;
 pub enum Error {
     CommunicationError,
     InvalidParameter,
+    FrequencyOutOfTolerance,
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::CommunicationError => write!(f, "Communication Error"),
-            Error::InvalidParameter => write!(f, "Invalid Parameter")
+            Error::InvalidParameter => write!(f, "Invalid Parameter"),
+            Error::FrequencyOutOfTolerance => write!(f, "Frequency Out Of Tolerance"),
         }
     }
 }
@@ -244,6 +246,18 @@ impl ClockOutput {
     fn ix(&self) -> u8 {
         *self as u8
     }
+
+    fn multisynth(self) -> Result<Multisynth, Error> {
+        match self {
+            ClockOutput::Clk0 => Ok(Multisynth::MS0),
+            ClockOutput::Clk1 => Ok(Multisynth::MS1),
+            ClockOutput::Clk2 => Ok(Multisynth::MS2),
+            ClockOutput::Clk3 => Ok(Multisynth::MS3),
+            ClockOutput::Clk4 => Ok(Multisynth::MS4),
+            ClockOutput::Clk5 => Ok(Multisynth::MS5),
+            _ => Err(Error::InvalidParameter),
+        }
+    }
 }
 
 impl OutputDivider {
@@ -290,6 +304,53 @@ pub enum Current {
     Output8mA = 0b11,
 }
 
+/// Divider settings for a set of outputs sharing one PLL, as chosen by
+/// `set_frequencies`. Output 0 is always an integer division of the PLL.
+struct FrequencyPlan {
+    // PLL multiplier, `mult + num/denom`
+    mult: u8,
+    num: u32,
+    denom: u32,
+    // Multisynth divider of each output, `int + num/denom`
+    ms: [(u16, u32, u32); 8],
+    r_div: OutputDivider,
+}
+
+impl FrequencyPlan {
+    /// Frequency actually synthesized on output `i`, rounded to the nearest Hz.
+    fn output_freq(&self, xtal_freq: u32, i: usize) -> u32 {
+        synth_freq(xtal_freq, (self.mult, self.num, self.denom), self.ms[i], self.r_div)
+    }
+}
+
+/// Frequency of an output with PLL multiplier `mult + num/denom` and
+/// multisynth divider `int + num/denom`, rounded to the nearest Hz.
+fn synth_freq(xtal_freq: u32, pll: (u8, u32, u32), ms: (u16, u32, u32), r_div: OutputDivider) -> u32 {
+    let (mult, pll_num, pll_denom) = pll;
+    let (ms_int, ms_num, ms_denom) = ms;
+    let num = xtal_freq as u128
+        * (mult as u128 * pll_denom as u128 + pll_num as u128)
+        * ms_denom as u128;
+    let den = pll_denom as u128
+        * (ms_int as u128 * ms_denom as u128 + ms_num as u128)
+        * r_div.denominator_u8() as u128;
+    ((num + den / 2) / den) as u32
+}
+
+/// Fractional multisynth divider (1e-4 resolution) for `freq` from a PLL
+/// running at `pll_freq`.
+fn fractional_divider(pll_freq: f32, freq: u32) -> (u16, u32, u32) {
+    let factor = pll_freq / (freq as f32);
+    let int_factor = factor as u16;
+    let rem = ((factor - (int_factor as f32)) * 1e4) as u32;
+    #[cfg(test)]
+    {
+        log::info!("pll_freq={} factor={} int_factor={} rem={}", pll_freq, factor, int_factor, rem);
+        log::info!("f_out={}", pll_freq / (int_factor as f32 + (rem as f32 / 1e4)));
+    }
+    (int_factor, rem, 10000)
+}
+
 /// Si5351 driver
 pub struct Si5351Device<I2C> {
     i2c: I2C,
@@ -298,6 +359,8 @@ pub struct Si5351Device<I2C> {
     clk_enabled_mask: u8,
     ms_int_mode_mask: u8,
     ms_src_mask: u8,
+    // Last `setup_pll` coefficients (mult, num, denom) of PLL A and B.
+    pll_coeffs: [Option<(u8, u32, u32)>; 2],
 }
 
 pub struct SpreadParams {
@@ -357,9 +420,22 @@ pub trait Si5351 {
 
     fn set_frequency(&mut self, pll: PLL, clk: ClockOutput, freq: u32, spread: Option<f32>) -> Result<(), Error>;
     fn set_frequencies(&mut self, pll: PLL, clks: &[ClockOutput], freqs: &[u32], spread: Option<f32>) -> Result<(), Error>;
+    /// As `set_frequencies`, but also returns the frequency actually synthesized
+    /// on each output, which may differ from what was asked for due to rounding
+    /// of the divider ratios.
+    fn set_frequencies_checked<const N: usize>(&mut self, pll: PLL, clks: &[ClockOutput; N], freqs: &[u32; N],
+                                               spread: Option<f32>) -> Result<[u32; N], Error>;
     fn set_clock_enabled(&mut self, clk: ClockOutput, enabled: bool);
     fn setup_spread_spectrum(&mut self, pll: PLL, params: &SpreadParams) -> Result<u8, Error>;
     fn clear_spread_spectrum(&mut self) -> Result<(), Error>;
+    /// Change the spread spectrum of a PLL that is already running, without
+    /// touching its dividers. `None` disables spread spectrum. Only PLL A
+    /// supports it.
+    fn set_spread_spectrum(&mut self, pll: PLL, amount: Option<f32>) -> Result<(), Error>;
+    /// Retune one output of a PLL that is already running, with a fractional
+    /// divider. The PLL and other outputs are not touched, so they keep
+    /// running undisturbed. Returns the frequency achieved.
+    fn set_output_frequency(&mut self, pll: PLL, clk: ClockOutput, freq: u32) -> Result<u32, Error>;
 
     fn flush_output_enabled(&mut self) -> Result<(), Error>;
     fn flush_clock_control(&mut self, clk: ClockOutput) -> Result<(), Error>;
@@ -396,6 +472,7 @@ impl<I2C: I2c> Si5351Device<I2C> {
             clk_enabled_mask: 0,
             ms_int_mode_mask: 0,
             ms_src_mask: 0,
+            pll_coeffs: [None; 2],
         };
 
         si5351
@@ -537,6 +614,88 @@ impl<I2C: I2c> Si5351Device<I2C> {
             )
             .map_err(i2c_error)
     }
+
+    fn pll_freq(&self, pll: (u8, u32, u32)) -> f32 {
+        let (mult, num, denom) = pll;
+        (self.xtal_freq as u64 * mult as u64
+            + (self.xtal_freq as u64 * num as u64) / denom as u64) as f32
+    }
+
+    fn enable_spread_spectrum(&mut self, reg0: u8) -> Result<(), Error> {
+        self.i2c
+            .write(
+                self.address,
+                &[
+                    0x95,
+                    reg0 | 0x80
+                ],
+            )
+            .map_err(i2c_error)
+    }
+
+    fn plan_frequencies(&self, freqs: &[u32]) -> Result<FrequencyPlan, Error> {
+        if freqs.is_empty() || freqs.len() > 8 || freqs.contains(&0) {
+            return Err(Error::InvalidParameter);
+        }
+
+        let denom0: u32 = 1048575;
+        let (ms_divider, r_div) = self.find_int_dividers_for_max_pll_freq(900_000_000, freqs[0])?;
+        let total_div = ms_divider as u32 * r_div.denominator_u8() as u32;
+        let (mult0, num0) = self.find_pll_coeffs_for_dividers(total_div, denom0, freqs[0])?;
+
+        let mut ms = [(0u16, 0u32, 1u32); 8];
+        ms[0] = (ms_divider, 0, 1);
+        let pll_freq = self.pll_freq((mult0, num0, denom0));
+        for i in 1..freqs.len() {
+            ms[i] = fractional_divider(pll_freq, freqs[i]);
+        }
+
+        Ok(FrequencyPlan {
+            mult: mult0,
+            num: num0,
+            denom: denom0,
+            ms,
+            r_div,
+        })
+    }
+
+    fn program_frequencies(&mut self, pll: PLL, clks: &[ClockOutput], plan: &FrequencyPlan,
+                           spread: Option<f32>) -> Result<(), Error> {
+        let mut reg0 = 0u8;
+        if let Some(spread_percent) = spread {
+            let params = SpreadParams {
+                f_pfd: self.xtal_freq as f32,
+                a: plan.mult as f32,
+                b: plan.num as f32,
+                c: plan.denom as f32,
+                ssc_amp: spread_percent,
+            };
+            reg0 = self.setup_spread_spectrum(pll, &params)?;
+        }
+
+        for (i, clk) in clks.iter().enumerate() {
+            let ms = clk.multisynth()?;
+            let (ms_int, ms_num, ms_denom) = plan.ms[i];
+            if i == 0 {
+                self.setup_multisynth_int(ms, ms_int, plan.r_div)?;
+                self.setup_pll(pll, plan.mult, plan.num, plan.denom)?;
+            } else {
+                self.setup_multisynth(ms, ms_int, ms_num, ms_denom, plan.r_div)?;
+            }
+            self.select_clock_pll(*clk, pll);
+            self.set_clock_enabled(*clk, true);
+            self.flush_clock_control(*clk)?;
+        }
+
+        self.reset_pll(pll)?;
+
+        // HACK: delay SSC_EN until AFTER PLL lock!
+        self.enable_spread_spectrum(reg0)?;
+
+        self.flush_output_enabled()?;
+
+        Ok(())
+    }
 }
 
 impl<I2C: I2c> Si5351 for Si5351Device<I2C>
@@ -663,6 +822,42 @@ impl<I2C: I2c> Si5351 for Si5351Device<I2C>
         )
     }
 
+    fn set_spread_spectrum(&mut self, pll: PLL, amount: Option<f32>) -> Result<(), Error> {
+        let PLL::A = pll else {
+            return Err(Error::InvalidParameter);
+        };
+        let (mult, num, denom) = self.pll_coeffs[pll as usize].ok_or(Error::InvalidParameter)?;
+        match amount {
+            Some(ssc_amp) => {
+                let params = SpreadParams {
+                    f_pfd: self.xtal_freq as f32,
+                    a: mult as f32,
+                    b: num as f32,
+                    c: denom as f32,
+                    ssc_amp,
+                };
+                let reg0 = self.setup_spread_spectrum(pll, &params)?;
+                self.enable_spread_spectrum(reg0)
+            }
+            None => self.clear_spread_spectrum(),
+        }
+    }
+
+    fn set_output_frequency(&mut self, pll: PLL, clk: ClockOutput, freq: u32) -> Result<u32, Error> {
+        let coeffs = self.pll_coeffs[pll as usize].ok_or(Error::InvalidParameter)?;
+        if freq == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        let ms = clk.multisynth()?;
+        let (ms_int, ms_num, ms_denom) = fractional_divider(self.pll_freq(coeffs), freq);
+        self.setup_multisynth(ms, ms_int, ms_num, ms_denom, OutputDivider::Div1)?;
+        self.select_clock_pll(clk, pll);
+        self.set_clock_enabled(clk, true);
+        self.flush_clock_control(clk)?;
+        self.flush_output_enabled()?;
+        Ok(synth_freq(self.xtal_freq, coeffs, (ms_int, ms_num, ms_denom), OutputDivider::Div1))
+    }
+
     fn set_frequency(&mut self, pll: PLL, clk: ClockOutput, freq: u32, spread: Option<f32>) -> Result<(), Error> {
 
         let denom: u32 = 1048575;
@@ -703,71 +898,15 @@ impl<I2C: I2c> Si5351 for Si5351Device<I2C>
     }
 
     fn set_frequencies(&mut self, pll: PLL, clks: &[ClockOutput], freqs: &[u32], spread: Option<f32>) -> Result<(), Error> {
+        let plan = self.plan_frequencies(freqs)?;
+        self.program_frequencies(pll, clks, &plan, spread)
+    }
 
-        let denom0: u32 = 1048575;
-        let (ms_divider, r_div) = self.find_int_dividers_for_max_pll_freq(900_000_000, freqs[0])?;
-        let total_div = ms_divider as u32 * r_div.denominator_u8() as u32;
-        let (mult0, num0) = self.find_pll_coeffs_for_dividers(total_div, denom0, freqs[0])?;
-
-        let mut reg0 = 0u8;
-        if let Some(spread_percent) = spread {
-            let params = SpreadParams {
-                f_pfd: self.xtal_freq as f32,
-                a: mult0 as f32,
-                b: num0 as f32,
-                c: denom0 as f32,
-                ssc_amp: spread_percent,
-            };
-            reg0 = self.setup_spread_spectrum(pll, &params)?;
-        }
-
-        for (i, clk) in clks.iter().enumerate() {
-            let ms = match clk {
-                ClockOutput::Clk0 => Multisynth::MS0,
-                ClockOutput::Clk1 => Multisynth::MS1,
-                ClockOutput::Clk2 => Multisynth::MS2,
-                ClockOutput::Clk3 => Multisynth::MS3,
-                ClockOutput::Clk4 => Multisynth::MS4,
-                ClockOutput::Clk5 => Multisynth::MS5,
-                _ => return Err(Error::InvalidParameter),
-            };
-            if i == 0 {
-                self.setup_multisynth_int(ms, ms_divider, r_div)?;
-                self.setup_pll(pll, mult0, num0, denom0)?;
-            } else {
-                let pll_freq = (self.xtal_freq as u64 * mult0 as u64
-                    + (self.xtal_freq as u64 * num0 as u64) / denom0 as u64) as f32;
-                let factor = pll_freq / (freqs[i] as f32);
-                let int_factor = factor as u16;
-                let rem = ((factor - (int_factor as f32)) * 1e4) as u32;
-                #[cfg(test)]
-                {
-                    log::info!("pll_freq={} factor={} int_factor={} rem={}", pll_freq, factor, int_factor, rem);
-                    log::info!("f_out={}", pll_freq / (int_factor as f32 + (rem as f32 / 1e4)));
-                }
-                self.setup_multisynth(ms, int_factor, rem, 10000, r_div)?;
-            }
-            self.select_clock_pll(*clk, pll);
-            self.set_clock_enabled(*clk, true);
-            self.flush_clock_control(*clk)?;
-        }
-
-        self.reset_pll(pll)?;
-
-        // HACK: delay SSC_EN until AFTER PLL lock!
-        self.i2c
-            .write(
-                self.address,
-                &[
-                    0x95,
-                    reg0 | 0x80
-                ],
-            )
-            .map_err(i2c_error)?;
-
-        self.flush_output_enabled()?;
-
-        Ok(())
+    fn set_frequencies_checked<const N: usize>(&mut self, pll: PLL, clks: &[ClockOutput; N], freqs: &[u32; N],
+                                               spread: Option<f32>) -> Result<[u32; N], Error> {
+        let plan = self.plan_frequencies(freqs)?;
+        self.program_frequencies(pll, clks, &plan, spread)?;
+        Ok(core::array::from_fn(|i| plan.output_freq(self.xtal_freq, i)))
     }
 
     fn set_clock_enabled(&mut self, clk: ClockOutput, enabled: bool) {
@@ -828,6 +967,7 @@ impl<I2C: I2c> Si5351 for Si5351Device<I2C>
             denom,
             OutputDivider::Div1,
         )?;
+        self.pll_coeffs[pll as usize] = Some((mult, num, denom));
 
         if mult % 2 == 0 && num == 0 {
         } else {
@@ -998,4 +1138,143 @@ mod tests {
             Some(0.015))
             .expect("Failed to set frequency");
     }
+
+    #[test]
+    fn test_frequencies_checked() {
+
+        setup_logger();
+
+        let mut si = Si5351Device::new(MockI2c, false, 25_000_000);
+        let xtal = 25_000_000f64;
+
+        // (audio clock, pixel clock) pairs like the bootloader asks for.
+        let targets = [
+            (12_288_000, 25_175_000),
+            (12_288_000, 40_000_000),
+            (12_288_000, 65_750_000),
+            (12_288_000, 74_250_000),
+            (49_152_000, 37_390_000),
+            (49_152_000, 60_500_000),
+        ];
+
+        for (f0, f1) in targets {
+            let actual = si.set_frequencies_checked(
+                PLL::A, &[ClockOutput::Clk0, ClockOutput::Clk1], &[f0, f1], None)
+                .expect("Failed to set frequency");
+
+            // Reference: integer output divider for clk0, PLL tuned to hit
+            // it, then a fractional divider (1e-4 resolution) for clk1.
+            let (ms_divider, r_div) = si.find_int_dividers_for_max_pll_freq(900_000_000, f0).unwrap();
+            let total_div = ms_divider as u32 * r_div.denominator_u8() as u32;
+            let (mult, num) = si.find_pll_coeffs_for_dividers(total_div, 1048575, f0).unwrap();
+            let pll_freq = xtal * (mult as f64 + num as f64 / 1048575.0);
+            let ref0 = pll_freq / total_div as f64;
+            let plan = si.plan_frequencies(&[f0, f1]).unwrap();
+            let (int1, rem1, _) = plan.ms[1];
+            let ref1 = pll_freq / ((int1 as f64 + rem1 as f64 / 1e4) * r_div.denominator_u8() as f64);
+            log::info!("f0={} actual={} ref={}", f0, actual[0], ref0);
+            log::info!("f1={} actual={} ref={}", f1, actual[1], ref1);
+            assert!((actual[0] as f64 - ref0).abs() <= 1.0);
+            assert!((actual[1] as f64 - ref1).abs() <= 1.0);
+
+            // clk0 is near-exact, clk1 is limited by its divider resolution.
+            let ppm = |actual: u32, target: u32| {
+                (actual as f64 - target as f64).abs() * 1e6 / target as f64
+            };
+            assert!(ppm(actual[0], f0) < 1.0, "clk0 error {}ppm", ppm(actual[0], f0));
+            assert!(ppm(actual[1], f1) < 20.0, "clk1 error {}ppm", ppm(actual[1], f1));
+        }
+
+        assert!(si.set_frequencies_checked(PLL::A, &[ClockOutput::Clk0], &[0], None).is_err());
+    }
+
+    /// Records the register writes made by the driver.
+    #[derive(Default)]
+    struct RecordingI2c {
+        writes: std::vec::Vec<std::vec::Vec<u8>>,
+    }
+
+    impl embedded_hal::i2c::ErrorType for RecordingI2c {
+        type Error = core::convert::Infallible;
+    }
+
+    impl I2c for RecordingI2c {
+        fn transaction(
+            &mut self,
+            _address: u8,
+            operations: &mut [embedded_hal::i2c::Operation<'_>],
+        ) -> Result<(), Self::Error> {
+            for op in operations.iter_mut() {
+                match op {
+                    embedded_hal::i2c::Operation::Write(bytes) => self.writes.push(bytes.to_vec()),
+                    embedded_hal::i2c::Operation::Read(buffer) => buffer.fill(0),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Every register address written, in order.
+    fn written_registers(writes: &[std::vec::Vec<u8>]) -> std::vec::Vec<u8> {
+        writes.iter()
+              .flat_map(|w| (0..w.len() as u8 - 1).map(move |n| w[0] + n))
+              .collect()
+    }
+
+    fn configured_device() -> Si5351Device<RecordingI2c> {
+        let mut si = Si5351Device::new(RecordingI2c::default(), false, 25_000_000);
+        si.set_frequencies(PLL::A, &[ClockOutput::Clk0, ClockOutput::Clk1],
+                           &[12_288_000, 74_250_000], Some(0.01)).unwrap();
+        si.i2c.writes.clear();
+        si
+    }
+
+    #[test]
+    fn test_set_spread_spectrum() {
+        setup_logger();
+        let ssc_registers = 0x95..=0xA1u8;
+
+        let mut si = configured_device();
+        si.set_spread_spectrum(PLL::A, Some(0.005)).unwrap();
+        let registers = written_registers(&si.i2c.writes);
+        assert!(!registers.is_empty());
+        assert!(registers.iter().all(|r| ssc_registers.contains(r)), "{:?}", registers);
+        // SSC_EN is set last, once the parameters are in place.
+        assert_eq!(si.i2c.writes.last().unwrap()[0], 0x95);
+        assert_eq!(si.i2c.writes.last().unwrap()[1] & 0x80, 0x80);
+
+        si.i2c.writes.clear();
+        si.set_spread_spectrum(PLL::A, None).unwrap();
+        let registers = written_registers(&si.i2c.writes);
+        assert!(registers.iter().all(|r| ssc_registers.contains(r)), "{:?}", registers);
+        assert!(si.i2c.writes.iter().all(|w| w[1..].iter().all(|b| *b == 0)));
+
+        // PLL B has no spread spectrum, and PLLs must be set up first.
+        assert!(si.set_spread_spectrum(PLL::B, Some(0.01)).is_err());
+        let mut si = Si5351Device::new(RecordingI2c::default(), false, 25_000_000);
+        assert!(si.set_spread_spectrum(PLL::A, Some(0.01)).is_err());
+    }
+
+    #[test]
+    fn test_set_output_frequency() {
+        setup_logger();
+
+        let mut si = configured_device();
+        let achieved = si.set_output_frequency(PLL::A, ClockOutput::Clk1, 65_000_000).unwrap();
+        let ppm = (achieved as f64 - 65_000_000.0).abs() * 1e6 / 65_000_000.0;
+        assert!(ppm < 20.0, "clk1 error {}ppm", ppm);
+
+        // Only the MS1 divider, CLK1 control and output enables are written:
+        // no PLL, MS0, PLL reset or spread spectrum registers.
+        let registers = written_registers(&si.i2c.writes);
+        let allowed = |r: &u8| (50..58).contains(r) || *r == 17 || *r == 3;
+        assert!(registers.iter().all(allowed), "{:?}", registers);
+
+        // Same divider as planning both outputs from scratch.
+        let plan = si.plan_frequencies(&[12_288_000, 65_000_000]).unwrap();
+        assert_eq!(achieved, plan.output_freq(25_000_000, 1));
+
+        assert!(si.set_output_frequency(PLL::B, ClockOutput::Clk1, 74_250_000).is_err());
+        assert!(si.set_output_frequency(PLL::A, ClockOutput::Clk6, 74_250_000).is_err());
+    }
 }
@@ -13,6 +13,148 @@ impl NorFlashError for Error {
     }
 }
 
+/// Error from `write_verified` and `erase_verified`.
+#[derive(Debug, PartialEq)]
+pub enum VerifyError<E> {
+    Flash(E),
+    /// Flash at this offset didn't read back as expected after the write
+    /// or erase (bad sector, write protection).
+    Mismatch(u32),
+}
+
+impl<E: NorFlashError> NorFlashError for VerifyError<E> {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            VerifyError::Flash(e) => e.kind(),
+            VerifyError::Mismatch(_) => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+const VERIFY_CHUNK: usize = 64;
+
+/// Read back `len` bytes from `offset`, in chunks, and return the offset
+/// of the first byte for which `ok(offset, byte)` is false.
+fn verify<F: ReadNorFlash>(flash: &mut F, offset: u32, len: usize,
+                           ok: impl Fn(usize, u8) -> bool) -> Result<(), VerifyError<F::Error>> {
+    let mut buf = [0u8; VERIFY_CHUNK];
+    for start in (0..len).step_by(VERIFY_CHUNK) {
+        let chunk = &mut buf[..VERIFY_CHUNK.min(len - start)];
+        flash.read(offset + start as u32, chunk).map_err(VerifyError::Flash)?;
+        if let Some(n) = chunk.iter().enumerate().position(|(n, b)| !ok(start + n, *b)) {
+            return Err(VerifyError::Mismatch(offset + (start + n) as u32));
+        }
+    }
+    Ok(())
+}
+
+/// `NorFlash::write`, then read back to check every bit written as 0 was
+/// programmed. Bits written as 1 aren't checked, as they may already have
+/// been 0 (see `MultiwriteNorFlash`).
+pub fn write_verified<F: NorFlash>(flash: &mut F, offset: u32, bytes: &[u8]) ->
+    Result<(), VerifyError<F::Error>> {
+    flash.write(offset, bytes).map_err(VerifyError::Flash)?;
+    verify(flash, offset, bytes.len(), |n, b| b & !bytes[n] == 0)
+}
+
+/// `NorFlash::erase`, then read back to check everything is 0xFF.
+pub fn erase_verified<F: NorFlash>(flash: &mut F, from: u32, to: u32) ->
+    Result<(), VerifyError<F::Error>> {
+    flash.erase(from, to).map_err(VerifyError::Flash)?;
+    verify(flash, from, to.saturating_sub(from) as usize, |_, b| b == 0xFF)
+}
+
+/// Flash whose `write` and `erase` are `write_verified` and `erase_verified`,
+/// for anything that can't afford to find out about a failed program from a
+/// later CRC mismatch (e.g. option storage). Reading back roughly doubles
+/// the time of each write, so streaming writes keep using the flash directly.
+#[derive(Debug)]
+pub struct VerifiedFlash<F> {
+    flash: F,
+}
+
+impl<F> VerifiedFlash<F> {
+    pub fn new(flash: F) -> Self {
+        Self { flash }
+    }
+
+    pub fn free(self) -> F {
+        self.flash
+    }
+}
+
+impl<F: NorFlash> ErrorType for VerifiedFlash<F> {
+    type Error = VerifyError<F::Error>;
+}
+
+impl<F: NorFlash> ReadNorFlash for VerifiedFlash<F> {
+    const READ_SIZE: usize = F::READ_SIZE;
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.flash.read(offset, bytes).map_err(VerifyError::Flash)
+    }
+    fn capacity(&self) -> usize {
+        self.flash.capacity()
+    }
+}
+
+impl<F: NorFlash> NorFlash for VerifiedFlash<F> {
+    const WRITE_SIZE: usize = F::WRITE_SIZE;
+    const ERASE_SIZE: usize = F::ERASE_SIZE;
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        erase_verified(&mut self.flash, from, to)
+    }
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        write_verified(&mut self.flash, offset, bytes)
+    }
+}
+
+impl<F: MultiwriteNorFlash> MultiwriteNorFlash for VerifiedFlash<F> { }
+
+/// Copy `dst.len()` bytes from memory-mapped flash at `addr`. Aligned words are
+/// fetched with 32-bit reads, which is much faster than reading them byte-by-byte.
+///
+/// # Safety
+///
+/// `addr..addr+dst.len()` must be readable memory.
+pub unsafe fn read_mapped(addr: usize, dst: &mut [u8]) {
+    // Bytes up to the first word boundary, then whole words, then the tail.
+    let head = ((4 - addr % 4) % 4).min(dst.len());
+    let n_words = (dst.len() - head) / 4;
+    for (n, byte) in dst[..head].iter_mut().enumerate() {
+        *byte = core::ptr::read_volatile((addr + n) as *const u8);
+    }
+    for (n, chunk) in dst[head..head + n_words * 4].chunks_exact_mut(4).enumerate() {
+        let word = core::ptr::read_volatile((addr + head + n * 4) as *const u32);
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    let tail = head + n_words * 4;
+    for (n, byte) in dst[tail..].iter_mut().enumerate() {
+        *byte = core::ptr::read_volatile((addr + tail + n) as *const u8);
+    }
+}
+
+/// Copy `dst.len()` little-endian words from memory-mapped flash at `addr`,
+/// with 32-bit reads if `addr` is word-aligned, otherwise byte-by-byte.
+///
+/// # Safety
+///
+/// `addr..addr+4*dst.len()` must be readable memory.
+pub unsafe fn read_mapped_words(addr: usize, dst: &mut [u32]) {
+    if addr % 4 == 0 {
+        for (n, word) in dst.iter_mut().enumerate() {
+            *word = core::ptr::read_volatile((addr + n * 4) as *const u32);
+        }
+    } else {
+        for (n, word) in dst.iter_mut().enumerate() {
+            let mut bytes = [0u8; 4];
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = core::ptr::read_volatile((addr + n * 4 + i) as *const u8);
+            }
+            *word = u32::from_le_bytes(bytes);
+        }
+    }
+}
+
 pub trait SpiFlash {
     type Error;
     fn write_transaction(&mut self, cmd: &[u8]) -> Result<(), Error>;
@@ -56,6 +198,103 @@ macro_rules! impl_spiflash {
                 pub fn free(self) -> $PACSPIX {
                     self.registers
                 }
+
+                /// Bulk read of whole words from (memory-mapped) flash, see
+                /// `read_mapped_words`. Faster than `ReadNorFlash::read`
+                /// for large, word-aligned regions.
+                pub fn read_words(&mut self, offset: u32, dst: &mut [u32]) -> Result<(), $crate::spiflash::Error> {
+                    unsafe { $crate::spiflash::read_mapped_words(self.base + offset as usize, dst) };
+                    Ok(())
+                }
+
+                /// See `spiflash::write_verified`.
+                pub fn write_verified(&mut self, offset: u32, bytes: &[u8]) ->
+                    Result<(), $crate::spiflash::VerifyError<$crate::spiflash::Error>> {
+                    $crate::spiflash::write_verified(self, offset, bytes)
+                }
+
+                /// See `spiflash::erase_verified`.
+                pub fn erase_verified(&mut self, from: u32, to: u32) ->
+                    Result<(), $crate::spiflash::VerifyError<$crate::spiflash::Error>> {
+                    $crate::spiflash::erase_verified(self, from, to)
+                }
+
+                /// Write enable, then an erase or program `command` (followed
+                /// by `data`), then wait until the flash has finished it.
+                ///
+                /// Until then the flash can't be read, so nothing here may be
+                /// fetched from it, as XiP firmware (the bootloader) would on an
+                /// icache miss. This is linked into `.data`, which the runtime
+                /// copies to RAM, and only touches registers, so must be called
+                /// with interrupts disabled.
+                #[inline(never)]
+                #[link_section = ".data.spiflash_ram_command"]
+                unsafe fn ram_command(&mut self, command: &[u8; 4], data: &[u8]) -> Result<(), $crate::spiflash::Error> {
+                    let r = &self.registers;
+                    r.phy().write(|w| w.length().bits(8).width().bits(1).mask().bits(1));
+                    let mut timeout = 0;
+                    while !r.status().read().tx_ready().bit() {
+                        timeout += 1;
+                        if timeout > 1000 {
+                            return Err($crate::spiflash::Error::TxTimeout);
+                        }
+                    }
+
+                    r.cs().write(|w| w.select().bit(true));
+                    r.data().write(|w| w.tx().bits(SPIFLASH_CMD_WRITE_ENABLE as u32));
+                    while !r.status().read().rx_ready().bit() { }
+                    let _ = r.data().read().rx().bits();
+                    r.cs().write(|w| w.select().bit(false));
+
+                    // As `page_program`, draining RX whenever the FIFO may be full.
+                    r.cs().write(|w| w.select().bit(true));
+                    let mut bytes_in_fifo: usize = 0;
+                    for byte in command.iter().chain(data.iter()) {
+                        while !r.status().read().tx_ready().bit() { }
+                        r.data().write(|w| w.tx().bits(*byte as u32));
+                        bytes_in_fifo += 1;
+                        if bytes_in_fifo >= SPIFLASH_FIFO_LEN {
+                            while bytes_in_fifo > 0 {
+                                while !r.status().read().rx_ready().bit() { }
+                                let _ = r.data().read().rx().bits();
+                                bytes_in_fifo -= 1;
+                            }
+                        }
+                    }
+                    while bytes_in_fifo > 0 {
+                        while !r.status().read().rx_ready().bit() { }
+                        let _ = r.data().read().rx().bits();
+                        bytes_in_fifo -= 1;
+                    }
+                    r.cs().write(|w| w.select().bit(false));
+
+                    // As `busy`, until the WIP bit clears.
+                    loop {
+                        r.phy().write(|w| w.length().bits(8).width().bits(1).mask().bits(1));
+                        r.cs().write(|w| w.select().bit(true));
+                        r.data().write(|w| w.tx().bits(SPIFLASH_CMD_STATUS1 as u32));
+                        r.phy().write(|w| w.length().bits(8).width().bits(1).mask().bits(0));
+                        r.data().write(|w| w.tx().bits(0));
+                        while !r.status().read().rx_ready().bit() { }
+                        let _ = r.data().read().rx().bits();
+                        while !r.status().read().rx_ready().bit() { }
+                        let status = r.data().read().rx().bits() as u8;
+                        r.cs().write(|w| w.select().bit(false));
+                        if status & 0b0000_0001 == 0 {
+                            return Ok(());
+                        }
+                    }
+                }
+
+                fn erase_or_program(&mut self, command: u8, addr: u32, data: &[u8]) -> Result<(), $crate::spiflash::Error> {
+                    let command: [u8; 4] = [
+                        command,
+                        ((addr >> 16) & 0xff) as u8,
+                        ((addr >> 8) & 0xff) as u8,
+                        (addr & 0xff) as u8,
+                    ];
+                    critical_section::with(|_| unsafe { self.ram_command(&command, data) })
+                }
             }
 
             fn spi_ready(f: &dyn Fn() -> bool) -> bool {
@@ -246,10 +485,8 @@ macro_rules! impl_spiflash {
                 const READ_SIZE: usize = 1;
                 fn read(&mut self, offset: u32, bytes: &mut [u8]) ->
                     Result<(), Self::Error> {
-                    for n in 0..bytes.len() {
-                        let addr = self.base + (offset as usize) + n;
-                        bytes[n] = unsafe { core::ptr::read_volatile(addr as *mut u8) };
-                    }
+                    // Word reads where possible, this is used for option and sample loading.
+                    unsafe { $crate::spiflash::read_mapped(self.base + offset as usize, bytes) };
                     Ok(())
                 }
                 fn capacity(&self) -> usize {
@@ -262,19 +499,18 @@ macro_rules! impl_spiflash {
                 const ERASE_SIZE: usize = 4096;
 
                 fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
-                    use $crate::spiflash::SpiFlash;
                     // TODO $crate::nor_flash::check_erase(self, from, to)?;
                     let mut addr = from;
                     while addr < to {
-                        self.write_enable()?;
-                        self.sector_erase(addr)?;
-                        while self.busy()? { } // TODO timeout
+                        // TODO timeout
+                        self.erase_or_program(SPIFLASH_CMD_SECTOR_ERASE, addr, &[])?;
                         addr += Self::ERASE_SIZE as u32;
                     }
+                    // Reads are memory-mapped and cached, drop anything stale.
+                    pac::cpu::vexriscv::flush_dcache();
                     Ok(())
                 }
                 fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
-                    use $crate::spiflash::SpiFlash;
                     const PAGE_SIZE: usize = 256;
                     let mut written = 0;
                     let mut current_offset = offset;
@@ -284,15 +520,121 @@ macro_rules! impl_spiflash {
                                 PAGE_SIZE - page_offset,
                                 bytes.len() - written
                         );
-                        self.write_enable()?;
-                        self.page_program(current_offset, &bytes[written..written + bytes_to_write])?;
-                        while self.busy()? { } // TODO timeout
+                        // TODO timeout
+                        self.erase_or_program(SPIFLASH_CMD_PAGE_PROGRAM, current_offset,
+                                              &bytes[written..written + bytes_to_write])?;
                         written += bytes_to_write;
                         current_offset += bytes_to_write as u32;
                     }
+                    pac::cpu::vexriscv::flush_dcache();
                     Ok(())
                 }
             }
         )+
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flash that behaves like NOR (writes only clear bits), with a range
+    /// that silently ignores writes and erases, like a protected sector.
+    struct MockFlash {
+        mem: Vec<u8>,
+        protected: core::ops::Range<usize>,
+    }
+
+    impl ErrorType for MockFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for MockFlash {
+        const READ_SIZE: usize = 1;
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            bytes.copy_from_slice(&self.mem[offset as usize..offset as usize + bytes.len()]);
+            Ok(())
+        }
+        fn capacity(&self) -> usize {
+            self.mem.len()
+        }
+    }
+
+    impl NorFlash for MockFlash {
+        const WRITE_SIZE: usize = 1;
+        const ERASE_SIZE: usize = 4096;
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            for n in from as usize..to as usize {
+                if !self.protected.contains(&n) {
+                    self.mem[n] = 0xFF;
+                }
+            }
+            Ok(())
+        }
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            for (n, b) in bytes.iter().enumerate() {
+                let addr = offset as usize + n;
+                if !self.protected.contains(&addr) {
+                    self.mem[addr] &= *b;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl MultiwriteNorFlash for MockFlash { }
+
+    #[test]
+    fn test_verified_flash() {
+        let mut flash = MockFlash { mem: vec![0u8; 4 * 4096], protected: 0x2100..0x2104 };
+
+        assert_eq!(erase_verified(&mut flash, 0x0000, 0x2000), Ok(()));
+        let data: Vec<u8> = (0..300).map(|n| n as u8).collect();
+        assert_eq!(write_verified(&mut flash, 0x0010, &data), Ok(()));
+        assert_eq!(&flash.mem[0x10..0x10 + 300], &data[..]);
+
+        // Writing over written flash only clears bits, and bits written as 1
+        // that were already cleared aren't a mismatch either.
+        assert_eq!(write_verified(&mut flash, 0x0010, &[0u8; 16]), Ok(()));
+        assert_eq!(write_verified(&mut flash, 0x0020, &[0xFF; 4]), Ok(()));
+        assert_eq!(&flash.mem[0x10..0x20], &[0u8; 16]);
+        assert_eq!(&flash.mem[0x20..0x24], &[16, 17, 18, 19]);
+
+        // Erase and write into the protected bytes, past the first readback chunk.
+        assert_eq!(erase_verified(&mut flash, 0x2000, 0x3000), Err(VerifyError::Mismatch(0x2100)));
+        flash.mem[0x2100..0x2104].fill(0xFF);
+        assert_eq!(erase_verified(&mut flash, 0x2000, 0x3000), Ok(()));
+        assert_eq!(write_verified(&mut flash, 0x20F0, &[0x5A; 32]), Err(VerifyError::Mismatch(0x2100)));
+
+        // Same through the wrapper, as used by option storage.
+        let mut verified = VerifiedFlash::new(flash);
+        assert_eq!(verified.erase(0x1000, 0x2000), Ok(()));
+        assert_eq!(verified.write(0x1000, &[0x12, 0x34]), Ok(()));
+        assert_eq!(verified.write(0x2102, &[0x00]), Err(VerifyError::Mismatch(0x2102)));
+        let mut bytes = [0u8; 2];
+        verified.read(0x1000, &mut bytes).unwrap();
+        assert_eq!(bytes, [0x12, 0x34]);
+        assert_eq!(verified.free().mem[0x1000], 0x12);
+    }
+
+    #[test]
+    fn test_read_mapped() {
+        let words: [u32; 16] = core::array::from_fn(|n| 0x03020100u32 + 0x04040404 * n as u32);
+        let base = words.as_ptr() as usize;
+        let reference = |offset: usize, len: usize| -> Vec<u8> {
+            (offset..offset+len).map(|n| unsafe { core::ptr::read_volatile((base + n) as *const u8) }).collect()
+        };
+        // Every combination of alignment at the start and end of the read.
+        for offset in 0..8 {
+            for len in 0..24 {
+                let mut bytes = vec![0u8; len];
+                unsafe { read_mapped(base + offset, &mut bytes) };
+                assert_eq!(bytes, reference(offset, len), "offset={} len={}", offset, len);
+            }
+            let mut dst = [0u32; 6];
+            unsafe { read_mapped_words(base + offset, &mut dst) };
+            let bytes: Vec<u8> = dst.iter().flat_map(|w| w.to_le_bytes()).collect();
+            assert_eq!(bytes, reference(offset, 24), "offset={}", offset);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
 /// Timer Events
 ///
 /// Each event is a possible interrupt source, if enabled.
@@ -11,6 +13,67 @@ pub enum Mode {
     Periodic,
 }
 
+/// Uptime bookkeeping for a timer generating periodic tick interrupts.
+///
+/// The tick ISR is the only writer (see `tick`), so a plain load/store of
+/// the period count is enough, and works on cores without atomic RMW
+/// instructions. Readers retry if a tick lands while they are reading.
+pub struct Uptime {
+    periods: AtomicU32,
+    period_ticks: AtomicU32,
+}
+
+impl Uptime {
+    pub const fn new() -> Self {
+        Self {
+            periods: AtomicU32::new(0),
+            period_ticks: AtomicU32::new(0),
+        }
+    }
+
+    pub fn set_period_ticks(&self, ticks: u32) {
+        self.period_ticks.store(ticks, Ordering::Relaxed);
+    }
+
+    /// Count one elapsed period. Must only be called from the tick ISR.
+    pub fn tick(&self) {
+        let periods = self.periods.load(Ordering::Relaxed);
+        self.periods.store(periods.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Total elapsed timer ticks. `counter` reads the (down-counting) timer
+    /// value and `pending` whether its timeout interrupt is pending. A
+    /// pending interrupt means the counter already reloaded for a period
+    /// that `tick` hasn't counted yet, e.g. when read from inside a
+    /// critical section.
+    pub fn ticks(&self, counter: impl Fn() -> u32, pending: impl Fn() -> bool) -> u64 {
+        let period = self.period_ticks.load(Ordering::Relaxed) as u64;
+        loop {
+            let periods = self.periods.load(Ordering::Acquire);
+            let count_before = counter();
+            let reloaded = pending();
+            let count_after = counter();
+            if self.periods.load(Ordering::Acquire) != periods {
+                continue;
+            }
+            // If nothing was pending, the counter hadn't reloaded yet when
+            // first read. Otherwise it had, before the second read.
+            let (periods, count) = if reloaded {
+                (periods as u64 + 1, count_after)
+            } else {
+                (periods as u64, count_before)
+            };
+            return periods * period + period.saturating_sub(count as u64);
+        }
+    }
+}
+
+impl Default for Uptime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[macro_export]
 macro_rules! impl_timer {
     ($(
@@ -98,12 +161,41 @@ macro_rules! impl_timer {
 
                 /// Set timeout using system ticks
                 pub fn set_timeout_ticks(&mut self, ticks: u32) {
+                    Self::uptime().set_period_ticks(ticks);
                     self.registers.reload().write(|w| unsafe {
                         w.value().bits(ticks)
                     });
                 }
             }
 
+            // uptime
+            impl $TIMERX {
+                fn uptime() -> &'static $crate::timer::Uptime {
+                    static UPTIME: $crate::timer::Uptime = $crate::timer::Uptime::new();
+                    &UPTIME
+                }
+
+                /// Count one tick ISR period toward the uptime. The shared
+                /// `DefaultHandler` calls this whenever the timeout is pending.
+                pub fn tick_uptime(&self) {
+                    Self::uptime().tick();
+                }
+
+                /// Time since `enable_tick_isr`. Safe to call from the main loop
+                /// (without a critical section) or the ISR. Only meaningful
+                /// while the tick ISR is running, using the timer for delays
+                /// or benchmarks in the meantime breaks it. Needs `clk`, so
+                /// does not work on a `summon`ed timer.
+                pub fn uptime_us(&self) -> u64 {
+                    let ticks = Self::uptime().ticks(|| self.counter(), || self.is_pending());
+                    ticks / (self.clk as u64 / 1_000_000)
+                }
+
+                pub fn uptime_ms(&self) -> u64 {
+                    self.uptime_us() / 1_000
+                }
+            }
+
             // interrupts
             impl $TIMERX {
 
@@ -170,3 +262,48 @@ macro_rules! impl_timer {
         )+
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    #[test]
+    fn test_uptime_ticks() {
+        let uptime = Uptime::new();
+        uptime.set_period_ticks(1000);
+        assert_eq!(uptime.ticks(|| 1000, || false), 0);
+        assert_eq!(uptime.ticks(|| 250, || false), 750);
+        for _ in 0..3 {
+            uptime.tick();
+        }
+        assert_eq!(uptime.ticks(|| 400, || false), 3600);
+
+        // Counter reloaded but the ISR hasn't run yet.
+        let reads = Cell::new(0);
+        let counter = || {
+            reads.set(reads.get() + 1);
+            if reads.get() == 1 { 2 } else { 995 }
+        };
+        assert_eq!(uptime.ticks(counter, || true), 4005);
+
+        // The ISR runs partway through a read, which is retried.
+        reads.set(0);
+        let counter = || {
+            reads.set(reads.get() + 1);
+            if reads.get() == 1 {
+                uptime.tick();
+            }
+            500
+        };
+        assert_eq!(uptime.ticks(counter, || false), 4500);
+        assert!(reads.get() > 2);
+
+        // Many periods don't overflow the tick count.
+        for _ in 0..100_000 {
+            uptime.tick();
+        }
+        uptime.set_period_ticks(u32::MAX);
+        assert_eq!(uptime.ticks(|| u32::MAX, || false), 100_004 * u32::MAX as u64);
+    }
+}
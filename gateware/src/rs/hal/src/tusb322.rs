@@ -9,6 +9,10 @@ use embedded_hal::i2c::Operation;
 
 const TUSB322_ADDR: u8 = 0x47;
 
+/// Default time the attached state must hold before `attached_stable`
+/// reports it.
+pub const ATTACH_DEBOUNCE_MS: u64 = 200;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TUSB322Mode {
     DrpFromSnk,
@@ -30,6 +34,15 @@ pub enum CableDirection {
     CC2,
 }
 
+/// Which CC line the cable is connected on, or none if nothing is attached
+/// (the CABLE_DIR bit is meaningless until then).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CableOrientation {
+    Cc1,
+    Cc2,
+    NotConnected,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum CurrentModeAdvertise {
     Default,
@@ -78,11 +91,23 @@ pub struct ConnectionStatusControl {
 
 pub struct TUSB322Driver<I2C> {
     i2c: I2C,
+    /// The attached state must be stable for this long before
+    /// `attached_stable` reports a change.
+    pub debounce_ms: u64,
+    attached: bool,
+    raw: bool,
+    raw_since_ms: u64,
 }
 
 impl<I2C: I2c> TUSB322Driver<I2C> {
     pub fn new(i2c: I2C) -> Self {
-        Self { i2c }
+        Self {
+            i2c,
+            debounce_ms: ATTACH_DEBOUNCE_MS,
+            attached: false,
+            raw: false,
+            raw_since_ms: 0,
+        }
     }
 
     fn read_register(&mut self, reg: u8) -> Result<u8, I2C::Error> {
@@ -182,6 +207,57 @@ impl<I2C: I2c> TUSB322Driver<I2C> {
         })
     }
 
+    /// Read the connection status, and acknowledge INTERRUPT_STATUS if it
+    /// was set (i.e. the attached state changed since the last clear).
+    pub fn read_and_clear_interrupts(&mut self) -> Result<ConnectionStatusControl, I2C::Error> {
+        let status = self.read_connection_status_control()?;
+        if status.interrupt_status {
+            // Write 1 to clear. Only the low 3 bits are writable otherwise,
+            // so write those back as they were.
+            let reg = 0x10 | (status.drp_duty_cycle << 1) | (status.disable_ufp_accessory as u8);
+            self.write_register(0x09, reg)?;
+        }
+        Ok(status)
+    }
+
+    /// Whether we are attached as a sink (see `read_vbus_detect`), debounced
+    /// so a bouncing CC line doesn't flap the PHY connection. Returns the
+    /// new state once it has held for `debounce_ms`, otherwise None. Failed
+    /// reads are ignored.
+    pub fn attached_stable(&mut self, now_ms: u64) -> Option<bool> {
+        if let Ok(status) = self.read_and_clear_interrupts() {
+            let attached = status.attached_state == AttachedState::AttachedSnk;
+            // A pending interrupt with no change in state means we missed
+            // a detach and reattach between polls, which is also a bounce.
+            if attached != self.raw || status.interrupt_status {
+                self.raw = attached;
+                self.raw_since_ms = now_ms;
+            }
+        }
+        if self.raw == self.attached || now_ms.saturating_sub(self.raw_since_ms) < self.debounce_ms {
+            return None;
+        }
+        self.attached = self.raw;
+        Some(self.attached)
+    }
+
+    pub fn read_cable_orientation(&mut self) -> Result<CableOrientation, I2C::Error> {
+        let status = self.read_connection_status_control()?;
+        Ok(match (status.attached_state, status.cable_dir) {
+            (AttachedState::NotAttached, _) => CableOrientation::NotConnected,
+            (_, CableDirection::CC1) => CableOrientation::Cc1,
+            (_, CableDirection::CC2) => CableOrientation::Cc2,
+        })
+    }
+
+    /// The TUSB322I has no VBUS detect input, so this reports whether it
+    /// attached as a sink, which it only does once the upstream port has
+    /// advertised itself on CC (and so should be supplying VBUS).
+    pub fn read_vbus_detect(&mut self) -> Result<bool, I2C::Error> {
+        let status = self.read_connection_status_control()?;
+        Ok(status.attached_state == AttachedState::AttachedSnk)
+    }
+
     pub fn disable_term(&mut self) -> Result<(), I2C::Error> {
         self.write_register(0x0A, 0x01)
     }
@@ -206,3 +282,132 @@ impl<I2C: I2c> TUSB322Driver<I2C> {
         self.write_register(0x0A, 0x08)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::i2c::{ErrorType, Operation};
+
+    /// TUSB322 register file behind an I2C bus.
+    struct FakeTusb322 {
+        regs: [u8; 256],
+        /// Register writes, in order.
+        writes: Vec<(u8, u8)>,
+    }
+
+    impl ErrorType for FakeTusb322 {
+        type Error = core::convert::Infallible;
+    }
+
+    impl I2c for FakeTusb322 {
+        fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>])
+            -> Result<(), Self::Error> {
+            assert_eq!(address, TUSB322_ADDR);
+            let mut reg = 0usize;
+            for op in operations {
+                match op {
+                    Operation::Write(bytes) => {
+                        reg = bytes[0] as usize;
+                        for (i, b) in bytes[1..].iter().enumerate() {
+                            self.writes.push(((reg + i) as u8, *b));
+                            if reg + i == 0x09 {
+                                // INTERRUPT_STATUS is write 1 to clear, and
+                                // only the low 3 bits are otherwise writable.
+                                let r = &mut self.regs[0x09];
+                                *r = (*r & 0xf8 & !(*b & 0x10)) | (*b & 0x07);
+                            } else {
+                                self.regs[reg + i] = *b;
+                            }
+                        }
+                    }
+                    Operation::Read(buffer) => {
+                        let n = buffer.len();
+                        buffer.copy_from_slice(&self.regs[reg..reg + n]);
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn with_status_control(reg: u8) -> TUSB322Driver<FakeTusb322> {
+        let mut regs = [0u8; 256];
+        regs[0x09] = reg;
+        TUSB322Driver::new(FakeTusb322 { regs, writes: Vec::new() })
+    }
+
+    #[test]
+    fn test_cable_orientation() {
+        // ATTACHED_STATE [7:6], CABLE_DIR [5], INTERRUPT_STATUS [4]
+        let cases = [
+            (0b0000_0000, CableOrientation::NotConnected, false),
+            // Stale CABLE_DIR while unattached is ignored.
+            (0b0010_0000, CableOrientation::NotConnected, false),
+            (0b1000_0000, CableOrientation::Cc1, true),
+            (0b1011_0000, CableOrientation::Cc2, true),
+            (0b0100_0000, CableOrientation::Cc1, false),
+            (0b0110_0000, CableOrientation::Cc2, false),
+            (0b1110_1000, CableOrientation::Cc2, false),
+        ];
+        for (reg, orientation, vbus) in cases {
+            let mut tusb322 = with_status_control(reg);
+            assert_eq!(tusb322.read_cable_orientation().unwrap(), orientation, "reg 0x{:02x}", reg);
+            assert_eq!(tusb322.read_vbus_detect().unwrap(), vbus, "reg 0x{:02x}", reg);
+        }
+    }
+
+    #[test]
+    fn test_clear_interrupts() {
+        // Attached as sink, interrupt pending, DRP_DUTY_CYCLE=2
+        let mut tusb322 = with_status_control(0b1001_0100);
+        let status = tusb322.read_and_clear_interrupts().unwrap();
+        assert!(status.interrupt_status);
+        assert_eq!(status.attached_state, AttachedState::AttachedSnk);
+        // Writable bits are preserved.
+        assert_eq!(tusb322.i2c.writes, [(0x09, 0b0001_0100)]);
+        assert_eq!(tusb322.i2c.regs[0x09], 0b1000_0100);
+
+        // Nothing to clear, nothing written.
+        let status = tusb322.read_and_clear_interrupts().unwrap();
+        assert!(!status.interrupt_status);
+        assert_eq!(tusb322.i2c.writes.len(), 1);
+    }
+
+    #[test]
+    fn test_attach_debounce() {
+        const SNK: u8 = 0b1000_0000;
+        const IRQ: u8 = 0b0001_0000;
+        let mut tusb322 = with_status_control(0);
+        let mut events = Vec::new();
+        // Cable going in: CC bounces for a while, then settles attached.
+        let trace = [
+            (10, SNK | IRQ), (20, IRQ), (30, SNK | IRQ), (50, SNK),
+            (100, IRQ), (120, SNK | IRQ), (200, SNK), (319, SNK),
+            (320, SNK), (400, SNK),
+        ];
+        for (now_ms, reg) in trace {
+            tusb322.i2c.regs[0x09] = reg;
+            if let Some(attached) = tusb322.attached_stable(now_ms) {
+                events.push((now_ms, attached));
+            }
+        }
+        // Reported once, 200ms after the last edge at 120ms.
+        assert_eq!(events, [(320, true)]);
+
+        // A detach and reattach between polls is only seen as an interrupt,
+        // but still restarts the window.
+        tusb322.i2c.regs[0x09] = SNK | IRQ;
+        assert_eq!(tusb322.attached_stable(500), None);
+        tusb322.i2c.regs[0x09] = 0;
+        assert_eq!(tusb322.attached_stable(600), None);
+        assert_eq!(tusb322.attached_stable(799), None);
+        assert_eq!(tusb322.attached_stable(800), Some(false));
+        assert_eq!(tusb322.attached_stable(900), None);
+
+        // Custom window.
+        tusb322.debounce_ms = 10;
+        tusb322.i2c.regs[0x09] = SNK;
+        assert_eq!(tusb322.attached_stable(1000), None);
+        assert_eq!(tusb322.attached_stable(1010), Some(true));
+    }
+}
@@ -0,0 +1,120 @@
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+/// Software watchdog, fed by the main loop and checked from the timer ISR.
+///
+/// There is no hardware watchdog, so this only catches hangs outside the
+/// ISR (e.g. busy-waiting on a peripheral from the main loop, or the
+/// `loop {}` after a panic). What to do on expiry is up to the caller of
+/// `check`, usually asking the `REBOOT0` peripheral to return to the
+/// bootloader.
+///
+/// Like `timer::Uptime`, only plain loads and stores are used. A `feed`
+/// racing with `check` can be lost, which just means the next one counts.
+pub struct Watchdog {
+    /// 0 while stopped.
+    timeout_ms: AtomicU32,
+    fed: AtomicBool,
+    last_fed_ms: AtomicU32,
+}
+
+impl Watchdog {
+    pub const fn new() -> Self {
+        Self {
+            timeout_ms: AtomicU32::new(0),
+            fed: AtomicBool::new(false),
+            last_fed_ms: AtomicU32::new(0),
+        }
+    }
+
+    /// Expire if not fed for `timeout_ms`, counting from the next `check`.
+    pub fn start(&self, timeout_ms: u32) {
+        self.fed.store(true, Ordering::Relaxed);
+        self.timeout_ms.store(timeout_ms.max(1), Ordering::Release);
+    }
+
+    pub fn stop(&self) {
+        self.timeout_ms.store(0, Ordering::Release);
+    }
+
+    pub fn running(&self) -> bool {
+        self.timeout_ms.load(Ordering::Acquire) != 0
+    }
+
+    /// Safe to call from the main loop without a critical section.
+    pub fn feed(&self) {
+        self.fed.store(true, Ordering::Release);
+    }
+
+    /// Call from the timer ISR with the current uptime. Returns true (once)
+    /// if the watchdog was not fed for its timeout, and stops it.
+    pub fn check(&self, now_ms: u32) -> bool {
+        let timeout_ms = self.timeout_ms.load(Ordering::Acquire);
+        if timeout_ms == 0 {
+            return false;
+        }
+        if self.fed.load(Ordering::Acquire) {
+            self.fed.store(false, Ordering::Relaxed);
+            self.last_fed_ms.store(now_ms, Ordering::Relaxed);
+            return false;
+        }
+        if now_ms.wrapping_sub(self.last_fed_ms.load(Ordering::Relaxed)) >= timeout_ms {
+            self.stop();
+            return true;
+        }
+        false
+    }
+}
+
+impl Default for Watchdog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog() {
+        let wd = Watchdog::new();
+        // Stopped by default.
+        for now in (0..10_000).step_by(5) {
+            assert!(!wd.check(now));
+        }
+
+        // Fed every frame, never expires.
+        wd.start(500);
+        assert!(wd.running());
+        for now in (10_000..20_000).step_by(5) {
+            if now % 20 == 0 {
+                wd.feed();
+            }
+            assert!(!wd.check(now));
+        }
+
+        // Main loop hangs: expires a timeout after the last feed seen by
+        // `check`, exactly once, and stops.
+        let expired: Vec<u32> = (20_000..22_000).step_by(5).filter(|now| wd.check(*now)).collect();
+        assert_eq!(expired, [19_980 + 500]);
+        assert!(!wd.running());
+
+        // Restarting counts from the first check, whatever happened before.
+        wd.start(100);
+        assert!(!wd.check(50_000));
+        assert!(!wd.check(50_095));
+        assert!(wd.check(50_100));
+
+        // Uptime in ms wraps after ~49 days.
+        wd.start(100);
+        assert!(!wd.check(u32::MAX - 10));
+        assert!(!wd.check(50));
+        assert!(wd.check(89));
+
+        // Stopped while hung.
+        wd.start(100);
+        assert!(!wd.check(0));
+        wd.stop();
+        assert!(!wd.check(1_000));
+    }
+}
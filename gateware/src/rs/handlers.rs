@@ -19,6 +19,8 @@ use core::cell::RefCell;
 use core::fmt::Write;
 
 use tiliqua_lib::logger::WriteLogger;
+use tiliqua_lib::bootinfo::{PanicInfoLite, PANIC_OFFSET};
+use tiliqua_hal::watchdog::Watchdog;
 
 use irq::{handler, scoped_interrupts};
 use amaranth_soc_isr::return_as_is;
@@ -38,6 +40,27 @@ static LOGGER: WriteLogger<Serial0> = WriteLogger {
     level: Level::Trace,
 };
 
+/// Main loops that `start` this feed it once per frame. Checked by the
+/// timer ISR below, which returns to the bootloader if it expires.
+pub static WATCHDOG: Watchdog = Watchdog::new();
+
+/// Long enough for anything a main loop does between feeds, like saving
+/// options to flash.
+pub const WATCHDOG_TIMEOUT_MS: u32 = 2000;
+
+// Leave a breadcrumb (unless a panic already did, which is likely why the
+// main loop stopped) and ask the gateware to reboot into the bootloader,
+// which shows it. No logging, the main loop may be hung holding the logger.
+fn watchdog_expired() {
+    let addr = pac::constants::BOOTINFO_BASE + PANIC_OFFSET;
+    if unsafe { PanicInfoLite::from_addr(addr) }.is_none() {
+        let breadcrumb = PanicInfoLite::new(file!(), line!(), format_args!("watchdog: main loop hung"));
+        unsafe { breadcrumb.to_addr(addr) };
+    }
+    let peripherals = unsafe { pac::Peripherals::steal() };
+    peripherals.REBOOT0.request().write(|w| w.request().bit(true));
+}
+
 pub fn logger_init(writer: Serial0) {
     LOGGER.writer.replace(Some(writer));
     unsafe {
@@ -62,6 +85,10 @@ fn panic(panic_info: &PanicInfo) -> ! {
         error!("panic(): no location information");
     }
     error!("{:?}", panic_info.message());
+    // Leave a breadcrumb for the bootloader to show after a reboot.
+    let (file, line) = panic_info.location().map_or(("", 0), |l| (l.file(), l.line()));
+    let breadcrumb = PanicInfoLite::new(file, line, format_args!("{}", panic_info.message()));
+    unsafe { breadcrumb.to_addr(pac::constants::BOOTINFO_BASE + PANIC_OFFSET) };
     loop {}
 }
 
@@ -77,6 +104,11 @@ fn default_isr_handler() {
     let sysclk = pac::clock::sysclk();
     let timer = Timer0::new(peripherals.TIMER0, sysclk);
     if timer.is_pending() {
+        timer.tick_uptime();
+        // Before the application's handler, in case that is what hangs.
+        if WATCHDOG.check(timer.uptime_ms() as u32) {
+            watchdog_expired();
+        }
         unsafe { TIMER0(); }
         timer.clear_pending();
     }
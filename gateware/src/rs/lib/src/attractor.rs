@@ -0,0 +1,73 @@
+use micromath::F32Ext;
+
+/// Peter de Jong strange attractor, iterated one point at a time:
+///
+///   x' = sin(a*y) - cos(b*x)
+///   y' = sin(c*x) - cos(d*y)
+///
+/// Every point lies inside [-2, 2] on both axes, so the output can
+/// be scaled directly onto the vectorscope deflection range.
+#[derive(Copy, Clone)]
+pub struct DeJong {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    x: f32,
+    y: f32,
+}
+
+impl DeJong {
+    pub const DEFAULT_A: f32 =  1.4;
+    pub const DEFAULT_B: f32 = -2.3;
+    pub const DEFAULT_C: f32 =  2.4;
+    pub const DEFAULT_D: f32 = -2.1;
+
+    pub fn new(a: f32, b: f32, c: f32, d: f32) -> Self {
+        DeJong { a, b, c, d, x: 0.0, y: 0.0 }
+    }
+
+    pub fn set_params(&mut self, a: f32, b: f32, c: f32, d: f32) {
+        self.a = a;
+        self.b = b;
+        self.c = c;
+        self.d = d;
+    }
+
+    /// Advance the attractor by one iteration, returning the new point.
+    pub fn step(&mut self) -> (f32, f32) {
+        let x = F32Ext::sin(self.a * self.y) - F32Ext::cos(self.b * self.x);
+        let y = F32Ext::sin(self.c * self.x) - F32Ext::cos(self.d * self.y);
+        self.x = x;
+        self.y = y;
+        (x, y)
+    }
+}
+
+impl Default for DeJong {
+    fn default() -> Self {
+        DeJong::new(DeJong::DEFAULT_A, DeJong::DEFAULT_B,
+                    DeJong::DEFAULT_C, DeJong::DEFAULT_D)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dejong_bounded() {
+        let mut attractor = DeJong::default();
+        let (mut x_min, mut x_max) = (0.0f32, 0.0f32);
+        for _ in 0..100_000 {
+            let (x, y) = attractor.step();
+            assert!(x.is_finite() && y.is_finite());
+            // Small margin for the sin/cos approximations.
+            assert!(x.abs() <= 2.01 && y.abs() <= 2.01, "escaped: ({}, {})", x, y);
+            x_min = x_min.min(x);
+            x_max = x_max.max(x);
+        }
+        // Sanity check that it didn't collapse to a fixed point.
+        assert!(x_max - x_min > 1.0);
+    }
+}
@@ -1,33 +1,370 @@
 use tiliqua_hal::dma_framebuffer::DVIModeline;
-use tiliqua_manifest::BitstreamManifest;
+use tiliqua_manifest::{BitstreamManifest, Feature, BITSTREAM_TAG_LEN, REQUIRES_MAX_N};
 use serde_derive::{Serialize, Deserialize};
 use crc::{Crc, CRC_32_BZIP2};
+use heapless::{String, Vec};
 
 const BOOTINFO_MAX_SIZE: usize = 1024;
 const CRC_ALGORITHM: Crc<u32> = Crc::<u32>::new(&CRC_32_BZIP2);
 
+/// Version of the `BootInfoExt` record that follows the `BootInfo` fields.
+const BOOTINFO_EXT_VERSION: u8 = 4;
+
+/// The panic breadcrumb lives at this offset from the `bootinfo` address,
+/// in the last KiB of the 4KiB reserved at the end of PSRAM. It is outside
+/// the `BootInfo` record, so a panicking bitstream never overwrites that.
+pub const PANIC_OFFSET: usize = 3072;
+const PANIC_MAX_SIZE: usize = 128;
+
+pub const PANIC_REASON_LEN: usize = 48;
+
 /// Information shared from Tiliqua bootloader to SoC bitstreams.
 /// This is placed in PSRAM at a known address.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct BootInfo {
+    /// Manifest fields added since are `#[serde(skip)]`, and carried in
+    /// `BootInfoExt` instead.
     pub manifest: BitstreamManifest,
     pub modeline: DVIModeline,
+    /// Panic left behind by the bitstream that ran before this one.
+    ///
+    /// Bitstreams built before this field existed stop reading after
+    /// `modeline`, so it is not serialized with the fields above, but in
+    /// a separate `BootInfoExt` record that follows them.
+    #[serde(skip)]
+    pub last_panic: Option<PanicInfoLite>,
+    /// Clocks the bootloader programmed into the external PLL for this
+    /// bitstream. None if the manifest has no `external_pll_config`, or
+    /// the bootloader is too old to say (also in `BootInfoExt`).
+    #[serde(skip)]
+    pub clocks: Option<ClockInfo>,
+}
+
+/// Frequencies achieved by the external PLL, which may be slightly off
+/// from those requested (and so from `CLOCK_AUDIO_HZ` or the modeline).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClockInfo {
+    /// Audio clock (PLL clk0) in Hz.
+    pub audio_hz: u32,
+    /// Pixel clock (PLL clk1) in Hz, None if it is disabled.
+    pub pixel_hz: Option<u32>,
+}
+
+/// Fields added to `BootInfo` after its layout was fixed, with a CRC of
+/// their own. New fields go at the end, and bump `BOOTINFO_EXT_VERSION`.
+#[derive(Serialize, Deserialize)]
+struct BootInfoExt {
+    version: u8,
+    last_panic: Option<PanicInfoLite>,
+    clocks: Option<ClockInfo>,
+    min_bootloader_sha: Option<String<BITSTREAM_TAG_LEN>>,
+    requires: Vec<Feature, REQUIRES_MAX_N>,
+    built_unix: Option<u32>,
+}
+
+/// `BootInfoExt` as written by bootloaders before `built_unix` was added.
+#[derive(Deserialize)]
+struct BootInfoExtV3 {
+    version: u8,
+    last_panic: Option<PanicInfoLite>,
+    clocks: Option<ClockInfo>,
+    min_bootloader_sha: Option<String<BITSTREAM_TAG_LEN>>,
+    requires: Vec<Feature, REQUIRES_MAX_N>,
+}
+
+/// `BootInfoExt` as written by bootloaders before the manifest fields
+/// were added.
+#[derive(Deserialize)]
+struct BootInfoExtV2 {
+    version: u8,
+    last_panic: Option<PanicInfoLite>,
+    clocks: Option<ClockInfo>,
+}
+
+/// `BootInfoExt` as written by bootloaders before `clocks` was added.
+#[derive(Deserialize)]
+struct BootInfoExtV1 {
+    version: u8,
+    last_panic: Option<PanicInfoLite>,
 }
 
 impl BootInfo {
     /// Serialize BootInfo to memory at the given address in PSRAM.
     /// This is intended to be only used by the bootloader bitstream.
     pub unsafe fn to_addr(&self, addr: usize) -> Option<usize> {
-        let digest = CRC_ALGORITHM.digest();
         let buffer = core::slice::from_raw_parts_mut(addr as *mut u8, BOOTINFO_MAX_SIZE);
-        postcard::to_slice_crc32(self, buffer, digest).ok().map(|slice| slice.len())
+        let n = postcard::to_slice_crc32(self, buffer, CRC_ALGORITHM.digest()).ok()?.len();
+        let ext = BootInfoExt {
+            version: BOOTINFO_EXT_VERSION,
+            last_panic: self.last_panic.clone(),
+            clocks: self.clocks.clone(),
+            min_bootloader_sha: self.manifest.min_bootloader_sha.clone(),
+            requires: self.manifest.requires.clone(),
+            built_unix: self.manifest.built_unix,
+        };
+        postcard::to_slice_crc32(&ext, &mut buffer[n..], CRC_ALGORITHM.digest()).ok()
+            .map(|slice| n + slice.len())
     }
 
     /// Deserialize BootInfo from memory at the given address in PSRAM.
     /// This is intended to only be used by application bitstreams.
     pub unsafe fn from_addr(addr: usize) -> Option<BootInfo> {
-        let digest = CRC_ALGORITHM.digest();
         let buffer = core::slice::from_raw_parts(addr as *const u8, BOOTINFO_MAX_SIZE);
-        postcard::from_bytes_crc32(buffer, digest).ok()
+        let (mut bootinfo, rest): (BootInfo, _) =
+            postcard::take_from_bytes_crc32(buffer, CRC_ALGORITHM.digest()).ok()?;
+        // Missing (older bootloader) or unknown extensions are ignored.
+        if let Ok(ext) = postcard::from_bytes_crc32::<BootInfoExt>(rest, CRC_ALGORITHM.digest()) {
+            if ext.version == BOOTINFO_EXT_VERSION {
+                bootinfo.last_panic = ext.last_panic;
+                bootinfo.clocks = ext.clocks;
+                bootinfo.manifest.min_bootloader_sha = ext.min_bootloader_sha;
+                bootinfo.manifest.requires = ext.requires;
+                bootinfo.manifest.built_unix = ext.built_unix;
+            }
+        } else if let Ok(ext) = postcard::from_bytes_crc32::<BootInfoExtV3>(rest, CRC_ALGORITHM.digest()) {
+            if ext.version == 3 {
+                bootinfo.last_panic = ext.last_panic;
+                bootinfo.clocks = ext.clocks;
+                bootinfo.manifest.min_bootloader_sha = ext.min_bootloader_sha;
+                bootinfo.manifest.requires = ext.requires;
+            }
+        } else if let Ok(ext) = postcard::from_bytes_crc32::<BootInfoExtV2>(rest, CRC_ALGORITHM.digest()) {
+            if ext.version == 2 {
+                bootinfo.last_panic = ext.last_panic;
+                bootinfo.clocks = ext.clocks;
+            }
+        } else if let Ok(ext) = postcard::from_bytes_crc32::<BootInfoExtV1>(rest, CRC_ALGORITHM.digest()) {
+            if ext.version == 1 {
+                bootinfo.last_panic = ext.last_panic;
+            }
+        }
+        Some(bootinfo)
+    }
+}
+
+/// Where and why a bitstream panicked, small enough to be written from
+/// the panic handler and read back by the bootloader after a reboot.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PanicInfoLite {
+    /// FNV-1a hash of the source file path, which is too long to keep.
+    pub file_hash: u32,
+    pub line: u32,
+    /// Start of the panic message, truncated to fit.
+    pub reason: String<PANIC_REASON_LEN>,
+}
+
+/// `core::fmt::Write` adapter that drops whatever doesn't fit, instead of
+/// failing the whole `write!` like `heapless::String` does.
+struct Truncate<'a>(&'a mut String<PANIC_REASON_LEN>);
+
+impl core::fmt::Write for Truncate<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            if self.0.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn fnv1a(s: &str) -> u32 {
+    s.bytes().fold(0x811c9dc5u32, |h, b| (h ^ b as u32).wrapping_mul(0x01000193))
+}
+
+impl PanicInfoLite {
+    pub fn new(file: &str, line: u32, reason: core::fmt::Arguments) -> Self {
+        let mut s = String::new();
+        core::fmt::write(&mut Truncate(&mut s), reason).ok();
+        Self { file_hash: fnv1a(file), line, reason: s }
+    }
+
+    /// Store the breadcrumb at `addr` (the `bootinfo` address plus
+    /// `PANIC_OFFSET`), where it survives a reboot into the bootloader.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must point to `PANIC_MAX_SIZE` bytes of memory that nothing
+    /// else is using, like all of the `*_addr` functions here.
+    pub unsafe fn to_addr(&self, addr: usize) -> Option<usize> {
+        let buffer = core::slice::from_raw_parts_mut(addr as *mut u8, PANIC_MAX_SIZE);
+        postcard::to_slice_crc32(self, buffer, CRC_ALGORITHM.digest()).ok().map(|slice| slice.len())
+    }
+
+    /// Breadcrumb stored at `addr`, if there is one. After a power cycle
+    /// PSRAM holds garbage, which fails the CRC.
+    ///
+    /// # Safety
+    ///
+    /// See `to_addr`.
+    pub unsafe fn from_addr(addr: usize) -> Option<PanicInfoLite> {
+        let buffer = core::slice::from_raw_parts(addr as *const u8, PANIC_MAX_SIZE);
+        postcard::from_bytes_crc32(buffer, CRC_ALGORITHM.digest()).ok()
+    }
+
+    /// Like `from_addr`, but also invalidate the breadcrumb, so that it
+    /// is only reported once.
+    ///
+    /// # Safety
+    ///
+    /// See `to_addr`.
+    pub unsafe fn take_from_addr(addr: usize) -> Option<PanicInfoLite> {
+        let panic = Self::from_addr(addr);
+        core::slice::from_raw_parts_mut(addr as *mut u8, PANIC_MAX_SIZE).fill(0);
+        panic
+    }
+}
+
+impl core::fmt::Display for PanicInfoLite {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "panic at {:08x}:{}: {}", self.file_hash, self.line, self.reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiliqua_manifest::{BitstreamHelp, ExternalPLLConfig, MemoryRegion};
+
+    fn bootinfo() -> BootInfo {
+        let json = br#"{"hw_rev":5,"name":"XBEAM","tag":"v1.1.0","regions":[],
+                        "help":null,"external_pll_config":null,
+                        "min_bootloader_sha":null,"magic":4277010159}"#;
+        BootInfo {
+            manifest: BitstreamManifest::from_slice(json).unwrap(),
+            modeline: DVIModeline::default(),
+            last_panic: None,
+            clocks: None,
+        }
+    }
+
+    #[test]
+    fn test_panic_breadcrumb() {
+        let mut buf = [0xAAu8; 4096];
+        let addr = buf.as_mut_ptr() as usize + PANIC_OFFSET;
+        assert_eq!(unsafe { PanicInfoLite::from_addr(addr) }, None);
+
+        let long = "a".repeat(100);
+        let panic = PanicInfoLite::new("src/main.rs", 42, format_args!("index {} out of {}", 7, long));
+        assert_eq!(panic.file_hash, fnv1a("src/main.rs"));
+        assert_eq!(panic.reason.len(), PANIC_REASON_LEN);
+        assert!(panic.reason.starts_with("index 7 out of aaa"));
+
+        unsafe { panic.to_addr(addr).unwrap() };
+        assert_eq!(unsafe { PanicInfoLite::take_from_addr(addr) }, Some(panic));
+        assert_eq!(unsafe { PanicInfoLite::from_addr(addr) }, None);
+    }
+
+    #[test]
+    fn test_bootinfo_ext_compat() {
+        let mut buf = [0u8; 4096];
+        let addr = buf.as_mut_ptr() as usize;
+        let mut info = bootinfo();
+        info.last_panic = Some(PanicInfoLite::new("src/lib.rs", 7, format_args!("oops")));
+        let n = unsafe { info.to_addr(addr) }.unwrap();
+        let read = unsafe { BootInfo::from_addr(addr) }.unwrap();
+        assert_eq!(read.last_panic, info.last_panic);
+        assert_eq!(read.manifest.name, info.manifest.name);
+
+        // Old bootloader: only the original fields, no extension record.
+        buf[..n].fill(0);
+        let old = postcard::to_slice_crc32(&info, &mut buf, CRC_ALGORITHM.digest()).unwrap().len();
+        let read = unsafe { BootInfo::from_addr(addr) }.unwrap();
+        assert_eq!(read.last_panic, None);
+
+        // Old bitstream: reads the original fields from a new bootloader
+        // and ignores the extension record behind them. Manifest fields
+        // added since must not change the layout it expects.
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct ManifestV0 {
+            hw_rev: u32,
+            name: String<32>,
+            tag: String<8>,
+            regions: Vec<MemoryRegion, 5>,
+            help: Option<BitstreamHelp>,
+            external_pll_config: Option<ExternalPLLConfig>,
+            magic: u32,
+        }
+        #[derive(Deserialize)]
+        struct BootInfoV0 {
+            manifest: ManifestV0,
+            modeline: DVIModeline,
+        }
+        info.manifest.requires = Vec::from_slice(&[Feature::ExternalPll]).unwrap();
+        info.manifest.built_unix = Some(1_792_195_200);
+        unsafe { info.to_addr(addr) }.unwrap();
+        let v0: BootInfoV0 = postcard::from_bytes_crc32(&buf, CRC_ALGORITHM.digest()).unwrap();
+        assert_eq!(v0.manifest.name, info.manifest.name);
+        assert_eq!(v0.manifest.magic, info.manifest.magic);
+        assert_eq!(v0.modeline, info.modeline);
+        assert!(n > old);
+    }
+
+    #[test]
+    fn test_bootinfo_manifest_ext() {
+        let mut buf = [0u8; 4096];
+        let addr = buf.as_mut_ptr() as usize;
+        let mut info = bootinfo();
+        info.manifest.min_bootloader_sha = Some(String::try_from("1a2b3c4d").unwrap());
+        info.manifest.requires = Vec::from_slice(&[Feature::ExternalPll, Feature::DynamicModeline]).unwrap();
+        info.manifest.built_unix = Some(1_792_195_200);
+        unsafe { info.to_addr(addr) }.unwrap();
+        let read = unsafe { BootInfo::from_addr(addr) }.unwrap();
+        assert_eq!(read.manifest.min_bootloader_sha, info.manifest.min_bootloader_sha);
+        assert_eq!(read.manifest.requires, info.manifest.requires);
+        assert_eq!(read.manifest.built_unix, info.manifest.built_unix);
+
+        // Bootloader from before the manifest fields: clocks are still
+        // handed on, the manifest fields are left empty.
+        #[derive(Serialize)]
+        struct BootInfoExtV2Out {
+            version: u8,
+            last_panic: Option<PanicInfoLite>,
+            clocks: Option<ClockInfo>,
+        }
+        buf.fill(0);
+        let clocks = Some(ClockInfo { audio_hz: 12_288_000, pixel_hz: None });
+        let n = postcard::to_slice_crc32(&info, &mut buf, CRC_ALGORITHM.digest()).unwrap().len();
+        let ext = BootInfoExtV2Out { version: 2, last_panic: None, clocks: clocks.clone() };
+        postcard::to_slice_crc32(&ext, &mut buf[n..], CRC_ALGORITHM.digest()).unwrap();
+        let read = unsafe { BootInfo::from_addr(addr) }.unwrap();
+        assert_eq!(read.clocks, clocks);
+        assert_eq!(read.manifest.min_bootloader_sha, None);
+        assert!(read.manifest.requires.is_empty());
+        assert_eq!(read.manifest.built_unix, None);
+    }
+
+    #[test]
+    fn test_bootinfo_clocks() {
+        let mut buf = [0u8; 4096];
+        let addr = buf.as_mut_ptr() as usize;
+        let mut info = bootinfo();
+        info.last_panic = Some(PanicInfoLite::new("src/lib.rs", 7, format_args!("oops")));
+        info.clocks = Some(ClockInfo { audio_hz: 12_288_012, pixel_hz: Some(74_250_000) });
+        unsafe { info.to_addr(addr) }.unwrap();
+        let read = unsafe { BootInfo::from_addr(addr) }.unwrap();
+        assert_eq!(read.clocks, info.clocks);
+        assert_eq!(read.last_panic, info.last_panic);
+
+        // Bootloader from before `clocks`: the panic is still handed on.
+        #[derive(Serialize)]
+        struct BootInfoExtV1Out {
+            version: u8,
+            last_panic: Option<PanicInfoLite>,
+        }
+        buf.fill(0);
+        let n = postcard::to_slice_crc32(&info, &mut buf, CRC_ALGORITHM.digest()).unwrap().len();
+        let ext = BootInfoExtV1Out { version: 1, last_panic: info.last_panic.clone() };
+        postcard::to_slice_crc32(&ext, &mut buf[n..], CRC_ALGORITHM.digest()).unwrap();
+        let read = unsafe { BootInfo::from_addr(addr) }.unwrap();
+        assert_eq!(read.clocks, None);
+        assert_eq!(read.last_panic, info.last_panic);
+
+        // Bitstream from before `clocks`: the new record fails its CRC
+        // check, so it is ignored rather than misread.
+        unsafe { info.to_addr(addr) }.unwrap();
+        let (_, rest): (BootInfo, _) = postcard::take_from_bytes_crc32(&buf, CRC_ALGORITHM.digest()).unwrap();
+        assert!(postcard::from_bytes_crc32::<BootInfoExtV1>(rest, CRC_ALGORITHM.digest()).is_err());
     }
 }
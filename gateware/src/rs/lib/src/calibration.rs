@@ -6,6 +6,9 @@ use crate::eeprominfo::{EepromCalibration, EepromManager};
 use heapless::String;
 use core::fmt::Write;
 
+// Calibrated samples are ASQ, where 1.0 is this many counts.
+const ASQ_COUNTS: f32 = 32768.0;
+
 #[derive(Debug, PartialEq)]
 pub struct DefaultCalibrationConstants {
     pub adc_scale: f32,
@@ -44,6 +47,64 @@ pub struct TweakableConstants {
     pub dac_zero:  [i16; 4],
 }
 
+/// Writes calibration constants to the audio interface without
+/// busy-waiting on each channel, so the 8 writes can be spread over
+/// several calls to `poll` (e.g. from a timer ISR).
+pub struct CalibrationWriter {
+    constants: [(i32, i32); 8],
+    next: usize,
+    in_flight: bool,
+}
+
+impl Default for CalibrationWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CalibrationWriter {
+    pub fn new() -> Self {
+        CalibrationWriter {
+            constants: [(0, 0); 8],
+            next: 8,
+            in_flight: false,
+        }
+    }
+
+    /// Queue `constants` (already scaled for the hardware `f_bits`) to be
+    /// written. Writing restarts from the first channel only if they differ
+    /// from what was last queued.
+    pub fn update(&mut self, constants: [(i32, i32); 8]) {
+        if constants != self.constants {
+            self.constants = constants;
+            self.next = 0;
+        }
+    }
+
+    /// Start as many writes as possible without waiting. Returns true once
+    /// all queued constants have been committed.
+    pub fn poll<Pmod>(&mut self, pmod: &mut Pmod) -> bool
+    where
+        Pmod: EurorackPmod
+    {
+        loop {
+            if self.in_flight {
+                if !pmod.calibration_write_done() {
+                    return false;
+                }
+                self.in_flight = false;
+            }
+            if self.next >= self.constants.len() {
+                return true;
+            }
+            let (a, b) = self.constants[self.next];
+            pmod.write_calibration_constant_start(self.next as u8, a, b);
+            self.in_flight = true;
+            self.next += 1;
+        }
+    }
+}
+
 impl DefaultCalibrationConstants {
     pub fn from_array(c: &[f32; 4], fractional_bits: u8) -> Self {
         DefaultCalibrationConstants {
@@ -56,6 +117,39 @@ impl DefaultCalibrationConstants {
     }
 }
 
+/// Least-squares fit of `y = slope*x + intercept`, returned as
+/// `(slope, intercept)`. None if the points don't span more than one `x`.
+fn fit_line<I>(points: I) -> Option<(f32, f32)>
+where
+    I: Iterator<Item = (f32, f32)> + Clone
+{
+    let mut n = 0f32;
+    let mut sum_x = 0f32;
+    let mut sum_y = 0f32;
+    for (x, y) in points.clone() {
+        n += 1.0;
+        sum_x += x;
+        sum_y += y;
+    }
+    if n < 2.0 {
+        return None;
+    }
+    // Work around the means, so large offsets don't eat precision.
+    let mean_x = sum_x / n;
+    let mean_y = sum_y / n;
+    let mut sxx = 0f32;
+    let mut sxy = 0f32;
+    for (x, y) in points {
+        sxx += (x - mean_x) * (x - mean_x);
+        sxy += (x - mean_x) * (y - mean_y);
+    }
+    if sxx <= f32::EPSILON {
+        return None;
+    }
+    let slope = sxy / sxx;
+    Some((slope, mean_y - slope * mean_x))
+}
+
 impl CalibrationConstants {
     fn fixed_to_f32(&self, x: i32) -> f32 {
         let divisor = (1 << self.cal.fractional_bits) as f32;
@@ -86,32 +180,79 @@ impl CalibrationConstants {
         result
     }
 
-    pub fn write_to_pmod<Pmod>(&self, pmod: &mut Pmod)
-    where
-        Pmod: EurorackPmod
-    {
-        let hw_f_bits = pmod.f_bits() as i8;
-        let cal_f_bits = self.cal.fractional_bits as i8;
-        let shift = hw_f_bits - cal_f_bits;
-        if shift != 0 {
-            info!("audio/calibration: calibration (f_bits={}) != hardware (f_bits={}), shift={}",
-                 cal_f_bits, hw_f_bits, shift);
+    /// Fit new `(scale, zero)` constants for channel `ch` from a set of
+    /// `(reference, measured)` sample pairs, in ASQ counts, captured
+    /// with the current constants applied. Channel indices follow
+    /// `write_calibration_constant`, i.e. 0..4 are ADCs and 4..8 are DACs.
+    ///
+    /// - ADC: `reference` is the (known) voltage on the input jack, and
+    ///   `measured` is the calibrated ADC reading.
+    /// - DAC: `reference` is the sample sent to the DAC, and `measured`
+    ///   is what a calibrated ADC reads back in loopback.
+    ///
+    /// Returns None if the samples can't determine a fit, for example if
+    /// they were all captured at the same level.
+    pub fn fit_channel(&self, ch: usize, samples: &[(i16, i16)]) -> Option<(f32, f32)> {
+        let points = samples.iter().map(|(r, m)| (*r as f32 / ASQ_COUNTS, *m as f32 / ASQ_COUNTS));
+        if ch < 4 {
+            // reference = a*measured + b, and measured = scale*raw + zero,
+            // so the raw -> reference mapping is a*scale*raw + (a*zero + b).
+            let (a, b) = fit_line(points.map(|(r, m)| (m, r)))?;
+            let scale = self.fixed_to_f32(self.cal.adc_scale[ch]);
+            let zero = self.fixed_to_f32(self.cal.adc_zero[ch]);
+            Some((a * scale, a * zero + b))
+        } else {
+            // measured = a*reference + b. Scale the DAC constants so
+            // that reading back the same sample becomes the identity.
+            let (a, b) = fit_line(points)?;
+            if a.abs() <= f32::EPSILON {
+                return None;
+            }
+            let scale = self.fixed_to_f32(self.cal.dac_scale[ch-4]);
+            let zero = self.fixed_to_f32(self.cal.dac_zero[ch-4]);
+            Some((scale / a, zero - b * scale / a))
         }
+    }
+
+    /// Overwrite the constants of one channel (indexed as in `fit_channel`).
+    pub fn set_channel(&mut self, ch: usize, scale: f32, zero: f32) {
+        let scale = self.f32_to_fixed(scale);
+        let zero = self.f32_to_fixed(zero);
+        if ch < 4 {
+            self.cal.adc_scale[ch] = scale;
+            self.cal.adc_zero[ch] = zero;
+        } else {
+            self.cal.dac_scale[ch-4] = scale;
+            self.cal.dac_zero[ch-4] = zero;
+        }
+    }
+
+    /// Constants for each channel (indexed as in `fit_channel`), rescaled
+    /// to the calibration fixed-point format of the hardware (`hw_f_bits`).
+    pub fn pmod_constants(&self, hw_f_bits: u8) -> [(i32, i32); 8] {
+        let shift = hw_f_bits as i8 - self.cal.fractional_bits as i8;
         let rescale = |v: i32| -> i32 {
             if shift > 0 { v << shift } else { v >> (-shift) }
         };
-
+        let mut result = [(0, 0); 8];
         for ch in 0..4usize {
-            pmod.write_calibration_constant(
-                ch as u8,
-                rescale(self.cal.adc_scale[ch]),
-                rescale(self.cal.adc_zero[ch]),
-            );
-            pmod.write_calibration_constant(
-                (ch+4) as u8,
-                rescale(self.cal.dac_scale[ch]),
-                rescale(self.cal.dac_zero[ch]),
-            );
+            result[ch] = (rescale(self.cal.adc_scale[ch]), rescale(self.cal.adc_zero[ch]));
+            result[ch+4] = (rescale(self.cal.dac_scale[ch]), rescale(self.cal.dac_zero[ch]));
+        }
+        result
+    }
+
+    pub fn write_to_pmod<Pmod>(&self, pmod: &mut Pmod)
+    where
+        Pmod: EurorackPmod
+    {
+        let hw_f_bits = pmod.f_bits();
+        if hw_f_bits != self.cal.fractional_bits {
+            info!("audio/calibration: calibration (f_bits={}) != hardware (f_bits={})",
+                 self.cal.fractional_bits, hw_f_bits);
+        }
+        for (ch, (a, b)) in self.pmod_constants(hw_f_bits).iter().enumerate() {
+            pmod.write_calibration_constant(ch as u8, *a, *b);
         }
     }
 
@@ -215,6 +356,133 @@ impl CalibrationConstants {
     }
 }
 
+/// Steps of the calibration [`Wizard`], in the order they are visited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WizardStep {
+    PatchLoopback,
+    MeasureZero,
+    MeasurePositive,
+    MeasureNegative,
+    /// Solved, waiting for the user to confirm saving.
+    Save,
+    /// Solving failed, usually because a cable is missing.
+    Failed,
+    Done,
+}
+
+/// DAC levels (in volts) measured by the [`Wizard`], in step order.
+pub const WIZARD_VOLTS: [i32; 3] = [0, 5, -5];
+
+/// Guided loopback calibration of the DACs. Each encoder press (`press`)
+/// either moves on to the next instruction, or records the input readings
+/// for the level the outputs are currently driven at (`stimulus`). After
+/// the last level, DAC constants are fit from all of them (as `fit_channel`)
+/// and the user is asked whether to save them.
+///
+/// The inputs are used as the reference, so they should be calibrated
+/// first (i.e. constants loaded from the EEPROM, or tweaked by hand).
+pub struct Wizard {
+    step: WizardStep,
+    samples: [[(i16, i16); WIZARD_VOLTS.len()]; 4],
+    solved: Option<CalibrationConstants>,
+}
+
+impl Default for Wizard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Wizard {
+    pub fn new() -> Self {
+        Wizard {
+            step: WizardStep::PatchLoopback,
+            samples: [[(0, 0); WIZARD_VOLTS.len()]; 4],
+            solved: None,
+        }
+    }
+
+    pub fn step(&self) -> WizardStep {
+        self.step
+    }
+
+    /// Constants found by the last successful solve.
+    pub fn solved(&self) -> Option<&CalibrationConstants> {
+        self.solved.as_ref()
+    }
+
+    /// Index into `WIZARD_VOLTS` of a measurement step.
+    fn level(&self) -> Option<usize> {
+        match self.step {
+            WizardStep::MeasureZero     => Some(0),
+            WizardStep::MeasurePositive => Some(1),
+            WizardStep::MeasureNegative => Some(2),
+            _ => None,
+        }
+    }
+
+    /// Sample all outputs should be driven at during this step.
+    pub fn stimulus(&self, counts_per_v: i32) -> i32 {
+        self.level().map_or(0, |n| WIZARD_VOLTS[n] * counts_per_v)
+    }
+
+    /// Step counter and instruction shown for this step.
+    pub fn prompt(&self) -> (&'static str, &'static str) {
+        match self.step {
+            WizardStep::PatchLoopback   => ("1/5", "patch out0-3 to in0-3, press to start"),
+            WizardStep::MeasureZero     => ("2/5", "outputs at 0V, press to measure"),
+            WizardStep::MeasurePositive => ("3/5", "outputs at +5V, press to measure"),
+            WizardStep::MeasureNegative => ("4/5", "outputs at -5V, press to measure"),
+            WizardStep::Save            => ("5/5", "solved! press to save"),
+            WizardStep::Failed          => ("5/5", "fit failed, check cables. press to retry"),
+            WizardStep::Done            => ("5/5", "saved. press to start again"),
+        }
+    }
+
+    /// Advance on an encoder press. `readings` are the (averaged) input
+    /// samples, only used by measurement steps. Returns the constants to
+    /// save when the user confirms them.
+    pub fn press(&mut self, constants: &CalibrationConstants, counts_per_v: i32,
+                 readings: [i32; 4]) -> Option<&CalibrationConstants> {
+        if let Some(n) = self.level() {
+            let stimulus = self.stimulus(counts_per_v);
+            for (ch_samples, reading) in self.samples.iter_mut().zip(readings) {
+                ch_samples[n] = (stimulus as i16, reading as i16);
+            }
+        }
+        self.step = match self.step {
+            WizardStep::PatchLoopback   => WizardStep::MeasureZero,
+            WizardStep::MeasureZero     => WizardStep::MeasurePositive,
+            WizardStep::MeasurePositive => WizardStep::MeasureNegative,
+            WizardStep::MeasureNegative => {
+                self.solved = self.solve(constants);
+                if self.solved.is_some() { WizardStep::Save } else { WizardStep::Failed }
+            }
+            WizardStep::Save => {
+                self.step = WizardStep::Done;
+                return self.solved.as_ref();
+            }
+            WizardStep::Failed | WizardStep::Done => WizardStep::PatchLoopback,
+        };
+        None
+    }
+
+    fn solve(&self, constants: &CalibrationConstants) -> Option<CalibrationConstants> {
+        let mut fitted = CalibrationConstants { cal: constants.cal.clone() };
+        for (ch, ch_samples) in self.samples.iter().enumerate() {
+            let (scale, zero) = constants.fit_channel(ch+4, ch_samples)?;
+            info!("calibration/wizard: dac{} scale={:.4} zero={:.4}", ch, scale, zero);
+            // An unpatched input reads back (almost) nothing, which would
+            // otherwise fit an enormous scale.
+            if !(0.5..2.0).contains(&scale) {
+                return None;
+            }
+            fitted.set_channel(ch+4, scale, zero);
+        }
+        Some(fitted)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -244,4 +512,193 @@ mod tests {
             assert!(tol(test.cal.dac_zero[ch], converted.cal.dac_zero[ch], 1));
         }
     }
+
+    #[test]
+    pub fn least_squares_fit() {
+        let defaults_r33 = DefaultCalibrationConstants {
+            adc_scale: -1.158,
+            adc_zero:  0.008,
+            dac_scale: 0.97,
+            dac_zero:  0.03,
+            fractional_bits: 15,
+        };
+        let mut rng = fastrand::Rng::with_seed(1234);
+        let counts_per_v = 4000f32;
+        let volts = [-5.0f32, -2.5, 0.0, 2.5, 5.0];
+        let asq = |x: f32| (x * ASQ_COUNTS) as i16;
+        let tol = |x: f32, y: f32| (x-y).abs() < 2e-3;
+        let constants = CalibrationConstants::from_defaults(&defaults_r33);
+
+        // ADC channel whose ideal constants differ from the defaults.
+        let (true_scale, true_zero) = (-1.131f32, 0.021f32);
+        let mut samples = [(0i16, 0i16); 5*8];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let reference = volts[i % 5] * counts_per_v / ASQ_COUNTS;
+            let raw = (reference - true_zero) / true_scale;
+            let measured = defaults_r33.adc_scale * raw + defaults_r33.adc_zero;
+            let noise = rng.i16(-8..=8);
+            *sample = (asq(reference), asq(measured) + noise);
+        }
+        let (scale, zero) = constants.fit_channel(1, &samples).unwrap();
+        assert!(tol(scale, true_scale), "adc scale: {}", scale);
+        assert!(tol(zero, true_zero), "adc zero: {}", zero);
+
+        // DAC channel with some analog gain and offset after the DAC.
+        let (gain, offset) = (1.04f32, -0.015f32);
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let reference = volts[i % 5] * counts_per_v / ASQ_COUNTS;
+            let dac = defaults_r33.dac_scale * reference + defaults_r33.dac_zero;
+            let measured = gain * dac + offset;
+            let noise = rng.i16(-8..=8);
+            *sample = (asq(reference), asq(measured) + noise);
+        }
+        let (scale, zero) = constants.fit_channel(6, &samples).unwrap();
+        assert!(tol(scale, 1.0 / gain), "dac scale: {}", scale);
+        assert!(tol(zero, -offset / gain), "dac zero: {}", zero);
+
+        let mut fitted = CalibrationConstants::from_defaults(&defaults_r33);
+        fitted.set_channel(6, scale, zero);
+        assert_eq!(fitted.cal.dac_scale[2], fitted.f32_to_fixed(scale));
+        assert_eq!(fitted.cal.adc_scale, constants.cal.adc_scale);
+
+        // A single level can't determine a scale.
+        assert!(constants.fit_channel(0, &[(0, 10), (0, 12)]).is_none());
+        assert!(constants.fit_channel(4, &[]).is_none());
+    }
+
+    #[test]
+    pub fn wizard() {
+        let defaults = DefaultCalibrationConstants::from_array(&[-1.158, 0.008, 0.97, 0.03], 15);
+        let constants = CalibrationConstants::from_defaults(&defaults);
+        let counts_per_v = 4000;
+        let tol = |x: f32, y: f32| (x-y).abs() < 2e-3;
+        // Each output has its own gain and offset after the DAC.
+        let gains = [1.04f32, 0.98, 1.0, 1.1];
+        let offsets = [-0.015f32, 0.01, 0.0, 0.02];
+        let readback = |stimulus: i32, ch: usize| -> i32 {
+            let reference = stimulus as f32 / ASQ_COUNTS;
+            let dac = defaults.dac_scale * reference + defaults.dac_zero;
+            ((gains[ch] * dac + offsets[ch]) * ASQ_COUNTS) as i32
+        };
+
+        let mut wizard = Wizard::new();
+        assert_eq!(wizard.step(), WizardStep::PatchLoopback);
+        assert_eq!(wizard.stimulus(counts_per_v), 0);
+        let steps = [
+            (WizardStep::MeasureZero, 0),
+            (WizardStep::MeasurePositive, 5*counts_per_v),
+            (WizardStep::MeasureNegative, -5*counts_per_v),
+            (WizardStep::Save, 0),
+        ];
+        for (step, stimulus) in steps {
+            // Readings are only recorded on measurement steps.
+            let s = wizard.stimulus(counts_per_v);
+            let readings = core::array::from_fn(|ch| readback(s, ch));
+            assert!(wizard.press(&constants, counts_per_v, readings).is_none());
+            assert_eq!(wizard.step(), step);
+            assert_eq!(wizard.stimulus(counts_per_v), stimulus);
+        }
+
+        let solved = wizard.solved().unwrap();
+        for ch in 0..4 {
+            let scale = solved.fixed_to_f32(solved.cal.dac_scale[ch]);
+            let zero = solved.fixed_to_f32(solved.cal.dac_zero[ch]);
+            assert!(tol(scale, 1.0 / gains[ch]), "dac{} scale: {}", ch, scale);
+            assert!(tol(zero, -offsets[ch] / gains[ch]), "dac{} zero: {}", ch, zero);
+        }
+        // ADC constants are the reference, so they are untouched.
+        assert_eq!(solved.cal.adc_scale, constants.cal.adc_scale);
+        assert_eq!(solved.cal.adc_zero, constants.cal.adc_zero);
+
+        // Confirming hands back the solved constants, once.
+        let expected = solved.cal.clone();
+        assert_eq!(wizard.press(&constants, counts_per_v, [0; 4]).unwrap().cal, expected);
+        assert_eq!(wizard.step(), WizardStep::Done);
+        assert!(wizard.press(&constants, counts_per_v, [0; 4]).is_none());
+        assert_eq!(wizard.step(), WizardStep::PatchLoopback);
+
+        // An unpatched output reads back nothing on one input.
+        for _ in 0..4 {
+            let s = wizard.stimulus(counts_per_v);
+            let readings = core::array::from_fn(|ch| if ch == 2 { 0 } else { readback(s, ch) });
+            wizard.press(&constants, counts_per_v, readings);
+        }
+        assert_eq!(wizard.step(), WizardStep::Failed);
+        assert!(wizard.solved().is_none());
+        wizard.press(&constants, counts_per_v, [0; 4]);
+        assert_eq!(wizard.step(), WizardStep::PatchLoopback);
+    }
+
+    // Calibration writes complete after `latency` polls of the done flag.
+    struct FakePmod {
+        latency: u32,
+        busy: core::cell::Cell<u32>,
+        written: [Option<(i32, i32)>; 8],
+    }
+
+    impl FakePmod {
+        fn new(latency: u32) -> Self {
+            FakePmod { latency, busy: core::cell::Cell::new(0), written: [None; 8] }
+        }
+    }
+
+    impl EurorackPmod for FakePmod {
+        fn jack(&self) -> u8 { 0 }
+        fn touch_err(&self) -> u8 { 0 }
+        fn touch(&self) -> [u8; 8] { [0; 8] }
+        fn sample_i(&self) -> [i32; 4] { [0; 4] }
+        fn led_set_manual(&mut self, _index: usize, _value: i8) {}
+        fn led_set_auto(&mut self, _index: usize) {}
+        fn led_all_auto(&mut self) {}
+        fn led_all_manual(&mut self) {}
+        fn write_calibration_constant_start(&mut self, ch: u8, a: i32, b: i32) {
+            assert_eq!(self.busy.get(), 0, "write started before the last one was done");
+            self.written[ch as usize] = Some((a, b));
+            self.busy.set(self.latency);
+        }
+        fn calibration_write_done(&self) -> bool {
+            let busy = self.busy.get();
+            self.busy.set(busy.saturating_sub(1));
+            busy == 0
+        }
+        fn mute(&mut self, _mute: bool) {}
+        fn mute_ramp(&mut self, _target: bool, _step: i16) {}
+        fn is_mute_ramp_done(&self) -> bool { true }
+        fn hard_reset(&mut self) {}
+        fn set_aclk_unstable(&mut self) {}
+        fn f_bits(&self) -> u8 { 17 }
+        fn counts_per_v(&self) -> i32 { 4000 }
+    }
+
+    #[test]
+    pub fn pipelined_write() {
+        let defaults = DefaultCalibrationConstants::from_array(&[-1.158, 0.008, 0.97, 0.03], 15);
+        let mut constants = CalibrationConstants::from_defaults(&defaults);
+        constants.cal.dac_zero[3] += 100;
+        let expected = constants.pmod_constants(17);
+        // Rescaled from 15 to 17 fractional bits.
+        assert_eq!(expected[7].1, constants.cal.dac_zero[3] << 2);
+
+        let mut pmod = FakePmod::new(3);
+        let mut writer = CalibrationWriter::new();
+        assert!(writer.poll(&mut pmod));
+        writer.update(expected);
+        let mut calls = 1;
+        while !writer.poll(&mut pmod) {
+            calls += 1;
+        }
+        // Never blocks, so each write takes several polls to complete.
+        assert_eq!(calls, 8 * 3 + 1);
+        assert_eq!(pmod.written, expected.map(Some));
+
+        // Queuing the same constants again doesn't rewrite them.
+        pmod.written = [None; 8];
+        writer.update(expected);
+        assert!(writer.poll(&mut pmod));
+        assert_eq!(pmod.written, [None; 8]);
+
+        // The blocking write gives the same result.
+        constants.write_to_pmod(&mut pmod);
+        assert_eq!(pmod.written, expected.map(Some));
+    }
 }
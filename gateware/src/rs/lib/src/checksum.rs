@@ -0,0 +1,36 @@
+use crc::{Crc, CRC_32_BZIP2};
+
+/// CRC32 (BZIP2) of `size` bytes starting at `ptr`, read as (volatile)
+/// little-endian words. Used to check memory-mapped regions (SPI flash,
+/// PSRAM) against the CRCs in a bitstream manifest.
+///
+/// # Safety
+///
+/// `ptr` must be valid for reads of `size.div_ceil(4)` words.
+pub unsafe fn crc32_bzip2_region(ptr: *const u32, size: usize) -> u32 {
+    let crc_bzip2 = Crc::<u32>::new(&CRC_32_BZIP2);
+    let mut digest = crc_bzip2.digest();
+    for i in 0..size.div_ceil(4) {
+        let d = unsafe { ptr.add(i).read_volatile() }.to_le_bytes();
+        let n_bytes = (size - i*4).min(4);
+        digest.update(&d[..n_bytes]);
+    }
+    digest.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_bzip2_region() {
+        let words: Vec<u32> = (0..4u32).map(|i| 0x04030201u32.wrapping_mul(i + 1)).collect();
+        let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        let crc_bzip2 = Crc::<u32>::new(&CRC_32_BZIP2);
+        // Including sizes that end part way through a word.
+        for size in 0..=bytes.len() {
+            assert_eq!(unsafe { crc32_bzip2_region(words.as_ptr(), size) },
+                       crc_bzip2.checksum(&bytes[..size]), "size: {}", size);
+        }
+    }
+}
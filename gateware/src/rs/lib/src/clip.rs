@@ -0,0 +1,268 @@
+// A `DrawTarget` wrapper that discards everything outside a clipping
+// rectangle before it reaches the wrapped target.
+//
+// The accelerated line and blit paths of the framebuffer take raw
+// coordinates, and off-screen line endpoints may stall the Bresenham
+// hardware. Wrapping the framebuffer with its active area means lines are
+// clipped to valid endpoints, blits that cross the edge fall back to
+// (clipped) pixel plots, and stray pixels are dropped.
+
+use tiliqua_hal::embedded_graphics::{
+    prelude::*,
+    primitives::Rectangle,
+};
+
+pub struct ClippedDrawTarget<'a, D> {
+    inner: &'a mut D,
+    area: Rectangle,
+}
+
+impl<'a, D> ClippedDrawTarget<'a, D>
+where
+    D: DrawTarget,
+{
+    pub fn new(inner: &'a mut D, area: Rectangle) -> Self {
+        Self { inner, area }
+    }
+
+    /// Clip to the bounding box of `inner`, i.e. the active area for a framebuffer.
+    pub fn active_area(inner: &'a mut D) -> Self {
+        let area = inner.bounding_box();
+        Self::new(inner, area)
+    }
+}
+
+// Cohen-Sutherland outcode bits.
+const LEFT: u8   = 0b0001;
+const RIGHT: u8  = 0b0010;
+const TOP: u8    = 0b0100;
+const BOTTOM: u8 = 0b1000;
+
+/// Clip the line `p0`-`p1` to `area` (inclusive of its edge pixels), or `None`
+/// if no part of it is inside. Clipped endpoints are rounded onto the pixel
+/// grid, so they are always inside `area` but may sit up to one pixel off the
+/// ideal line.
+pub fn clip_line(area: &Rectangle, p0: Point, p1: Point) -> Option<(Point, Point)> {
+    let br = area.bottom_right()?;
+    let (x_min, y_min) = (area.top_left.x as i64, area.top_left.y as i64);
+    let (x_max, y_max) = (br.x as i64, br.y as i64);
+    let outcode = |x: i64, y: i64| {
+        (if x < x_min { LEFT } else if x > x_max { RIGHT } else { 0 }) |
+        (if y < y_min { TOP } else if y > y_max { BOTTOM } else { 0 })
+    };
+    let (mut x0, mut y0) = (p0.x as i64, p0.y as i64);
+    let (mut x1, mut y1) = (p1.x as i64, p1.y as i64);
+    let mut code0 = outcode(x0, y0);
+    let mut code1 = outcode(x1, y1);
+    // Each pass moves one endpoint onto an edge, so 4 passes are enough.
+    for _ in 0..4 {
+        if code0 | code1 == 0 {
+            return Some((Point::new(x0 as i32, y0 as i32), Point::new(x1 as i32, y1 as i32)));
+        }
+        if code0 & code1 != 0 {
+            return None;
+        }
+        let code = if code0 != 0 { code0 } else { code1 };
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let (x, y) = if code & TOP != 0 {
+            (x0 + dx * (y_min - y0) / dy, y_min)
+        } else if code & BOTTOM != 0 {
+            (x0 + dx * (y_max - y0) / dy, y_max)
+        } else if code & LEFT != 0 {
+            (x_min, y0 + dy * (x_min - x0) / dx)
+        } else {
+            (x_max, y0 + dy * (x_max - x0) / dx)
+        };
+        if code == code0 {
+            (x0, y0) = (x, y);
+            code0 = outcode(x0, y0);
+        } else {
+            (x1, y1) = (x, y);
+            code1 = outcode(x1, y1);
+        }
+    }
+    if code0 | code1 == 0 {
+        Some((Point::new(x0 as i32, y0 as i32), Point::new(x1 as i32, y1 as i32)))
+    } else {
+        // Only grazes a corner, after rounding.
+        None
+    }
+}
+
+impl<D> Dimensions for ClippedDrawTarget<'_, D>
+where
+    D: DrawTarget,
+{
+    fn bounding_box(&self) -> Rectangle {
+        self.area
+    }
+}
+
+impl<D> DrawTarget for ClippedDrawTarget<'_, D>
+where
+    D: DrawTarget,
+{
+    type Color = D::Color;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let area = self.area;
+        self.inner.draw_iter(pixels.into_iter().filter(|Pixel(p, _)| area.contains(*p)))
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.area);
+        if area.is_zero_sized() {
+            return Ok(());
+        }
+        self.inner.fill_solid(&area, color)
+    }
+
+    fn upload_spritesheet(&mut self, key: u32, pixels: &[u8], width: u32, height: u32, bpp: u8) -> bool {
+        self.inner.upload_spritesheet(key, pixels, width, height, bpp)
+    }
+
+    /// Blits entirely inside the clipping area are accelerated, any others
+    /// fall back to pixel plots through `draw_iter`.
+    fn blit_sprite(&mut self, key: u32, src_x: u32, src_y: u32, width: u32, height: u32,
+                   dst_x: i32, dst_y: i32, color: Self::Color) -> bool {
+        let dst = Rectangle::new(Point::new(dst_x, dst_y), Size::new(width, height));
+        if self.area.intersection(&dst) != dst {
+            return false;
+        }
+        self.inner.blit_sprite(key, src_x, src_y, width, height, dst_x, dst_y, color)
+    }
+
+    fn draw_line_solid(&mut self, start_x: i32, start_y: i32, end_x: i32, end_y: i32,
+                       stroke_width: u32, color: Self::Color) -> bool {
+        if stroke_width != 1 {
+            return false;
+        }
+        match clip_line(&self.area, Point::new(start_x, start_y), Point::new(end_x, end_y)) {
+            Some((p0, p1)) => self.inner.draw_line_solid(p0.x, p0.y, p1.x, p1.y, stroke_width, color),
+            // Entirely outside, nothing to draw.
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::HI8;
+
+    const W: u32 = 640;
+    const H: u32 = 480;
+
+    /// Records accelerated lines and plotted pixels.
+    #[derive(Default)]
+    struct RecordingDisplay {
+        lines: Vec<(Point, Point)>,
+        pixels: Vec<Point>,
+        fills: Vec<Rectangle>,
+    }
+
+    impl DrawTarget for RecordingDisplay {
+        type Color = HI8;
+        type Error = core::convert::Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            self.pixels.extend(pixels.into_iter().map(|Pixel(p, _)| p));
+            Ok(())
+        }
+
+        fn fill_solid(&mut self, area: &Rectangle, _color: Self::Color) -> Result<(), Self::Error> {
+            self.fills.push(*area);
+            Ok(())
+        }
+
+        fn draw_line_solid(&mut self, start_x: i32, start_y: i32, end_x: i32, end_y: i32,
+                           _stroke_width: u32, _color: Self::Color) -> bool {
+            self.lines.push((Point::new(start_x, start_y), Point::new(end_x, end_y)));
+            true
+        }
+    }
+
+    impl OriginDimensions for RecordingDisplay {
+        fn size(&self) -> Size {
+            Size::new(W, H)
+        }
+    }
+
+    // Distance of `p` from the infinite line through `a` and `b`.
+    fn distance(a: Point, b: Point, p: Point) -> f32 {
+        let (dx, dy) = (b.x as f32 - a.x as f32, b.y as f32 - a.y as f32);
+        let cross = dx * (p.y as f32 - a.y as f32) - dy * (p.x as f32 - a.x as f32);
+        cross.abs() / (dx * dx + dy * dy).sqrt()
+    }
+
+    #[test]
+    fn test_clip_lines() {
+        let mut disp = RecordingDisplay::default();
+        let mut clipped = ClippedDrawTarget::active_area(&mut disp);
+        let area = clipped.bounding_box();
+        let lines = [
+            // Entirely inside, untouched.
+            (Point::new(10, 10), Point::new(600, 400)),
+            // One endpoint off each edge.
+            (Point::new(-100, 50), Point::new(300, 200)),
+            (Point::new(300, 200), Point::new(2000, 250)),
+            (Point::new(320, -1), Point::new(100, 479)),
+            (Point::new(5, 5), Point::new(7, 60000)),
+            // Both endpoints off, crossing the whole screen diagonally.
+            (Point::new(-300, -300), Point::new(1000, 1000)),
+            (Point::new(i32::MIN / 2, 240), Point::new(i32::MAX / 2, 240)),
+            // Steep and shallow lines off opposite corners.
+            (Point::new(-10, 500), Point::new(650, -20)),
+        ];
+        for (p0, p1) in lines {
+            assert!(clipped.draw_line_solid(p0.x, p0.y, p1.x, p1.y, 1, HI8::WHITE));
+        }
+        // Entirely outside: handled, but nothing reaches the hardware.
+        assert!(clipped.draw_line_solid(-10, -10, -1, 400, 1, HI8::WHITE));
+        assert!(clipped.draw_line_solid(700, 0, 900, 900, 1, HI8::WHITE));
+        assert!(clipped.draw_line_solid(-50, 20, 20, -50, 1, HI8::WHITE));
+        // Thick lines are left to the software fallback.
+        assert!(!clipped.draw_line_solid(0, 0, 10, 10, 2, HI8::WHITE));
+
+        assert_eq!(disp.lines.len(), lines.len());
+        assert_eq!(disp.lines[0], lines[0]);
+        for ((c0, c1), (p0, p1)) in disp.lines.iter().zip(lines.iter()) {
+            assert!(area.contains(*c0) && area.contains(*c1), "{:?} {:?}", c0, c1);
+            // Still on the original line, within rounding.
+            assert!(distance(*p0, *p1, *c0) <= 1.0, "{:?} off {:?}-{:?}", c0, p0, p1);
+            assert!(distance(*p0, *p1, *c1) <= 1.0, "{:?} off {:?}-{:?}", c1, p0, p1);
+        }
+        // Clipping happens on the edge the line leaves through.
+        assert_eq!(disp.lines[1].0, Point::new(0, 87));
+        assert_eq!(disp.lines[2].1, Point::new(639, 209));
+        assert_eq!(disp.lines[5], (Point::new(0, 0), Point::new(479, 479)));
+        assert_eq!(disp.lines[6], (Point::new(0, 240), Point::new(639, 240)));
+    }
+
+    #[test]
+    fn test_clip_pixels_and_fills() {
+        let mut disp = RecordingDisplay::default();
+        let area = Rectangle::new(Point::new(100, 100), Size::new(50, 50));
+        let mut clipped = ClippedDrawTarget::new(&mut disp, area);
+        clipped.draw_iter([
+            Pixel(Point::new(99, 120), HI8::WHITE),
+            Pixel(Point::new(100, 100), HI8::WHITE),
+            Pixel(Point::new(149, 149), HI8::WHITE),
+            Pixel(Point::new(150, 120), HI8::WHITE),
+            Pixel(Point::new(-1, -1), HI8::WHITE),
+        ]).ok();
+        clipped.fill_solid(&Rectangle::new(Point::new(0, 0), Size::new(120, 1000)), HI8::WHITE).ok();
+        clipped.fill_solid(&Rectangle::new(Point::new(0, 0), Size::new(100, 100)), HI8::WHITE).ok();
+        // Blits crossing the edge go through (clipped) pixel plots instead.
+        assert!(!clipped.blit_sprite(0, 0, 0, 9, 15, 145, 120, HI8::WHITE));
+        assert_eq!(disp.pixels, [Point::new(100, 100), Point::new(149, 149)]);
+        assert_eq!(disp.fills, [Rectangle::new(Point::new(100, 100), Size::new(20, 50))]);
+    }
+}
@@ -43,6 +43,21 @@ impl HI8 {
         Self::new(self.hue(), intensity)
     }
 
+    /// Move intensity toward `other`'s by `t`/255 (rounded), keeping this hue.
+    pub fn lerp(self, other: Self, t: u8) -> Self {
+        let a = self.intensity() as i32;
+        let b = other.intensity() as i32;
+        let scaled = a * 255 + (b - a) * t as i32;
+        self.with_intensity(((scaled + 127) / 255) as u8)
+    }
+
+    /// Scale intensity by `num`/`den` (rounded), saturating at full intensity.
+    /// `den` must be nonzero.
+    pub fn scale_intensity(self, num: u8, den: u8) -> Self {
+        let scaled = (self.intensity() as u32 * num as u32 + den as u32 / 2) / den as u32;
+        self.with_intensity(scaled.min(15) as u8)
+    }
+
     // Some standard colors with the default palette.
     // A non-default palette will invalidate this.
     pub const BLACK: Self = Self::new(0, 0);
@@ -83,3 +98,34 @@ impl From<HI8> for u8 {
         color.to_raw()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lerp() {
+        let a = HI8::new(3, 0);
+        let b = HI8::new(3, 15);
+        assert_eq!(a.lerp(b, 0), a);
+        assert_eq!(a.lerp(b, 255), b);
+        assert_eq!(b.lerp(a, 0), b);
+        assert_eq!(b.lerp(a, 255), a);
+        // 7.5 rounds up, just under it rounds down.
+        assert_eq!(a.lerp(b, 128).intensity(), 8);
+        assert_eq!(a.lerp(b, 127).intensity(), 7);
+        assert_eq!(b.lerp(a, 128).intensity(), 7);
+        // Hue is kept from `self`.
+        assert_eq!(HI8::new(9, 4).lerp(HI8::new(1, 12), 255), HI8::new(9, 12));
+    }
+
+    #[test]
+    fn test_scale_intensity() {
+        let c = HI8::new(5, 10);
+        assert_eq!(c.scale_intensity(1, 2), HI8::new(5, 5));
+        assert_eq!(c.scale_intensity(1, 3).intensity(), 3);
+        assert_eq!(c.scale_intensity(1, 4).intensity(), 3);
+        assert_eq!(c.scale_intensity(0, 1), HI8::new(5, 0));
+        assert_eq!(c.scale_intensity(3, 1), HI8::new(5, 15));
+    }
+}
@@ -0,0 +1,67 @@
+use core::fmt::Write;
+
+/// Decides which startup diagnostics are run.
+///
+/// Slow diagnostics may be skipped once a unit is known-good, unless
+/// `full_test` is set, in which case everything is run regardless.
+#[derive(Clone, Copy)]
+pub struct DiagnosticGate {
+    pub full_test: bool,
+}
+
+impl DiagnosticGate {
+    pub fn new(full_test: bool) -> Self {
+        DiagnosticGate { full_test }
+    }
+
+    /// Run `diagnostic` (which appends to the report `s`) unless it should
+    /// be skipped. Skipped diagnostics are still noted in the report, so a
+    /// missing result is never mistaken for a pass. Returns whether it ran.
+    pub fn run<S, F>(&self, s: &mut S, name: &str, skip: bool, diagnostic: F) -> bool
+    where
+        S: Write,
+        F: FnOnce(&mut S),
+    {
+        if skip && !self.full_test {
+            write!(s, "SKIP: {}\r\n", name).ok();
+            false
+        } else {
+            diagnostic(s);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::String;
+
+    #[test]
+    fn test_skip_diagnostic() {
+        let mut ran = false;
+        let mut s = String::<128>::new();
+        let gate = DiagnosticGate::new(false);
+        assert!(!gate.run(&mut s, "PSRAM memtest", true, |s| {
+            ran = true;
+            write!(s, "PASS: PSRAM memtest\r\n").ok();
+        }));
+        assert!(!ran);
+        assert_eq!(s.as_str(), "SKIP: PSRAM memtest\r\n");
+
+        // Diagnostics that are not skipped run as normal.
+        let mut s = String::<128>::new();
+        assert!(gate.run(&mut s, "PSRAM memtest", false, |s| {
+            write!(s, "PASS: PSRAM memtest\r\n").ok();
+        }));
+        assert_eq!(s.as_str(), "PASS: PSRAM memtest\r\n");
+
+        // Full test mode ignores the skip flags.
+        let mut ran = false;
+        let mut s = String::<128>::new();
+        let gate = DiagnosticGate::new(true);
+        assert!(gate.run(&mut s, "PSRAM memtest", true, |_| ran = true));
+        assert!(ran);
+        assert!(s.is_empty());
+    }
+}
@@ -1,11 +1,14 @@
 use tiliqua_hal::embedded_graphics::{
     primitives::{PrimitiveStyleBuilder, Line, Ellipse, Rectangle, Circle},
     mono_font::{ascii::FONT_9X15, ascii::FONT_9X15_BOLD, MonoTextStyle},
-    text::{Alignment, Text},
+    text::{Alignment, Text, renderer::TextRenderer},
     prelude::*,
 };
 
 use crate::color::HI8;
+use crate::ui::DigitEdit;
+use tiliqua_hal::cy8cmbr3xxx::SensorCounts;
+use crate::meter::{MeterLevel, FLOOR_DBFS};
 
 use opts::Options;
 use crate::logo_coords;
@@ -22,6 +25,7 @@ where
 {
     let font_small_white = MonoTextStyle::new(&FONT_9X15_BOLD, HI8::new(hue, 15));
     let font_small_grey = MonoTextStyle::new(&FONT_9X15, HI8::new(hue, 10));
+    let font_small_disabled = MonoTextStyle::new(&FONT_9X15, HI8::new(hue, 5));
 
     let opts_view = opts.view().options();
 
@@ -53,30 +57,39 @@ where
 
     let vx = vx-2;
 
+    // Options in collapsed groups are skipped, so rows don't match indices.
+    let mut row: usize = 0;
     for (n, opt) in opts_view.iter().enumerate() {
-        let mut font = font_small_grey;
+        if !opts.view().visible(n) {
+            continue;
+        }
+        let vy_row = (vy+vspace*row) as i32;
+        row += 1;
+        let mut font = if opt.enabled() { font_small_grey } else { font_small_disabled };
         if let Some(n_selected) = opts.selected() {
             if n_selected == n {
                 font = font_small_white;
                 if opts.modify() {
                     Text::with_alignment(
                         "<",
-                        Point::new(vx+hspace+2, (vy+vspace*n) as i32),
+                        Point::new(vx+hspace+2, vy_row),
                         font,
                         Alignment::Left,
                     ).draw(d)?;
                 }
             }
         }
+        // Group children are indented under their header.
+        let indent = if opts.view().parent(n).is_some() { 9 } else { 0 };
         Text::with_alignment(
             opt.name(),
-            Point::new(vx+5, (vy+vspace*n) as i32),
+            Point::new(vx+5+indent, vy_row),
             font,
             Alignment::Left,
         ).draw(d)?;
         Text::with_alignment(
             &opt.value(),
-            Point::new(vx+hspace, (vy+vspace*n) as i32),
+            Point::new(vx+hspace, vy_row),
             font,
             Alignment::Right,
         ).draw(d)?;
@@ -87,13 +100,72 @@ where
         .stroke_width(1)
         .build();
     Line::new(Point::new(vx-3, vy as i32 - 10),
-              Point::new(vx-3, (vy - 13 + vspace*opts_view.len()) as i32))
+              Point::new(vx-3, (vy - 13 + vspace*row) as i32))
               .into_styled(stroke)
               .draw(d)?;
 
     Ok(())
 }
 
+/// Digit entry overlay for `ui::DigitEdit`, right-aligned at (x, y). All
+/// digits of the range are drawn (zero padded) so each can be selected.
+pub fn draw_spinbox<D>(d: &mut D, x: u32, y: u32, edit: &DigitEdit, hue: u8) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = HI8>,
+{
+    let font_active = MonoTextStyle::new(&FONT_9X15_BOLD, HI8::new(hue, 15));
+    let font_digit = MonoTextStyle::new(&FONT_9X15, HI8::new(hue, 10));
+    let stroke_active = PrimitiveStyleBuilder::new()
+        .stroke_color(HI8::new(hue, 15))
+        .stroke_width(1)
+        .build();
+    let stroke_box = PrimitiveStyleBuilder::new()
+        .stroke_color(HI8::new(hue, 10))
+        .stroke_width(1)
+        .build();
+
+    let char_w: i32 = 9;
+    let x = x as i32;
+    let y = y as i32;
+    let n_digits = edit.n_digits() as i32;
+
+    let mut magnitude = edit.value().unsigned_abs();
+    for n in 0..n_digits {
+        let c = [b'0' + (magnitude % 10) as u8];
+        magnitude /= 10;
+        let active = n == edit.digit() as i32;
+        let right = x - n*char_w;
+        Text::with_alignment(
+            core::str::from_utf8(&c).unwrap_or("?"),
+            Point::new(right, y),
+            if active { font_active } else { font_digit },
+            Alignment::Right,
+        ).draw(d)?;
+        if active {
+            Line::new(Point::new(right - char_w, y + 3), Point::new(right - 1, y + 3))
+                .into_styled(stroke_active)
+                .draw(d)?;
+        }
+    }
+
+    if edit.value() < 0 {
+        Text::with_alignment(
+            "-",
+            Point::new(x - n_digits*char_w, y),
+            font_digit,
+            Alignment::Right,
+        ).draw(d)?;
+    }
+
+    // Room for the sign either way, so the box doesn't jump around.
+    let width = (n_digits + 1)*char_w + 6;
+    Rectangle::new(Point::new(x - width + 3, y - 14), Size::new(width as u32, 21))
+        .into_styled(stroke_box)
+        .draw(d)?;
+
+    Ok(())
+}
+
 const NOTE_NAMES: [&'static str; 12] = [
     "C",
     "C#",
@@ -265,41 +337,17 @@ pub fn draw_waveform_preview<D>(d: &mut D, x: u32, y: u32, width: u32, height: u
 where
     D: DrawTarget<Color = HI8>,
 {
-    let stroke = PrimitiveStyleBuilder::new()
-        .stroke_color(HI8::new(hue, 15))
-        .stroke_width(1)
-        .build();
-
     let stroke_dim = PrimitiveStyleBuilder::new()
         .stroke_color(HI8::new(hue, 6))
         .stroke_width(1)
         .build();
 
-    let h = height as i32;
-    let half_h = h / 2;
-    let x = x as i32;
-    let y = y as i32;
-    let center_y = y + half_h;
-
     // Baseline
-    Line::new(Point::new(x, center_y), Point::new(x + width as i32, center_y))
+    let center_y = (y + height / 2) as i32;
+    Line::new(Point::new(x as i32, center_y), Point::new((x + width) as i32, center_y))
         .into_styled(stroke_dim).draw(d)?;
 
-    let n = samples.len();
-    if n < 2 {
-        return Ok(());
-    }
-
-    for i in 1..n {
-        let x0 = x + (i - 1) as i32 * width as i32 / (n - 1) as i32;
-        let x1 = x + i as i32 * width as i32 / (n - 1) as i32;
-        let y0 = center_y - (samples[i - 1] as i32 * half_h / 32767);
-        let y1 = center_y - (samples[i] as i32 * half_h / 32767);
-        Line::new(Point::new(x0, y0), Point::new(x1, y1))
-            .into_styled(stroke).draw(d)?;
-    }
-
-    Ok(())
+    draw_trace(d, x, y, width, height, hue, samples, TraceStyle::Lines, Some(1.0f32))
 }
 
 pub fn draw_boot_logo<D>(d: &mut D, sx: i32, sy: i32, ix: u32) -> Result<(), D::Error>
@@ -323,8 +371,12 @@ where
     Ok(())
 }
 
-use tiliqua_hal::dma_framebuffer::DVIModeline;
-pub fn draw_name<D>(d: &mut D, pos_x: u32, pos_y: u32, hue: u8, name: &str, tag: &str, modeline: &DVIModeline) -> Result<(), D::Error>
+use tiliqua_hal::dma_framebuffer::{DVIModeline, Rotate, TestPattern};
+use tiliqua_manifest::BuildDate;
+// Name, then tag and video mode, then the build date if known (`built_unix`
+// from the manifest), so several builds of the same app can be told apart.
+pub fn draw_name<D>(d: &mut D, pos_x: u32, pos_y: u32, hue: u8, name: &str, tag: &str, modeline: &DVIModeline,
+                    built_unix: Option<u32>) -> Result<(), D::Error>
 where
     D: DrawTarget<Color = HI8>,
 {
@@ -358,6 +410,119 @@ where
         Alignment::Center
     ).draw(d)?;
 
+    if let Some(built_unix) = built_unix {
+        let mut built_text: String<32> = String::new();
+        write!(built_text, "built {}", BuildDate::from_unix(built_unix)).ok();
+        Text::with_alignment(
+            &built_text,
+            Point::new(pos_x as i32, (pos_y + 36) as i32),
+            font_small_grey,
+            Alignment::Center
+        ).draw(d)?;
+    }
+
+    Ok(())
+}
+
+/// Offset of a pixel in the framebuffer's (logical) coordinates, for a
+/// pixel `offset` from the text origin as seen on the physical panel. This
+/// undoes the rotation applied by the framebuffer hardware.
+fn panel_to_logical(offset: Point, rotation: Rotate) -> Point {
+    match rotation {
+        Rotate::Normal   => offset,
+        Rotate::Left     => Point::new(offset.y, -offset.x),
+        Rotate::Inverted => Point::new(-offset.x, -offset.y),
+        Rotate::Right    => Point::new(-offset.y, offset.x),
+    }
+}
+
+fn logical_to_panel(offset: Point, rotation: Rotate) -> Point {
+    match rotation {
+        Rotate::Normal   => offset,
+        Rotate::Left     => Point::new(-offset.y, offset.x),
+        Rotate::Inverted => Point::new(-offset.x, -offset.y),
+        Rotate::Right    => Point::new(offset.y, -offset.x),
+    }
+}
+
+/// Text-space view of a rotated framebuffer, see `draw_text_rotated`.
+struct PanelText<'a, D> {
+    target: &'a mut D,
+    origin: Point,
+    rotation: Rotate,
+}
+
+impl<D: DrawTarget<Color = HI8>> Dimensions for PanelText<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        let bb = self.target.bounding_box();
+        let Some(bottom_right) = bb.bottom_right() else {
+            return Rectangle::zero();
+        };
+        Rectangle::with_corners(
+            logical_to_panel(bb.top_left - self.origin, self.rotation),
+            logical_to_panel(bottom_right - self.origin, self.rotation))
+    }
+}
+
+impl<D: DrawTarget<Color = HI8>> DrawTarget for PanelText<'_, D> {
+    type Color = HI8;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (origin, rotation) = (self.origin, self.rotation);
+        self.target.draw_iter(pixels.into_iter().map(|Pixel(p, color)| {
+            Pixel(origin + panel_to_logical(p, rotation), color)
+        }))
+    }
+
+    // The defaults assume rows in text space are rows in the target.
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.draw_iter(area.points().zip(colors).map(|(p, color)| Pixel(p, color)))
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_contiguous(area, core::iter::repeat(color))
+    }
+}
+
+/// Draw `text` so that it reads upright, left to right, on the physical
+/// panel when the framebuffer is rotated by `rotation` (usually the
+/// current modeline's). `origin` is the left end of the baseline, in
+/// framebuffer coordinates like any other draw.
+pub fn draw_text_rotated<D, S>(d: &mut D, origin: Point, text: &str, rotation: Rotate, style: S) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = HI8>,
+    S: TextRenderer<Color = HI8>,
+{
+    let mut panel = PanelText { target: d, origin, rotation };
+    Text::new(text, Point::zero(), style).draw(&mut panel)?;
+    Ok(())
+}
+
+/// Audio underrun status line, highlighted while underruns are still happening.
+pub fn draw_underruns<D>(d: &mut D, pos_x: u32, pos_y: u32, hue: u8, total: u32, per_sec: u32) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = HI8>,
+{
+    let intensity = if per_sec > 0 { 15 } else { 10 };
+    let font = MonoTextStyle::new(&FONT_9X15, HI8::new(hue, intensity));
+
+    let mut underrun_text: String<48> = String::new();
+    write!(underrun_text, "underruns: {} ({}/sec)", total, per_sec).ok();
+
+    Text::with_alignment(
+        &underrun_text,
+        Point::new(pos_x as i32, pos_y as i32),
+        font,
+        Alignment::Center
+    ).draw(d)?;
+
     Ok(())
 }
 
@@ -580,6 +745,131 @@ where
     Ok(())
 }
 
+/// Touch sensor counts for each jack, with the difference count plotted
+/// against that sensor's finger threshold.
+pub fn draw_touch_counts<D>(d: &mut D, x: u32, y: u32, hue: u8,
+                            counts: &[SensorCounts; 8], thresholds: &[u8; 8]) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = HI8>,
+{
+    let font_small_white = MonoTextStyle::new(&FONT_9X15_BOLD, HI8::new(hue, 15));
+    let font_small_grey = MonoTextStyle::new(&FONT_9X15, HI8::new(hue, 10));
+    let stroke_grey = PrimitiveStyleBuilder::new()
+           .stroke_color(HI8::new(hue, 10))
+           .stroke_width(1)
+           .build();
+    let stroke_white = PrimitiveStyleBuilder::new()
+           .stroke_color(HI8::new(hue, 15))
+           .stroke_width(4)
+           .build();
+
+    let spacing = 20;
+    let bar_x = (x + 200) as i32;
+    // Difference counts are plotted up to 2x the highest threshold.
+    let full_scale = 2 * thresholds.iter().copied().max().unwrap_or(0).max(1) as u32;
+    let width = 256;
+    let to_px = |count: u32| (count.min(full_scale) * width / full_scale) as i32;
+
+    Text::with_alignment(
+        "jack   raw  base  diff",
+        Point::new(x as i32, y as i32),
+        font_small_white,
+        Alignment::Left
+    ).draw(d)?;
+
+    for (jack, c) in counts.iter().enumerate() {
+        let row_y = (y + (jack as u32 + 1) * spacing) as i32;
+        let mut text: String<32> = String::new();
+        write!(text, "{:4} {:5} {:5} {:5}", jack, c.raw, c.baseline, c.diff).ok();
+        Text::with_alignment(
+            &text,
+            Point::new(x as i32, row_y),
+            font_small_grey,
+            Alignment::Left
+        ).draw(d)?;
+
+        let bar_y = row_y - 5;
+        Line::new(Point::new(bar_x, bar_y), Point::new(bar_x + width as i32, bar_y))
+              .into_styled(stroke_grey)
+              .draw(d)?;
+        let threshold_x = bar_x + to_px(thresholds[jack] as u32);
+        Line::new(Point::new(threshold_x, bar_y - 6), Point::new(threshold_x, bar_y + 6))
+              .into_styled(stroke_grey)
+              .draw(d)?;
+        if c.diff > 0 {
+            Line::new(Point::new(bar_x, bar_y), Point::new(bar_x + to_px(c.diff as u32), bar_y))
+                  .into_styled(stroke_white)
+                  .draw(d)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn draw_meters<D>(d: &mut D, x: u32, y: u32, hue: u8, levels: &[MeterLevel; 4]) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = HI8>,
+{
+    let font_small_grey = MonoTextStyle::new(&FONT_9X15, HI8::new(hue, 10));
+    let stroke_grey = PrimitiveStyleBuilder::new()
+           .stroke_color(HI8::new(hue, 5))
+           .stroke_width(1)
+           .build();
+    let stroke_rms = PrimitiveStyleBuilder::new()
+           .stroke_color(HI8::new(hue, 12))
+           .stroke_width(6)
+           .build();
+    let stroke_peak = PrimitiveStyleBuilder::new()
+           .stroke_color(HI8::new(hue, 15))
+           .stroke_width(2)
+           .build();
+
+    let spacing = 20;
+    let bar_x = (x + 40) as i32;
+    // 2px per dB, empty at `FLOOR_DBFS`.
+    let to_px = |dbfs: i8| 2 * (dbfs.max(FLOOR_DBFS) - FLOOR_DBFS) as i32;
+    let width = to_px(0);
+
+    for (ch, level) in levels.iter().enumerate() {
+        let row_y = (y + ch as u32 * spacing) as i32;
+        let mut text: String<16> = String::new();
+        write!(text, "in{}", ch).ok();
+        Text::with_alignment(
+            &text,
+            Point::new(x as i32, row_y),
+            font_small_grey,
+            Alignment::Left
+        ).draw(d)?;
+
+        let bar_y = row_y - 5;
+        Line::new(Point::new(bar_x, bar_y), Point::new(bar_x + width, bar_y))
+              .into_styled(stroke_grey)
+              .draw(d)?;
+        if level.rms_dbfs > FLOOR_DBFS {
+            Line::new(Point::new(bar_x, bar_y), Point::new(bar_x + to_px(level.rms_dbfs), bar_y))
+                  .into_styled(stroke_rms)
+                  .draw(d)?;
+        }
+        if level.peak_dbfs > FLOOR_DBFS {
+            let peak_x = bar_x + to_px(level.peak_dbfs);
+            Line::new(Point::new(peak_x, bar_y - 6), Point::new(peak_x, bar_y + 6))
+                  .into_styled(stroke_peak)
+                  .draw(d)?;
+        }
+
+        let mut text: String<16> = String::new();
+        write!(text, "{:3}dB", level.peak_dbfs).ok();
+        Text::with_alignment(
+            &text,
+            Point::new(bar_x + width + 10, row_y),
+            font_small_grey,
+            Alignment::Left
+        ).draw(d)?;
+    }
+
+    Ok(())
+}
+
 pub fn draw_cal_constants<D>(
     d: &mut D, x: u32, y: u32, hue: u8,
     adc_scale: &[i32; 4],
@@ -630,6 +920,33 @@ where
     Ok(())
 }
 
+/// Current step of the calibration wizard, see `calibration::Wizard::prompt`.
+pub fn draw_cal_wizard<D>(d: &mut D, x: u32, y: u32, hue: u8,
+                          progress: &str, prompt: &str) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = HI8>,
+{
+    let font_small_white = MonoTextStyle::new(&FONT_9X15_BOLD, HI8::new(hue, 15));
+    let font_small_grey = MonoTextStyle::new(&FONT_9X15, HI8::new(hue, 10));
+
+    let mut title: String<32> = String::new();
+    write!(title, "calibration wizard [{}]", progress).ok();
+    Text::with_alignment(
+        &title,
+        Point::new(x as i32, y as i32),
+        font_small_white,
+        Alignment::Center
+    ).draw(d)?;
+    Text::with_alignment(
+        prompt,
+        Point::new(x as i32, (y + 20) as i32),
+        font_small_grey,
+        Alignment::Center
+    ).draw(d)?;
+
+    Ok(())
+}
+
 pub fn draw_tiliqua<D>(d: &mut D, x: i32, y: i32, hue: u8,
                        str_l: [&str; 8], str_r: [&str; 6]) -> Result<(), D::Error>
 where
@@ -926,35 +1243,99 @@ where
     Ok(())
 }
 
-// Like `draw_waveform`, but connecting lines instead of 'peak' bars.
-// This is more useful for plotting CV. It's currently only used in the
-// sampler bitstream.
-pub fn draw_waveform_lines<D>(
+/// How `draw_trace` renders a slice of samples.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TraceStyle {
+    /// Adjacent samples connected by lines.
+    Lines,
+    /// Like `Lines`, with the area between zero and the trace filled in.
+    Filled,
+    /// One pixel per sample.
+    Dots,
+}
+
+// Draw a trace of `samples` spread evenly from `x` to `x + width`. With a
+// fixed `gain`, full scale (times `gain`) spans `height` centered on zero.
+// Otherwise the min/max of `samples` is stretched to span `height`. The
+// trace is clipped to the box, and all lines are 1px (accelerated) lines.
+pub fn draw_trace<D>(
     d: &mut D,
     x: u32, y: u32,
     width: u32, height: u32,
     hue: u8,
     samples: &[i16],
+    style: TraceStyle,
+    gain: Option<f32>,
 ) -> Result<(), D::Error>
 where
     D: DrawTarget<Color = HI8>,
 {
+    let n = samples.len();
+    if n == 0 || height == 0 {
+        return Ok(());
+    }
     let stroke = PrimitiveStyleBuilder::new()
         .stroke_color(HI8::new(hue, 12))
         .stroke_width(1)
         .build();
-    let center_y = y as i32 + height as i32 / 2;
-    let half_height = height as i32 / 2;
-    let sample_width = if samples.len() > 0 { width / samples.len() as u32 } else { 1 };
-    for i in 1..samples.len() {
-        let x0 = x as i32 + ((i - 1) as i32 * sample_width as i32);
-        let x1 = x as i32 + (i as i32 * sample_width as i32);
-        let y0 = center_y - (samples[i - 1] as i32 * half_height) / 32768;
-        let y1 = center_y - (samples[i] as i32 * half_height) / 32768;
-        Line::new(
-            Point::new(x0, y0),
-            Point::new(x1, y1)
-        ).into_styled(stroke).draw(d)?;
+    let stroke_fill = PrimitiveStyleBuilder::new()
+        .stroke_color(HI8::new(hue, 5))
+        .stroke_width(1)
+        .build();
+
+    let (x, y, width, height) = (x as i32, y as i32, width as i32, height as i32);
+    // y = zero - sample * scale
+    let (zero, scale) = match gain {
+        Some(gain) => ((y + height / 2) as f32, gain * (height / 2) as f32 / 32768.0f32),
+        None => {
+            let min = *samples.iter().min().unwrap() as f32;
+            let max = *samples.iter().max().unwrap() as f32;
+            if max > min {
+                let scale = (height - 1) as f32 / (max - min);
+                ((y + height - 1) as f32 + min * scale, scale)
+            } else {
+                ((y + height / 2) as f32, 0.0f32)
+            }
+        }
+    };
+    let y_of = |sample: f32| ((zero - sample * scale + 0.5f32) as i32).clamp(y, y + height - 1);
+    let x_of = |i: usize| if n > 1 { x + i as i32 * width / (n - 1) as i32 } else { x };
+
+    match style {
+        TraceStyle::Dots => {
+            let color = HI8::new(hue, 12);
+            d.draw_iter(samples.iter().enumerate().map(|(i, &sample)| {
+                Pixel(Point::new(x_of(i), y_of(sample as f32)), color)
+            }))?;
+        }
+        TraceStyle::Lines | TraceStyle::Filled => {
+            if style == TraceStyle::Filled {
+                // One column per pixel, interpolating between samples.
+                let baseline = y_of(0.0f32);
+                let mut fill_column = |cx: i32, sample: f32| {
+                    Line::new(Point::new(cx, baseline), Point::new(cx, y_of(sample)))
+                        .into_styled(stroke_fill).draw(d)
+                };
+                if n == 1 {
+                    fill_column(x, samples[0] as f32)?;
+                }
+                for i in 1..n {
+                    let (x0, x1) = (x_of(i - 1), x_of(i));
+                    let (s0, s1) = (samples[i - 1] as f32, samples[i] as f32);
+                    let end = if i == n - 1 { x1 + 1 } else { x1 };
+                    for cx in x0..end {
+                        let t = if x1 > x0 { (cx - x0) as f32 / (x1 - x0) as f32 } else { 0.0f32 };
+                        fill_column(cx, s0 + (s1 - s0) * t)?;
+                    }
+                }
+            }
+            for i in 1..n {
+                Line::new(
+                    Point::new(x_of(i - 1), y_of(samples[i - 1] as f32)),
+                    Point::new(x_of(i), y_of(samples[i] as f32))
+                ).into_styled(stroke).draw(d)?;
+            }
+        }
     }
 
     Ok(())
@@ -982,6 +1363,109 @@ where
     Ok(())
 }
 
+// Grid spacing of `TestPattern::Crosshatch`.
+pub const CROSSHATCH_SPACING: u32 = 40;
+
+// Fill the whole display with a test pattern. Only fills and 1px lines are
+// used, so every draw goes through the accelerated paths.
+pub fn draw_test_pattern<D>(d: &mut D, pattern: TestPattern) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = HI8>,
+{
+    let Size { width: w, height: h } = d.bounding_box().size;
+    let line = |d: &mut D, x0: u32, y0: u32, x1: u32, y1: u32, color: HI8| {
+        let stroke = PrimitiveStyleBuilder::new()
+            .stroke_color(color)
+            .stroke_width(1)
+            .build();
+        Line::new(Point::new(x0 as i32, y0 as i32), Point::new(x1 as i32, y1 as i32))
+            .into_styled(stroke).draw(d)
+    };
+    let rect = |x0: u32, y0: u32, x1: u32, y1: u32| {
+        Rectangle::new(Point::new(x0 as i32, y0 as i32), Size::new(x1 - x0, y1 - y0))
+    };
+    match pattern {
+        TestPattern::ColorBars => {
+            let split = h * 2 / 3;
+            for n in 0..16u32 {
+                let (x0, x1) = (w * n / 16, w * (n + 1) / 16);
+                d.fill_solid(&rect(x0, 0, x1, split), HI8::new(n as u8, 12))?;
+                d.fill_solid(&rect(x0, split, x1, h), HI8::new(0, n as u8))?;
+            }
+        }
+        TestPattern::Crosshatch => {
+            d.clear(HI8::BLACK)?;
+            let grid = HI8::new(0, 8);
+            for x in (0..w).step_by(CROSSHATCH_SPACING as usize) {
+                line(d, x, 0, x, h - 1, grid)?;
+            }
+            for y in (0..h).step_by(CROSSHATCH_SPACING as usize) {
+                line(d, 0, y, w - 1, y, grid)?;
+            }
+            line(d, 0, 0, w - 1, 0, HI8::WHITE)?;
+            line(d, 0, h - 1, w - 1, h - 1, HI8::WHITE)?;
+            line(d, 0, 0, 0, h - 1, HI8::WHITE)?;
+            line(d, w - 1, 0, w - 1, h - 1, HI8::WHITE)?;
+            line(d, w / 2, 0, w / 2, h - 1, HI8::RED)?;
+            line(d, 0, h / 2, w - 1, h / 2, HI8::RED)?;
+            let diagonal = w.min(h) - 1;
+            line(d, 0, 0, diagonal, diagonal, HI8::GREEN)?;
+            d.fill_solid(&rect(1, 1, CROSSHATCH_SPACING / 2, CROSSHATCH_SPACING / 2), HI8::BLUE)?;
+        }
+        TestPattern::White => d.clear(HI8::WHITE)?,
+        TestPattern::Red => d.clear(HI8::RED)?,
+        TestPattern::Green => d.clear(HI8::GREEN)?,
+        TestPattern::Blue => d.clear(HI8::BLUE)?,
+    }
+    Ok(())
+}
+
+// Draw a parametric path (`path(t)` for `t` in 0..=1) as a glowing 'beam'
+// stroke, approximated with `n_segments` straight lines. The glow is made of
+// 1px lines offset across the minor axis of each segment, so every line still
+// goes through the (accelerated) line engine. Intensity falls off linearly from
+// `intensity` in the center to the edges of a stroke `width` pixels wide.
+pub fn draw_beam_stroke<D, P>(
+    d: &mut D,
+    path: P,
+    n_segments: u32,
+    width: u32,
+    hue: u8,
+    intensity: u8,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = HI8>,
+    P: Fn(f32) -> Point,
+{
+    let n_segments = n_segments.max(1);
+    let half = (width.max(1) / 2) as i32;
+    // Outermost (dimmest) lines first, so the core is drawn last and
+    // is never overwritten by the glow of an adjacent segment.
+    for k in (0..=half).rev() {
+        let level = (intensity as i32 * (half + 1 - k) / (half + 1)) as u8;
+        let stroke = PrimitiveStyleBuilder::new()
+            .stroke_color(HI8::new(hue, level))
+            .stroke_width(1)
+            .build();
+        let mut p0 = path(0.0f32);
+        for n in 1..=n_segments {
+            let p1 = path(n as f32 / n_segments as f32);
+            let delta = p1 - p0;
+            let offset = if delta.x.abs() >= delta.y.abs() {
+                Point::new(0, k)
+            } else {
+                Point::new(k, 0)
+            };
+            Line::new(p0 + offset, p1 + offset).into_styled(stroke).draw(d)?;
+            if k != 0 {
+                Line::new(p0 - offset, p1 - offset).into_styled(stroke).draw(d)?;
+            }
+            p0 = p1;
+        }
+    }
+    Ok(())
+}
+
 pub fn draw_benchmark_lines<D>(
     d: &mut D, count: u32, rng: &mut Rng) -> Result<(), D::Error>
 where
@@ -1042,6 +1526,23 @@ where
     Ok(())
 }
 
+pub fn draw_benchmark_fill<D>(
+    d: &mut D, count: u32, rng: &mut Rng) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = HI8>,
+{
+    let size = d.bounding_box().size;
+    for _ in 0..count {
+        let x = rng.u32(0..size.width);
+        let y = rng.u32(0..size.height);
+        let w = rng.u32(1..=size.width - x);
+        let h = rng.u32(1..=size.height - y);
+        let color = HI8::new(rng.u8(0..16), rng.u8(0..16));
+        d.fill_solid(&Rectangle::new(Point::new(x as i32, y as i32), Size::new(w, h)), color)?;
+    }
+    Ok(())
+}
+
 pub fn draw_benchmark_stats<D>(d: &mut D, pos_x: u32, pos_y: u32, hue: u8,
                               refresh_rate: u32, frame_count: u32) -> Result<(), D::Error>
 where
@@ -1149,6 +1650,12 @@ mod test_data {
         pub xscale: IntOption<ScaleParams>,
         #[option]
         pub palette: EnumOption<palette::ColorPalette>,
+        #[option]
+        pub trace: GroupOption,
+        #[option(3)]
+        pub intensity: IntOption<ScaleParams>,
+        #[option(10)]
+        pub hue: IntOption<ScaleParams>,
     }
 
     #[derive(Options, Clone)]
@@ -1219,11 +1726,157 @@ mod tests {
         opts.tick_up();
         opts.toggle_modify();
 
-        draw_name(&mut disp, H_ACTIVE/2, 30, 0, "MACRO-OSC", "b2d3aa", &DVIModeline::default()).ok();
+        draw_name(&mut disp, H_ACTIVE/2, 30, 0, "MACRO-OSC", "b2d3aa", &DVIModeline::default(), Some(1792195200)).ok();
+        draw_underruns(&mut disp, H_ACTIVE/2, 12, 0, 1234, 5).ok();
         draw_options(&mut disp, &opts, H_ACTIVE/2-30, 70, 0).ok();
+        draw_spinbox(&mut disp, H_ACTIVE/2+118, 46, &DigitEdit::new(-250, -1000, 1000), 0).ok();
         disp.img.save("draw_options.png").unwrap();
     }
 
+    #[test]
+    fn test_draw_options_group() {
+        let opts_x = H_ACTIVE/2-30;
+        let opts_y = 70;
+        // Anything drawn on the text row at `row`, right of the separator line.
+        let row_drawn = |disp: &FakeDisplay, row: u32| {
+            let y = opts_y + 18*row;
+            (y-12..y+3).any(|y| (opts_x..opts_x+150).any(|x| disp.img.get_pixel(x, y)[0] != 0))
+        };
+
+        let mut opts = test_data::Opts::default();
+        let mut disp = setup_display();
+        draw_options(&mut disp, &opts, opts_x, opts_y, 0).ok();
+        disp.img.save("draw_options_group_collapsed.png").unwrap();
+        // 4 ungrouped options and the header.
+        assert!(row_drawn(&disp, 4));
+        assert!(!row_drawn(&disp, 5));
+
+        opts.scope.trace.expanded = true;
+        let mut disp = setup_display();
+        draw_options(&mut disp, &opts, opts_x, opts_y, 0).ok();
+        disp.img.save("draw_options_group_expanded.png").unwrap();
+        assert!(row_drawn(&disp, 6));
+        assert!(!row_drawn(&disp, 7));
+    }
+
+    #[test]
+    fn test_draw_touch_counts() {
+        let mut disp = setup_display();
+        let counts: [SensorCounts; 8] = core::array::from_fn(|n| SensorCounts {
+            raw: 2000 + 40 * n as u16,
+            baseline: 2000,
+            diff: 40 * n as u16,
+        });
+        draw_touch_counts(&mut disp, 100, 100, 0, &counts, &[0x80; 8]).ok();
+        disp.img.save("draw_touch_counts.png").unwrap();
+    }
+
+    #[test]
+    fn test_draw_meters() {
+        let mut disp = setup_display();
+        let levels = [
+            MeterLevel { peak_dbfs: 0, rms_dbfs: -3 },
+            MeterLevel { peak_dbfs: -12, rms_dbfs: -20 },
+            MeterLevel { peak_dbfs: -45, rms_dbfs: -60 },
+            MeterLevel::default(),
+        ];
+        draw_meters(&mut disp, 100, 100, 0, &levels).ok();
+        disp.img.save("draw_meters.png").unwrap();
+    }
+
+    #[test]
+    fn test_draw_text_rotated() {
+        let style = MonoTextStyle::new(&FONT_9X15, HI8::new(0, 15));
+        let rotations = [Rotate::Normal, Rotate::Left, Rotate::Inverted, Rotate::Right];
+        for rotation in rotations {
+            let mut disp = setup_display();
+            let origin = Point::new(200, 300);
+            draw_text_rotated(&mut disp, origin, "Tiliqua 0123", rotation, style).ok();
+
+            // What the panel shows, using the same mapping as the gateware
+            // plotter (see `raster/plot.py`).
+            let (w, h) = (H_ACTIVE as i32, V_ACTIVE as i32);
+            let to_panel = |p: Point| match rotation {
+                Rotate::Normal   => p,
+                Rotate::Left     => Point::new(w - 1 - p.y, p.x),
+                Rotate::Inverted => Point::new(w - 1 - p.x, h - 1 - p.y),
+                Rotate::Right    => Point::new(p.y, h - 1 - p.x),
+            };
+            let mut panel = setup_display();
+            for (x, y, pixel) in disp.img.enumerate_pixels() {
+                let p = to_panel(Point::new(x as i32, y as i32));
+                *panel.img.get_pixel_mut(p.x as u32, p.y as u32) = *pixel;
+            }
+
+            // Should be identical to unrotated text at the same panel position.
+            let mut expect = setup_display();
+            Text::new("Tiliqua 0123", to_panel(origin), style).draw(&mut expect).ok();
+            let name: &'static str = rotation.into();
+            panel.img.save(format!("draw_text_rotated_{}.png", name)).unwrap();
+            assert!(panel.img == expect.img, "{} differs", name);
+        }
+    }
+
+    #[test]
+    fn test_draw_test_pattern() {
+        use strum::IntoEnumIterator;
+        let (w, h) = (H_ACTIVE, V_ACTIVE);
+        let marker = 1..CROSSHATCH_SPACING / 2;
+        for pattern in TestPattern::iter() {
+            let mut disp = setup_display();
+            draw_test_pattern(&mut disp, pattern).ok();
+            let name: &'static str = pattern.into();
+            disp.img.save(format!("draw_test_pattern_{}.png", name)).unwrap();
+
+            // Every pixel, against a reference computed independently of
+            // the line and fill primitives.
+            let expect = |x: u32, y: u32| match pattern {
+                TestPattern::ColorBars if y < h * 2 / 3 => HI8::new((x * 16 / w) as u8, 12),
+                TestPattern::ColorBars => HI8::new(0, (x * 16 / w) as u8),
+                TestPattern::Crosshatch => {
+                    if marker.contains(&x) && marker.contains(&y) {
+                        HI8::BLUE
+                    } else if x == y {
+                        HI8::GREEN
+                    } else if x == w / 2 || y == h / 2 {
+                        HI8::RED
+                    } else if x == 0 || y == 0 || x == w - 1 || y == h - 1 {
+                        HI8::WHITE
+                    } else if x % CROSSHATCH_SPACING == 0 || y % CROSSHATCH_SPACING == 0 {
+                        HI8::new(0, 8)
+                    } else {
+                        HI8::BLACK
+                    }
+                }
+                TestPattern::White => HI8::WHITE,
+                TestPattern::Red => HI8::RED,
+                TestPattern::Green => HI8::GREEN,
+                TestPattern::Blue => HI8::BLUE,
+            };
+            for (x, y, pixel) in disp.img.enumerate_pixels() {
+                assert_eq!(pixel[0], expect(x, y).to_raw(), "{} differs at ({}, {})", name, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn test_draw_beam_stroke() {
+        let mut disp = setup_display();
+        let (x0, x1, y) = (100.0f32, 600.0f32, 360);
+        draw_beam_stroke(&mut disp, |t| Point::new((x0 + (x1 - x0) * t) as i32, y),
+                         16, 7, 0, 15).ok();
+        disp.img.save("draw_beam_stroke.png").unwrap();
+        let intensity_at = |dy: i32| disp.img.get_pixel(350, (y + dy) as u32)[0] >> 4;
+        // Bright center, dimming toward the edges, nothing outside the stroke.
+        assert_eq!(intensity_at(0), 15);
+        for dy in 1..=3 {
+            assert!(intensity_at(dy) < intensity_at(dy - 1));
+            assert!(intensity_at(dy) > 0);
+            assert_eq!(intensity_at(dy), intensity_at(-dy));
+        }
+        assert_eq!(intensity_at(4), 0);
+    }
+
     #[test]
     fn test_draw_voices() {
         let mut disp = setup_display();
@@ -1237,6 +1890,22 @@ mod tests {
         disp.img.save("draw_voices.png").unwrap();
     }
 
+    #[test]
+    fn test_draw_trace() {
+        let mut disp = setup_display();
+        let sine: [i16; 64] = core::array::from_fn(|i| {
+            (12000.0f32 * f32::sin(2.0f32 * core::f32::consts::PI * i as f32 / 32.0f32)) as i16
+        });
+        let styles = [TraceStyle::Lines, TraceStyle::Filled, TraceStyle::Dots];
+        for (n, style) in styles.iter().enumerate() {
+            let y = 50 + n as u32 * 200;
+            // Fixed gain on the left, auto-scaled on the right.
+            draw_trace(&mut disp, 50, y, 300, 150, 0, &sine, *style, Some(1.0f32)).ok();
+            draw_trace(&mut disp, 370, y, 300, 150, 8, &sine, *style, None).ok();
+        }
+        disp.img.save("draw_trace.png").unwrap();
+    }
+
     #[test]
     fn test_draw_help() {
         let mut disp = setup_display();
@@ -1299,6 +1968,16 @@ mod tests {
         disp.img.save("draw_unicode.png").unwrap();
     }
 
+    #[test]
+    fn test_draw_benchmark_fill() {
+        let mut disp = setup_display();
+        let mut rng = Rng::with_seed(0);
+
+        draw_benchmark_fill(&mut disp, 20, &mut rng).ok();
+
+        disp.img.save("draw_fill.png").unwrap();
+    }
+
     #[test]
     fn test_draw_xbeam_help() {
         let mut disp = setup_display();
@@ -1392,7 +2071,7 @@ lines, USB streams).  Some usage ideas:
         // Test without manifest help (Tiliqua diagram won't be drawn)
         draw_help_page(&mut disp, XBEAM_HELP_TEXT, None, H_ACTIVE, V_ACTIVE, 3, 0).ok();
 
-        draw_name(&mut disp, H_ACTIVE/2, V_ACTIVE-50, 0, "XBEAM", "b2d3aa", &DVIModeline::default()).ok();
+        draw_name(&mut disp, H_ACTIVE/2, V_ACTIVE-50, 0, "XBEAM", "b2d3aa", &DVIModeline::default(), None).ok();
 
         let mut opts = test_data::Opts::default();
         draw_options(&mut disp, &opts, H_ACTIVE/2-30, V_ACTIVE-135, 0).ok();
@@ -1,4 +1,9 @@
 use fixed::{FixedI32, types::extra::U16};
+// micromath approximations are called as `F32Ext::sin(x)` rather than
+// `x.sin()` across this crate. With std (i.e. in tests), the inherent f32
+// methods would otherwise win, and tests would not see the same
+// approximations as the firmware.
+use micromath::F32Ext;
 
 /// Fixed point DSP below should use 32-bit integers with a 16.16 split.
 /// This could be made generic below, but isn't to reduce noise...
@@ -18,6 +23,13 @@ impl OnePoleSmoother {
         }
     }
 
+    /// Smoother that covers ~63% of a step in `tau_ms`, when `proc` is
+    /// called every `dt_ms`. Unlike `new`, it behaves the same whatever
+    /// rate a firmware calls it at.
+    pub fn from_time_constant(tau_ms: f32, dt_ms: f32) -> Self {
+        Self::new(1.0f32 - (-dt_ms / tau_ms.max(f32::EPSILON)).exp())
+    }
+
     pub fn proc(&mut self, x_k: Fix) -> Fix {
         self.y_k1 = self.y_k1 * (Fix::from_num(1.0f32) -  self.alpha) + x_k * self.alpha;
         self.y_k1
@@ -27,3 +39,787 @@ impl OnePoleSmoother {
         self.proc(Fix::from_bits(x_k as i32)).to_bits() as u16
     }
 }
+
+/// Peak envelope of a signal, for driving visuals from audio. Rises through
+/// one `OnePoleSmoother` and falls through another, so a burst is picked up
+/// quickly and dies away slowly. Feed it the peak magnitude of each block.
+#[derive(Copy, Clone)]
+pub struct EnvelopeFollower {
+    attack: OnePoleSmoother,
+    decay: OnePoleSmoother,
+}
+
+impl EnvelopeFollower {
+    /// Time constants as for `OnePoleSmoother::from_time_constant`, with
+    /// `process` called every `dt_ms`.
+    pub fn new(attack_ms: f32, decay_ms: f32, dt_ms: f32) -> Self {
+        Self {
+            attack: OnePoleSmoother::from_time_constant(attack_ms, dt_ms),
+            decay: OnePoleSmoother::from_time_constant(decay_ms, dt_ms),
+        }
+    }
+
+    pub fn process(&mut self, peak: f32) -> f32 {
+        let x = Fix::from_num(peak.abs());
+        let y = if x > self.attack.y_k1 {
+            self.attack.proc(x)
+        } else {
+            self.decay.proc(x)
+        };
+        // Both smoothers continue from wherever the envelope is now.
+        self.attack.y_k1 = y;
+        self.decay.y_k1 = y;
+        y.to_num()
+    }
+
+    pub fn value(&self) -> f32 {
+        self.attack.y_k1.to_num()
+    }
+}
+
+/// Second-order IIR filter (transposed direct form II), with
+/// coefficients from the RBJ 'Audio EQ Cookbook'.
+///
+/// `cutoff` is normalized to the sample rate (0..0.5), so 1kHz at
+/// 48kHz is `1000.0/48000.0`. A `q` of `1/sqrt(2)` gives a
+/// maximally flat (Butterworth) lowpass or highpass.
+#[derive(Copy, Clone)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    // Coefficients are given un-normalized as in the cookbook, i.e. (b0, b1, b2, a0, a1, a2).
+    fn from_coefficients(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Biquad {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    // (cos(w0), alpha) for a normalized cutoff and Q.
+    fn prewarp(cutoff: f32, q: f32) -> (f32, f32) {
+        let w0 = 2.0 * core::f32::consts::PI * cutoff.clamp(0.0, 0.5);
+        (F32Ext::cos(w0), F32Ext::sin(w0) / (2.0 * q))
+    }
+
+    pub fn lowpass(cutoff: f32, q: f32) -> Self {
+        let (cos_w0, alpha) = Self::prewarp(cutoff, q);
+        let b1 = 1.0 - cos_w0;
+        Self::from_coefficients(b1 / 2.0, b1, b1 / 2.0,
+                                1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha)
+    }
+
+    pub fn highpass(cutoff: f32, q: f32) -> Self {
+        let (cos_w0, alpha) = Self::prewarp(cutoff, q);
+        let b1 = -(1.0 + cos_w0);
+        Self::from_coefficients(-b1 / 2.0, b1, -b1 / 2.0,
+                                1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha)
+    }
+
+    /// Bandpass with 0dB gain at the center frequency.
+    pub fn bandpass(cutoff: f32, q: f32) -> Self {
+        let (cos_w0, alpha) = Self::prewarp(cutoff, q);
+        Self::from_coefficients(alpha, 0.0, -alpha,
+                                1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha)
+    }
+
+    pub fn notch(cutoff: f32, q: f32) -> Self {
+        let (cos_w0, alpha) = Self::prewarp(cutoff, q);
+        Self::from_coefficients(1.0, -2.0 * cos_w0, 1.0,
+                                1.0 + alpha, -2.0 * cos_w0, 1.0 - alpha)
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+
+    /// Clear the filter state, keeping the coefficients.
+    pub fn reset(&mut self) {
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+}
+
+/// One-pole DC blocking highpass, `y[n] = x[n] - x[n-1] + r*y[n-1]`.
+///
+/// `cutoff` is normalized to the sample rate, as for [`Biquad`].
+#[derive(Copy, Clone)]
+pub struct DcBlocker {
+    r: f32,
+    x1: f32,
+    y1: f32,
+}
+
+impl DcBlocker {
+    pub fn new(cutoff: f32) -> Self {
+        DcBlocker {
+            r: 1.0 - 2.0 * core::f32::consts::PI * cutoff.clamp(0.0, 0.5),
+            x1: 0.0,
+            y1: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = x - self.x1 + self.r * self.y1;
+        self.x1 = x;
+        self.y1 = y;
+        y
+    }
+}
+
+impl Default for DcBlocker {
+    /// ~5Hz at 48kHz, well below anything audible.
+    fn default() -> Self {
+        Self::new(5.0 / 48000.0)
+    }
+}
+
+/// Linear slew limiter, for portamento/glide on pitch CV. Unlike
+/// [`OnePoleSmoother`], the output moves toward the target at a constant
+/// rate, so a glide takes time proportional to the interval.
+///
+/// Rates are in units per second, with `process` called every `dt`
+/// seconds. A rate of 0 disables slewing in that direction.
+#[derive(Copy, Clone, Default)]
+pub struct Slew {
+    rise: f32,
+    fall: f32,
+    y: f32,
+}
+
+impl Slew {
+    pub fn new(units_per_sec: f32, dt: f32) -> Self {
+        let mut slew = Self::default();
+        slew.set_rate(units_per_sec, dt);
+        slew
+    }
+
+    /// Same rate in both directions.
+    pub fn set_rate(&mut self, units_per_sec: f32, dt: f32) {
+        self.set_rise_fall(units_per_sec, units_per_sec, dt);
+    }
+
+    pub fn set_rise_fall(&mut self, rise_per_sec: f32, fall_per_sec: f32, dt: f32) {
+        self.rise = rise_per_sec.max(0.0) * dt;
+        self.fall = fall_per_sec.max(0.0) * dt;
+    }
+
+    /// Jump straight to `y`, e.g. on a new note that should not glide.
+    pub fn reset(&mut self, y: f32) {
+        self.y = y;
+    }
+
+    /// Move one step toward `target`, snapping to it once it is within a step.
+    pub fn process(&mut self, target: f32) -> f32 {
+        let step = if target > self.y { self.rise } else { self.fall };
+        let delta = target - self.y;
+        self.y = if step == 0.0 || delta.abs() <= step {
+            target
+        } else {
+            self.y + step.copysign(delta)
+        };
+        self.y
+    }
+}
+
+/// Saturate `x` with `tanh(x * drive)`, so the output is always in
+/// [-1, 1]. Small signals pass with a gain of roughly `drive`.
+pub fn softclip(x: f32, drive: f32) -> f32 {
+    // tanh(y) = 1 - 2/(exp(2y) + 1). Clamp the argument first, both to
+    // keep exp() in range and because tanh(8) is already 1 in f32.
+    let y = (x * drive).clamp(-8.0, 8.0);
+    let t = 1.0 - 2.0 / (F32Ext::exp(2.0 * y) + 1.0);
+    t.clamp(-1.0, 1.0)
+}
+
+/// Unity gain for the Q15 helpers below. Note this is just outside
+/// the range of a Q15 value, so unity results saturate to `i16::MAX`.
+pub const Q15_ONE: i32 = 1 << 15;
+
+fn saturate_i16(x: i64) -> i16 {
+    x.clamp(i16::MIN as i64, i16::MAX as i64) as i16
+}
+
+/// Multiply `x` by `gain` (Q15, `Q15_ONE` is unity), saturating
+/// the result to the Q15 range.
+pub fn scale_q15(x: i32, gain: i32) -> i16 {
+    saturate_i16((x as i64 * gain as i64) >> 15)
+}
+
+/// Crossfade from `a` (`mix` = 0) to `b` (`mix` = `Q15_ONE`), saturating
+/// the result to the Q15 range. `mix` outside this range is clamped.
+pub fn mix_q15(a: i32, b: i32, mix: i32) -> i16 {
+    let mix = mix.clamp(0, Q15_ONE) as i64;
+    saturate_i16((a as i64 * (Q15_ONE as i64 - mix) + b as i64 * mix) >> 15)
+}
+
+/// Pan positions (-`i16::MAX` hard left, `i16::MAX` hard right) for `N`
+/// voices spread evenly across the stereo field. `amount` is Q15, from
+/// everything centered (0) to the full width (`Q15_ONE`).
+///
+/// Neighbouring voices land on opposite sides, outermost first, so that
+/// a handful of held notes is already spread wide: for 4 voices the
+/// order is left, right, left-center, right-center.
+pub fn stereo_spread<const N: usize>(amount: u16) -> [i16; N] {
+    let amount = (amount as i32).min(Q15_ONE);
+    core::array::from_fn(|n| {
+        if N < 2 {
+            return 0;
+        }
+        let j = if n % 2 == 0 { n / 2 } else { N - 1 - n / 2 };
+        let position = (2 * j as i32 - (N as i32 - 1)) * i16::MAX as i32 / (N as i32 - 1);
+        scale_q15(position, amount)
+    })
+}
+
+/// Index of the zero crossing in `buf` nearest to `center`, or `None` if
+/// the waveform stays on one side of zero across the whole buffer.
+///
+/// A crossing lies between 2 neighbouring samples of opposite sign (or
+/// on a sample that is exactly zero). Of the 2, the one closest to zero
+/// is returned, so a loop point snapped there starts as quietly as the
+/// recording allows. Crossings equally far from `center` resolve to the
+/// earlier one.
+pub fn nearest_zero_crossing(buf: &[i16], center: usize) -> Option<usize> {
+    let crossing = |k: usize| -> Option<usize> {
+        if buf[k] == 0 {
+            return Some(k);
+        }
+        if k == 0 || (buf[k - 1] < 0) == (buf[k] < 0) {
+            return None;
+        }
+        Some(if buf[k - 1].unsigned_abs() < buf[k].unsigned_abs() { k - 1 } else { k })
+    };
+    if center >= buf.len() {
+        return None;
+    }
+    (0..buf.len()).find_map(|d| {
+        let before = center.checked_sub(d).and_then(crossing);
+        let after = (center + d < buf.len()).then(|| crossing(center + d)).flatten();
+        match (before, after) {
+            (Some(b), Some(a)) => Some(if center - b <= a.abs_diff(center) { b } else { a }),
+            (b, a) => b.or(a),
+        }
+    })
+}
+
+// (sin, cos) of `turns` full turns, to f32 precision. The micromath
+// approximations (~1e-3) are too coarse for Goertzel and FFT twiddles,
+// where the error shows up as leakage and compounds over FFT stages.
+fn sin_cos_turns(turns: f32) -> (f32, f32) {
+    // Reduce to the nearest quarter turn, leaving |x| <= pi/4 for the
+    // Taylor series.
+    let quarters = F32Ext::round(turns * 4.0);
+    let x = (turns * 4.0 - quarters) * core::f32::consts::FRAC_PI_2;
+    let x2 = x * x;
+    let s = x * (1.0 - x2 / 6.0 * (1.0 - x2 / 20.0 * (1.0 - x2 / 42.0 * (1.0 - x2 / 72.0))));
+    let c = 1.0 - x2 / 2.0 * (1.0 - x2 / 12.0 * (1.0 - x2 / 30.0 * (1.0 - x2 / 56.0)));
+    match (quarters as i32) & 3 {
+        0 => (s, c),
+        1 => (c, -s),
+        2 => (-s, -c),
+        _ => (-c, s),
+    }
+}
+
+// sqrt(power), refining the (few % off) micromath estimate with a
+// Newton step so that spectrum magnitudes are accurate to ~0.1%.
+fn sqrt_refined(power: f32) -> f32 {
+    if power <= 0.0 {
+        return 0.0;
+    }
+    let r = F32Ext::sqrt(power);
+    0.5 * (r + power / r)
+}
+
+/// Magnitude of a single frequency, accumulated one sample at a time.
+/// Cheaper than an FFT when only a handful of frequencies matter, e.g.
+/// to check that an oscillator is on pitch.
+///
+/// `freq` is normalized to the sample rate, as for [`Biquad`]. Feed a
+/// block of samples to `process`, read `magnitude`, then `reset` before
+/// the next block. For no leakage from other bins, `freq` should land
+/// exactly on a bin of the block (see `from_bin`).
+#[derive(Copy, Clone)]
+pub struct Goertzel {
+    coeff: f32,
+    s1: f32,
+    s2: f32,
+    n: u32,
+}
+
+impl Goertzel {
+    pub fn new(freq: f32) -> Self {
+        let (_, cos_w) = sin_cos_turns(freq.clamp(0.0, 0.5));
+        Goertzel {
+            coeff: 2.0 * cos_w,
+            s1: 0.0,
+            s2: 0.0,
+            n: 0,
+        }
+    }
+
+    /// Tuned to bin `k` of an `n`-point DFT.
+    pub fn from_bin(k: usize, n: usize) -> Self {
+        Self::new(k as f32 / n as f32)
+    }
+
+    pub fn process(&mut self, x: f32) {
+        let s0 = x + self.coeff * self.s1 - self.s2;
+        self.s2 = self.s1;
+        self.s1 = s0;
+        self.n += 1;
+    }
+
+    /// Magnitude over the samples so far, scaled so that a unit sine
+    /// on the tuned bin reads 1.0.
+    pub fn magnitude(&self) -> f32 {
+        if self.n == 0 {
+            return 0.0;
+        }
+        let power = self.s1 * self.s1 + self.s2 * self.s2 - self.coeff * self.s1 * self.s2;
+        2.0 * sqrt_refined(power) / self.n as f32
+    }
+
+    /// Clear the accumulated samples, keeping the frequency.
+    pub fn reset(&mut self) {
+        self.s1 = 0.0;
+        self.s2 = 0.0;
+        self.n = 0;
+    }
+}
+
+/// In-place radix-2 FFT of `N` complex values, `N` a power of 2.
+/// Twiddles are computed on the fly, so this is best kept to small
+/// sizes (64 or 128 points) run between frames.
+pub fn fft<const N: usize>(re: &mut [f32; N], im: &mut [f32; N]) {
+    assert!(N.is_power_of_two());
+    if N < 2 {
+        return;
+    }
+    // Bit-reversed reordering.
+    let shift = usize::BITS - N.trailing_zeros();
+    for k in 0..N {
+        let j = k.reverse_bits() >> shift;
+        if j > k {
+            re.swap(k, j);
+            im.swap(k, j);
+        }
+    }
+    let mut m = 2;
+    while m <= N {
+        for j in 0..m / 2 {
+            let (w_im, w_re) = sin_cos_turns(-(j as f32) / m as f32);
+            for k in (j..N).step_by(m) {
+                let l = k + m / 2;
+                let t_re = re[l] * w_re - im[l] * w_im;
+                let t_im = re[l] * w_im + im[l] * w_re;
+                re[l] = re[k] - t_re;
+                im[l] = im[k] - t_im;
+                re[k] += t_re;
+                im[k] += t_im;
+            }
+        }
+        m *= 2;
+    }
+}
+
+/// Magnitude spectrum of the real signal `x`, for a spectrum display.
+/// Fills `mags` with bins `0..N/2` (or as many as fit), scaled so that
+/// a unit sine on a bin reads 1.0 and DC reads the mean.
+pub fn fft_real<const N: usize>(x: &[f32; N], mags: &mut [f32]) {
+    let mut re = *x;
+    let mut im = [0f32; N];
+    fft(&mut re, &mut im);
+    for (k, mag) in mags.iter_mut().take(N / 2).enumerate() {
+        let scale = if k == 0 { 1.0 } else { 2.0 } / N as f32;
+        *mag = scale * sqrt_refined(re[k] * re[k] + im[k] * im[k]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dc_blocker() {
+        let mut dc = DcBlocker::default();
+        // A 5Hz cutoff takes a few seconds to settle on a step.
+        let mut y = 0.0f32;
+        for n in 0..5*48000 {
+            let x = 0.5 + 0.1 * F32Ext::sin(n as f32 * 0.05);
+            y = dc.process(x);
+        }
+        // Offset is gone, what is left is the (barely attenuated) sine.
+        assert!(y.abs() < 0.11);
+        let mut mean = 0.0f32;
+        for n in 0..48000 {
+            mean += dc.process(0.5 + 0.1 * F32Ext::sin(n as f32 * 0.05));
+        }
+        assert!((mean / 48000.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_smoother_time_constant() {
+        // Same time constant at the ISR periods used across firmware.
+        for dt_ms in [5.0f32, 10.0] {
+            let mut s = OnePoleSmoother::from_time_constant(100.0, dt_ms);
+            let step = Fix::from_num(1.0f32);
+            let mut y = Fix::from_num(0);
+            for _ in 0..(100.0 / dt_ms) as usize {
+                y = s.proc(step);
+            }
+            let y: f32 = y.to_num();
+            assert!((y - 0.632).abs() < 0.01, "dt_ms={} y={}", dt_ms, y);
+        }
+    }
+
+    #[test]
+    fn test_envelope_follower() {
+        let mut env = EnvelopeFollower::new(10.0, 200.0, 2.0);
+        // 100ms burst: most of the way up within a couple of attack time
+        // constants, then settles at the peak.
+        let mut rise = [0.0f32; 50];
+        for y in rise.iter_mut() {
+            *y = env.process(0.8);
+        }
+        assert!(rise.windows(2).all(|w| w[1] >= w[0]));
+        assert!((rise[4] - 0.8 * 0.632).abs() < 0.02, "{}", rise[4]);
+        assert!(rise[10] > 0.7, "{}", rise[10]);
+        assert!((env.value() - 0.8).abs() < 0.01);
+        // Silence: falls with the much slower decay time constant.
+        let mut fall = [0.0f32; 300];
+        for y in fall.iter_mut() {
+            *y = env.process(0.0);
+        }
+        assert!(fall.windows(2).all(|w| w[1] <= w[0]));
+        assert!((fall[99] - 0.8 * 0.368).abs() < 0.02, "{}", fall[99]);
+        assert!(fall[299] < 0.05);
+        // Negative peaks count by magnitude, and a louder burst during
+        // the decay is picked up at the attack rate.
+        let start = env.value();
+        for _ in 0..5 {
+            env.process(-0.5);
+        }
+        let expected = 0.5 - (0.5 - start) * 0.368;
+        assert!((env.value() - expected).abs() < 0.02, "{}", env.value());
+    }
+
+    #[test]
+    fn test_slew() {
+        // Number of steps to reach `target`, and check the output never overshoots.
+        fn steps_to(slew: &mut Slew, target: f32) -> usize {
+            let start = slew.process(slew.y);
+            for n in 1..100000 {
+                let y = slew.process(target);
+                assert!((y - start).abs() <= (target - start).abs(), "overshoot {}", y);
+                if y == target {
+                    return n;
+                }
+            }
+            panic!("never reached {}", target);
+        }
+
+        // Rates and dt chosen so each step is exact in f32: 16V/s called
+        // every 1/256s is steps of 1/16V.
+        let dt = 1.0 / 256.0;
+        let mut slew = Slew::new(16.0, dt);
+        assert_eq!(steps_to(&mut slew, 1.0), 16);
+        assert_eq!(steps_to(&mut slew, -1.0), 32);
+        // Partial final step snaps onto the target.
+        assert_eq!(steps_to(&mut slew, -0.87), 3);
+        assert_eq!(slew.process(-0.87), -0.87);
+
+        // Asymmetric: rise 4x faster than fall.
+        slew.reset(0.0);
+        slew.set_rise_fall(32.0, 8.0, dt);
+        assert_eq!(steps_to(&mut slew, 1.0), 8);
+        assert_eq!(steps_to(&mut slew, 0.0), 32);
+        // A zero rate does not slew in that direction.
+        slew.set_rise_fall(0.0, 8.0, dt);
+        assert_eq!(steps_to(&mut slew, 3.0), 1);
+        assert_eq!(steps_to(&mut slew, 2.0), 32);
+        slew.set_rate(0.0, dt);
+        assert_eq!(steps_to(&mut slew, -5.0), 1);
+    }
+
+    #[test]
+    fn test_softclip() {
+        for drive in [0.5f32, 1.0, 4.0, 20.0] {
+            let mut last = -1.0f32;
+            for n in -1000..=1000 {
+                let y = softclip(n as f32 / 100.0, drive);
+                assert!((-1.0..=1.0).contains(&y));
+                assert!(y >= last);
+                last = y;
+            }
+        }
+        // Odd symmetric, roughly linear with gain `drive` near zero.
+        assert_eq!(softclip(0.0, 2.0), 0.0);
+        assert!((softclip(0.3, 2.0) + softclip(-0.3, 2.0)).abs() < 1e-3);
+        assert!((softclip(0.01, 2.0) - 0.02).abs() < 1e-3);
+        assert!(softclip(100.0, 1.0) > 0.999);
+    }
+
+    #[test]
+    fn test_scale_q15() {
+        assert_eq!(scale_q15(1000, Q15_ONE), 1000);
+        assert_eq!(scale_q15(1000, Q15_ONE/2), 500);
+        assert_eq!(scale_q15(-1000, Q15_ONE/2), -500);
+        // Saturates at the Q15 extremes instead of wrapping.
+        assert_eq!(scale_q15(i16::MAX as i32, 2*Q15_ONE), i16::MAX);
+        assert_eq!(scale_q15(i16::MIN as i32, 2*Q15_ONE), i16::MIN);
+        assert_eq!(scale_q15(i16::MIN as i32, -Q15_ONE), i16::MAX);
+        assert_eq!(scale_q15(i32::MAX, i32::MAX), i16::MAX);
+        assert_eq!(scale_q15(i32::MIN, i32::MAX), i16::MIN);
+    }
+
+    #[test]
+    fn test_mix_q15() {
+        assert_eq!(mix_q15(1000, -1000, 0), 1000);
+        assert_eq!(mix_q15(1000, -1000, Q15_ONE), -1000);
+        assert_eq!(mix_q15(1000, -1000, Q15_ONE/2), 0);
+        // Unity lands just outside Q15, so it saturates.
+        assert_eq!(mix_q15(Q15_ONE, 0, 0), i16::MAX);
+        assert_eq!(mix_q15(-Q15_ONE, 0, 0), i16::MIN);
+        assert_eq!(mix_q15(i16::MAX as i32, i16::MAX as i32, Q15_ONE/2), i16::MAX);
+        assert_eq!(mix_q15(i16::MIN as i32, i16::MIN as i32, Q15_ONE/3), i16::MIN);
+        // Out of range mix amounts are clamped.
+        assert_eq!(mix_q15(1000, -1000, 2*Q15_ONE), -1000);
+        assert_eq!(mix_q15(1000, -1000, -Q15_ONE), 1000);
+    }
+
+    #[test]
+    fn test_stereo_spread() {
+        // Full width, 8 voices: evenly spaced, alternating sides.
+        let m = i16::MAX as i32;
+        let full = stereo_spread::<8>(Q15_ONE as u16);
+        let expect: [i32; 8] = [-7, 7, -5, 5, -3, 3, -1, 1];
+        for (pan, e) in full.iter().zip(expect) {
+            assert_eq!(*pan as i32, e * m / 7);
+        }
+        // Equal steps between sorted positions.
+        let mut sorted = full;
+        sorted.sort();
+        for w in sorted.windows(2) {
+            assert!(((w[1] - w[0]) as i32 - 2 * m / 7).abs() <= 1);
+        }
+        // Zero spread is mono, half spread halves every position.
+        assert_eq!(stereo_spread::<8>(0), [0; 8]);
+        for (half, full) in stereo_spread::<8>(Q15_ONE as u16 / 2).iter().zip(full) {
+            assert!((*half as i32 - full as i32 / 2).abs() <= 1);
+        }
+        // Odd voice counts keep one voice centered, 1 voice is always centered.
+        assert_eq!(stereo_spread::<3>(Q15_ONE as u16), [-i16::MAX, i16::MAX, 0]);
+        assert_eq!(stereo_spread::<1>(Q15_ONE as u16), [0]);
+        // Amounts above unity are clamped.
+        assert_eq!(stereo_spread::<8>(u16::MAX), full);
+    }
+
+    #[test]
+    fn test_nearest_zero_crossing() {
+        // 100-sample period sine, offset by a quarter sample so that no
+        // sample lands exactly on zero. Sample 0 has nothing before it,
+        // so the crossings are at samples 50, 100, .. 350.
+        let buf: [i16; 400] = core::array::from_fn(|n| {
+            let phase = 2.0 * core::f32::consts::PI * (n as f32 + 0.25) / 100.0;
+            (10000.0 * phase.sin()) as i16
+        });
+        for (center, expect) in [(0, 50), (30, 50), (50, 50), (70, 50),
+                                 (75, 50), (76, 100), (123, 100), (399, 350)] {
+            let zc = nearest_zero_crossing(&buf, center).unwrap();
+            assert_eq!(zc, expect, "center={}", center);
+            // Always the sample nearest zero at the crossing.
+            assert!(buf[zc].unsigned_abs() < 200, "center={} buf[zc]={}", center, buf[zc]);
+        }
+
+        // A sample exactly on zero counts, even if the signal doesn't
+        // change sign across it.
+        let mut bump = [1000i16; 32];
+        bump[20] = 0;
+        assert_eq!(nearest_zero_crossing(&bump, 5), Some(20));
+
+        // No crossing in the buffer at all: the point must stay put.
+        assert_eq!(nearest_zero_crossing(&[1000i16; 32], 16), None);
+        assert_eq!(nearest_zero_crossing(&[-1i16; 32], 0), None);
+        assert_eq!(nearest_zero_crossing(&buf, buf.len()), None);
+        assert_eq!(nearest_zero_crossing(&[], 0), None);
+    }
+
+    // Steady-state peak output for a unit sine at `freq` (normalized).
+    fn sine_gain(filter: &mut Biquad, freq: f32) -> f32 {
+        let n = 48000;
+        let mut peak = 0f32;
+        for i in 0..n {
+            let x = (2.0 * core::f32::consts::PI * freq * i as f32).sin();
+            let y = filter.process(x);
+            // Skip the start, while the filter settles.
+            if i > n / 2 {
+                peak = peak.max(y.abs());
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn test_biquad_lowpass() {
+        let cutoff = 1000.0 / 48000.0;
+        let q = core::f32::consts::FRAC_1_SQRT_2;
+
+        // Unity gain at DC.
+        let mut lpf = Biquad::lowpass(cutoff, q);
+        let mut y = 0f32;
+        for _ in 0..10000 {
+            y = lpf.process(1.0);
+        }
+        assert!((y - 1.0).abs() < 1e-3, "dc gain: {}", y);
+
+        // -3dB at the cutoff, and 2nd order (-12dB/oct) rolloff above it.
+        let gain_cutoff = sine_gain(&mut Biquad::lowpass(cutoff, q), cutoff);
+        assert!((gain_cutoff - core::f32::consts::FRAC_1_SQRT_2).abs() < 0.01,
+                "gain at cutoff: {}", gain_cutoff);
+        let gain_above = sine_gain(&mut Biquad::lowpass(cutoff, q), 8.0 * cutoff);
+        let expected = 1.0 / (1.0 + (8.0f32).powi(4)).sqrt();
+        assert!((gain_above - expected).abs() < 0.005, "gain 3oct above: {}", gain_above);
+
+        lpf.reset();
+        assert_eq!(lpf.process(0.0), 0.0);
+    }
+
+    #[test]
+    fn test_biquad_other() {
+        let cutoff = 2000.0 / 48000.0;
+        let q = core::f32::consts::FRAC_1_SQRT_2;
+
+        // Highpass blocks DC.
+        let mut hpf = Biquad::highpass(cutoff, q);
+        let mut y = 1f32;
+        for _ in 0..10000 {
+            y = hpf.process(1.0);
+        }
+        assert!(y.abs() < 1e-3, "hpf dc: {}", y);
+        let gain = sine_gain(&mut Biquad::highpass(cutoff, q), cutoff);
+        assert!((gain - core::f32::consts::FRAC_1_SQRT_2).abs() < 0.01, "hpf cutoff: {}", gain);
+
+        // Bandpass passes its center frequency, notch removes it.
+        let gain = sine_gain(&mut Biquad::bandpass(cutoff, 2.0), cutoff);
+        assert!((gain - 1.0).abs() < 0.01, "bpf center: {}", gain);
+        let gain = sine_gain(&mut Biquad::bandpass(cutoff, 2.0), cutoff / 8.0);
+        assert!(gain < 0.1, "bpf below: {}", gain);
+        let gain = sine_gain(&mut Biquad::notch(cutoff, 2.0), cutoff);
+        // Not as deep as an exact notch, as the coefficients use the
+        // micromath approximation of cos().
+        assert!(gain < 0.05, "notch center: {}", gain);
+        let gain = sine_gain(&mut Biquad::notch(cutoff, 2.0), cutoff / 8.0);
+        assert!((gain - 1.0).abs() < 0.01, "notch below: {}", gain);
+    }
+
+    // Reference DFT in f64, as (re, im) per bin.
+    fn dft(x: &[f32]) -> Vec<(f64, f64)> {
+        let n = x.len();
+        (0..n).map(|k| {
+            x.iter().enumerate().fold((0.0, 0.0), |(re, im), (i, v)| {
+                let w = -2.0 * core::f64::consts::PI * (k * i) as f64 / n as f64;
+                (re + *v as f64 * w.cos(), im + *v as f64 * w.sin())
+            })
+        }).collect()
+    }
+
+    #[test]
+    fn test_sin_cos_turns() {
+        for n in -2000..=2000 {
+            let turns = n as f32 / 1000.0;
+            let (s, c) = sin_cos_turns(turns);
+            let w = 2.0 * core::f64::consts::PI * turns as f64;
+            assert!((s as f64 - w.sin()).abs() < 1e-6, "sin {} turns", turns);
+            assert!((c as f64 - w.cos()).abs() < 1e-6, "cos {} turns", turns);
+        }
+    }
+
+    #[test]
+    fn test_goertzel() {
+        const N: usize = 128;
+        let tone = |k: f32, i: usize| (2.0 * core::f32::consts::PI * k * i as f32 / N as f32).sin();
+        // A tone on bin 10 shows up there, and (almost) nowhere else.
+        for bin in 1..N/2 {
+            let mut g = Goertzel::from_bin(bin, N);
+            for i in 0..N {
+                g.process(0.5 * tone(10.0, i));
+            }
+            let mag = g.magnitude();
+            if bin == 10 {
+                assert!((mag - 0.5).abs() < 1e-3, "bin {}: {}", bin, mag);
+            } else {
+                assert!(mag < 1e-3, "bin {}: {}", bin, mag);
+            }
+        }
+        // Reset starts a new block.
+        let mut g = Goertzel::from_bin(10, N);
+        for i in 0..N {
+            g.process(tone(10.0, i));
+        }
+        g.reset();
+        assert_eq!(g.magnitude(), 0.0);
+        for i in 0..N {
+            g.process(tone(3.0, i));
+        }
+        assert!(g.magnitude() < 1e-3);
+    }
+
+    #[test]
+    fn test_fft() {
+        fn check<const N: usize>(x: [f32; N]) {
+            let reference = dft(&x);
+            let (mut re, mut im) = (x, [0f32; N]);
+            fft(&mut re, &mut im);
+            for k in 0..N {
+                let tolerance = 1e-4 * N as f64;
+                assert!((re[k] as f64 - reference[k].0).abs() < tolerance, "N={} re[{}]", N, k);
+                assert!((im[k] as f64 - reference[k].1).abs() < tolerance, "N={} im[{}]", N, k);
+            }
+            let mut mags = [0f32; 64];
+            fft_real(&x, &mut mags);
+            for k in 0..N/2 {
+                let scale = if k == 0 { 1.0 } else { 2.0 } / N as f64;
+                let (r, i) = reference[k];
+                let expect = scale * (r * r + i * i).sqrt();
+                assert!((mags[k] as f64 - expect).abs() < 1e-3, "N={} mag[{}]", N, k);
+            }
+        }
+        // 2 tones and an offset.
+        check::<64>(core::array::from_fn(|i| {
+            let t = 2.0 * core::f32::consts::PI * i as f32 / 64.0;
+            0.25 + 0.5 * (5.0 * t).sin() + 0.2 * (17.0 * t).cos()
+        }));
+        // A tone between bins, which leaks everywhere.
+        check::<128>(core::array::from_fn(|i| {
+            (2.0 * core::f32::consts::PI * 7.5 * i as f32 / 128.0).sin()
+        }));
+        // Square wave, odd harmonics only.
+        check::<128>(core::array::from_fn(|i| if (i / 8) % 2 == 0 { 1.0 } else { -1.0 }));
+
+        // A tone on a bin reads its amplitude.
+        let x: [f32; 64] = core::array::from_fn(|i| {
+            0.8 * (2.0 * core::f32::consts::PI * 12.0 * i as f32 / 64.0).sin()
+        });
+        let mut mags = [0f32; 32];
+        fft_real(&x, &mut mags);
+        assert!((mags[12] - 0.8).abs() < 1e-3);
+        assert!(mags.iter().enumerate().all(|(k, m)| k == 12 || *m < 1e-4));
+    }
+}
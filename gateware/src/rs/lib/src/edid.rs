@@ -1,6 +1,13 @@
-/// Tiny EDID parser, only handles the header and detailed timing descriptor.
+use heapless::String;
+use serde_derive::{Serialize, Deserialize};
+use strum_macros::IntoStaticStr;
+use tiliqua_hal::dma_framebuffer::{DVIModeline, Rotate};
+
+/// Tiny EDID parser, only handles the header, detailed timing and name/serial descriptors.
 /// Does not handle extension blocks. This should be enough for most small embedded monitors.
 
+const EDID_HEADER_PATTERN: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+
 /// Main EDID structure representing the first 128 bytes of an EDID block
 #[derive(Debug)]
 pub struct Edid {
@@ -34,6 +41,24 @@ pub struct EdidHeader {
     pub revision: u8,
 }
 
+/// Enough of the EDID header to tell displays apart.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DisplayId {
+    pub manufacturer_id: [u8; 2],
+    pub product_code: u16,
+    pub serial_number: u32,
+}
+
+impl EdidHeader {
+    pub fn display_id(&self) -> DisplayId {
+        DisplayId {
+            manufacturer_id: self.manufacturer_id,
+            product_code: self.product_code,
+            serial_number: self.serial_number,
+        }
+    }
+}
+
 /// Detailed timing descriptors (18 bytes each)
 /// For simplicity, we're just storing the raw data for now
 #[derive(Debug, Copy, Clone)]
@@ -42,9 +67,13 @@ pub struct RawDescriptor {
 }
 
 /// Descriptor types for the 18-byte descriptor blocks
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub enum Descriptor {
     DetailedTiming(DetailedTimingDescriptor),
+    /// Display product name (tag 0xFC)
+    MonitorName(String<13>),
+    /// Display product serial number (tag 0xFF)
+    MonitorSerial(String<13>),
     RawDescriptor([u8; 18]),
 }
 
@@ -108,6 +137,12 @@ pub enum SyncType {
 impl Edid {
     /// Parse the EDID from raw bytes
     pub fn parse(edid_data: &[u8; 128]) -> Result<Self, EdidError> {
+        // Verify header pattern first, so a NAK'd or floating bus (all 0x00
+        // or 0xFF, the former even with a valid checksum) is told apart from
+        // a block with a few corrupted bytes.
+        if edid_data[..8] != EDID_HEADER_PATTERN {
+            return Err(EdidError::InvalidHeaderPattern);
+        }
         // Verify checksum
         let mut checksum: u8 = 0;
         for &byte in edid_data.iter() {
@@ -132,18 +167,13 @@ impl Edid {
             version: edid_data[18],
             revision: edid_data[19],
         };
-        // Verify header pattern
-        if header.pattern != [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00] {
-            return Err(EdidError::InvalidHeaderPattern);
-        }
-        let mut descriptors = [Descriptor::RawDescriptor([0; 18]); 4];
-        for i in 0..4 {
+        let descriptors = core::array::from_fn(|i| {
             let offset = 54 + i * 18;
             let mut data = [0; 18];
             data.copy_from_slice(&edid_data[offset..offset + 18]);
             // Parse the descriptor based on its format
-            descriptors[i] = Self::parse_descriptor(&data);
-        }
+            Self::parse_descriptor(&data)
+        });
 
         Ok(Edid {
             header,
@@ -153,6 +183,63 @@ impl Edid {
         })
     }
 
+    /// The display's preferred timing, which by convention is the first
+    /// detailed timing descriptor. Returned even if we cannot generate it.
+    pub fn preferred_timing(&self) -> Option<&DetailedTimingDescriptor> {
+        self.descriptors.iter().find_map(|descriptor| match descriptor {
+            Descriptor::DetailedTiming(desc) => Some(desc),
+            _ => None,
+        })
+    }
+
+    /// Display product name, if the display reports one.
+    pub fn monitor_name(&self) -> Option<&str> {
+        self.descriptors.iter().find_map(|descriptor| match descriptor {
+            Descriptor::MonitorName(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Display serial number string, if the display reports one.
+    pub fn monitor_serial(&self) -> Option<&str> {
+        self.descriptors.iter().find_map(|descriptor| match descriptor {
+            Descriptor::MonitorSerial(serial) => Some(serial.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Modeline for the preferred timing, if we can generate it. Otherwise,
+    /// the closest reduced-blanking mode with a pixel clock (in kHz) inside
+    /// `min_khz..=max_khz`, found by lowering the refresh rate and then
+    /// the resolution, keeping the aspect ratio. Most displays will scale
+    /// such a mode up to their native resolution.
+    pub fn clamp_to_supported(&self, min_khz: u32, max_khz: u32) -> Option<DVIModeline> {
+        let preferred = self.preferred_timing()?;
+        if preferred.check_supported(min_khz, max_khz).is_ok() {
+            return preferred.to_modeline();
+        }
+        let (h_active, v_active) = if preferred.features.interlaced {
+            // Active lines are per-field.
+            (preferred.horizontal_active, preferred.vertical_active * 2)
+        } else {
+            (preferred.horizontal_active, preferred.vertical_active)
+        };
+        let native_hz = preferred.refresh_rate();
+        for divisor in 1..=4u16 {
+            // CVT wants the horizontal resolution in 8-pixel character cells.
+            let h = (h_active / divisor) & !7;
+            let v = v_active / divisor;
+            for refresh_hz in [native_hz, 60.0f32, 50.0f32, 30.0f32] {
+                if let Some(modeline) = DVIModeline::cvt_reduced_blanking(h, v, refresh_hz) {
+                    if (min_khz..=max_khz).contains(&modeline.pixel_clk_khz()) {
+                        return Some(modeline);
+                    }
+                }
+            }
+        }
+        None
+    }
+
     /// Parse a descriptor block
     fn parse_descriptor(data: &[u8; 18]) -> Descriptor {
         // Check if it's a detailed timing descriptor (pixel clock != 0)
@@ -161,8 +248,28 @@ impl Edid {
             // Detailed timing descriptor
             return Descriptor::DetailedTiming(Self::parse_detailed_timing(data));
         }
-        // It's not a detailed timing descriptor, store raw data
-        Descriptor::RawDescriptor(*data)
+        // Display descriptor, the tag is in byte 3
+        match data[3] {
+            0xFC => Descriptor::MonitorName(Self::parse_descriptor_text(data)),
+            0xFF => Descriptor::MonitorSerial(Self::parse_descriptor_text(data)),
+            // Anything else we don't understand, store raw data
+            _ => Descriptor::RawDescriptor(*data),
+        }
+    }
+
+    /// Parse the 13-byte text field of a display descriptor. Text is terminated
+    /// by 0x0A (if shorter than 13 bytes) and then padded with spaces.
+    fn parse_descriptor_text(data: &[u8; 18]) -> String<13> {
+        let mut text = String::new();
+        for &c in data[5..].iter().take_while(|&&c| c != 0x0A) {
+            // Only printable ASCII is allowed here, substitute anything else.
+            let c = if c.is_ascii_graphic() || c == b' ' { c as char } else { '?' };
+            text.push(c).ok();
+        }
+        while text.ends_with(' ') {
+            text.pop();
+        }
+        text
     }
 
     /// Parse a detailed timing descriptor
@@ -252,43 +359,222 @@ impl Edid {
     }
 }
 
-/// Error type for EDID parsing
-#[derive(Debug)]
+/// Reasons a detailed timing descriptor may be unusable for video output.
+#[derive(Debug, Copy, Clone, PartialEq, IntoStaticStr)]
+pub enum TimingRejection {
+    #[strum(to_string = "pixel clock too slow")]
+    PixelClockTooSlow,
+    #[strum(to_string = "pixel clock too fast")]
+    PixelClockTooFast,
+    #[strum(to_string = "interlaced")]
+    Interlaced,
+    #[strum(to_string = "unknown sync format")]
+    UnknownSyncFormat,
+}
+
+impl DetailedTimingDescriptor {
+    pub fn refresh_rate(&self) -> f32 {
+        let h_total = (self.horizontal_active + self.horizontal_blanking) as u32;
+        let v_total = (self.vertical_active + self.vertical_blanking) as u32;
+        (self.pixel_clock_khz as f32 * 1e3f32) / (h_total * v_total) as f32
+    }
+
+    /// The modeline described by this timing. Only digital separate sync
+    /// carries the sync polarities we need, so other sync types are `None`.
+    pub fn to_modeline(&self) -> Option<DVIModeline> {
+        let SyncType::DigitalSeparate { vsync_positive, hsync_positive } = self.features.sync_type else {
+            return None;
+        };
+        Some(DVIModeline {
+            h_active      : self.horizontal_active,
+            h_sync_start  : self.horizontal_active +
+                            self.horizontal_sync_offset,
+            h_sync_end    : self.horizontal_active +
+                            self.horizontal_sync_offset +
+                            self.horizontal_sync_pulse_width,
+            h_total       : self.horizontal_active +
+                            self.horizontal_blanking,
+            h_sync_invert : !hsync_positive,
+            v_active      : self.vertical_active,
+            v_sync_start  : self.vertical_active +
+                            self.vertical_sync_offset,
+            v_sync_end    : self.vertical_active +
+                            self.vertical_sync_offset +
+                            self.vertical_sync_pulse_width,
+            v_total       : self.vertical_active +
+                            self.vertical_blanking,
+            v_sync_invert : !vsync_positive,
+            pixel_clk_mhz : (self.pixel_clock_khz as f32) / 1e3f32,
+            rotate        : Rotate::Normal,
+        })
+    }
+
+    /// Check whether we are able to generate this timing, given the
+    /// range of pixel clocks (in kHz) supported by the video PLL.
+    pub fn check_supported(&self, pixel_clk_min_khz: u32, pixel_clk_max_khz: u32)
+        -> Result<(), TimingRejection> {
+        if self.pixel_clock_khz < pixel_clk_min_khz {
+            return Err(TimingRejection::PixelClockTooSlow);
+        }
+        if self.pixel_clock_khz > pixel_clk_max_khz {
+            return Err(TimingRejection::PixelClockTooFast);
+        }
+        if self.features.interlaced {
+            return Err(TimingRejection::Interlaced);
+        }
+        if let SyncType::DigitalSeparate { .. } = self.features.sync_type {
+            Ok(())
+        } else {
+            Err(TimingRejection::UnknownSyncFormat)
+        }
+    }
+}
+
+/// Error type for EDID parsing. Both usually mean a bad read rather
+/// than a bad display, so are worth retrying.
+#[derive(Debug, PartialEq)]
 pub enum EdidError {
-    InvalidChecksum,
+    /// Bytes 0-7 are not `00 FF FF FF FF FF FF 00`.
     InvalidHeaderPattern,
+    /// Sum of all 128 bytes is nonzero.
+    InvalidChecksum,
 }
 
 // A simple example of how to use the parser
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    // Example EDID data from Tiliqua screen
+    const TILIQUA_EDID: [u8; 128] = [
+        0x0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x0,
+        0xff, 0xff, 0x32, 0x31, 0x45, 0x6, 0x0, 0x0,
+        0xc, 0x1c, 0x1, 0x3, 0x80, 0xf, 0xa, 0x78,
+        0xa, 0xd, 0xc9, 0xa0, 0x57, 0x47, 0x98, 0x27,
+        0x12, 0x48, 0x4c, 0x0, 0x0, 0x0, 0x1, 0xc1,
+        0x1, 0x1, 0x1, 0xc1, 0x1, 0x1, 0x1, 0x1,
+        0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x9b, 0xe,
+        0xd0, 0x64, 0x20, 0xd0, 0x28, 0x20, 0x28, 0x14,
+        0x84, 0x4, 0xd0, 0xd0, 0x22, 0x0, 0x0, 0x1e,
+        0x9c, 0xe, 0xd0, 0x64, 0x20, 0xd0, 0x28, 0x20,
+        0x14, 0x28, 0x48, 0x1, 0x5, 0x28, 0x0, 0x20,
+        0x20, 0x20, 0x0, 0x0, 0x0, 0xfa, 0x0, 0xa,
+        0x20, 0x20, 0x20, 0x20, 0x2, 0x0, 0x20, 0x20,
+        0x20, 0x20, 0x20, 0xa, 0x0, 0x0, 0x0, 0xfc,
+        0x0, 0x5a, 0x4c, 0x37, 0x32, 0x30, 0x58, 0x37,
+        0x32, 0x30, 0xa, 0x20, 0x20, 0x20, 0x1, 0x62,
+    ];
+
     #[test]
     fn test_edid_parse() {
-        // Example EDID data from Tiliqua screen
-        let edid_data = [
-            0x0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x0,
-            0xff, 0xff, 0x32, 0x31, 0x45, 0x6, 0x0, 0x0,
-            0xc, 0x1c, 0x1, 0x3, 0x80, 0xf, 0xa, 0x78,
-            0xa, 0xd, 0xc9, 0xa0, 0x57, 0x47, 0x98, 0x27,
-            0x12, 0x48, 0x4c, 0x0, 0x0, 0x0, 0x1, 0xc1,
-            0x1, 0x1, 0x1, 0xc1, 0x1, 0x1, 0x1, 0x1,
-            0x1, 0x1, 0x1, 0x1, 0x1, 0x1, 0x9b, 0xe,
-            0xd0, 0x64, 0x20, 0xd0, 0x28, 0x20, 0x28, 0x14,
-            0x84, 0x4, 0xd0, 0xd0, 0x22, 0x0, 0x0, 0x1e,
-            0x9c, 0xe, 0xd0, 0x64, 0x20, 0xd0, 0x28, 0x20,
-            0x14, 0x28, 0x48, 0x1, 0x5, 0x28, 0x0, 0x20,
-            0x20, 0x20, 0x0, 0x0, 0x0, 0xfa, 0x0, 0xa,
-            0x20, 0x20, 0x20, 0x20, 0x2, 0x0, 0x20, 0x20,
-            0x20, 0x20, 0x20, 0xa, 0x0, 0x0, 0x0, 0xfc,
-            0x0, 0x5a, 0x4c, 0x37, 0x32, 0x30, 0x58, 0x37,
-            0x32, 0x30, 0xa, 0x20, 0x20, 0x20, 0x1, 0x62,
-        ];
-
-        let edid = Edid::parse(&edid_data);
+        let edid = Edid::parse(&TILIQUA_EDID);
         match edid {
             Ok(data) => println!("Successfully parsed EDID: {:#?}", data),
             Err(e) => panic!("Failed to parse EDID: {:?}", e),
         }
     }
+
+    #[test]
+    fn test_edid_validation() {
+        assert!(Edid::parse(&TILIQUA_EDID).is_ok());
+
+        // Single corrupted byte anywhere in the block.
+        let mut data = TILIQUA_EDID;
+        data[60] ^= 0x04;
+        assert_eq!(Edid::parse(&data).unwrap_err(), EdidError::InvalidChecksum);
+        let mut data = TILIQUA_EDID;
+        data[127] = data[127].wrapping_add(1);
+        assert_eq!(Edid::parse(&data).unwrap_err(), EdidError::InvalidChecksum);
+
+        // Corrupted header is reported as such, even though the checksum
+        // is now also wrong.
+        let mut data = TILIQUA_EDID;
+        data[3] = 0x7F;
+        assert_eq!(Edid::parse(&data).unwrap_err(), EdidError::InvalidHeaderPattern);
+
+        // Nothing on the bus. All zeroes has a valid checksum.
+        assert_eq!(Edid::parse(&[0x00; 128]).unwrap_err(), EdidError::InvalidHeaderPattern);
+        assert_eq!(Edid::parse(&[0xFF; 128]).unwrap_err(), EdidError::InvalidHeaderPattern);
+    }
+
+    #[test]
+    fn test_edid_monitor_name() {
+        let edid = Edid::parse(&TILIQUA_EDID).unwrap();
+        assert_eq!(edid.monitor_name(), Some("ZL720X720"));
+        assert_eq!(edid.monitor_serial(), None);
+        // Full-length serial (no 0x0A terminator), with padding spaces
+        let mut data = TILIQUA_EDID;
+        data[90..108].copy_from_slice(&[0x0, 0x0, 0x0, 0xff, 0x0,
+            b'S', b'N', b'1', b'2', b'3', b' ', b' ', b' ', b' ', b' ', b' ', b' ', b' ']);
+        data[127] = 0;
+        data[127] = data.iter().fold(0u8, |sum, &b| sum.wrapping_sub(b));
+        let edid = Edid::parse(&data).unwrap();
+        assert_eq!(edid.monitor_serial(), Some("SN123"));
+        assert_eq!(edid.monitor_name(), Some("ZL720X720"));
+    }
+
+    #[test]
+    fn test_timing_rejection() {
+        const PCLK_MIN_KHZ: u32 = 24_000;
+        const PCLK_MAX_KHZ: u32 = 74_250;
+        let edid = Edid::parse(&TILIQUA_EDID).unwrap();
+        let Descriptor::DetailedTiming(mut desc) = edid.descriptors[0] else {
+            panic!("expected a detailed timing descriptor");
+        };
+        assert_eq!(desc.check_supported(PCLK_MIN_KHZ, PCLK_MAX_KHZ), Ok(()));
+        // 1080p60 preferred timing of a typical desktop monitor
+        desc.pixel_clock_khz = 148_500;
+        let reason = desc.check_supported(PCLK_MIN_KHZ, PCLK_MAX_KHZ).unwrap_err();
+        assert_eq!(reason, TimingRejection::PixelClockTooFast);
+        let reason_str: &'static str = reason.into();
+        assert_eq!(reason_str, "pixel clock too fast");
+        desc.pixel_clock_khz = 12_000;
+        assert_eq!(desc.check_supported(PCLK_MIN_KHZ, PCLK_MAX_KHZ),
+                   Err(TimingRejection::PixelClockTooSlow));
+    }
+
+    #[test]
+    fn test_clamp_to_supported() {
+        const PCLK_MIN_KHZ: u32 = 24_000;
+        const PCLK_MAX_KHZ: u32 = 74_250;
+        let mut edid = Edid::parse(&TILIQUA_EDID).unwrap();
+
+        // Usable preferred timing is passed through as-is.
+        let preferred = *edid.preferred_timing().unwrap();
+        assert_eq!((preferred.horizontal_active, preferred.vertical_active), (720, 720));
+        let modeline = edid.clamp_to_supported(PCLK_MIN_KHZ, PCLK_MAX_KHZ).unwrap();
+        assert_eq!(Some(modeline), preferred.to_modeline());
+
+        // A display that only advertises 2160p60 (594MHz) gets 1080p30 CVT-RB.
+        let Descriptor::DetailedTiming(ref mut desc) = edid.descriptors[0] else {
+            panic!("expected a detailed timing descriptor");
+        };
+        desc.horizontal_active = 3840;
+        desc.horizontal_blanking = 560;
+        desc.vertical_active = 2160;
+        desc.vertical_blanking = 90;
+        desc.pixel_clock_khz = 594_000;
+        assert!((edid.preferred_timing().unwrap().refresh_rate() - 60.0).abs() < 0.01);
+        let modeline = edid.clamp_to_supported(PCLK_MIN_KHZ, PCLK_MAX_KHZ).unwrap();
+        assert_eq!((modeline.h_active, modeline.h_total), (1920, 2000));
+        assert_eq!((modeline.v_active, modeline.v_total), (1080, 1096));
+        assert_eq!(modeline.pixel_clk_khz(), 65_750);
+        assert!(modeline.is_consistent());
+
+        // Interlaced timings are rejected, but their resolution is kept.
+        let Descriptor::DetailedTiming(ref mut desc) = edid.descriptors[0] else {
+            panic!("expected a detailed timing descriptor");
+        };
+        desc.horizontal_active = 1920;
+        desc.horizontal_blanking = 280;
+        desc.vertical_active = 540;
+        desc.vertical_blanking = 22;
+        desc.pixel_clock_khz = 74_250;
+        desc.features.interlaced = true;
+        let modeline = edid.clamp_to_supported(PCLK_MIN_KHZ, PCLK_MAX_KHZ).unwrap();
+        assert_eq!((modeline.h_active, modeline.v_active), (1920, 1080));
+
+        // Nothing fits.
+        assert_eq!(edid.clamp_to_supported(PCLK_MIN_KHZ, PCLK_MIN_KHZ), None);
+    }
 }
@@ -4,10 +4,27 @@ use tiliqua_hal::eeprom::{EepromDriver, EepromError};
 use crc::{Crc, CRC_32_BZIP2};
 use serde;
 
+use crate::edid::DisplayId;
+use tiliqua_hal::dma_framebuffer::DVIModeline;
+use tiliqua_manifest::N_MANIFESTS;
+
 const EEPROM_CALIBRATION_ADDR: u8 = 0x00;
 const EEPROM_CALIBRATION_SIZE: usize = 0x40;
+// Fixed `EepromConfig` region, also where bootloaders before the config
+// ring kept it. Overlaps the first slot of the bootloader's config ring.
 const EEPROM_CONFIG_ADDR: u8 = 0x40;
-const EEPROM_CONFIG_SIZE: usize = 0x40;
+const EEPROM_CONFIG_SIZE: usize = 0x20;
+/// Config ring used by the bootloader, see `EepromManager::new_ring`.
+pub const EEPROM_CONFIG_RING_ADDR: u8 = 0x40;
+pub const EEPROM_CONFIG_RING_SLOTS: u8 = 3;
+// Fits the largest `ConfigRingEntry` encoding (19 bytes).
+const EEPROM_CONFIG_RING_SLOT_SIZE: usize = 0x14;
+const EEPROM_CRC_CACHE_ADDR: u8 = 0x80;
+const EEPROM_CRC_CACHE_SIZE: usize = 0x10;
+const EEPROM_MODELINE_ADDR: u8 = 0x90;
+const EEPROM_MODELINE_SIZE: usize = 0x30;
+// Only 0x00..0xC0 is writable, the rest holds the factory serial number.
+const EEPROM_WRITABLE_SIZE: usize = 0xC0;
 const CRC_ALGORITHM: Crc<u32> = Crc::<u32>::new(&CRC_32_BZIP2);
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -19,13 +36,203 @@ pub struct EepromCalibration {
     pub fractional_bits: u8,
 }
 
+/// First byte of a stored calibration that starts with a format version.
+/// Legacy (unversioned) calibrations start with the varint encoding of
+/// `adc_scale[0]`, which has its top bit set for any realistic scale.
+const CALIBRATION_MAGIC: u8 = 0x54;
+/// Bump whenever the stored layout of `EepromCalibration` changes.
+pub const CALIBRATION_FORMAT_VERSION: u8 = 1;
+
+/// `EepromCalibration` as it is stored, behind a magic byte and format version.
+#[derive(Serialize, Deserialize)]
+struct VersionedCalibration {
+    magic: u8,
+    format_version: u8,
+    cal: EepromCalibration,
+}
+
+/// Autoboot countdown used when none has been stored.
+pub const DEFAULT_AUTOBOOT_DELAY_MS: u16 = 5000;
+
+/// Order in which the bootloader lists slots, and which it leaves out.
+/// This only affects the menu, slots are always referred to by their
+/// physical index elsewhere (e.g. `EepromConfig::last_boot_slot`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SlotOrder {
+    /// Physical slot shown on each row, hidden slots included.
+    order: [u8; N_MANIFESTS],
+    /// Bit `n` set if physical slot `n` is hidden.
+    hidden: u8,
+}
+
+impl Default for SlotOrder {
+    fn default() -> Self {
+        Self {
+            order: core::array::from_fn(|n| n as u8),
+            hidden: 0,
+        }
+    }
+}
+
+impl SlotOrder {
+    /// The stored order, or the default one if it is not a permutation
+    /// of all slots or hides every slot.
+    pub fn validated(self) -> Self {
+        let mut seen = 0u32;
+        for slot in self.order {
+            if (slot as usize) < N_MANIFESTS {
+                seen |= 1 << slot;
+            }
+        }
+        if seen != (1 << N_MANIFESTS) - 1 || self.visible().next().is_none() {
+            return Self::default();
+        }
+        self
+    }
+
+    pub fn is_hidden(&self, slot: usize) -> bool {
+        self.hidden & (1 << slot) != 0
+    }
+
+    /// Visible physical slots, in the order they are listed.
+    pub fn visible(&self) -> impl Iterator<Item = usize> + '_ {
+        self.order.iter()
+            .map(|slot| *slot as usize)
+            .filter(|slot| !self.is_hidden(*slot))
+    }
+
+    /// Show or hide `slot`. The last visible slot can't be hidden, in
+    /// which case this returns `false`.
+    pub fn set_hidden(&mut self, slot: usize, hidden: bool) -> bool {
+        if hidden && !self.is_hidden(slot) && self.visible().count() == 1 {
+            return false;
+        }
+        if hidden {
+            self.hidden |= 1 << slot;
+        } else {
+            self.hidden &= !(1 << slot);
+        }
+        true
+    }
+
+    /// Swap `slot` with the visible slot listed before it, if any.
+    pub fn move_up(&mut self, slot: usize) {
+        if let Some(pos) = self.position(slot) {
+            if let Some(other) = (0..pos).rev().find(|p| !self.is_hidden(self.order[*p] as usize)) {
+                self.order.swap(pos, other);
+            }
+        }
+    }
+
+    /// Swap `slot` with the visible slot listed after it, if any.
+    pub fn move_down(&mut self, slot: usize) {
+        if let Some(pos) = self.position(slot) {
+            if let Some(other) = (pos+1..N_MANIFESTS).find(|p| !self.is_hidden(self.order[*p] as usize)) {
+                self.order.swap(pos, other);
+            }
+        }
+    }
+
+    fn position(&self, slot: usize) -> Option<usize> {
+        self.order.iter().position(|s| *s as usize == slot)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EepromConfig {
     pub last_boot_slot: Option<u8>,
+    pub autoboot_delay_ms: u16,
+    pub slot_order: SlotOrder,
+}
+
+impl Default for EepromConfig {
+    fn default() -> Self {
+        Self {
+            last_boot_slot: None,
+            autoboot_delay_ms: DEFAULT_AUTOBOOT_DELAY_MS,
+            slot_order: SlotOrder::default(),
+        }
+    }
+}
+
+/// `EepromConfig` as written by bootloaders before the autoboot delay
+/// was configurable.
+#[derive(Deserialize)]
+struct EepromConfigV0 {
+    last_boot_slot: Option<u8>,
+}
+
+impl From<EepromConfigV0> for EepromConfig {
+    fn from(v0: EepromConfigV0) -> Self {
+        Self {
+            last_boot_slot: v0.last_boot_slot,
+            ..Default::default()
+        }
+    }
+}
+
+/// `EepromConfig` as written by bootloaders before slots could be
+/// reordered or hidden.
+#[derive(Deserialize)]
+struct EepromConfigV1 {
+    last_boot_slot: Option<u8>,
+    autoboot_delay_ms: u16,
+}
+
+impl From<EepromConfigV1> for EepromConfig {
+    fn from(v1: EepromConfigV1) -> Self {
+        Self {
+            last_boot_slot: v1.last_boot_slot,
+            autoboot_delay_ms: v1.autoboot_delay_ms,
+            ..Default::default()
+        }
+    }
+}
+
+/// Last slot that passed CRC validation, so booting it again can skip
+/// recomputing CRCs over every region in the slot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EepromCrcCache {
+    pub slot: u8,
+    /// CRC of the raw slot manifest, which changes whenever the slot is reflashed.
+    pub manifest_crc: u32,
+    /// CRC over the region CRCs that were validated.
+    pub regions_crc: u32,
+}
+
+/// Custom modeline, only applied when the same display is attached.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EepromModeline {
+    pub display: DisplayId,
+    pub modeline: DVIModeline,
+}
+
+/// One slot of a config ring. Of all slots holding a valid entry, the one
+/// with the latest `seq` is the current config. `seq` wraps, which is fine
+/// as long as the ring has fewer than 128 slots.
+#[derive(Serialize, Deserialize)]
+struct ConfigRingEntry {
+    seq: u8,
+    config: EepromConfig,
+}
+
+/// Config writes rotate through `n_slots` consecutive slots from `base`,
+/// so frequent config updates are spread over more EEPROM cells.
+#[derive(Clone, Copy)]
+struct ConfigRing {
+    base: u8,
+    n_slots: u8,
+}
+
+impl ConfigRing {
+    fn slot_addr(&self, slot: u8) -> u8 {
+        self.base + slot * EEPROM_CONFIG_RING_SLOT_SIZE as u8
+    }
 }
 
 pub struct EepromManager<I2C> {
     eeprom: EepromDriver<I2C>,
+    config_ring: Option<ConfigRing>,
 }
 
 impl<I2C> EepromManager<I2C>
@@ -35,6 +242,21 @@ where
     pub fn new(i2c: I2C) -> Self {
         Self {
             eeprom: EepromDriver::new(i2c),
+            config_ring: None,
+        }
+    }
+
+    /// Like `new`, but `EepromConfig` is kept in a ring of `n_slots` slots
+    /// (0x14 bytes each) starting at `base`, instead of its fixed region.
+    /// Until the ring is first written, the fixed region is read instead.
+    pub fn new_ring(i2c: I2C, base: u8, n_slots: u8) -> Self {
+        let end = base as usize + n_slots as usize * EEPROM_CONFIG_RING_SLOT_SIZE;
+        if n_slots == 0 || n_slots >= 128 || end > EEPROM_WRITABLE_SIZE {
+            panic!("bad config ring");
+        }
+        Self {
+            eeprom: EepromDriver::new(i2c),
+            config_ring: Some(ConfigRing { base, n_slots }),
         }
     }
 
@@ -62,19 +284,445 @@ where
         self.eeprom.write_bytes(addr, serialized)
     }
 
+    /// Calibrations of an unknown format version are `InvalidData`. Without
+    /// the magic byte, the calibration is read in the legacy (unversioned)
+    /// layout, which is replaced by the current one on the next write.
     pub fn read_calibration(&mut self) -> Result<EepromCalibration, EepromError<I2C::Error>> {
-        self.read_data::<EepromCalibration, EEPROM_CALIBRATION_SIZE>(EEPROM_CALIBRATION_ADDR)
+        match self.read_data::<VersionedCalibration, EEPROM_CALIBRATION_SIZE>(EEPROM_CALIBRATION_ADDR) {
+            Ok(stored) if stored.magic == CALIBRATION_MAGIC => {
+                if stored.format_version == CALIBRATION_FORMAT_VERSION {
+                    Ok(stored.cal)
+                } else {
+                    Err(EepromError::InvalidData)
+                }
+            }
+            _ => self.read_data::<EepromCalibration, EEPROM_CALIBRATION_SIZE>(EEPROM_CALIBRATION_ADDR),
+        }
     }
 
     pub fn write_calibration(&mut self, cal_data: &EepromCalibration) -> Result<(), EepromError<I2C::Error>> {
-        self.write_data::<EepromCalibration, EEPROM_CALIBRATION_SIZE>(EEPROM_CALIBRATION_ADDR, cal_data)
+        let stored = VersionedCalibration {
+            magic: CALIBRATION_MAGIC,
+            format_version: CALIBRATION_FORMAT_VERSION,
+            cal: cal_data.clone(),
+        };
+        self.write_data::<VersionedCalibration, EEPROM_CALIBRATION_SIZE>(EEPROM_CALIBRATION_ADDR, &stored)
+    }
+
+    /// Slot and contents of the latest valid entry in the ring, if any.
+    fn ring_latest(&mut self, ring: ConfigRing) -> Result<Option<(u8, ConfigRingEntry)>, EepromError<I2C::Error>> {
+        let mut latest: Option<(u8, ConfigRingEntry)> = None;
+        for slot in 0..ring.n_slots {
+            match self.read_data::<ConfigRingEntry, EEPROM_CONFIG_RING_SLOT_SIZE>(ring.slot_addr(slot)) {
+                Ok(entry) => {
+                    if latest.as_ref().is_none_or(|(_, l)| entry.seq.wrapping_sub(l.seq) as i8 > 0) {
+                        latest = Some((slot, entry));
+                    }
+                }
+                // Never written, or torn by a power loss during the write.
+                Err(EepromError::InvalidData) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(latest)
     }
 
     pub fn read_config(&mut self) -> Result<EepromConfig, EepromError<I2C::Error>> {
-        self.read_data::<EepromConfig, EEPROM_CONFIG_SIZE>(EEPROM_CONFIG_ADDR)
+        let latest = match self.config_ring {
+            Some(ring) => self.ring_latest(ring)?.map(|(_, entry)| entry.config),
+            None => None,
+        };
+        let config = match latest {
+            Some(config) => config,
+            None => self.read_data::<EepromConfig, EEPROM_CONFIG_SIZE>(EEPROM_CONFIG_ADDR).or_else(|_| {
+                self.read_data::<EepromConfigV1, EEPROM_CONFIG_SIZE>(EEPROM_CONFIG_ADDR)
+                    .map(EepromConfig::from)
+            }).or_else(|_| {
+                self.read_data::<EepromConfigV0, EEPROM_CONFIG_SIZE>(EEPROM_CONFIG_ADDR)
+                    .map(EepromConfig::from)
+            })?,
+        };
+        Ok(EepromConfig {
+            slot_order: config.slot_order.validated(),
+            ..config
+        })
     }
 
     pub fn write_config(&mut self, config: &EepromConfig) -> Result<(), EepromError<I2C::Error>> {
+        if let Some(ring) = self.config_ring {
+            // Overwrite the oldest slot, which is the one after the latest.
+            let (slot, seq) = match self.ring_latest(ring)? {
+                Some((slot, latest)) => ((slot + 1) % ring.n_slots, latest.seq.wrapping_add(1)),
+                None => (0, 0),
+            };
+            let entry = ConfigRingEntry { seq, config: config.clone() };
+            return self.write_data::<ConfigRingEntry, EEPROM_CONFIG_RING_SLOT_SIZE>(ring.slot_addr(slot), &entry);
+        }
         self.write_data::<EepromConfig, EEPROM_CONFIG_SIZE>(EEPROM_CONFIG_ADDR, config)
     }
+
+    /// Modify some fields of the stored config, keeping the others. Starts
+    /// from the defaults if nothing valid is stored.
+    pub fn update_config<F>(&mut self, f: F) -> Result<(), EepromError<I2C::Error>>
+    where
+        F: FnOnce(&mut EepromConfig),
+    {
+        let mut config = self.read_config().unwrap_or_default();
+        f(&mut config);
+        self.write_config(&config)
+    }
+
+    pub fn read_crc_cache(&mut self) -> Result<EepromCrcCache, EepromError<I2C::Error>> {
+        self.read_data::<EepromCrcCache, EEPROM_CRC_CACHE_SIZE>(EEPROM_CRC_CACHE_ADDR)
+    }
+
+    pub fn write_crc_cache(&mut self, cache: &EepromCrcCache) -> Result<(), EepromError<I2C::Error>> {
+        self.write_data::<EepromCrcCache, EEPROM_CRC_CACHE_SIZE>(EEPROM_CRC_CACHE_ADDR, cache)
+    }
+
+    /// Run `validate` unless `entry` is what was cached by the last successful
+    /// validation (and `force` is not set). On success, `entry` is cached.
+    /// Returns whether `validate` was run.
+    pub fn validate_cached<E, F>(&mut self, entry: &EepromCrcCache, force: bool, validate: F) -> Result<bool, E>
+    where
+        F: FnOnce() -> Result<(), E>,
+    {
+        if !force && self.read_crc_cache().is_ok_and(|cached| cached == *entry) {
+            return Ok(false);
+        }
+        validate()?;
+        // A failed write only costs a revalidation on the next boot.
+        self.write_crc_cache(entry).ok();
+        Ok(true)
+    }
+
+    pub fn read_modeline(&mut self) -> Result<EepromModeline, EepromError<I2C::Error>> {
+        self.read_data::<EepromModeline, EEPROM_MODELINE_SIZE>(EEPROM_MODELINE_ADDR)
+    }
+
+    pub fn write_modeline(&mut self, modeline: &EepromModeline) -> Result<(), EepromError<I2C::Error>> {
+        self.write_data::<EepromModeline, EEPROM_MODELINE_SIZE>(EEPROM_MODELINE_ADDR, modeline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_hal::i2c::{ErrorType, Operation};
+    use tiliqua_hal::dma_framebuffer::ModelineField;
+
+    /// Byte-addressed EEPROM behind an I2C bus, as seen by `EepromDriver`.
+    struct FakeEeprom {
+        mem: [u8; 256],
+    }
+
+    impl ErrorType for FakeEeprom {
+        type Error = core::convert::Infallible;
+    }
+
+    impl I2c for FakeEeprom {
+        fn transaction(&mut self, _address: u8, operations: &mut [Operation<'_>])
+            -> Result<(), Self::Error> {
+            let mut addr = 0usize;
+            for op in operations {
+                match op {
+                    Operation::Write(bytes) => {
+                        addr = bytes[0] as usize;
+                        for (i, b) in bytes[1..].iter().enumerate() {
+                            self.mem[addr + i] = *b;
+                        }
+                    }
+                    Operation::Read(buffer) => {
+                        let n = buffer.len();
+                        buffer.copy_from_slice(&self.mem[addr..addr + n]);
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_modeline_roundtrip() {
+        let mut manager = EepromManager::new(FakeEeprom { mem: [0xFF; 256] });
+        assert!(matches!(manager.read_modeline(), Err(EepromError::InvalidData)));
+
+        // Worst case (largest) encoding must still fit in its region.
+        let mut modeline = DVIModeline::default();
+        for field in [ModelineField::HActive, ModelineField::VActive] {
+            modeline.adjust(field, u16::MAX as i32);
+        }
+        let stored = EepromModeline {
+            display: DisplayId {
+                manufacturer_id: [0x4c, 0x2d],
+                product_code: 0x0f41,
+                serial_number: u32::MAX,
+            },
+            modeline,
+        };
+        manager.write_config(&EepromConfig { last_boot_slot: Some(3), ..Default::default() }).unwrap();
+        manager.write_modeline(&stored).unwrap();
+        assert_eq!(manager.read_modeline().unwrap(), stored);
+        // Neighbouring regions are untouched.
+        assert_eq!(manager.read_config().unwrap().last_boot_slot, Some(3));
+    }
+
+    #[test]
+    fn test_crc_cache() {
+        let mut manager = EepromManager::new(FakeEeprom { mem: [0xFF; 256] });
+        let entry = EepromCrcCache {
+            slot: 2,
+            manifest_crc: 0x1234_5678,
+            regions_crc: 0xdead_beef,
+        };
+        let computed = core::cell::Cell::new(0);
+        let validate = |ok: bool| {
+            computed.set(computed.get() + 1);
+            if ok { Ok(()) } else { Err(()) }
+        };
+
+        // Nothing cached yet.
+        assert_eq!(manager.validate_cached(&entry, false, || validate(true)), Ok(true));
+        // Matching cache entry skips recomputation.
+        assert_eq!(manager.validate_cached(&entry, false, || validate(true)), Ok(false));
+        assert_eq!(computed.get(), 1);
+        // Unless it is forced.
+        assert_eq!(manager.validate_cached(&entry, true, || validate(true)), Ok(true));
+        assert_eq!(computed.get(), 2);
+
+        // Any mismatch forces recomputation.
+        for other in [
+            EepromCrcCache { slot: 3, ..entry.clone() },
+            EepromCrcCache { manifest_crc: 0x1234_5679, ..entry.clone() },
+            EepromCrcCache { regions_crc: 0, ..entry.clone() },
+        ] {
+            manager.write_crc_cache(&entry).unwrap();
+            let before = computed.get();
+            assert_eq!(manager.validate_cached(&other, false, || validate(true)), Ok(true));
+            assert_eq!(computed.get(), before + 1);
+        }
+
+        // Failed validations are not cached.
+        manager.write_crc_cache(&entry).unwrap();
+        let bad = EepromCrcCache { slot: 5, ..entry.clone() };
+        assert_eq!(manager.validate_cached(&bad, false, || validate(false)), Err(()));
+        assert_eq!(manager.read_crc_cache().unwrap(), entry);
+        assert_eq!(manager.validate_cached(&bad, false, || validate(false)), Err(()));
+    }
+
+    #[test]
+    fn test_config_legacy() {
+        #[derive(Serialize)]
+        struct LegacyConfig {
+            last_boot_slot: Option<u8>,
+        }
+
+        let mut manager = EepromManager::new(FakeEeprom { mem: [0xFF; 256] });
+        manager.write_data::<_, EEPROM_CONFIG_SIZE>(EEPROM_CONFIG_ADDR,
+            &LegacyConfig { last_boot_slot: Some(2) }).unwrap();
+        let config = manager.read_config().unwrap();
+        assert_eq!(config.last_boot_slot, Some(2));
+        assert_eq!(config.autoboot_delay_ms, DEFAULT_AUTOBOOT_DELAY_MS);
+
+        // Updating one field keeps the others.
+        manager.update_config(|c| c.autoboot_delay_ms = 0).unwrap();
+        manager.update_config(|c| c.last_boot_slot = None).unwrap();
+        let config = manager.read_config().unwrap();
+        assert_eq!(config.last_boot_slot, None);
+        assert_eq!(config.autoboot_delay_ms, 0);
+        manager.update_config(|c| c.last_boot_slot = Some(7)).unwrap();
+        assert_eq!(manager.read_config().unwrap().autoboot_delay_ms, 0);
+    }
+
+    #[test]
+    fn test_config_v1() {
+        #[derive(Serialize)]
+        struct ConfigV1 {
+            last_boot_slot: Option<u8>,
+            autoboot_delay_ms: u16,
+        }
+
+        let mut manager = EepromManager::new(FakeEeprom { mem: [0xFF; 256] });
+        manager.write_data::<_, EEPROM_CONFIG_SIZE>(EEPROM_CONFIG_ADDR,
+            &ConfigV1 { last_boot_slot: Some(4), autoboot_delay_ms: 1500 }).unwrap();
+        let config = manager.read_config().unwrap();
+        assert_eq!(config.last_boot_slot, Some(4));
+        assert_eq!(config.autoboot_delay_ms, 1500);
+        assert_eq!(config.slot_order, SlotOrder::default());
+
+        // Worst case (largest) encoding must still fit in its region.
+        let mut slot_order = SlotOrder::default();
+        slot_order.order.reverse();
+        slot_order.set_hidden(0, true);
+        manager.update_config(|c| {
+            c.last_boot_slot = Some(7);
+            c.autoboot_delay_ms = u16::MAX;
+            c.slot_order = slot_order.clone();
+        }).unwrap();
+        let config = manager.read_config().unwrap();
+        assert_eq!(config.autoboot_delay_ms, u16::MAX);
+        assert_eq!(config.slot_order, slot_order);
+    }
+
+    #[test]
+    fn test_slot_order() {
+        let mut order = SlotOrder::default();
+        assert!(order.visible().eq(0..N_MANIFESTS));
+
+        order.move_up(0);
+        order.move_down(7);
+        assert!(order.visible().eq(0..N_MANIFESTS));
+        order.move_up(3);
+        order.move_down(0);
+        assert!(order.visible().eq([1, 0, 3, 2, 4, 5, 6, 7]));
+
+        // Hidden slots are skipped, also when moving past them.
+        assert!(order.set_hidden(3, true));
+        assert!(order.set_hidden(5, true));
+        assert!(order.visible().eq([1, 0, 2, 4, 6, 7]));
+        order.move_up(2);
+        assert!(order.visible().eq([1, 2, 0, 4, 6, 7]));
+        order.move_down(4);
+        assert!(order.visible().eq([1, 2, 0, 6, 4, 7]));
+        assert!(order.set_hidden(3, false));
+        assert!(order.visible().eq([1, 2, 3, 0, 6, 4, 7]));
+
+        // The last visible slot can't be hidden.
+        for slot in 0..N_MANIFESTS - 1 {
+            assert!(order.set_hidden(slot, true));
+        }
+        assert!(!order.set_hidden(7, true));
+        assert!(order.visible().eq([7]));
+
+        // Corrupt orders fall back to the default.
+        assert_eq!(order.clone().validated(), order);
+        let mut bad = SlotOrder::default();
+        bad.order[2] = 3;
+        assert_eq!(bad.validated(), SlotOrder::default());
+        let bad = SlotOrder { hidden: 0xFF, ..Default::default() };
+        assert_eq!(bad.validated(), SlotOrder::default());
+    }
+
+    #[test]
+    fn test_config_ring() {
+        const BASE: u8 = EEPROM_CONFIG_RING_ADDR;
+        const N_SLOTS: u8 = EEPROM_CONFIG_RING_SLOTS;
+        let ring = |mem| EepromManager::new_ring(FakeEeprom { mem }, BASE, N_SLOTS);
+        let mut manager = ring([0xFF; 256]);
+        assert!(matches!(manager.read_config(), Err(EepromError::InvalidData)));
+
+        // Enough writes for the sequence number to wrap.
+        for n in 0..600u32 {
+            let config = EepromConfig {
+                last_boot_slot: Some((n % 8) as u8),
+                autoboot_delay_ms: n as u16 * 100,
+                ..Default::default()
+            };
+            manager.write_config(&config).unwrap();
+            let read = manager.read_config().unwrap();
+            assert_eq!(read.last_boot_slot, config.last_boot_slot);
+            assert_eq!(read.autoboot_delay_ms, config.autoboot_delay_ms);
+            // Writes go round the slots in order.
+            let addr = BASE + (n % N_SLOTS as u32) as u8 * EEPROM_CONFIG_RING_SLOT_SIZE as u8;
+            let entry = manager.read_data::<ConfigRingEntry, EEPROM_CONFIG_RING_SLOT_SIZE>(addr).unwrap();
+            assert_eq!(entry.seq, n as u8);
+        }
+
+        // Nothing is written outside the ring.
+        let end = BASE as usize + N_SLOTS as usize * EEPROM_CONFIG_RING_SLOT_SIZE;
+        assert!(end <= EEPROM_CRC_CACHE_ADDR as usize);
+        let mut mem = [0u8; EEPROM_WRITABLE_SIZE];
+        manager.eeprom.read_bytes(0, &mut mem).unwrap();
+        assert!(mem[..BASE as usize].iter().chain(&mem[end..]).all(|b| *b == 0xFF));
+
+        // A torn write to the latest slot falls back to the one before it.
+        let torn = BASE + 2 * EEPROM_CONFIG_RING_SLOT_SIZE as u8;
+        manager.eeprom.write_bytes(torn, &[!mem[torn as usize]]).unwrap();
+        assert_eq!(manager.read_config().unwrap().autoboot_delay_ms, 59800);
+        // And the next write replaces the torn slot.
+        manager.update_config(|c| c.last_boot_slot = None).unwrap();
+        let entry = manager.read_data::<ConfigRingEntry, EEPROM_CONFIG_RING_SLOT_SIZE>(torn).unwrap();
+        assert_eq!(entry.seq, 599u32 as u8);
+        assert_eq!(entry.config.last_boot_slot, None);
+        assert_eq!(entry.config.autoboot_delay_ms, 59800);
+
+        // Worst case (largest) encoding must still fit in a slot.
+        let mut slot_order = SlotOrder::default();
+        slot_order.order.reverse();
+        slot_order.set_hidden(0, true);
+        manager.update_config(|c| {
+            c.last_boot_slot = Some(7);
+            c.autoboot_delay_ms = u16::MAX;
+            c.slot_order = slot_order.clone();
+        }).unwrap();
+        assert_eq!(manager.read_config().unwrap().slot_order, slot_order);
+    }
+
+    #[test]
+    fn test_config_ring_migration() {
+        let mut manager = EepromManager::new_ring(FakeEeprom { mem: [0xFF; 256] },
+            EEPROM_CONFIG_RING_ADDR, EEPROM_CONFIG_RING_SLOTS);
+        // Config left in the fixed region by an older bootloader.
+        manager.write_data::<_, EEPROM_CONFIG_SIZE>(EEPROM_CONFIG_ADDR,
+            &EepromConfig { autoboot_delay_ms: 1234, ..Default::default() }).unwrap();
+        manager.write_crc_cache(&EepromCrcCache { slot: 1, manifest_crc: 2, regions_crc: 3 }).unwrap();
+        assert_eq!(manager.read_config().unwrap().autoboot_delay_ms, 1234);
+        // The first ring write takes over from it, keeping its fields.
+        manager.update_config(|c| c.last_boot_slot = Some(2)).unwrap();
+        for _ in 0..EEPROM_CONFIG_RING_SLOTS {
+            manager.update_config(|c| c.autoboot_delay_ms += 1).unwrap();
+        }
+        let config = manager.read_config().unwrap();
+        assert_eq!(config.last_boot_slot, Some(2));
+        assert_eq!(config.autoboot_delay_ms, 1234 + EEPROM_CONFIG_RING_SLOTS as u16);
+        // Neighbouring regions are untouched.
+        assert_eq!(manager.read_crc_cache().unwrap().regions_crc, 3);
+    }
+
+    fn test_calibration() -> EepromCalibration {
+        // Typical scales are close to 1.0 with 15 fractional bits.
+        EepromCalibration {
+            adc_scale: [-32900, -32800, -32700, -32600],
+            adc_zero:  [-300, 120, 50, -80],
+            dac_scale: [32100, 32200, 32300, 32400],
+            dac_zero:  [400, -200, 10, 0],
+            fractional_bits: 15,
+        }
+    }
+
+    #[test]
+    fn test_calibration_versions() {
+        let cal = test_calibration();
+        let mut manager = EepromManager::new(FakeEeprom { mem: [0xFF; 256] });
+        assert!(matches!(manager.read_calibration(), Err(EepromError::InvalidData)));
+
+        // Current format.
+        manager.write_calibration(&cal).unwrap();
+        let mut header = [0u8; 2];
+        manager.eeprom.read_bytes(EEPROM_CALIBRATION_ADDR, &mut header).unwrap();
+        assert_eq!(header, [CALIBRATION_MAGIC, CALIBRATION_FORMAT_VERSION]);
+        assert_eq!(manager.read_calibration().unwrap(), cal);
+
+        // Legacy (unversioned) calibrations are still read.
+        manager.write_data::<_, EEPROM_CALIBRATION_SIZE>(EEPROM_CALIBRATION_ADDR, &cal).unwrap();
+        manager.eeprom.read_bytes(EEPROM_CALIBRATION_ADDR, &mut header).unwrap();
+        assert_ne!(header[0], CALIBRATION_MAGIC);
+        assert_eq!(manager.read_calibration().unwrap(), cal);
+
+        // Unknown format versions are rejected, even if they decode.
+        manager.write_data::<_, EEPROM_CALIBRATION_SIZE>(EEPROM_CALIBRATION_ADDR, &VersionedCalibration {
+            magic: CALIBRATION_MAGIC,
+            format_version: CALIBRATION_FORMAT_VERSION + 1,
+            cal: cal.clone(),
+        }).unwrap();
+        assert!(matches!(manager.read_calibration(), Err(EepromError::InvalidData)));
+
+        // Corrupted calibrations in either format are rejected.
+        manager.write_calibration(&cal).unwrap();
+        manager.eeprom.write_bytes(EEPROM_CALIBRATION_ADDR + 5, &[0x00]).unwrap();
+        assert!(matches!(manager.read_calibration(), Err(EepromError::InvalidData)));
+        manager.write_data::<_, EEPROM_CALIBRATION_SIZE>(EEPROM_CALIBRATION_ADDR, &cal).unwrap();
+        manager.eeprom.write_bytes(EEPROM_CALIBRATION_ADDR + 5, &[0x00]).unwrap();
+        assert!(matches!(manager.read_calibration(), Err(EepromError::InvalidData)));
+    }
 }
@@ -0,0 +1,111 @@
+//! Keeping several grain players (see `tiliqua_hal::grain_player`) in step.
+//!
+//! A grain player only reports the delay it is reading from, and can only
+//! be restarted from the start of its grain. So 'sync' here always means
+//! restarting the followers at the moment the leader restarts, rather than
+//! seeking them to a matching phase, which would assume equal grain lengths.
+
+use serde_derive::{Serialize, Deserialize};
+use strum_macros::{EnumIter, IntoStaticStr};
+
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum SyncMode {
+    /// Every channel plays on its own.
+    #[default]
+    Independent,
+    /// Followers restart whenever the leader loops or is retriggered.
+    SyncToCh0,
+    /// Followers restart on every rising edge of the leader's gate.
+    RetrigAll,
+}
+
+/// Snapshot of one grain player, taken once per control tick.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct GrainPhase {
+    /// Grain start, as a delay.
+    pub start: u32,
+    /// Grain length in samples.
+    pub length: u32,
+    /// Delay the grain player is reading from.
+    pub position: u32,
+}
+
+impl GrainPhase {
+    /// Whether the grain jumped back to its start (looped, or was retriggered)
+    /// since `prev`. During playback the position moves by a small fraction
+    /// of the grain per tick, in either direction, so any jump of more than
+    /// half the grain is a restart. Changes to the grain itself don't count.
+    pub fn restarted_since(&self, prev: &GrainPhase) -> bool {
+        self.length > 0 &&
+        self.start == prev.start &&
+        self.length == prev.length &&
+        self.position.abs_diff(prev.position) > self.length / 2
+    }
+}
+
+/// Whether a follower should be restarted this tick, given the previous
+/// and current phase of the leader and the follower, and whether the leader's
+/// gate had a rising edge.
+///
+/// A follower that restarted by itself in the same tick as the leader (for
+/// example because both grains are the same length) is left alone.
+pub fn restart_follower(mode: SyncMode, leader: (&GrainPhase, &GrainPhase),
+                        follower: (&GrainPhase, &GrainPhase), leader_gate_rising: bool) -> bool {
+    match mode {
+        SyncMode::Independent => false,
+        SyncMode::SyncToCh0 => leader.1.restarted_since(leader.0) &&
+                               !follower.1.restarted_since(follower.0),
+        SyncMode::RetrigAll => leader_gate_rising,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phase(start: u32, length: u32, position: u32) -> GrainPhase {
+        GrainPhase { start, length, position }
+    }
+
+    #[test]
+    fn test_restarted_since() {
+        // Forward: the delay falls from `start` towards `start - length`, then wraps.
+        let prev = phase(10000, 4000, 6200);
+        assert!(phase(10000, 4000, 9900).restarted_since(&prev));
+        assert!(!phase(10000, 4000, 6000).restarted_since(&prev));
+        // Reverse: the delay rises towards `start`, then wraps.
+        let prev = phase(10000, 4000, 9800);
+        assert!(phase(10000, 4000, 6100).restarted_since(&prev));
+        assert!(!phase(10000, 4000, 9990).restarted_since(&prev));
+        // Stopped, or never played.
+        assert!(!phase(10000, 4000, 6200).restarted_since(&phase(10000, 4000, 6200)));
+        assert!(!phase(0, 0, 0).restarted_since(&phase(0, 0, 0)));
+        // Moving the grain also moves the position, but isn't a restart.
+        assert!(!phase(20000, 4000, 20000).restarted_since(&phase(10000, 4000, 6200)));
+        assert!(!phase(10000, 8000, 9900).restarted_since(&phase(10000, 4000, 6200)));
+    }
+
+    #[test]
+    fn test_restart_follower() {
+        let leader_playing = (&phase(10000, 4000, 7000), &phase(10000, 4000, 6760));
+        let leader_looped = (&phase(10000, 4000, 6100), &phase(10000, 4000, 9860));
+        // A shorter follower, part way through its grain.
+        let short = (&phase(30000, 1000, 29500), &phase(30000, 1000, 29260));
+        // A longer follower, which would never reach its end.
+        let long = (&phase(50000, 9000, 44000), &phase(50000, 9000, 43760));
+        // An equal length follower, which looped at the same time.
+        let equal = (&phase(20000, 4000, 16100), &phase(20000, 4000, 19860));
+
+        for follower in [short, long, equal] {
+            for rising in [false, true] {
+                assert!(!restart_follower(SyncMode::Independent, leader_looped, follower, rising));
+                assert!(!restart_follower(SyncMode::SyncToCh0, leader_playing, follower, rising));
+                assert_eq!(restart_follower(SyncMode::RetrigAll, leader_playing, follower, rising), rising);
+            }
+        }
+        assert!(restart_follower(SyncMode::SyncToCh0, leader_looped, short, false));
+        assert!(restart_follower(SyncMode::SyncToCh0, leader_looped, long, false));
+        assert!(!restart_follower(SyncMode::SyncToCh0, leader_looped, equal, false));
+    }
+}
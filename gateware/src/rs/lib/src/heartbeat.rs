@@ -0,0 +1,71 @@
+use core::fmt;
+
+/// ECP5 DTR code to (approximate) die temperature in degrees C.
+///
+/// From Table 4.3 in FPGA-TN-02210-1-4
+/// "Power Consumption and Management for ECP5 and ECP5-5G Devices"
+pub fn die_temperature_celsius(code: u8) -> i16 {
+    const CODE_TO_CELSIUS: [i16; 64] = [
+        -58, -56, -54, -52, -45, -44, -43, -42,
+        -41, -40, -39, -38, -37, -36, -30, -20,
+        -10,  -4,   0,   4,  10,  21,  22,  23,
+         24,  25,  26,  27,  28,  29,  40,  50,
+         60,  70,  76,  80,  81,  82,  83,  84,
+         85,  86,  87,  88,  89,  95,  96,  97,
+         98,  99, 100, 101, 102, 103, 104, 105,
+        106, 107, 108, 116, 120, 124, 128, 132
+    ];
+    CODE_TO_CELSIUS[(code & 0x3F) as usize]
+}
+
+/// Periodic status message, so a host can tell a bitstream is still alive.
+///
+/// Formatted as a single line of `key=value` fields, which is easy to
+/// pick out of the rest of the log with a regex.
+#[derive(Clone, Copy)]
+pub struct Heartbeat {
+    pub uptime_ms: u32,
+    pub temperature_c: i16,
+    pub underruns: u32,
+}
+
+impl fmt::Display for Heartbeat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "heartbeat uptime_s={}.{:03} temp_c={} underruns={}",
+               self.uptime_ms / 1000, self.uptime_ms % 1000,
+               self.temperature_c, self.underruns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_format() {
+        let hb = Heartbeat {
+            uptime_ms: 3_723_045,
+            temperature_c: die_temperature_celsius(24),
+            underruns: 7,
+        };
+        assert_eq!(format!("{}", hb),
+                   "heartbeat uptime_s=3723.045 temp_c=24 underruns=7");
+
+        let hb = Heartbeat {
+            uptime_ms: 5,
+            temperature_c: die_temperature_celsius(0),
+            underruns: 0,
+        };
+        assert_eq!(format!("{}", hb),
+                   "heartbeat uptime_s=0.005 temp_c=-58 underruns=0");
+
+        // Fits in the fixed-size strings used by the firmware.
+        let hb = Heartbeat {
+            uptime_ms: u32::MAX,
+            temperature_c: i16::MIN,
+            underruns: u32::MAX,
+        };
+        let mut s = heapless::String::<80>::new();
+        assert!(fmt::Write::write_fmt(&mut s, format_args!("{}", hb)).is_ok());
+    }
+}
@@ -0,0 +1,114 @@
+/// What happened since the last `IdleMonitor::update`.
+#[derive(Default, Clone, Copy)]
+pub struct Activity {
+    /// A display is attached (HPD asserted).
+    pub display: bool,
+    pub audio: bool,
+    pub midi: bool,
+    /// Encoder rotated or pressed, or jacks touched.
+    pub user: bool,
+}
+
+impl Activity {
+    fn any(&self) -> bool {
+        self.display || self.audio || self.midi || self.user
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdleTransition {
+    Enter,
+    Exit,
+}
+
+/// Decides when a bitstream may drop into a low-power idle state.
+///
+/// Idle is entered once nothing (including a display) has been active
+/// for `timeout_ms`, and is left as soon as anything becomes active.
+pub struct IdleMonitor {
+    timeout_ms: u32,
+    quiet_ms: u32,
+    idle: bool,
+}
+
+impl IdleMonitor {
+    pub fn new(timeout_ms: u32) -> Self {
+        IdleMonitor {
+            timeout_ms,
+            quiet_ms: 0,
+            idle: false,
+        }
+    }
+
+    pub fn idle(&self) -> bool {
+        self.idle
+    }
+
+    pub fn update(&mut self, elapsed_ms: u32, activity: &Activity) -> Option<IdleTransition> {
+        if activity.any() {
+            self.quiet_ms = 0;
+            if self.idle {
+                self.idle = false;
+                return Some(IdleTransition::Exit);
+            }
+            return None;
+        }
+        self.quiet_ms = self.quiet_ms.saturating_add(elapsed_ms);
+        if !self.idle && self.quiet_ms >= self.timeout_ms {
+            self.idle = true;
+            return Some(IdleTransition::Enter);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_entry_exit() {
+        let quiet = Activity::default();
+        let mut m = IdleMonitor::new(1000);
+
+        // Enters idle only after the full timeout with nothing going on.
+        for _ in 0..99 {
+            assert_eq!(m.update(10, &quiet), None);
+        }
+        assert!(!m.idle());
+        assert_eq!(m.update(10, &quiet), Some(IdleTransition::Enter));
+        assert!(m.idle());
+        assert_eq!(m.update(10, &quiet), None);
+
+        // Any single kind of activity wakes it up immediately.
+        for activity in [
+            Activity { display: true, ..quiet },
+            Activity { audio: true, ..quiet },
+            Activity { midi: true, ..quiet },
+            Activity { user: true, ..quiet },
+        ] {
+            for _ in 0..100 {
+                m.update(10, &quiet);
+            }
+            assert!(m.idle());
+            assert_eq!(m.update(10, &activity), Some(IdleTransition::Exit));
+            assert!(!m.idle());
+        }
+
+        // Activity before the timeout restarts it.
+        for _ in 0..50 {
+            m.update(10, &quiet);
+        }
+        m.update(10, &Activity { midi: true, ..quiet });
+        for _ in 0..99 {
+            assert_eq!(m.update(10, &quiet), None);
+        }
+        assert_eq!(m.update(10, &quiet), Some(IdleTransition::Enter));
+
+        // Never idles with a display attached.
+        let mut m = IdleMonitor::new(1000);
+        for _ in 0..1000 {
+            assert_eq!(m.update(10, &Activity { display: true, ..quiet }), None);
+        }
+    }
+}
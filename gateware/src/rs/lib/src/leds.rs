@@ -4,6 +4,9 @@ const PCA9635_BAR_GREEN: [usize; 6] = [0, 2, 14, 12, 6, 4];
 const PCA9635_BAR_RED:   [usize; 6] = [1, 3, 15, 13, 7, 5];
 const PCA9635_MIDI:      [usize; 2] = [8, 9];
 
+/// Positions on the LED bar, each with a green and a red LED.
+pub const BAR_LEN: usize = 6;
+
 pub fn mobo_pca9635_set_bargraph<T: Options>(
     opts: &T, leds: &mut [u8; 16], toggle: bool) {
     if let Some(n) = opts.selected() {
@@ -39,3 +42,144 @@ pub fn mobo_pca9635_set_midi(leds: &mut [u8; 16], red: u8, green: u8) {
     leds[PCA9635_MIDI[0]] = green;
     leds[PCA9635_MIDI[1]] = red;
 }
+
+/// Level meter on the LED bar, for a single value or a stereo pair.
+///
+/// Levels are 0..1. A single value uses all `BAR_LEN` positions, and the top
+/// one is red. A stereo pair uses the first half of the bar for the left
+/// channel and the second half for the right. Any level at or above 1 is
+/// clipping, and lights all positions of that channel red.
+///
+/// With peak-hold, the highest position reached stays lit for `hold` updates.
+pub struct Bargraph {
+    hold: u32,
+    // Per channel: highest position reached, and updates left to hold it.
+    peaks: [(usize, u32); 2],
+}
+
+impl Bargraph {
+    pub fn new(hold: u32) -> Self {
+        Self { hold, peaks: [(0, 0); 2] }
+    }
+
+    pub fn set_mono(&mut self, leds: &mut [u8; 16], level: f32) {
+        self.set_channel(leds, 0, 0..BAR_LEN, level);
+    }
+
+    pub fn set_stereo(&mut self, leds: &mut [u8; 16], left: f32, right: f32) {
+        self.set_channel(leds, 0, 0..BAR_LEN/2, left);
+        self.set_channel(leds, 1, BAR_LEN/2..BAR_LEN, right);
+    }
+
+    /// One band per position, as the brightness of its green LED. Clipping
+    /// bands are red.
+    pub fn set_spectrum(leds: &mut [u8; 16], bands: &[f32; BAR_LEN]) {
+        for (n, band) in bands.iter().enumerate() {
+            let brightness = (band.clamp(0.0, 1.0) * 255.0) as u8;
+            let clip = *band >= 1.0;
+            leds[PCA9635_BAR_GREEN[n]] = if clip { 0 } else { brightness };
+            leds[PCA9635_BAR_RED[n]] = if clip { 0xff } else { 0 };
+        }
+    }
+
+    fn set_channel(&mut self, leds: &mut [u8; 16], ch: usize,
+                   positions: core::ops::Range<usize>, level: f32) {
+        let len = positions.len();
+        let lit = ((level.max(0.0) * len as f32 + 0.5) as usize).min(len);
+        let clip = level >= 1.0;
+        let (peak, ticks) = &mut self.peaks[ch];
+        if lit >= *peak {
+            *peak = lit;
+            *ticks = self.hold;
+        } else if *ticks > 0 {
+            *ticks -= 1;
+        } else {
+            *peak = lit;
+        }
+        for (i, n) in positions.enumerate() {
+            let on = i < lit || i + 1 == *peak;
+            let red = clip || i + 1 == len;
+            leds[PCA9635_BAR_GREEN[n]] = if on && !red { 0xff } else { 0 };
+            leds[PCA9635_BAR_RED[n]] = if on && red { 0xff } else { 0 };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (green, red) for each position along the bar.
+    fn bar(leds: &[u8; 16]) -> [(bool, bool); BAR_LEN] {
+        core::array::from_fn(|n| (leds[PCA9635_BAR_GREEN[n]] == 0xff,
+                                  leds[PCA9635_BAR_RED[n]] == 0xff))
+    }
+
+    const OFF: (bool, bool) = (false, false);
+    const GRN: (bool, bool) = (true, false);
+    const RED: (bool, bool) = (false, true);
+
+    #[test]
+    fn test_bargraph_mono() {
+        let mut leds = [0u8; 16];
+        let mut meter = Bargraph::new(0);
+        for (level, expect) in [
+            (0.0,  [OFF; BAR_LEN]),
+            (-1.0, [OFF; BAR_LEN]),
+            (0.05, [OFF; BAR_LEN]),
+            (0.1,  [GRN, OFF, OFF, OFF, OFF, OFF]),
+            (0.5,  [GRN, GRN, GRN, OFF, OFF, OFF]),
+            (0.9,  [GRN, GRN, GRN, GRN, GRN, OFF]),
+            (0.99, [GRN, GRN, GRN, GRN, GRN, RED]),
+            (1.0,  [RED; BAR_LEN]),
+            (4.0,  [RED; BAR_LEN]),
+        ] {
+            meter.set_mono(&mut leds, level);
+            assert_eq!(bar(&leds), expect, "level {}", level);
+        }
+        // The MIDI LEDs are left alone.
+        assert_eq!(leds[PCA9635_MIDI[0]], 0);
+        assert_eq!(leds[PCA9635_MIDI[1]], 0);
+    }
+
+    #[test]
+    fn test_bargraph_stereo() {
+        let mut leds = [0u8; 16];
+        let mut meter = Bargraph::new(0);
+        meter.set_stereo(&mut leds, 0.3, 0.0);
+        assert_eq!(bar(&leds), [GRN, OFF, OFF, OFF, OFF, OFF]);
+        meter.set_stereo(&mut leds, 0.7, 0.9);
+        assert_eq!(bar(&leds), [GRN, GRN, OFF, GRN, GRN, RED]);
+        meter.set_stereo(&mut leds, 0.1, 1.0);
+        assert_eq!(bar(&leds), [OFF, OFF, OFF, RED, RED, RED]);
+    }
+
+    #[test]
+    fn test_bargraph_peak_hold() {
+        let mut leds = [0u8; 16];
+        let mut meter = Bargraph::new(2);
+        meter.set_mono(&mut leds, 0.7);
+        assert_eq!(bar(&leds), [GRN, GRN, GRN, GRN, OFF, OFF]);
+        // Peak held for 2 updates after the level falls.
+        for _ in 0..2 {
+            meter.set_mono(&mut leds, 0.2);
+            assert_eq!(bar(&leds), [GRN, OFF, OFF, GRN, OFF, OFF]);
+        }
+        meter.set_mono(&mut leds, 0.2);
+        assert_eq!(bar(&leds), [GRN, OFF, OFF, OFF, OFF, OFF]);
+        // A new, higher peak restarts the hold.
+        meter.set_mono(&mut leds, 0.99);
+        meter.set_mono(&mut leds, 0.0);
+        assert_eq!(bar(&leds), [OFF, OFF, OFF, OFF, OFF, RED]);
+    }
+
+    #[test]
+    fn test_bargraph_spectrum() {
+        let mut leds = [0u8; 16];
+        Bargraph::set_spectrum(&mut leds, &[0.0, 0.5, 1.0, 0.25, -1.0, 2.0]);
+        assert_eq!(bar(&leds), [OFF, OFF, RED, OFF, OFF, RED]);
+        assert_eq!(leds[PCA9635_BAR_GREEN[1]], 127);
+        assert_eq!(leds[PCA9635_BAR_GREEN[3]], 63);
+        assert_eq!(leds[PCA9635_BAR_GREEN[4]], 0);
+    }
+}
@@ -1,6 +1,7 @@
 #![cfg_attr(not(test), no_std)]
 
 pub mod color;
+pub mod clip;
 pub mod draw;
 pub mod logger;
 pub mod palette;
@@ -15,3 +16,15 @@ pub mod bootinfo;
 pub mod eeprominfo;
 pub mod mono_6x12_optimized;
 pub mod scope;
+pub mod attractor;
+pub mod scaling;
+pub mod siggen;
+pub mod idle;
+pub mod heartbeat;
+pub mod diagnostics;
+pub mod checksum;
+pub mod xmodem;
+pub mod slotwriter;
+pub mod meter;
+pub mod wavetable;
+pub mod grain_sync;
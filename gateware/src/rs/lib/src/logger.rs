@@ -3,6 +3,81 @@ use log::{Level, Metadata, Record};
 use core::cell::RefCell;
 use core::fmt::Write;
 
+use critical_section::Mutex;
+use heapless::{Deque, String};
+
+/// Number of lines kept by `recent_lines`.
+pub const RECENT_LINES: usize = 16;
+/// Longer lines are truncated.
+pub const RECENT_LINE_LEN: usize = 64;
+
+pub type LogLine = String<RECENT_LINE_LEN>;
+
+/// The last `N` log lines, oldest first.
+#[derive(Clone, Default)]
+pub struct LogRing<const N: usize> {
+    lines: Deque<LogLine, N>,
+}
+
+impl<const N: usize> LogRing<N> {
+    pub const fn new() -> Self {
+        Self { lines: Deque::new() }
+    }
+
+    pub fn push(&mut self, level: Level, args: &core::fmt::Arguments) {
+        let mut line = LogLine::new();
+        write!(Truncate(&mut line), "{} {}", level, args).ok();
+        if self.lines.is_full() {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line).ok();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.lines.iter().map(|line| line.as_str())
+    }
+}
+
+/// Writes as much as fits, dropping the rest.
+struct Truncate<'a>(&'a mut LogLine);
+
+impl Write for Truncate<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            if self.0.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+static RECENT: Mutex<RefCell<Option<LogRing<RECENT_LINES>>>> = Mutex::new(RefCell::new(None));
+
+/// Keep the most recent log lines in memory (as well as writing them out),
+/// so they can be shown on screen without a serial adapter.
+pub fn enable_recent_lines() {
+    critical_section::with(|cs| {
+        RECENT.borrow_ref_mut(cs).get_or_insert_with(LogRing::new);
+    });
+}
+
+/// Snapshot of the most recent log lines. Empty unless `enable_recent_lines`
+/// was called.
+pub fn recent_lines() -> LogRing<RECENT_LINES> {
+    critical_section::with(|cs| {
+        RECENT.borrow_ref(cs).clone().unwrap_or_default()
+    })
+}
+
 pub struct WriteLogger<W>
 where
     W: Write + Send,
@@ -42,9 +117,55 @@ where
                 panic!("Logger has not been initialized");
             }
         }
+
+        critical_section::with(|cs| {
+            if let Some(recent) = RECENT.borrow_ref_mut(cs).as_mut() {
+                recent.push(record.level(), record.args());
+            }
+        });
     }
 
     fn flush(&self) {}
 }
 
 unsafe impl<W: Write + Send> Sync for WriteLogger<W> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Log;
+
+    #[test]
+    fn test_recent_lines() {
+        let logger = WriteLogger {
+            writer: RefCell::new(Some(String::<256>::new())),
+            level: Level::Info,
+        };
+        fn log(logger: &WriteLogger<String<256>>, level: Level, args: core::fmt::Arguments) {
+            logger.log(&Record::builder().level(level).args(args).build());
+        }
+
+        // Nothing is kept until enabled.
+        log(&logger, Level::Info, format_args!("before"));
+        assert!(recent_lines().is_empty());
+
+        enable_recent_lines();
+        for n in 0..20 {
+            logger.writer.borrow_mut().as_mut().unwrap().clear();
+            log(&logger, Level::Warn, format_args!("line {}", n));
+        }
+        // Filtered out by the logger level.
+        log(&logger, Level::Debug, format_args!("debug"));
+        // The serial output is unchanged.
+        assert!(logger.writer.borrow().as_ref().unwrap().ends_with("line 19\r\n"));
+
+        let recent = recent_lines();
+        assert_eq!(recent.len(), RECENT_LINES);
+        assert_eq!(recent.iter().next(), Some("WARN line 4"));
+        assert_eq!(recent.iter().last(), Some("WARN line 19"));
+
+        log(&logger, Level::Error, format_args!("{}", "x".repeat(100)));
+        let long = recent_lines().iter().last().unwrap().len();
+        assert_eq!(long, RECENT_LINE_LEN);
+    }
+}
@@ -0,0 +1,179 @@
+// Peak and RMS level meters for audio channels. Frames are fed one at a
+// time (usually from a timer ISR), so this only uses integer math.
+
+/// Levels at or below this are drawn as an empty meter.
+pub const FLOOR_DBFS: i8 = -60;
+
+/// ASQ full scale. Calibrated samples can exceed it slightly, and
+/// -32768 has no positive counterpart, so magnitudes are clamped here.
+const FULL_SCALE: u32 = 32768;
+
+/// Amplitude of each 1dB step below full scale, from 0dBFS down to
+/// `FLOOR_DBFS`: `round(32768 * 10^(-n/20))`.
+const DBFS_TABLE: [u32; 61] = [
+    32768, 29205, 26029, 23198, 20675, 18427, 16423, 14637, 13045, 11627,
+    10362,  9235,  8231,  7336,  6538,  5827,  5193,  4629,  4125,  3677,
+     3277,  2920,  2603,  2320,  2068,  1843,  1642,  1464,  1305,  1163,
+     1036,   924,   823,   734,   654,   583,   519,   463,   413,   368,
+      328,   292,   260,   232,   207,   184,   164,   146,   130,   116,
+      104,    92,    82,    73,    65,    58,    52,    46,    41,    37,
+       33,
+];
+
+/// Peaks fall by 1/2^PEAK_DECAY_SHIFT of their value per frame once the
+/// hold time has expired.
+const PEAK_DECAY_SHIFT: u32 = 5;
+
+fn magnitude(sample: i32) -> u32 {
+    sample.unsigned_abs().min(FULL_SCALE)
+}
+
+/// Sample magnitude to dBFS, rounded down to the next 1dB step.
+pub fn amplitude_to_dbfs(amplitude: u32) -> i8 {
+    DBFS_TABLE.iter()
+              .position(|&a| amplitude >= a)
+              .map_or(FLOOR_DBFS, |n| -(n as i8))
+}
+
+/// Like `amplitude_to_dbfs`, for a mean square. Comparing against the
+/// squared table avoids taking a square root.
+pub fn mean_square_to_dbfs(mean_square: u32) -> i8 {
+    DBFS_TABLE.iter()
+              .position(|&a| mean_square >= a * a)
+              .map_or(FLOOR_DBFS, |n| -(n as i8))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeterLevel {
+    pub peak_dbfs: i8,
+    pub rms_dbfs: i8,
+}
+
+impl Default for MeterLevel {
+    fn default() -> Self {
+        Self { peak_dbfs: FLOOR_DBFS, rms_dbfs: FLOOR_DBFS }
+    }
+}
+
+/// Accumulates `N` channels of samples. The RMS is taken over windows
+/// of `window` frames, and is updated at the end of each window. Peaks
+/// are held for `hold` frames before they start to decay.
+pub struct LevelMeter<const N: usize> {
+    window: u32,
+    hold: u32,
+    n: u32,
+    sum_sq: [u64; N],
+    mean_sq: [u32; N],
+    peak: [u32; N],
+    hold_left: [u32; N],
+}
+
+impl<const N: usize> LevelMeter<N> {
+    pub fn new(window: u32, hold: u32) -> Self {
+        Self {
+            window: window.max(1),
+            hold,
+            n: 0,
+            sum_sq: [0; N],
+            mean_sq: [0; N],
+            peak: [0; N],
+            hold_left: [0; N],
+        }
+    }
+
+    pub fn set_hold(&mut self, hold: u32) {
+        self.hold = hold;
+    }
+
+    pub fn feed(&mut self, samples: &[i32; N]) {
+        for (ch, sample) in samples.iter().enumerate() {
+            let a = magnitude(*sample);
+            self.sum_sq[ch] += (a * a) as u64;
+            if a >= self.peak[ch] {
+                self.peak[ch] = a;
+                self.hold_left[ch] = self.hold;
+            } else if self.hold_left[ch] > 0 {
+                self.hold_left[ch] -= 1;
+            } else {
+                // Decay, but never below the current sample.
+                let decay = (self.peak[ch] >> PEAK_DECAY_SHIFT).max(1);
+                self.peak[ch] = (self.peak[ch] - decay).max(a);
+            }
+        }
+        self.n += 1;
+        if self.n == self.window {
+            for ch in 0..N {
+                self.mean_sq[ch] = (self.sum_sq[ch] / self.window as u64) as u32;
+                self.sum_sq[ch] = 0;
+            }
+            self.n = 0;
+        }
+    }
+
+    pub fn levels(&self) -> [MeterLevel; N] {
+        core::array::from_fn(|ch| MeterLevel {
+            peak_dbfs: amplitude_to_dbfs(self.peak[ch]),
+            rms_dbfs: mean_square_to_dbfs(self.mean_sq[ch]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dbfs_table() {
+        for (n, a) in DBFS_TABLE.iter().enumerate() {
+            let expect = 32768.0f64 * 10.0f64.powf(-(n as f64) / 20.0);
+            assert!((*a as f64 - expect).abs() <= 0.5, "{}dB: {} != {}", n, a, expect);
+            // Exactly on a step, and just below it.
+            assert_eq!(amplitude_to_dbfs(*a), -(n as i8));
+            assert_eq!(mean_square_to_dbfs(a * a), -(n as i8));
+            if n < DBFS_TABLE.len() - 1 {
+                assert_eq!(amplitude_to_dbfs(*a - 1), -(n as i8) - 1);
+            }
+        }
+        assert_eq!(amplitude_to_dbfs(16384), -7);
+        assert_eq!(amplitude_to_dbfs(0), FLOOR_DBFS);
+        // Negative full scale, and calibrated samples beyond it, clamp to
+        // 0dBFS without overflowing.
+        assert_eq!(amplitude_to_dbfs(magnitude(-32768)), 0);
+        assert_eq!(amplitude_to_dbfs(magnitude(-40000)), 0);
+        assert_eq!(amplitude_to_dbfs(magnitude(i32::MIN)), 0);
+        assert_eq!(mean_square_to_dbfs(magnitude(i32::MIN).pow(2)), 0);
+    }
+
+    #[test]
+    fn test_level_meter() {
+        let mut m = LevelMeter::<2>::new(4, 4);
+        assert_eq!(m.levels(), [MeterLevel::default(); 2]);
+
+        // Full-scale negative square wave on ch0, silence on ch1.
+        for _ in 0..4 {
+            m.feed(&[-32768, 0]);
+        }
+        let l = m.levels();
+        assert_eq!(l[0], MeterLevel { peak_dbfs: 0, rms_dbfs: 0 });
+        assert_eq!(l[1], MeterLevel::default());
+
+        // RMS of a half-scale square wave is -6dB (rounded down to -7).
+        for _ in 0..4 {
+            m.feed(&[16384, -16384]);
+        }
+        let l = m.levels();
+        assert_eq!(l[0].rms_dbfs, -7);
+        assert_eq!(l[1].rms_dbfs, -7);
+        // ch0 peak still held at full scale, ch1 peak follows the input.
+        assert_eq!(l[0].peak_dbfs, 0);
+        assert_eq!(l[1].peak_dbfs, -7);
+
+        // Once the hold expires, peaks decay towards the input, not below it.
+        for _ in 0..128 {
+            m.feed(&[0, -16384]);
+        }
+        let l = m.levels();
+        assert!(l[0].peak_dbfs < -30);
+        assert_eq!(l[1].peak_dbfs, -7);
+    }
+}
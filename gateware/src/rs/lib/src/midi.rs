@@ -3,11 +3,255 @@ use crate::dsp::{OnePoleSmoother, Fix};
 
 const N_TOUCH: usize = 8;
 
+/// MIDI CC 'All Notes Off' (channel mode message).
+pub const CC_ALL_NOTES_OFF: u8 = 123;
+/// MIDI CC 'Sustain Pedal'.
+pub const CC_SUSTAIN: u8 = 64;
+
+pub fn is_all_notes_off(msg: &MidiMessage) -> bool {
+    matches!(msg, MidiMessage::ControlChange(_, cc, _) if u8::from(*cc) == CC_ALL_NOTES_OFF)
+}
+
+/// Messages that release every voice, for recovering from stuck notes.
+///
+/// Voices only report the last note they were assigned, not whether they
+/// are gated, so a NOTE_OFF is sent for every voice. Extra NOTE_OFFs are
+/// harmless. The sustain pedal is released first, otherwise the NOTE_OFFs
+/// would only mark held voices as sustained.
+pub fn all_notes_off(channel: Channel, voice_notes: &[u8]) -> impl Iterator<Item = MidiMessage> + '_ {
+    core::iter::once(MidiMessage::ControlChange(channel, Control::from(CC_SUSTAIN), Value7::new(0)))
+        .chain(voice_notes.iter().map(move |note| {
+            MidiMessage::NoteOff(channel, Note::from(*note), Value7::new(0))
+        }))
+}
+
+/// MIDI clocks per quarter note.
+pub const CLOCKS_PER_QUARTER: u32 = 24;
+/// Inter-tick intervals in the tempo median filter.
+const CLOCK_MEDIAN_N: usize = 5;
+/// Gaps longer than this (10 BPM) mean the clock source went away,
+/// rather than a slow tempo, so the tempo estimate starts over.
+const CLOCK_MAX_INTERVAL_US: u32 = 250_000;
+
+const STATUS_SONG_POSITION: u8 = 0xF2;
+const STATUS_CLOCK: u8 = 0xF8;
+const STATUS_START: u8 = 0xFA;
+const STATUS_CONTINUE: u8 = 0xFB;
+const STATUS_STOP: u8 = 0xFC;
+
+/// Follows an external MIDI clock from a raw MIDI byte stream.
+///
+/// Tracks tempo (0xF8 clock, median filtered over the last few tick
+/// intervals to reject jitter), transport (0xFA start, 0xFB continue,
+/// 0xFC stop) and song position pointers. Real-time bytes may arrive
+/// between the data bytes of other messages and do not disturb
+/// running status. Everything else in the stream is skipped.
+///
+/// Timestamps are in microseconds from any free-running counter, and
+/// may wrap.
+pub struct MidiClock {
+    intervals:    [u32; CLOCK_MEDIAN_N],
+    n_intervals:  usize,
+    next_ix:      usize,
+    last_tick_us: Option<u32>,
+    running:      bool,
+    // The first clock after START is position 0, not 1.
+    start_pending: bool,
+    // Clocks since the start of the song.
+    clocks:       u32,
+    // Running status and data bytes of the current message.
+    status:       Option<u8>,
+    data:         [u8; 2],
+    n_data:       usize,
+}
+
+impl MidiClock {
+    pub fn new() -> Self {
+        MidiClock {
+            intervals:     [0u32; CLOCK_MEDIAN_N],
+            n_intervals:   0,
+            next_ix:       0,
+            last_tick_us:  None,
+            running:       false,
+            start_pending: false,
+            clocks:        0,
+            status:        None,
+            data:          [0u8; 2],
+            n_data:        0,
+        }
+    }
+
+    /// Consume one byte of the incoming MIDI stream, received at `now_us`.
+    pub fn feed(&mut self, byte: u8, now_us: u32) {
+        match byte {
+            STATUS_CLOCK => self.tick(now_us),
+            STATUS_START => {
+                self.running = true;
+                self.start_pending = true;
+                self.clocks = 0;
+            }
+            STATUS_CONTINUE => self.running = true,
+            STATUS_STOP => self.running = false,
+            // Other real-time bytes, which also leave running status alone.
+            0xF9..=0xFF => {}
+            // System common and SysEx cancel running status.
+            0xF0..=0xF7 => {
+                self.status = Some(byte).filter(|b| *b == STATUS_SONG_POSITION);
+                self.n_data = 0;
+            }
+            0x80..=0xEF => {
+                self.status = Some(byte);
+                self.n_data = 0;
+            }
+            _ => self.data_byte(byte),
+        }
+    }
+
+    fn data_byte(&mut self, byte: u8) {
+        let Some(status) = self.status else {
+            return;
+        };
+        let len = match status & 0xF0 {
+            0xC0 | 0xD0 => 1,
+            _ => 2,
+        };
+        self.data[self.n_data] = byte;
+        self.n_data += 1;
+        if self.n_data < len {
+            return;
+        }
+        self.n_data = 0;
+        if status == STATUS_SONG_POSITION {
+            // Position is counted in 16th notes, 6 clocks each.
+            let sixteenths = self.data[0] as u32 | ((self.data[1] as u32) << 7);
+            self.clocks = sixteenths * 6;
+            self.start_pending = false;
+            // Song position is not a running status.
+            self.status = None;
+        }
+    }
+
+    fn tick(&mut self, now_us: u32) {
+        if let Some(last) = self.last_tick_us {
+            let interval = now_us.wrapping_sub(last);
+            if interval > CLOCK_MAX_INTERVAL_US {
+                self.n_intervals = 0;
+            } else if interval > 0 {
+                self.intervals[self.next_ix] = interval;
+                self.next_ix = (self.next_ix + 1) % CLOCK_MEDIAN_N;
+                self.n_intervals = (self.n_intervals + 1).min(CLOCK_MEDIAN_N);
+            }
+        }
+        self.last_tick_us = Some(now_us);
+        if self.running {
+            if self.start_pending {
+                self.start_pending = false;
+            } else {
+                self.clocks = self.clocks.wrapping_add(1);
+            }
+        }
+    }
+
+    /// Median of the recent inter-tick intervals, in microseconds.
+    fn interval_us(&self) -> Option<u32> {
+        if self.n_intervals == 0 {
+            return None;
+        }
+        let mut sorted = self.intervals;
+        let sorted = &mut sorted[..self.n_intervals];
+        sorted.sort_unstable();
+        Some(sorted[self.n_intervals / 2])
+    }
+
+    /// Tempo estimate, or `None` until at least 2 clocks have arrived.
+    pub fn bpm(&self) -> Option<f32> {
+        self.interval_us().map(|interval| {
+            60_000_000f32 / (interval as f32 * CLOCKS_PER_QUARTER as f32)
+        })
+    }
+
+    /// Transport state, set by START / CONTINUE and cleared by STOP.
+    pub fn running(&self) -> bool {
+        self.running
+    }
+
+    /// Position within the current quarter note, 0..1. Interpolates
+    /// between clocks using the tempo estimate, but never runs past
+    /// the next expected clock.
+    pub fn phase(&self, now_us: u32) -> f32 {
+        let mut frac = 0f32;
+        if let (Some(interval), Some(last), false) =
+                (self.interval_us(), self.last_tick_us, self.start_pending) {
+            let since = now_us.wrapping_sub(last) as f32 / interval as f32;
+            frac = since.clamp(0f32, 0.999f32);
+        }
+        ((self.clocks % CLOCKS_PER_QUARTER) as f32 + frac) / CLOCKS_PER_QUARTER as f32
+    }
+}
+
+/// MIDI CC 'Modulation Wheel'.
+pub const CC_MOD_WHEEL: u8 = 1;
+
+/// Minimum time between CONTROL_CHANGE / PITCH_BEND messages from one
+/// mapped source, so a moving source can't flood the synth MIDI FIFO.
+pub const CC_MIN_INTERVAL_MS: u32 = 10;
+/// Changes smaller than this (in 14-bit units) are treated as noise and not
+/// sent, unless the source has come back to rest. For CONTROL_CHANGE this is
+/// one 7-bit step, so a value sitting on a step boundary does not toggle.
+const CC_DEADBAND: u16 = 128;
+const PITCH_BEND_DEADBAND: u16 = 32;
+const VALUE14_MAX: u16 = 0x3FFF;
+const PITCH_BEND_CENTER: u16 = 0x2000;
+/// CV inputs span this many volts over the full controller range.
+const CC_CV_RANGE_V: i32 = 5;
+
+/// Where a continuous controller mapping reads from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CcSource {
+    /// Touch magnitude of a jack (0..=7).
+    Touch(usize),
+    /// CV on an input jack (0..=3). 0V..5V for CONTROL_CHANGE, and
+    /// -5V..5V (centered at 0V) for PITCH_BEND.
+    Cv(usize),
+}
+
+/// Message a continuous controller mapping emits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CcTarget {
+    /// CONTROL_CHANGE on this controller, 7-bit value.
+    Control(u8),
+    /// PITCH_BEND, 14-bit value. An untouched pad rests at the center,
+    /// touching it bends upward.
+    PitchBend,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CcMapping {
+    pub source: CcSource,
+    pub target: CcTarget,
+}
+
+/// Turns jack touches into MIDI notes.
+///
+/// By default touches only gate notes (at full velocity), so the synth
+/// envelope shapes each note. With `set_pressure(true)`, the touch
+/// magnitude is also sent continuously as POLY_PRESSURE.
+///
+/// With `set_cc`, one touch pad or CV input instead drives a
+/// CONTROL_CHANGE or PITCH_BEND, see `update_cc`. A pad mapped this way
+/// no longer plays its note.
 pub struct MidiTouchController {
     notes:     [Note; N_TOUCH],
     l_touch:   [u8; N_TOUCH],
     l_jack:    u8,
     smoothers: [OnePoleSmoother; N_TOUCH],
+    pressure:  bool,
+    cc:        Option<CcMapping>,
+    // Touch pad that was mapped to a CC on the last `update`.
+    l_cc_pad:  Option<usize>,
+    // Last sent 14-bit value, and time since it was sent.
+    cc_sent:   Option<u16>,
+    cc_since_ms: u32,
 }
 
 impl MidiTouchController {
@@ -27,7 +271,32 @@ impl MidiTouchController {
             l_touch: [0u8; N_TOUCH],
             l_jack:  0u8,
             // Smoothers to de-noise touch values
-            smoothers: [OnePoleSmoother::new(0.2); N_TOUCH]
+            smoothers: [OnePoleSmoother::new(0.2); N_TOUCH],
+            pressure: false,
+            cc:       None,
+            l_cc_pad: None,
+            cc_sent:  None,
+            cc_since_ms: 0,
+        }
+    }
+
+    pub fn set_pressure(&mut self, pressure: bool) {
+        self.pressure = pressure;
+    }
+
+    /// Map a touch pad or CV input to a continuous controller, or `None`
+    /// to disable. Changing the mapping resends the current value.
+    pub fn set_cc(&mut self, cc: Option<CcMapping>) {
+        if cc != self.cc {
+            self.cc = cc;
+            self.cc_sent = None;
+        }
+    }
+
+    fn cc_pad(&self) -> Option<usize> {
+        match self.cc {
+            Some(CcMapping { source: CcSource::Touch(pad), .. }) => Some(pad),
+            _ => None,
         }
     }
 
@@ -36,14 +305,25 @@ impl MidiTouchController {
         let channel = Channel::C1;
         for i in 0..N_TOUCH {
             let sm = self.smoothers[i].proc(Fix::from_bits(touch[i] as i32));
-            let pressure = Value7::new((sm.to_bits() as u8)>>1);
+            let pressure = if self.pressure {
+                Value7::new((sm.to_bits() as u8)>>1)
+            } else {
+                Value7::new(127)
+            };
+            if self.cc_pad() == Some(i) {
+                // Release a note still held from before the pad was mapped.
+                if self.l_cc_pad != Some(i) && self.l_touch[i] != 0 {
+                    out[i] = MidiMessage::NoteOff(channel, self.notes[i], pressure);
+                }
+                continue;
+            }
             let jack_currently_unplugged = ((1 << i) & !jack) != 0;
             if jack_currently_unplugged {
                 // emit NOTE_ON once after the touch starts, and
-                // POLY_PRESSURE for all cycles afterward.
+                // POLY_PRESSURE for all cycles afterward (if enabled).
                 if self.l_touch[i] == 0 && touch[i] > 0 {
                     out[i] = MidiMessage::NoteOn(channel, self.notes[i], pressure);
-                } else if touch[i] != 0 {
+                } else if touch[i] != 0 && self.pressure {
                     out[i] = MidiMessage::KeyPressure(channel, self.notes[i], pressure);
                 } else if self.l_touch[i] != 0 && touch[i] == 0 {
                     // warn: note off logic currently assumes note ids don't change
@@ -57,6 +337,296 @@ impl MidiTouchController {
         }
         self.l_touch = *touch;
         self.l_jack  = jack;
+        self.l_cc_pad = self.cc_pad();
         out
     }
+
+    /// Message for the mapped continuous controller (see `set_cc`), if its
+    /// value changed. `cv` are the raw input samples, `elapsed_ms` is the
+    /// time since the last call.
+    ///
+    /// Changes within a small deadband are ignored, and messages are sent
+    /// at most every `CC_MIN_INTERVAL_MS`. Returning to rest (or either end
+    /// of the range) is always sent, once the rate limit allows.
+    pub fn update_cc(&mut self, touch: &[u8; N_TOUCH], cv: &[i32; 4], counts_per_v: i32,
+                     elapsed_ms: u32) -> Option<MidiMessage> {
+        let mapping = self.cc?;
+        self.cc_since_ms = self.cc_since_ms.saturating_add(elapsed_ms);
+        let bend = mapping.target == CcTarget::PitchBend;
+        // Source in 14-bit units, resting at 0 (or the center for pitch bend).
+        let value: u16 = match mapping.source {
+            CcSource::Touch(pad) => {
+                let level = touch[pad] as u32 * VALUE14_MAX as u32 / u8::MAX as u32;
+                if bend {
+                    (PITCH_BEND_CENTER as u32 + level * (VALUE14_MAX - PITCH_BEND_CENTER) as u32
+                        / VALUE14_MAX as u32) as u16
+                } else {
+                    level as u16
+                }
+            }
+            CcSource::Cv(ch) => {
+                let full_scale = (counts_per_v * CC_CV_RANGE_V).max(1) as i64;
+                let (offset, span) = if bend { (full_scale, 2 * full_scale) } else { (0, full_scale) };
+                // Over 2^14 rather than 2^14-1, so 0V lands exactly on the center.
+                ((cv[ch] as i64 + offset) * (VALUE14_MAX as i64 + 1) / span)
+                    .clamp(0, VALUE14_MAX as i64) as u16
+            }
+        };
+        let rest = if bend { PITCH_BEND_CENTER } else { 0 };
+        let deadband = if bend { PITCH_BEND_DEADBAND } else { CC_DEADBAND };
+        let changed = match self.cc_sent {
+            None => true,
+            Some(sent) if sent == value => false,
+            Some(sent) => sent.abs_diff(value) >= deadband
+                || value == rest || value == 0 || value == VALUE14_MAX,
+        };
+        if !changed || self.cc_since_ms < CC_MIN_INTERVAL_MS {
+            return None;
+        }
+        let channel = Channel::C1;
+        let msg = match mapping.target {
+            CcTarget::Control(cc) => {
+                let value7 = (value >> 7) as u8;
+                if self.cc_sent.map(|sent| (sent >> 7) as u8) == Some(value7) {
+                    return None;
+                }
+                MidiMessage::ControlChange(channel, Control::from(cc), Value7::new(value7))
+            }
+            CcTarget::PitchBend => MidiMessage::PitchBendChange(channel, Value14::from(value)),
+        };
+        self.cc_sent = Some(value);
+        self.cc_since_ms = 0;
+        Some(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_touch_controller() {
+        let ch = Channel::C1;
+        let mut touch = [0u8; N_TOUCH];
+        let mut tc = MidiTouchController::new();
+
+        // Gate only: full velocity NOTE_ON, nothing while held, NOTE_OFF.
+        touch[1] = 20;
+        let msgs = tc.update(&touch, 0);
+        assert_eq!(msgs[1], MidiMessage::NoteOn(ch, Note::G2, Value7::new(127)));
+        assert!(msgs.iter().enumerate().all(|(i, m)| i == 1 || *m == MidiMessage::Stop));
+        touch[1] = 200;
+        assert_eq!(tc.update(&touch, 0)[1], MidiMessage::Stop);
+        touch[1] = 0;
+        assert!(matches!(tc.update(&touch, 0)[1], MidiMessage::NoteOff(_, Note::G2, _)));
+
+        // Pressure: touch magnitude is tracked after the NOTE_ON.
+        tc.set_pressure(true);
+        touch[1] = 200;
+        assert!(matches!(tc.update(&touch, 0)[1], MidiMessage::NoteOn(_, Note::G2, _)));
+        assert!(matches!(tc.update(&touch, 0)[1], MidiMessage::KeyPressure(_, Note::G2, _)));
+
+        // Touching a patched jack does nothing, patching one releases its note.
+        assert!(matches!(tc.update(&touch, 0b10)[1], MidiMessage::NoteOff(_, Note::G2, _)));
+        assert_eq!(tc.update(&touch, 0b10)[1], MidiMessage::Stop);
+    }
+
+    #[test]
+    fn test_touch_controller_cc() {
+        let touch_cc = |tc: &mut MidiTouchController, touch: &[u8; N_TOUCH], ms: u32| {
+            tc.update_cc(touch, &[0; 4], 4000, ms)
+        };
+        let mut touch = [0u8; N_TOUCH];
+        let mut tc = MidiTouchController::new();
+        assert_eq!(touch_cc(&mut tc, &touch, 100), None);
+
+        // A held note is released when its pad is mapped, and the pad no
+        // longer plays notes afterward.
+        touch[2] = 100;
+        assert!(matches!(tc.update(&touch, 0)[2], MidiMessage::NoteOn(_, Note::C3, _)));
+        tc.set_cc(Some(CcMapping { source: CcSource::Touch(2), target: CcTarget::Control(CC_MOD_WHEEL) }));
+        assert!(matches!(tc.update(&touch, 0)[2], MidiMessage::NoteOff(_, Note::C3, _)));
+        touch[2] = 0;
+        assert_eq!(tc.update(&touch, 0)[2], MidiMessage::Stop);
+        touch[2] = 100;
+        assert_eq!(tc.update(&touch, 0)[2], MidiMessage::Stop);
+
+        // A slow ramp gives monotonic CC values, ending at full scale.
+        touch[2] = 0;
+        let mut values: heapless::Vec<u8, 256> = heapless::Vec::new();
+        for level in 0..=255u8 {
+            touch[2] = level;
+            if let Some(msg) = touch_cc(&mut tc, &touch, CC_MIN_INTERVAL_MS) {
+                let MidiMessage::ControlChange(_, cc, value) = msg else {
+                    panic!("unexpected {:?}", msg);
+                };
+                assert_eq!(u8::from(cc), CC_MOD_WHEEL);
+                values.push(value.into()).unwrap();
+            }
+        }
+        assert_eq!(values[0], 0);
+        assert_eq!(*values.last().unwrap(), 127);
+        assert!(values.windows(2).all(|w| w[0] < w[1]), "{:?}", values);
+
+        // Changes are rate limited, and jitter of a single count is not resent.
+        touch[2] = 0;
+        assert!(touch_cc(&mut tc, &touch, CC_MIN_INTERVAL_MS).is_some());
+        touch[2] = 255;
+        assert_eq!(touch_cc(&mut tc, &touch, 1), None);
+        assert!(touch_cc(&mut tc, &touch, CC_MIN_INTERVAL_MS - 1).is_some());
+        touch[2] = 254;
+        assert_eq!(touch_cc(&mut tc, &touch, CC_MIN_INTERVAL_MS), None);
+    }
+
+    #[test]
+    fn test_touch_controller_pitch_bend() {
+        let bend = |msg: Option<MidiMessage>| -> u16 {
+            let Some(MidiMessage::PitchBendChange(_, value)) = msg else {
+                panic!("unexpected {:?}", msg);
+            };
+            u16::from(value)
+        };
+        let mut tc = MidiTouchController::new();
+        let touch = [0u8; N_TOUCH];
+        let counts_per_v = 4000;
+        tc.set_cc(Some(CcMapping { source: CcSource::Cv(1), target: CcTarget::PitchBend }));
+        // 14-bit encoding, 0V is the center, +/-5V are the ends.
+        for (v, expect) in [(0, 0x2000), (5, 0x3FFF), (-5, 0), (10, 0x3FFF), (0, 0x2000)] {
+            let cv = [0, v * counts_per_v, 0, 0];
+            assert_eq!(bend(tc.update_cc(&touch, &cv, counts_per_v, CC_MIN_INTERVAL_MS)), expect,
+                       "{}V", v);
+        }
+        let cv = [0, counts_per_v * 5 / 2, 0, 0];
+        assert_eq!(bend(tc.update_cc(&touch, &cv, counts_per_v, CC_MIN_INTERVAL_MS)), 0x3000);
+        // Small wobbles around a held bend are not resent.
+        let cv = [0, counts_per_v * 5 / 2 + 5, 0, 0];
+        assert_eq!(tc.update_cc(&touch, &cv, counts_per_v, CC_MIN_INTERVAL_MS), None);
+
+        // A touched pad bends up from the center.
+        let mut touch = [0u8; N_TOUCH];
+        tc.set_cc(Some(CcMapping { source: CcSource::Touch(0), target: CcTarget::PitchBend }));
+        assert_eq!(bend(tc.update_cc(&touch, &[0; 4], counts_per_v, CC_MIN_INTERVAL_MS)), 0x2000);
+        touch[0] = 255;
+        assert_eq!(bend(tc.update_cc(&touch, &[0; 4], counts_per_v, CC_MIN_INTERVAL_MS)), 0x3FFF);
+    }
+
+    // 120 BPM
+    const TICK_US: u32 = 60_000_000 / (120 * CLOCKS_PER_QUARTER);
+
+    #[test]
+    fn test_midi_clock_bpm() {
+        let mut clock = MidiClock::new();
+        assert_eq!(clock.bpm(), None);
+        // Jittery 120 BPM clock with the occasional badly late tick,
+        // starting near the timer wraparound.
+        let jitter = [0i32, 50, -40, 30, -60, 4000, -30, 40];
+        let mut t = u32::MAX - 10 * TICK_US;
+        for i in 0..96 {
+            let now = t.wrapping_add_signed(jitter[i % jitter.len()]);
+            clock.feed(STATUS_CLOCK, now);
+            t = t.wrapping_add(TICK_US);
+            if i > 8 {
+                let bpm = clock.bpm().unwrap();
+                assert!((bpm - 120f32).abs() <= 1f32, "tick {}: bpm {}", i, bpm);
+            }
+        }
+        // Tempo change is followed once the median window refills.
+        let slow = 60_000_000 / (90 * CLOCKS_PER_QUARTER);
+        for _ in 0..CLOCK_MEDIAN_N {
+            t = t.wrapping_add(slow);
+            clock.feed(STATUS_CLOCK, t);
+        }
+        assert!((clock.bpm().unwrap() - 90f32).abs() <= 1f32);
+        // A long gap means the source stopped, the estimate starts over.
+        clock.feed(STATUS_CLOCK, t.wrapping_add(2 * CLOCK_MAX_INTERVAL_US));
+        assert_eq!(clock.bpm(), None);
+    }
+
+    #[test]
+    fn test_midi_clock_transport() {
+        let mut clock = MidiClock::new();
+        let mut t = 0u32;
+        // Clocks while stopped estimate tempo but don't move the position.
+        for _ in 0..4 {
+            t += TICK_US;
+            clock.feed(STATUS_CLOCK, t);
+        }
+        assert!(!clock.running());
+        assert_eq!(clock.phase(t), 0f32);
+        clock.feed(STATUS_START, t);
+        assert!(clock.running());
+        // First clock after START is the downbeat, then 6 clocks in.
+        for _ in 0..7 {
+            t += TICK_US;
+            clock.feed(STATUS_CLOCK, t);
+        }
+        assert!((clock.phase(t) - 0.25f32).abs() < 0.001);
+        // Halfway to the next clock.
+        let p = clock.phase(t + TICK_US / 2);
+        assert!((p - 6.5f32 / 24f32).abs() < 0.001, "phase {}", p);
+        // Interpolation never reaches the next clock on its own.
+        assert!(clock.phase(t + 10 * TICK_US) < 7f32 / 24f32);
+        clock.feed(STATUS_STOP, t);
+        t += TICK_US;
+        clock.feed(STATUS_CLOCK, t);
+        assert!(!clock.running());
+        assert!((clock.phase(t) - 0.25f32).abs() < 0.001);
+        clock.feed(STATUS_CONTINUE, t);
+        t += TICK_US;
+        clock.feed(STATUS_CLOCK, t);
+        assert!((clock.phase(t) - 7f32 / 24f32).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_midi_clock_running_status() {
+        let mut clock = MidiClock::new();
+        let mut t = 0u32;
+        let mut clk = |clock: &mut MidiClock| {
+            t += TICK_US;
+            clock.feed(STATUS_CLOCK, t);
+        };
+        clock.feed(STATUS_START, 0);
+        clk(&mut clock);
+        // NOTE_ON with running status, clocks between data bytes.
+        clock.feed(0x90, 0);
+        clock.feed(0x3C, 0);
+        clk(&mut clock);
+        clock.feed(0x40, 0);
+        clock.feed(0x3E, 0);
+        clk(&mut clock);
+        clock.feed(0x40, 0);
+        assert_eq!(clock.clocks, 2);
+        // Song position (16th note 5 = clock 30), split by a clock.
+        clock.feed(STATUS_SONG_POSITION, 0);
+        clock.feed(5, 0);
+        clk(&mut clock);
+        clock.feed(0, 0);
+        assert_eq!(clock.clocks, 30);
+        // Stray data bytes afterward are not another song position.
+        clock.feed(9, 0);
+        clock.feed(0, 0);
+        assert_eq!(clock.clocks, 30);
+        clk(&mut clock);
+        assert_eq!(clock.clocks, 31);
+        assert!(clock.running());
+    }
+
+    #[test]
+    fn test_all_notes_off() {
+        let voice_notes = [60u8, 64, 67, 0, 72, 60, 48, 127];
+        let msgs: heapless::Vec<MidiMessage, 16> = all_notes_off(Channel::C1, &voice_notes).collect();
+        assert_eq!(msgs.len(), voice_notes.len() + 1);
+        assert_eq!(msgs[0], MidiMessage::ControlChange(Channel::C1, Control::from(CC_SUSTAIN), Value7::new(0)));
+        for note in voice_notes {
+            assert!(msgs.contains(&MidiMessage::NoteOff(Channel::C1, Note::from(note), Value7::new(0))),
+                    "missing NOTE_OFF for {}", note);
+        }
+
+        assert!(is_all_notes_off(&MidiMessage::ControlChange(
+            Channel::C1, Control::from(CC_ALL_NOTES_OFF), Value7::new(0))));
+        assert!(!is_all_notes_off(&MidiMessage::ControlChange(
+            Channel::C1, Control::from(CC_SUSTAIN), Value7::new(0))));
+        assert!(!is_all_notes_off(&MidiMessage::NoteOff(
+            Channel::C1, Note::C3, Value7::new(0))));
+    }
 }
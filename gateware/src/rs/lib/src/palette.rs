@@ -1,6 +1,8 @@
 use tiliqua_hal::dma_framebuffer::DMAFramebuffer;
+use micromath::F32Ext;
 use serde_derive::{Serialize, Deserialize};
 
+use strum::IntoEnumIterator;
 use strum_macros::{EnumIter, IntoStaticStr};
 
 // TODO: take this dynamically from DMAFramebuffer configuration.
@@ -19,6 +21,39 @@ pub enum ColorPalette {
     InvGray,
     Inferno,
     Hueswap,
+    Ice,
+    Fire,
+    Mono,
+    Rainbow,
+    Sunset,
+}
+
+/// How the palette is spread across the 8 bits of each framebuffer pixel.
+///
+/// The framebuffer always stores `HI8` pixels (4 intensity bits, 4 hue bits).
+/// Layouts with fewer hue bits borrow the lower hue bits as extra intensity
+/// bits, trading the number of hues for smoother intensity gradients. For
+/// example `gray` palette with the `1x256` layout is a 256-level grayscale.
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+pub enum PaletteLayout {
+    #[default]
+    #[strum(serialize = "16x16")]
+    Hue16Int16,
+    #[strum(serialize = "4x64")]
+    Hue4Int64,
+    #[strum(serialize = "1x256")]
+    Hue1Int256,
+}
+
+impl PaletteLayout {
+    /// Number of upper hue bits that actually select a hue.
+    pub fn hue_bits(&self) -> u32 {
+        match self {
+            PaletteLayout::Hue16Int16 => 4,
+            PaletteLayout::Hue4Int64  => 2,
+            PaletteLayout::Hue1Int256 => 0,
+        }
+    }
 }
 
 const fn hue2rgb(p: f64, q: f64, mut t: f64) -> f64 {
@@ -190,6 +225,54 @@ const fn gen_hueswap() -> [(u8, u8, u8); PALETTE_LEN] {
     lut
 }
 
+/// A procedural palette. Hue and saturation are swept linearly across the
+/// hue columns, and brightness (HSV value) up the intensity rows. The
+/// bottom row is dim rather than black, so every entry is distinct.
+struct HsvSweep {
+    hue: (f64, f64),
+    sat: (f64, f64),
+}
+
+/// Converts an HSV color to RGB. Assumes s and v are contained in
+/// the set [0, 1], h in [0, 2) (wrapping at 1), and returns RGB in
+/// the set [0, 255].
+const fn hsv2rgb(h: f64, s: f64, v: f64) -> (u8, u8, u8) {
+    let h = if h >= 1.0 { h - 1.0 } else { h };
+    let sector = (h * 6.0) as usize;
+    let f = h * 6.0 - sector as f64;
+    let p = v * (1.0 - s);
+    let q = v * (1.0 - s * f);
+    let t = v * (1.0 - s * (1.0 - f));
+    let (r, g, b) = match sector {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    };
+    ((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8)
+}
+
+const fn gen_hsv_sweep(sweep: HsvSweep) -> [(u8, u8, u8); PALETTE_LEN] {
+    let mut lut = [(0u8, 0u8, 0u8); PALETTE_LEN];
+    let mut i = 0;
+    while i < PX_INTENSITY_MAX {
+        let value = (i + 1) as f64 / PX_INTENSITY_MAX as f64;
+        let mut h = 0;
+        while h < PX_HUE_MAX {
+            let x = h as f64 / PX_HUE_MAX as f64;
+            lut[i * PX_HUE_MAX + h] = hsv2rgb(
+                sweep.hue.0 + (sweep.hue.1 - sweep.hue.0) * x,
+                sweep.sat.0 + (sweep.sat.1 - sweep.sat.0) * x,
+                value);
+            h += 1;
+        }
+        i += 1;
+    }
+    lut
+}
+
 static PALETTE_EXP:      [(u8, u8, u8); PALETTE_LEN] = gen_exp();
 static PALETTE_LINEAR:   [(u8, u8, u8); PALETTE_LEN] = gen_linear();
 static PALETTE_DIM:      [(u8, u8, u8); PALETTE_LEN] = gen_dim();
@@ -197,6 +280,20 @@ static PALETTE_GRAY:     [(u8, u8, u8); PALETTE_LEN] = gen_gray();
 static PALETTE_INV_GRAY: [(u8, u8, u8); PALETTE_LEN] = gen_inv_gray();
 static PALETTE_INFERNO:  [(u8, u8, u8); PALETTE_LEN] = gen_inferno();
 static PALETTE_HUESWAP:  [(u8, u8, u8); PALETTE_LEN] = gen_hueswap();
+// Cyan to deep blue.
+static PALETTE_ICE:      [(u8, u8, u8); PALETTE_LEN] = gen_hsv_sweep(
+    HsvSweep { hue: (0.45, 0.70), sat: (0.8, 0.8) });
+// Red through orange to yellow.
+static PALETTE_FIRE:     [(u8, u8, u8); PALETTE_LEN] = gen_hsv_sweep(
+    HsvSweep { hue: (0.95, 1.15), sat: (0.95, 0.95) });
+// Green phosphor, from white to fully saturated.
+static PALETTE_MONO:     [(u8, u8, u8); PALETTE_LEN] = gen_hsv_sweep(
+    HsvSweep { hue: (0.28, 0.40), sat: (0.0, 1.0) });
+static PALETTE_RAINBOW:  [(u8, u8, u8); PALETTE_LEN] = gen_hsv_sweep(
+    HsvSweep { hue: (0.0, 1.0), sat: (0.9, 0.9) });
+// Purple through magenta to orange.
+static PALETTE_SUNSET:   [(u8, u8, u8); PALETTE_LEN] = gen_hsv_sweep(
+    HsvSweep { hue: (0.75, 1.10), sat: (0.8, 0.8) });
 
 impl ColorPalette {
     fn lut(&self) -> &'static [(u8, u8, u8); PALETTE_LEN] {
@@ -208,14 +305,77 @@ impl ColorPalette {
             ColorPalette::InvGray  => &PALETTE_INV_GRAY,
             ColorPalette::Inferno  => &PALETTE_INFERNO,
             ColorPalette::Hueswap  => &PALETTE_HUESWAP,
+            ColorPalette::Ice      => &PALETTE_ICE,
+            ColorPalette::Fire     => &PALETTE_FIRE,
+            ColorPalette::Mono     => &PALETTE_MONO,
+            ColorPalette::Rainbow  => &PALETTE_RAINBOW,
+            ColorPalette::Sunset   => &PALETTE_SUNSET,
         }
     }
 
-    pub fn write_to_hardware(&self, video: &mut impl DMAFramebuffer) {
+    /// Name as shown in menus, e.g. `inv-gray`. Stable, so it can be used
+    /// to reference a palette from outside the firmware.
+    pub fn name(&self) -> &'static str {
+        self.into()
+    }
+
+    /// Palette with the given `name`, if any.
+    pub fn from_name(name: &str) -> Option<ColorPalette> {
+        ColorPalette::iter().find(|palette| palette.name() == name)
+    }
+
+    /// RGB value of the hardware palette entry at (intensity, hue), given a layout.
+    /// Intensities in between LUT rows are linearly interpolated.
+    pub fn rgb(&self, layout: PaletteLayout, intensity: u8, hue: u8) -> (u8, u8, u8) {
+        self.rgb_with_gamma(layout, intensity, hue, 1.0)
+    }
+
+    /// As `rgb`, but the (normalized) intensity axis is first remapped through
+    /// `x.powf(gamma)`. `gamma` > 1 darkens the low intensities, `gamma` < 1
+    /// brings them up, which helps on dim displays. Hues are unaffected.
+    pub fn rgb_with_gamma(&self, layout: PaletteLayout, intensity: u8, hue: u8,
+                          gamma: f32) -> (u8, u8, u8) {
         let lut = self.lut();
+        let fine_bits = 4 - layout.hue_bits();
+        let fine_mask = (1u32 << fine_bits) - 1;
+        // Hue column in the LUT, ignoring the bits borrowed for intensity.
+        let h = ((hue as u32 & !fine_mask) & 0xF) as usize;
+        // Extended intensity, stretched evenly across all LUT rows.
+        let e = ((intensity as u32 & 0xF) << fine_bits) | (hue as u32 & fine_mask);
+        let den = ((PX_INTENSITY_MAX as u32) << fine_bits) - 1;
+        let (i, frac, den) = if gamma == 1.0 || e == 0 {
+            let num = e * (PX_INTENSITY_MAX as u32 - 1);
+            ((num / den) as usize, (num % den) as i32, den)
+        } else {
+            // Position between LUT rows, with 8 fractional bits.
+            let x = F32Ext::powf(e as f32 / den as f32, gamma);
+            let pos = ((x * (PX_INTENSITY_MAX - 1) as f32 * 256.0 + 0.5) as u32)
+                .min(((PX_INTENSITY_MAX - 1) as u32) << 8);
+            ((pos >> 8) as usize, (pos & 0xFF) as i32, 256)
+        };
+        let c0 = lut[i * PX_HUE_MAX + h];
+        if frac == 0 {
+            return c0;
+        }
+        let c1 = lut[(i + 1) * PX_HUE_MAX + h];
+        let lerp = |a: u8, b: u8| (a as i32 + (b as i32 - a as i32) * frac / den as i32) as u8;
+        (lerp(c0.0, c1.0), lerp(c0.1, c1.1), lerp(c0.2, c1.2))
+    }
+
+    pub fn write_to_hardware(&self, video: &mut impl DMAFramebuffer) {
+        self.write_to_hardware_with_layout(video, PaletteLayout::default());
+    }
+
+    pub fn write_to_hardware_with_layout(&self, video: &mut impl DMAFramebuffer, layout: PaletteLayout) {
+        self.write_to_hardware_with_gamma(video, layout, 1.0);
+    }
+
+    /// Write the palette with the intensity axis adjusted, see `rgb_with_gamma`.
+    pub fn write_to_hardware_with_gamma(&self, video: &mut impl DMAFramebuffer,
+                                        layout: PaletteLayout, gamma: f32) {
         for i in 0..PX_INTENSITY_MAX {
             for h in 0..PX_HUE_MAX {
-                let (r, g, b) = lut[i * PX_HUE_MAX + h];
+                let (r, g, b) = self.rgb_with_gamma(layout, i as u8, h as u8, gamma);
                 video.set_palette_rgb(i as u8, h as u8, r, g, b);
             }
         }
@@ -227,7 +387,6 @@ impl ColorPalette {
 mod tests {
     use super::*;
     use image::{ImageBuffer, RgbImage, Rgb};
-    use strum::IntoEnumIterator;
 
     const BLOCK_SIZE: u32 = 8;
 
@@ -257,4 +416,82 @@ mod tests {
             img.save(&filename).unwrap();
         }
     }
+
+    #[test]
+    fn test_named_palettes() {
+        for palette in [ColorPalette::Ice, ColorPalette::Fire, ColorPalette::Mono,
+                        ColorPalette::Rainbow, ColorPalette::Sunset] {
+            let mut entries = palette.lut().to_vec();
+            entries.sort();
+            entries.dedup();
+            assert_eq!(entries.len(), PALETTE_LEN, "{}", palette.name());
+            assert!(ColorPalette::from_name(palette.name()) == Some(palette));
+        }
+        for palette in ColorPalette::iter() {
+            assert!(ColorPalette::from_name(palette.name()) == Some(palette));
+        }
+        assert_eq!(ColorPalette::Ice.name(), "ice");
+        assert!(ColorPalette::from_name("inv-gray") == Some(ColorPalette::InvGray));
+        assert!(ColorPalette::from_name("Ice").is_none());
+        assert!(ColorPalette::from_name("").is_none());
+    }
+
+    #[test]
+    fn test_default_layout_matches_lut() {
+        for palette in ColorPalette::iter() {
+            for i in 0..PX_INTENSITY_MAX {
+                for h in 0..PX_HUE_MAX {
+                    assert!(palette.rgb(PaletteLayout::default(), i as u8, h as u8) ==
+                            palette.lut()[i * PX_HUE_MAX + h]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_gamma() {
+        // gamma = 1.0 is bit-exact with the palettes before gamma was added.
+        for (palette, layout, i, h, rgb) in [
+            (ColorPalette::Linear,  PaletteLayout::Hue16Int16, 7,  3,  (186, 211, 11)),
+            (ColorPalette::Linear,  PaletteLayout::Hue16Int16, 15, 9,  (224, 242, 253)),
+            (ColorPalette::Inferno, PaletteLayout::Hue4Int64,  5,  6,  (120, 28, 108)),
+            (ColorPalette::Gray,    PaletteLayout::Hue1Int256, 9,  13, (147, 147, 147)),
+            (ColorPalette::Exp,     PaletteLayout::Hue1Int256, 2,  5,  (9, 0, 0)),
+        ] {
+            assert!(palette.rgb_with_gamma(layout, i, h, 1.0) == rgb);
+            assert!(palette.rgb(layout, i, h) == rgb);
+        }
+        // Gamma only moves intensities in between the end points.
+        let gray = |i: u8, gamma: f32| ColorPalette::Gray.rgb_with_gamma(
+            PaletteLayout::default(), i, 0, gamma).0;
+        for i in 1..(PX_INTENSITY_MAX as u8 - 1) {
+            assert!(gray(i, 2.2) < gray(i, 1.0));
+            assert!(gray(i, 0.5) > gray(i, 1.0));
+        }
+        for gamma in [0.5, 2.2] {
+            assert_eq!(gray(0, gamma), gray(0, 1.0));
+            assert_eq!(gray(PX_INTENSITY_MAX as u8 - 1, gamma), gray(PX_INTENSITY_MAX as u8 - 1, 1.0));
+        }
+    }
+
+    #[test]
+    fn test_grayscale_layout_monotonic() {
+        // Walk every framebuffer pixel value in order of increasing 'extended'
+        // intensity, which for the 1x256 layout is just the raw HI8 value.
+        let mut last: Option<u8> = None;
+        let mut n_levels = 0;
+        for raw in 0..=255u8 {
+            let (r, g, b) = ColorPalette::Gray.rgb(PaletteLayout::Hue1Int256, raw >> 4, raw & 0xF);
+            assert!(r == g && g == b);
+            if let Some(last) = last {
+                assert!(r >= last, "gray level decreased at raw={}: {} -> {}", raw, last, r);
+                if r > last {
+                    n_levels += 1;
+                }
+            }
+            last = Some(r);
+        }
+        // Many more gray levels than the 16 available with the default layout.
+        assert!(n_levels > 200);
+    }
 }
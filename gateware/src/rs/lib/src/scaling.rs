@@ -0,0 +1,90 @@
+use serde_derive::{Serialize, Deserialize};
+use strum_macros::{EnumIter, IntoStaticStr};
+
+use crate::scope::VScale;
+
+/// Calibrated ADC/DAC counts per volt at the jacks.
+pub const COUNTS_PER_VOLT: i32 = 4000;
+
+/// Voltage standard of the source plugged into an input.
+///
+/// Samples are always calibrated to real volts at the jack. A preset changes
+/// how those volts are interpreted, such that the full-scale swing of each
+/// standard looks like a +/-5V Eurorack signal to the rest of the firmware.
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum InputScale {
+    #[default]
+    #[strum(serialize = "5V")]
+    Eurorack5V,
+    #[strum(serialize = "10V")]
+    Eurorack10V,
+    // Pro line level is +4dBu (~1.74V peak), leave a little headroom.
+    #[strum(serialize = "line")]
+    Line,
+}
+
+impl InputScale {
+    /// Input voltage (peak) which is treated as full scale.
+    pub fn full_scale_volts(&self) -> f32 {
+        match self {
+            InputScale::Eurorack5V  => 5.0,
+            InputScale::Eurorack10V => 10.0,
+            InputScale::Line        => 2.5,
+        }
+    }
+
+    /// Calibrated sample (in counts) as a fraction of full scale.
+    pub fn normalize(&self, counts: i32) -> f32 {
+        counts as f32 / (self.full_scale_volts() * COUNTS_PER_VOLT as f32)
+    }
+
+    /// Calibrated sample (in counts) as an equivalent voltage of a +/-5V
+    /// source. This is what should be used to interpret note CV.
+    pub fn to_volts(&self, counts: i32) -> f32 {
+        self.normalize(counts) * InputScale::Eurorack5V.full_scale_volts()
+    }
+
+    /// Adjust a display scale so a full scale input of this preset covers
+    /// the same number of divisions as a +/-5V signal would.
+    pub fn adjust_vscale(&self, vs: VScale) -> VScale {
+        let bits = vs.to_scale_bits() as i8 + match self {
+            InputScale::Eurorack5V  =>  0,
+            InputScale::Eurorack10V =>  1,
+            InputScale::Line        => -1,
+        };
+        VScale::from_scale_bits(bits.clamp(2, 9) as u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn test_full_scale_inputs() {
+        for (preset, full_scale_counts) in [
+            (InputScale::Eurorack5V,  20000),
+            (InputScale::Eurorack10V, 40000),
+            (InputScale::Line,        10000),
+        ] {
+            assert_eq!(preset.normalize(full_scale_counts), 1.0);
+            assert_eq!(preset.normalize(-full_scale_counts), -1.0);
+            assert_eq!(preset.to_volts(full_scale_counts), 5.0);
+        }
+        // The default preset does not change how note CV is interpreted.
+        assert_eq!(InputScale::default().to_volts(COUNTS_PER_VOLT), 1.0);
+    }
+
+    #[test]
+    fn test_adjust_vscale() {
+        for vs in VScale::iter() {
+            assert!(InputScale::Eurorack5V.adjust_vscale(vs) == vs);
+        }
+        assert!(InputScale::Eurorack10V.adjust_vscale(VScale::Scale1V) == VScale::Scale2V);
+        assert!(InputScale::Line.adjust_vscale(VScale::Scale1V) == VScale::Scale500mV);
+        // Saturates at the ends of the available scales.
+        assert!(InputScale::Eurorack10V.adjust_vscale(VScale::Scale8V) == VScale::Scale8V);
+    }
+}
@@ -89,4 +89,18 @@ impl VScale {
             VScale::Scale64mV  => 2,
         }
     }
+
+    /// Inverse of `to_scale_bits`, out-of-range values saturate.
+    pub fn from_scale_bits(bits: u8) -> Self {
+        match bits {
+            9.. => VScale::Scale8V,
+            8   => VScale::Scale4V,
+            7   => VScale::Scale2V,
+            6   => VScale::Scale1V,
+            5   => VScale::Scale500mV,
+            4   => VScale::Scale250mV,
+            3   => VScale::Scale125mV,
+            _   => VScale::Scale64mV,
+        }
+    }
 }
@@ -0,0 +1,114 @@
+use micromath::F32Ext;
+use serde_derive::{Serialize, Deserialize};
+use strum_macros::{EnumIter, IntoStaticStr};
+
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Waveform {
+    #[default]
+    Sine,
+    Square,
+    Noise,
+    Dc,
+}
+
+/// Test signal generator, producing samples in calibrated DAC counts.
+///
+/// Phase is a 32-bit accumulator, so the frequency resolution is
+/// `sample_rate / 2^32` and there is no drift over long runs.
+pub struct SignalGenerator {
+    pub waveform: Waveform,
+    sample_rate: u32,
+    phase: u32,
+    phase_inc: u32,
+    amplitude: i32,
+    rng: fastrand::Rng,
+}
+
+impl SignalGenerator {
+    pub fn new(sample_rate: u32) -> Self {
+        SignalGenerator {
+            waveform: Waveform::default(),
+            sample_rate,
+            phase: 0,
+            phase_inc: 0,
+            amplitude: 0,
+            rng: fastrand::Rng::with_seed(0),
+        }
+    }
+
+    pub fn set_frequency(&mut self, hz: u32) {
+        self.phase_inc = ((hz as u64) << 32).checked_div(self.sample_rate as u64)
+                         .unwrap_or(0) as u32;
+    }
+
+    /// Peak amplitude in DAC counts. For `Dc`, this is the output level.
+    pub fn set_amplitude(&mut self, counts: i32) {
+        self.amplitude = counts;
+    }
+
+    pub fn next_sample(&mut self) -> i32 {
+        let phase = self.phase;
+        self.phase = self.phase.wrapping_add(self.phase_inc);
+        match self.waveform {
+            Waveform::Sine => {
+                let theta = (phase as f32 / 4294967296.0f32) * 2.0 * core::f32::consts::PI;
+                (F32Ext::sin(theta) * self.amplitude as f32) as i32
+            }
+            Waveform::Square => {
+                if phase < 0x8000_0000 { self.amplitude } else { -self.amplitude }
+            }
+            Waveform::Noise => {
+                // Levels may be negative, which would be an empty range.
+                let amplitude = self.amplitude.abs();
+                self.rng.i32(-amplitude..=amplitude)
+            }
+            Waveform::Dc => self.amplitude,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sine_frequency_amplitude() {
+        let fs = 48000;
+        let mut gen = SignalGenerator::new(fs);
+        gen.set_frequency(1000);
+        gen.set_amplitude(4000);
+        let samples: Vec<i32> = (0..fs).map(|_| gen.next_sample()).collect();
+
+        // One second of output should hold one rising zero crossing per cycle.
+        let crossings = samples.windows(2)
+            .filter(|w| w[0] < 0 && w[1] >= 0)
+            .count();
+        assert!((999..=1001).contains(&crossings), "crossings: {}", crossings);
+
+        // Peak should hit the requested amplitude (within approximation error).
+        let peak = *samples.iter().max().unwrap();
+        let trough = *samples.iter().min().unwrap();
+        assert!((peak - 4000).abs() < 40, "peak: {}", peak);
+        assert!((trough + 4000).abs() < 40, "trough: {}", trough);
+    }
+
+    #[test]
+    fn test_other_waveforms() {
+        let mut gen = SignalGenerator::new(48000);
+        gen.set_frequency(100);
+        gen.set_amplitude(2000);
+        gen.waveform = Waveform::Square;
+        assert!((0..480).all(|_| gen.next_sample().abs() == 2000));
+        gen.waveform = Waveform::Noise;
+        assert!((0..480).all(|_| gen.next_sample().abs() <= 2000));
+        gen.waveform = Waveform::Dc;
+        assert!((0..480).all(|_| gen.next_sample() == 2000));
+
+        // Negative levels invert, noise is unaffected.
+        gen.set_amplitude(-2000);
+        assert_eq!(gen.next_sample(), -2000);
+        gen.waveform = Waveform::Noise;
+        assert!((0..480).all(|_| gen.next_sample().abs() <= 2000));
+    }
+}
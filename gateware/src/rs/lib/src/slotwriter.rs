@@ -0,0 +1,162 @@
+// Sequential programming of a user slot in SPI flash, used by the bootloader
+// recovery mode as blocks of a new slot image arrive.
+
+use tiliqua_hal::nor_flash::NorFlash;
+use tiliqua_manifest::{MANIFEST_OFFSET, N_MANIFESTS, SLOT_BITSTREAM_BASE, SLOT_SIZE};
+
+#[derive(Debug, PartialEq)]
+pub enum SlotWriteError<E> {
+    /// Not a user slot. The bootloader slot can't be written this way.
+    InvalidSlot,
+    /// Empty chunk, or longer than a flash sector.
+    ChunkSize,
+    /// The image is larger than a slot.
+    SlotFull,
+    Flash(E),
+}
+
+/// Writes a slot image to user slot `slot`, in order, from its first byte.
+/// Each flash sector is erased before the first chunk that touches it.
+pub struct SlotWriter<F> {
+    flash: F,
+    base: u32,
+    written: u32,
+}
+
+impl<F: NorFlash> SlotWriter<F> {
+    /// Starts by erasing the slot's manifest, so an interrupted transfer
+    /// leaves an empty slot rather than a manifest describing half-written
+    /// regions.
+    pub fn new(mut flash: F, slot: usize) -> Result<Self, SlotWriteError<F::Error>> {
+        if slot >= N_MANIFESTS {
+            return Err(SlotWriteError::InvalidSlot);
+        }
+        let base = (SLOT_BITSTREAM_BASE + slot * SLOT_SIZE) as u32;
+        let manifest = base + MANIFEST_OFFSET as u32;
+        flash.erase(manifest, manifest + F::ERASE_SIZE as u32)
+             .map_err(SlotWriteError::Flash)?;
+        Ok(Self { flash, base, written: 0 })
+    }
+
+    /// Bytes written so far.
+    pub fn written(&self) -> u32 {
+        self.written
+    }
+
+    pub fn write(&mut self, chunk: &[u8]) -> Result<(), SlotWriteError<F::Error>> {
+        let sector = F::ERASE_SIZE as u32;
+        if chunk.is_empty() || chunk.len() > F::ERASE_SIZE {
+            return Err(SlotWriteError::ChunkSize);
+        }
+        let end = self.written + chunk.len() as u32;
+        if end > SLOT_SIZE as u32 {
+            return Err(SlotWriteError::SlotFull);
+        }
+        // Everything below `erased` was erased by an earlier chunk.
+        let erased = self.written.next_multiple_of(sector);
+        if end > erased {
+            self.flash.erase(self.base + erased, self.base + end.next_multiple_of(sector))
+                      .map_err(SlotWriteError::Flash)?;
+        }
+        self.flash.write(self.base + self.written, chunk).map_err(SlotWriteError::Flash)?;
+        self.written = end;
+        Ok(())
+    }
+
+    pub fn free(self) -> F {
+        self.flash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiliqua_hal::nor_flash::{ErrorType, NorFlashErrorKind, ReadNorFlash};
+
+    /// Flash that records erases, and only allows writes to erased bytes.
+    struct MockFlash {
+        mem: Vec<u8>,
+        erases: Vec<(u32, u32)>,
+    }
+
+    impl ErrorType for MockFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for MockFlash {
+        const READ_SIZE: usize = 1;
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            bytes.copy_from_slice(&self.mem[offset as usize..offset as usize + bytes.len()]);
+            Ok(())
+        }
+        fn capacity(&self) -> usize {
+            self.mem.len()
+        }
+    }
+
+    impl NorFlash for MockFlash {
+        const WRITE_SIZE: usize = 1;
+        const ERASE_SIZE: usize = 4096;
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            assert_eq!(from % 4096, 0);
+            assert_eq!(to % 4096, 0);
+            self.mem[from as usize..to as usize].fill(0xFF);
+            self.erases.push((from, to));
+            Ok(())
+        }
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            for (n, b) in bytes.iter().enumerate() {
+                let cell = &mut self.mem[offset as usize + n];
+                assert_eq!(*cell, 0xFF, "write to unerased byte at {:#x}", offset as usize + n);
+                *cell = *b;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_slot_writer() {
+        let flash = MockFlash {
+            mem: vec![0u8; SLOT_BITSTREAM_BASE + N_MANIFESTS * SLOT_SIZE],
+            erases: Vec::new(),
+        };
+        assert!(matches!(SlotWriter::new(flash, N_MANIFESTS), Err(SlotWriteError::InvalidSlot)));
+
+        let flash = MockFlash {
+            mem: vec![0u8; SLOT_BITSTREAM_BASE + N_MANIFESTS * SLOT_SIZE],
+            erases: Vec::new(),
+        };
+        let mut w = SlotWriter::new(flash, 2).unwrap();
+        let base = (SLOT_BITSTREAM_BASE + 2 * SLOT_SIZE) as u32;
+        let manifest = base + MANIFEST_OFFSET as u32;
+        assert_eq!(w.flash.erases, [(manifest, manifest + 4096)]);
+
+        // 128-byte blocks, then 1K blocks straddling a sector boundary.
+        for n in 0..31 {
+            w.write(&[n as u8; 128]).unwrap();
+        }
+        w.write(&[0xAA; 1024]).unwrap();
+        w.write(&[0xBB; 1024]).unwrap();
+        assert_eq!(w.written(), 31 * 128 + 2048);
+        assert_eq!(w.flash.erases[1..], [(base, base + 4096), (base + 4096, base + 8192)]);
+
+        assert_eq!(w.write(&[]), Err(SlotWriteError::ChunkSize));
+        assert_eq!(w.write(&[0u8; 8192]), Err(SlotWriteError::ChunkSize));
+
+        let flash = w.free();
+        let b = base as usize;
+        assert_eq!(flash.mem[b + 128], 1);
+        assert_eq!(flash.mem[b + 31 * 128], 0xAA);
+        assert_eq!(flash.mem[b + 31 * 128 + 1024], 0xBB);
+        // Nothing outside the slot was touched.
+        assert!(flash.mem[..b].iter().all(|x| *x == 0));
+        assert!(flash.mem[b + SLOT_SIZE..].iter().all(|x| *x == 0));
+
+        // Fill the rest of the slot.
+        let mut w = SlotWriter::new(flash, 2).unwrap();
+        for _ in 0..SLOT_SIZE / 4096 {
+            w.write(&[0x55; 4096]).unwrap();
+        }
+        assert_eq!(w.write(&[0x55; 128]), Err(SlotWriteError::SlotFull));
+    }
+}
@@ -5,11 +5,118 @@
 
 use opts::{Options, OptionsEncoderInterface};
 use crate::leds;
+use crate::idle::{Activity, IdleMonitor, IdleTransition};
 use embedded_hal::i2c::I2c;
-use tiliqua_hal::encoder::Encoder;
+use tiliqua_hal::encoder::{Encoder, PressConfig, PressKind};
 use tiliqua_hal::pmod::EurorackPmod;
 use tiliqua_hal::pca9635::{Pca9635Driver, Pca9635};
 
+/// Default cap on encoder acceleration, see `UI::set_accel`.
+pub const ACCEL_MAX_MULTIPLIER: u8 = 8;
+
+/// Second press within this long of the first is a double press, which
+/// resets the option being modified to its default.
+pub const DOUBLE_PRESS_MS: u32 = 250;
+
+/// Touching an unplugged jack harder than this counts as activity for
+/// the screensaver.
+pub const SCREENSAVER_TOUCH_THRESHOLD: u8 = 200;
+
+/// How many times to apply `ticks` that arrived in a single update. One
+/// detent is always a single step, faster turns grow as `1 + ticks²`.
+fn accel_multiplier(ticks: i8, max_multiplier: u8) -> u8 {
+    let n = ticks.unsigned_abs() as u32;
+    if n <= 1 {
+        return 1;
+    }
+    (1 + n * n).min(max_multiplier.max(1) as u32) as u8
+}
+
+/// Digit-by-digit entry of an integer option, for ranges too large to
+/// comfortably tick through. Rotation selects the active digit and each
+/// press adds one to it, carrying into the digits above.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DigitEdit {
+    value: i32,
+    min: i32,
+    max: i32,
+    digit: u8,
+    n_digits: u8,
+}
+
+impl DigitEdit {
+    /// Starts on the most significant digit of the range.
+    pub fn new(value: i32, min: i32, max: i32) -> Self {
+        let largest = min.unsigned_abs().max(max.unsigned_abs());
+        let n_digits = largest.checked_ilog10().unwrap_or(0) as u8 + 1;
+        Self {
+            value: value.clamp(min, max),
+            min,
+            max,
+            digit: n_digits - 1,
+            n_digits,
+        }
+    }
+
+    pub fn value(&self) -> i32 {
+        self.value
+    }
+
+    /// Active digit, 0 being the ones.
+    pub fn digit(&self) -> u8 {
+        self.digit
+    }
+
+    pub fn n_digits(&self) -> u8 {
+        self.n_digits
+    }
+
+    /// Clockwise moves the active digit right, towards the ones.
+    pub fn select(&mut self, ticks: i8) {
+        let digit = self.digit as i32 - ticks as i32;
+        self.digit = digit.clamp(0, self.n_digits as i32 - 1) as u8;
+    }
+
+    /// Same range and active digit, at another value.
+    pub fn with_value(self, value: i32) -> Self {
+        Self { value: value.clamp(self.min, self.max), ..self }
+    }
+
+    /// Add one to the active digit. Past the top of the range, wraps
+    /// around to the bottom.
+    pub fn increment(&mut self) {
+        let step = 10i64.pow(self.digit as u32);
+        let next = self.value as i64 + step;
+        self.value = if next > self.max as i64 { self.min } else { next as i32 };
+    }
+}
+
+/// Tracks how long the UI has gone untouched, to blank or dim the display
+/// before static elements burn in. Disabled with a timeout of 0.
+#[derive(Default)]
+pub struct Screensaver {
+    monitor: Option<IdleMonitor>,
+}
+
+impl Screensaver {
+    pub fn new(timeout_ms: u32) -> Self {
+        Self {
+            monitor: (timeout_ms > 0).then(|| IdleMonitor::new(timeout_ms)),
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.monitor.as_ref().is_some_and(IdleMonitor::idle)
+    }
+
+    /// Enters after `timeout_ms` without `user_activity`, and exits on the
+    /// first update with any.
+    pub fn update(&mut self, elapsed_ms: u32, user_activity: bool) -> Option<IdleTransition> {
+        let activity = Activity { user: user_activity, ..Default::default() };
+        self.monitor.as_mut()?.update(elapsed_ms, &activity)
+    }
+}
+
 pub struct UI<EncoderT, PmodT, MoboI2CT, OptionsT>
 where
     EncoderT: Encoder,
@@ -28,7 +135,12 @@ where
     period_ms: u32,
     encoder_fade_ms: u32,
     touch_led_mask: u8,
+    accel_max_multiplier: u8,
+    digit_edit_enabled: bool,
+    digit_edit: Option<DigitEdit>,
+    double_press: bool,
     draw: bool,
+    screensaver: Screensaver,
 }
 
 impl<EncoderT: Encoder,
@@ -36,8 +148,12 @@ impl<EncoderT: Encoder,
      MoboI2CT: I2c,
      OptionsT: Options + OptionsEncoderInterface>
          UI<EncoderT, PmodT, MoboI2CT, OptionsT> {
-    pub fn new(opts: OptionsT, period_ms: u32, encoder: EncoderT,
+    pub fn new(opts: OptionsT, period_ms: u32, mut encoder: EncoderT,
                pca9635: Pca9635Driver<MoboI2CT>, pmod: PmodT) -> Self {
+        // Double presses are only enabled while modifying, see
+        // `update_press_config`.
+        encoder.set_press_config(PressConfig::from_ms(period_ms, 20, 500, 0));
+        encoder.set_update_period_ms(period_ms);
         Self {
             opts,
             encoder,
@@ -50,7 +166,12 @@ impl<EncoderT: Encoder,
             period_ms,
             encoder_fade_ms: 1000u32,
             touch_led_mask: 0u8,
+            accel_max_multiplier: ACCEL_MAX_MULTIPLIER,
+            digit_edit_enabled: false,
+            digit_edit: None,
+            double_press: false,
             draw: true,
+            screensaver: Screensaver::default(),
         }
     }
 
@@ -63,6 +184,13 @@ impl<EncoderT: Encoder,
         self.time_since_encoder_touched = 0;
     }
 
+    /// Encoder acceleration when modifying an option value. If several ticks
+    /// arrive in one update, they are applied up to `max_multiplier` times.
+    /// Disable it where precise single steps matter (e.g. calibration).
+    pub fn set_accel(&mut self, enabled: bool, max_multiplier: u8) {
+        self.accel_max_multiplier = if enabled { max_multiplier } else { 1 };
+    }
+
     pub fn touch_led_mask(&mut self, mask: u8) {
         self.touch_led_mask = mask;
     }
@@ -71,10 +199,73 @@ impl<EncoderT: Encoder,
         self.draw
     }
 
+    /// Start the screensaver after `timeout_ms` without the encoder being
+    /// used or an unplugged jack touched. 0 (the default) disables it.
+    pub fn set_screensaver_timeout(&mut self, timeout_ms: u32) {
+        self.screensaver = Screensaver::new(timeout_ms);
+    }
+
+    /// Whether the screensaver is running. It is up to the application to
+    /// blank or dim the display while it is, and to restore it afterwards.
+    pub fn screensaver(&self) -> bool {
+        self.screensaver.active()
+    }
+
+    /// Allow digit entry (see `digit_edit`). Off by default, as only the
+    /// application can draw it. Otherwise a long press always backs out.
+    pub fn set_digit_edit(&mut self, enabled: bool) {
+        self.digit_edit_enabled = enabled;
+        if !enabled {
+            self.digit_edit = None;
+        }
+    }
+
+    /// Digit entry in progress, if any. Entered by a long press while
+    /// modifying an integer option, left by another long press.
+    pub fn digit_edit(&self) -> Option<DigitEdit> {
+        self.digit_edit
+    }
+
+    fn selected_int_range(&self) -> Option<(i32, i32, i32)> {
+        let n = self.opts.selected()?;
+        let options = self.opts.view().options();
+        let (min, max) = options[n].int_range()?;
+        Some((options[n].int_value()?, min, max))
+    }
+
+    fn apply_digit_edit(&mut self) {
+        if let (Some(edit), Some(n)) = (self.digit_edit, self.opts.selected()) {
+            self.opts.view_mut().options_mut()[n].set_int_value(edit.value());
+            // The option rounds to its step, continue from what it holds.
+            if let Some((value, _, _)) = self.selected_int_range() {
+                self.digit_edit = Some(edit.with_value(value));
+            }
+        }
+    }
+
+    /// Double presses reset the option being modified to its default.
+    /// Elsewhere they are not used, so they are left disabled to avoid
+    /// delaying every short press waiting for a second one. Digit entry
+    /// also counts quick presses one by one.
+    fn update_press_config(&mut self) {
+        let double_press = self.opts.modify() && self.opts.selected().is_some() &&
+                           self.digit_edit.is_none();
+        if double_press != self.double_press {
+            self.double_press = double_press;
+            let double_gap_ms = if double_press { DOUBLE_PRESS_MS } else { 0 };
+            self.encoder.set_press_config(
+                PressConfig::from_ms(self.period_ms, 20, 500, double_gap_ms));
+        }
+    }
+
     pub fn encoder_recently_touched(&self, threshold_ms: u32) -> bool {
         self.time_since_encoder_touched < threshold_ms
     }
 
+    pub fn midi_recently_active(&self, threshold_ms: u32) -> bool {
+        self.time_since_midi_activity < threshold_ms
+    }
+
     pub fn update(&mut self) {
         //
         // Consume encoder, update options
@@ -87,15 +278,82 @@ impl<EncoderT: Encoder,
         self.uptime_ms += self.period_ms;
 
         let ticks = self.encoder.poke_ticks();
-        if ticks != 0 {
-            self.opts.consume_ticks(ticks);
+        let press = self.encoder.poke_press();
+        if ticks != 0 || press.is_some() {
             self.time_since_encoder_touched = 0;
         }
-        if self.encoder.poke_btn() {
-            self.opts.toggle_modify();
-            self.time_since_encoder_touched = 0;
+
+        // Anything else moving the selection (e.g. MIDI) ends digit entry.
+        if !self.opts.modify() || self.selected_int_range().is_none() {
+            self.digit_edit = None;
+        }
+
+        if let Some(edit) = self.digit_edit.as_mut() {
+            edit.select(ticks);
+            match press {
+                Some(PressKind::Short) | Some(PressKind::Double) => {
+                    edit.increment();
+                    self.apply_digit_edit();
+                }
+                Some(PressKind::Long) => {
+                    self.digit_edit = None;
+                }
+                None => {}
+            }
+        } else {
+            if ticks != 0 {
+                // Only accelerate value changes, never menu navigation. Each tick is
+                // applied through the option itself, so its min/max clamping holds.
+                let multiplier = if self.opts.modify() && self.opts.selected().is_some() {
+                    accel_multiplier(ticks, self.accel_max_multiplier)
+                } else {
+                    1
+                };
+                for _ in 0..multiplier {
+                    self.opts.consume_ticks(ticks);
+                }
+            }
+            match press {
+                Some(PressKind::Double) if self.opts.modify() && self.opts.selected().is_some() => {
+                    self.opts.reset_selected();
+                }
+                Some(PressKind::Short) | Some(PressKind::Double) => {
+                    self.opts.toggle_modify();
+                }
+                Some(PressKind::Long) => {
+                    if let (true, true, Some((value, min, max))) =
+                        (self.digit_edit_enabled, self.opts.modify(), self.selected_int_range()) {
+                        self.digit_edit = Some(DigitEdit::new(value, min, max));
+                    } else if self.opts.leave_group() {
+                        // Back out to the group header.
+                    } else {
+                        // Back out to the page selector.
+                        self.opts.modify_mut(false);
+                        if self.opts.page().n_unique_values() > 1 {
+                            self.opts.set_selected(None);
+                        }
+                    }
+                }
+                None => {}
+            }
         }
 
+        self.update_press_config();
+
+        //
+        // Screensaver
+        //
+
+        // Also covers `external_modify` since the last update.
+        let mut user_activity = self.time_since_encoder_touched <= self.period_ms;
+        let touch = self.pmod.touch();
+        for n in 0..8 {
+            if (self.pmod.jack() & (1<<n)) == 0 && touch[n] > SCREENSAVER_TOUCH_THRESHOLD {
+                user_activity = true;
+            }
+        }
+        self.screensaver.update(self.period_ms, user_activity);
+
         //
         // Update LEDs
         //
@@ -171,3 +429,106 @@ impl<EncoderT: Encoder,
         self.draw = self.time_since_encoder_touched < self.encoder_fade_ms || self.opts.modify();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accel_multiplier() {
+        // Single detents are never accelerated, in either direction.
+        assert_eq!(accel_multiplier(1, 8), 1);
+        assert_eq!(accel_multiplier(-1, 8), 1);
+        assert_eq!(accel_multiplier(2, 8), 5);
+        assert_eq!(accel_multiplier(-2, 8), 5);
+        assert_eq!(accel_multiplier(3, 8), 8);
+        assert_eq!(accel_multiplier(i8::MIN, 200), 200);
+        // Disabled
+        assert_eq!(accel_multiplier(4, 1), 1);
+        assert_eq!(accel_multiplier(4, 0), 1);
+    }
+
+    #[test]
+    fn test_digit_edit() {
+        let mut edit = DigitEdit::new(250, 0, 1000);
+        assert_eq!(edit.n_digits(), 4);
+        assert_eq!(edit.digit(), 3);
+
+        // Selection is clamped to the digits in range.
+        edit.select(-1);
+        assert_eq!(edit.digit(), 3);
+        edit.select(2);
+        assert_eq!(edit.digit(), 1);
+        edit.select(5);
+        assert_eq!(edit.digit(), 0);
+
+        // Increment carries into the digits above.
+        edit.select(-1);
+        for _ in 0..5 {
+            edit.increment();
+        }
+        assert_eq!(edit.value(), 300);
+        edit.select(1);
+        edit.increment();
+        assert_eq!(edit.value(), 301);
+
+        // And wraps to the bottom of the range past the top.
+        edit.select(-3);
+        edit.increment();
+        assert_eq!(edit.value(), 0);
+        edit.increment();
+        assert_eq!(edit.value(), 1000);
+
+        // Negative ranges count digits on the magnitude.
+        let mut edit = DigitEdit::new(-50, -128, 127);
+        assert_eq!(edit.n_digits(), 3);
+        edit.select(1);
+        edit.increment();
+        assert_eq!(edit.value(), -40);
+        assert_eq!(DigitEdit::new(0, 0, 9).n_digits(), 1);
+        assert_eq!(DigitEdit::new(500, 0, 100).value(), 100);
+
+        // Resynced to a value rounded by the option, the digit is kept.
+        let mut edit = DigitEdit::new(500, 0, 20000);
+        edit.select(3);
+        edit.increment();
+        assert_eq!(edit.value(), 510);
+        let edit = edit.with_value(500);
+        assert_eq!((edit.value(), edit.digit()), (500, 1));
+        assert_eq!(edit.with_value(30000).value(), 20000);
+    }
+
+    #[test]
+    fn test_screensaver() {
+        // Disabled by default.
+        let mut s = Screensaver::default();
+        for _ in 0..1000 {
+            assert_eq!(s.update(10, false), None);
+        }
+        assert!(!s.active());
+
+        let mut s = Screensaver::new(1000);
+        for _ in 0..99 {
+            assert_eq!(s.update(10, false), None);
+        }
+        assert!(!s.active());
+        assert_eq!(s.update(10, false), Some(IdleTransition::Enter));
+        assert!(s.active());
+        assert_eq!(s.update(10, false), None);
+
+        // Activity wakes it on the same update.
+        assert_eq!(s.update(10, true), Some(IdleTransition::Exit));
+        assert!(!s.active());
+        assert_eq!(s.update(10, true), None);
+
+        // And restarts the timeout.
+        for _ in 0..50 {
+            s.update(10, false);
+        }
+        s.update(10, true);
+        for _ in 0..99 {
+            assert_eq!(s.update(10, false), None);
+        }
+        assert_eq!(s.update(10, false), Some(IdleTransition::Enter));
+    }
+}
@@ -0,0 +1,162 @@
+use micromath::F32Ext;
+
+/// Samples in each single-cycle wave of a user wavetable.
+pub const WAVE_LEN: usize = 256;
+
+/// User wavetable: a sequence of single-cycle waves, each `WAVE_LEN`
+/// signed 16-bit samples, back to back (i.e. a raw mono s16le file).
+#[derive(Copy, Clone)]
+pub struct Wavetable<'a> {
+    waves: &'a [i16],
+}
+
+impl<'a> Wavetable<'a> {
+    /// `None` if `samples` is empty or not a whole number of waves.
+    pub fn new(samples: &'a [i16]) -> Option<Self> {
+        if samples.is_empty() || samples.len() % WAVE_LEN != 0 {
+            None
+        } else {
+            Some(Self { waves: samples })
+        }
+    }
+
+    pub fn n_waves(&self) -> usize {
+        self.waves.len() / WAVE_LEN
+    }
+
+    fn wave_sample(&self, wave: usize, phase: f32) -> f32 {
+        let wave = &self.waves[wave*WAVE_LEN..(wave+1)*WAVE_LEN];
+        let x = phase * WAVE_LEN as f32;
+        let i = x as usize;
+        let frac = x - i as f32;
+        let a = wave[i % WAVE_LEN] as f32;
+        let b = wave[(i + 1) % WAVE_LEN] as f32;
+        a + (b - a) * frac
+    }
+
+    /// Sample at `phase` in [0, 1) of a wave, interpolating linearly both
+    /// within each wave and across waves. `position` in [0, 1] sweeps from
+    /// the first to the last wave. Output is in [-1, 1).
+    pub fn sample(&self, phase: f32, position: f32) -> f32 {
+        let x = position.clamp(0.0, 1.0) * (self.n_waves() - 1) as f32;
+        let i = x as usize;
+        let frac = x - i as f32;
+        let a = self.wave_sample(i, phase);
+        let y = if frac > 0.0 {
+            a + (self.wave_sample(i + 1, phase) - a) * frac
+        } else {
+            a
+        };
+        y / 32768.0
+    }
+}
+
+/// 2^x, accurate to better than 0.1 cent for pitch conversion.
+fn exp2(x: f32) -> f32 {
+    let i = F32Ext::floor(x);
+    let f = x - i;
+    // Taylor series of 2^f = e^(f*ln2), f in [0, 1)
+    let p = 1.0 + f*(0.693_147_2 + f*(0.240_226_5 + f*(0.055_504_1 +
+                 f*(0.009_618_1 + f*(0.001_333_4 + f*0.000_154_0)))));
+    let e = (i as i32).clamp(-126, 127);
+    p * f32::from_bits(((e + 127) as u32) << 23)
+}
+
+/// Frequency in Hz of a (fractional) MIDI note, A4 (69) = 440Hz.
+pub fn note_to_frequency(note: f32) -> f32 {
+    440.0 * exp2((note - 69.0) / 12.0)
+}
+
+/// Oscillator scanning a [`Wavetable`].
+#[derive(Copy, Clone, Default)]
+pub struct WavetableOsc {
+    phase: f32,
+    position: f32,
+}
+
+impl WavetableOsc {
+    /// Render one block. `freq` is in cycles per sample. `position` is
+    /// ramped from the previous block's value to avoid zipper noise.
+    ///
+    /// `aux` is the same signal quantized to 5 bits, for a lo-fi version.
+    pub fn render(&mut self, table: &Wavetable, freq: f32, position: f32,
+                  out: &mut [f32], aux: &mut [f32]) {
+        let freq = freq.clamp(0.0, 0.5);
+        let position = position.clamp(0.0, 1.0);
+        let step = (position - self.position) / out.len() as f32;
+        for (o, a) in out.iter_mut().zip(aux.iter_mut()) {
+            self.position += step;
+            let y = table.sample(self.phase, self.position);
+            *o = y;
+            *a = F32Ext::floor(y * 16.0 + 0.5) / 16.0;
+            self.phase += freq;
+            if self.phase >= 1.0 {
+                self.phase -= 1.0;
+            }
+        }
+        self.position = position;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wavetable() {
+        assert!(Wavetable::new(&[]).is_none());
+        assert!(Wavetable::new(&[0i16; WAVE_LEN+1]).is_none());
+
+        // Wave 0 is a rising ramp, wave 1 is constant.
+        let mut samples = [0i16; 2*WAVE_LEN];
+        for i in 0..WAVE_LEN {
+            samples[i] = (i as i32 * 256 - 32768) as i16;
+            samples[WAVE_LEN+i] = 16384;
+        }
+        let table = Wavetable::new(&samples).unwrap();
+        assert_eq!(table.n_waves(), 2);
+
+        assert_eq!(table.sample(0.0, 0.0), -1.0);
+        assert_eq!(table.sample(0.5, 0.0), 0.0);
+        // Halfway between samples.
+        assert_eq!(table.sample(0.5 + 0.5 / WAVE_LEN as f32, 0.0), 256.0 / 65536.0);
+        // Wraps from the last sample back to the first.
+        let last = (255 * 256 - 32768) as f32 / 32768.0;
+        assert!((table.sample(1.0 - 0.5 / WAVE_LEN as f32, 0.0) - 0.5 * (last - 1.0)).abs() < 1e-6);
+        // Crossfade between waves, clamped at either end.
+        assert_eq!(table.sample(0.25, 1.0), 0.5);
+        assert_eq!(table.sample(0.25, 2.0), 0.5);
+        assert_eq!(table.sample(0.0, 0.5), -0.25);
+        assert_eq!(table.sample(0.0, -1.0), -1.0);
+    }
+
+    #[test]
+    fn test_note_to_frequency() {
+        for (note, hz) in [(69.0, 440.0), (81.0, 880.0), (57.0, 220.0),
+                           (60.0, 261.6256), (0.0, 8.175799), (127.5, 12911.417)] {
+            let err = note_to_frequency(note) / hz - 1.0;
+            // 0.1 cent is ~6e-5
+            assert!(err.abs() < 6e-5, "note {} err {}", note, err);
+        }
+    }
+
+    #[test]
+    fn test_wavetable_osc() {
+        let mut samples = [0i16; WAVE_LEN];
+        for i in 0..WAVE_LEN {
+            samples[i] = if i < WAVE_LEN/2 { 10000 } else { -10000 };
+        }
+        let table = Wavetable::new(&samples).unwrap();
+        let mut osc = WavetableOsc::default();
+        let mut out = [0.0f32; 128];
+        let mut aux = [0.0f32; 128];
+        // 32 samples per cycle.
+        osc.render(&table, 1.0 / 32.0, 0.0, &mut out, &mut aux);
+        for i in 0..out.len() {
+            let expect = if (i % 32) < 16 { 10000.0 / 32768.0 } else { -10000.0 / 32768.0 };
+            assert!((out[i] - expect).abs() < 1e-6, "{} {}", i, out[i]);
+            // 0.305 -> 5/16
+            assert_eq!(aux[i], if (i % 32) < 16 { 0.3125 } else { -0.3125 });
+        }
+    }
+}
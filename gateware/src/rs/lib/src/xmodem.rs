@@ -0,0 +1,193 @@
+// XMODEM receiver, used by the bootloader recovery mode to accept a slot
+// image over the UART. Supports 128-byte (SOH) and 1K (STX) blocks with
+// CRC-16, which is what `sx -k` and most terminal programs send. YMODEM
+// batch headers (block 0) are not supported.
+
+use crc::{Crc, CRC_16_XMODEM};
+
+pub const SOH: u8 = 0x01;
+pub const STX: u8 = 0x02;
+pub const EOT: u8 = 0x04;
+pub const ACK: u8 = 0x06;
+pub const NAK: u8 = 0x15;
+pub const CAN: u8 = 0x18;
+/// Sent (instead of NAK) to ask the sender to start in CRC-16 mode.
+pub const CRC_MODE: u8 = b'C';
+
+pub const BLOCK_MAX: usize = 1024;
+
+const CRC_ALGORITHM: Crc<u16> = Crc::<u16>::new(&CRC_16_XMODEM);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum XmodemEvent {
+    /// The next block arrived intact, see `XmodemReceiver::block()`.
+    /// Reply with `ACK` once it has been stored.
+    Block,
+    /// The sender missed our `ACK` and resent the last block. Reply `ACK`.
+    Repeat,
+    /// Bad block number or CRC. Reply `NAK` to have it resent.
+    Corrupt,
+    /// Blocks were skipped, the transfer can't continue. Reply `CAN`.
+    OutOfSequence,
+    /// End of transfer. Reply `ACK`.
+    End,
+    /// The sender aborted the transfer.
+    Cancelled,
+}
+
+/// Byte-at-a-time XMODEM packet decoder. Replies are left to the caller, so
+/// that a block can be stored before it is acknowledged.
+pub struct XmodemReceiver {
+    // Block number, its complement, data, 2 CRC bytes.
+    buf: [u8; BLOCK_MAX + 4],
+    len: usize,
+    // Data length of the packet being received, `None` between packets.
+    block_len: Option<usize>,
+    // Data length of the last `XmodemEvent::Block`.
+    last_len: usize,
+    next_block: u8,
+    n_blocks: u32,
+}
+
+impl Default for XmodemReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl XmodemReceiver {
+    pub fn new() -> Self {
+        Self {
+            buf: [0u8; BLOCK_MAX + 4],
+            len: 0,
+            block_len: None,
+            last_len: 0,
+            next_block: 1,
+            n_blocks: 0,
+        }
+    }
+
+    /// Blocks received so far (not counting repeats).
+    pub fn n_blocks(&self) -> u32 {
+        self.n_blocks
+    }
+
+    /// Data of the last `XmodemEvent::Block`, including any padding the
+    /// sender added to the final block. Only valid until the next byte is fed.
+    pub fn block(&self) -> &[u8] {
+        &self.buf[2..2 + self.last_len]
+    }
+
+    /// Drop any partially received packet, e.g. after the line went quiet.
+    /// The caller should `NAK` so that the sender retries.
+    pub fn timeout(&mut self) {
+        self.block_len = None;
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Option<XmodemEvent> {
+        let Some(block_len) = self.block_len else {
+            return match byte {
+                SOH => { self.start(128); None },
+                STX => { self.start(BLOCK_MAX); None },
+                EOT => Some(XmodemEvent::End),
+                CAN => Some(XmodemEvent::Cancelled),
+                // Line noise between packets.
+                _ => None,
+            };
+        };
+        self.buf[self.len] = byte;
+        self.len += 1;
+        if self.len < block_len + 4 {
+            return None;
+        }
+        self.block_len = None;
+        let event = self.check(block_len);
+        if event == XmodemEvent::Block {
+            self.last_len = block_len;
+            self.next_block = self.next_block.wrapping_add(1);
+            self.n_blocks += 1;
+        }
+        Some(event)
+    }
+
+    fn start(&mut self, block_len: usize) {
+        self.block_len = Some(block_len);
+        self.len = 0;
+    }
+
+    fn check(&self, block_len: usize) -> XmodemEvent {
+        let block = self.buf[0];
+        let data = &self.buf[2..2 + block_len];
+        let crc = u16::from_be_bytes([self.buf[2 + block_len], self.buf[3 + block_len]]);
+        if block != !self.buf[1] || CRC_ALGORITHM.checksum(data) != crc {
+            XmodemEvent::Corrupt
+        } else if block == self.next_block {
+            XmodemEvent::Block
+        } else if block == self.next_block.wrapping_sub(1) {
+            XmodemEvent::Repeat
+        } else {
+            XmodemEvent::OutOfSequence
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(block: u8, data: &[u8]) -> Vec<u8> {
+        let mut p = vec![if data.len() == 128 { SOH } else { STX }, block, !block];
+        p.extend_from_slice(data);
+        p.extend_from_slice(&CRC_ALGORITHM.checksum(data).to_be_bytes());
+        p
+    }
+
+    fn feed_all(rx: &mut XmodemReceiver, bytes: &[u8]) -> Vec<XmodemEvent> {
+        bytes.iter().filter_map(|b| rx.feed(*b)).collect()
+    }
+
+    #[test]
+    fn test_xmodem_receive() {
+        let mut rx = XmodemReceiver::new();
+        let a = [0x5Au8; 128];
+        let b: Vec<u8> = (0..1024).map(|n| n as u8).collect();
+
+        // Noise before the first packet is ignored.
+        assert_eq!(feed_all(&mut rx, &[0x00, 0xFF]), []);
+        assert_eq!(feed_all(&mut rx, &packet(1, &a)), [XmodemEvent::Block]);
+        assert_eq!(rx.block(), a);
+        assert_eq!(feed_all(&mut rx, &packet(2, &b)), [XmodemEvent::Block]);
+        assert_eq!(rx.block(), b);
+
+        // Resent after a lost ACK.
+        assert_eq!(feed_all(&mut rx, &packet(2, &b)), [XmodemEvent::Repeat]);
+        assert_eq!(rx.n_blocks(), 2);
+
+        // Flipped data bit, then a bad block number complement.
+        let mut bad = packet(3, &a);
+        bad[10] ^= 0x01;
+        assert_eq!(feed_all(&mut rx, &bad), [XmodemEvent::Corrupt]);
+        let mut bad = packet(3, &a);
+        bad[2] = 0;
+        assert_eq!(feed_all(&mut rx, &bad), [XmodemEvent::Corrupt]);
+
+        // A truncated packet is dropped on timeout, the resend is accepted.
+        assert_eq!(feed_all(&mut rx, &packet(3, &a)[..50]), []);
+        rx.timeout();
+        assert_eq!(feed_all(&mut rx, &packet(3, &a)), [XmodemEvent::Block]);
+
+        assert_eq!(feed_all(&mut rx, &packet(5, &a)), [XmodemEvent::OutOfSequence]);
+        assert_eq!(feed_all(&mut rx, &[EOT]), [XmodemEvent::End]);
+        assert_eq!(feed_all(&mut rx, &[CAN]), [XmodemEvent::Cancelled]);
+    }
+
+    #[test]
+    fn test_xmodem_block_number_wraps() {
+        let mut rx = XmodemReceiver::new();
+        let a = [0u8; 128];
+        for n in 1..=300u32 {
+            assert_eq!(feed_all(&mut rx, &packet(n as u8, &a)), [XmodemEvent::Block]);
+        }
+        assert_eq!(rx.n_blocks(), 300);
+    }
+}
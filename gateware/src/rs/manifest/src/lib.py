@@ -58,6 +58,11 @@ class RegionType(StrEnum):
     OptionStorage = "OptionStorage"  # Option storage region for persistent application settings
     Manifest = "Manifest"          # Manifest region containing metadata about the bitstream
 
+class Feature(StrEnum):
+    """Bootloader capabilities a bitstream may require, matching the Rust schema"""
+    DynamicModeline = "DynamicModeline"  # Bootloader forwards the EDID modeline (for `clk1_inherit`)
+    ExternalPll = "ExternalPll"          # Bootloader set up the external PLL
+
 @dataclass_json
 @dataclass
 class MemoryRegion:
@@ -136,11 +141,19 @@ class BitstreamManifest:
     regions: List[MemoryRegion]
     help: Optional[BitstreamHelp] = None
     external_pll_config: Optional[ExternalPLLConfig] = None
+    # Shown by the bootloader if a required feature is missing. Not checked,
+    # as there is no way to order git hashes.
+    min_bootloader_sha: Optional[str] = None
+    requires: List[Feature] = field(default_factory=list)
+    # Build time (seconds since the unix epoch), displayed by the bootloader
+    # and apps as a date, to tell apart several builds of the same app.
+    built_unix: Optional[int] = None
     magic: int = MANIFEST_MAGIC
 
     BITSTREAM_NAME_LEN = RUST_CONSTANTS['BITSTREAM_NAME_LEN']
     BITSTREAM_TAG_LEN = RUST_CONSTANTS['BITSTREAM_TAG_LEN']
     REGION_MAX_N = RUST_CONSTANTS['REGION_MAX_N']
+    REQUIRES_MAX_N = RUST_CONSTANTS['REQUIRES_MAX_N']
 
     def __post_init__(self):
         if len(self.name) > self.BITSTREAM_NAME_LEN:
@@ -149,6 +162,10 @@ class BitstreamManifest:
             raise ValueError(f"Field 'tag' (len={len(self.tag)}) is too long (max={self.BITSTREAM_TAG_LEN}).")
         if len(self.regions) > self.REGION_MAX_N:
             raise ValueError(f"Field 'regions' (len={len(self.regions)}) is too long (max={self.REGION_MAX_N}).")
+        if self.min_bootloader_sha is not None and len(self.min_bootloader_sha) > self.BITSTREAM_TAG_LEN:
+            raise ValueError(f"Field 'min_bootloader_sha' (len={len(self.min_bootloader_sha)}) is too long (max={self.BITSTREAM_TAG_LEN}).")
+        if len(self.requires) > self.REQUIRES_MAX_N:
+            raise ValueError(f"Field 'requires' (len={len(self.requires)}) is too long (max={self.REQUIRES_MAX_N}).")
 
     def write_to_path(self, manifest_path):
         # Clean up empty keys for improved backwards compatibility of manifests.
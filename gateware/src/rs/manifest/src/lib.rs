@@ -15,6 +15,7 @@
 use heapless::{String, Vec};
 use serde::{Deserialize, Serialize};
 use log::info;
+use strum_macros::IntoStaticStr;
 
 pub const FLASH_PAGE_SZ: u32         = 0x1000;
 pub const FLASH_SECTOR_SZ: u32       = 0x10000;
@@ -26,12 +27,29 @@ pub const MANIFEST_OFFSET: usize     = 0xF0000;
 pub const MANIFEST_SIZE: usize       = 0x1000;
 pub const BITSTREAM_NAME_LEN: usize  = 32;
 pub const BITSTREAM_TAG_LEN: usize   = 8;
-pub const REGION_MAX_N: usize        = 5;
+pub const REGION_MAX_N: usize        = 8;
 pub const REGION_FILE_LEN: usize     = 16;
 pub const HELP_BRIEF_MAX_SIZE: usize = 64;
 pub const HELP_IO_MAX_SIZE: usize    = 20;
 pub const HELP_IO_LEFT_N: usize      = 8;
 pub const HELP_IO_RIGHT_N: usize     = 6;
+pub const REQUIRES_MAX_N: usize      = 4;
+
+// PSRAM that `RamLoad` regions may be copied to. Below it is the framebuffer
+// (this is the default PSRAM `--fw-offset`), and the last page of PSRAM holds
+// BootInfo. Must match `psram_size` and `bootinfo_base` in `tiliqua_soc.py`.
+pub const PSRAM_LOAD_BASE: u32 = 0x200000;
+pub const PSRAM_LOAD_END: u32  = 0x1000000 - 0x1000;
+
+// Upper bounds on the serialized size of one `MemoryRegion` (longest
+// filename and region type, all optional fields present as 10-digit u32s)
+// and of everything else in a manifest. `lib.py` takes REGION_MAX_N from
+// here, so check a manifest with every region in use still fits.
+const REGION_JSON_MAX: usize    = 130 + REGION_FILE_LEN;
+const MANIFEST_JSON_BASE: usize = 256 + BITSTREAM_NAME_LEN + 2 * BITSTREAM_TAG_LEN + 64 +
+    HELP_BRIEF_MAX_SIZE + (HELP_IO_LEFT_N + HELP_IO_RIGHT_N) * (HELP_IO_MAX_SIZE + 3) +
+    REQUIRES_MAX_N * 24 + 24;
+const _: () = assert!(MANIFEST_JSON_BASE + REGION_MAX_N * REGION_JSON_MAX <= MANIFEST_SIZE);
 
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
 pub enum RegionType {
@@ -73,6 +91,89 @@ pub struct BitstreamHelp {
     pub io_right: [String<HELP_IO_MAX_SIZE>; HELP_IO_RIGHT_N],
 }
 
+/// Bootloader capabilities a bitstream may depend on.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, IntoStaticStr)]
+#[strum(serialize_all = "SCREAMING-KEBAB-CASE")]
+pub enum Feature {
+    /// Bootloader reads the display EDID and forwards its modeline, so
+    /// bitstreams can inherit the pixel clock (`clk1_inherit`).
+    DynamicModeline,
+    /// Bootloader has set up the external PLL and can reconfigure it.
+    ExternalPll,
+}
+
+/// Why a bootloader can't start a bitstream, see [`BitstreamManifest::check_compat`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompatError {
+    pub missing: Feature,
+    pub min_bootloader_sha: Option<String<BITSTREAM_TAG_LEN>>,
+}
+
+impl core::fmt::Display for CompatError {
+    // Short enough for the 32-character error line in the bootloader.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.min_bootloader_sha {
+            Some(sha) => write!(f, "NEEDS-BOOTLOADER-{}", sha),
+            None => {
+                let missing: &'static str = self.missing.into();
+                write!(f, "NEEDS-{}", missing)
+            }
+        }
+    }
+}
+
+/// Why the regions of a manifest can't be trusted, see [`BitstreamManifest::validate`].
+/// Regions are identified by their index in `regions`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ManifestError {
+    /// `spiflash_src` is not on a flash sector, or `psram_dst` not on a word.
+    Misaligned(usize),
+    /// Two regions share flash sectors.
+    FlashOverlap(usize, usize),
+    /// Two regions are copied to overlapping PSRAM.
+    PsramOverlap(usize, usize),
+    /// Region crosses the end of the slot it starts in, or of the flash.
+    FlashBounds(usize),
+    /// Region would be copied outside `PSRAM_LOAD_BASE..PSRAM_LOAD_END`.
+    PsramBounds(usize),
+}
+
+impl core::fmt::Display for ManifestError {
+    // Short enough for the 32-character error line in the bootloader.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ManifestError::Misaligned(n)      => write!(f, "REGION-{}-MISALIGNED", n),
+            ManifestError::FlashOverlap(a, b) => write!(f, "REGION-{}-{}-FLASH-OVERLAP", a, b),
+            ManifestError::PsramOverlap(a, b) => write!(f, "REGION-{}-{}-PSRAM-OVERLAP", a, b),
+            ManifestError::FlashBounds(n)     => write!(f, "REGION-{}-FLASH-BOUNDS", n),
+            ManifestError::PsramBounds(n)     => write!(f, "REGION-{}-PSRAM-BOUNDS", n),
+        }
+    }
+}
+
+impl MemoryRegion {
+    /// Flash sectors this region occupies. Regions are erased and written
+    /// in whole sectors, so that is what must not overlap.
+    fn flash_range(&self) -> Option<core::ops::Range<u64>> {
+        let src = self.spiflash_src? as u64;
+        let sector = FLASH_SECTOR_SZ as u64;
+        Some(src..(src + self.size as u64).div_ceil(sector) * sector)
+    }
+
+    /// PSRAM written by the bootloader, only for `RamLoad` regions.
+    fn psram_range(&self) -> Option<core::ops::Range<u64>> {
+        if self.region_type != RegionType::RamLoad {
+            return None;
+        }
+        let dst = self.psram_dst? as u64;
+        Some(dst..dst + (self.size as u64).div_ceil(4) * 4)
+    }
+}
+
+fn overlaps(a: &core::ops::Range<u64>, b: &core::ops::Range<u64>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
 #[derive(Deserialize, Serialize, Clone)]
 pub struct BitstreamManifest {
     pub hw_rev: u32,
@@ -81,9 +182,68 @@ pub struct BitstreamManifest {
     pub regions: Vec<MemoryRegion, REGION_MAX_N>,
     pub help: Option<BitstreamHelp>,
     pub external_pll_config: Option<ExternalPLLConfig>,
+    /// Informational only: git hashes have no order, so this can't be
+    /// checked. It is reported if a feature in `requires` is missing.
+    ///
+    /// This and `requires` are parsed from JSON by `from_slice`, but
+    /// skipped here so the postcard layout of `BootInfo`, which flashed
+    /// bitstreams decode, stays the same (see `ManifestJsonExt`).
+    #[serde(skip)]
+    pub min_bootloader_sha: Option<String<BITSTREAM_TAG_LEN>>,
+    #[serde(skip)]
+    pub requires: Vec<Feature, REQUIRES_MAX_N>,
+    /// Build time (seconds since the unix epoch), to tell apart several
+    /// builds of the same app. Missing from manifests of older builds.
+    /// JSON only, like `min_bootloader_sha`.
+    #[serde(skip)]
+    pub built_unix: Option<u32>,
     pub magic: u32,
 }
 
+/// `BitstreamManifest` fields that are only (de)serialized as JSON, not
+/// as part of `BootInfo`, which carries them in its extension record.
+#[derive(Deserialize, Serialize, Default)]
+struct ManifestJsonExt {
+    min_bootloader_sha: Option<String<BITSTREAM_TAG_LEN>>,
+    #[serde(default)]
+    requires: Vec<Feature, REQUIRES_MAX_N>,
+    built_unix: Option<u32>,
+}
+
+/// Calendar date (UTC) of a unix timestamp, displayed as YYYY-MM-DD.
+///
+/// There is no RTC, so only absolute dates can be shown, never the time
+/// since a build.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BuildDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl BuildDate {
+    pub fn from_unix(secs: u32) -> Self {
+        // Days to civil date, from Howard Hinnant's `civil_from_days`,
+        // with eras starting on 0000-03-01. Always after 1970 for a u32.
+        let z = secs / 86400 + 719468;
+        let era = z / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+        let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u8;
+        let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as u16;
+        BuildDate { year, month, day }
+    }
+}
+
+impl core::fmt::Display for BuildDate {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
 impl BitstreamManifest {
 
     pub fn print(&self) {
@@ -98,6 +258,15 @@ impl BitstreamManifest {
             info!("\t\tvideo:   '{}'", help.video);
             info!("\t}}");
         }
+        if let Some(sha) = &self.min_bootloader_sha {
+            info!("\tmin_bootloader_sha: '{}'", sha);
+        }
+        if !self.requires.is_empty() {
+            info!("\trequires: {:?}", self.requires);
+        }
+        if let Some(date) = self.build_date() {
+            info!("\tbuilt:    {}", date);
+        }
         if let Some(clocks) = &self.external_pll_config {
             info!("\texternal_pll_config = {{");
             info!("\t\tclk0_hz: {}", clocks.clk0_hz);
@@ -127,9 +296,13 @@ impl BitstreamManifest {
     }
 
     pub fn from_slice(manifest_slice: &[u8]) -> Option<BitstreamManifest> {
-        let manifest_de = serde_json_core::from_slice::<BitstreamManifest>(manifest_slice);
+        let manifest_de = serde_json_core::from_slice::<BitstreamManifest>(manifest_slice)
+            .and_then(|(manifest, _rest)| {
+                let (ext, _rest) = serde_json_core::from_slice::<ManifestJsonExt>(manifest_slice)?;
+                Ok(manifest.with_json_ext(ext))
+            });
         match manifest_de {
-            Ok((contents, _rest)) => {
+            Ok(contents) => {
                 info!("BitstreamManifest: parse OK");
                 Some(contents)
             }
@@ -140,6 +313,13 @@ impl BitstreamManifest {
         }
     }
 
+    fn with_json_ext(mut self, ext: ManifestJsonExt) -> Self {
+        self.min_bootloader_sha = ext.min_bootloader_sha;
+        self.requires = ext.requires;
+        self.built_unix = ext.built_unix;
+        self
+    }
+
     pub fn from_addr(addr: usize, size: usize) -> Option<BitstreamManifest> {
 
         let manifest_slice = unsafe {
@@ -171,6 +351,63 @@ impl BitstreamManifest {
         Self::from_slice(manifest_slice)
     }
 
+    /// Check that every feature this bitstream `requires` is in `supported`,
+    /// i.e. that the running bootloader is able to start it.
+    pub fn check_compat(&self, supported: &[Feature]) -> Result<(), CompatError> {
+        match self.requires.iter().find(|f| !supported.contains(f)) {
+            Some(missing) => Err(CompatError {
+                missing: *missing,
+                min_bootloader_sha: self.min_bootloader_sha.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Sanity check the regions, before the bootloader copies anything
+    /// around based on them. A malformed manifest could otherwise have it
+    /// write over the framebuffer, BootInfo or another region.
+    ///
+    /// Regions without a `spiflash_src` (e.g. in simulation) are skipped.
+    pub fn validate(&self) -> Result<(), ManifestError> {
+        let flash_end = (SLOT_BITSTREAM_BASE + N_MANIFESTS * SLOT_SIZE) as u64;
+        let slot_size = SLOT_SIZE as u64;
+        for (n, region) in self.regions.iter().enumerate() {
+            let misaligned_src = region.spiflash_src.is_some_and(|src| src % FLASH_SECTOR_SZ != 0);
+            let misaligned_dst = region.psram_range().is_some_and(|dst| dst.start % 4 != 0);
+            if misaligned_src || misaligned_dst {
+                return Err(ManifestError::Misaligned(n));
+            }
+            if let Some(flash) = region.flash_range() {
+                let slot_end = (flash.start / slot_size + 1) * slot_size;
+                if flash.end > slot_end.min(flash_end) {
+                    return Err(ManifestError::FlashBounds(n));
+                }
+            }
+            if let Some(psram) = region.psram_range() {
+                if psram.start < PSRAM_LOAD_BASE as u64 || psram.end > PSRAM_LOAD_END as u64 {
+                    return Err(ManifestError::PsramBounds(n));
+                }
+            }
+            for (m, other) in self.regions.iter().enumerate().take(n) {
+                if let (Some(a), Some(b)) = (other.flash_range(), region.flash_range()) {
+                    if overlaps(&a, &b) {
+                        return Err(ManifestError::FlashOverlap(m, n));
+                    }
+                }
+                if let (Some(a), Some(b)) = (other.psram_range(), region.psram_range()) {
+                    if overlaps(&a, &b) {
+                        return Err(ManifestError::PsramOverlap(m, n));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn build_date(&self) -> Option<BuildDate> {
+        self.built_unix.map(BuildDate::from_unix)
+    }
+
     pub fn get_option_storage_window(&self) -> Option<core::ops::Range<u32>> {
         for region in self.regions.iter() {
             if region.region_type == RegionType::OptionStorage {
@@ -181,4 +418,213 @@ impl BitstreamManifest {
         }
         None
     }
+
+    /// A `RamLoad` region with this filename, which the bootloader has
+    /// already copied to PSRAM by the time the bitstream starts.
+    pub fn get_ram_region(&self, filename: &str) -> Option<&MemoryRegion> {
+        self.regions.iter().find(|region| {
+            region.region_type == RegionType::RamLoad && region.filename == filename
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `to_slice` leaves out the fields that are only parsed as JSON, so
+    // splice them in, like the manifests written by `lib.py` have them.
+    fn to_json(manifest: &BitstreamManifest, buf: &mut [u8]) -> usize {
+        let ext = ManifestJsonExt {
+            min_bootloader_sha: manifest.min_bootloader_sha.clone(),
+            requires: manifest.requires.clone(),
+            built_unix: manifest.built_unix,
+        };
+        let n_ext = serde_json_core::to_slice(&ext, buf).unwrap();
+        let n = serde_json_core::to_slice(manifest, &mut buf[n_ext-1..]).unwrap();
+        buf[n_ext-1] = b',';
+        n_ext - 1 + n
+    }
+
+    #[test]
+    fn test_manifest_roundtrip_max_regions() {
+        let region_types = [
+            RegionType::Bitstream,
+            RegionType::XipFirmware,
+            RegionType::RamLoad,
+            RegionType::OptionStorage,
+            RegionType::Manifest,
+        ];
+        let mut manifest = BitstreamManifest {
+            hw_rev: 5,
+            name: String::try_from("SAMPLER").unwrap(),
+            tag: String::try_from("v1.2.3").unwrap(),
+            regions: Vec::new(),
+            help: Some(BitstreamHelp {
+                brief: String::try_from("x".repeat(HELP_BRIEF_MAX_SIZE).as_str()).unwrap(),
+                video: String::try_from("1280x720p60").unwrap(),
+                io_left: core::array::from_fn(|_| String::try_from("y".repeat(HELP_IO_MAX_SIZE).as_str()).unwrap()),
+                io_right: core::array::from_fn(|_| String::try_from("z".repeat(HELP_IO_MAX_SIZE).as_str()).unwrap()),
+            }),
+            external_pll_config: Some(ExternalPLLConfig {
+                clk0_hz: 49152000,
+                clk1_hz: Some(74250000),
+                clk1_inherit: false,
+                spread_spectrum: Some(0.01),
+            }),
+            min_bootloader_sha: Some(String::try_from("x".repeat(BITSTREAM_TAG_LEN).as_str()).unwrap()),
+            requires: Vec::from_slice(&[Feature::DynamicModeline; REQUIRES_MAX_N]).unwrap(),
+            built_unix: Some(u32::MAX),
+            magic: MANIFEST_MAGIC,
+        };
+        for i in 0..REGION_MAX_N {
+            manifest.regions.push(MemoryRegion {
+                filename: String::try_from(format!("region{:06}.bin", i).as_str()).unwrap(),
+                region_type: region_types[i % region_types.len()].clone(),
+                spiflash_src: Some(0x1000000 + (i as u32) * 0x100000),
+                psram_dst: Some(u32::MAX - i as u32),
+                size: 0xf0000,
+                crc: Some(u32::MAX),
+            }).ok().unwrap();
+        }
+
+        let mut buf = [0u8; MANIFEST_SIZE];
+        let n = to_json(&manifest, &mut buf);
+        assert!(n <= MANIFEST_JSON_BASE + REGION_MAX_N * REGION_JSON_MAX);
+
+        let parsed = BitstreamManifest::from_slice(&buf[..n]).unwrap();
+        assert_eq!(parsed.regions.len(), REGION_MAX_N);
+        for (a, b) in parsed.regions.iter().zip(manifest.regions.iter()) {
+            assert_eq!(a.filename, b.filename);
+            assert_eq!(a.region_type, b.region_type);
+            assert_eq!(a.spiflash_src, b.spiflash_src);
+            assert_eq!(a.psram_dst, b.psram_dst);
+            assert_eq!(a.size, b.size);
+            assert_eq!(a.crc, b.crc);
+        }
+        assert_eq!(parsed.name, manifest.name);
+        assert_eq!(parsed.magic, MANIFEST_MAGIC);
+        assert_eq!(parsed.get_option_storage_window(), Some(0x1300000..0x13f0000));
+        assert_eq!(parsed.get_ram_region("region000002.bin").unwrap().psram_dst, Some(u32::MAX - 2));
+        // Wrong region type, and missing.
+        assert!(parsed.get_ram_region("region000001.bin").is_none());
+        assert!(parsed.get_ram_region("wavetable.bin").is_none());
+        assert_eq!(parsed.min_bootloader_sha, manifest.min_bootloader_sha);
+        assert_eq!(parsed.requires, manifest.requires);
+        assert_eq!(parsed.built_unix, Some(u32::MAX));
+    }
+
+    fn region(filename: &str, region_type: RegionType, spiflash_src: Option<u32>,
+              psram_dst: Option<u32>, size: u32) -> MemoryRegion {
+        MemoryRegion {
+            filename: String::try_from(filename).unwrap(),
+            region_type, spiflash_src, psram_dst, size, crc: None,
+        }
+    }
+
+    #[test]
+    fn test_manifest_validate() {
+        // Layout of a real user bitstream in slot 2.
+        let mut manifest = BitstreamManifest::from_slice(
+            br#"{"hw_rev":5,"name":"XBEAM","tag":"v1.0.0","regions":[],"magic":4277010159}"#).unwrap();
+        manifest.regions = Vec::from_slice(&[
+            region("top.bit", RegionType::Bitstream, Some(0x300000), None, 0x8c000),
+            region("firmware.bin", RegionType::RamLoad, Some(0x390000), Some(0x200000), 0x30002),
+            region("<options>", RegionType::OptionStorage, Some(0x3e0000), None, 0x2000),
+            region("manifest.json", RegionType::Manifest, Some(0x3f0000), None, MANIFEST_SIZE as u32),
+        ]).ok().unwrap();
+        assert_eq!(manifest.validate(), Ok(()));
+
+        let check = |edit: &dyn Fn(&mut BitstreamManifest)| {
+            let mut m = manifest.clone();
+            edit(&mut m);
+            m.validate()
+        };
+
+        // Misaligned
+        assert_eq!(check(&|m| m.regions[1].spiflash_src = Some(0x391000)),
+                   Err(ManifestError::Misaligned(1)));
+        assert_eq!(check(&|m| m.regions[1].psram_dst = Some(0x200002)),
+                   Err(ManifestError::Misaligned(1)));
+        // Only RamLoad regions are copied to PSRAM.
+        assert_eq!(check(&|m| m.regions[2].psram_dst = Some(0x3)), Ok(()));
+
+        // Overlapping: regions occupy whole sectors in flash.
+        assert_eq!(check(&|m| m.regions[1].size = 0x50001),
+                   Err(ManifestError::FlashOverlap(1, 2)));
+        assert_eq!(check(&|m| m.regions[1].size = 0x50000), Ok(()));
+        assert_eq!(check(&|m| m.regions[3].spiflash_src = Some(0x300000)),
+                   Err(ManifestError::FlashOverlap(0, 3)));
+        assert_eq!(check(&|m| m.regions.push(
+            region("wavetable.bin", RegionType::RamLoad, Some(0x3d0000), Some(0x230000), 0x1000)).ok().unwrap()),
+                   Err(ManifestError::PsramOverlap(1, 4)));
+        assert_eq!(check(&|m| m.regions.push(
+            region("wavetable.bin", RegionType::RamLoad, Some(0x3d0000), Some(0x230004), 0x1000)).ok().unwrap()),
+                   Ok(()));
+
+        // Oversized
+        assert_eq!(check(&|m| m.regions[3].size = 0x10001),
+                   Err(ManifestError::FlashBounds(3)));
+        assert_eq!(check(&|m| m.regions[0].spiflash_src = Some(0x900000)),
+                   Err(ManifestError::FlashBounds(0)));
+        assert_eq!(check(&|m| m.regions[0].size = u32::MAX),
+                   Err(ManifestError::FlashBounds(0)));
+        assert_eq!(check(&|m| m.regions[1].psram_dst = Some(0x1f0000)),
+                   Err(ManifestError::PsramBounds(1)));
+        assert_eq!(check(&|m| m.regions[1].psram_dst = Some(PSRAM_LOAD_END - 0x30000)),
+                   Err(ManifestError::PsramBounds(1)));
+        assert_eq!(check(&|m| m.regions[1].psram_dst = Some(PSRAM_LOAD_END - 0x30004)), Ok(()));
+        assert_eq!(check(&|m| m.regions[1].psram_dst = Some(u32::MAX - 3)),
+                   Err(ManifestError::PsramBounds(1)));
+
+        // Regions without a flash address (simulation) are skipped.
+        assert_eq!(check(&|m| m.regions[3].spiflash_src = None), Ok(()));
+
+        let err = ManifestError::PsramOverlap(REGION_MAX_N, REGION_MAX_N);
+        assert_eq!(format!("{}", err), "REGION-8-8-PSRAM-OVERLAP");
+        assert!(format!("{}", err).len() <= 32);
+    }
+
+    #[test]
+    fn test_build_date() {
+        let date = |secs| format!("{}", BuildDate::from_unix(secs));
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(951_782_400), "2000-02-29");
+        assert_eq!(date(951_868_799), "2000-02-29");
+        assert_eq!(date(951_868_800), "2000-03-01");
+        assert_eq!(date(1_735_689_599), "2024-12-31");
+        assert_eq!(date(1_792_195_200), "2026-10-17");
+        assert_eq!(date(u32::MAX), "2106-02-07");
+    }
+
+    #[test]
+    fn test_manifest_compat() {
+        // Manifests from before `min_bootloader_sha` and `requires` still parse.
+        let legacy = br#"{"hw_rev":5,"name":"XBEAM","tag":"v1.0.0","regions":[],"magic":4277010159}"#;
+        let manifest = BitstreamManifest::from_slice(legacy).unwrap();
+        assert_eq!(manifest.min_bootloader_sha, None);
+        assert!(manifest.requires.is_empty());
+        assert_eq!(manifest.built_unix, None);
+        assert_eq!(manifest.build_date(), None);
+        assert_eq!(manifest.check_compat(&[]), Ok(()));
+
+        let json = br#"{"hw_rev":5,"name":"XBEAM","tag":"v1.1.0","regions":[],
+                        "requires":["ExternalPll","DynamicModeline"],"magic":4277010159}"#;
+        let mut manifest = BitstreamManifest::from_slice(json).unwrap();
+        assert_eq!(manifest.requires, [Feature::ExternalPll, Feature::DynamicModeline]);
+        assert_eq!(manifest.check_compat(&[Feature::DynamicModeline, Feature::ExternalPll]), Ok(()));
+
+        // Static modeline bootloader: the first missing feature is reported.
+        let err = manifest.check_compat(&[Feature::ExternalPll]).unwrap_err();
+        assert_eq!(err.missing, Feature::DynamicModeline);
+        assert_eq!(format!("{}", err), "NEEDS-DYNAMIC-MODELINE");
+        let err = manifest.check_compat(&[]).unwrap_err();
+        assert_eq!(err.missing, Feature::ExternalPll);
+
+        // With a known-good bootloader, that is more useful to show.
+        manifest.min_bootloader_sha = Some(String::try_from("1a2b3c4d").unwrap());
+        let err = manifest.check_compat(&[]).unwrap_err();
+        assert_eq!(format!("{}", err), "NEEDS-BOOTLOADER-1a2b3c4d");
+        assert!(format!("{}", err).len() <= 32);
+    }
 }
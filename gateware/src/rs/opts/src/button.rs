@@ -17,7 +17,9 @@ pub struct ButtonOption<T: ButtonOptionParams> {
     // value delayed by 1 poll, only used for drawing oneshot
     value_delay1: bool,
     init: bool,
+    default: bool,
     option_key: OptionKey,
+    disabled: bool,
     _phantom: core::marker::PhantomData<T>,
 }
 
@@ -25,6 +27,19 @@ pub trait ButtonOptionParams {
     const MODE: ButtonMode;
 }
 
+/// Parameters of `ActionOption`.
+#[derive(Clone)]
+pub struct ActionParams;
+
+impl ButtonOptionParams for ActionParams {
+    const MODE: ButtonMode = ButtonMode::OneShot;
+}
+
+/// Momentary action, like 'save' or 'wipe'. An encoder press latches it
+/// until the next `poll()`, which returns true once and clears it. Drawn
+/// as a button, and never persisted.
+pub type ActionOption = ButtonOption<ActionParams>;
+
 impl<T: ButtonOptionParams> ButtonOption<T> {
     pub fn new(name: &'static str, init: bool, key: u32) -> Self {
         Self {
@@ -32,7 +47,9 @@ impl<T: ButtonOptionParams> ButtonOption<T> {
             value: init,
             value_delay1: init,
             init,
+            default: init,
             option_key: OptionKey::new(key),
+            disabled: false,
             _phantom: core::marker::PhantomData,
         }
     }
@@ -77,6 +94,14 @@ impl<T: ButtonOptionParams> OptionTrait for ButtonOption<T> {
         &mut self.option_key
     }
 
+    fn enabled(&self) -> bool {
+        !self.disabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.disabled = !enabled;
+    }
+
     fn tick_up(&mut self) {
         // Button options don't respond to encoder rotation
     }
@@ -96,6 +121,25 @@ impl<T: ButtonOptionParams> OptionTrait for ButtonOption<T> {
         }
     }
 
+    // Only toggles have a value to set, a one-shot would fire its action.
+    fn set_to_min(&mut self) {
+        if T::MODE == ButtonMode::Toggle {
+            self.value = false;
+        }
+    }
+
+    fn set_to_max(&mut self) {
+        if T::MODE == ButtonMode::Toggle {
+            self.value = true;
+        }
+    }
+
+    fn reset_to_default(&mut self) {
+        if T::MODE == ButtonMode::Toggle {
+            self.value = self.default;
+        }
+    }
+
     fn button_press(&mut self) -> bool {
         match T::MODE {
             ButtonMode::Toggle => {
@@ -150,3 +194,52 @@ macro_rules! button_params {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[derive(OptionPage, Clone)]
+    struct MiscOpts {
+        #[option]
+        save_opts: ActionOption,
+    }
+
+    #[test]
+    fn test_action_poll_once() {
+        let mut action = ActionOption::new("save", false, 0);
+        assert!(!action.poll());
+
+        // Fires exactly once per press, however often it is polled.
+        for _ in 0..3 {
+            assert!(action.button_press());
+            assert_eq!(action.value().as_str(), "<>");
+            assert!(action.poll());
+            assert!(!action.poll());
+            assert!(!action.poll());
+            assert_eq!(action.value().as_str(), "");
+        }
+
+        // Presses between 2 polls are a single activation.
+        action.button_press();
+        action.button_press();
+        assert!(action.poll());
+        assert!(!action.poll());
+
+        // Rotation does nothing, and nothing is persisted.
+        action.tick_up();
+        assert!(!action.poll());
+        action.button_press();
+        let mut buf = [0u8; 8];
+        assert_eq!(action.encode(&mut buf), None);
+    }
+
+    #[test]
+    fn test_action_derive() {
+        let mut opts = MiscOpts::default();
+        assert_eq!(opts.options()[0].name(), "save-opts");
+        opts.options_mut()[0].button_press();
+        assert!(opts.save_opts.poll());
+        assert!(!opts.save_opts.poll());
+    }
+}
@@ -10,7 +10,9 @@ pub struct EnumOption<T: Copy + IntoEnumIterator + Default> {
     pub name: &'static str,
     pub value: T,
     init: T,
+    default: T,
     option_key: OptionKey,
+    disabled: bool,
 }
 
 impl<T: Copy + IntoEnumIterator + Default> EnumOption<T> {
@@ -19,7 +21,9 @@ impl<T: Copy + IntoEnumIterator + Default> EnumOption<T> {
             name,
             value,
             init: value,
+            default: value,
             option_key: OptionKey::new(key),
+            disabled: false,
         }
     }
 }
@@ -51,6 +55,14 @@ where
         &mut self.option_key
     }
 
+    fn enabled(&self) -> bool {
+        !self.disabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.disabled = !enabled;
+    }
+
     fn tick_up(&mut self) {
         let mut it = T::iter();
         for v in it.by_ref() {
@@ -93,6 +105,22 @@ where
         T::iter().count()
     }
 
+    fn set_to_min(&mut self) {
+        if let Some(v) = T::iter().next() {
+            self.value = v;
+        }
+    }
+
+    fn set_to_max(&mut self) {
+        if let Some(v) = T::iter().last() {
+            self.value = v;
+        }
+    }
+
+    fn reset_to_default(&mut self) {
+        self.value = self.default;
+    }
+
     fn set_from_cc(&mut self, cc: u8) -> bool {
         let count = T::iter().count();
         let index = (cc as usize * count) / 128;
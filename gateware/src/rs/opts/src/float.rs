@@ -15,7 +15,9 @@ pub struct FloatOption<T: FloatOptionParams> {
     name: &'static str,
     pub value: T::Value,
     init: T::Value,
+    default: T::Value,
     option_key: OptionKey,
+    disabled: bool,
 }
 
 pub trait FloatOptionParams {
@@ -32,7 +34,9 @@ impl<T: FloatOptionParams> FloatOption<T> {
             name,
             value,
             init: value,
+            default: value,
             option_key: OptionKey::new(key),
+            disabled: false,
         }
     }
 }
@@ -78,6 +82,14 @@ where
         &mut self.option_key
     }
 
+    fn enabled(&self) -> bool {
+        !self.disabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.disabled = !enabled;
+    }
+
     fn tick_up(&mut self) {
         let new_value = self.value + T::STEP;
         if new_value <= T::MAX {
@@ -104,6 +116,18 @@ where
         f32::from(steps) as usize + 1
     }
 
+    fn set_to_min(&mut self) {
+        self.value = T::MIN;
+    }
+
+    fn set_to_max(&mut self) {
+        self.value = T::MAX;
+    }
+
+    fn reset_to_default(&mut self) {
+        self.value = self.default;
+    }
+
     fn encode(&self, buf: &mut [u8]) -> Option<usize> {
         if self.value != self.init {
             use postcard::to_slice;
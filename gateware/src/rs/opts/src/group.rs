@@ -0,0 +1,86 @@
+use heapless::String;
+use core::fmt::Write;
+
+use crate::traits::*;
+
+/// Collapsible header on an `OptionPage`. All option fields declared after
+/// it belong to the group, up to the next `GroupOption` field, so ungrouped
+/// options go first. Grouping does not move options to another page, so
+/// their persistence keys are unchanged.
+///
+/// Pressing the header expands or collapses the group. Collapsed children
+/// are not drawn and are skipped when moving the selection.
+#[derive(Clone)]
+pub struct GroupOption {
+    name: &'static str,
+    pub expanded: bool,
+    option_key: OptionKey,
+}
+
+impl GroupOption {
+    pub fn new(name: &'static str, expanded: bool, key: u32) -> Self {
+        Self {
+            name,
+            expanded,
+            option_key: OptionKey::new(key),
+        }
+    }
+}
+
+impl OptionTrait for GroupOption {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn value(&self) -> OptionString {
+        let mut s: OptionString = String::new();
+        write!(&mut s, "{}", if self.expanded { "[-]" } else { "[+]" }).ok();
+        s
+    }
+
+    fn key(&self) -> &OptionKey {
+        &self.option_key
+    }
+
+    fn key_mut(&mut self) -> &mut OptionKey {
+        &mut self.option_key
+    }
+
+    fn tick_up(&mut self) {
+        // Headers don't respond to encoder rotation
+    }
+
+    fn tick_down(&mut self) {
+        // Headers don't respond to encoder rotation
+    }
+
+    fn percent(&self) -> f32 {
+        if self.expanded { 1.0 } else { 0.0 }
+    }
+
+    fn n_unique_values(&self) -> usize {
+        2
+    }
+
+    fn button_press(&mut self) -> bool {
+        self.expanded = !self.expanded;
+        true
+    }
+
+    fn expanded(&self) -> Option<bool> {
+        Some(self.expanded)
+    }
+
+    fn set_expanded(&mut self, expanded: bool) {
+        self.expanded = expanded;
+    }
+
+    fn encode(&self, _buf: &mut [u8]) -> Option<usize> {
+        // Menu layout state only, always starts from the default.
+        None
+    }
+
+    fn decode(&mut self, _buf: &[u8]) -> bool {
+        false
+    }
+}
@@ -17,7 +17,9 @@ pub struct IntOption<T: IntOptionParams> {
     name: &'static str,
     pub value: T::Value,
     init: T::Value,
+    default: T::Value,
     option_key: OptionKey,
+    disabled: bool,
 }
 
 pub trait IntOptionParams {
@@ -34,7 +36,9 @@ impl<T: IntOptionParams> IntOption<T> {
             name,
             value,
             init: value,
+            default: value,
             option_key: OptionKey::new(key),
+            disabled: false,
         }
     }
 }
@@ -49,8 +53,10 @@ where
         + core::fmt::Display
         + Serialize
         + for<'de> Deserialize<'de>
-        + AsPrimitive<f32>,
+        + AsPrimitive<f32>
+        + AsPrimitive<i32>,
     f32: AsPrimitive<T::Value>,
+    i32: AsPrimitive<T::Value>,
 {
     fn name(&self) -> &'static str {
         self.name
@@ -63,7 +69,8 @@ where
                 write!(&mut s, "{}", self.value).ok();
             }
             IntFormat::Scaled { divisor, precision, suffix } => {
-                let scaled = self.value.as_() / divisor as f32;
+                let value: f32 = self.value.as_();
+                let scaled = value / divisor as f32;
                 write!(&mut s, "{:.*}{}", precision, scaled, suffix).ok();
             }
         }
@@ -78,6 +85,14 @@ where
         &mut self.option_key
     }
 
+    fn enabled(&self) -> bool {
+        !self.disabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.disabled = !enabled;
+    }
+
     fn tick_up(&mut self) {
         let new_value = self.value + T::STEP;
         // Tolerate unsigned overflow.
@@ -94,9 +109,11 @@ where
     }
 
     fn percent(&self) -> f32 {
-        let range = T::MAX - T::MIN;
-        let value = self.value - T::MIN;
-        value.as_() / range.as_()
+        // In f32, as e.g. MAX - MIN of an i16 spanning -16384..=16384 overflows.
+        let min: f32 = T::MIN.as_();
+        let max: f32 = T::MAX.as_();
+        let value: f32 = self.value.as_();
+        (value - min) / (max - min)
     }
 
     fn n_unique_values(&self) -> usize {
@@ -115,6 +132,35 @@ where
         true
     }
 
+    fn int_value(&self) -> Option<i32> {
+        Some(self.value.as_())
+    }
+
+    fn int_range(&self) -> Option<(i32, i32)> {
+        Some((T::MIN.as_(), T::MAX.as_()))
+    }
+
+    fn set_int_value(&mut self, value: i32) -> bool {
+        let min: i32 = T::MIN.as_();
+        let max: i32 = T::MAX.as_();
+        let step: i32 = T::STEP.as_();
+        let value = value.clamp(min, max);
+        self.value = (min + (value - min) / step.max(1) * step.max(1)).as_();
+        true
+    }
+
+    fn set_to_min(&mut self) {
+        self.value = T::MIN;
+    }
+
+    fn set_to_max(&mut self) {
+        self.value = T::MAX;
+    }
+
+    fn reset_to_default(&mut self) {
+        self.value = self.default;
+    }
+
     fn encode(&self, buf: &mut [u8]) -> Option<usize> {
         if self.value != self.init {
             use postcard::to_slice;
@@ -140,6 +186,125 @@ where
     }
 }
 
+/// Integer option that steps the raw value, but is displayed in
+/// engineering units (e.g. `-128.0mV`, `440.0Hz`).
+///
+/// Dereferences to the underlying `IntOption`, so `.value` is still
+/// the raw integer and `percent()` is still based on the raw range.
+#[derive(Clone)]
+pub struct ScaledIntOption<T: ScaledIntOptionParams> {
+    inner: IntOption<T>,
+}
+
+pub trait ScaledIntOptionParams: IntOptionParams {
+    const DISPLAY_UNIT: &'static str;
+    fn to_display(raw: Self::Value) -> f32;
+}
+
+impl<T: ScaledIntOptionParams> ScaledIntOption<T> {
+    pub fn new(name: &'static str, value: T::Value, key: u32) -> Self {
+        Self {
+            inner: IntOption::new(name, value, key),
+        }
+    }
+}
+
+impl<T: ScaledIntOptionParams> core::ops::Deref for ScaledIntOption<T> {
+    type Target = IntOption<T>;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T: ScaledIntOptionParams> core::ops::DerefMut for ScaledIntOption<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl<T: ScaledIntOptionParams> OptionTrait for ScaledIntOption<T>
+where
+    IntOption<T>: OptionTrait,
+{
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn value(&self) -> OptionString {
+        let mut s: OptionString = String::new();
+        write!(&mut s, "{:.1}{}", T::to_display(self.inner.value), T::DISPLAY_UNIT).ok();
+        s
+    }
+
+    fn key(&self) -> &OptionKey {
+        self.inner.key()
+    }
+
+    fn key_mut(&mut self) -> &mut OptionKey {
+        self.inner.key_mut()
+    }
+
+    fn enabled(&self) -> bool {
+        self.inner.enabled()
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.inner.set_enabled(enabled)
+    }
+
+    fn tick_up(&mut self) {
+        self.inner.tick_up()
+    }
+
+    fn tick_down(&mut self) {
+        self.inner.tick_down()
+    }
+
+    fn percent(&self) -> f32 {
+        self.inner.percent()
+    }
+
+    fn n_unique_values(&self) -> usize {
+        self.inner.n_unique_values()
+    }
+
+    fn set_from_cc(&mut self, cc: u8) -> bool {
+        self.inner.set_from_cc(cc)
+    }
+
+    fn int_value(&self) -> Option<i32> {
+        self.inner.int_value()
+    }
+
+    fn int_range(&self) -> Option<(i32, i32)> {
+        self.inner.int_range()
+    }
+
+    fn set_int_value(&mut self, value: i32) -> bool {
+        self.inner.set_int_value(value)
+    }
+
+    fn set_to_min(&mut self) {
+        self.inner.set_to_min()
+    }
+
+    fn set_to_max(&mut self) {
+        self.inner.set_to_max()
+    }
+
+    fn reset_to_default(&mut self) {
+        self.inner.reset_to_default()
+    }
+
+    fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        self.inner.encode(buf)
+    }
+
+    fn decode(&mut self, buf: &[u8]) -> bool {
+        self.inner.decode(buf)
+    }
+}
+
 #[macro_export]
 macro_rules! int_params {
     ($name:ident<$t:ty> { step: $step:expr, min: $min:expr, max: $max:expr }) => {
@@ -166,3 +331,17 @@ macro_rules! int_params {
         }
     };
 }
+
+#[macro_export]
+macro_rules! scaled_int_params {
+    ($name:ident<$t:ty> { step: $step:expr, min: $min:expr, max: $max:expr, unit: $unit:expr, to_display: $to_display:expr }) => {
+        $crate::int_params!($name<$t> { step: $step, min: $min, max: $max });
+
+        impl ScaledIntOptionParams for $name {
+            const DISPLAY_UNIT: &'static str = $unit;
+            fn to_display(raw: Self::Value) -> f32 {
+                ($to_display)(raw)
+            }
+        }
+    };
+}
@@ -10,6 +10,7 @@ mod enumeration;
 mod float;
 mod string;
 mod button;
+mod group;
 pub mod persistence;
 pub mod cc_map;
 
@@ -19,6 +20,7 @@ pub use crate::enumeration::*;
 pub use crate::float::*;
 pub use crate::string::*;
 pub use crate::button::*;
+pub use crate::group::*;
 
 #[derive(Clone, Default)]
 pub struct ScreenTracker<ScreenT: Copy + IntoEnumIterator + Default> {
@@ -1,12 +1,36 @@
-use sequential_storage::map::{fetch_item, store_item, remove_all_items};
+use sequential_storage::map::{fetch_all_items, fetch_item, store_item, remove_all_items};
 use sequential_storage::cache::NoCache;
 use embassy_futures::block_on;
 use embassy_embedded_hal::adapter::BlockingAsync;
 
-use crate::traits::Options;
+use crc::{Crc, CRC_32_BZIP2};
 
-const DATA_BUFFER_SZ: usize = 32;
+use crate::traits::{Options, MAX_N_OPTS};
+
+pub const DATA_BUFFER_SZ: usize = 32;
 const DEFAULT_PAGE_KEY: u32 = 0xdeadbeef;
+// Layouts saved before versioning was added have no version key, and are version 0.
+const SCHEMA_VERSION_KEY: u32 = 0xdeadbeee;
+
+// Options are saved as two copies (A/B) in separate key namespaces of the
+// same store. Every value of a copy carries a trailer with the copy's
+// generation and a CRC32, and the copy's commit record (also a trailered
+// value, holding the schema version) is written last. A copy is only loaded
+// if its commit record and every record it holds for the current options
+// are intact and from the same generation, so a save interrupted by power
+// loss falls back to the previous copy.
+//
+// Stores written before this have raw keys and no commit records. They are
+// still loaded, without any checks, until the next save.
+const COMMIT_KEYS: [u32; 2] = [0xdeadbeed, 0xdeadbeec];
+const COPY_KEY_XOR: [u32; 2] = [0x5555_5555, 0xaaaa_aaaa];
+const TRAILER_SZ: usize = 8;
+const RECORD_SZ: usize = DATA_BUFFER_SZ + TRAILER_SZ;
+
+const CRC_ALGORITHM: Crc<u32> = Crc::<u32>::new(&CRC_32_BZIP2);
+
+/// New key, and raw value to decode into the option with that key.
+pub type MigratedValue = (u32, heapless::Vec<u8, DATA_BUFFER_SZ>);
 
 #[derive(Debug)]
 pub enum PersistenceError {
@@ -15,6 +39,111 @@ pub enum PersistenceError {
     FlashRangeError,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Commit {
+    generation: u32,
+    schema_version: u16,
+}
+
+enum Record {
+    Missing,
+    /// Length of the value, which is at the start of the buffer.
+    Valid(usize),
+    /// Unreadable, bad CRC or from another generation.
+    Invalid,
+}
+
+fn record_crc(key: u32, value: &[u8], generation: u32) -> u32 {
+    let mut digest = CRC_ALGORITHM.digest();
+    digest.update(&key.to_le_bytes());
+    digest.update(value);
+    digest.update(&generation.to_le_bytes());
+    digest.finalize()
+}
+
+fn encode_record(key: u32, value: &[u8], generation: u32, record: &mut [u8; RECORD_SZ]) -> usize {
+    let len = value.len();
+    record[..len].copy_from_slice(value);
+    record[len..len+4].copy_from_slice(&generation.to_le_bytes());
+    record[len+4..len+8].copy_from_slice(&record_crc(key, value, generation).to_le_bytes());
+    len + TRAILER_SZ
+}
+
+/// Value and generation of a record stored under `key`, if its CRC matches.
+fn decode_record(key: u32, record: &[u8]) -> Option<(&[u8], u32)> {
+    let len = record.len().checked_sub(TRAILER_SZ)?;
+    let (value, trailer) = record.split_at(len);
+    let generation = u32::from_le_bytes(trailer[..4].try_into().ok()?);
+    let crc = u32::from_le_bytes(trailer[4..].try_into().ok()?);
+    (record_crc(key, value, generation) == crc).then_some((value, generation))
+}
+
+fn load_commit<P: OptionsPersistence + ?Sized>(p: &mut P, copy: usize) -> Option<Commit> {
+    let mut record = [0u8; RECORD_SZ];
+    let len = p.load_key(COMMIT_KEYS[copy], &mut record).ok()??;
+    let (value, generation) = decode_record(COMMIT_KEYS[copy], &record[..len])?;
+    Some(Commit { generation, schema_version: u16::from_le_bytes(value.try_into().ok()?) })
+}
+
+fn load_record<P: OptionsPersistence + ?Sized>(
+    p: &mut P, copy: usize, key: u32, generation: u32, record: &mut [u8; RECORD_SZ]) -> Record {
+    let stored_key = key ^ COPY_KEY_XOR[copy];
+    match p.load_key(stored_key, record) {
+        Ok(None) => Record::Missing,
+        Ok(Some(len)) => match decode_record(stored_key, &record[..len]) {
+            Some((value, g)) if g == generation => Record::Valid(value.len()),
+            _ => Record::Invalid,
+        },
+        Err(_) => Record::Invalid,
+    }
+}
+
+/// Newest copy that can be loaded into `opts`, see above.
+fn newest_valid_copy<P, O>(p: &mut P, opts: &O) -> Option<(usize, Commit)>
+where
+    P: OptionsPersistence + ?Sized,
+    O: Options,
+{
+    let mut copies: heapless::Vec<(usize, Commit), 2> = (0..2)
+        .filter_map(|copy| load_commit(p, copy).map(|commit| (copy, commit)))
+        .collect();
+    copies.sort_unstable_by_key(|(_, commit)| core::cmp::Reverse(commit.generation));
+    let mut record = [0u8; RECORD_SZ];
+    copies.into_iter().find(|(copy, commit)| {
+        let keys = opts.all().map(|opt| opt.key().value()).chain([DEFAULT_PAGE_KEY]);
+        let valid = keys.into_iter().all(|key| !matches!(
+            load_record(p, *copy, key, commit.generation, &mut record), Record::Invalid));
+        if !valid {
+            log::warn!("opts/load: copy {} (generation {}) is corrupt", copy, commit.generation);
+        }
+        valid
+    })
+}
+
+/// Options saved before A/B copies, under their raw keys.
+fn load_legacy_options<P, O>(p: &mut P, opts: &mut O) -> Result<bool, P::Error>
+where
+    P: OptionsPersistence + ?Sized,
+    O: Options,
+{
+    let mut loaded = false;
+    for opt in opts.all_mut() {
+        let mut buf: [u8; DATA_BUFFER_SZ] = [0u8; DATA_BUFFER_SZ];
+        if let Some(len) = p.load_key(opt.key().value(), &mut buf)? {
+            opt.decode(&buf[..len]);
+            loaded = true;
+            log::info!("opts/load: {}={} ({:x}={:?})", 
+                      opt.name(), opt.value(), opt.key().value(), &buf[..len]);
+        }
+    }
+    let mut buf: [u8; DATA_BUFFER_SZ] = [0u8; DATA_BUFFER_SZ];
+    if let Some(len) = p.load_key(DEFAULT_PAGE_KEY, &mut buf)? {
+        opts.page_mut().decode(&buf[..len]);
+        loaded = true;
+    }
+    Ok(loaded)
+}
+
 pub trait OptionsPersistence {
     type Error;
 
@@ -23,14 +152,233 @@ pub trait OptionsPersistence {
     fn load_key(&mut self, key: u32, buffer: &mut [u8]) -> Result<Option<usize>, Self::Error>;
 
     fn erase_all(&mut self) -> Result<(), Self::Error>;
-    fn load_options<O: Options>(&mut self, opts: &mut O) -> Result<(), Self::Error>;
-    fn save_options<O: Options>(&mut self, opts: &O) -> Result<(), Self::Error>;
+
+    /// Call `f` for every stored (key, value). The same key may be visited more
+    /// than once, in which case the last visit is the most recent value.
+    fn for_each_item<F: FnMut(u32, &[u8])>(&mut self, f: F) -> Result<(), Self::Error>;
+
+    /// Version of the option layout, written alongside the options by `save_options`.
+    fn schema_version(&self) -> u16;
+
+    /// Version of the most recently saved options.
+    fn load_schema_version(&mut self) -> Result<u16, Self::Error> {
+        let newest = (0..2).filter_map(|copy| load_commit(self, copy))
+                           .max_by_key(|commit| commit.generation);
+        if let Some(commit) = newest {
+            return Ok(commit.schema_version);
+        }
+        let mut buf = [0u8; 2];
+        Ok(match self.load_key(SCHEMA_VERSION_KEY, &mut buf)? {
+            Some(2) => u16::from_le_bytes(buf),
+            _ => 0,
+        })
+    }
+
+    /// Load the newest intact copy of the options. Returns `false`, leaving
+    /// `opts` untouched, if there is none and the defaults should be used.
+    fn load_options<O: Options>(&mut self, opts: &mut O) -> Result<bool, Self::Error> {
+        let Some((copy, commit)) = newest_valid_copy(self, opts) else {
+            if (0..2).any(|copy| load_commit(self, copy).is_some()) {
+                log::warn!("opts/load: no intact copy of the options, using defaults");
+                return Ok(false);
+            }
+            return load_legacy_options(self, opts);
+        };
+        let mut record = [0u8; RECORD_SZ];
+        for opt in opts.all_mut() {
+            if let Record::Valid(len) = load_record(self, copy, opt.key().value(),
+                                                    commit.generation, &mut record) {
+                opt.decode(&record[..len]);
+                log::info!("opts/load: {}={} ({:x}={:?})",
+                          opt.name(), opt.value(), opt.key().value(), &record[..len]);
+            }
+        }
+        if let Record::Valid(len) = load_record(self, copy, DEFAULT_PAGE_KEY,
+                                                commit.generation, &mut record) {
+            opts.page_mut().decode(&record[..len]);
+        }
+        Ok(true)
+    }
+
+    /// Like `load_options`, but if the stored layout is older than `schema_version()`,
+    /// every stored key that no option claims is passed to `migrate` (along with
+    /// the stored version and raw value) instead of being ignored. `migrate` may
+    /// return the key of an option in the new layout and a raw value for it, which
+    /// is used unless that option already has a value stored under its own key.
+    fn load_options_migrating<O, M>(&mut self, opts: &mut O, migrate: M) -> Result<bool, Self::Error>
+    where
+        O: Options,
+        M: Fn(u16, u32, &[u8]) -> Option<MigratedValue>,
+    {
+        let copy = newest_valid_copy(self, opts);
+        let stored_version = match copy {
+            Some((_, commit)) => commit.schema_version,
+            None => self.load_schema_version()?,
+        };
+        // Options with a value under their own key are never overwritten by a migration.
+        let mut loaded: heapless::Vec<u32, MAX_N_OPTS> = heapless::Vec::new();
+        let mut record = [0u8; RECORD_SZ];
+        for opt in opts.all() {
+            let key = opt.key().value();
+            let stored = match copy {
+                Some((copy, commit)) => matches!(
+                    load_record(self, copy, key, commit.generation, &mut record), Record::Valid(_)),
+                None => self.load_key(key, &mut record)?.is_some(),
+            };
+            if stored {
+                loaded.push(key).ok();
+            }
+        }
+        if !self.load_options(opts)? {
+            return Ok(false);
+        }
+        if stored_version >= self.schema_version() {
+            return Ok(true);
+        }
+        log::info!("opts/load: migrating from schema version {} to {}",
+                   stored_version, self.schema_version());
+        self.for_each_item(|key, raw| {
+            if COMMIT_KEYS.contains(&key) {
+                return;
+            }
+            // Skip anything not in the copy that was loaded.
+            let (key, raw) = match copy {
+                Some((copy, commit)) => match decode_record(key, raw) {
+                    Some((value, g)) if g == commit.generation => (key ^ COPY_KEY_XOR[copy], value),
+                    _ => return,
+                },
+                None => (key, raw),
+            };
+            if key == DEFAULT_PAGE_KEY || key == SCHEMA_VERSION_KEY ||
+               opts.all().any(|opt| opt.key().value() == key) {
+                return;
+            }
+            let Some((new_key, new_raw)) = migrate(stored_version, key, raw) else {
+                return;
+            };
+            if loaded.contains(&new_key) {
+                return;
+            }
+            if let Some(opt) = opts.all_mut().find(|opt| opt.key().value() == new_key) {
+                if opt.decode(&new_raw) {
+                    log::info!("opts/migrate: {}={} ({:x} -> {:x})",
+                              opt.name(), opt.value(), key, new_key);
+                }
+            }
+        })?;
+        Ok(true)
+    }
+
+    /// Save to the copy not holding the newest intact options, commit record last.
+    fn save_options<O: Options>(&mut self, opts: &O) -> Result<(), Self::Error> {
+        let copy = newest_valid_copy(self, opts).map_or(0, |(copy, _)| 1 - copy);
+        // Newer than both commit records, even one of a corrupt copy.
+        let generation = (0..2).filter_map(|c| load_commit(self, c))
+                               .map(|commit| commit.generation.wrapping_add(1))
+                               .max()
+                               .unwrap_or(0);
+        log::info!("opts/save: copy {} (generation {})", copy, generation);
+        let mut record = [0u8; RECORD_SZ];
+        for opt in opts.all() {
+            let mut buf: [u8; DATA_BUFFER_SZ] = [0u8; DATA_BUFFER_SZ];
+            if let Some(encoded_len) = opt.encode(&mut buf) {
+                log::info!("opts/save: {}={} ({:x}={:?})", 
+                          opt.name(), opt.value(), opt.key().value(), &buf[..encoded_len]);
+                let key = opt.key().value() ^ COPY_KEY_XOR[copy];
+                let len = encode_record(key, &buf[..encoded_len], generation, &mut record);
+                self.save_key_retries(key, &record[..len], 2)?;
+            }
+        }
+        let mut buf: [u8; DATA_BUFFER_SZ] = [0u8; DATA_BUFFER_SZ];
+        if let Some(encoded_len) = opts.page().encode(&mut buf) {
+            let key = DEFAULT_PAGE_KEY ^ COPY_KEY_XOR[copy];
+            let len = encode_record(key, &buf[..encoded_len], generation, &mut record);
+            self.save_key(key, &record[..len])?;
+        }
+        let version = self.schema_version().to_le_bytes();
+        let len = encode_record(COMMIT_KEYS[copy], &version, generation, &mut record);
+        self.save_key_retries(COMMIT_KEYS[copy], &record[..len], 2)
+    }
+}
+
+// Blobs from `serialize_all` start with this, then `BLOB_VERSION`.
+const BLOB_MAGIC: [u8; 2] = *b"TQ";
+// Bump whenever the blob layout (not the option layout) changes.
+const BLOB_VERSION: u8 = 1;
+const BLOB_HEADER_SZ: usize = 3;
+const BLOB_CRC_SZ: usize = 4;
+
+fn blob_push<const N: usize>(blob: &mut heapless::Vec<u8, N>, bytes: &[u8]) -> Result<(), PersistenceError> {
+    blob.extend_from_slice(bytes).map_err(|_| PersistenceError::SerializationError)
+}
+
+/// Every option value (and the current page) as one blob, independent of
+/// any flash store, e.g. to share a preset over serial. The blob is a
+/// header, one (key, length, value) entry per option, in the same
+/// encoding and under the same keys as `save_options` uses, and a CRC32.
+/// Fails if the blob doesn't fit in `N` bytes.
+pub fn serialize_all<O: Options, const N: usize>(opts: &O) -> Result<heapless::Vec<u8, N>, PersistenceError> {
+    let mut blob = heapless::Vec::new();
+    blob_push(&mut blob, &BLOB_MAGIC)?;
+    blob_push(&mut blob, &[BLOB_VERSION])?;
+    let page = opts.page();
+    let entries = opts.all().map(|opt| (opt.key().value(), opt)).chain([(DEFAULT_PAGE_KEY, page)]);
+    for (key, opt) in entries {
+        let mut buf: [u8; DATA_BUFFER_SZ] = [0u8; DATA_BUFFER_SZ];
+        if let Some(len) = opt.encode(&mut buf) {
+            blob_push(&mut blob, &key.to_le_bytes())?;
+            blob_push(&mut blob, &[len as u8])?;
+            blob_push(&mut blob, &buf[..len])?;
+        }
+    }
+    let crc = CRC_ALGORITHM.checksum(&blob);
+    blob_push(&mut blob, &crc.to_le_bytes())?;
+    Ok(blob)
+}
+
+/// Load a blob from `serialize_all` into `opts`, returning how many
+/// values were applied. Keys that no option claims (e.g. from another
+/// firmware version) are skipped. A blob with a bad header or CRC is
+/// rejected as a whole, leaving `opts` untouched.
+pub fn deserialize_all<O: Options>(opts: &mut O, blob: &[u8]) -> Result<usize, PersistenceError> {
+    let body_len = blob.len().checked_sub(BLOB_CRC_SZ)
+        .filter(|len| *len >= BLOB_HEADER_SZ)
+        .ok_or(PersistenceError::SerializationError)?;
+    let (body, crc) = blob.split_at(body_len);
+    if body[..2] != BLOB_MAGIC || body[2] != BLOB_VERSION ||
+       CRC_ALGORITHM.checksum(body).to_le_bytes() != crc {
+        return Err(PersistenceError::SerializationError);
+    }
+    // Check every entry is well formed before touching `opts`.
+    let mut entries = &body[BLOB_HEADER_SZ..];
+    while !entries.is_empty() {
+        let len = *entries.get(4).ok_or(PersistenceError::SerializationError)? as usize;
+        entries = entries.get(5 + len..).ok_or(PersistenceError::SerializationError)?;
+    }
+    let mut applied = 0;
+    let mut entries = &body[BLOB_HEADER_SZ..];
+    while !entries.is_empty() {
+        let key = u32::from_le_bytes([entries[0], entries[1], entries[2], entries[3]]);
+        let len = entries[4] as usize;
+        let value = &entries[5..5 + len];
+        entries = &entries[5 + len..];
+        let decoded = if key == DEFAULT_PAGE_KEY {
+            opts.page_mut().decode(value)
+        } else {
+            opts.all_mut().find(|opt| opt.key().value() == key).is_some_and(|opt| opt.decode(value))
+        };
+        if decoded {
+            applied += 1;
+        }
+    }
+    Ok(applied)
 }
 
 pub struct FlashOptionsPersistence<F> {
     flash: BlockingAsync<F>,
     flash_range: core::ops::Range<u32>,
-    data_buffer: [u8; DATA_BUFFER_SZ],
+    data_buffer: [u8; 2*RECORD_SZ],
+    schema_version: u16,
 }
 
 impl<F> FlashOptionsPersistence<F> {
@@ -38,9 +386,17 @@ impl<F> FlashOptionsPersistence<F> {
         Self {
             flash: BlockingAsync::new(flash),
             flash_range,
-            data_buffer: [0u8; DATA_BUFFER_SZ],
+            data_buffer: [0u8; 2*RECORD_SZ],
+            schema_version: 0,
         }
     }
+
+    /// Bump this whenever option keys change in a way `load_options_migrating`
+    /// should know about (renamed fields, changed types).
+    pub fn with_schema_version(mut self, schema_version: u16) -> Self {
+        self.schema_version = schema_version;
+        self
+    }
 }
 
 impl<F> OptionsPersistence for FlashOptionsPersistence<F>
@@ -99,36 +455,351 @@ where
         )).map_err(|_| PersistenceError::StorageError)
     }
 
-    fn save_options<O: Options>(&mut self, opts: &O) -> Result<(), Self::Error> {
-        for opt in opts.all() {
-            let mut buf: [u8; DATA_BUFFER_SZ] = [0u8; DATA_BUFFER_SZ];
-            if let Some(encoded_len) = opt.encode(&mut buf) {
-                log::info!("opts/save: {}={} ({:x}={:?})", 
-                          opt.name(), opt.value(), opt.key().value(), &buf[..encoded_len]);
-                self.save_key_retries(opt.key().value(), &buf[..encoded_len], 2)?;
+    fn for_each_item<G: FnMut(u32, &[u8])>(&mut self, mut f: G) -> Result<(), Self::Error> {
+        let mut cache = NoCache::new();
+        let mut buf = [0u8; 2*RECORD_SZ];
+        let mut items = block_on(fetch_all_items::<u32, _, _>(
+            &mut self.flash,
+            self.flash_range.clone(),
+            &mut cache,
+            &mut self.data_buffer,
+        )).map_err(|_| PersistenceError::StorageError)?;
+        while let Some((key, value)) = block_on(items.next::<&[u8]>(&mut buf))
+                .map_err(|_| PersistenceError::StorageError)? {
+            f(key, value);
+        }
+        Ok(())
+    }
+
+    fn schema_version(&self) -> u16 {
+        self.schema_version
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    /// Append-only key/value store, like the flash map but in RAM.
+    struct RamPersistence {
+        items: std::vec::Vec<(u32, std::vec::Vec<u8>)>,
+        schema_version: u16,
+    }
+
+    impl OptionsPersistence for RamPersistence {
+        type Error = ();
+
+        fn save_key(&mut self, key: u32, value: &[u8]) -> Result<(), Self::Error> {
+            self.items.push((key, value.to_vec()));
+            Ok(())
+        }
+
+        fn save_key_retries(&mut self, key: u32, value: &[u8], _retries: usize) -> Result<(), Self::Error> {
+            self.save_key(key, value)
+        }
+
+        fn load_key(&mut self, key: u32, buffer: &mut [u8]) -> Result<Option<usize>, Self::Error> {
+            Ok(self.items.iter().rev().find(|(k, _)| *k == key).map(|(_, v)| {
+                buffer[..v.len()].copy_from_slice(v);
+                v.len()
+            }))
+        }
+
+        fn erase_all(&mut self) -> Result<(), Self::Error> {
+            self.items.clear();
+            Ok(())
+        }
+
+        fn for_each_item<F: FnMut(u32, &[u8])>(&mut self, mut f: F) -> Result<(), Self::Error> {
+            for (key, value) in self.items.iter() {
+                f(*key, value);
             }
+            Ok(())
         }
-        let mut buf: [u8; DATA_BUFFER_SZ] = [0u8; DATA_BUFFER_SZ];
-        if let Some(encoded_len) = opts.page().encode(&mut buf) {
-            self.save_key(DEFAULT_PAGE_KEY, &buf[..encoded_len])?;
+
+        fn schema_version(&self) -> u16 {
+            self.schema_version
         }
-        Ok(())
     }
 
-    fn load_options<O: Options>(&mut self, opts: &mut O) -> Result<(), Self::Error> {
-        for opt in opts.all_mut() {
-            let mut buf: [u8; DATA_BUFFER_SZ] = [0u8; DATA_BUFFER_SZ];
-            if let Some(len) = self.load_key(opt.key().value(), &mut buf)? {
-                opt.decode(&buf[..len]);
-                log::info!("opts/load: {}={} ({:x}={:?})", 
-                          opt.name(), opt.value(), opt.key().value(), &buf[..len]);
+    // Same page/field names in both layouts, so unchanged fields keep their keys.
+    mod v1 {
+        use crate::*;
+        use serde_derive::{Serialize, Deserialize};
+        use strum_macros::{EnumIter, IntoStaticStr};
+
+        #[derive(Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Default, Serialize, Deserialize)]
+        pub enum Page {
+            #[default]
+            Main,
+        }
+
+        int_params!(GainParams<u8>  { step: 1, min: 0, max: 15 });
+        int_params!(LevelParams<u8> { step: 1, min: 0, max: 100 });
+
+        #[derive(OptionPage, Clone)]
+        pub struct MainOpts {
+            #[option(2)]
+            pub gain: IntOption<GainParams>,
+            #[option(50)]
+            pub level: IntOption<LevelParams>,
+        }
+
+        #[derive(Options, Clone)]
+        pub struct Opts {
+            pub tracker: ScreenTracker<Page>,
+            #[page(Page::Main)]
+            pub main: MainOpts,
+        }
+    }
+
+    mod v2 {
+        use crate::*;
+        use serde_derive::{Serialize, Deserialize};
+        use strum_macros::{EnumIter, IntoStaticStr};
+
+        #[derive(Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Default, Serialize, Deserialize)]
+        pub enum Page {
+            #[default]
+            Main,
+        }
+
+        int_params!(GainParams<u8>  { step: 1, min: 0, max: 15 });
+        // 'level' (1% steps) is now 'volume' (0.1% steps), so it has a new key.
+        int_params!(VolumeParams<u16> { step: 10, min: 0, max: 1000 });
+
+        #[derive(OptionPage, Clone)]
+        pub struct MainOpts {
+            #[option(2)]
+            pub gain: IntOption<GainParams>,
+            #[option(500)]
+            pub volume: IntOption<VolumeParams>,
+        }
+
+        #[derive(Options, Clone)]
+        pub struct Opts {
+            pub tracker: ScreenTracker<Page>,
+            #[page(Page::Main)]
+            pub main: MainOpts,
+        }
+    }
+
+    #[test]
+    fn test_load_options_migrating() {
+        let mut opts_v1 = v1::Opts::default();
+        opts_v1.main.gain.value = 7;
+        opts_v1.main.level.value = 30;
+        let mut store = RamPersistence { items: std::vec::Vec::new(), schema_version: 1 };
+        store.save_options(&opts_v1).unwrap();
+
+        let old_level_key = opts_v1.main.level.key().value();
+        let new_volume_key = v2::Opts::default().main.volume.key().value();
+        assert_ne!(old_level_key, new_volume_key);
+        let migrate = |version: u16, key: u32, raw: &[u8]| -> Option<MigratedValue> {
+            if version == 1 && key == old_level_key {
+                let mut volume = v2::Opts::default().main.volume;
+                volume.value = postcard::from_bytes::<u8>(raw).ok()? as u16 * 10;
+                let mut buf = [0u8; DATA_BUFFER_SZ];
+                let len = volume.encode(&mut buf)?;
+                Some((new_volume_key, heapless::Vec::from_slice(&buf[..len]).unwrap()))
+            } else {
+                None
+            }
+        };
+
+        // Without a migration, the old level is dropped.
+        store.schema_version = 2;
+        let mut opts_v2 = v2::Opts::default();
+        store.load_options(&mut opts_v2).unwrap();
+        assert_eq!(opts_v2.main.gain.value, 7);
+        assert_eq!(opts_v2.main.volume.value, 500);
+
+        let mut opts_v2 = v2::Opts::default();
+        store.load_options_migrating(&mut opts_v2, migrate).unwrap();
+        assert_eq!(opts_v2.main.gain.value, 7);
+        assert_eq!(opts_v2.main.volume.value, 300);
+
+        // Once saved with the new layout, the stale v1 key is never used again.
+        opts_v2.main.volume.value = 420;
+        store.save_options(&opts_v2).unwrap();
+        assert_eq!(store.load_schema_version().unwrap(), 2);
+        let mut opts_v2 = v2::Opts::default();
+        store.load_options_migrating(&mut opts_v2, migrate).unwrap();
+        assert_eq!(opts_v2.main.volume.value, 420);
+    }
+
+    fn opts_with(gain: u8, level: u8) -> v1::Opts {
+        let mut opts = v1::Opts::default();
+        opts.main.gain.value = gain;
+        opts.main.level.value = level;
+        opts
+    }
+
+    fn load(store: &mut RamPersistence) -> (bool, u8, u8) {
+        let mut opts = v1::Opts::default();
+        let loaded = store.load_options(&mut opts).unwrap();
+        (loaded, opts.main.gain.value, opts.main.level.value)
+    }
+
+    #[test]
+    fn test_torn_save_loads_backup() {
+        let mut store = RamPersistence { items: std::vec::Vec::new(), schema_version: 0 };
+        assert_eq!(load(&mut store), (false, 2, 50));
+
+        store.save_options(&opts_with(3, 30)).unwrap();
+        store.save_options(&opts_with(4, 40)).unwrap();
+        assert_eq!(load(&mut store), (true, 4, 40));
+
+        // Power lost partway through a save: one option written, but not
+        // the rest or the commit record.
+        let n_items = store.items.len();
+        store.save_options(&opts_with(5, 60)).unwrap();
+        store.items.truncate(n_items + 1);
+        assert_eq!(load(&mut store), (true, 4, 40));
+
+        // The next save doesn't overwrite the intact copy.
+        store.save_options(&opts_with(6, 70)).unwrap();
+        assert_eq!(load(&mut store), (true, 6, 70));
+    }
+
+    #[test]
+    fn test_corrupt_copy_loads_backup() {
+        let mut store = RamPersistence { items: std::vec::Vec::new(), schema_version: 0 };
+        store.save_options(&opts_with(3, 30)).unwrap();
+        store.save_options(&opts_with(4, 40)).unwrap();
+
+        // Flip a bit in the newest value of 'level'.
+        let level_key = v1::Opts::default().main.level.key().value();
+        let (_, value) = store.items.iter_mut().rev()
+            .find(|(k, _)| COPY_KEY_XOR.iter().any(|x| k ^ x == level_key))
+            .unwrap();
+        value[0] ^= 0x01;
+        assert_eq!(load(&mut store), (true, 3, 30));
+
+        // Corrupt both commit records: nothing is loaded.
+        for (key, value) in store.items.iter_mut() {
+            if COMMIT_KEYS.contains(key) {
+                value[0] ^= 0x01;
             }
         }
-        let mut buf: [u8; DATA_BUFFER_SZ] = [0u8; DATA_BUFFER_SZ];
-        if let Some(len) = self.load_key(DEFAULT_PAGE_KEY, &mut buf)? {
-            opts.page_mut().decode(&buf[..len]);
+        assert_eq!(load(&mut store), (false, 2, 50));
+    }
+
+    #[test]
+    fn test_load_legacy_options() {
+        // Raw values under the option keys, as saved before A/B copies.
+        let opts = opts_with(9, 90);
+        let mut store = RamPersistence { items: std::vec::Vec::new(), schema_version: 0 };
+        for opt in opts.all() {
+            let mut buf = [0u8; DATA_BUFFER_SZ];
+            if let Some(len) = opt.encode(&mut buf) {
+                store.save_key(opt.key().value(), &buf[..len]).unwrap();
+            }
         }
-        Ok(())
+        assert_eq!(load(&mut store), (true, 9, 90));
+        store.save_options(&opts_with(1, 10)).unwrap();
+        assert_eq!(load(&mut store), (true, 1, 10));
     }
-}
 
+    mod full {
+        use crate::*;
+        use serde_derive::{Serialize, Deserialize};
+        use strum_macros::{EnumIter, IntoStaticStr};
+
+        #[derive(Clone, Copy, PartialEq, Debug, EnumIter, IntoStaticStr, Default, Serialize, Deserialize)]
+        pub enum Page {
+            #[default]
+            Main,
+            Extra,
+        }
+
+        #[derive(Clone, Copy, PartialEq, Debug, EnumIter, IntoStaticStr, Default, Serialize, Deserialize)]
+        pub enum Shape {
+            #[default]
+            Sine,
+            Square,
+            Saw,
+        }
+
+        int_params!(GainParams<u8>   { step: 1, min: 0, max: 15 });
+        int_params!(OffsetParams<i16> { step: 10, min: -1000, max: 1000 });
+        float_params!(MixParams<f32> { step: 0.05, min: 0.0, max: 1.0, format: FloatFormat::Percent(0) });
+        button_params!(ToggleParams { mode: ButtonMode::Toggle });
+
+        #[derive(OptionPage, Clone)]
+        pub struct MainOpts {
+            #[option(2)]
+            pub gain: IntOption<GainParams>,
+            #[option(0)]
+            pub offset: IntOption<OffsetParams>,
+            #[option]
+            pub shape: EnumOption<Shape>,
+        }
+
+        #[derive(OptionPage, Clone)]
+        pub struct ExtraOpts {
+            #[option(0.5)]
+            pub mix: FloatOption<MixParams>,
+            #[option(false)]
+            pub hold: ButtonOption<ToggleParams>,
+            #[option(false)]
+            pub reset: ActionOption,
+        }
+
+        #[derive(Options, Clone)]
+        pub struct Opts {
+            pub tracker: ScreenTracker<Page>,
+            #[page(Page::Main)]
+            pub main: MainOpts,
+            #[page(Page::Extra)]
+            pub extra: ExtraOpts,
+        }
+    }
+
+    #[test]
+    fn test_serialize_all() {
+        let mut opts = full::Opts::default();
+        opts.tracker.page.value = full::Page::Extra;
+        opts.main.gain.value = 11;
+        opts.main.offset.value = -730;
+        opts.main.shape.value = full::Shape::Saw;
+        opts.extra.mix.value = 0.85;
+        opts.extra.hold.value = true;
+        let blob = serialize_all::<_, 128>(&opts).unwrap();
+
+        let mut loaded = full::Opts::default();
+        // Every option but the action, and the page.
+        assert_eq!(deserialize_all(&mut loaded, &blob).unwrap(), 6);
+        assert_eq!(loaded.tracker.page.value, full::Page::Extra);
+        assert_eq!(loaded.main.gain.value, 11);
+        assert_eq!(loaded.main.offset.value, -730);
+        assert_eq!(loaded.main.shape.value, full::Shape::Saw);
+        assert_eq!(loaded.extra.mix.value, 0.85);
+        assert!(loaded.extra.hold.value);
+        for (a, b) in opts.all().zip(loaded.all()) {
+            assert_eq!(a.value(), b.value(), "{}", a.name());
+        }
+
+        // Too small a buffer is an error, not a truncated blob.
+        assert!(serialize_all::<_, 16>(&opts).is_err());
+
+        // Corrupt or truncated blobs are rejected without touching anything.
+        let mut fresh = full::Opts::default();
+        for n in 0..blob.len() {
+            let mut bad = blob.clone();
+            bad[n] ^= 0x01;
+            assert!(deserialize_all(&mut fresh, &bad).is_err());
+            assert!(deserialize_all(&mut fresh, &blob[..n]).is_err());
+        }
+        assert_eq!(fresh.main.gain.value, 2);
+        assert_eq!(fresh.tracker.page.value, full::Page::Main);
+
+        // Options unknown to this layout are skipped.
+        let mut other = v1::Opts::default();
+        assert_eq!(deserialize_all(&mut other, &blob).unwrap(), 1);
+        assert_eq!(other.main.gain.value, 11);
+        assert_eq!(other.main.level.value, 50);
+    }
+}
@@ -9,6 +9,7 @@ pub struct StringOption {
     pub name: &'static str,
     pub value: OptionString,
     option_key: OptionKey,
+    disabled: bool,
 }
 
 impl StringOption {
@@ -17,6 +18,7 @@ impl StringOption {
             name,
             value: OptionString::from_str(value).unwrap(),
             option_key: OptionKey::new(key),
+            disabled: false,
         }
     }
 }
@@ -38,6 +40,14 @@ impl OptionTrait for StringOption {
         &mut self.option_key
     }
 
+    fn enabled(&self) -> bool {
+        !self.disabled
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.disabled = !enabled;
+    }
+
     fn tick_up(&mut self) {
         // do nothing (for now)
     }
@@ -43,14 +43,57 @@ pub trait OptionTrait {
 
     fn set_from_cc(&mut self, _value: u8) -> bool { false }
 
+    /// Raw value and inclusive range of integer options, for editing them
+    /// directly rather than by ticks. `None` for everything else.
+    fn int_value(&self) -> Option<i32> { None }
+    fn int_range(&self) -> Option<(i32, i32)> { None }
+    /// Set the raw value of an integer option, clamped to its range and
+    /// rounded down to its step. Returns false if this is not one.
+    fn set_int_value(&mut self, _value: i32) -> bool { false }
+
+    /// Jump to either end of the range.
+    fn set_to_min(&mut self) {}
+    fn set_to_max(&mut self) {}
+    /// Go back to the value given at construction (the compiled default),
+    /// rather than the last one loaded from persistent storage.
+    fn reset_to_default(&mut self) {}
+
+    /// Disabled options are still drawn (greyed out), but are skipped when
+    /// moving the selection and can't be modified from the menu. Firmware
+    /// updates this when other options make this one irrelevant.
+    fn enabled(&self) -> bool { true }
+    fn set_enabled(&mut self, _enabled: bool) {}
+
     /// Handle button press (toggle_modify). Returns true if handled, false otherwise.
     fn button_press(&mut self) -> bool { false }
+
+    /// Only group headers (`GroupOption`) are expandable, `None` for anything else.
+    fn expanded(&self) -> Option<bool> { None }
+    fn set_expanded(&mut self, _expanded: bool) {}
 }
 
 pub trait OptionPage {
     fn options(&self) -> OptionVec<'_>;
     fn options_mut(&mut self) -> OptionVecMut<'_>;
     fn set_parent_key(&mut self, parent_key: u32);
+
+    /// Index of the group header option `n` belongs to, if any.
+    fn parent(&self, _n: usize) -> Option<usize> { None }
+
+    /// Option `n` is drawn and selectable, i.e. not in a collapsed group.
+    fn visible(&self, n: usize) -> bool {
+        match self.parent(n) {
+            Some(p) => self.options()[p].expanded().unwrap_or(true),
+            None => true,
+        }
+    }
+
+    /// Expand the group option `n` belongs to, if any.
+    fn reveal(&mut self, n: usize) {
+        if let Some(p) = self.parent(n) {
+            self.options_mut()[p].set_expanded(true);
+        }
+    }
 }
 
 pub trait Options {
@@ -88,11 +131,28 @@ pub trait Options {
     }
 }
 
+/// First enabled (and visible) option at or after `from`.
+fn next_enabled(page: &dyn OptionPage, from: usize) -> Option<usize> {
+    let options = page.options();
+    (from..options.len()).find(|&n| options[n].enabled() && page.visible(n))
+}
+
+/// Last enabled (and visible) option before `before`.
+fn prev_enabled(page: &dyn OptionPage, before: usize) -> Option<usize> {
+    let options = page.options();
+    (0..before).rev().find(|&n| options[n].enabled() && page.visible(n))
+}
+
 pub trait OptionsEncoderInterface {
     fn toggle_modify(&mut self);
     fn tick_up(&mut self);
     fn tick_down(&mut self);
     fn consume_ticks(&mut self, ticks: i8);
+    /// Reset the selected option to its default, if it is enabled.
+    fn reset_selected(&mut self);
+    /// If an option inside a group is selected, collapse the group and
+    /// select its header instead. Returns false if not in a group.
+    fn leave_group(&mut self) -> bool;
 }
 
 impl<T> OptionsEncoderInterface for T
@@ -100,6 +160,11 @@ where
     T: Options,
 {
     fn toggle_modify(&mut self) {
+        if let Some(n_selected) = self.selected() {
+            if !self.modify() && !self.view().options()[n_selected].enabled() {
+                return;
+            }
+        }
         let handled = if let Some(n_selected) = self.selected() {
             self.view_mut().options_mut()[n_selected].button_press()
         } else {
@@ -114,23 +179,29 @@ where
     fn tick_up(&mut self) {
         if let Some(n_selected) = self.selected() {
             if self.modify() {
-                self.view_mut().options_mut()[n_selected].tick_up();
-            } else if n_selected < self.view().options().len()-1 {
-                self.set_selected(Some(n_selected + 1));
+                let mut options = self.view_mut().options_mut();
+                if options[n_selected].enabled() {
+                    options[n_selected].tick_up();
+                }
+            } else if let Some(next) = next_enabled(self.view(), n_selected + 1) {
+                self.set_selected(Some(next));
             }
         } else if self.modify() {
             self.page_mut().tick_up();
-        } else if !self.view().options().is_empty() {
-            self.set_selected(Some(0));
+        } else if let Some(first) = next_enabled(self.view(), 0) {
+            self.set_selected(Some(first));
         }
     }
 
     fn tick_down(&mut self) {
         if let Some(n_selected) = self.selected() {
             if self.modify() {
-                self.view_mut().options_mut()[n_selected].tick_down();
-            } else if n_selected != 0 {
-                self.set_selected(Some(n_selected - 1));
+                let mut options = self.view_mut().options_mut();
+                if options[n_selected].enabled() {
+                    options[n_selected].tick_down();
+                }
+            } else if let Some(prev) = prev_enabled(self.view(), n_selected) {
+                self.set_selected(Some(prev));
             } else {
                 if self.page().n_unique_values() > 1 {
                     self.set_selected(None);
@@ -153,5 +224,239 @@ where
             }
         }
     }
+
+    fn reset_selected(&mut self) {
+        if let Some(n_selected) = self.selected() {
+            let mut options = self.view_mut().options_mut();
+            if options[n_selected].enabled() {
+                options[n_selected].reset_to_default();
+            }
+        }
+    }
+
+    fn leave_group(&mut self) -> bool {
+        let Some(header) = self.selected().and_then(|n| self.view().parent(n)) else {
+            return false;
+        };
+        self.modify_mut(false);
+        self.view_mut().options_mut()[header].set_expanded(false);
+        self.set_selected(Some(header));
+        true
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use serde_derive::{Serialize, Deserialize};
+    use strum_macros::{EnumIter, IntoStaticStr};
+
+    #[derive(Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Default, Serialize, Deserialize)]
+    enum Page {
+        #[default]
+        Main,
+        Other,
+    }
+
+    int_params!(LevelParams<u8> { step: 1, min: 0, max: 100 });
+    float_params!(GainParams<f32> { step: 0.1, min: 0.0, max: 2.0, format: FloatFormat::Precision(1) });
+
+    #[derive(Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Default, Serialize, Deserialize)]
+    enum Shape {
+        Sine,
+        #[default]
+        Tri,
+        Saw,
+    }
+
+    #[derive(OptionPage, Clone)]
+    struct MainOpts {
+        #[option(0)]
+        a: IntOption<LevelParams>,
+        #[option(0)]
+        b: IntOption<LevelParams>,
+        #[option(0)]
+        c: IntOption<LevelParams>,
+        #[option(0)]
+        d: IntOption<LevelParams>,
+        #[option(0)]
+        e: IntOption<LevelParams>,
+    }
+
+    #[derive(OptionPage, Clone)]
+    struct OtherOpts {
+        #[option(0)]
+        x: IntOption<LevelParams>,
+        #[option]
+        group: GroupOption,
+        #[option(0)]
+        y: IntOption<LevelParams>,
+        #[option(0)]
+        z: IntOption<LevelParams>,
+    }
+
+    #[derive(Options, Clone)]
+    struct Opts {
+        tracker: ScreenTracker<Page>,
+        #[page(Page::Main)]
+        main: MainOpts,
+        #[page(Page::Other)]
+        other: OtherOpts,
+    }
+
+    #[test]
+    fn test_skip_disabled() {
+        let mut opts = Opts::default();
+        opts.main.a.set_enabled(false);
+        opts.main.c.set_enabled(false);
+        opts.main.d.set_enabled(false);
+
+        // Selection from the page skips to the first enabled option.
+        assert_eq!(opts.selected(), None);
+        opts.tick_up();
+        assert_eq!(opts.selected(), Some(1));
+        // And over the disabled ones in between.
+        opts.tick_up();
+        assert_eq!(opts.selected(), Some(4));
+        // Stays on the last enabled option.
+        opts.tick_up();
+        assert_eq!(opts.selected(), Some(4));
+        opts.tick_down();
+        assert_eq!(opts.selected(), Some(1));
+        // Nothing enabled above, so back to the page.
+        opts.tick_down();
+        assert_eq!(opts.selected(), None);
+
+        // Options disabled while selected can't be modified.
+        opts.set_selected(Some(2));
+        opts.toggle_modify();
+        assert!(!opts.modify());
+        opts.tick_up();
+        assert_eq!(opts.selected(), Some(4));
+        assert_eq!(opts.main.c.value, 0);
+
+        // Enabled options still are.
+        opts.toggle_modify();
+        assert!(opts.modify());
+        opts.tick_up();
+        assert_eq!(opts.main.e.value, 1);
+        // Disabling mid-edit stops changes, but modify can still be left.
+        opts.main.e.set_enabled(false);
+        opts.tick_up();
+        assert_eq!(opts.main.e.value, 1);
+        opts.toggle_modify();
+        assert!(!opts.modify());
+
+        // Page with everything enabled behaves as before.
+        opts.set_selected(None);
+        opts.toggle_modify();
+        opts.tick_up();
+        opts.toggle_modify();
+        assert!(opts.tracker.page.value == Page::Other);
+        opts.tick_up();
+        assert_eq!(opts.selected(), Some(0));
+    }
+
+    #[test]
+    fn test_groups() {
+        let mut opts = Opts::default();
+        opts.tracker.page.value = Page::Other;
+        assert_eq!(opts.other.parent(0), None);
+        assert_eq!(opts.other.parent(1), None);
+        assert_eq!(opts.other.parent(2), Some(1));
+        assert_eq!(opts.other.parent(3), Some(1));
+
+        // Collapsed by default, so selection stops at the header.
+        opts.tick_up();
+        opts.tick_up();
+        assert_eq!(opts.selected(), Some(1));
+        opts.tick_up();
+        assert_eq!(opts.selected(), Some(1));
+
+        // Pressing the header expands it rather than entering modify.
+        opts.toggle_modify();
+        assert!(!opts.modify());
+        assert!(opts.other.group.expanded);
+        opts.tick_up();
+        opts.tick_up();
+        assert_eq!(opts.selected(), Some(3));
+        opts.toggle_modify();
+        opts.tick_up();
+        assert_eq!(opts.other.z.value, 1);
+
+        // Leaving the group collapses it and goes back to the header.
+        assert!(opts.leave_group());
+        assert!(!opts.modify());
+        assert!(!opts.other.group.expanded);
+        assert_eq!(opts.selected(), Some(1));
+        assert!(!opts.leave_group());
+        opts.tick_down();
+        assert_eq!(opts.selected(), Some(0));
+
+        // Selecting a grouped option from elsewhere (e.g. MIDI CC) reveals it.
+        let n_main = opts.main.options().len();
+        assert!(opts.select_global(n_main + 2));
+        assert_eq!(opts.selected(), Some(2));
+        assert!(opts.other.group.expanded);
+
+        // Headers are not persisted, the options in the group are.
+        let mut buf = [0u8; 8];
+        assert_eq!(opts.other.group.encode(&mut buf), None);
+        assert!(opts.other.z.encode(&mut buf).is_some());
+    }
+
+    #[test]
+    fn test_reset_to_default() {
+        let mut level = IntOption::<LevelParams>::new("level", 37, 0);
+        level.set_to_max();
+        assert_eq!(level.value, 100);
+        level.set_to_min();
+        assert_eq!(level.value, 0);
+        level.reset_to_default();
+        assert_eq!(level.value, 37);
+
+        let mut shape = EnumOption::new("shape", Shape::Tri, 0);
+        shape.set_to_max();
+        assert!(shape.value == Shape::Saw);
+        shape.set_to_min();
+        assert!(shape.value == Shape::Sine);
+        shape.reset_to_default();
+        assert!(shape.value == Shape::Tri);
+
+        // Exactly, not by ticking back.
+        let mut gain = FloatOption::<GainParams>::new("gain", 0.7, 0);
+        for _ in 0..3 {
+            gain.tick_up();
+        }
+        gain.set_to_max();
+        assert_eq!(gain.value, 2.0);
+        gain.set_to_min();
+        assert_eq!(gain.value, 0.0);
+        gain.reset_to_default();
+        assert_eq!(gain.value, 0.7);
+
+        // The default is the compiled one, not the last loaded from storage.
+        let mut buf = [0u8; 8];
+        level.value = 80;
+        let n = level.encode(&mut buf).unwrap();
+        let mut loaded = IntOption::<LevelParams>::new("level", 37, 0);
+        assert!(loaded.decode(&buf[..n]));
+        assert_eq!(loaded.value, 80);
+        loaded.reset_to_default();
+        assert_eq!(loaded.value, 37);
+
+        // From the menu, only the selected option is reset, if enabled.
+        let mut opts = Opts::default();
+        opts.main.b.value = 50;
+        opts.main.c.value = 60;
+        opts.set_selected(Some(1));
+        opts.reset_selected();
+        assert_eq!(opts.main.b.value, 0);
+        assert_eq!(opts.main.c.value, 60);
+        opts.main.c.set_enabled(false);
+        opts.set_selected(Some(2));
+        opts.reset_selected();
+        assert_eq!(opts.main.c.value, 60);
+    }
+}
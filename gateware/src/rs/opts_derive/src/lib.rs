@@ -35,6 +35,8 @@ pub fn derive_option(input: TokenStream) -> TokenStream {
 
         let constructor = if is_int_option(field_type) {
             quote! { IntOption::new }
+        } else if is_scaled_int_option(field_type) {
+            quote! { ScaledIntOption::new }
         } else if is_enum_option(field_type) {
             quote! { EnumOption::new }
         } else if is_float_option(field_type) {
@@ -43,6 +45,10 @@ pub fn derive_option(input: TokenStream) -> TokenStream {
             quote! { StringOption::new }
         } else if is_button_option(field_type) {
             quote! { ButtonOption::new }
+        } else if is_action_option(field_type) {
+            quote! { ActionOption::new }
+        } else if is_group_option(field_type) {
+            quote! { GroupOption::new }
         } else {
             panic!("Unsupported field type for OptionPage")
         };
@@ -68,6 +74,23 @@ pub fn derive_option(input: TokenStream) -> TokenStream {
         .map(|field| field.ident.as_ref().unwrap())
         .collect();
 
+    // Each option belongs to the closest GroupOption declared before it.
+    let mut header: Option<usize> = None;
+    let parents: Vec<_> = fields.iter()
+        .filter(|field| is_option_type(&field.ty))
+        .enumerate()
+        .map(|(n, field)| {
+            if is_group_option(&field.ty) {
+                header = Some(n);
+                quote! { None }
+            } else if let Some(h) = header {
+                quote! { Some(#h) }
+            } else {
+                quote! { None }
+            }
+        })
+        .collect();
+
     let expanded = quote! {
         impl Default for #name {
             fn default() -> Self {
@@ -93,6 +116,11 @@ pub fn derive_option(input: TokenStream) -> TokenStream {
             fn set_parent_key(&mut self, parent_key: u32) {
                 #(self.#option_fields.key_mut().hash_with(parent_key);)*
             }
+
+            fn parent(&self, n: usize) -> Option<usize> {
+                const PARENTS: &[Option<usize>] = &[#(#parents),*];
+                PARENTS.get(n).copied().flatten()
+            }
         }
     };
 
@@ -106,6 +134,12 @@ fn is_int_option(ty: &Type) -> bool {
         .unwrap_or(false))
 }
 
+fn is_scaled_int_option(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.segments.first()
+        .map(|seg| seg.ident == "ScaledIntOption")
+        .unwrap_or(false))
+}
+
 fn is_enum_option(ty: &Type) -> bool {
     matches!(ty, Type::Path(path) if path.path.segments.first()
         .map(|seg| seg.ident == "EnumOption")
@@ -130,8 +164,20 @@ fn is_button_option(ty: &Type) -> bool {
         .unwrap_or(false))
 }
 
+fn is_action_option(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.segments.first()
+        .map(|seg| seg.ident == "ActionOption")
+        .unwrap_or(false))
+}
+
+fn is_group_option(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.segments.first()
+        .map(|seg| seg.ident == "GroupOption")
+        .unwrap_or(false))
+}
+
 fn is_option_type(ty: &Type) -> bool {
-    is_int_option(ty) || is_enum_option(ty) || is_float_option(ty) || is_string_option(ty) || is_button_option(ty)
+    is_int_option(ty) || is_scaled_int_option(ty) || is_enum_option(ty) || is_float_option(ty) || is_string_option(ty) || is_button_option(ty) || is_action_option(ty) || is_group_option(ty)
 }
 
 #[proc_macro_derive(Options, attributes(page))]
@@ -268,6 +314,7 @@ pub fn page_derive(input: TokenStream) -> TokenStream {
                         if global_index < offset + len {
                             self.tracker.page.value = #page_values;
                             self.tracker.selected = Some(global_index - offset);
+                            self.#page_field_names.reveal(global_index - offset);
                             return true;
                         }
                         offset += len;
@@ -15,11 +15,12 @@ import os
 import tarfile
 import json
 import tempfile
+import time
 from pathlib import Path
 
 from dataclasses import dataclass, field
 from fastcrc import crc32
-from typing import Optional, List
+from typing import Optional, List, Tuple
 from .types import *
 from ..platform import TiliquaRevision
 
@@ -37,6 +38,7 @@ class ArchiveBuilder:
     _regions: List[MemoryRegion] = field(default_factory=list)
     _manifest: Optional[BitstreamManifest] = None
     _firmware_bin_path: Optional[str] = None
+    _extra_files: List[Tuple[str, str]] = field(default_factory=list)
 
     def __post_init__(self):
         # Ensure build directory exists
@@ -128,6 +130,36 @@ class ArchiveBuilder:
 
         return self
 
+    def with_ram_region(self, file_path: str, psram_dst: int, filename: Optional[str] = None) -> 'ArchiveBuilder':
+        """
+        Add a data file that the bootloader copies to PSRAM before the bitstream
+        starts, and return self for chaining.
+
+        Args:
+            file_path: Path to the (raw binary) file to include
+            psram_dst: PSRAM offset the bootloader should copy it to
+            filename: Name of the region (and file) in the archive, so firmware
+                      can find it in the manifest. Defaults to the file name.
+        """
+        if not os.path.exists(file_path):
+            print(f"WARNING: RamLoad file not found at {file_path}")
+            return self
+
+        if filename is None:
+            filename = os.path.basename(file_path)
+
+        region = MemoryRegion(
+            filename=filename,
+            region_type=RegionType.RamLoad,
+            spiflash_src=None,  # Will be set by flash.py based on slot
+            psram_dst=psram_dst,
+            size=os.path.getsize(file_path),
+            crc=crc32.bzip2(open(file_path, "rb").read())
+        )
+        self._regions.append(region)
+        self._extra_files.append((file_path, filename))
+        return self
+
     def with_option_storage(self, filename: str = "<options>", size: int = 2*FLASH_PAGE_SZ) -> 'ArchiveBuilder':
         """Add option storage region and return self for chaining."""
         region = MemoryRegion(
@@ -154,6 +186,19 @@ class ArchiveBuilder:
         self._regions.append(manifest_region)
         return self
 
+    def requires(self) -> List[Feature]:
+        """Bootloader features needed to start this bitstream."""
+        requires = []
+        if self.external_pll_config is not None:
+            requires.append(Feature.ExternalPll)
+            if self.external_pll_config.clk1_inherit:
+                requires.append(Feature.DynamicModeline)
+        return requires
+
+    def built_unix(self) -> int:
+        """Build time for the manifest, honoring SOURCE_DATE_EPOCH for reproducible builds."""
+        return int(os.environ.get("SOURCE_DATE_EPOCH", time.time()))
+
     def write_manifest(self) -> BitstreamManifest:
         """Write serialized manifest file, return the BitstreamManifest object."""
         # Ensure manifest region is added if not already present
@@ -167,7 +212,9 @@ class ArchiveBuilder:
             tag=self.tag,
             regions=self._regions,
             help=self.bitstream_help,
-            external_pll_config=self.external_pll_config
+            external_pll_config=self.external_pll_config,
+            requires=self.requires(),
+            built_unix=self.built_unix(),
         )
         self._manifest.write_to_path(self.manifest_path)
         return self._manifest
@@ -198,6 +245,8 @@ class ArchiveBuilder:
             tar.add(self.manifest_path, arcname="manifest.json")
             if self._firmware_bin_path and os.path.exists(self._firmware_bin_path):
                 tar.add(self._firmware_bin_path, arcname="firmware.bin")
+            for file_path, arcname in self._extra_files:
+                tar.add(file_path, arcname=arcname)
 
         self._print_archive_info()
         print(f"\nSaved to '{self.build_path}/{self.archive_name}'")
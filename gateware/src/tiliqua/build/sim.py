@@ -137,6 +137,7 @@ def simulate(fragment, ports, harness, hw_platform, clock_settings, tracing=Fals
             "--h-active", str(h_active),
             "--v-active", str(v_active),
             "--fixed-pclk-hz", str(dvi_clk_hz),
+            "--audio-hz", str(clock_settings.frequencies.audio),
             "--output", bootinfo_path
         ], env=os.environ)
         print(f"Generated bootinfo: {bootinfo_path}")
@@ -184,7 +184,9 @@ class MultiADSR(wiring.Component):
     """
     N ADSR envelope generators with:
     - Independent gate/velocity per voice (output is velocity * adsr product)
-    - Global attack/decay/sustain/release control
+    - Global attack/decay/sustain/release control. These are latched per voice
+      on each rising gate, so changes apply from the next note, rather than
+      jumping the level of voices that are already sounding.
     - Outgoing bitfield of which voices are still traversing through ADSR states
       (e.g. gate released, but voice is still releasing - used for culling)
 
@@ -223,10 +225,18 @@ class MultiADSR(wiring.Component):
 
         # Per-voice ADSR state memory
 
+        adsr_params_layout = data.StructLayout({
+            "attack_rate":   EnvUQ,
+            "decay_rate":    EnvUQ,
+            "sustain_level": EnvUQ,
+            "release_rate":  EnvUQ,
+        })
+
         adsr_state_layout = data.StructLayout({
             "l_gate":     unsigned(1),
             "adsr_phase": self.Phase,
             "adsr_level": EnvUQ,
+            "params":     adsr_params_layout,
         })
         m.submodules.state_mem = state_mem = Memory(
             shape=adsr_state_layout, depth=N, init=[])
@@ -239,6 +249,7 @@ class MultiADSR(wiring.Component):
         l_gate      = Signal(1)
         adsr_phase  = Signal(self.Phase)
         adsr_level  = Signal(EnvUQ)
+        params      = Signal(adsr_params_layout)
         cur_gate    = Signal(1)
         cur_vel_mod = Signal(EnvUQ)
         voice_ix    = Signal(range(N))
@@ -254,6 +265,7 @@ class MultiADSR(wiring.Component):
                     l_gate.eq(st_rport.data.l_gate),
                     adsr_phase.eq(st_rport.data.adsr_phase),
                     adsr_level.eq(st_rport.data.adsr_level),
+                    params.eq(st_rport.data.params),
                 ]
                 with m.Switch(voice_ix):
                     for n in range(N):
@@ -274,10 +286,20 @@ class MultiADSR(wiring.Component):
                     gate_falling.eq(~cur_gate & l_gate),
                 ]
 
+                # Parameters of this note, or the global ones on a new note.
+                p = Signal(adsr_params_layout)
+                m.d.comb += p.eq(params)
+
                 active_phase = Signal(self.Phase)
                 m.d.comb += active_phase.eq(adsr_phase)
                 with m.If(gate_rising):
-                    m.d.comb += active_phase.eq(self.Phase.ATTACK)
+                    m.d.comb += [
+                        active_phase.eq(self.Phase.ATTACK),
+                        p.attack_rate.eq(self.attack_rate),
+                        p.decay_rate.eq(self.decay_rate),
+                        p.sustain_level.eq(self.sustain_level),
+                        p.release_rate.eq(self.release_rate),
+                    ]
                 with m.Elif(gate_falling):
                     m.d.comb += active_phase.eq(self.Phase.RELEASE)
 
@@ -292,7 +314,7 @@ class MultiADSR(wiring.Component):
                     with m.Case(self.Phase.IDLE):
                         m.d.comb += next_level.eq(0)
                     with m.Case(self.Phase.ATTACK):
-                        attack_sum = adsr_level + self.attack_rate
+                        attack_sum = adsr_level + p.attack_rate
                         with m.If(attack_sum >= EnvUQ.max()):
                             m.d.comb += [
                                 next_level.eq(EnvUQ.max()),
@@ -301,28 +323,29 @@ class MultiADSR(wiring.Component):
                         with m.Else():
                             m.d.comb += next_level.eq(attack_sum)
                     with m.Case(self.Phase.DECAY):
-                        with m.If(adsr_level <= self.sustain_level + self.decay_rate):
+                        with m.If(adsr_level <= p.sustain_level + p.decay_rate):
                             m.d.comb += [
-                                next_level.eq(self.sustain_level),
+                                next_level.eq(p.sustain_level),
                                 out_phase.eq(self.Phase.SUSTAIN),
                             ]
                         with m.Else():
-                            m.d.comb += next_level.eq(adsr_level - self.decay_rate)
+                            m.d.comb += next_level.eq(adsr_level - p.decay_rate)
                     with m.Case(self.Phase.SUSTAIN):
-                        m.d.comb += next_level.eq(self.sustain_level)
+                        m.d.comb += next_level.eq(p.sustain_level)
                     with m.Case(self.Phase.RELEASE):
-                        with m.If(adsr_level <= self.release_rate):
+                        with m.If(adsr_level <= p.release_rate):
                             m.d.comb += [
                                 next_level.eq(0),
                                 out_phase.eq(self.Phase.IDLE),
                             ]
                         with m.Else():
-                            m.d.comb += next_level.eq(adsr_level - self.release_rate)
+                            m.d.comb += next_level.eq(adsr_level - p.release_rate)
 
                 m.d.sync += [
                     adsr_level.eq(next_level),
                     adsr_phase.eq(out_phase),
                     l_gate.eq(cur_gate),
+                    params.eq(p),
                 ]
                 m.next = 'STORE'
 
@@ -332,6 +355,7 @@ class MultiADSR(wiring.Component):
                     st_wport.data.l_gate.eq(l_gate),
                     st_wport.data.adsr_phase.eq(adsr_phase),
                     st_wport.data.adsr_level.eq(adsr_level),
+                    st_wport.data.params.eq(params),
                     st_wport.en.eq(1),
                 ]
                 with m.Switch(voice_ix):
@@ -511,7 +535,8 @@ class VoiceMixer(wiring.Component):
 
     """
     Downmix N voices into a stereo output stream.
-    - Even-indexed voices sum to left, odd-indexed to right
+    - Each voice is panned by ``voice_pans`` (linear pan law). -0x8000 is
+      hard left, 0x7FFF hard right and 0 center (each side at half gain).
     - Gain-scaled by 2/N to prevent clipping
     """
 
@@ -521,6 +546,7 @@ class VoiceMixer(wiring.Component):
         super().__init__({
             "i": In(stream.Signature(Block(sq))),
             "o": Out(stream.Signature(data.ArrayLayout(sq, 2))),
+            "voice_pans": In(data.ArrayLayout(signed(16), n)),
         })
 
     def elaborate(self, platform):
@@ -534,6 +560,14 @@ class VoiceMixer(wiring.Component):
         acc_r = Signal(sq)
         voice_ix = Signal(range(N))
 
+        # Pan position -> right channel gain in [0, 1). Flipping the sign bit
+        # maps -0x8000..0x7FFF onto 0..0xFFFF. Left gets the remainder.
+        gain_r = Signal(fixed.UQ(0, 16))
+        m.d.comb += gain_r.as_value().eq(self.voice_pans[voice_ix] ^ 0x8000)
+
+        scaled = Signal(sq)
+        scaled_r = Signal(sq)
+
         with m.FSM():
 
             with m.State('CLEAR'):
@@ -547,13 +581,14 @@ class VoiceMixer(wiring.Component):
             with m.State('WAIT-VALID'):
                 m.d.comb += self.i.ready.eq(1)
                 with m.If(self.i.valid):
-                    scaled = self.i.payload.sample * mix_gain
-
-                    # Even voices -> left, odd voices -> right
-                    with m.If(voice_ix[0] == 0):
-                        m.d.sync += acc_l.eq(acc_l + scaled)
-                    with m.Else():
-                        m.d.sync += acc_r.eq(acc_r + scaled)
+                    m.d.comb += [
+                        scaled.eq(self.i.payload.sample * mix_gain),
+                        scaled_r.eq(scaled * gain_r),
+                    ]
+                    m.d.sync += [
+                        acc_l.eq(acc_l + scaled - scaled_r),
+                        acc_r.eq(acc_r + scaled_r),
+                    ]
 
                     with m.If(voice_ix == N - 1):
                         m.next = 'EMIT-OUTPUT'
@@ -603,6 +638,9 @@ class VoiceBlock(wiring.Component):
             # Phase modulation input (applied to all oscillators)
             "phase_mod":      In(sq),
 
+            # Per-voice stereo position, see `VoiceMixer`
+            "voice_pans":     In(data.ArrayLayout(signed(16), self.n_voices)),
+
             # Per-voice ADSR idle status
             "voice_active":   Out(data.ArrayLayout(unsigned(1), self.n_voices)),
 
@@ -628,6 +666,7 @@ class VoiceBlock(wiring.Component):
                 osc.voice_freq_incs[n].eq(self.voice_freq_incs[n]),
                 adsr.voice_gates[n].eq(self.voice_gates[n]),
                 adsr.voice_velocity[n].eq(self.voice_velocity[n]),
+                mixer.voice_pans[n].eq(self.voice_pans[n]),
                 self.voice_active[n].eq(adsr.voice_active[n]),
             ]
         m.d.comb += [
@@ -30,7 +30,7 @@ from typing import Optional
 
 from ..build.types import N_MANIFESTS
 from .archive_loader import ArchiveLoader
-from .spiflash_layout import compute_concrete_regions_to_flash
+from .spiflash_layout import compute_concrete_regions_to_flash, slot_image
 from .spiflash_status import flash_status
 from .openfpgaloader import *
 
@@ -44,7 +44,7 @@ def flash_archive(args, detected_hw_rev: int):
 
         # Validate hardware compatibility
 
-        if manifest.hw_rev != detected_hw_rev:
+        if detected_hw_rev is not None and manifest.hw_rev != detected_hw_rev:
             print(f"Aborting: attached Tiliqua (hw=r{detected_hw_rev}) does not match archive (hw=r{manifest.hw_rev}).")
             sys.exit(1)
 
@@ -58,6 +58,9 @@ def flash_archive(args, detected_hw_rev: int):
         elif not is_bootloader and slot is None:
             print("Error: Please specify target `--slot` for user bitstreams")
             sys.exit(1)
+        elif is_bootloader and args.image:
+            print("Error: recovery mode can't write the bootloader, `--image` is only for user bitstreams")
+            sys.exit(1)
 
         # Assign real SPI flash addresses to memory regions that must exist
         # in the SPI flash (but could not have their addresses calculated until now,
@@ -80,6 +83,13 @@ def flash_archive(args, detected_hw_rev: int):
         for region in sorted(regions_to_flash):
             print(f"  {region}")
 
+        if args.image:
+            # Not flashed here, sent later using the bootloader's recovery mode.
+            with open(args.image, "wb") as f:
+                f.write(slot_image(regions_to_flash, slot, loader.tmpdir))
+            print(f"\nWrote slot {slot} image to {args.image}")
+            return
+
         # Generate and execute flashing commands (with optional confirmation)
 
         sequence = OpenFPGALoaderCommandSequence.from_flashable_regions(
@@ -115,17 +125,22 @@ def main():
     archive_parser.add_argument("--noconfirm", action="store_true", help="Do not ask for confirmation before flashing")
     archive_parser.add_argument("--erase-option-storage", action="store_true", help="Erase option storage regions in the manifest")
     archive_parser.add_argument("--dump-manifest", action="store_true", help="Dump the final JSON manifest before flashing it.")
+    archive_parser.add_argument("--image", help="Instead of flashing, write a whole-slot image to this path (for the bootloader's serial recovery mode).")
 
     # Status command
     subparsers.add_parser('status', help='Display current bitstream status')
 
     args = parser.parse_args()
 
-    hw_rev_major = scan_for_tiliqua_hardware_version()
-    if not isinstance(hw_rev_major, int):
-        print("Could not find Tiliqua debugger.")
-        print("Check it is turned on, plugged in ('dbg' port), permissions correct, and RP2040 firmware is up to date.")
-        sys.exit(1)
+    if args.command == 'archive' and args.image:
+        # Nothing is flashed, so no debugger is needed.
+        hw_rev_major = None
+    else:
+        hw_rev_major = scan_for_tiliqua_hardware_version()
+        if not isinstance(hw_rev_major, int):
+            print("Could not find Tiliqua debugger.")
+            print("Check it is turned on, plugged in ('dbg' port), permissions correct, and RP2040 firmware is up to date.")
+            sys.exit(1)
 
     match args.command:
         case 'archive':
@@ -7,6 +7,7 @@ SPI flash based on the ``--slot`` the user wants to flash to.
 """
 
 import copy
+import os
 from colorama import Fore, Style
 
 from ..build.types import *
@@ -176,3 +177,26 @@ def compute_concrete_regions_to_flash(
                              f"and {sorted_regions[i+1].name} (starts at 0x{next_start:x})")
 
     return (manifest, regions_to_flash)
+
+
+def slot_image(regions_to_flash: List[FlashableRegion], slot: int, path) -> bytes:
+
+    """
+    Lay out ``regions_to_flash`` (from ``compute_concrete_regions_to_flash``) as
+    a single image of the whole user ``slot``, reading region files from ``path``.
+
+    This is what the bootloader's recovery mode expects to receive over serial.
+    Anything not covered by a region, including option storage, is left erased.
+    """
+
+    layout = SlotLayout(slot)
+    assert not layout.is_bootloader, "slot images are only for user slots"
+    image = bytearray(b'\xff' * SLOT_SIZE)
+    for region in regions_to_flash:
+        if region.memory_region.region_type == RegionType.OptionStorage:
+            continue
+        with open(os.path.join(path, region.memory_region.filename), "rb") as f:
+            data = f.read()
+        offset = region.addr - layout.slot_start_addr
+        image[offset:offset+len(data)] = data
+    return bytes(image)
@@ -41,17 +41,30 @@ class MidiVoiceTracker(wiring.Component):
     Sustain pedal (CC64) is supported: while held, NOTE_OFF events mark voices as
     sustained instead of clearing their gates. When the pedal is released, all
     sustained voices have their gates cleared.
+
+    If every voice is occupied, a :py:`NOTE_ON` is dropped. With :py:`steal` set,
+    it instead takes over the voice slot nominated by :py:`steal_ix`, so the
+    choice of which voice to steal is left to whoever drives it (e.g. the SoC).
+    The stolen voice keeps its gate high, so its envelope is not retriggered.
+    The :py:`sustained` output flags voices held only by the sustain pedal.
     """
 
-    def __init__(self, max_voices=8, velocity_mod=False, zero_velocity_gate=False):
+    def __init__(self, max_voices=8, velocity_mod=False, zero_velocity_gate=False, steal=False):
         self.max_voices = max_voices
         self.velocity_mod = velocity_mod
         self.zero_velocity_gate = zero_velocity_gate
-        super().__init__({
+        self.steal = steal
+        ports = {
             "i": In(stream.Signature(MidiMessage)),
             "voice_active": In(data.ArrayLayout(unsigned(1), max_voices)),
             "o": Out(MidiVoice).array(max_voices),
-        });
+        }
+        if steal:
+            ports |= {
+                "steal_ix": In(range(max_voices)),
+                "sustained": Out(data.ArrayLayout(unsigned(1), max_voices)),
+            }
+        super().__init__(ports)
 
     def elaborate(self, platform):
         m = Module()
@@ -85,6 +98,9 @@ class MidiVoiceTracker(wiring.Component):
         sustain_held = Signal()
         sustain_mask = Signal(self.max_voices)
 
+        if self.steal:
+            m.d.comb += self.sustained.as_value().eq(sustain_mask)
+
         # freq / mod / pb update index
         ix_update = Signal(range(self.max_voices))
 
@@ -159,8 +175,13 @@ class MidiVoiceTracker(wiring.Component):
                 with m.Else():
                     m.d.sync += voice_ix_write.eq(voice_ix_write + 1)
                     with m.If(voice_ix_write == self.max_voices - 1):
-                        # no free note slots
-                        m.next = 'WAIT-VALID'
+                        if self.steal:
+                            # no free note slots, take the nominated one
+                            m.d.sync += voice_ix_write.eq(self.steal_ix)
+                            m.next = 'NOTE-ON-COMMIT'
+                        else:
+                            # no free note slots
+                            m.next = 'WAIT-VALID'
 
             with m.State('NOTE-ON-COMMIT'):
                 # commit the new note to the found slot
@@ -276,6 +276,9 @@ class I2SCalibrator(wiring.Component):
     # Low latency ADC sample peeking (sync domain, can be used by softcore at same time as streams)
     o_cal_peek: Out(data.ArrayLayout(ASQ, 4))
 
+    # Gain applied to DAC samples before calibration (sync domain). 1<<15 is unity.
+    dac_gain: In(unsigned(16), init=1<<15)
+
     # Write port for calibration memory (sync domain)
     # Set values and assert `valid` until `ready` is strobed by this core, which
     # indicates the calibration memory write has been committed.
@@ -337,6 +340,11 @@ class I2SCalibrator(wiring.Component):
         # Low latency ADC sample peeking in sync domain
         m.submodules += FFSynchronizer(adc_samples, self.o_cal_peek, o_domain="sync", init=[0, 0, 0, 0])
 
+        # Gain changes are slow ramps from the softcore, so a multi-bit
+        # synchronizer is fine here (an occasional torn value is inaudible).
+        dac_gain_audio = Signal(unsigned(16), init=1<<15)
+        m.submodules += FFSynchronizer(self.dac_gain, dac_gain_audio, o_domain="audio", init=1<<15)
+
         # into / out of the scale/cal process
         in_sample = Signal(ASQ)
         out_sample = Signal(ASQ)
@@ -374,9 +382,11 @@ class I2SCalibrator(wiring.Component):
                 # Fetch DAC readings one channel back
                 channel_dac = Signal.like(self.channel)
                 m.d.comb += channel_dac.eq(self.channel+1)
+                dac_gained = Signal(signed(ASQ.width + 17))
+                m.d.comb += dac_gained.eq(dac_samples[channel_dac].as_value() * dac_gain_audio)
                 m.d.audio += [
                     cal_read.addr.eq(self.channel + I2STDM.N_CHANNELS),
-                    in_sample.eq(dac_samples[channel_dac])
+                    in_sample.as_value().eq(dac_gained >> 15)
                 ]
                 m.next = "PROCESS_DAC"
             with m.State("PROCESS_DAC"):
@@ -826,6 +836,7 @@ class EurorackPmod(wiring.Component):
     jack: Out(8)
     touch_err: Out(8) # Roughly proportional to touch IC NACKs (0 is good)
     codec_mute: In(1) # Hold at 1 to soft mute CODEC
+    dac_gain: In(unsigned(16), init=1<<15) # Output gain, 1<<15 is unity (for click-free muting)
     hard_reset: In(1) # Strobe a 1 to hard reset the CODEC (pops!)
 
     # Indicates audio MCLK is changing, we should be held in reset
@@ -866,6 +877,7 @@ class EurorackPmod(wiring.Component):
         # User core <-> calibrator
         wiring.connect(m, calibrator.o_cal, wiring.flipped(self.o_cal))
         wiring.connect(m, wiring.flipped(self.i_cal), calibrator.i_cal)
+        m.d.comb += calibrator.dac_gain.eq(self.dac_gain)
 
         #
         # I2C MASTER CONTROL (with global reset for CODEC re-init)
@@ -1015,6 +1027,9 @@ class Peripheral(wiring.Component):
         hard_reset: csr.Field(csr.action.W, unsigned(1))
         aclk_unstable: csr.Field(csr.action.W, unsigned(1))
 
+    class DacGainReg(csr.Register, access="w"):
+        gain: csr.Field(csr.action.W, unsigned(16))
+
     class CalibrationConstant(csr.Register, access="w"):
         value: csr.Field(csr.action.W, signed(32))
 
@@ -1053,6 +1068,7 @@ class Peripheral(wiring.Component):
         self._info = regs.add("info", self.InfoReg())
 
         self._flags = regs.add("flags", self.FlagsReg())
+        self._dac_gain = regs.add("dac_gain", self.DacGainReg())
 
         self._bridge = csr.Bridge(regs.as_memory_map())
 
@@ -1084,6 +1100,9 @@ class Peripheral(wiring.Component):
             # Strobe PMOD hard reset.
             m.d.comb += self.pmod.hard_reset.eq(1)
 
+        with m.If(self._dac_gain.f.gain.w_stb):
+            m.d.sync += self.pmod.dac_gain.eq(self._dac_gain.f.gain.w_data)
+
         with m.If(self._flags.f.aclk_unstable.w_stb):
             m.d.sync += self.pmod.aclk_unstable.eq(self._flags.f.aclk_unstable.w_data)
 
@@ -21,6 +21,11 @@ class Peripheral(wiring.Component):
     sections of a `DelayLine`, with adjustable start, stop, speed and
     loop settings. Playback can be triggered with CSR writes or connected
     to a hardware gate trigger with `hw_gate_enable`.
+
+    In LOOP mode, `crossfade.length` samples at the end of the grain are
+    crossfaded into the start of the grain. `crossfade.gain_step` must be
+    set to `2**16 / crossfade.length` (saturating), as the gateware does
+    not divide. The crossfade length must not exceed the grain length.
     """
 
     class ControlReg(csr.Register, access="rw"):
@@ -41,6 +46,10 @@ class Peripheral(wiring.Component):
     class StatusReg(csr.Register, access="r"):
         position: csr.Field(csr.action.R, unsigned(32))
 
+    class CrossfadeReg(csr.Register, access="rw"):
+        length: csr.Field(csr.action.RW, unsigned(16))
+        gain_step: csr.Field(csr.action.RW, unsigned(16))
+
     def __init__(self, delayln):
         self._delayln = delayln
         self._grain_player = GrainPlayer(delayln)
@@ -51,6 +60,7 @@ class Peripheral(wiring.Component):
         self._start = regs.add("start", self.StartReg(), offset=0x08)
         self._length = regs.add("length", self.LengthReg(), offset=0x0C)
         self._status = regs.add("status", self.StatusReg(), offset=0x10)
+        self._crossfade = regs.add("crossfade", self.CrossfadeReg(), offset=0x14)
         self._bridge = csr.Bridge(regs.as_memory_map())
 
         super().__init__({
@@ -84,6 +94,8 @@ class Peripheral(wiring.Component):
             grain_player.start.eq(self._start.f.start.data),
             grain_player.length.eq(self._length.f.length.data),
             grain_player.speed.as_value().eq(self._speed.f.speed.data),
+            grain_player.crossfade.eq(self._crossfade.f.length.data),
+            grain_player.crossfade_step.as_value().eq(self._crossfade.f.gain_step.data),
             self._status.f.position.r_data.eq(grain_player.position),
         ]
 
@@ -114,6 +126,9 @@ class GrainPlayer(wiring.Component):
             "speed": In(fixed.UQ(8, 8)),
             "start": In(unsigned(self.delayln.address_width)),
             "length": In(unsigned(self.delayln.address_width)),
+            # LOOP mode crossfade length (samples), and 1/length.
+            "crossfade": In(unsigned(16)),
+            "crossfade_step": In(fixed.UQ(0, 16)),
             "scrub": In(stream.Signature(sq)), # Scrub CV (only used in SCRUB mode)
             # Status
             "position": Out(unsigned(self.delayln.address_width)),
@@ -132,6 +147,26 @@ class GrainPlayer(wiring.Component):
         bouncing = Signal()  # Toggles fwd/rev in BOUNCE mode
         pos      = Signal(fixed.UQ(len(self.length), 8))
 
+        # LOOP crossfade: over the last `crossfade` samples of the grain, the
+        # same number of samples from the start of the grain are fetched and
+        # mixed in. The next loop then starts just after them.
+        crossfade      = Signal.like(self.crossfade)
+        crossfade_step = Signal(fixed.UQ(0, 16))
+        looped     = Signal()  # START-GATE entered at the end of a LOOP
+        fading     = Signal()  # Fetching the sample from the start of the grain
+        tail       = Signal(self.sq)  # Sample from the end of the grain
+        fade_start = Signal.like(self.length)
+        fade_gain  = Signal(fixed.UQ(0, 16))
+        fetch_pos  = Signal(fixed.UQ(len(self.length), 8))
+        m.d.comb += [
+            fade_start.eq(length - crossfade),
+            fade_gain.eq((pos.truncate().as_value() - fade_start)[:16] * crossfade_step.as_value()),
+            # Raw (UQ.8) position of the sample currently being fetched.
+            fetch_pos.eq(Mux(fading, pos.as_value() - (fade_start << 8), pos.as_value())),
+        ]
+        in_fade = ((mode == GrainPlayer.Mode.LOOP) & (crossfade != 0) &
+                   (pos.truncate().as_value() >= fade_start))
+
         # Linear interpolation
         sample0   = Signal(self.sq)  # sample at integer position
         tap_addr0 = Signal.like(self.start)  # tap address for first fetch
@@ -164,8 +199,14 @@ class GrainPlayer(wiring.Component):
                     mode.eq(self.mode),
                     start.eq(self.start),
                     length.eq(self.length),
-                    pos.eq(0),
+                    crossfade.eq(self.crossfade),
+                    crossfade_step.eq(self.crossfade_step),
+                    # Skip the samples already played during the crossfade.
+                    pos.eq(fixed.Value.cast(Mux(looped & (self.mode == GrainPlayer.Mode.LOOP),
+                                                self.crossfade, 0))),
                     bouncing.eq(0),
+                    looped.eq(0),
+                    fading.eq(0),
                 ]
                 m.next = 'COMPUTE-ADDR'
             with m.State('COMPUTE-ADDR'):
@@ -184,20 +225,22 @@ class GrainPlayer(wiring.Component):
                 with m.If(self.scrub.valid):
                     m.next = 'COMPUTE-ADDR-FORWARD'
             with m.State('COMPUTE-ADDR-REVERSE'):
-                delay = start - length + pos.truncate().as_value() + 1
+                delay = start - length + fetch_pos.truncate().as_value() + 1
                 m.d.sync += [
                     tap_addr0.eq(delay),
                     tap_addr1.eq(delay + 1),
-                    self.position.eq(delay),
                 ]
+                with m.If(~fading):
+                    m.d.sync += self.position.eq(delay)
                 m.next = 'TAP0-ADDR'
             with m.State('COMPUTE-ADDR-FORWARD'):
-                delay = start - pos.truncate().as_value()
+                delay = start - fetch_pos.truncate().as_value()
                 m.d.sync += [
                     tap_addr0.eq(delay),
                     tap_addr1.eq(delay - 1),
-                    self.position.eq(delay),
                 ]
+                with m.If(~fading):
+                    m.d.sync += self.position.eq(delay)
                 m.next = 'TAP0-ADDR'
             with m.State('TAP0-ADDR'):
                 m.d.comb += [
@@ -223,9 +266,27 @@ class GrainPlayer(wiring.Component):
                 # Linear interpolation: output = sample0 + frac * (sample1 - sample0)
                 frac = pos - pos.truncate()
                 diff = self.tap.o.payload - sample0
-                m.d.sync += self.o.payload.eq(sample0 + diff * frac)
+                interp = sample0 + diff * frac
+                m.d.sync += self.o.payload.eq(interp)
                 with m.If(self.tap.o.valid):
-                    m.next = 'OUT'
+                    with m.If(fading):
+                        m.next = 'CROSSFADE'
+                    with m.Elif(in_fade):
+                        # Go back and fetch the sample to mix with this one.
+                        m.d.sync += [
+                            tail.eq(interp),
+                            fading.eq(1),
+                        ]
+                        m.next = 'COMPUTE-ADDR'
+                    with m.Else():
+                        m.next = 'OUT'
+            with m.State('CROSSFADE'):
+                # Linear crossfade: output = tail + gain * (head - tail)
+                m.d.sync += [
+                    self.o.payload.eq(tail + (self.o.payload - tail) * fade_gain),
+                    fading.eq(0),
+                ]
+                m.next = 'OUT'
             with m.State('OUT'):
                 m.d.comb += self.o.valid.eq(1)
                 with m.If(self.o.ready):
@@ -244,6 +305,7 @@ class GrainPlayer(wiring.Component):
                         with m.If((mode == GrainPlayer.Mode.GATE) | (mode == GrainPlayer.Mode.ONESHOT)):
                             m.next = 'WAIT-READY-ZERO'
                         with m.Elif(mode == GrainPlayer.Mode.LOOP):
+                            m.d.sync += looped.eq(1)
                             m.next = 'START-GATE'
                         with m.Elif(mode == GrainPlayer.Mode.BOUNCE):
                             m.d.sync += bouncing.eq(~bouncing)
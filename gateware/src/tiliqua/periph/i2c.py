@@ -36,20 +36,23 @@ class Provider(Component):
         return m
 
 class I2CStreamerTransaction(data.Struct):
-    last: unsigned(1)
-    rw:   unsigned(1)
-    data: unsigned(8)
+    last:      unsigned(1)
+    rw:        unsigned(1)
+    data:      unsigned(8)
+    addr_only: unsigned(1)
 
 class I2CStreamerStatus(data.Struct):
     tx_empty: unsigned(1)
     rx_empty: unsigned(1)
     busy:  unsigned(1)
     error: unsigned(1)
+    sda:   unsigned(1)
 
 class I2CStreamerControl(wiring.Signature):
     def __init__(self):
         super().__init__({
             "address": Out(7),
+            "recover": Out(1),
             "status":  In(I2CStreamerStatus),
             "i":       Out(stream.Signature(I2CStreamerTransaction)),
             "o":       In(stream.Signature(unsigned(8))),
@@ -66,14 +69,21 @@ class I2CStreamer(wiring.Component):
         - The core drains the transaction FIFO until all are consumed.
         - Read operations push a single byte to the `o` stream per read.
         - NACK errors abort the whole process and drain all FIFOs.
+        - A lone write entry with `addr_only` set sends only the address (and
+          a STOP), without its data byte. This probes for an ACK.
     More detail on how transactions are dilineated can be found in the `i2c.Peripheral` core below.
+
+    Strobing `recover` while idle clocks out a slave stuck holding SDA low in the
+    middle of a transaction. This is a read with the final ACK released (9 SCL
+    clocks, SDA never driven low), followed by a STOP. `status.sda` reflects the
+    current state of the SDA line, so it can be checked afterwards.
     """
 
     def __init__(self, period_cyc=None, clk_stretch=False,
                  transaction_depth=32, rx_depth=8, **kwargs):
         self.period_cyc = period_cyc
         self.clk_stretch = clk_stretch
-        self._transactions = SyncFIFOBuffered(width=10, depth=transaction_depth)
+        self._transactions = SyncFIFOBuffered(width=11, depth=transaction_depth)
         self._rx_fifo = SyncFIFOBuffered(width=8, depth=rx_depth)
         super().__init__({
             "pins":    Out(I2CPinSignature()),
@@ -106,6 +116,7 @@ class I2CStreamer(wiring.Component):
         m.d.comb += self.control.status.tx_empty.eq(self._transactions.level == 0)
         m.d.comb += self.control.status.rx_empty.eq(self._rx_fifo.level == 0)
         m.d.comb += self.control.status.error.eq(err)
+        m.d.comb += self.control.status.sda.eq(i2c.bus.sda_i)
 
         with m.FSM() as fsm:
 
@@ -116,6 +127,9 @@ class I2CStreamer(wiring.Component):
                 with m.If(self.control.i.ready & self.control.i.valid & self.control.i.payload.last):
                     m.d.sync += err.eq(0)
                     m.next = 'START'
+                with m.Elif(self.control.recover):
+                    m.d.sync += err.eq(0)
+                    m.next = 'RECOVER'
 
             with m.State('START'):
                 with m.If(~i2c.busy):
@@ -135,6 +149,10 @@ class I2CStreamer(wiring.Component):
                 with m.If(~i2c.busy):
                     with m.If(~i2c.ack_o):
                         m.next = "ABORT"
+                    with m.Elif(tx.addr_only & ~self.control.status.tx_empty):
+                        # address-only probe, its data byte is never sent
+                        m.d.comb += self._transactions.r_en.eq(1)
+                        m.next = "FINISH"
                     with m.Elif(self.control.status.tx_empty):
                         # zero-length transaction
                         m.next = "FINISH"
@@ -199,6 +217,20 @@ class I2CStreamer(wiring.Component):
                     m.d.comb += i2c.stop.eq(1)
                     m.next = "DRAIN_FIFOS"
 
+            with m.State("RECOVER"):
+                with m.If(~i2c.busy):
+                    # 8 data clocks + 1 (NACK) clock with SDA released.
+                    m.d.comb += [
+                        i2c.ack_i.eq(0),
+                        i2c.read .eq(1),
+                    ]
+                    m.next = "RECOVER_STOP"
+
+            with m.State("RECOVER_STOP"):
+                with m.If(~i2c.busy):
+                    m.d.comb += i2c.stop.eq(1)
+                    m.next = "IDLE"
+
             with m.State("DRAIN_FIFOS"):
                 with m.If((self._transactions.level == 0) &
                           (self._rx_fifo.level == 0)):
@@ -250,9 +282,17 @@ class Peripheral(wiring.Component):
         The 8 bit data words are the data to write (for write transactions),
         or simply ignored (for read transactions). The 'last' bit denotes
         the last entry in a transaction, and permits the core to start.
+        Setting 'addr_only' on a single 'last' write entry sends just the
+        address and a STOP, as a non-destructive probe (e.g. a bus scan).
     rx_data : read-only
         Read FIFO. 8-bit entries, one per successful read transaction.
         This should only be read once 'busy' has deasserted.
+    recover : write-only
+        Writing '1' to 'start' clocks a stuck slave out of a partial
+        transaction (9 SCL pulses) and then issues a STOP. Only write
+        this while the core is not busy. Only effective for cores
+        directly connected to an `I2CStreamer` (i.e. not the
+        `eurorack-pmod` override port).
 
     -- status registers --
     busy : read-only
@@ -262,6 +302,9 @@ class Peripheral(wiring.Component):
     err :  read-only
         '1' if an error (e.g. NACK) has occurred.
         this flag is reset on a new set of transactions ('start' is set).
+    sda :  read-only
+        Current state of the SDA line. If this is '0' while the core
+        is idle, a slave is holding the bus and 'recover' may help.
 
     TODO
     ----
@@ -279,6 +322,7 @@ class Peripheral(wiring.Component):
         data: csr.Field(csr.action.W, unsigned(8))
         rw:   csr.Field(csr.action.W, unsigned(1))
         last: csr.Field(csr.action.W, unsigned(1))
+        addr_only: csr.Field(csr.action.W, unsigned(1))
 
     class RxDataReg(csr.Register, access="r"):
         data: csr.Field(csr.action.R, unsigned(8))
@@ -287,6 +331,10 @@ class Peripheral(wiring.Component):
         busy:  csr.Field(csr.action.R, unsigned(1))
         ready:  csr.Field(csr.action.R, unsigned(1))
         error: csr.Field(csr.action.R, unsigned(1))
+        sda:   csr.Field(csr.action.R, unsigned(1))
+
+    class RecoverReg(csr.Register, access="w"):
+        start: csr.Field(csr.action.W, unsigned(1))
 
     def __init__(self, **kwargs):
 
@@ -296,6 +344,7 @@ class Peripheral(wiring.Component):
         self._transaction_reg = regs.add("transaction_reg", self.TransactionReg(), offset=0x8)
         self._rx_data         = regs.add("rx_data",         self.RxDataReg(),      offset=0xC)
         self._status          = regs.add("status",          self.StatusReg(),      offset=0x10)
+        self._recover         = regs.add("recover",         self.RecoverReg(),     offset=0x14)
 
         self._bridge = csr.Bridge(regs.as_memory_map())
 
@@ -318,6 +367,9 @@ class Peripheral(wiring.Component):
         m.d.comb += [
             self._status.f.busy.r_data.eq(self.i2c_stream.status.busy | self.i2c_stream.i.valid),
             self._status.f.ready.r_data.eq(self.i2c_stream.i.ready),
+            self._status.f.sda.r_data.eq(self.i2c_stream.status.sda),
+            self.i2c_stream.recover.eq(self._recover.f.start.w_stb &
+                                       self._recover.f.start.w_data),
         ]
 
         # only if connected!
@@ -331,6 +383,7 @@ class Peripheral(wiring.Component):
                 self.i2c_stream.i.payload.last.eq(self._transaction_reg.f.last.w_data),
                 self.i2c_stream.i.payload.rw.eq(self._transaction_reg.f.rw.w_data),
                 self.i2c_stream.i.payload.data.eq(self._transaction_reg.f.data.w_data),
+                self.i2c_stream.i.payload.addr_only.eq(self._transaction_reg.f.addr_only.w_data),
             ]
 
         with m.If(self.i2c_stream.i.valid & self.i2c_stream.i.ready):
@@ -0,0 +1,47 @@
+# Reboot into the bootloader on request of the firmware.
+#
+# Copyright (c) 2024 S. Holzapfel <me@sebholzapfel.com>
+#
+# SPDX-License-Identifier: BSD-3-Clause
+
+from amaranth import *
+from amaranth.lib import wiring
+from amaranth.lib.wiring import In, Out, connect, flipped
+from amaranth_soc import csr
+
+
+class Peripheral(wiring.Component):
+
+    """
+    Writing 1 to 'request' raises the 'request' output, which stays high
+    until the FPGA is reconfigured. It is wired to the ``RebootProvider``,
+    which mutes the CODEC and then returns to the bootloader, the same as
+    holding the encoder. Used by the firmware watchdog.
+    """
+
+    class RequestReg(csr.Register, access="w"):
+        request: csr.Field(csr.action.W, unsigned(1))
+
+    def __init__(self, **kwargs):
+        regs = csr.Builder(addr_width=5, data_width=8)
+
+        self._request = regs.add("request", self.RequestReg())
+
+        self._bridge = csr.Bridge(regs.as_memory_map())
+
+        super().__init__({
+            "bus": In(csr.Signature(addr_width=regs.addr_width, data_width=regs.data_width)),
+            "request": Out(1),
+        })
+        self.bus.memory_map = self._bridge.bus.memory_map
+
+    def elaborate(self, platform):
+        m = Module()
+        m.submodules.bridge = self._bridge
+
+        connect(m, flipped(self.bus), self._bridge.bus)
+
+        with m.If(self._request.f.request.w_stb & self._request.f.request.w_data):
+            m.d.sync += self.request.eq(1)
+
+        return m
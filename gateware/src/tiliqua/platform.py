@@ -637,10 +637,14 @@ class RebootProvider(wiring.Component):
     Issue a 'self_program' (return to bootloader) when the 'button'
     signal is high for 'reboot_seconds', and a 'mute' output shortly
     before then (to warn the CODEC to prevent pops).
+
+    A 'request' (e.g. from the firmware watchdog) skips straight to
+    the mute, rebooting 'reboot_seconds - mute_seconds' later.
     """
 
-    button: wiring.In(unsigned(1))
-    mute:   wiring.Out(unsigned(1), init=1)
+    button:  wiring.In(unsigned(1))
+    request: wiring.In(unsigned(1))
+    mute:    wiring.Out(unsigned(1), init=1)
 
     def __init__(self, clock_sync_hz, reboot_seconds=3, mute_seconds=2.5, unmute_seconds=0.25):
         self.reboot_seconds = reboot_seconds
@@ -668,6 +672,8 @@ class RebootProvider(wiring.Component):
             m.d.sync += self.mute.eq(1)
         with m.If(button_counter >= timeout_reboot):
             m.d.comb += platform.request("self_program").o.eq(1)
+        with m.Elif(self.request & (button_counter < timeout_mute)):
+            m.d.sync += button_counter.eq(timeout_mute)
         with m.Else():
             # we already started muting. point of no return.
             with m.If(self.button | self.mute):
@@ -212,11 +212,15 @@ class Peripheral(wiring.Component):
     - SoC must use ``point.cmd == END`` on the final segment in each strip.
     - SoC must always check that ``status.full`` is not asserted before
       enqueuing more line strips.
+    - Alternatively, ``status.fifo_depth - status.fifo_level`` points may be
+      enqueued without checking ``status.full`` in between.
     """
 
     class StatusReg(csr.Register, access="r"):
         full: csr.Field(csr.action.R, unsigned(1))
         empty: csr.Field(csr.action.R, unsigned(1))
+        fifo_level: csr.Field(csr.action.R, unsigned(8))
+        fifo_depth: csr.Field(csr.action.R, unsigned(8))
 
     class PointReg(csr.Register, access="w"):
         # Note: Writing to this register enqueues the point
@@ -266,6 +270,8 @@ class Peripheral(wiring.Component):
         m.d.comb += [
             self._status.f.full.r_data.eq(~cmd_fifo.i.ready),
             self._status.f.empty.r_data.eq(~cmd_fifo.o.valid),
+            self._status.f.fifo_level.r_data.eq(cmd_fifo.fifo.level),
+            self._status.f.fifo_depth.r_data.eq(cmd_fifo.fifo.depth),
         ]
 
         wiring.connect(m, cmd_fifo.o, line_plotter.i)
@@ -84,6 +84,8 @@ class Peripheral(wiring.Component):
     - The plot request is enqueued to the internal FIFO and will be executed as
       soon as possible.
     - Enqueue as many pixels as you want until ``status.busy`` is asserted (``fifo_depth``).
+    - Alternatively, ``status.fifo_depth - status.fifo_level`` pixels may be
+      enqueued without checking ``status.busy`` in between.
 
     This core always plots using OffsetMode.ABSOLUTE and BlendMode.REPLACE, which
     is generally what an SoC wants to do when drawing text/menus.
@@ -92,6 +94,7 @@ class Peripheral(wiring.Component):
     class StatusReg(csr.Register, access="r"):
         fifo_level: csr.Field(csr.action.R, unsigned(16))
         busy:       csr.Field(csr.action.R, unsigned(1))
+        fifo_depth: csr.Field(csr.action.R, unsigned(8))
 
     class PlotReg(csr.Register, access="w"):
         # Note: Writing to this register enqueues the plot operation!
@@ -139,6 +142,7 @@ class Peripheral(wiring.Component):
         m.d.comb += [
             self._status.f.fifo_level.r_data.eq(cmd_fifo.fifo.level),
             self._status.f.busy.r_data.eq(~cmd_fifo.i.ready), # Busy when FIFO full
+            self._status.f.fifo_depth.r_data.eq(cmd_fifo.fifo.depth),
         ]
 
         # Send plot requests to shared plotting backend on `o`
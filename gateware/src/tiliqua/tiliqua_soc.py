@@ -54,7 +54,7 @@ from vendor.vexiiriscv import VexiiRiscv
 from . import pll
 from .build import sim
 from .build.types import FirmwareLocation
-from .periph import dtr, encoder, eurorack_pmod, i2c, psram
+from .periph import dtr, encoder, eurorack_pmod, i2c, psram, reboot
 from .platform import *
 from .raster import blit, line, persist, plot
 from .video import framebuffer, palette
@@ -107,6 +107,7 @@ class TiliquaSoc(Component):
         self.pixel_plot_csr_base  = 0x00000D00
         self.blit_csr_base        = 0x00000E00
         self.line_csr_base        = 0x00000F00
+        self.reboot0_base         = 0x00001000
 
         # Some settings depend on whether code is in block RAM or SPI flash
         self.fw_location = fw_location
@@ -126,11 +127,16 @@ class TiliquaSoc(Component):
                 self.fw_max_size = 0x50000 # 320KiB
 
 
+        # XiP firmware runs flash erase/program from mainram, as the
+        # flash can't be read (fetched from) until they complete.
+        mainram_executable = fw_location == FirmwareLocation.SPIFlash
+
         # VexiiRiscv CPU instance
         self.cpu = VexiiRiscv(
             # Writing outside these regions will cause CPU traps.
             regions = [
-                VexiiRiscv.MemoryRegion(base=self.mainram_base, size=self.mainram_size, cacheable=True, executable=False),
+                VexiiRiscv.MemoryRegion(base=self.mainram_base, size=self.mainram_size, cacheable=True,
+                                        executable=mainram_executable),
                 VexiiRiscv.MemoryRegion(base=self.spiflash_base, size=self.spiflash_size, cacheable=True, executable=True),
                 VexiiRiscv.MemoryRegion(base=self.psram_base, size=self.psram_size, cacheable=True, executable=True),
                 VexiiRiscv.MemoryRegion(base=self.csr_base, size=0x10000, cacheable=False, executable=False),
@@ -215,6 +221,10 @@ class TiliquaSoc(Component):
         self.dtr0 = dtr.Peripheral()
         self.csr_decoder.add(self.dtr0.bus, addr=self.dtr0_base, name="dtr0")
 
+        # reboot into the bootloader (watchdog)
+        self.reboot0 = reboot.Peripheral()
+        self.csr_decoder.add(self.reboot0.bus, addr=self.reboot0_base, name="reboot0")
+
         # framebuffer palette interface
         self.palette_periph = palette.Peripheral()
         self.csr_decoder.add(
@@ -329,6 +339,9 @@ class TiliquaSoc(Component):
             m.submodules.encoder0_provider = encoder0_provider
             wiring.connect(m, self.encoder0.pins, encoder0_provider.pins)
 
+        # reboot0
+        m.submodules.reboot0 = self.reboot0
+
         # psram
         m.submodules.psram_periph = self.psram_periph
 
@@ -348,6 +361,7 @@ class TiliquaSoc(Component):
                 i=~self.fb.fbp.enable, o=reset_dvi, o_domain="dvi", reset=1)
         m.submodules.fb = ResetInserter({'sync': ~self.fb.fbp.enable, 'dvi': reset_dvi, 'dvi5x': reset_dvi})(self.fb)
         m.submodules.framebuffer_periph = self.framebuffer_periph
+        m.d.comb += self.framebuffer_periph.vblank.eq(self.fb.vblank)
 
         # video periph / persist
         m.submodules.persist_periph = self.persist_periph
@@ -411,6 +425,7 @@ class TiliquaSoc(Component):
             # Connect encoder button to RebootProvider
             m.submodules.reboot = reboot = RebootProvider(self.clock_settings.frequencies.sync)
             m.d.comb += reboot.button.eq(self.encoder0._button.f.button.r_data)
+            m.d.comb += reboot.request.eq(self.reboot0.request)
             m.d.comb += self.pmod0_periph.mute.eq(reboot.mute)
         else:
             m.submodules.car = sim.FakeTiliquaDomainGenerator()
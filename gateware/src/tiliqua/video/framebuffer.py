@@ -76,7 +76,10 @@ class DMAFramebuffer(wiring.Component):
             # Dynamic timing / modeline information shared with other cores.
             "fbp": In(self.Properties()),
             # Enough information to plot the output of this core to images
-            "simif": Out(self.SimulationInterface())
+            "simif": Out(self.SimulationInterface()),
+            # Scanout is in vertical blanking ('sync' domain). Drawing to the
+            # (single) framebuffer while this is set does not tear.
+            "vblank": Out(1),
         })
 
     def elaborate(self, platform) -> Module:
@@ -99,6 +102,10 @@ class DMAFramebuffer(wiring.Component):
         m.submodules.vsync_ff = FFSynchronizer(
                 i=dvi_tgen.ctrl.vsync, o=phy_vsync_sync, o_domain="sync")
 
+        # Vertical blanking is every line before the first active one.
+        m.submodules.vblank_ff = FFSynchronizer(
+                i=dvi_tgen.y < 0, o=self.vblank, o_domain="sync")
+
         # DMA master bus
         bus = self.bus
 
@@ -245,6 +252,15 @@ class Peripheral(wiring.Component):
     class HpdReg(csr.Register, access="r"):
         # DVI hot plug detect
         hpd: csr.Field(csr.action.R, unsigned(1))
+        # Scanout is in vertical blanking (always set while disabled)
+        vblank: csr.Field(csr.action.R, unsigned(1))
+        # Scanout is enabled ('enable' in FlagsReg, which is write-only)
+        enabled: csr.Field(csr.action.R, unsigned(1))
+
+    class FramesReg(csr.Register, access="r"):
+        # Free-running count of frames scanned out (wraps). Firmware reads
+        # it twice across a known time window to measure the refresh rate.
+        frames: csr.Field(csr.action.R, unsigned(32))
 
     def __init__(self):
         regs = csr.Builder(addr_width=6, data_width=8)
@@ -257,12 +273,15 @@ class Peripheral(wiring.Component):
         self._flags        = regs.add("flags",        self.FlagsReg(),       offset=0x14)
         self._fb_base      = regs.add("fb_base",      self.FBBaseReg(),      offset=0x18)
         self._hpd          = regs.add("hpd",          self.HpdReg(),         offset=0x1C)
+        self._frames       = regs.add("frames",       self.FramesReg(),      offset=0x20)
 
         self._bridge = csr.Bridge(regs.as_memory_map())
 
         super().__init__({
             "bus": In(csr.Signature(addr_width=regs.addr_width, data_width=regs.data_width)),
             "fbp": Out(DMAFramebuffer.Properties()),
+            # From DMAFramebuffer.vblank
+            "vblank": In(1),
         })
 
         self.bus.memory_map = self._bridge.bus.memory_map
@@ -303,5 +322,19 @@ class Peripheral(wiring.Component):
             # Fake connected screen in simulation
             m.d.comb += self._hpd.f.hpd.r_data.eq(1)
 
+        # Nothing is scanned out while disabled, so nobody waiting on
+        # vblank should be stuck.
+        m.d.comb += self._hpd.f.vblank.r_data.eq(self.vblank | ~self.fbp.enable)
+        m.d.comb += self._hpd.f.enabled.r_data.eq(self.fbp.enable)
+
+        # Count the start of each vertical blanking interval, so the count
+        # stops if the DVI clock or timing generator does.
+        frames = Signal(32)
+        l_vblank = Signal()
+        m.d.sync += l_vblank.eq(self.vblank)
+        with m.If(self.vblank & ~l_vblank):
+            m.d.sync += frames.eq(frames + 1)
+        m.d.comb += self._frames.f.frames.r_data.eq(frames)
+
         return m
 
@@ -6,7 +6,7 @@ use log::{info, warn};
 use riscv_rt::entry;
 use irq::handler;
 use core::cell::RefCell;
-use heapless::String;
+use heapless::{String, Vec};
 use micromath::{F32Ext};
 use strum_macros::{EnumIter, IntoStaticStr};
 use embedded_hal::delay::DelayNs;
@@ -16,7 +16,10 @@ use core::str::FromStr;
 use core::fmt::Write;
 
 use tiliqua_lib::*;
-use tiliqua_lib::eeprominfo::{EepromConfig, EepromManager};
+use tiliqua_lib::eeprominfo::{EepromConfig, EepromCrcCache, EepromManager, EepromModeline, SlotOrder,
+                               EEPROM_CONFIG_RING_ADDR, EEPROM_CONFIG_RING_SLOTS};
+use tiliqua_lib::edid::DisplayId;
+use tiliqua_lib::checksum::crc32_bzip2_region;
 use pac::constants::*;
 use tiliqua_fw::*;
 use tiliqua_hal::pmod::EurorackPmod;
@@ -25,7 +28,7 @@ use tiliqua_hal::si5351::*;
 use tiliqua_hal::cy8cmbr3xxx::*;
 use tiliqua_hal::dma_framebuffer::DMAFramebuffer;
 use tiliqua_manifest::*;
-use opts::OptionString;
+use opts::{OptionString, OptionTrait};
 
 use tiliqua_hal::embedded_graphics::{
     mono_font::{ascii::FONT_9X15, ascii::FONT_9X15_BOLD, MonoTextStyle},
@@ -38,14 +41,20 @@ use tiliqua_lib::color::HI8;
 use tiliqua_fw::options::*;
 use hal::pca9635::Pca9635Driver;
 use hal::tusb322::{TUSB322Driver, TUSB322Mode};
-use hal::dma_framebuffer::{Rotate, DVIModeline};
+use hal::dma_framebuffer::{Rotate, DVIModeline, ModelineField, VideoHpd, HpdEvent, TestPattern};
 
 pub const TIMER0_ISR_PERIOD_MS: u32 = 10;
+// Ramp the outputs down to silence over ~50ms before muting the CODEC.
+const MUTE_RAMP_STEP: i16 = (hal::pmod::DAC_GAIN_UNITY as u32 * TIMER0_ISR_PERIOD_MS / 50) as i16;
 // Technically this lower bound is out of the ECP5 PLL spec,
 // see the notes in `tiliqua_pll.py:create_dynamic_dvi_pll`.
 // But we keep it this low for compatibility with low res modes.
-pub const PIXEL_CLK_MIN_KHZ: u32 = 24_000u32;
+pub const PIXEL_CLK_MIN_KHZ: u32 = hal::dma_framebuffer::PIXEL_CLK_MIN_KHZ;
 pub const PIXEL_CLK_MAX_KHZ: u32 = CLOCK_DVI_HZ / 1000u32;
+// Refuse external PLL configurations that miss the requested frequencies by
+// more than this. Fractional divider rounding is normally a few ppm, while
+// displays only lose lock at thousands of ppm.
+pub const PLL_MAX_ERROR_PPM: u32 = 500;
 
 #[derive(Clone, Copy, PartialEq, EnumIter, IntoStaticStr)]
 #[strum(serialize_all = "SCREAMING-KEBAB-CASE")]
@@ -53,9 +62,12 @@ pub enum BitstreamError {
     InvalidManifest,
     HwVersionMismatch,
     SpiflashCrcError,
+    PsramCrcError,
     PllBadConfigError,
     PllI2cError,
     BootloaderStaticModeline,
+    Incompatible,
+    InvalidRegions,
 }
 
 struct App {
@@ -64,36 +76,167 @@ struct App {
     eeprom_manager: EepromManager<I2c1>,
     reboot_n: Option<usize>,
     error_n: [Option<String<32>>; N_MANIFESTS],
+    features: Vec<Feature, REQUIRES_MAX_N>,
     time_since_reboot_requested: u32,
     manifests: [Option<BitstreamManifest>; N_MANIFESTS],
+    names: [OptionString; N_MANIFESTS],
+    // Boot page order, see `apply_slot_order`.
+    slot_order: SlotOrder,
+    // Physical slot on each boot page row, `None` for unused rows.
+    slot_rows: [Option<usize>; N_MANIFESTS],
+    // Slot last selected on the slots page.
+    slots_edit_slot: usize,
     animation_elapsed_ms: u32,
     modeline: DVIModeline,
+    display_id: Option<DisplayId>,
+    // Modeline on the video page, which may not have been applied yet.
+    edit_modeline: DVIModeline,
+    video_status: Option<&'static str>,
     autoboot_slot: Option<usize>,
     autoboot_countdown_ms: u32,
+    // Autoboot delay last written to the EEPROM.
+    autoboot_delay: AutobootDelay,
+    // Hidden test pattern mode, see `update_test_pattern`.
+    test_pattern: Option<TestPattern>,
+    test_pattern_touch: bool,
 }
 
 impl App {
     pub fn new(opts: Opts, manifests: [Option<BitstreamManifest>; N_MANIFESTS],
-               pll: Option<Si5351Device<I2c0>>, modeline: DVIModeline, autoboot_slot: Option<usize>, 
-               eeprom_manager: EepromManager<I2c1>) -> Self {
+               names: [OptionString; N_MANIFESTS], slot_order: SlotOrder,
+               pll: Option<Si5351Device<I2c0>>, modeline: DVIModeline, display_id: Option<DisplayId>,
+               autoboot_slot: Option<usize>, eeprom_manager: EepromManager<I2c1>) -> Self {
         let peripherals = unsafe { pac::Peripherals::steal() };
         let encoder = Encoder0::new(peripherals.ENCODER0);
         let i2cdev = I2c0::new(peripherals.I2C0);
         let pca9635 = Pca9635Driver::new(i2cdev);
         let pmod = EurorackPmod0::new(peripherals.PMOD0_PERIPH);
-        Self {
+        let autoboot_delay = opts.misc.autoboot.value;
+        let features = bootloader_features(pll.is_some());
+        // Flag malformed or incompatible bitstreams up front, rather than when booting.
+        let error_n = core::array::from_fn(|n| {
+            let manifest = manifests[n].as_ref()?;
+            let mut s = String::new();
+            if let Err(e) = manifest.validate() {
+                info!("(entry {}) invalid regions: {}", n, e);
+                write!(s, "{}", e).ok();
+            } else if let Err(e) = manifest.check_compat(&features) {
+                info!("(entry {}) incompatible: {}", n, e);
+                write!(s, "{}", e).ok();
+            } else {
+                return None;
+            }
+            Some(s)
+        });
+        let slots_edit_slot = opts.slots.slot.value as usize;
+        let mut app = Self {
             ui: ui::UI::new(opts, TIMER0_ISR_PERIOD_MS,
                             encoder, pca9635, pmod),
             pll,
             eeprom_manager,
             reboot_n: None,
-            error_n: [const { None }; N_MANIFESTS],
+            error_n,
+            features,
             time_since_reboot_requested: 0u32,
             manifests,
+            names,
+            slot_order,
+            slot_rows: [None; N_MANIFESTS],
+            slots_edit_slot,
             animation_elapsed_ms: 0u32,
+            edit_modeline: modeline.clone(),
             modeline,
+            display_id,
+            video_status: None,
             autoboot_slot,
-            autoboot_countdown_ms: if autoboot_slot.is_some() { 5000 } else { 0 },
+            // Even with no delay, count down for one tick so the countdown is
+            // drawn and touching the encoder can still cancel the autoboot.
+            autoboot_countdown_ms: if autoboot_slot.is_some() {
+                (autoboot_delay.ms() as u32).max(TIMER0_ISR_PERIOD_MS)
+            } else {
+                0
+            },
+            autoboot_delay,
+            test_pattern: None,
+            test_pattern_touch: false,
+        };
+        app.apply_slot_order();
+        app
+    }
+
+    // List the visible slots on the boot page in their configured order. Rows
+    // past the last visible slot are left blank and can't be selected.
+    fn apply_slot_order(&mut self) {
+        const ROW_NAMES: [&str; N_MANIFESTS] = [
+            "slot0", "slot1", "slot2", "slot3", "slot4", "slot5", "slot6", "slot7"
+        ];
+        let mut visible = self.slot_order.visible();
+        self.slot_rows = core::array::from_fn(|_| visible.next());
+        for (row, option) in self.ui.opts.boot.rows_mut().into_iter().enumerate() {
+            match self.slot_rows[row] {
+                Some(n) => {
+                    option.name = ROW_NAMES[n];
+                    option.value = self.names[n].clone();
+                    option.set_enabled(true);
+                }
+                None => {
+                    option.name = "";
+                    option.value = OptionString::new();
+                    option.set_enabled(false);
+                }
+            }
+        }
+    }
+
+    /// Boot page row listing physical slot `n`, if it is shown.
+    fn slot_row(&self, n: usize) -> Option<usize> {
+        self.slot_rows.iter().position(|row| *row == Some(n))
+    }
+
+    // Slots page: apply and persist visibility or order changes. Autoboot
+    // and `last_boot_slot` refer to physical slots, so are unaffected.
+    fn update_slot_order(&mut self) {
+        let slot = self.ui.opts.slots.slot.value as usize;
+        let hidden = self.ui.opts.slots.show.value == SlotVisibility::Hidden;
+        let mut order = self.slot_order.clone();
+        if slot != self.slots_edit_slot {
+            // Newly selected slot, only show its current visibility.
+            self.slots_edit_slot = slot;
+        } else if hidden != order.is_hidden(slot) && !order.set_hidden(slot, hidden) {
+            info!("slots: can't hide the last visible slot");
+        }
+        if self.ui.opts.slots.move_up.poll() {
+            order.move_up(slot);
+        }
+        if self.ui.opts.slots.move_down.poll() {
+            order.move_down(slot);
+        }
+        self.ui.opts.slots.show.value = if order.is_hidden(slot) {
+            SlotVisibility::Hidden
+        } else {
+            SlotVisibility::Shown
+        };
+        if order != self.slot_order {
+            info!("slots: visible order now {:?}", order);
+            self.slot_order = order.clone();
+            self.eeprom_manager.update_config(|c| c.slot_order = order).ok();
+            self.apply_slot_order();
+        }
+    }
+
+    // Hidden gesture for diagnosing video: on the VIDEO page, each touch of the
+    // out3 jack shows the next test pattern instead of the menu. Any encoder
+    // activity goes back to the menu.
+    pub fn update_test_pattern(&mut self) {
+        let touch = self.ui.pmod.touch()[7];
+        let touched = if self.test_pattern_touch { touch >= 150 } else { touch > 200 };
+        if touched && !self.test_pattern_touch && self.ui.opts.tracker.page.value == Page::Video {
+            self.test_pattern = Some(self.test_pattern.map_or(TestPattern::default(), TestPattern::next));
+            info!("video: test pattern {:?}", self.test_pattern);
+        }
+        self.test_pattern_touch = touched;
+        if self.ui.encoder_recently_touched(TIMER0_ISR_PERIOD_MS*2) {
+            self.test_pattern = None;
         }
     }
 
@@ -122,6 +265,48 @@ impl App {
         }
         return false;
     }
+
+    // Fold any changes on the video page into `edit_modeline`. Fields are
+    // adjusted one at a time so timings that would cross each other are pushed
+    // along, and the result is written back so the page shows what will be applied.
+    pub fn update_edit_modeline(&mut self) {
+        use ModelineField::*;
+        let video = &self.ui.opts.video;
+        let m = &mut self.edit_modeline;
+        for (field, value, current) in [
+            (HActive,    video.h_active.value,     m.h_active),
+            (HSyncStart, video.h_sync_start.value, m.h_sync_start),
+            (HSyncEnd,   video.h_sync_end.value,   m.h_sync_end),
+            (HTotal,     video.h_total.value,      m.h_total),
+            (VActive,    video.v_active.value,     m.v_active),
+            (VSyncStart, video.v_sync_start.value, m.v_sync_start),
+            (VSyncEnd,   video.v_sync_end.value,   m.v_sync_end),
+            (VTotal,     video.v_total.value,      m.v_total),
+        ] {
+            m.adjust(field, value as i32 - current as i32);
+        }
+        m.adjust(HSyncInvert, ((video.h_sync.value == SyncPolarity::Negative) != m.h_sync_invert) as i32);
+        m.adjust(VSyncInvert, ((video.v_sync.value == SyncPolarity::Negative) != m.v_sync_invert) as i32);
+        m.adjust(PixelClk, video.pixel_clk.value as i32 - m.pixel_clk_khz() as i32);
+        self.write_video_opts();
+    }
+
+    pub fn write_video_opts(&mut self) {
+        let polarity = |invert| if invert { SyncPolarity::Negative } else { SyncPolarity::Positive };
+        let m = &self.edit_modeline;
+        let video = &mut self.ui.opts.video;
+        video.h_active.value     = m.h_active;
+        video.h_sync_start.value = m.h_sync_start;
+        video.h_sync_end.value   = m.h_sync_end;
+        video.h_total.value      = m.h_total;
+        video.h_sync.value       = polarity(m.h_sync_invert);
+        video.v_active.value     = m.v_active;
+        video.v_sync_start.value = m.v_sync_start;
+        video.v_sync_end.value   = m.v_sync_end;
+        video.v_total.value      = m.v_total;
+        video.v_sync.value       = polarity(m.v_sync_invert);
+        video.pixel_clk.value    = m.pixel_clk_khz();
+    }
 }
 
 fn print_rebooting<D>(d: &mut D, rng: &mut fastrand::Rng)
@@ -160,8 +345,37 @@ where
     .draw(d).ok();
 }
 
+fn print_recovery<D>(d: &mut D, slot: usize, name: &OptionString, status: &str)
+where
+    D: DrawTarget<Color = HI8> + OriginDimensions,
+{
+    let style = MonoTextStyle::new(&FONT_9X15, HI8::new(0, 10));
+    let style_bold = MonoTextStyle::new(&FONT_9X15_BOLD, HI8::WHITE);
+    let h_active = d.size().width as i32;
+    let v_active = d.size().height as i32;
+
+    let mut target: String<64> = String::new();
+    write!(target, "slot {}: {}", slot, if name.is_empty() { "<empty>" } else { name.as_str() }).ok();
+    let lines: [(&str, MonoTextStyle<HI8>); 4] = [
+        ("RECOVERY MODE", style_bold),
+        (&target, style_bold),
+        ("turn to choose slot, press to receive (XMODEM over USB serial)", style),
+        (status, style),
+    ];
+    for (n, (text, style)) in lines.into_iter().enumerate() {
+        Text::with_alignment(
+            text,
+            Point::new(h_active/2, v_active/2 - 40 + 20*n as i32),
+            style,
+            Alignment::Center,
+        )
+        .draw(d).ok();
+    }
+}
+
 fn draw_summary<D>(d: &mut D,
                    bitstream_manifest: &Option<BitstreamManifest>,
+                   options_saved: Option<bool>,
                    error: &Option<String<32>>,
                    startup_report: &String<256>,
                    or: i32, ot: i32, hue: u8)
@@ -209,25 +423,47 @@ where
             Alignment::Right,
         )
         .draw(d).ok();
+        // Builds of the same tag are told apart by their build date.
+        let mut tag: String<32> = String::new();
+        match bitstream.build_date() {
+            Some(date) => write!(tag, "{} ({})", bitstream.tag, date).ok(),
+            None => write!(tag, "{}", bitstream.tag).ok(),
+        };
         Text::with_alignment(
-            &bitstream.tag,
+            &tag,
             Point::new((h_active/2) as i32 + or, (v_active/2+60) as i32 + ot),
             norm,
             Alignment::Left,
         )
         .draw(d).ok();
+        if let Some(saved) = options_saved {
+            Text::with_alignment(
+                "options:".into(),
+                Point::new((h_active/2 - 10) as i32 + or, (v_active/2+80) as i32 + ot),
+                norm,
+                Alignment::Right,
+            )
+            .draw(d).ok();
+            Text::with_alignment(
+                if saved { "modified" } else { "defaults" },
+                Point::new((h_active/2) as i32 + or, (v_active/2+80) as i32 + ot),
+                norm,
+                Alignment::Left,
+            )
+            .draw(d).ok();
+        }
     }
     if let Some(error_string) = &error {
         Text::with_alignment(
             "error:".into(),
-            Point::new((h_active/2 - 10) as i32 + or, (v_active/2+80) as i32 + ot),
+            Point::new((h_active/2 - 10) as i32 + or, (v_active/2+100) as i32 + ot),
             norm,
             Alignment::Right,
         )
         .draw(d).ok();
         Text::with_alignment(
             &error_string,
-            Point::new((h_active/2) as i32 + or, (v_active/2+80) as i32 + ot),
+            Point::new((h_active/2) as i32 + or, (v_active/2+100) as i32 + ot),
             norm,
             Alignment::Left,
         )
@@ -249,40 +485,192 @@ where
     .draw(d).ok();
 }
 
-fn configure_external_pll(pll_config: &ExternalPLLConfig, pll: &mut Si5351Device<I2c0>)
-    -> Result<(), tiliqua_hal::si5351::Error> {
+fn draw_edit_modeline<D>(d: &mut D, edit_modeline: &DVIModeline, status: Option<&str>, hue: u8)
+where
+    D: DrawTarget<Color = HI8> + OriginDimensions,
+{
+    let h_active = d.size().width as i32;
+    let v_active = d.size().height as i32;
+    let norm = MonoTextStyle::new(&FONT_9X15, HI8::new(hue, 10));
+    let mut s: String<64> = String::new();
+    write!(s, "edit: {}x{} @ {:.2}Hz ({:.2}MHz)",
+           edit_modeline.h_active, edit_modeline.v_active,
+           edit_modeline.refresh_rate(), edit_modeline.pixel_clk_mhz).ok();
+    Text::with_alignment(
+        &s,
+        Point::new(h_active/2, v_active/2 + 100),
+        norm,
+        Alignment::Left,
+    )
+    .draw(d).ok();
+    if let Some(status) = status {
+        Text::with_alignment(
+            status,
+            Point::new(h_active/2, v_active/2 + 120),
+            norm,
+            Alignment::Left,
+        )
+        .draw(d).ok();
+    }
+    Text::with_alignment(
+        "Edit timings, then 'apply' to try them. 'save' keeps them for this display.",
+        Point::new(h_active/2, v_active-180),
+        norm,
+        Alignment::Center,
+    )
+    .draw(d).ok();
+}
+
+// Slots page: every slot in boot page order, hidden ones last and dimmed.
+fn draw_slot_order<D>(d: &mut D, slot_order: &SlotOrder, names: &[OptionString; N_MANIFESTS],
+                      edit_slot: usize, hue: u8)
+where
+    D: DrawTarget<Color = HI8> + OriginDimensions,
+{
+    let h_active = d.size().width as i32;
+    let v_active = d.size().height as i32;
+    let hidden = (0..N_MANIFESTS).filter(|n| slot_order.is_hidden(*n));
+    for (row, n) in slot_order.visible().chain(hidden).enumerate() {
+        let mut s: String<64> = String::new();
+        write!(s, "{} slot{}: {}", if n == edit_slot { ">" } else { " " }, n,
+               if names[n].is_empty() { "<empty>" } else { names[n].as_str() }).ok();
+        let brightness = if slot_order.is_hidden(n) { 5 } else { 10 };
+        Text::with_alignment(
+            &s,
+            Point::new(h_active/2, v_active/2 - 40 + 18*row as i32),
+            MonoTextStyle::new(&FONT_9X15, HI8::new(hue, brightness)),
+            Alignment::Left,
+        )
+        .draw(d).ok();
+    }
+    Text::with_alignment(
+        "Pick a slot, then hide it or move it up or down the boot page.",
+        Point::new(h_active/2, v_active-180),
+        MonoTextStyle::new(&FONT_9X15, HI8::new(hue, 10)),
+        Alignment::Center,
+    )
+    .draw(d).ok();
+}
+
+// Error (in ppm) between achieved and requested frequency, rounded up.
+fn freq_error_ppm(actual_hz: u32, requested_hz: u32) -> u32 {
+    let error = (actual_hz as i64 - requested_hz as i64).unsigned_abs();
+    (error * 1_000_000).div_ceil(requested_hz.max(1) as u64) as u32
+}
+
+fn bootloader_features(pll_present: bool) -> Vec<Feature, REQUIRES_MAX_N> {
+    let mut features = Vec::new();
+    if FIXED_MODELINE.is_none() {
+        features.push(Feature::DynamicModeline).ok();
+    }
+    if pll_present {
+        features.push(Feature::ExternalPll).ok();
+    }
+    features
+}
+
+// Returns the frequencies actually achieved, for `BootInfo::clocks`.
+fn configure_external_pll(pll_config: &ExternalPLLConfig, pll: &mut Si5351Device<I2c0>, max_error_ppm: u32)
+    -> Result<bootinfo::ClockInfo, tiliqua_hal::si5351::Error> {
     pll.init_adafruit_module()?;
+    let mut requested: heapless::Vec<u32, 2> = heapless::Vec::new();
+    let mut actual: heapless::Vec<u32, 2> = heapless::Vec::new();
     match pll_config.clk1_hz {
         Some(clk1_hz) => {
             info!("si5351/pll: configure for clk0={}Hz, clk1={}Hz", pll_config.clk0_hz, clk1_hz);
-            pll.set_frequencies(
+            let freqs = [pll_config.clk0_hz, clk1_hz];
+            let achieved = pll.set_frequencies_checked(
                 PLL::A,
                 &[
                     ClockOutput::Clk0,
                     ClockOutput::Clk1,
                 ],
-                &[
-                    pll_config.clk0_hz,
-                    clk1_hz,
-                ],
-                pll_config.spread_spectrum)
+                &freqs,
+                pll_config.spread_spectrum)?;
+            requested.extend_from_slice(&freqs).ok();
+            actual.extend_from_slice(&achieved).ok();
         }
         _ => {
             info!("si5351/pll: configure for clk0={}Hz, clk1=disabled", pll_config.clk0_hz);
-            pll.set_frequencies(
+            let freqs = [pll_config.clk0_hz];
+            let achieved = pll.set_frequencies_checked(
                 PLL::A,
                 &[
                     ClockOutput::Clk0,
                 ],
-                &[
-                    pll_config.clk0_hz,
-                ],
-                pll_config.spread_spectrum)
+                &freqs,
+                pll_config.spread_spectrum)?;
+            requested.extend_from_slice(&freqs).ok();
+            actual.extend_from_slice(&achieved).ok();
+        }
+    }
+    let mut result = Ok(bootinfo::ClockInfo {
+        audio_hz: actual[0],
+        pixel_hz: actual.get(1).copied(),
+    });
+    for (n, (requested_hz, actual_hz)) in requested.iter().zip(actual.iter()).enumerate() {
+        let error_ppm = freq_error_ppm(*actual_hz, *requested_hz);
+        info!("si5351/pll: clk{} requested={}Hz achieved={}Hz error={}ppm",
+              n, requested_hz, actual_hz, error_ppm);
+        if error_ppm > max_error_ppm {
+            warn!("si5351/pll: clk{} error exceeds {}ppm!", n, max_error_ppm);
+            result = Err(tiliqua_hal::si5351::Error::FrequencyOutOfTolerance);
         }
     }
+    result
 }
 
-fn validate_and_copy_spiflash_region(region: &MemoryRegion) -> Result<(), BitstreamError> {
+// Change clk1 on the PLL already set up by `configure_external_pll`,
+// without touching the PLL itself or clk0.
+fn retune_pixel_clock(pll: &mut Si5351Device<I2c0>, pixel_clk_hz: u32, max_error_ppm: u32)
+    -> Result<(), tiliqua_hal::si5351::Error> {
+    let actual_hz = pll.set_output_frequency(PLL::A, ClockOutput::Clk1, pixel_clk_hz)?;
+    let error_ppm = freq_error_ppm(actual_hz, pixel_clk_hz);
+    info!("si5351/pll: clk1 requested={}Hz achieved={}Hz error={}ppm",
+          pixel_clk_hz, actual_hz, error_ppm);
+    if error_ppm > max_error_ppm {
+        warn!("si5351/pll: clk1 error exceeds {}ppm!", max_error_ppm);
+        return Err(tiliqua_hal::si5351::Error::FrequencyOutOfTolerance);
+    }
+    Ok(())
+}
+
+fn manifest_addr(n: usize) -> usize {
+    SPIFLASH_BASE + MANIFEST_OFFSET + (n+1)*SLOT_SIZE
+}
+
+// Whether a bitstream has saved options, or `None` if it has no option storage.
+// Only the first word of each flash sector is read, which the option storage
+// writes to as soon as it starts using that sector, so this is fast enough to
+// do for every slot.
+fn options_saved(manifest: &Option<BitstreamManifest>) -> Option<bool> {
+    const OPTION_SECTOR_SIZE: usize = 4096;
+    let window = manifest.as_ref()?.get_option_storage_window()?;
+    Some(window.step_by(OPTION_SECTOR_SIZE).any(|addr| {
+        let word = (SPIFLASH_BASE + addr as usize) as *const u32;
+        unsafe { word.read_volatile() } != 0xFFFF_FFFF
+    }))
+}
+
+// Identifies the contents of slot `n` for `EepromCrcCache`, without
+// touching anything but the (small) manifest.
+fn crc_cache_entry(n: usize, manifest: &BitstreamManifest) -> EepromCrcCache {
+    let crc_bzip2 = crc::Crc::<u32>::new(&crc::CRC_32_BZIP2);
+    let manifest_bytes = unsafe {
+        core::slice::from_raw_parts(manifest_addr(n) as *const u8, MANIFEST_SIZE)
+    };
+    let mut regions_digest = crc_bzip2.digest();
+    for region in &manifest.regions {
+        regions_digest.update(&region.crc.unwrap_or(0).to_le_bytes());
+    }
+    EepromCrcCache {
+        slot: n as u8,
+        manifest_crc: crc_bzip2.checksum(manifest_bytes),
+        regions_crc: regions_digest.finalize(),
+    }
+}
+
+fn validate_spiflash_region(region: &MemoryRegion) -> Result<(), BitstreamError> {
     // Skip regions without spiflash_src (e.g. during simulation)
     let spiflash_src = match region.spiflash_src {
         Some(addr) => addr,
@@ -292,10 +680,6 @@ fn validate_and_copy_spiflash_region(region: &MemoryRegion) -> Result<(), Bitstr
         }
     };
 
-    let spiflash_ptr = SPIFLASH_BASE as *mut u32;
-    let spiflash_offset_words = spiflash_src as isize / 4isize;
-    let size_words = region.size as isize / 4isize + 1;
-
     match region.region_type {
         RegionType::Bitstream | RegionType::XipFirmware | RegionType::RamLoad => {
             info!("Validate region '{}' at {:#x} (size: {} KiB) ...", 
@@ -310,19 +694,10 @@ fn validate_and_copy_spiflash_region(region: &MemoryRegion) -> Result<(), Bitstr
 
     // Always validate CRC from SPI flash source first
     if let Some(crc_target) = region.crc {
-        let crc_bzip2 = crc::Crc::<u32>::new(&crc::CRC_32_BZIP2);
-        let mut digest = crc_bzip2.digest();
-        for i in 0..size_words {
-            unsafe {
-                let d = spiflash_ptr.offset(spiflash_offset_words + i).read_volatile();
-                if i != (size_words - 1) {
-                    digest.update(&d.to_le_bytes());
-                } else {
-                    digest.update(&d.to_le_bytes()[0..(region.size as usize)%4usize]);
-                }
-            }
-        }
-        let crc_result = digest.finalize();
+        let crc_result = unsafe {
+            crc32_bzip2_region((SPIFLASH_BASE + spiflash_src as usize) as *const u32,
+                               region.size as usize)
+        };
         info!("got SPI flash crc: {:#x}, manifest wants: {:#x}", crc_result, crc_target);
         if crc_result != crc_target {
             return Err(BitstreamError::SpiflashCrcError);
@@ -332,7 +707,20 @@ fn validate_and_copy_spiflash_region(region: &MemoryRegion) -> Result<(), Bitstr
         return Err(BitstreamError::InvalidManifest);
     }
 
-    // Now copy to PSRAM if needed
+    Ok(())
+}
+
+fn copy_spiflash_region(region: &MemoryRegion) -> Result<(), BitstreamError> {
+    // Skip regions without spiflash_src (e.g. during simulation)
+    let Some(spiflash_src) = region.spiflash_src else {
+        return Ok(());
+    };
+
+    let spiflash_ptr = SPIFLASH_BASE as *mut u32;
+    let spiflash_offset_words = spiflash_src as isize / 4isize;
+    // Whole words, as checked against the PSRAM bounds by `validate`.
+    let size_words = (region.size as isize + 3) / 4isize;
+
     if region.region_type == RegionType::RamLoad {
         if let Some(psram_dst) = region.psram_dst {
             let psram_ptr = PSRAM_BASE as *mut u32;
@@ -347,6 +735,19 @@ fn validate_and_copy_spiflash_region(region: &MemoryRegion) -> Result<(), Bitstr
                 }
             }
             info!("Copy completed ({} KiB)", (size_words*4) / 1024);
+            // Catch any corruption on the way into PSRAM, which the flash CRC can't.
+            if let Some(crc_target) = region.crc {
+                // Read back from PSRAM itself, not the copy in the dcache.
+                pac::cpu::vexriscv::flush_dcache();
+                let crc_result = unsafe {
+                    crc32_bzip2_region((PSRAM_BASE + psram_dst as usize) as *const u32,
+                                       region.size as usize)
+                };
+                info!("got PSRAM crc: {:#x}, manifest wants: {:#x}", crc_result, crc_target);
+                if crc_result != crc_target {
+                    return Err(BitstreamError::PsramCrcError);
+                }
+            }
         } else {
             warn!("RamLoad region'{}' without psram_dst! marking invalid...", region.filename);
             return Err(BitstreamError::InvalidManifest);
@@ -356,6 +757,165 @@ fn validate_and_copy_spiflash_region(region: &MemoryRegion) -> Result<(), Bitstr
     Ok(())
 }
 
+fn send_byte(serial: &mut Serial0, byte: u8) {
+    use hal::hal_nb::serial::Write;
+    hal::nb::block!(serial.write(byte)).ok();
+}
+
+fn send_cancel(serial: &mut Serial0) {
+    use tiliqua_lib::xmodem::CAN;
+    send_byte(serial, CAN);
+    send_byte(serial, CAN);
+}
+
+// Receive a slot image with XMODEM and program it to user slot `slot`. Each
+// block is written to flash before it is acknowledged. The slot is only
+// touched once the first block arrives. The sender must see nothing but
+// protocol bytes, so logging is switched off for the transfer. The bootloader
+// runs from the same flash, which the HAL erases and programs from RAM.
+fn recovery_receive(display: &mut DMAFramebuffer0, timer: &mut Timer0, slot: usize,
+                    name: &OptionString) -> Result<u32, &'static str> {
+    use tiliqua_lib::xmodem::*;
+    use tiliqua_lib::slotwriter::{SlotWriter, SlotWriteError};
+    use tiliqua_hal::spiflash::{VerifiedFlash, VerifyError};
+    use hal::hal_nb::serial::Read;
+
+    // Polls between bytes, ~1 sec without any means the sender stalled.
+    const POLL_US: u32 = 10;
+    const IDLE_POLLS: u32 = 1_000_000 / POLL_US;
+    // Time to start the sender before giving up, then retries per block.
+    const START_RETRIES: u32 = 60;
+    const BLOCK_RETRIES: u32 = 10;
+
+    let mut serial = unsafe { Serial0::summon() };
+    let mut writer: Option<SlotWriter<VerifiedFlash<SPIFlash0>>> = None;
+    let mut rx = XmodemReceiver::new();
+    let mut idle = 0u32;
+    let mut retries = 0u32;
+
+    unsafe { log::set_max_level_racy(log::LevelFilter::Off) };
+    send_byte(&mut serial, CRC_MODE);
+    let result = loop {
+        let Ok(byte) = serial.read() else {
+            timer.delay_us(POLL_US);
+            idle += 1;
+            if idle > IDLE_POLLS {
+                idle = 0;
+                retries += 1;
+                rx.timeout();
+                if rx.n_blocks() == 0 {
+                    if retries > START_RETRIES {
+                        break Err("timeout waiting for sender");
+                    }
+                    send_byte(&mut serial, CRC_MODE);
+                } else {
+                    if retries > BLOCK_RETRIES {
+                        send_cancel(&mut serial);
+                        break Err("timeout during transfer");
+                    }
+                    send_byte(&mut serial, NAK);
+                }
+            }
+            continue;
+        };
+        idle = 0;
+        match rx.feed(byte) {
+            None => {},
+            Some(XmodemEvent::Block) => {
+                retries = 0;
+                if writer.is_none() {
+                    let spiflash = SPIFlash0::new(unsafe { pac::SPIFLASH_CTRL::steal() },
+                                                  SPIFLASH_BASE, SPIFLASH_SZ_BYTES);
+                    match SlotWriter::new(VerifiedFlash::new(spiflash), slot) {
+                        Ok(w) => writer = Some(w),
+                        Err(_) => {
+                            send_cancel(&mut serial);
+                            break Err("flash erase failed");
+                        }
+                    }
+                }
+                let w = writer.as_mut().unwrap();
+                match w.write(rx.block()) {
+                    Ok(()) => {},
+                    Err(SlotWriteError::Flash(VerifyError::Mismatch(_))) => {
+                        send_cancel(&mut serial);
+                        break Err("flash verify failed");
+                    },
+                    Err(_) => {
+                        send_cancel(&mut serial);
+                        break Err("flash write failed (image too large?)");
+                    },
+                }
+                let mut status: String<64> = String::new();
+                write!(status, "received {} KiB", w.written() / 1024).ok();
+                print_recovery(display, slot, name, &status);
+                send_byte(&mut serial, ACK);
+            },
+            Some(XmodemEvent::Repeat) => send_byte(&mut serial, ACK),
+            Some(XmodemEvent::Corrupt) => {
+                retries += 1;
+                if retries > BLOCK_RETRIES {
+                    send_cancel(&mut serial);
+                    break Err("too many corrupt blocks");
+                }
+                send_byte(&mut serial, NAK);
+            },
+            Some(XmodemEvent::OutOfSequence) => {
+                send_cancel(&mut serial);
+                break Err("blocks out of sequence");
+            },
+            Some(XmodemEvent::End) => {
+                send_byte(&mut serial, ACK);
+                break Ok(writer.as_ref().map_or(0, |w| w.written()));
+            },
+            Some(XmodemEvent::Cancelled) => break Err("cancelled by sender"),
+        }
+    };
+    unsafe { log::set_max_level_racy(log::LevelFilter::Trace) };
+    result
+}
+
+// Recovery mode, entered by holding the encoder at power on. Lets the user
+// reprogram any user slot over the UART, for example to replace a bitstream
+// that hangs on boot. Only user slots can be chosen, so the bootloader can't
+// overwrite itself. Power cycle to leave.
+fn recovery_mode(display: &mut DMAFramebuffer0, timer: &mut Timer0,
+                 names: &[OptionString; N_MANIFESTS]) -> ! {
+    use hal::encoder::{Encoder, PressKind};
+
+    info!("recovery: entered recovery mode");
+    let encoder_registers = unsafe { pac::ENCODER0::steal() };
+    // Don't take releasing the entry gesture as a press.
+    while encoder_registers.button().read().bits() != 0 {
+        print_recovery(display, 0, &names[0], "release encoder");
+        timer.delay_ms(10);
+    }
+    let mut encoder = Encoder0::new(encoder_registers);
+    let mut slot = 0usize;
+    let mut status: String<64> = String::new();
+    loop {
+        encoder.update();
+        let ticks = encoder.poke_ticks() as i32;
+        slot = (slot as i32 + ticks).rem_euclid(N_MANIFESTS as i32) as usize;
+        if encoder.poke_press() == Some(PressKind::Short) {
+            info!("recovery: waiting for XMODEM transfer to slot {}", slot);
+            print_recovery(display, slot, &names[slot], "start XMODEM-1K (CRC) send now");
+            status.clear();
+            match recovery_receive(display, timer, slot, &names[slot]) {
+                Ok(n_bytes) => {
+                    info!("recovery: wrote {} bytes to slot {}", n_bytes, slot);
+                    write!(status, "slot {} done ({} KiB), power cycle to boot", slot, n_bytes / 1024).ok();
+                },
+                Err(e) => {
+                    warn!("recovery: slot {} failed: {}", slot, e);
+                    write!(status, "slot {} FAILED: {}", slot, e).ok();
+                }
+            }
+        }
+        print_recovery(display, slot, &names[slot], &status);
+        timer.delay_ms(10);
+    }
+}
 
 fn timer0_handler(app: &Mutex<RefCell<App>>) {
 
@@ -369,6 +929,14 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
 
         if !app.startup_animation() {
             app.ui.update();
+            app.update_test_pattern();
+        }
+
+        // Always mute the CODEC to stop pops on flashing while in the bootloader.
+        // Ramping the outputs down first avoids a click on the way in.
+        app.ui.pmod.mute_ramp(true, MUTE_RAMP_STEP);
+        if app.ui.pmod.is_mute_ramp_done() {
+            app.ui.pmod.mute(true);
         }
 
         // Handle autoboot countdown
@@ -377,8 +945,7 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
                 // Encoder was touched during countdown, cancel autoboot, clear flag for next boot.
                 app.autoboot_slot = None;
                 app.autoboot_countdown_ms = 0;
-                let config = EepromConfig { last_boot_slot: None };
-                app.eeprom_manager.write_config(&config).ok();
+                app.eeprom_manager.update_config(|c| c.last_boot_slot = None).ok();
             } else if app.autoboot_countdown_ms > 0 {
                 // Autoboot is configured, continue countdown
                 app.autoboot_countdown_ms = app.autoboot_countdown_ms.saturating_sub(TIMER0_ISR_PERIOD_MS);
@@ -388,8 +955,17 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
             }
         }
 
-        if app.ui.opts.tracker.modify {
-            if let Some(n) = app.ui.opts.tracker.selected {
+        // Persist changes to the autoboot delay for the next cold boot.
+        let autoboot_delay = app.ui.opts.misc.autoboot.value;
+        if autoboot_delay != app.autoboot_delay {
+            app.autoboot_delay = autoboot_delay;
+            app.eeprom_manager.update_config(|c| c.autoboot_delay_ms = autoboot_delay.ms()).ok();
+        }
+
+        app.update_slot_order();
+
+        if app.ui.opts.tracker.modify && app.ui.opts.tracker.page.value == Page::Boot {
+            if let Some(n) = app.ui.opts.tracker.selected.and_then(|row| app.slot_rows[row]) {
                 app.reboot_n = Some(n)
             }
         }
@@ -397,7 +973,7 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
         if let Some(n) = app.reboot_n {
             app.time_since_reboot_requested += TIMER0_ISR_PERIOD_MS;
             // Give codec time to mute and display time to draw 'REBOOTING'
-            if app.time_since_reboot_requested > 250 {
+            if app.time_since_reboot_requested > 250 && app.ui.pmod.is_mute_ramp_done() {
                 // Is there a firmware image to copy to PSRAM before we switch bitstreams?
                 let error = if let Some(manifest) = &app.manifests[n].clone() {
                     || -> Result<(), BitstreamError> {
@@ -407,13 +983,30 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
                         if manifest.hw_rev != HW_REV_MAJOR {
                             Err(BitstreamError::HwVersionMismatch)?;
                         }
+                        if manifest.validate().is_err() {
+                            Err(BitstreamError::InvalidRegions)?;
+                        }
+                        if manifest.check_compat(&app.features).is_err() {
+                            Err(BitstreamError::Incompatible)?;
+                        }
                         // BootInfo structure placed at the end of PSRAM
                         let mut bootinfo = bootinfo::BootInfo {
                             manifest: manifest.clone(),
                             modeline: app.modeline.clone(),
+                            last_panic: None,
+                            clocks: None,
                         };
+                        // CRCs are only recomputed if this slot changed since it last passed.
+                        let force = app.ui.opts.misc.crc_check.value == CrcCheck::Always;
+                        let validated = app.eeprom_manager.validate_cached(
+                            &crc_cache_entry(n, manifest), force, || {
+                                manifest.regions.iter().try_for_each(validate_spiflash_region)
+                            })?;
+                        if !validated {
+                            info!("Slot {} unchanged since last validation, skip CRC checks.", n);
+                        }
                         for region in &manifest.regions {
-                            validate_and_copy_spiflash_region(region)?;
+                            copy_spiflash_region(region)?;
                         }
 
                         // Save this bitstream as the last_boot_slot for future autoboot.
@@ -421,8 +1014,7 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
                         // cause a BitstreamError, however the PLL reconfiguration below
                         // can cause the CODEC to go into a state where it NAKs I2C transactions,
                         // causing I2C writes to fail.
-                        let config = EepromConfig { last_boot_slot: Some(n as u8) };
-                        app.eeprom_manager.write_config(&config).ok();
+                        app.eeprom_manager.update_config(|c| c.last_boot_slot = Some(n as u8)).ok();
 
 
                         // If required, reconfigure the external PLL to what the bitstream wants.
@@ -432,7 +1024,7 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
                         if let Some(mut pll_config) = manifest.external_pll_config.clone() {
                             if pll_config.clk1_inherit {
                                 info!("video/pll: inherit pixel clock from bootloader modeline.");
-                                pll_config.clk1_hz = Some((bootinfo.modeline.pixel_clk_mhz*1e6f32) as u32);
+                                pll_config.clk1_hz = Some(bootinfo.modeline.pixel_clk_hz());
                                 bootinfo.manifest.external_pll_config = Some(pll_config.clone());
                                 if FIXED_MODELINE.is_some() {
                                     // Can't boot a dynamic modeline bitstream if the bootloader
@@ -451,15 +1043,19 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
                                     w.enable().bit(false)
                                 );
                                 riscv::asm::delay(10_000_000);
-                                configure_external_pll(&pll_config, pll).or(
-                                    Err(BitstreamError::PllI2cError))?;
+                                bootinfo.clocks = Some(configure_external_pll(&pll_config, pll, PLL_MAX_ERROR_PPM).or(
+                                    Err(BitstreamError::PllI2cError))?);
                             } else {
                                 // External PLL config is in manifest but this bootloader
                                 // didn't set up the PLL (likely hardware / gateware mismatch).
                                 Err(BitstreamError::PllBadConfigError)?;
                             }
                         }
-                        // Place BootInfo at the end of PSRAM
+                        // Place BootInfo at the end of PSRAM, handing on (and clearing)
+                        // any panic breadcrumb, so it is only reported once.
+                        bootinfo.last_panic = unsafe {
+                            bootinfo::PanicInfoLite::take_from_addr(BOOTINFO_BASE + bootinfo::PANIC_OFFSET)
+                        };
                         unsafe { bootinfo.to_addr(BOOTINFO_BASE).expect("Failed to serialize BootInfo") };
                         riscv::asm::fence();
                         riscv::asm::fence_i();
@@ -473,12 +1069,15 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
                     app.ui.opts.tracker.modify = false;
                     app.reboot_n = None;
                     app.time_since_reboot_requested = 0;
-                    app.error_n[n] = Some(String::from_str(bitstream_error.into()).unwrap());
+                    // Incompatible or invalid slots already say what is wrong.
+                    if !matches!(bitstream_error, BitstreamError::Incompatible |
+                                                  BitstreamError::InvalidRegions) {
+                        app.error_n[n] = Some(String::from_str(bitstream_error.into()).unwrap());
+                    }
                     info!("Failed to load bitstream: {:?}", app.error_n[n]);
                     // Clear the autoboot flag, as it's possible an error occurred after
                     // the autoboot flag was set (during/after PLL reconfiguration).
-                    let config = EepromConfig { last_boot_slot: None };
-                    app.eeprom_manager.write_config(&config).ok();
+                    app.eeprom_manager.update_config(|c| c.last_boot_slot = None).ok();
                 } else {
                     // Ask RP2040 to replay the JTAG command sequence to reconfigure the ECP5
                     // to new bitstream. The number is corresponding to SPI flash addresses
@@ -580,6 +1179,11 @@ fn read_edid(i2cdev: &mut I2c0) -> Result<edid::Edid, edid::EdidError> {
     // Some screens fail on the first read and require a delay and re-attempted
     // read for the valid EDID to actually become available.
     const EDID_READ_ATTEMPTS: usize = 3;
+    // A monitor interrupted mid-read (e.g. by a reset) may still be holding
+    // SDA low, and would NAK (or corrupt) everything after it.
+    if i2cdev.recover_bus().is_err() {
+        warn!("video/edid: i2c0 SDA still held low after bus recovery");
+    }
     let mut read_attempts = 0;
     loop {
         info!("video/edid: read_edid from i2c0 address 0x{:x}", EDID_ADDR);
@@ -594,7 +1198,9 @@ fn read_edid(i2cdev: &mut I2c0) -> Result<edid::Edid, edid::EdidError> {
         info!("video/edid: (attempt {}) read_edid got {:?}", read_attempts, edid);
         match edid {
             Ok(edid_parsed) => return Ok(edid_parsed),
-            Err(error) => {
+            // Header or checksum mismatch is most likely a bad read.
+            Err(error @ (edid::EdidError::InvalidHeaderPattern |
+                         edid::EdidError::InvalidChecksum)) => {
                 read_attempts += 1;
                 if read_attempts == (EDID_READ_ATTEMPTS+1) {
                     return Err(error)
@@ -605,74 +1211,128 @@ fn read_edid(i2cdev: &mut I2c0) -> Result<edid::Edid, edid::EdidError> {
     }
 }
 
-fn modeline_from_edid(edid: edid::Edid) -> Option<DVIModeline> {
+// Preferred EDID timing that we could not use, and the reason why.
+type RejectedTiming = (edid::DetailedTimingDescriptor, edid::TimingRejection);
+
+fn modeline_from_edid(edid: edid::Edid) -> (Option<DVIModeline>, Option<RejectedTiming>) {
 
     // Read the EDID contents and see if we can use it to dynamically create a
-    // sensible modeline. If we can't fine a reasonable descriptor, we return
-    // None and assumably the caller falls back to some default modeline.
+    // sensible modeline. If no descriptor is usable as-is, we fall back to the
+    // closest mode to the preferred timing that we can generate. Only if that
+    // fails too do we return None and let the caller use a default modeline.
+    //
+    // The first descriptor we had to skip (usually the display's preferred
+    // timing) is also returned, so the user can be told why they are not
+    // getting the resolution they might expect.
 
     info!("video/edid: valid edid. scanning detailed timing descriptors...");
-    for descriptor in edid.descriptors.iter() {
+    let rotate = if edid.header.product_code == 0x3132 || edid.header.product_code == 0xAA61 {
+        info!("video/edid: detected tiliqua screen! rotate framebuffer 90 degrees.");
+        Rotate::Left
+    } else {
+        Rotate::Normal
+    };
+    let mut rejected: Option<RejectedTiming> = None;
+    for (n, descriptor) in edid.descriptors.iter().enumerate() {
         if let edid::Descriptor::DetailedTiming(desc) = descriptor {
-            info!("video/edid: checking detailed timing descriptor, contents: {:?}", descriptor);
-            if desc.pixel_clock_khz < PIXEL_CLK_MIN_KHZ || desc.pixel_clock_khz > PIXEL_CLK_MAX_KHZ {
-                warn!("video/edid: skip descriptor (out-of-range pixel clock)");
-                continue;
-            }
-            if desc.features.interlaced {
-                warn!("video/edid: skip descriptor (interlaced timings)");
-                continue;
-            }
-            if let edid::SyncType::DigitalSeparate { vsync_positive, hsync_positive } = desc.features.sync_type {
-                let mut rotate = Rotate::Normal;
-                if edid.header.product_code == 0x3132 || edid.header.product_code == 0xAA61 {
-                    info!("video/edid: detected tiliqua screen! rotate framebuffer 90 degrees.");
-                    rotate = Rotate::Left;
+            info!("video/edid: checking detailed timing descriptor {}, contents: {:?}", n, descriptor);
+            if let Err(reason) = desc.check_supported(PIXEL_CLK_MIN_KHZ, PIXEL_CLK_MAX_KHZ) {
+                let reason_str: &'static str = reason.into();
+                warn!("video/edid: skip descriptor {} ({}x{} @ {}kHz: {})", n,
+                      desc.horizontal_active, desc.vertical_active,
+                      desc.pixel_clock_khz, reason_str);
+                if rejected.is_none() {
+                    rejected = Some((*desc, reason));
                 }
-                let modeline = DVIModeline {
-                    h_active      : desc.horizontal_active,
-                    h_sync_start  : desc.horizontal_active +
-                                    desc.horizontal_sync_offset,
-                    h_sync_end    : desc.horizontal_active +
-                                    desc.horizontal_sync_offset +
-                                    desc.horizontal_sync_pulse_width,
-                    h_total       : desc.horizontal_active +
-                                    desc.horizontal_blanking,
-                    h_sync_invert : !hsync_positive,
-                    v_active      : desc.vertical_active,
-                    v_sync_start  : desc.vertical_active +
-                                    desc.vertical_sync_offset,
-                    v_sync_end    : desc.vertical_active +
-                                    desc.vertical_sync_offset +
-                                    desc.vertical_sync_pulse_width,
-                    v_total       : desc.vertical_active +
-                                    desc.vertical_blanking,
-                    v_sync_invert : !vsync_positive,
-                    pixel_clk_mhz : (desc.pixel_clock_khz as f32) / 1e3f32,
-                    rotate
-                };
-                info!("video/edid: found useable modeline, returning: {:?}", modeline);
-                return Some(modeline)
-            } else {
-                warn!("video/edid: skip descriptor (unknown sync format)");
                 continue;
             }
+            if let Some(mut modeline) = desc.to_modeline() {
+                modeline.rotate = rotate;
+                info!("video/edid: using descriptor {}, modeline: {:?}", n, modeline);
+                return (Some(modeline), rejected)
+            }
         }
     }
-    None
+    if let Some(mut modeline) = edid.clamp_to_supported(PIXEL_CLK_MIN_KHZ, PIXEL_CLK_MAX_KHZ) {
+        modeline.rotate = rotate;
+        warn!("video/edid: no usable descriptor, using closest supported mode {}x{} @ {:.1}Hz: {:?}",
+              modeline.h_active, modeline.v_active, modeline.refresh_rate(), modeline);
+        return (Some(modeline), rejected)
+    }
+    warn!("video/edid: no usable descriptor or fallback mode.");
+    (None, rejected)
 }
 
-fn modeline_or_fallback(i2cdev: &mut I2c0) -> DVIModeline {
+// Infer a modeline, along with an on-screen warning if the display's
+// preferred timing had to be rejected, and which display it came from.
+fn modeline_or_fallback(i2cdev: &mut I2c0) -> (DVIModeline, Option<String<80>>, Option<DisplayId>) {
     if FIXED_MODELINE.is_none() {
         match read_edid(i2cdev) {
-            Ok(edid) => match modeline_from_edid(edid) {
-                Some(edid_modeline) => edid_modeline,
-                _ => DVIModeline::default()
+            Ok(edid) => {
+                let display_id = edid.header.display_id();
+                let (maybe_modeline, rejected) = modeline_from_edid(edid);
+                let warning = rejected.map(|(desc, reason)| {
+                    let mut s: String<80> = String::new();
+                    let reason_str: &'static str = reason.into();
+                    write!(s, "video/edid: rejected {}x{} @ {}kHz ({})",
+                           desc.horizontal_active, desc.vertical_active,
+                           desc.pixel_clock_khz, reason_str).ok();
+                    s
+                });
+                (maybe_modeline.unwrap_or_default(), warning, Some(display_id))
             }
-            _ => DVIModeline::default()
+            _ => (DVIModeline::default(), None, None)
         }
     } else {
-        DVIModeline::default().maybe_override_fixed(FIXED_MODELINE, CLOCK_DVI_HZ)
+        (DVIModeline::default().maybe_override_fixed(FIXED_MODELINE, CLOCK_DVI_HZ), None, None)
+    }
+}
+
+fn modeline_supported(modeline: &DVIModeline) -> bool {
+    modeline.is_consistent() &&
+    (PIXEL_CLK_MIN_KHZ..=PIXEL_CLK_MAX_KHZ).contains(&modeline.pixel_clk_khz())
+}
+
+// A modeline saved from the video page takes priority over the EDID, but
+// only for the same display it was saved with.
+fn custom_modeline(eeprom_manager: &mut EepromManager<I2c1>, display_id: Option<DisplayId>)
+    -> Option<DVIModeline> {
+    let stored = eeprom_manager.read_modeline().ok()?;
+    if Some(stored.display) == display_id && modeline_supported(&stored.modeline) {
+        info!("video/eeprom: using custom modeline {:?}", stored.modeline);
+        Some(stored.modeline)
+    } else {
+        None
+    }
+}
+
+// Reprogram the pixel clock and reinitialize the display with a new modeline.
+// Without an external PLL the pixel clock can't change, so nothing is touched.
+fn switch_modeline(app: &mut App, display: &mut DMAFramebuffer0, new_modeline: DVIModeline) -> bool {
+    if let Some(ref mut external_pll) = app.pll {
+        // Hold DVI PHY in reset before touching the video PLL
+        unsafe { pac::FRAMEBUFFER_PERIPH::steal() }.flags().write(|w|
+            w.enable().bit(false)
+        );
+        // Configure new pixel clock. Only the clk1 divider changes, so the
+        // audio clock (clk0) keeps running.
+        retune_pixel_clock(external_pll, new_modeline.pixel_clk_hz(), PLL_MAX_ERROR_PPM).unwrap();
+        // Finally, reinitialize the display.
+        let peripherals = unsafe { pac::Peripherals::steal() };
+        *display = DMAFramebuffer0::new(
+            peripherals.FRAMEBUFFER_PERIPH,
+            peripherals.PALETTE_PERIPH,
+            peripherals.BLIT,
+            peripherals.PIXEL_PLOT,
+            peripherals.LINE,
+            PSRAM_FB_BASE,
+            new_modeline.clone(),
+            BLIT_MEM_BASE,
+        );
+        app.modeline = new_modeline;
+        true
+    } else {
+        false
     }
 }
 
@@ -707,28 +1367,44 @@ fn main() -> ! {
     let cold_boot = unsafe { bootinfo::BootInfo::from_addr(BOOTINFO_BASE) }.is_none();
     info!("cold_boot: {}", cold_boot);
 
+    // A bitstream that panicked leaves a breadcrumb behind before halting. PSRAM
+    // is garbage after a cold boot, so only look for one on warm boots.
+
+    if !cold_boot {
+        if let Some(p) = unsafe { bootinfo::PanicInfoLite::from_addr(BOOTINFO_BASE + bootinfo::PANIC_OFFSET) } {
+            warn!("last bitstream: {}", p);
+            write!(startup_report, "{}\r\n", p).ok();
+        }
+    }
+
+    // Holding the encoder at power on enters recovery mode. Only on cold boots,
+    // as the encoder is also held to return to the bootloader from a bitstream.
+
+    let recovery = cold_boot && unsafe { pac::ENCODER0::steal() }.button().read().bits() != 0;
+
     // Read autoboot flag and maybe configure us to start an autoboot countdown by
     // setting 'autoboot_to'.
 
     let mut autoboot_to: Option<usize> = None;
-    let mut eeprom_manager = EepromManager::new(unsafe{I2c1::summon()});
+    // The config is written on every boot, so spread it over a ring of slots.
+    let mut eeprom_manager = EepromManager::new_ring(unsafe{I2c1::summon()},
+        EEPROM_CONFIG_RING_ADDR, EEPROM_CONFIG_RING_SLOTS);
+    let config = match eeprom_manager.read_config() {
+        Ok(config) => {
+            log::info!("EepromConfig.read_config() wants: {:?}", config);
+            config
+        },
+        Err(e) => {
+            log::warn!("EepromConfig.read_config() failed: {:?}", e);
+            EepromConfig::default()
+        }
+    };
     if !cold_boot {
         // Warm boot: Clear the autoboot flag.
-        let config = EepromConfig { last_boot_slot: None };
-        eeprom_manager.write_config(&config).ok();
-    } else {
+        eeprom_manager.update_config(|c| c.last_boot_slot = None).ok();
+    } else if let (false, Some(slot)) = (recovery, config.last_boot_slot) {
         // Cold boot: Check the autoboot flag and boot
-        match eeprom_manager.read_config() {
-            Ok(config) => {
-                log::info!("EepromConfig.read_config() wants: {:?}", config);
-                if let Some(slot) = config.last_boot_slot {
-                    autoboot_to = Some(slot as usize);
-                }
-            },
-            Err(e) => {
-                log::warn!("EepromConfig.read_config() failed: {:?}", e);
-            }
-        }
+        autoboot_to = Some(slot as usize);
     }
 
     // Verify/reprogram touch sensing NVM
@@ -765,7 +1441,10 @@ fn main() -> ! {
 
     timer.delay_ms(10);
     let mut i2cdev_edid = I2c0::new(unsafe { pac::I2C0::steal() } );
-    let mut modeline = modeline_or_fallback(&mut i2cdev_edid);
+    let (mut modeline, mut edid_warning, display_id) = modeline_or_fallback(&mut i2cdev_edid);
+    if let Some(custom) = custom_modeline(&mut eeprom_manager, display_id) {
+        modeline = custom;
+    }
 
     // Setup audio clocks on external PLL
 
@@ -774,10 +1453,10 @@ fn main() -> ! {
         let mut si5351drv = Si5351Device::new_adafruit_module(i2cdev_mobo_pll);
         configure_external_pll(&ExternalPLLConfig{
             clk0_hz: CLOCK_AUDIO_HZ,
-            clk1_hz: Some((modeline.pixel_clk_mhz*1e6) as u32),
+            clk1_hz: Some(modeline.pixel_clk_hz()),
             clk1_inherit: false,
             spread_spectrum: Some(0.01),
-        }, &mut si5351drv).unwrap();
+        }, &mut si5351drv, PLL_MAX_ERROR_PPM).unwrap();
         Some(si5351drv)
     } else {
         None
@@ -797,11 +1476,14 @@ fn main() -> ! {
     let mut manifests: [Option<BitstreamManifest>; 8] = [const { None }; 8];
     for n in 0usize..N_MANIFESTS {
         let size: usize = MANIFEST_SIZE;
-        let addr: usize = SPIFLASH_BASE + MANIFEST_OFFSET + (n+1)*SLOT_SIZE;
+        let addr: usize = manifest_addr(n);
         info!("(entry {}) look for manifest from {:#x} to {:#x}", n, addr, addr+size);
         manifests[n] = BitstreamManifest::from_addr(addr, size);
     }
 
+    // Cached, only rescanned when returning to the boot page.
+    let mut options_saved_n = manifests.each_ref().map(options_saved);
+
     let mut opts = Opts::default();
 
     // Populate option string values with bitstream names from manifest.
@@ -812,21 +1494,24 @@ fn main() -> ! {
             names[n] = manifest.name.clone();
         }
     }
-    opts.boot.slot0.value = names[0].clone();
-    opts.boot.slot1.value = names[1].clone();
-    opts.boot.slot2.value = names[2].clone();
-    opts.boot.slot3.value = names[3].clone();
-    opts.boot.slot4.value = names[4].clone();
-    opts.boot.slot5.value = names[5].clone();
-    opts.boot.slot6.value = names[6].clone();
-    opts.boot.slot7.value = names[7].clone();
-    opts.tracker.selected = Some(0); // Don't start with page highlighted.
-    if let Some(n) = autoboot_to {
-        opts.tracker.selected = Some(n);
+    // Boot page rows are filled in by `App::apply_slot_order`.
+    opts.misc.autoboot.value = AutobootDelay::from_ms(config.autoboot_delay_ms);
+    if config.slot_order.is_hidden(0) {
+        opts.slots.show.value = SlotVisibility::Hidden;
     }
+    opts.tracker.selected = Some(0); // Don't start with page highlighted.
 
     let app = Mutex::new(RefCell::new(
-            App::new(opts, manifests.clone(), maybe_external_pll, modeline.clone(), autoboot_to, eeprom_manager)));
+            App::new(opts, manifests.clone(), names.clone(), config.slot_order.clone(),
+                     maybe_external_pll, modeline.clone(), display_id, autoboot_to, eeprom_manager)));
+    critical_section::with(|cs| {
+        let mut app = app.borrow_ref_mut(cs);
+        app.write_video_opts();
+        // Start on the autoboot slot, unless it is hidden.
+        if let Some(row) = autoboot_to.and_then(|n| app.slot_row(n)) {
+            app.ui.opts.tracker.selected = Some(row);
+        }
+    });
 
     // Until this point, the video gateware is held in reset. Now that we have a target modeline
     // and the external PLL is appropriately configured, we can bring it up.
@@ -842,6 +1527,13 @@ fn main() -> ! {
         BLIT_MEM_BASE,
     );
 
+    if recovery {
+        persist.set_persistence(64);
+        palette::ColorPalette::default().write_to_hardware(&mut display);
+        pmod.mute(true);
+        recovery_mode(&mut display, &mut timer, &names);
+    }
+
     handler!(timer0 = || timer0_handler(&app));
 
     irq::scope(|s| {
@@ -859,80 +1551,104 @@ fn main() -> ! {
         palette::ColorPalette::default().write_to_hardware(&mut display);
 
         log::info!("{}", startup_report);
+        if let Some(ref w) = edid_warning {
+            log::warn!("{}", w);
+        }
 
         s.register(handlers::Interrupt::TIMER0, timer0);
         timer.enable_tick_isr(TIMER0_ISR_PERIOD_MS,
                               pac::Interrupt::TIMER0);
 
 
-        let mut last_hpd = display.get_hpd();
+        let mut hpd = VideoHpd::new(display.get_hpd(), timer.uptime_ms());
+        let mut last_page = Page::Boot;
 
         loop {
 
             let h_active = display.size().width;
             let v_active = display.size().height;
 
-            // Always mute the CODEC to stop pops on flashing while in the bootloader.
-            pmod.mute(true);
-
-            let (opts, reboot_n, error_n, final_modeline, autoboot_countdown_ms) = critical_section::with(|cs| {
+            let (opts, reboot_n, error_n, final_modeline, autoboot_countdown_ms, (edit_modeline, video_status),
+                 test_pattern, (slot_order, slot_rows)) = critical_section::with(|cs| {
 
                 let mut app = app.borrow_ref_mut(cs);
 
                 //
                 // Dynamic modeline switching.
-                // Rising edge hotplug checks EDID, reprograms PLL and reinitializes display.
+                // Hotplug (debounced, so a flaky connector doesn't reprogram the PLL
+                // on every bounce) checks EDID, reprograms PLL and reinitializes display.
                 //
 
-                if display.get_hpd() && !last_hpd {
-                    // Rising edge of DVI HPD
+                if hpd.poll(display.get_hpd(), timer.uptime_ms()) == Some(HpdEvent::Connected) {
                     info!("video/hpd: display reconnected!");
-                    let new_modeline = modeline_or_fallback(&mut i2cdev_edid);
+                    let (mut new_modeline, new_edid_warning, new_display_id) =
+                        modeline_or_fallback(&mut i2cdev_edid);
+                    if let Some(custom) = custom_modeline(&mut app.eeprom_manager, new_display_id) {
+                        new_modeline = custom;
+                    }
+                    edid_warning = new_edid_warning;
+                    app.display_id = new_display_id;
                     info!("video/hpd: modeline was {:?}", modeline);
                     info!("video/hpd: modeline infer {:?}", new_modeline);
-                    let mut reprogrammed_pll = false;
                     if new_modeline != modeline {
                         info!("video/hpd: display inferred different modeline to previous. switching timings...");
-                        if let Some(ref mut external_pll) = app.pll {
-                            // Hold DVI PHY in reset before touching the video PLL
-                            unsafe { pac::FRAMEBUFFER_PERIPH::steal() }.flags().write(|w|
-                                w.enable().bit(false)
-                            );
-                            // Configure new pixel clock. Technically we don't need to touch
-                            // the audio clock. This might be important to separate if we decide
-                            // to support dynamic hotplug timings in user bitstreams where
-                            // we want the audio streams to not be interrupted.
-                            configure_external_pll(&ExternalPLLConfig{
-                                clk0_hz: CLOCK_AUDIO_HZ,
-                                clk1_hz: Some((new_modeline.pixel_clk_mhz*1e6) as u32),
-                                clk1_inherit: false,
-                                spread_spectrum: Some(0.01),
-                            }, external_pll).unwrap();
-                            reprogrammed_pll = true;
-                        }
+                        switch_modeline(&mut app, &mut display, new_modeline);
                     } else {
                         info!("video/hpd: display inferred same modeline as previous. do nothing");
                     }
+                    // Edits for the previous display no longer make sense.
+                    app.edit_modeline = app.modeline.clone();
+                    app.write_video_opts();
+                }
 
-                    if reprogrammed_pll {
-                        // Finally, reinitialize the display.
-                        let peripherals = unsafe { pac::Peripherals::steal() };
-                        display = DMAFramebuffer0::new(
-                            peripherals.FRAMEBUFFER_PERIPH,
-                            peripherals.PALETTE_PERIPH,
-                            peripherals.BLIT,
-                            peripherals.PIXEL_PLOT,
-                            peripherals.LINE,
-                            PSRAM_FB_BASE,
-                            new_modeline.clone(),
-                            BLIT_MEM_BASE,
-                        );
-                        app.modeline = new_modeline;
+                //
+                // Modeline editor (video page).
+                //
+
+                let apply = app.ui.opts.video.apply.poll();
+                let save = app.ui.opts.video.save.poll();
+                let revert = app.ui.opts.video.revert.poll();
+                if FIXED_MODELINE.is_some() {
+                    if apply || save {
+                        app.video_status = Some("not supported (static modeline)");
+                    }
+                } else {
+                    app.update_edit_modeline();
+                    if revert {
+                        app.edit_modeline = app.modeline.clone();
+                        app.write_video_opts();
+                        app.video_status = Some("reverted");
+                    }
+                    if (apply || save) && !modeline_supported(&app.edit_modeline) {
+                        app.video_status = Some("rejected (pixel clock out of range)");
+                    } else {
+                        if apply {
+                            let edited = app.edit_modeline.clone();
+                            info!("video/edit: apply {:?}", edited);
+                            app.video_status = if switch_modeline(&mut app, &mut display, edited) {
+                                Some("applied")
+                            } else {
+                                Some("not supported (no external pll)")
+                            };
+                        }
+                        if save {
+                            if let Some(display_id) = app.display_id {
+                                let stored = EepromModeline {
+                                    display: display_id,
+                                    modeline: app.edit_modeline.clone(),
+                                };
+                                info!("video/edit: save {:?}", stored);
+                                app.video_status = match app.eeprom_manager.write_modeline(&stored) {
+                                    Ok(_) => Some("saved for this display"),
+                                    Err(_) => Some("save failed (eeprom)"),
+                                };
+                            } else {
+                                app.video_status = Some("not saved (no edid)");
+                            }
+                        }
                     }
                 }
 
-                last_hpd = display.get_hpd();
-
                 //
                 // Copy out mutable application state
                 //
@@ -941,17 +1657,46 @@ fn main() -> ! {
                  app.reboot_n.clone(),
                  app.error_n.clone(),
                  app.modeline.clone(),
-                 app.autoboot_countdown_ms)
+                 app.autoboot_countdown_ms,
+                 (app.edit_modeline.clone(), app.video_status),
+                 app.test_pattern,
+                 (app.slot_order.clone(), app.slot_rows))
             });
 
             modeline = final_modeline;
 
-            draw::draw_options(&mut display, &opts, 80, v_active/2-50, 0).ok();
-            draw::draw_name(&mut display, h_active/2, v_active-50, 0, UI_NAME, UI_TAG, &modeline).ok();
+            if let Some(pattern) = test_pattern {
+                // Redrawn every loop, as the framebuffer slowly decays.
+                display.draw_test_pattern(pattern);
+                continue;
+            }
 
+            if opts.tracker.page.value == Page::Boot && last_page != Page::Boot {
+                options_saved_n = manifests.each_ref().map(options_saved);
+            }
+            last_page = opts.tracker.page.value;
 
-            if let Some(n) = opts.tracker.selected {
-                draw_summary(&mut display, &manifests[n], &error_n[n], &startup_report, -20, -110, 0);
+            draw::draw_options(&mut display, &opts, 80, v_active/2-50, 0).ok();
+            draw::draw_name(&mut display, h_active/2, v_active-50, 0, UI_NAME, UI_TAG, &modeline, None).ok();
+
+
+            // Other pages have more rows than there are slots.
+            let selected_slot = match opts.tracker.page.value {
+                Page::Boot => opts.tracker.selected.and_then(|row| slot_rows.get(row).copied().flatten()),
+                _ => None,
+            };
+            if opts.tracker.page.value == Page::Video {
+                draw_edit_modeline(&mut display, &edit_modeline, video_status, 0);
+            } else if opts.tracker.page.value == Page::Slots {
+                draw_slot_order(&mut display, &slot_order, &names, opts.slots.slot.value as usize, 0);
+            } else if let (Page::Boot, Some(n)) = (opts.tracker.page.value, selected_slot) {
+                // The EDID warning may change on hotplug, so it is appended
+                // to the (otherwise fixed) startup report on every frame.
+                let mut report = startup_report.clone();
+                if let Some(ref w) = edid_warning {
+                    write!(report, "{}\r\n", w).ok();
+                }
+                draw_summary(&mut display, &manifests[n], options_saved_n[n], &error_n[n], &report, -20, -110, 0);
                 if let Some(ref manifest) = manifests[n] {
                     if let Some(ref help) = manifest.help {
                         draw::draw_tiliqua(&mut display,
@@ -7,8 +7,75 @@ use serde_derive::{Serialize, Deserialize};
 pub enum Page {
     #[default]
     Boot,
+    Slots,
+    Video,
+    Misc,
 }
 
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum SyncPolarity {
+    #[default]
+    Positive,
+    Negative,
+}
+
+/// When to recompute region CRCs before booting a slot.
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum CrcCheck {
+    /// Skip if the slot is unchanged since it last passed.
+    #[default]
+    Cached,
+    Always,
+}
+
+/// Countdown before booting the last used slot on a cold boot.
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+pub enum AutobootDelay {
+    #[strum(serialize = "0s")]
+    Instant,
+    #[strum(serialize = "2s")]
+    Short,
+    #[default]
+    #[strum(serialize = "5s")]
+    Normal,
+    #[strum(serialize = "10s")]
+    Long,
+}
+
+impl AutobootDelay {
+    pub fn ms(self) -> u16 {
+        match self {
+            AutobootDelay::Instant => 0,
+            AutobootDelay::Short   => 2000,
+            AutobootDelay::Normal  => 5000,
+            AutobootDelay::Long    => 10000,
+        }
+    }
+
+    /// Closest delay to a stored value.
+    pub fn from_ms(ms: u16) -> Self {
+        use strum::IntoEnumIterator;
+        AutobootDelay::iter()
+            .min_by_key(|d| d.ms().abs_diff(ms))
+            .unwrap_or_default()
+    }
+}
+
+/// Whether a slot is listed on the boot page.
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum SlotVisibility {
+    #[default]
+    Shown,
+    Hidden,
+}
+
+int_params!(SlotParams<u8>      { step: 1, min: 0, max: 7 });
+int_params!(TimingParams<u16>   { step: 1, min: 1, max: 4095 });
+int_params!(PixelClkParams<u32> { step: 250, min: 1000, max: 400000, format: IntFormat::Scaled { divisor: 1000, precision: 2, suffix: "MHz" } });
+
 #[derive(OptionPage, Clone)]
 pub struct BootOpts {
     #[option]
@@ -29,9 +96,79 @@ pub struct BootOpts {
     pub slot7: StringOption,
 }
 
+impl BootOpts {
+    /// Boot page rows, top to bottom.
+    pub fn rows_mut(&mut self) -> [&mut StringOption; 8] {
+        [&mut self.slot0, &mut self.slot1, &mut self.slot2, &mut self.slot3,
+         &mut self.slot4, &mut self.slot5, &mut self.slot6, &mut self.slot7]
+    }
+}
+
+/// Reorder or hide the slots listed on the boot page. Changes are
+/// applied and saved immediately.
+#[derive(OptionPage, Clone)]
+pub struct SlotsOpts {
+    #[option]
+    pub slot: IntOption<SlotParams>,
+    #[option]
+    pub show: EnumOption<SlotVisibility>,
+    #[option(false)]
+    pub move_up: ActionOption,
+    #[option(false)]
+    pub move_down: ActionOption,
+}
+
+/// Modeline editor. Values are overwritten with the current modeline at
+/// startup, so the defaults here only matter for the option layout.
+#[derive(OptionPage, Clone)]
+pub struct VideoOpts {
+    #[option(1280)]
+    pub h_active: IntOption<TimingParams>,
+    #[option(1390)]
+    pub h_sync_start: IntOption<TimingParams>,
+    #[option(1430)]
+    pub h_sync_end: IntOption<TimingParams>,
+    #[option(1650)]
+    pub h_total: IntOption<TimingParams>,
+    #[option]
+    pub h_sync: EnumOption<SyncPolarity>,
+    #[option(720)]
+    pub v_active: IntOption<TimingParams>,
+    #[option(725)]
+    pub v_sync_start: IntOption<TimingParams>,
+    #[option(730)]
+    pub v_sync_end: IntOption<TimingParams>,
+    #[option(750)]
+    pub v_total: IntOption<TimingParams>,
+    #[option]
+    pub v_sync: EnumOption<SyncPolarity>,
+    #[option(74250)]
+    pub pixel_clk: IntOption<PixelClkParams>,
+    #[option(false)]
+    pub apply: ActionOption,
+    #[option(false)]
+    pub save: ActionOption,
+    #[option(false)]
+    pub revert: ActionOption,
+}
+
+#[derive(OptionPage, Clone)]
+pub struct MiscOpts {
+    #[option]
+    pub crc_check: EnumOption<CrcCheck>,
+    #[option]
+    pub autoboot: EnumOption<AutobootDelay>,
+}
+
 #[derive(Options, Clone)]
 pub struct Opts {
     pub tracker: ScreenTracker<Page>,
     #[page(Page::Boot)]
     pub boot: BootOpts,
+    #[page(Page::Slots)]
+    pub slots: SlotsOpts,
+    #[page(Page::Video)]
+    pub video: VideoOpts,
+    #[page(Page::Misc)]
+    pub misc: MiscOpts,
 }
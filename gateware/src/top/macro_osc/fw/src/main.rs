@@ -6,6 +6,7 @@
 use log::{info, warn};
 use riscv_rt::entry;
 use core::cell::RefCell;
+use core::sync::atomic::{AtomicU32, Ordering};
 use critical_section::Mutex;
 use irq::handler;
 use embedded_alloc::LlffHeap as Heap;
@@ -16,29 +17,64 @@ use tiliqua_pac as pac;
 use tiliqua_hal as hal;
 use tiliqua_fw::*;
 use tiliqua_lib::*;
+use tiliqua_lib::attractor::DeJong;
+use tiliqua_lib::dsp::{DcBlocker, EnvelopeFollower, softclip};
+use tiliqua_lib::heartbeat::die_temperature_celsius;
+use tiliqua_lib::wavetable::{Wavetable, WavetableOsc, note_to_frequency};
 use pac::constants::*;
 use tiliqua_hal::persist::Persist;
+use tiliqua_hal::spiflash::VerifiedFlash;
 use options::*;
 use opts::persistence::*;
 use hal::pca9635::*;
 
 pub const TIMER0_ISR_PERIOD_MS: u32 = 5;
 const BLOCK_SIZE: usize = 128;
+
+// Envelope follower for `intensity_follow`, updated once per rendered block.
+const BLOCK_MS: f32 = BLOCK_SIZE as f32 * 1000.0f32 / 48000.0f32;
+const ENVELOPE_ATTACK_MS: f32 = 10.0f32;
+const ENVELOPE_DECAY_MS: f32 = 250.0f32;
+// Envelope at which the beam reaches the `intensity` set in the menu.
+const ENVELOPE_FULL_INTENSITY: f32 = 0.5f32;
 // PSRAM heap for big audio buffers.
 const HEAP_START: usize = PSRAM_BASE + (PSRAM_SZ_BYTES / 2);
 const HEAP_SIZE: usize = 128*1024;
 
 static HEAP: Heap = Heap::empty();
 
+// The user wavetable window must not overlap the heap.
+const _: () = assert!(PSRAM_BASE + WAVETABLE_PSRAM_OFFSET >= HEAP_START + HEAP_SIZE);
+
+// Times the render loop gave up before the audio FIFO was topped up, and
+// the same over the last second. Written by the timer ISR, read by the
+// draw loop without entering a critical section.
+static UNDERRUNS: AtomicU32 = AtomicU32::new(0);
+static UNDERRUNS_PER_SEC: AtomicU32 = AtomicU32::new(0);
+
 struct App<'a> {
     voice: Voice<'a>,
     patch: Patch,
     modulations: Modulations,
+    attractor: DeJong,
+    // User wavetable (if any) and its oscillator, which replace the
+    // built-in table of the 'wavetbl' engine.
+    wavetable: Option<Wavetable<'static>>,
+    wavetable_osc: WavetableOsc,
+    // Per output (out, aux), ahead of the saturator.
+    dc_blockers: [DcBlocker; 2],
+    // Peak level of (out, aux) as sent to the DACs.
+    envelope: EnvelopeFollower,
     ui: ui::UI<Encoder0, EurorackPmod0, I2c0, Opts>,
+    dtr: pac::DTR0,
+    // `UNDERRUNS` at the start of the current 1sec rate window.
+    underruns_window_start: u32,
+    ms_since_underrun_window: u32,
+    ms_since_heartbeat: u32,
 }
 
 impl<'a> App<'a> {
-    pub fn new(opts: Opts) -> Self {
+    pub fn new(opts: Opts, wavetable: Option<Wavetable<'static>>) -> Self {
         let mut voice = Voice::new(&HEAP, BLOCK_SIZE);
         let mut patch = Patch::default();
 
@@ -60,10 +96,48 @@ impl<'a> App<'a> {
             voice,
             patch,
             modulations: Modulations::default(),
+            attractor: DeJong::default(),
+            wavetable,
+            wavetable_osc: WavetableOsc::default(),
+            dc_blockers: [DcBlocker::default(); 2],
+            envelope: EnvelopeFollower::new(ENVELOPE_ATTACK_MS, ENVELOPE_DECAY_MS, BLOCK_MS),
             ui: ui::UI::new(opts, TIMER0_ISR_PERIOD_MS,
                             encoder, pca9635, pmod),
+            dtr: peripherals.DTR0,
+            underruns_window_start: 0,
+            ms_since_underrun_window: 0,
+            ms_since_heartbeat: 0,
         }
     }
+
+    fn reset_underruns(&mut self) {
+        UNDERRUNS.store(0, Ordering::Relaxed);
+        UNDERRUNS_PER_SEC.store(0, Ordering::Relaxed);
+        self.underruns_window_start = 0;
+        self.ms_since_underrun_window = 0;
+    }
+}
+
+/// The user wavetable, if the manifest has one that the bootloader
+/// copied to where we expect it, and it is a sane size.
+fn user_wavetable(bootinfo: &bootinfo::BootInfo) -> Option<Wavetable<'static>> {
+    let region = bootinfo.manifest.get_ram_region(WAVETABLE_FILENAME)?;
+    if region.psram_dst != Some(WAVETABLE_PSRAM_OFFSET as u32) ||
+       region.size as usize > WAVETABLE_SZ_MAX {
+        warn!("wavetable: region at {:x?} ({} bytes) outside of reserved window, ignoring",
+              region.psram_dst, region.size);
+        return None;
+    }
+    let samples = unsafe {
+        core::slice::from_raw_parts((PSRAM_BASE + WAVETABLE_PSRAM_OFFSET) as *const i16,
+                                    region.size as usize / 2)
+    };
+    let wavetable = Wavetable::new(samples);
+    match wavetable {
+        Some(ref table) => info!("wavetable: loaded {} waves", table.n_waves()),
+        None => warn!("wavetable: size {} is not whole waves, ignoring", region.size),
+    }
+    wavetable
 }
 
 // TODO: move this to hardware as it is quite expensive.
@@ -104,10 +178,14 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
 
         app.ui.opts.misc.plot_type.value = match app.ui.opts.tracker.page.value {
             Page::Vector => PlotType::Vector,
+            Page::Attr => PlotType::Vector,
             Page::Scope => PlotType::Scope,
             _ => app.ui.opts.misc.plot_type.value
         };
 
+        let has_wavetable = app.wavetable.is_some();
+        app.ui.opts.osc.table.set_enabled(has_wavetable);
+
         //
         // Patch settings from UI
         //
@@ -134,8 +212,9 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
         modulations.morph_patched     = (jack & 0x8) != 0;
 
         if note_patched {
-            // 1V/oct
-            let v_oct = ((pmod.sample_i0().read().bits() as i16) as f32) / 4000.0f32;
+            // 1V/oct (for 5V sources, other standards are rescaled)
+            let v_oct = opts.misc.in_scale.value.to_volts(
+                (pmod.sample_i0().read().bits() as i16) as i32);
             modulations.note = v_oct * 12.0f32;
         }
 
@@ -143,10 +222,41 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
         modulations.timbre = ((pmod.sample_i2().read().bits() as i16) as f32) / 16384.0f32;
         modulations.morph = ((pmod.sample_i3().read().bits() as i16) as f32) / 16384.0f32;
 
+        //
+        // User wavetable, falls back to the built-in one if absent.
+        //
+
+        let wavetable = match (opts.osc.engine.value, opts.osc.table.value) {
+            (Engine::Wavetbl, WavetableSource::User) => app.wavetable,
+            _ => None,
+        };
+        // Same pitch as the other engines, which assume a 48kHz sample rate.
+        let wavetable_freq = note_to_frequency(patch.note + modulations.note) / 48000.0f32;
+        let wavetable_pos = if modulations.timbre_patched {
+            patch.timbre + modulations.timbre * 0.5f32
+        } else {
+            patch.timbre
+        };
+
+        //
+        // Attractor settings from UI
+        //
+
+        let attractor_on = opts.attr.mode.value == AttractorMode::On;
+        app.attractor.set_params(
+            (opts.attr.a.value as f32) / 1000.0f32,
+            (opts.attr.b.value as f32) / 1000.0f32,
+            (opts.attr.c.value as f32) / 1000.0f32,
+            (opts.attr.d.value as f32) / 1000.0f32,
+        );
+
         //
         // Render audio
         //
 
+        // Zero drive bypasses the saturator (and DC blocker) entirely.
+        let drive = (opts.osc.drive.value as f32) / 2.0f32;
+
         let mut out = [0.0f32; BLOCK_SIZE];
         let mut aux = [0.0f32; BLOCK_SIZE];
 
@@ -154,11 +264,50 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
         while (audio_fifo.fifo_len().read().bits() as usize) < AUDIO_FIFO_ELASTIC_SZ - BLOCK_SIZE {
             n_attempts += 1;
             if n_attempts > 10 {
-                // TODO set underrun flag
+                // Rendering can't keep up, the FIFO will run dry.
+                UNDERRUNS.fetch_add(1, Ordering::Relaxed);
                 break
             }
-            app.voice
-               .render(&patch, &modulations, &mut out, &mut aux);
+            if attractor_on {
+                // Attractor points are in [-2, 2], which we map to the full
+                // 'out' (x) and 'aux' (y) range for the vectorscope.
+                for i in 0..BLOCK_SIZE {
+                    let (x, y) = app.attractor.step();
+                    out[i] = x * 0.5f32;
+                    aux[i] = y * 0.5f32;
+                }
+            } else if let Some(ref table) = wavetable {
+                app.wavetable_osc
+                   .render(table, wavetable_freq, wavetable_pos, &mut out, &mut aux);
+            } else {
+                app.voice
+                   .render(&patch, &modulations, &mut out, &mut aux);
+            }
+            if drive > 0.0f32 && !attractor_on {
+                for i in 0..BLOCK_SIZE {
+                    out[i] = softclip(app.dc_blockers[0].process(out[i]), drive);
+                    aux[i] = softclip(app.dc_blockers[1].process(aux[i]), drive);
+                }
+            }
+            // The attractor needs both outputs for its x/y coordinates.
+            if !attractor_on {
+                match opts.osc.aux_mode.value {
+                    AuxMode::Separate => {},
+                    AuxMode::MixToMain => {
+                        // Halved so the sum can't overflow the conversion below.
+                        for i in 0..BLOCK_SIZE {
+                            out[i] = 0.5f32 * (out[i] + aux[i]);
+                            aux[i] = 0.0f32;
+                        }
+                    },
+                    AuxMode::Disabled => aux.fill(0.0f32),
+                }
+            }
+            let mut peak = 0.0f32;
+            for i in 0..BLOCK_SIZE {
+                peak = peak.max(out[i].abs()).max(aux[i].abs());
+            }
+            app.envelope.process(peak);
             for i in 0..BLOCK_SIZE {
                 unsafe {
                     let fifo_base = AUDIO_FIFO_MEM_BASE as *mut u32;
@@ -168,6 +317,33 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
             }
         }
 
+        app.ms_since_underrun_window += TIMER0_ISR_PERIOD_MS;
+        if app.ms_since_underrun_window >= 1000 {
+            let underruns = UNDERRUNS.load(Ordering::Relaxed);
+            UNDERRUNS_PER_SEC.store(underruns.wrapping_sub(app.underruns_window_start),
+                                    Ordering::Relaxed);
+            app.underruns_window_start = underruns;
+            app.ms_since_underrun_window = 0;
+        }
+
+        //
+        // Optional heartbeat over serial, for remote monitoring
+        //
+
+        if let Some(interval_ms) = opts.misc.heartbeat.value.interval_ms() {
+            app.ms_since_heartbeat += TIMER0_ISR_PERIOD_MS;
+            if app.ms_since_heartbeat >= interval_ms {
+                app.ms_since_heartbeat = 0;
+                let heartbeat = heartbeat::Heartbeat {
+                    uptime_ms: app.ui.uptime_ms,
+                    temperature_c: die_temperature_celsius(
+                        app.dtr.temperature().read().bits() as u8),
+                    underruns: UNDERRUNS.load(Ordering::Relaxed),
+                };
+                info!("{}", heartbeat);
+            }
+        }
+
     });
 }
 
@@ -225,7 +401,7 @@ fn main() -> ! {
 
     let mut opts = Opts::default();
     let mut flash_persist_opt = if let Some(storage_window) = bootinfo.manifest.get_option_storage_window() {
-        let mut flash_persist = FlashOptionsPersistence::new(spiflash, storage_window);
+        let mut flash_persist = FlashOptionsPersistence::new(VerifiedFlash::new(spiflash), storage_window);
         flash_persist.load_options(&mut opts).unwrap();
         Some(flash_persist)
     } else {
@@ -238,7 +414,8 @@ fn main() -> ! {
     //
 
     let mut last_palette = opts.beam.palette.value;
-    let app = App::new(opts);
+    let wavetable = user_wavetable(&bootinfo);
+    let app = App::new(opts, wavetable);
     let app = Mutex::new(RefCell::new(app));
 
     info!("heap usage {} KiB", HEAP.used()/1024);
@@ -301,18 +478,24 @@ fn main() -> ! {
         let h_active = display.size().width;
         let v_active = display.size().height;
 
+        handlers::WATCHDOG.start(handlers::WATCHDOG_TIMEOUT_MS);
+
         loop {
+            handlers::WATCHDOG.feed();
 
             //
             // Tiny critical section, prohibit timer ISR when we want
             // to copy out the current state of application options.
             //
 
-            let (opts, draw_options, save_opts, wipe_opts) = critical_section::with(|cs| {
+            let (opts, draw_options, save_opts, wipe_opts, envelope) = critical_section::with(|cs| {
                 let mut app = app.borrow_ref_mut(cs);
                 let save_opts = app.ui.opts.misc.save_opts.poll();
                 let wipe_opts = app.ui.opts.misc.wipe_opts.poll();
-                (app.ui.opts.clone(), app.ui.draw(), save_opts, wipe_opts)
+                if app.ui.opts.misc.clr_underruns.poll() {
+                    app.reset_underruns();
+                }
+                (app.ui.opts.clone(), app.ui.draw(), save_opts, wipe_opts, app.envelope.value())
             });
 
             let on_help_page = opts.tracker.page.value == Page::Help;
@@ -330,7 +513,11 @@ fn main() -> ! {
                 };
                 draw::draw_options(&mut display, &opts, x, y, opts.beam.hue.value).ok();
                 draw::draw_name(&mut display, h_active/2, v_active-50, opts.beam.hue.value,
-                                &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline).ok();
+                                &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline,
+                                bootinfo.manifest.built_unix).ok();
+                draw::draw_underruns(&mut display, h_active/2, v_active-14, opts.beam.hue.value,
+                                     UNDERRUNS.load(Ordering::Relaxed),
+                                     UNDERRUNS_PER_SEC.load(Ordering::Relaxed)).ok();
             }
 
             if on_help_page {
@@ -365,13 +552,21 @@ fn main() -> ! {
                 persist.set_persistence(opts.beam.persist.value);
             }
 
+            let intensity = match opts.beam.intensity_follow.value {
+                IntensityFollow::Off => opts.beam.intensity.value,
+                IntensityFollow::On => {
+                    let level = (envelope / ENVELOPE_FULL_INTENSITY).min(1.0f32);
+                    (opts.beam.intensity.value as f32 * level + 0.5f32) as u8
+                },
+            };
+
             vscope.set_hue(opts.beam.hue.value);
-            vscope.set_intensity(opts.beam.intensity.value);
+            vscope.set_intensity(intensity);
             vscope.set_xscale(opts.vector.xscale.value);
             vscope.set_yscale(opts.vector.yscale.value);
 
             scope.set_hue(opts.beam.hue.value + 6);
-            scope.set_intensity(opts.beam.intensity.value);
+            scope.set_intensity(intensity);
             scope.set_trigger_level(opts.scope.trig_lvl.value);
             scope.set_yscale(opts.scope.yscale.value);
             scope.set_timebase(opts.scope.timebase.value);
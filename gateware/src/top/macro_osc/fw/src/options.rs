@@ -3,6 +3,7 @@ use strum_macros::{EnumIter, IntoStaticStr};
 use serde_derive::{Serialize, Deserialize};
 use tiliqua_lib::palette::ColorPalette;
 pub use tiliqua_lib::scope::{Timebase, VScale};
+pub use tiliqua_lib::scaling::InputScale;
 
 #[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
 #[strum(serialize_all = "SCREAMING-KEBAB-CASE")]
@@ -11,6 +12,7 @@ pub enum Page {
     Help,
     Scope,
     Osc,
+    Attr,
     Misc,
     Beam,
     Vector,
@@ -59,19 +61,86 @@ pub enum Engine {
     Hihat,
 }
 
+/// Wave source for the 'wavetbl' engine.
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum WavetableSource {
+    /// Built-in wavetable of the original module.
+    #[default]
+    Builtin,
+    /// Wavetable bundled with the bitstream, if there is a valid one.
+    User,
+}
+
+/// What the oscillator's 'aux' signal is used for.
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum AuxMode {
+    /// 'out' and 'aux' on their own jacks.
+    #[default]
+    Separate,
+    /// 'out' and 'aux' mixed on the 'out' jack, 'aux' jack silent.
+    #[strum(serialize = "mix-main")]
+    MixToMain,
+    /// 'aux' jack silent.
+    Disabled,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum HeartbeatInterval {
+    #[default]
+    Off,
+    #[strum(serialize = "1s")]
+    Every1s,
+    #[strum(serialize = "10s")]
+    Every10s,
+    #[strum(serialize = "60s")]
+    Every60s,
+}
+
+impl HeartbeatInterval {
+    pub fn interval_ms(self) -> Option<u32> {
+        match self {
+            HeartbeatInterval::Off      => None,
+            HeartbeatInterval::Every1s  => Some(1_000),
+            HeartbeatInterval::Every10s => Some(10_000),
+            HeartbeatInterval::Every60s => Some(60_000),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum AttractorMode {
+    #[default]
+    Off,
+    On,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum IntensityFollow {
+    /// Beam intensity is fixed at `intensity`.
+    #[default]
+    Off,
+    /// Beam intensity follows the output level, up to `intensity`.
+    On,
+}
+
 int_params!(NoteParams<u8>        { step: 1, min: 0, max: 128 });
 int_params!(HarmonicsParams<u8>   { step: 8, min: 0, max: 240 });
 int_params!(TimbreParams<u8>      { step: 8, min: 0, max: 240 });
 int_params!(MorphParams<u8>       { step: 8, min: 0, max: 240 });
+int_params!(DriveParams<u8>       { step: 1, min: 0, max: 16, format: IntFormat::Scaled { divisor: 2, precision: 1, suffix: "x" } });
 int_params!(PersistParams<u8>     { step: 1, min: 1, max: 80 });
 int_params!(IntensityParams<u8>   { step: 1, min: 0, max: 15 });
 int_params!(HueParams<u8>         { step: 1, min: 0, max: 15 });
 int_params!(TriggerLvlParams<i16> { step: 500, min: -16000, max: 16000, format: IntFormat::Scaled { divisor: 4000, precision: 2, suffix: "V" } });
 int_params!(YPosParams<i16>       { step: 25, min: -500, max: 500 });
+int_params!(AttractorParams<i16>  { step: 50, min: -3000, max: 3000, format: IntFormat::Scaled { divisor: 1000, precision: 2, suffix: "" } });
 int_params!(ScrollParams<u8>      { step: 1, min: 0, max: 60 });
 
-button_params!(OneShotButtonParams { mode: ButtonMode::OneShot });
-
 #[derive(OptionPage, Clone)]
 pub struct HelpOpts {
     #[option(0)]
@@ -82,16 +151,24 @@ pub struct HelpOpts {
 pub struct MiscOpts {
     #[option]
     pub plot_type: EnumOption<PlotType>,
+    #[option]
+    pub in_scale: EnumOption<InputScale>,
+    #[option]
+    pub heartbeat: EnumOption<HeartbeatInterval>,
     #[option(false)]
-    pub save_opts: ButtonOption<OneShotButtonParams>,
+    pub clr_underruns: ActionOption,
     #[option(false)]
-    pub wipe_opts: ButtonOption<OneShotButtonParams>,
+    pub save_opts: ActionOption,
+    #[option(false)]
+    pub wipe_opts: ActionOption,
 }
 
 #[derive(OptionPage, Clone)]
 pub struct OscOpts {
     #[option]
     pub engine: EnumOption<Engine>,
+    #[option]
+    pub table: EnumOption<WavetableSource>,
     #[option(77)] // empirically match frequency knob full left
     pub note: IntOption<NoteParams>,
     #[option(96)]
@@ -100,6 +177,24 @@ pub struct OscOpts {
     pub timbre: IntOption<TimbreParams>,
     #[option(128)]
     pub morph: IntOption<MorphParams>,
+    #[option(0)] // saturator bypassed
+    pub drive: IntOption<DriveParams>,
+    #[option]
+    pub aux_mode: EnumOption<AuxMode>,
+}
+
+#[derive(OptionPage, Clone)]
+pub struct AttractorOpts {
+    #[option]
+    pub mode: EnumOption<AttractorMode>,
+    #[option(1400)]
+    pub a: IntOption<AttractorParams>,
+    #[option(-2300)]
+    pub b: IntOption<AttractorParams>,
+    #[option(2400)]
+    pub c: IntOption<AttractorParams>,
+    #[option(-2100)]
+    pub d: IntOption<AttractorParams>,
 }
 
 #[derive(OptionPage, Clone)]
@@ -116,6 +211,8 @@ pub struct BeamOpts {
     pub persist: IntOption<PersistParams>,
     #[option(8)]
     pub intensity: IntOption<IntensityParams>,
+    #[option]
+    pub intensity_follow: EnumOption<IntensityFollow>,
     #[option(10)]
     pub hue: IntOption<HueParams>,
     #[option]
@@ -132,6 +229,8 @@ pub struct ScopeOpts {
     pub trig_mode: EnumOption<TriggerMode>,
     #[option(512)]
     pub trig_lvl: IntOption<TriggerLvlParams>,
+    #[option]
+    pub position: GroupOption,
     #[option(-200)]
     pub ypos_out: IntOption<YPosParams>,
     #[option(200)]
@@ -149,6 +248,8 @@ pub struct Opts {
     pub scope: ScopeOpts,
     #[page(Page::Osc)]
     pub osc: OscOpts,
+    #[page(Page::Attr)]
+    pub attr: AttractorOpts,
     #[page(Page::Beam)]
     pub beam: BeamOpts,
     #[page(Page::Vector)]
@@ -46,6 +46,16 @@ softcore is spitting out.
                             │Oscilloscope│
                             └────────────┘
 
+The 'aux-mode' option on the 'OSC' page can instead mix 'aux' into the 'out'
+output (each at half level, so the sum doesn't clip), or mute it. Output
+routing is fixed in the gateware, so 'aux' can't be moved to out0/out1.
+
+The 'ATTR' page swaps the oscillator for a de Jong strange attractor, iterated
+once per sample on the softcore. Its x/y coordinates are written to the 'out'
+and 'aux' outputs, so the vectorscope draws the attractor as a point cloud.
+The a/b/c/d options are the attractor coefficients, small changes to these can
+give wildly different shapes.
+
 The original module was designed to run at 48kHz. Here, we instantiate a
 powerful (rv32imafc) softcore (this one includes an FPU), which is enough to run
 most engines at ~24kHz-48kHz, however with the video and menu system running
@@ -57,6 +67,22 @@ There is quite some heavy compute here and RAM usage, as a result, the audio
 buffers are too big to fit in BRAM. In this demo, both the firmware and the DSP
 buffers are allocated from external PSRAM.
 
+For remote monitoring, MISC/heartbeat periodically logs a line like
+'heartbeat uptime_s=12.345 temp_c=42 underruns=0' on the serial port. Here,
+'underruns' counts the times the softcore failed to keep the audio FIFO fed.
+The same count (and the rate over the last second) is shown under the bitstream
+name whenever the menu is drawn, and MISC/clr-underruns resets it. Underruns
+that keep climbing mean the selected engine is too heavy to run in real-time,
+which is heard as glitches in the output.
+
+A user wavetable may be bundled with the bitstream by building with
+'--wavetable <file>'. The file is raw mono 16-bit signed (little-endian)
+samples, made of single-cycle waves of 256 samples each, up to 64KiB in total.
+The bootloader copies it to PSRAM, and with OSC/table set to 'user', it is
+played in place of the built-in table whenever the 'wavetbl' engine is
+selected. 'timbre' then sweeps across the waves, and 'aux' is a lo-fi (5-bit)
+copy of 'out'. Without a valid user wavetable, the built-in table is used.
+
 Credits to Emilie Gillet for the original Plaits module and firmware.
 
 Credits to Oliver Rockstedt for the Rust port of said firmware:
@@ -169,6 +195,12 @@ class AudioFIFOPeripheral(wiring.Component):
         return m
 
 
+# Optional user wavetable, copied from flash to this PSRAM offset by the
+# bootloader. This sits just above the DSP heap used by the firmware.
+WAVETABLE_FILENAME      = "wavetable.bin"
+WAVETABLE_PSRAM_OFFSET  = 0x840000
+WAVETABLE_SZ_MAX        = 0x10000
+
 class MacroOscSoc(TiliquaSoc):
 
     # Used by `tiliqua_soc.py` to create a MODULE_DOCSTRING rust constant used by the 'help' page.
@@ -222,6 +254,12 @@ class MacroOscSoc(TiliquaSoc):
             f"pub const AUDIO_FIFO_MEM_BASE: usize = 0x{self.audio_fifo_mem_base:x};\n")
         self.add_rust_constant(
             f"pub const AUDIO_FIFO_ELASTIC_SZ: usize = {self.audio_fifo.elastic_sz};\n")
+        self.add_rust_constant(
+            f"pub const WAVETABLE_FILENAME: &str = \"{WAVETABLE_FILENAME}\";\n")
+        self.add_rust_constant(
+            f"pub const WAVETABLE_PSRAM_OFFSET: usize = 0x{WAVETABLE_PSRAM_OFFSET:x};\n")
+        self.add_rust_constant(
+            f"pub const WAVETABLE_SZ_MAX: usize = 0x{WAVETABLE_SZ_MAX:x};\n")
 
         # now we can freeze the memory map
         self.finalize_csr_bridge()
@@ -288,6 +326,35 @@ class MacroOscSoc(TiliquaSoc):
         return m
 
 
+# Set from `--wavetable`, if provided.
+wavetable_path = None
+
+def argparse_callback(parser):
+    parser.add_argument('--wavetable', type=str, default=None,
+                        help=("Raw mono s16le wavetable (256-sample waves) to bundle "
+                              f"with the bitstream, up to {WAVETABLE_SZ_MAX//1024}KiB."))
+
+def argparse_fragment(args):
+    # Only needed by the archiver, not the gateware.
+    global wavetable_path
+    wavetable_path = args.wavetable
+    if wavetable_path is not None:
+        size = os.path.getsize(wavetable_path)
+        if size == 0 or size > WAVETABLE_SZ_MAX or size % (256*2) != 0:
+            print(f"provided '--wavetable {wavetable_path}' ({size} bytes) must be a "
+                  f"nonzero multiple of 512 bytes, up to {WAVETABLE_SZ_MAX} bytes.")
+            sys.exit(-1)
+    return {}
+
+def archiver_callback(archiver):
+    archiver.with_option_storage()
+    if wavetable_path is not None:
+        archiver.with_ram_region(wavetable_path, psram_dst=WAVETABLE_PSRAM_OFFSET,
+                                 filename=WAVETABLE_FILENAME)
+
 if __name__ == "__main__":
     this_path = os.path.dirname(os.path.realpath(__file__))
-    top_level_cli(MacroOscSoc, path=this_path, archiver_callback=lambda archiver: archiver.with_option_storage())
+    top_level_cli(MacroOscSoc, path=this_path,
+                  argparse_callback=argparse_callback,
+                  argparse_fragment=argparse_fragment,
+                  archiver_callback=archiver_callback)
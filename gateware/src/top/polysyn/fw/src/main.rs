@@ -16,10 +16,12 @@ use tiliqua_pac as pac;
 use tiliqua_hal as hal;
 use tiliqua_lib::*;
 use tiliqua_lib::draw;
-use tiliqua_lib::dsp::OnePoleSmoother;
-use tiliqua_lib::midi::MidiTouchController;
+use tiliqua_lib::dsp::{OnePoleSmoother, mix_q15, stereo_spread, Q15_ONE};
+use tiliqua_lib::midi::{MidiTouchController, CcMapping, CcSource, CcTarget};
+use tiliqua_lib::idle::{Activity, IdleMonitor, IdleTransition};
 use pac::constants::*;
 use tiliqua_hal::persist::Persist;
+use tiliqua_hal::spiflash::VerifiedFlash;
 use tiliqua_fw::*;
 use tiliqua_fw::options::*;
 use opts::{Options, OptionTrait};
@@ -30,17 +32,19 @@ use tiliqua_hal::embedded_graphics::prelude::*;
 
 use opts::persistence::*;
 use hal::pca9635::Pca9635Driver;
+use hal::polysynth::adsr_rate;
 use hal::tusb322::{TUSB322Driver, TUSB322Mode, AttachedState, AccessoryType};
 
 use tiliqua_fw::wavetable;
 
 pub const TIMER0_ISR_PERIOD_MS: u32 = 5;
+// Drop into low-power idle after this long with no display and nothing playing.
+pub const IDLE_TIMEOUT_MS: u32 = 60_000;
 
 fn adsr_ui_to_rate(ui_value: u16) -> u16 {
-    // 0..32768 -> 1ms..2000ms -> hardware rate
-    let ms_x32k = 32768u32 + ui_value as u32 * 1999;
-    let rate = (45_000_000u64 / ms_x32k as u64) as u32;
-    rate.min(65535) as u16
+    // 0..32768 -> 1ms..2000ms (shown in seconds) -> hardware rate
+    let ms = 1 + ui_value as u32 * 1999 / 32768;
+    adsr_rate(ms, AUDIO_FS)
 }
 
 fn timer0_handler(app: &Mutex<RefCell<App>>) {
@@ -64,6 +68,7 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
         //
 
         let mut last_cc_index = None;
+        let mut all_notes_off = false;
         let midi_word = app.synth.midi_read();
         if midi_word != 0 {
             // Blink MIDI activity LED on TRS port
@@ -75,6 +80,7 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
                 ((midi_word >> 16) & 0xFF) as u8,
             ];
             if let Ok(msg) = MidiMessage::try_parse_slice(&bytes) {
+                all_notes_off = midi::is_all_notes_off(&msg);
                 if let MidiMessage::ControlChange(_, cc, val) = msg {
                     if let Some(action) = app.cc_mapper.process(cc.into(), val.into()) {
                         last_cc_index = Some(action.global_index);
@@ -105,15 +111,14 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
         let drive_smooth = app.drive_smoother.proc_u16(opts.effect.drive.value);
         app.synth.set_drive(drive_smooth);
 
-        // Map 0-1 UI range to 32768-8192 hardware range (inverted)
-        let reso_ui = opts.voice.reso.value as u32;
-        let reso_hw = (32768 - reso_ui * 24576 / 32768) as u16;
+        // Map 0-1 UI range to 32767-8192 hardware range (inverted)
+        let reso_hw = mix_q15(Q15_ONE, 8192, opts.voice.reso.value as i32) as u16;
         let reso_smooth = app.reso_smoother.proc_u16(reso_hw);
         app.synth.set_reso(reso_smooth);
 
-        let diffuse_smooth = app.diffusion_smoother.proc_u16(opts.effect.diffuse.value);
-        let coeff_dry: i32 = (32768 - diffuse_smooth) as i32;
-        let coeff_wet: i32 = diffuse_smooth as i32;
+        let diffuse_smooth = app.diffusion_smoother.proc_u16(opts.effect.diffuse.value) as i32;
+        let coeff_dry = mix_q15(Q15_ONE, 0, diffuse_smooth) as i32;
+        let coeff_wet = mix_q15(0, Q15_ONE, diffuse_smooth) as i32;
 
         app.synth.set_matrix_coefficient(0, 0, coeff_dry);
         app.synth.set_matrix_coefficient(1, 1, coeff_dry);
@@ -127,11 +132,20 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
 
         app.synth.set_midi_channel_filter(opts.misc.midi_ch.value.to_filter());
 
+        app.synth.set_steal_policy(opts.voice.steal.value);
+        app.synth.update_steal();
+
+        if opts.voice.spread.value != app.last_spread {
+            set_stereo_spread(&mut app.synth, opts.voice.spread.value);
+            app.last_spread = opts.voice.spread.value;
+        }
+
         // ADSR params
-        app.synth.set_attack_rate(adsr_ui_to_rate(opts.adsr.attack.value));
-        app.synth.set_decay_rate(adsr_ui_to_rate(opts.adsr.decay.value));
-        app.synth.set_sustain_level((opts.adsr.sustain.value as u32 * 65535 / 32768) as u16);
-        app.synth.set_release_rate(adsr_ui_to_rate(opts.adsr.release.value));
+        app.synth.set_adsr(
+            adsr_ui_to_rate(opts.adsr.attack.value),
+            adsr_ui_to_rate(opts.adsr.decay.value),
+            (opts.adsr.sustain.value as u32 * 65535 / 32768) as u16,
+            adsr_ui_to_rate(opts.adsr.release.value));
 
         // LFO -> phase modulation CSR
         {
@@ -161,7 +175,9 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
         }
 
         // Touch controller logic (sends MIDI to internal polysynth)
-        if opts.misc.touch_ctrl.value == TouchControl::On {
+        if opts.misc.touch_ctrl.value != TouchControl::Off {
+            app.touch_controller.set_pressure(
+                opts.misc.touch_ctrl.value == TouchControl::Pressure);
             app.ui.touch_led_mask(0b00111111);
             let touch = app.ui.pmod.touch();
             let jack = app.ui.pmod.jack();
@@ -170,23 +186,83 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
             if (jack & (1 << 5)) != 0 {
                 app.ui.pmod.led_set_auto(5);
             }
+            let src = opts.misc.touch_cc_src.value as usize;
+            let source = if (jack & (1 << src)) != 0 {
+                CcSource::Cv(src)
+            } else {
+                CcSource::Touch(src)
+            };
+            app.touch_controller.set_cc(match opts.misc.touch_cc.value {
+                TouchCc::Off => None,
+                TouchCc::ModWheel => Some(CcMapping { source, target: CcTarget::Control(midi::CC_MOD_WHEEL) }),
+                TouchCc::Bend => Some(CcMapping { source, target: CcTarget::PitchBend }),
+            });
             let msgs = app.touch_controller.update(&touch, jack);
             for msg in msgs {
                 if msg != MidiMessage::Stop {
-                    // TODO move MidiMessage rendering into HAL, perhaps
-                    // even inside synth.midi_write.
-                    let mut bytes = [0u8; 3];
-                    msg.render_slice(&mut bytes);
-                    let v: u32 = (bytes[2] as u32) << 16 |
-                                 (bytes[1] as u32) << 8 |
-                                 (bytes[0] as u32) << 0;
-                    app.synth.midi_write(v);
+                    synth_midi_write(&mut app.synth, msg);
                 }
             }
+            if let Some(msg) = app.touch_controller.update_cc(
+                    &touch, &app.ui.pmod.sample_i(), app.ui.pmod.counts_per_v(), TIMER0_ISR_PERIOD_MS) {
+                synth_midi_write(&mut app.synth, msg);
+            }
+        }
+
+        //
+        // Panic (menu or MIDI CC123) releases all voices
+        //
+
+        if app.ui.opts.misc.panic.poll() || all_notes_off {
+            info!("midi: all notes off");
+            // Internal messages go through the channel filter too.
+            let channel = Channel::from(opts.misc.midi_ch.value.to_filter()
+                                        .map(|ch| ch - 1).unwrap_or(0));
+            let notes = app.synth.voice_notes();
+            for msg in midi::all_notes_off(channel, &notes) {
+                synth_midi_write(&mut app.synth, msg);
+            }
+        }
+
+        //
+        // Low-power idle detection
+        //
+
+        let activity = Activity {
+            display: app.hpd,
+            // Voice filter cutoffs follow their envelopes, so this is
+            // nonzero whenever any voice is sounding.
+            audio: app.synth.voice_cutoffs().iter().any(|&c| c > 0),
+            midi: app.ui.midi_recently_active(TIMER0_ISR_PERIOD_MS),
+            user: app.ui.encoder_recently_touched(TIMER0_ISR_PERIOD_MS),
+        };
+        match app.idle.update(TIMER0_ISR_PERIOD_MS, &activity) {
+            Some(IdleTransition::Enter) => info!("idle: entering low-power idle"),
+            Some(IdleTransition::Exit)  => info!("idle: woken up"),
+            None => {}
         }
     });
 }
 
+fn synth_midi_write(synth: &mut Polysynth0, msg: MidiMessage) {
+    // TODO move MidiMessage rendering into HAL, perhaps
+    // even inside synth.midi_write.
+    let mut bytes = [0u8; 3];
+    msg.render_slice(&mut bytes);
+    let v: u32 = (bytes[2] as u32) << 16 |
+                 (bytes[1] as u32) << 8 |
+                 (bytes[0] as u32) << 0;
+    synth.midi_write(v);
+}
+
+/// Spread all voices evenly across the stereo field, from mono (0)
+/// to full width (`Q15_ONE`).
+fn set_stereo_spread(synth: &mut Polysynth0, amount: u16) {
+    for (voice, pan) in stereo_spread::<N_VOICES>(amount).into_iter().enumerate() {
+        synth.set_voice_pan(voice, pan);
+    }
+}
+
 fn global_index(opts: &Opts, opt: &dyn OptionTrait) -> usize {
     let key = opt.key().value();
     opts.all().enumerate()
@@ -204,13 +280,14 @@ fn apply_cc_action(opts: &mut Opts, action: &opts::cc_map::CcAction) {
 
 fn build_cc_mapper(opts: &Opts) -> MidiCcMapper {
     let mut m = MidiCcMapper::new();
-    // Voice page (CC 20-25)
+    // Voice page (CC 20-26)
     m.add(20, global_index(opts, &opts.voice.waveform),  CcMapMode::Absolute);
     m.add(21, global_index(opts, &opts.voice.proc),      CcMapMode::Absolute);
     m.add(22, global_index(opts, &opts.voice.proc_amt),  CcMapMode::Absolute);
     m.add(23, global_index(opts, &opts.voice.reso),      CcMapMode::Absolute);
     m.add(24, global_index(opts, &opts.voice.lfo_rate),  CcMapMode::Absolute);
     m.add(25, global_index(opts, &opts.voice.lfo_depth), CcMapMode::Absolute);
+    m.add(26, global_index(opts, &opts.voice.spread),    CcMapMode::Absolute);
     // ADSR page (CC 30-33)
     m.add(30, global_index(opts, &opts.adsr.attack),  CcMapMode::Absolute);
     m.add(31, global_index(opts, &opts.adsr.decay),   CcMapMode::Absolute);
@@ -239,10 +316,15 @@ struct App {
     last_waveform: Waveform,
     last_proc_mode: ProcMode,
     last_proc_amt: u16,
+    // last stereo spread written to the voice pans
+    last_spread: u16,
     // midi cc mapper
     cc_mapper: MidiCcMapper,
     // lfo phase accumulator
     lfo_phase: wavetable::Fix32,
+    // low-power idle tracking, `hpd` is written by the main loop
+    idle: IdleMonitor,
+    hpd: bool,
 }
 
 impl App {
@@ -271,8 +353,12 @@ impl App {
             last_waveform: Waveform::default(),
             last_proc_mode: ProcMode::default(),
             last_proc_amt: 0,
+            // Out of range, so the first update always writes the pans.
+            last_spread: u16::MAX,
             cc_mapper,
             lfo_phase: wavetable::Fix32::ZERO,
+            idle: IdleMonitor::new(IDLE_TIMEOUT_MS),
+            hpd: true,
         }
     }
 }
@@ -321,7 +407,7 @@ fn main() -> ! {
 
     let mut opts = Opts::default();
     let mut flash_persist_opt = if let Some(storage_window) = bootinfo.manifest.get_option_storage_window() {
-        let mut flash_persist = FlashOptionsPersistence::new(spiflash, storage_window);
+        let mut flash_persist = FlashOptionsPersistence::new(VerifiedFlash::new(spiflash), storage_window);
         flash_persist.load_options(&mut opts).unwrap();
         Some(flash_persist)
     } else {
@@ -371,7 +457,26 @@ fn main() -> ! {
         let mut last_attached_state = AttachedState::NotAttached;
         let mut last_opt_host_enabled = false;
 
+        handlers::WATCHDOG.start(handlers::WATCHDOG_TIMEOUT_MS);
+
         loop {
+            handlers::WATCHDOG.feed();
+
+            let hpd = display.get_hpd();
+            let idle = critical_section::with(|cs| {
+                let mut app = app.borrow_ref_mut(cs);
+                app.hpd = hpd;
+                app.idle.idle()
+            });
+
+            if idle {
+                // Nobody is looking and nothing is playing. Stop the vectorscope
+                // and skip drawing, sleeping until the next timer interrupt
+                // (which keeps polling the encoder, MIDI and voices for activity).
+                vscope.set_enabled(false);
+                riscv::asm::wfi();
+                continue;
+            }
 
             let (opts, notes, cutoffs, draw_options, save_opts, wipe_opts) = critical_section::with(|cs| {
                 let mut app = app.borrow_ref_mut(cs);
@@ -473,7 +578,8 @@ fn main() -> ! {
                 draw::draw_options(&mut display, &opts, x, y,
                                    opts.beam.hue.value).ok();
                 draw::draw_name(&mut display, h_active/2, v_active-50, opts.beam.hue.value,
-                                &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline).ok();
+                                &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline,
+                                bootinfo.manifest.built_unix).ok();
                 if opts.tracker.page.value == Page::Adsr {
                     use draw::AdsrPhase;
                     let highlight = opts.selected().and_then(|i| {
@@ -4,6 +4,7 @@ use serde_derive::{Serialize, Deserialize};
 
 use tiliqua_lib::palette::ColorPalette;
 use tiliqua_lib::scope::VScale;
+use tiliqua_hal::polysynth::StealPolicy;
 
 #[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
 #[strum(serialize_all = "SCREAMING-KEBAB-CASE")]
@@ -21,8 +22,34 @@ pub enum Page {
 #[strum(serialize_all = "kebab-case")]
 pub enum TouchControl {
     Off,
+    /// Touches gate notes, the ADSR shapes them.
     #[default]
     On,
+    /// As `On`, with touch pressure also modulating each note.
+    Pressure,
+}
+
+/// What the `touch_cc_src` jack sends, instead of playing its note.
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TouchCc {
+    #[default]
+    Off,
+    /// CC1, from touch or 0..5V.
+    ModWheel,
+    /// From touch (bends up) or -5..5V.
+    Bend,
+}
+
+/// Jack driving `TouchCc`: its touch while unplugged, its CV when patched.
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum TouchCcSource {
+    In0,
+    In1,
+    In2,
+    #[default]
+    In3,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
@@ -103,7 +130,7 @@ int_params!(ProcAmtParams<u16>   { step: 1, min: 0, max: 50, format: IntFormat::
 int_params!(DriveParams<u16>    { step: 2048, min: 0, max: 32768, format: IntFormat::Scaled { divisor: 32768, precision: 2, suffix: "" } });
 int_params!(ResoParams<u16>       { step: 2048, min: 0, max: 32768, format: IntFormat::Scaled { divisor: 32768, precision: 2, suffix: "" } });
 int_params!(DiffuseParams<u16>    { step: 2048, min: 0, max: 32768, format: IntFormat::Scaled { divisor: 32768, precision: 2, suffix: "" } });
-int_params!(AdsrTimeParams<u16>  { step: 1024, min: 0, max: 16384, format: IntFormat::Scaled { divisor: 16384, precision: 2, suffix: "" } });
+int_params!(AdsrTimeParams<u16>  { step: 1024, min: 0, max: 16384, format: IntFormat::Scaled { divisor: 16384, precision: 2, suffix: "s" } });
 int_params!(AdsrLevelParams<u16> { step: 1024, min: 0, max: 32768, format: IntFormat::Scaled { divisor: 32768, precision: 2, suffix: "" } });
 int_params!(PersistParams<u8>     { step: 1, min: 1, max: 80 });
 int_params!(IntensityParams<u8>   { step: 1, min: 0, max: 15 });
@@ -111,8 +138,7 @@ int_params!(HueParams<u8>         { step: 1, min: 0, max: 15 });
 int_params!(ScrollParams<u8>      { step: 1, min: 0, max: 125 });
 int_params!(LfoRateParams<u16>   { step: 2, min: 0, max: 50, format: IntFormat::Scaled { divisor: 10, precision: 1, suffix: "hz" } });
 int_params!(LfoDepthParams<u16>  { step: 2048, min: 0, max: 32768, format: IntFormat::Scaled { divisor: 32768, precision: 2, suffix: "" } });
-
-button_params!(OneShotButtonParams { mode: ButtonMode::OneShot });
+int_params!(SpreadParams<u16>    { step: 2048, min: 0, max: 32768, format: IntFormat::Scaled { divisor: 32768, precision: 2, suffix: "" } });
 
 #[derive(OptionPage, Clone)]
 pub struct HelpOpts {
@@ -134,6 +160,10 @@ pub struct VoiceOpts {
     pub lfo_rate: IntOption<LfoRateParams>,
     #[option(3277)]
     pub lfo_depth: IntOption<LfoDepthParams>,
+    #[option(32768)]
+    pub spread: IntOption<SpreadParams>,
+    #[option]
+    pub steal: EnumOption<StealPolicy>,
 }
 
 #[derive(OptionPage, Clone)]
@@ -175,6 +205,10 @@ pub struct MiscOpts {
     #[option]
     pub touch_ctrl: EnumOption<TouchControl>,
     #[option]
+    pub touch_cc: EnumOption<TouchCc>,
+    #[option]
+    pub touch_cc_src: EnumOption<TouchCcSource>,
+    #[option]
     pub cc_highlight: EnumOption<CcHighlight>,
     #[option]
     pub midi_ch: EnumOption<MidiChannel>,
@@ -183,9 +217,11 @@ pub struct MiscOpts {
     #[option]
     pub serial_debug: EnumOption<UsbMidiSerialDebug>,
     #[option(false)]
-    pub save_opts: ButtonOption<OneShotButtonParams>,
+    pub panic: ActionOption,
+    #[option(false)]
+    pub save_opts: ActionOption,
     #[option(false)]
-    pub wipe_opts: ButtonOption<OneShotButtonParams>,
+    pub wipe_opts: ActionOption,
 }
 
 #[derive(Options, Clone)]
@@ -26,9 +26,21 @@ the menu system (MISC page).
 
     - Voice mix is sent to output channels 2 and 3 (last 2 jacks).
 
-    - For touch, the touch magnitude controls the filter envelopes of each
-      voice. For MIDI, the velocity of each note and mod wheel affects the
-      filter envelopes.
+    - For touch, each touch gates a note, which the ADSR envelope shapes (set
+      touch-ctrl to 'pressure' for the touch magnitude to also modulate the
+      filter envelope of its voice). For MIDI, the velocity of each note and
+      mod wheel affects the filter envelopes.
+
+    - With touch-cc set, the touch-cc-src jack sends mod wheel or pitch bend
+      instead of playing its note. Its touch is used while unplugged, and its
+      CV (0..5V for mod wheel, -5..5V for pitch bend) once patched.
+
+    - With all 8 voices playing, a new note steals one, picked by the `steal`
+      setting: the oldest, quietest (lowest filter envelope), lowest or
+      highest note. Notes held only by the sustain pedal are stolen last.
+
+    - ADSR times are in seconds (for a full-scale ramp). Envelope changes
+      apply from the next note played, held notes keep their envelope.
 
     - When a jack is patched into input 0, 1 or 2, CV can be used to modulate
       all voices simultaneously up to audio rate (phase mod, filter cutoff and
@@ -37,9 +49,9 @@ the menu system (MISC page).
       like detuning. Patch an oscillator into phase CV for FM effects, or into
       drive for AM effect.
 
-Each voice is hard panned left or right in the stereo field, with 2 end-of-chain
-effects: distortion and diffusion (delay), both of which can be mixed in with
-the UI.
+Voices are spread across the stereo field (from all centered up to alternating
+hard left / right, see the `spread` setting), with 2 end-of-chain effects:
+distortion and diffusion (delay), both of which can be mixed in with the UI.
 
     .. code-block:: text
 
@@ -96,6 +108,8 @@ can also be used to control most of these through CCs as follows:
         VOICE   reso          23  filter resonance
         VOICE   lfo-rate      24  phase modulation LFO rate
         VOICE   lfo-depth     25  phase modulation LFO depth
+        VOICE   spread        26  stereo spread (0 = mono, 1 = full width)
+        VOICE   steal          -  voice stealing: oldest, quietest, lowest, highest
 
         ADSR    attack        30  filter envelope attack
         ADSR    decay         31  filter envelope decay
@@ -112,16 +126,24 @@ can also be used to control most of these through CCs as follows:
         BEAM    hue           54  trace and menu hue
         BEAM    palette       55  color palette
 
-        MISC    touch-ctrl     -  enable/disable jacktouch input
+        MISC    touch-ctrl     -  jacktouch input: off, on (gate), pressure
+        MISC    touch-cc       -  touch-cc-src sends mod wheel or pitch bend
+        MISC    touch-cc-src   -  jack for touch-cc (touch, or CV if patched)
         MISC    cc-highlight   -  highlight changed on CC input
         MISC    midi-ch        -  filter MIDI to specific channel (default: all)
         MISC    usb-host       -  enable USB host MIDI (disables TRS)
         MISC    serial-debug   -  dump MIDI data out serial port
+        MISC    panic          -  all notes off (release stuck notes)
         MISC    save-opts      -  save all options to flash
         MISC    wipe-opts      -  reset all options to defaults
 
     MIDI CC 1 (mod wheel) controls filter cutoff. CC 64 (sustain
-    pedal) holds voices. Pitch bend is also supported.
+    pedal) holds voices. Pitch bend is also supported. CC 123 (all
+    notes off) releases every voice, same as MISC/panic.
+
+    With no display attached, after 60sec without any notes playing, MIDI
+    traffic or encoder movement, the CPU drops into a low-power idle state
+    (no drawing, vectorscope disabled). Any of these wakes it up again.
 
 """
 
@@ -133,6 +155,7 @@ from amaranth import *
 from amaranth.lib import data, stream, wiring
 from amaranth.lib.fifo import SyncFIFOBuffered
 from amaranth.lib.wiring import In, Out, connect, flipped
+from amaranth.utils import exact_log2
 from amaranth_soc import csr
 
 from amaranth_future import fixed
@@ -206,10 +229,17 @@ class PolySynth(wiring.Component):
 
     lfo: In(ASQ)
 
+    # Per-voice stereo position (-0x8000 hard left, 0x7FFF hard right)
+    voice_pans: In(signed(16)).array(N_VOICES)
+
     # Jack detection (directly from pmod hardware)
     jack: In(unsigned(8))
 
+    # Voice slot taken by a NOTE_ON when all slots are occupied
+    steal_voice: In(range(N_VOICES))
+
     voice_states: Out(midi.MidiVoice).array(N_VOICES)
+    voice_sustained: Out(unsigned(1)).array(N_VOICES)
     voice_cutoffs: Out(unsigned(8)).array(N_VOICES)
 
     def __init__(self):
@@ -221,7 +251,8 @@ class PolySynth(wiring.Component):
         n_voices = self.N_VOICES
 
         m.submodules.voice_tracker = voice_tracker = midi.MidiVoiceTracker(
-            max_voices=n_voices, velocity_mod=True, zero_velocity_gate=False)
+            max_voices=n_voices, velocity_mod=True, zero_velocity_gate=False, steal=True)
+        m.d.comb += voice_tracker.steal_ix.eq(self.steal_voice)
 
         # Connect MIDI stream -> voice tracker
         wiring.connect(m, wiring.flipped(self.i_midi), voice_tracker.i)
@@ -263,10 +294,12 @@ class PolySynth(wiring.Component):
             m.d.comb += vel_out.eq(vel_sum.saturate(dsp.MultiADSR.EnvUQ))
             m.d.comb += [
                 self.voice_states[n].eq(voice_tracker.o[n]),
+                self.voice_sustained[n].eq(voice_tracker.sustained[n]),
                 self.voice_cutoffs[n].eq(voice_block.voice_cutoffs[n]),
                 voice_block.voice_gates[n].eq(voice_tracker.o[n].gate),
                 voice_block.voice_freq_incs[n].eq(voice_tracker.o[n].freq_inc),
                 voice_block.voice_velocity[n].eq(vel_out),
+                voice_block.voice_pans[n].eq(self.voice_pans[n]),
                 voice_tracker.voice_active[n].eq(voice_block.voice_active[n]),
             ]
 
@@ -362,8 +395,10 @@ class SynthPeripheral(wiring.Component):
         value: csr.Field(csr.action.W, signed(16))
 
     class Voice(csr.Register, access="r"):
-        note:   csr.Field(csr.action.R, unsigned(8))
-        cutoff: csr.Field(csr.action.R, unsigned(8))
+        note:      csr.Field(csr.action.R, unsigned(8))
+        cutoff:    csr.Field(csr.action.R, unsigned(8))
+        gate:      csr.Field(csr.action.R, unsigned(1))
+        sustained: csr.Field(csr.action.R, unsigned(1))
 
     class Matrix(csr.Register, access="w"):
         """Mixing matrix coefficient: commit on write strobe, MatrixBusy set until done."""
@@ -393,6 +428,15 @@ class SynthPeripheral(wiring.Component):
         """Channel filter. 0 = no filter, 1..16 = single MIDI channel."""
         value: csr.Field(csr.action.W, unsigned(5))
 
+    class VoicePan(csr.Register, access="w"):
+        """Stereo position of a single voice slot, committed on write strobe."""
+        voice: csr.Field(csr.action.W, unsigned(exact_log2(PolySynth.N_VOICES)))
+        value: csr.Field(csr.action.W, signed(16))
+
+    class StealVoice(csr.Register, access="w"):
+        """Voice slot a NOTE_ON takes over when all slots are occupied."""
+        voice: csr.Field(csr.action.W, unsigned(exact_log2(PolySynth.N_VOICES)))
+
     def __init__(self, synth=None):
         self.synth = synth
         regs = csr.Builder(addr_width=7, data_width=8)
@@ -416,6 +460,8 @@ class SynthPeripheral(wiring.Component):
         self._wt_data       = regs.add("wt_data",       self.WavetableData(), offset=voices_csr_end + 0x30)
         self._lfo           = regs.add("lfo",           self.Lfo(),           offset=voices_csr_end + 0x34)
         self._midi_ch_filt  = regs.add("midi_ch_filter",self.MidiChannelFilter(), offset=voices_csr_end + 0x38)
+        self._voice_pan     = regs.add("voice_pan",     self.VoicePan(),      offset=voices_csr_end + 0x3C)
+        self._steal_voice   = regs.add("steal_voice",   self.StealVoice(),    offset=voices_csr_end + 0x40)
         self._bridge = csr.Bridge(regs.as_memory_map())
         super().__init__({
             "bus": In(csr.Signature(addr_width=regs.addr_width, data_width=regs.data_width)),
@@ -472,9 +518,27 @@ class SynthPeripheral(wiring.Component):
         for i, voice in enumerate(self._voices):
             m.d.comb += [
                 voice.f.note.r_data  .eq(self.synth.voice_states[i].note),
-                voice.f.cutoff.r_data.eq(self.synth.voice_cutoffs[i])
+                voice.f.cutoff.r_data.eq(self.synth.voice_cutoffs[i]),
+                voice.f.gate.r_data     .eq(self.synth.voice_states[i].gate),
+                voice.f.sustained.r_data.eq(self.synth.voice_sustained[i]),
             ]
 
+        with m.If(self._steal_voice.f.voice.w_stb):
+            m.d.sync += self.synth.steal_voice.eq(self._steal_voice.f.voice.w_data)
+
+        # Per-voice pan. Pans belong to voice slots rather than notes, so they
+        # are kept when the voice tracker steals or retriggers a voice.
+        # Reset state matches the old fixed panning: even voices hard left,
+        # odd voices hard right.
+        voice_pans = Array(Signal(signed(16), name=f"voice_pan{n}",
+                                  init=-0x8000 if n % 2 == 0 else 0x7FFF)
+                           for n in range(PolySynth.N_VOICES))
+        with m.If(self._voice_pan.element.w_stb):
+            m.d.sync += voice_pans[self._voice_pan.f.voice.w_data].eq(
+                self._voice_pan.f.value.w_data)
+        for n in range(PolySynth.N_VOICES):
+            m.d.comb += self.synth.voice_pans[n].eq(voice_pans[n])
+
         # matrix coefficient update logic
         matrix_busy = Signal()
         m.d.comb += self._matrix_busy.f.busy.r_data.eq(matrix_busy)
@@ -1,23 +1,67 @@
 use tiliqua_hal::delay_line::DelayLine;
 use tiliqua_hal::grain_player::GrainPlayer;
-use crate::options::{ChannelOpts, PlaybackMode};
+use tiliqua_lib::dsp;
+use tiliqua_lib::grain_sync::{self, GrainPhase, SyncMode};
+use crate::options::{ChannelOpts, LenParams, PlaybackMode};
+use opts::IntOptionParams;
 use micromath::F32Ext;
 
+/// Loop points are only snapped to zero crossings within this many
+/// samples either side (~5ms at 48kHz, enough for anything above 100Hz).
+pub const ZERO_CROSSING_SEARCH: usize = 256;
+
+/// Same thresholds as the hardware gate detectors (4V on, 2V off), so the
+/// gate seen by the firmware on a jack matches the one the grain player sees.
+pub const CV_GATE_ON: i32 = 16000;
+pub const CV_GATE_OFF: i32 = 8000;
+
+/// State of the leader (channel 0) needed by the other channels to follow it.
+#[derive(Clone, Copy)]
+pub struct SyncSource {
+    pub mode: SyncMode,
+    pub l_phase: GrainPhase,
+    pub phase: GrainPhase,
+    pub gate_rising: bool,
+}
+
 pub struct Channel<G: GrainPlayer> {
     pub grain: G,
     l_gate: bool,
     l_mode: PlaybackMode,
     l_start: u32,
     l_len: u32,
+    length: u32,
+    crossfade: u32,
+    cv_gate: bool,
+    l_trigger: bool,
+    trigger_rising: bool,
+    l_phase: GrainPhase,
+    phase: GrainPhase,
 }
 
 impl<G: GrainPlayer> Channel<G> {
     pub fn new(grain: G) -> Self {
-        Self { grain, l_gate: false, l_mode: PlaybackMode::default(), l_start: 0, l_len: 0 }
+        Self { grain, l_gate: false, l_mode: PlaybackMode::default(), l_start: 0, l_len: 0,
+               length: 0, crossfade: 0, cv_gate: false, l_trigger: false, trigger_rising: false,
+               l_phase: GrainPhase::default(), phase: GrainPhase::default() }
+    }
+
+    /// Crossfade the last `samples` of a looping grain into its first `samples`.
+    /// Clamped to half the grain length, so the two regions never overlap.
+    pub fn set_crossfade(&mut self, samples: u32) {
+        self.crossfade = samples.min(self.length / 2);
+        self.grain.set_crossfade(self.crossfade);
     }
 
-    /// Update grain player from channel options and input state
-    pub fn update(&mut self, opts: &ChannelOpts, max_samples: u32, touch_idx: usize, touch: &[u8; 8], jack: u8, cv: i32) {
+    /// For the other channels to follow this one, from its last `update`.
+    pub fn sync_source(&self, mode: SyncMode) -> SyncSource {
+        SyncSource { mode, l_phase: self.l_phase, phase: self.phase, gate_rising: self.trigger_rising }
+    }
+
+    /// Update grain player from channel options and input state. With a `sync`
+    /// source, the grain is also restarted whenever the source asks for it.
+    pub fn update(&mut self, opts: &ChannelOpts, max_samples: u32, touch_idx: usize, touch: &[u8; 8], jack: u8, cv: i32,
+                  sync: Option<&SyncSource>) {
         let size = max_samples;
         let ui_start = (opts.start.value as i32).max(5) as u32;
         let start = size.saturating_sub(ui_start);
@@ -36,7 +80,12 @@ impl<G: GrainPlayer> Channel<G> {
         } else {
             opts.speed.value
         };
-        self.grain.set_params(speed, start, length);
+        self.grain.set_params(start, length);
+        self.grain.set_rate(if opts.reverse.value { -(speed as i32) } else { speed as i32 });
+        self.length = length;
+        self.set_crossfade(opts.xfade.value);
+        self.l_phase = self.phase;
+        self.phase = GrainPhase { start, length, position: self.grain.position() as u32 };
 
         // Pulse gate low for one tick on mode/start/len change to force a rising edge restart
         let mode_changed = opts.mode.value != self.l_mode;
@@ -62,12 +111,34 @@ impl<G: GrainPlayer> Channel<G> {
         };
         self.l_gate = gate;
 
-        self.grain.set_control(opts.mode.value.into(), gate, hw_gate_enable, opts.reverse.value);
+        // Track the jack gate in firmware too, so it can retrigger other channels.
+        self.cv_gate = if self.cv_gate { cv >= CV_GATE_OFF } else { cv > CV_GATE_ON };
+        let effective_gate = if hw_gate_enable { self.cv_gate } else { gate };
+        self.trigger_rising = effective_gate && !self.l_trigger;
+        self.l_trigger = effective_gate;
+
+        let mode = opts.mode.value;
+        self.grain.set_control(mode.into(), gate, hw_gate_enable);
+
+        if let Some(sync) = sync {
+            let restart = !(mode_changed || params_changed) && grain_sync::restart_follower(
+                sync.mode, (&sync.l_phase, &sync.phase), (&self.l_phase, &self.phase), sync.gate_rising);
+            // Pulse the gate low for a rising edge, the grain player restarts on its next
+            // sample. Only oneshot grains play without their own gate held high.
+            if restart && effective_gate {
+                self.grain.set_control(mode.into(), false, false);
+                self.grain.set_control(mode.into(), gate, hw_gate_enable);
+            } else if restart && mode == PlaybackMode::Oneshot {
+                self.grain.set_control(mode.into(), false, false);
+                self.grain.set_control(mode.into(), true, false);
+            }
+        }
     }
 
     pub fn view<D: DelayLine>(&self, delayln: &D) -> ChannelView {
         ChannelView {
             grain_position: self.grain.position(),
+            crossfade: self.crossfade as usize,
             delayln_max_samples: delayln.size_samples(),
             delayln_base: delayln.data_ptr(),
             delayln_wrpointer: delayln.wrpointer(),
@@ -78,6 +149,7 @@ impl<G: GrainPlayer> Channel<G> {
 #[derive(Clone)]
 pub struct ChannelView {
     pub grain_position: usize,
+    pub crossfade: usize,
     pub delayln_max_samples: usize,
     pub delayln_base: *const i16,
     pub delayln_wrpointer: usize,
@@ -87,6 +159,7 @@ impl ChannelView {
     pub fn from_delayln<D: DelayLine>(delayln: &D) -> Self {
         Self {
             grain_position: 0,
+            crossfade: 0,
             delayln_max_samples: delayln.size_samples(),
             delayln_base: delayln.data_ptr(),
             delayln_wrpointer: delayln.wrpointer(),
@@ -158,6 +231,22 @@ impl ChannelView {
         (start_x, end_x)
     }
 
+    /// X extents of the crossfaded regions at the end and start of the grain,
+    /// as `[(tail_start_x, tail_end_x), (head_start_x, head_end_x)]`.
+    pub fn crossfade_regions_x(&self, opts: &ChannelOpts, n_samples: usize, center_on_end: bool, waveform_x: u32, actual_span: u32) -> Option<[(u32, u32); 2]> {
+        // Only LOOP modes crossfade.
+        if self.crossfade == 0 || !matches!(opts.mode.value, PlaybackMode::Loop | PlaybackMode::LoopOn) {
+            return None;
+        }
+        let grain_start = self.grain_start_delay(opts);
+        let grain_end = grain_start.saturating_sub(Self::grain_len(opts));
+        let x = |delay: usize| self.delay_to_x(opts, delay, n_samples, center_on_end, waveform_x, actual_span);
+        Some([
+            (x(grain_end + self.crossfade), x(grain_end)),
+            (x(grain_start), x(grain_start.saturating_sub(self.crossfade))),
+        ])
+    }
+
     pub fn view_label(opts: &ChannelOpts, center_on_end: bool) -> &'static str {
         if opts.zoom.value == 0 {
             "[entire buffer]"
@@ -194,6 +283,41 @@ impl ChannelView {
         }
     }
 
+    /// Delay of the zero crossing nearest to `sample_index` (also a delay),
+    /// within `ZERO_CROSSING_SEARCH` samples. The waveform buffer read for
+    /// the display is decimated by the zoom stride, too coarse to find
+    /// crossings in, so this reads the neighbourhood at full resolution.
+    pub fn nearest_zero_crossing(&self, sample_index: u32) -> Option<u32> {
+        let delay = (sample_index as usize).min(self.delayln_max_samples);
+        // Clip the search window to the recorded part of the delay line.
+        let newest = delay.saturating_sub(ZERO_CROSSING_SEARCH);
+        let oldest = (delay + ZERO_CROSSING_SEARCH).min(self.delayln_max_samples);
+        let mut buf = [0i16; 2 * ZERO_CROSSING_SEARCH];
+        let buf = &mut buf[..oldest - newest];
+        self.delayln_read_samples(buf, oldest, 1);
+        // buf[i] is at delay `oldest - i`.
+        dsp::nearest_zero_crossing(buf, oldest - delay).map(|i| (oldest - i) as u32)
+    }
+
+    /// New `(start, len)` option values with both grain boundaries moved
+    /// onto zero crossings. Each boundary that has no crossing nearby is
+    /// left where it is, and reported by `false` in the returned flags.
+    ///
+    /// These are written to the options directly, so a snap that would take
+    /// either value past `LenParams::MAX` is rejected the same way.
+    pub fn snap_to_zero_crossings(&self, opts: &ChannelOpts) -> ((u32, u32), [bool; 2]) {
+        let max_samples = self.delayln_max_samples as u32;
+        let start = self.grain_start_delay(opts) as u32;
+        let snapped_start = self.nearest_zero_crossing(start)
+            .filter(|start| max_samples - start <= LenParams::MAX);
+        let start = snapped_start.unwrap_or(start);
+        let end = start.saturating_sub(Self::grain_len(opts) as u32);
+        let snapped_end = self.nearest_zero_crossing(end)
+            .filter(|end| *end < start && start - end <= LenParams::MAX);
+        let end = snapped_end.unwrap_or(end);
+        ((max_samples - start, start - end), [snapped_start.is_some(), snapped_end.is_some()])
+    }
+
     pub fn playback_position(&self) -> usize {
         self.grain_position
     }
@@ -25,6 +25,7 @@ use flash::DelaylineFlash;
 use hal::pca9635::Pca9635Driver;
 use tiliqua_hal::delay_line::DelayLine;
 use tiliqua_hal::persist::Persist;
+use tiliqua_hal::spiflash::VerifiedFlash;
 use tiliqua_hal::pmod::EurorackPmod;
 
 pub const TIMER0_ISR_PERIOD_MS: u32 = 5;
@@ -35,6 +36,14 @@ pub const WAVEFORM_SAMPLES: usize = 240;
 // color of each grainreader (head and peaks on each page)
 pub const CHANNEL_HUES: [u8; 3] = [0, 5, 10];
 
+// how long to show a warning after 'snap' leaves a loop point unchanged
+pub const SNAP_WARNING_MS: u32 = 2000;
+
+// Dim the display after this long without touching anything, as the menu
+// and waveforms are mostly static.
+pub const SCREENSAVER_TIMEOUT_MS: u32 = 10 * 60 * 1000;
+pub const SCREENSAVER_GAMMA: f32 = 6.0;
+
 // little helper for drawing waveform peaks in the correct spot
 struct WaveformLayout {
     x: u32,
@@ -63,11 +72,20 @@ impl WaveformLayout {
         (WAVEFORM_SAMPLES as u32 - 1) * self.sample_width
     }
 
+    // hatch the region between 2 x positions, e.g. to show a crossfade
+    fn shade(&self, display: &mut DMAFramebuffer0, x0: u32, x1: u32, hue: u8) {
+        let (lo, hi) = (x0.min(x1).max(self.x), x0.max(x1).min(self.x + self.span()));
+        for x in (lo..=hi).step_by(3) {
+            draw::draw_vline(display, x, self.y, self.height, hue, 4).ok();
+        }
+    }
+
     fn draw_waveform(&self, display: &mut DMAFramebuffer0, view: WaveformView, hue: u8, waveform: &[i16]) {
         let draw_width = WAVEFORM_SAMPLES as u32 * self.sample_width;
         match view {
             WaveformView::Peaks => draw::draw_waveform_peaks(display, self.x, self.y, draw_width, self.height, hue, waveform).ok(),
-            WaveformView::Lines => draw::draw_waveform_lines(display, self.x, self.y, draw_width, self.height, hue, waveform).ok(),
+            WaveformView::Lines => draw::draw_trace(display, self.x, self.y, self.span(), self.height, hue, waveform,
+                                                    draw::TraceStyle::Lines, Some(1.0f32)).ok(),
         };
     }
 }
@@ -115,9 +133,11 @@ impl App {
         let i2cdev = I2c0::new(peripherals.I2C0);
         let pca9635 = Pca9635Driver::new(i2cdev);
         let pmod = EurorackPmod0::new(peripherals.PMOD0_PERIPH);
+        let mut ui = ui::UI::new(opts, TIMER0_ISR_PERIOD_MS,
+                                 encoder, pca9635, pmod);
+        ui.set_screensaver_timeout(SCREENSAVER_TIMEOUT_MS);
         Self {
-            ui: ui::UI::new(opts, TIMER0_ISR_PERIOD_MS,
-                            encoder, pca9635, pmod),
+            ui,
             channels,
             delayln,
         }
@@ -170,9 +190,11 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
         let jack = pmod.jack().read().bits();
         let cv = app.ui.pmod.sample_i();
         let opts = app.ui.opts.clone();
-        app.channels.0.update(&opts.channel0, max_samples, 1, &touch, jack, cv[1]);
-        app.channels.1.update(&opts.channel1, max_samples, 2, &touch, jack, cv[2]);
-        app.channels.2.update(&opts.channel2, max_samples, 3, &touch, jack, cv[3]);
+        app.channels.0.update(&opts.channel0, max_samples, 1, &touch, jack, cv[1], None);
+        // Channels 1 and 2 follow channel 0, unless `sync_mode` is independent.
+        let sync = app.channels.0.sync_source(opts.record.sync_mode.value);
+        app.channels.1.update(&opts.channel1, max_samples, 2, &touch, jack, cv[2], Some(&sync));
+        app.channels.2.update(&opts.channel2, max_samples, 3, &touch, jack, cv[3], Some(&sync));
     });
 }
 
@@ -220,7 +242,7 @@ fn main() -> ! {
 
     let mut opts = Opts::default();
     let mut flash_persist_opt = if let Some(storage_window) = bootinfo.manifest.get_option_storage_window() {
-        let mut flash_persist = FlashOptionsPersistence::new(spiflash, storage_window);
+        let mut flash_persist = FlashOptionsPersistence::new(VerifiedFlash::new(spiflash), storage_window);
         flash_persist.load_options(&mut opts).unwrap();
         Some(flash_persist)
     } else {
@@ -257,16 +279,25 @@ fn main() -> ! {
 
         let hue = 10;
         let mut last_palette = palette::ColorPalette::default();
+        let mut last_screensaver = false;
+        let mut snap_warning: Option<(u32, &'static str)> = None;
+
+        handlers::WATCHDOG.start(handlers::WATCHDOG_TIMEOUT_MS);
 
         loop {
+            handlers::WATCHDOG.feed();
 
             let h_active = display.size().width;
             let v_active = display.size().height;
 
-            let (opts, _, channel_view, record_view, save_all, wipe_all) = critical_section::with(|cs| {
+            let (opts, _, channel_view, record_view, save_all, wipe_all, snap, uptime_ms, screensaver) = critical_section::with(|cs| {
                 let mut app = app.borrow_ref_mut(cs);
                 let save_all = app.ui.opts.record.save_all.poll();
                 let wipe_all = app.ui.opts.record.wipe_all.poll();
+                let snap = match app.ui.opts.tracker.page.value.channel_index() {
+                    Some(ix) => app.ui.opts.channel_opts_mut(ix).snap.poll(),
+                    None => false,
+                };
                 let channel_view = match app.ui.opts.tracker.page.value {
                     Page::Channel0 => Some((0usize, app.channels.0.view(&app.delayln), app.ui.opts.channel0.clone())),
                     Page::Channel1 => Some((1usize, app.channels.1.view(&app.delayln), app.ui.opts.channel1.clone())),
@@ -285,7 +316,8 @@ fn main() -> ! {
                 } else {
                     None
                 };
-                (app.ui.opts.clone(), app.ui.draw(), channel_view, record_view, save_all, wipe_all)
+                (app.ui.opts.clone(), app.ui.draw(), channel_view, record_view, save_all, wipe_all,
+                 snap, app.ui.uptime_ms, app.ui.screensaver())
             });
 
             let on_help_page = opts.tracker.page.value == Page::Help;
@@ -297,11 +329,15 @@ fn main() -> ! {
             };
             draw::draw_options(&mut display, &opts, x, y, hue).ok();
             draw::draw_name(&mut display, h_active/2, v_active-50, hue,
-                            &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline).ok();
+                            &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline,
+                            bootinfo.manifest.built_unix).ok();
 
-            if opts.record.palette.value != last_palette {
-                opts.record.palette.value.write_to_hardware(&mut display);
+            if opts.record.palette.value != last_palette || screensaver != last_screensaver {
+                let gamma = if screensaver { SCREENSAVER_GAMMA } else { 1.0f32 };
+                opts.record.palette.value.write_to_hardware_with_gamma(
+                    &mut display, palette::PaletteLayout::default(), gamma);
                 last_palette = opts.record.palette.value;
+                last_screensaver = screensaver;
             }
 
             if on_help_page {
@@ -330,7 +366,9 @@ fn main() -> ! {
                      app.delayln.wrpointer() * 2)
                 });
                 let data = unsafe { core::slice::from_raw_parts(ptr, size_bytes) };
+                // Takes much longer than the watchdog timeout.
                 delayln_flash.save(data, wr_bytes, |label, done_kb, total_kb| {
+                    handlers::WATCHDOG.feed();
                     draw_flash_progress(&mut display, hue, label, done_kb, total_kb);
                 });
             }
@@ -371,10 +409,29 @@ fn main() -> ! {
             }
 
             // 'ChannelX' page: show (maybe) zoomed delayline with grain start/end points.
-            if let Some((ch_idx, view, channel_opts)) = channel_view {
+            if let Some((ch_idx, view, mut channel_opts)) = channel_view {
                 let wf = WaveformLayout::new(h_active, v_active);
                 let ch_hue = CHANNEL_HUES[ch_idx];
 
+                // 'snap' action: move grain start/end onto zero crossings.
+                if snap {
+                    let ((start, len), found) = view.snap_to_zero_crossings(&channel_opts);
+                    channel_opts.start.value = start;
+                    channel_opts.len.value = len;
+                    critical_section::with(|cs| {
+                        let mut app = app.borrow_ref_mut(cs);
+                        let opts = app.ui.opts.channel_opts_mut(ch_idx);
+                        opts.start.value = start;
+                        opts.len.value = len;
+                    });
+                    snap_warning = match found {
+                        [true, true] => None,
+                        [false, true] => Some((uptime_ms, "no zero crossing near start")),
+                        [true, false] => Some((uptime_ms, "no zero crossing near end")),
+                        [false, false] => Some((uptime_ms, "no zero crossings nearby")),
+                    };
+                }
+
                 // HACK: when hovering on 'length' menu item, center on it instead of 'start'.
                 let center_on_end = opts.tracker.selected == Some(5);
 
@@ -387,6 +444,11 @@ fn main() -> ! {
                 let marker_y = wf.y + wf.height / 4;
                 draw::draw_vline(&mut display, start_x, marker_y, marker_height, ch_hue, 15).ok();
                 draw::draw_vline(&mut display, end_x, marker_y, marker_height, ch_hue, 15).ok();
+                if let Some(regions) = view.crossfade_regions_x(&channel_opts, WAVEFORM_SAMPLES, center_on_end, wf.x, wf.span()) {
+                    for (x0, x1) in regions {
+                        wf.shade(&mut display, x0, x1, ch_hue);
+                    }
+                }
 
                 let playback_pos = view.playback_position();
                 let pos_x = view.delay_to_x(&channel_opts, playback_pos, WAVEFORM_SAMPLES, center_on_end, wf.x, wf.span());
@@ -403,6 +465,19 @@ fn main() -> ! {
                     font,
                     Alignment::Center
                 ).draw(&mut display).ok();
+
+                if let Some((since_ms, warning)) = snap_warning {
+                    if uptime_ms.wrapping_sub(since_ms) < SNAP_WARNING_MS {
+                        Text::with_alignment(
+                            warning,
+                            Point::new((h_active / 2) as i32, (label_y + 20) as i32),
+                            font,
+                            Alignment::Center
+                        ).draw(&mut display).ok();
+                    } else {
+                        snap_warning = None;
+                    }
+                }
             }
 
         }
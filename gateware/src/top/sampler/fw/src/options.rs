@@ -2,6 +2,7 @@ use opts::*;
 use strum_macros::{EnumIter, IntoStaticStr};
 use serde_derive::{Serialize, Deserialize};
 use tiliqua_lib::palette::ColorPalette;
+pub use tiliqua_lib::grain_sync::SyncMode;
 
 #[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
 #[strum(serialize_all = "SCREAMING-KEBAB-CASE")]
@@ -80,9 +81,9 @@ int_params!(ScrollParams<u8> { step: 1, min: 0, max: 60 });
 int_params!(SpeedParams<u16> { step: 1, min: 32, max: 1024, format: IntFormat::Scaled { divisor: 256, precision: 2, suffix: "x" } });
 int_params!(LenParams<u32>     { step: 256, min: 0, max: 0x40000, format: IntFormat::Scaled { divisor: 48000, precision: 2, suffix: "" } });
 int_params!(ZoomParams<u8>     { step: 1, min: 0, max: 4 });
+int_params!(XfadeParams<u32>   { step: 240, min: 0, max: 48000, format: IntFormat::Scaled { divisor: 48, precision: 0, suffix: "ms" } });
 
 button_params!(ToggleButtonParams { mode: ButtonMode::Toggle });
-button_params!(OneShotButtonParams { mode: ButtonMode::OneShot });
 
 #[derive(OptionPage, Clone)]
 pub struct HelpOpts {
@@ -98,10 +99,12 @@ pub struct RecordOpts {
     pub view: EnumOption<WaveformView>,
     #[option]
     pub palette: EnumOption<ColorPalette>,
+    #[option]
+    pub sync_mode: EnumOption<SyncMode>,
     #[option(false)]
-    pub save_all: ButtonOption<OneShotButtonParams>,
+    pub save_all: ActionOption,
     #[option(false)]
-    pub wipe_all: ButtonOption<OneShotButtonParams>,
+    pub wipe_all: ActionOption,
 }
 
 #[derive(OptionPage, Clone)]
@@ -118,6 +121,10 @@ pub struct ChannelOpts {
     pub start: IntOption<LenParams>,
     #[option(0x23000)]
     pub len: IntOption<LenParams>,
+    #[option(480)]
+    pub xfade: IntOption<XfadeParams>,
+    #[option(false)]
+    pub snap: ActionOption,
 }
 
 #[derive(Options, Clone)]
@@ -36,6 +36,19 @@ and playback mode. The behaviour of touch/CV is different in each mode:
     - **ScrubFast**: CV scrubs position within grain.
     - **ScrubSlow**: CV scrubs position (with one-pole filter)
 
+Channels 1 and 2 may follow channel 0, selected by ``sync-mode`` on the
+DELAYLINE page:
+
+    - **Independent**: Every channel plays on its own.
+    - **SyncToCh0**: Channels 1 and 2 restart whenever channel 0 loops or is
+      retriggered, whatever their own grain lengths.
+    - **RetrigAll**: Channels 1 and 2 restart on every rising edge of the
+      channel 0 gate, so one trigger input retriggers all of them.
+
+A following channel is only restarted while it is playing (i.e. its own gate
+is high, or its mode has the gate stuck on), except in Oneshot mode, where
+the restart plays the whole grain.
+
 When no cable is plugged into a gate input, the corresponding jack
 captouch (1/2/3) acts as a gate. If a jack is plugged in, gate trigger
 is 2V with 1V hysteresis. Jack CV may also be used to control pitch or
@@ -53,8 +66,11 @@ slewable delayline and Karplus-strong effects (especially in SCRUB mode).
 
     .. note::
 
-        WARN: pop prevention is not implemented yet, you might need to fiddle
-        with the grain start/end positions to get clean gates.
+        WARN: pop prevention is only implemented at loop boundaries (the
+        ``xfade`` option, shaded on the waveform), you might need to fiddle
+        with the grain start/end positions to get clean gates. The ``snap``
+        action moves both to the nearest zero crossings of the recording,
+        which helps, or shows a warning if there is none close enough.
 
 """
 
@@ -6,5 +6,10 @@ pub use tiliqua_hal as hal;
 
 hal::impl_tiliqua_soc_pac!();
 
+hal::impl_gpio! {
+    Gpio0: pac::GPIO0,
+    Gpio1: pac::GPIO1,
+}
+
 pub mod handlers;
 pub mod options;
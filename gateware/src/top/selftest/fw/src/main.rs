@@ -3,7 +3,7 @@
 
 use riscv_rt::entry;
 use irq::handler;
-use log::{info, error};
+use log::{info, warn, error};
 
 use critical_section::Mutex;
 use core::cell::RefCell;
@@ -25,13 +25,19 @@ use tiliqua_fw::*;
 use tiliqua_lib::*;
 use pac::constants::*;
 use tiliqua_lib::draw;
+use tiliqua_lib::clip::ClippedDrawTarget;
 use tiliqua_lib::calibration::*;
+use tiliqua_lib::siggen::{SignalGenerator, Waveform};
+use tiliqua_lib::diagnostics::DiagnosticGate;
+use opts::OptionTrait;
+use opts::persistence::*;
 use tiliqua_lib::color::HI8;
 use tiliqua_fw::options::*;
 use tiliqua_hal::pmod::EurorackPmod;
 use tiliqua_hal::persist::Persist;
+use tiliqua_hal::spiflash::VerifiedFlash;
 use tiliqua_hal::pca9635::Pca9635Driver;
-use tiliqua_hal::dma_framebuffer::DMAFramebuffer;
+use tiliqua_hal::dma_framebuffer::{DMAFramebuffer, DVIModeline, ScanoutMeter};
 use tiliqua_hal::eeprom::EepromDriver;
 use tiliqua_hal::tusb322::TUSB322Driver;
 
@@ -39,6 +45,37 @@ pub type ReportString = String<512>;
 
 pub const TIMER0_ISR_PERIOD_MS: u32 = 10;
 
+// Test signal samples are written to the DAC registers by the CPU, so
+// the update rate is set by how fast we can poll, not the codec rate.
+pub const SIGGEN_FS: u32 = 16000;
+// Length of each burst of test signal output between UI redraws. The
+// outputs hold their last value while the screen is being drawn.
+pub const SIGGEN_BURST_MS: u32 = 40;
+
+// DAC levels stepped through by the loopback calibration sweep. Each is
+// held for SWEEP_SETTLE_BURSTS before SWEEP_AVERAGE readings are taken
+// (one per SIGGEN_BURST_MS), so the whole sweep takes ~2sec.
+pub const SWEEP_VOLTS: [i32; 5] = [-5, -2, 0, 2, 5];
+pub const SWEEP_SETTLE_BURSTS: u32 = 2;
+pub const SWEEP_AVERAGE: i32 = 8;
+
+// Distinct DC level per output for the loopback continuity test, so
+// swapped cables or jacks read back the wrong level. Readings further
+// than LOOPBACK_TOLERANCE_DIV of a volt off are a failure.
+pub const LOOPBACK_VOLTS: [i32; 4] = [-3, -1, 1, 3];
+pub const LOOPBACK_TOLERANCE_DIV: i32 = 4;
+
+// PSRAM bandwidth sweep. Each cell of the table writes and then reads
+// back BW_SWEEP_WORDS, as bursts of BW_BURST_WORDS consecutive words laid
+// out by a `BwPattern`, inside the same region as `psram_memtest`. The 12
+// cells move under 1MiB in total, so the sweep takes ~100ms.
+pub const BW_BURST_WORDS: [usize; 4] = [1, 4, 16, 64];
+pub const BW_SWEEP_WORDS: usize = 1024*8;
+pub const BW_REGION_WORDS: usize = 1024*16;
+// Spacing of strided bursts. Longer than the largest burst, and odd, so
+// successive bursts land on distinct addresses until the region wraps.
+pub const BW_STRIDE_WORDS: usize = 257;
+
 
 fn timer0_handler(app: &Mutex<RefCell<App>>) {
 
@@ -154,6 +191,81 @@ fn psram_memtest(s: &mut ReportString, timer: &mut Timer0) {
     write!(s, "  read {} KByte/sec\r\n", ((sysclk as u64) * (psram_sz_test/1024) as u64) / (read_ticks as u64)).ok();
 }
 
+#[derive(Clone, Copy)]
+enum BwPattern {
+    Sequential,
+    Strided,
+    Random,
+}
+
+// Word offset (into the sweep region) of burst number `n`.
+fn bw_burst_start(pattern: BwPattern, n: usize, burst: usize, rng: &mut u32) -> usize {
+    let span = BW_REGION_WORDS - burst;
+    match pattern {
+        BwPattern::Sequential => (n * burst) % span,
+        BwPattern::Strided => (n * BW_STRIDE_WORDS) % span,
+        BwPattern::Random => {
+            // xorshift32, cheap enough not to dominate single-word bursts.
+            *rng ^= *rng << 13;
+            *rng ^= *rng >> 17;
+            *rng ^= *rng << 5;
+            *rng as usize % span
+        }
+    }
+}
+
+// Write then read throughput (KByte/sec) for one cell of the sweep. Timed
+// with the uptime counter, so this must run after `enable_tick_isr`, and
+// includes time spent in the tick ISR, like any other main loop code.
+fn psram_bandwidth(timer: &Timer0, pattern: BwPattern, burst: usize) -> (u64, u64) {
+    let psram_ptr = PSRAM_BASE as *mut u32;
+    let region_start = PSRAM_SZ_WORDS/2 - BW_REGION_WORDS;
+    let n_bursts = BW_SWEEP_WORDS / burst;
+
+    let mut rng = 0x1234_5678u32;
+    let start = timer.uptime_us();
+    for n in 0..n_bursts {
+        let addr = region_start + bw_burst_start(pattern, n, burst, &mut rng);
+        for i in 0..burst {
+            unsafe { psram_ptr.add(addr + i).write_volatile(i as u32) };
+        }
+    }
+    let endwrite = timer.uptime_us();
+
+    // Make sure reads go out to PSRAM, not the data cache.
+    pac::cpu::vexriscv::flush_dcache();
+
+    let mut rng = 0x1234_5678u32;
+    let startread = timer.uptime_us();
+    for n in 0..n_bursts {
+        let addr = region_start + bw_burst_start(pattern, n, burst, &mut rng);
+        for i in 0..burst {
+            unsafe { psram_ptr.add(addr + i).read_volatile() };
+        }
+    }
+    let endread = timer.uptime_us();
+
+    let kbytes = (n_bursts * burst * 4 / 1024) as u64;
+    (kbytes * 1_000_000 / (endwrite - start).max(1),
+     kbytes * 1_000_000 / (endread - startread).max(1))
+}
+
+// Throughput table across access patterns and burst sizes. Run this with
+// the framebuffer and audio running to see how they contend with the CPU.
+fn psram_sweep(s: &mut ReportString, timer: &Timer0) {
+    write!(s, "psram KByte/sec (write/read)\r\n").ok();
+    write!(s, "burst {:<13} {:<13} {:<13}\r\n", "sequential", "strided", "random").ok();
+    for burst in BW_BURST_WORDS {
+        write!(s, "{:<5}", burst).ok();
+        for pattern in [BwPattern::Sequential, BwPattern::Strided, BwPattern::Random] {
+            let (write, read) = psram_bandwidth(timer, pattern, burst);
+            info!("psram_sweep: burst={} write={} read={} KByte/sec", burst, write, read);
+            write!(s, " {:>6}/{:<6}", write, read).ok();
+        }
+        write!(s, "\r\n").ok();
+    }
+}
+
 fn spiflash_memtest(s: &mut ReportString, timer: &mut Timer0) {
 
     let spiflash_ptr = SPIFLASH_BASE as *mut u32;
@@ -250,14 +362,19 @@ fn edid_test(s: &mut ReportString, i2cdev: &mut I2c0) {
     }
     let edid_parsed = edid::Edid::parse(&edid);
     match edid_parsed {
-        Ok(edid::Edid { header, descriptors, .. }) => {
-            write!(s, "mfg_id={:?} product={:?} serial={:?}\r\n",
+        Ok(ref edid) => {
+            let header = &edid.header;
+            write!(s, "mfg_id={:?} product={:?} serial={:?} name=\"{}\"\r\n",
                    header.manufacturer_id,
                    header.product_code,
                    header.serial_number,
+                   edid.monitor_name().unwrap_or(""),
                    ).ok();
+            if let Some(serial) = edid.monitor_serial() {
+                write!(s, "      serial_str=\"{}\"\r\n", serial).ok();
+            }
             info!("EDID header: {:?}", header);
-            for descriptor in descriptors.iter() {
+            for descriptor in edid.descriptors.iter() {
                 info!("EDID descriptor: {:?}", descriptor);
                 if let edid::Descriptor::DetailedTiming(desc) = descriptor {
                     write!(s, "      detailed [sz_x={} sz_y={} clk={}kHz]\r\n",
@@ -274,6 +391,15 @@ fn edid_test(s: &mut ReportString, i2cdev: &mut I2c0) {
     }
 }
 
+fn i2c_bus_map<I2C: I2c>(s: &mut ReportString, name: &str, i2cdev: &mut I2C) {
+    // Everything that ACKs, to spot missing or mis-addressed parts.
+    write!(s, "{}:", name).ok();
+    for address in tiliqua_hal::i2c::scan(i2cdev, tiliqua_hal::i2c::SCAN_ADDRESSES) {
+        write!(s, " {:02x}", address).ok();
+    }
+    write!(s, "\r\n").ok();
+}
+
 fn print_touch_err(s: &mut ReportString, pmod: &EurorackPmod0)
 {
     if pmod.touch_err() != 0 {
@@ -285,17 +411,14 @@ fn print_touch_err(s: &mut ReportString, pmod: &EurorackPmod0)
 
 fn print_usb_state(s: &mut ReportString, i2cdev: &mut I2c0)
 {
-    // Read TUSB322 connection status register
-    // We don't fully use this yet. But it's useful for checking for usb circuitry assembly problems.
-    // (in particular the cable orientation detection registers)
+    // Useful for checking for usb circuitry assembly problems
+    // (in particular the cable orientation detection).
     let mut tusb322 = TUSB322Driver::new(i2cdev);
-    match tusb322.read_connection_status_control() {
-        Ok(status) => {
-            write!(s, "tusb322 [AS={:?} CD={:?}]\r\n",
-                   status.attached_state,
-                   status.cable_dir).ok();
+    match (tusb322.read_cable_orientation(), tusb322.read_vbus_detect()) {
+        (Ok(orientation), Ok(vbus)) => {
+            write!(s, "tusb322 [CC={:?} VBUS={}]\r\n", orientation, vbus).ok();
         },
-        Err(_) => {
+        _ => {
             write!(s, "tusb322 NAK\r\n").ok();
         }
     }
@@ -322,22 +445,36 @@ fn print_pmod_state(s: &mut ReportString, pmod: &impl EurorackPmod)
 
 fn print_die_temperature(s: &mut ReportString, dtr: &pac::DTR0)
 {
-    // From Table 4.3 in FPGA-TN-02210-1-4
-    // "Power Consumption and Management for ECP5 and ECP5-5G Devices"
-    let code_to_celsius: [i16; 64] = [
-        -58, -56, -54, -52, -45, -44, -43, -42,
-        -41, -40, -39, -38, -37, -36, -30, -20,
-        -10,  -4,   0,   4,  10,  21,  22,  23,
-         24,  25,  26,  27,  28,  29,  40,  50,
-         60,  70,  76,  80,  81,  82,  83,  84,
-         85,  86,  87,  88,  89,  95,  96,  97,
-         98,  99, 100, 101, 102, 103, 104, 105,
-        106, 107, 108, 116, 120, 124, 128, 132
-    ];
     let code = dtr.temperature().read().bits();
     write!(s, "die_temp [code={} celsius={}]\r\n",
            code,
-           code_to_celsius[code as usize]).ok();
+           heartbeat::die_temperature_celsius(code as u8)).ok();
+}
+
+fn print_scanout(s: &mut ReportString, meter: &ScanoutMeter, modeline: &DVIModeline)
+{
+    match meter.refresh_hz() {
+        None => write!(s, "scanout [measuring]\r\n"),
+        Some(hz) if hz == 0.0 => write!(s, "scanout [no scanout, expected={:.1}Hz]\r\n",
+                                        modeline.refresh_rate()),
+        Some(hz) => write!(s, "scanout [measured={:.1}Hz expected={:.1}Hz line={:.1}kHz]\r\n",
+                           hz, modeline.refresh_rate(),
+                           meter.line_hz(modeline).unwrap_or(0.0) / 1000.0),
+    }.ok();
+}
+
+fn print_recent_log(s: &mut ReportString)
+{
+    let recent = logger::recent_lines();
+    // Newest lines first, the oldest ones are dropped if they don't fit.
+    let mut used = 0;
+    let n_fit = recent.iter().rev().take_while(|line| {
+        used += line.len() + 2;
+        used <= s.capacity()
+    }).count();
+    for line in recent.iter().skip(recent.len() - n_fit) {
+        write!(s, "{}\r\n", line).ok();
+    }
 }
 
 fn print_psram_stats(s: &mut ReportString, psram: &pac::PSRAM_CSR)
@@ -370,11 +507,140 @@ impl App {
         let pmod = EurorackPmod0::new(peripherals.PMOD0_PERIPH);
         let i2cdev = I2c0::new(peripherals.I2C0);
         let pca9635 = Pca9635Driver::new(i2cdev);
-        Self {
-            ui: ui::UI::new(opts, TIMER0_ISR_PERIOD_MS,
-                            encoder, pca9635, pmod),
+        let mut ui = ui::UI::new(opts, TIMER0_ISR_PERIOD_MS,
+                                 encoder, pca9635, pmod);
+        // Calibration tweaks want exact single steps.
+        ui.set_accel(false, 1);
+        ui.set_digit_edit(true);
+        Self { ui }
+    }
+}
+
+fn siggen_burst(gen: &mut SignalGenerator, pmod: &EurorackPmod0, timer: &Timer0,
+                output: SiggenOutput) {
+    let ticks_per_ms = pac::clock::sysclk() / 1000;
+    let ticks_per_sample = pac::clock::sysclk() / SIGGEN_FS;
+    // Timer0 is shared with the UI tick, so it counts down and reloads every
+    // TIMER0_ISR_PERIOD_MS. Track elapsed ticks across the reloads.
+    let reload = ticks_per_ms * TIMER0_ISR_PERIOD_MS;
+    let mut last = timer.counter();
+    let mut elapsed = 0u32;
+    let mut next_sample = 0u32;
+    while elapsed < ticks_per_ms * SIGGEN_BURST_MS {
+        let now = timer.counter();
+        elapsed += if now <= last { last - now } else { last + reload - now };
+        last = now;
+        if elapsed < next_sample {
+            continue;
+        }
+        next_sample += ticks_per_sample;
+        let sample = gen.next_sample() as u32;
+        let r = &pmod.registers;
+        match output {
+            SiggenOutput::All => {
+                r.sample_o0().write(|w| unsafe { w.sample().bits(sample) } );
+                r.sample_o1().write(|w| unsafe { w.sample().bits(sample) } );
+                r.sample_o2().write(|w| unsafe { w.sample().bits(sample) } );
+                r.sample_o3().write(|w| unsafe { w.sample().bits(sample) } );
+            }
+            SiggenOutput::Out0 => r.sample_o0().write(|w| unsafe { w.sample().bits(sample) } ),
+            SiggenOutput::Out1 => r.sample_o1().write(|w| unsafe { w.sample().bits(sample) } ),
+            SiggenOutput::Out2 => r.sample_o2().write(|w| unsafe { w.sample().bits(sample) } ),
+            SiggenOutput::Out3 => r.sample_o3().write(|w| unsafe { w.sample().bits(sample) } ),
+        }
+    }
+}
+
+// Fit DAC calibration constants in one pass, by stepping every output
+// through SWEEP_VOLTS and reading it back on the (already calibrated)
+// inputs in loopback. Returns None if any channel could not be fit.
+fn dac_sweep(constants: &CalibrationConstants, gen: &mut SignalGenerator,
+             pmod: &EurorackPmod0, timer: &Timer0) -> Option<CalibrationConstants> {
+    let counts_per_v = pmod.counts_per_v();
+    let mut samples = [[(0i16, 0i16); SWEEP_VOLTS.len()]; 4];
+    gen.waveform = Waveform::Dc;
+    for (n, volts) in SWEEP_VOLTS.iter().enumerate() {
+        let stimulus = counts_per_v * volts;
+        gen.set_amplitude(stimulus);
+        for _ in 0..SWEEP_SETTLE_BURSTS {
+            siggen_burst(gen, pmod, timer, SiggenOutput::All);
+        }
+        let mut sum = [0i32; 4];
+        for _ in 0..SWEEP_AVERAGE {
+            siggen_burst(gen, pmod, timer, SiggenOutput::All);
+            let sample_i = pmod.sample_i();
+            for ch in 0..4 {
+                sum[ch] += sample_i[ch];
+            }
+        }
+        for ch in 0..4 {
+            samples[ch][n] = (stimulus as i16, (sum[ch] / SWEEP_AVERAGE) as i16);
+        }
+    }
+    gen.set_amplitude(0);
+    let mut fitted = CalibrationConstants { cal: constants.cal.clone() };
+    for (ch, ch_samples) in samples.iter().enumerate() {
+        let (scale, zero) = constants.fit_channel(ch+4, ch_samples)?;
+        info!("autocal/sweep: dac{} scale={:.4} zero={:.4}", ch, scale, zero);
+        // An unconnected input reads back (almost) nothing, which would
+        // otherwise fit an enormous scale.
+        if !(0.5..2.0).contains(&scale) {
+            return None;
+        }
+        fitted.set_channel(ch+4, scale, zero);
+    }
+    Some(fitted)
+}
+
+// Average of SWEEP_AVERAGE input readings, 1ms apart.
+fn average_inputs(pmod: &EurorackPmod0) -> [i32; 4] {
+    let mut sum = [0i32; 4];
+    for _ in 0..SWEEP_AVERAGE {
+        riscv::asm::delay(pac::clock::sysclk() / 1000);
+        let sample_i = pmod.sample_i();
+        for ch in 0..4 {
+            sum[ch] += sample_i[ch];
         }
     }
+    sum.map(|s| s / SWEEP_AVERAGE)
+}
+
+// Drive each output with its own DC level and check it reads back on the
+// input of the same number. Expects outputs looped back to inputs.
+fn audio_loopback_test(pmod: &EurorackPmod0, stimulus: [i32; 4]) -> [bool; 4] {
+    let r = &pmod.registers;
+    let write_outputs = |o: [i32; 4]| {
+        r.sample_o0().write(|w| unsafe { w.sample().bits(o[0] as u32) } );
+        r.sample_o1().write(|w| unsafe { w.sample().bits(o[1] as u32) } );
+        r.sample_o2().write(|w| unsafe { w.sample().bits(o[2] as u32) } );
+        r.sample_o3().write(|w| unsafe { w.sample().bits(o[3] as u32) } );
+    };
+    write_outputs(stimulus);
+    // Let the DAC and ADC filters settle (~10ms), then average a few reads.
+    riscv::asm::delay(pac::clock::sysclk() / 100);
+    let average = average_inputs(pmod);
+    write_outputs([0; 4]);
+    let tolerance = pmod.counts_per_v() / LOOPBACK_TOLERANCE_DIV;
+    core::array::from_fn(|ch| {
+        let readback = average[ch];
+        info!("audio/loopback: ch{} stimulus={} readback={}", ch, stimulus[ch], readback);
+        (readback - stimulus[ch]).abs() < tolerance
+    })
+}
+
+fn print_loopback(s: &mut ReportString, jack: u8, pass: [bool; 4]) {
+    write!(s, "loopback     ").ok();
+    for ch in 0..4 {
+        // Inputs are jack bits 0-3, outputs 4-7.
+        let patched = (jack >> ch) & (jack >> (ch + 4)) & 1 != 0;
+        let result = match (patched, pass[ch]) {
+            (false, _)    => "SKIP",
+            (true, true)  => "PASS",
+            (true, false) => "FAIL",
+        };
+        write!(s, " [ch{}={}]", ch, result).ok();
+    }
+    write!(s, "\r\n").ok();
 }
 
 fn push_to_opts(constants: &CalibrationConstants, options: &mut Opts, d: &DefaultCalibrationConstants) {
@@ -403,6 +669,7 @@ fn main() -> ! {
 
     // initialize logging
     let serial = Serial0::new(peripherals.UART0);
+    logger::enable_recent_lines();
     tiliqua_fw::handlers::logger_init(serial);
 
     let sysclk = pac::clock::sysclk();
@@ -421,20 +688,44 @@ fn main() -> ! {
     let mut pmod = EurorackPmod0::new(peripherals.PMOD0_PERIPH);
     let dtr = peripherals.DTR0;
 
+    let spiflash = SPIFlash0::new(
+        peripherals.SPIFLASH_CTRL,
+        SPIFLASH_BASE,
+        SPIFLASH_SZ_BYTES
+    );
+
+    //
+    // Load options first, they decide which diagnostics to skip.
+    //
+
+    let mut opts = Opts::default();
+    let mut flash_persist_opt = if let Some(storage_window) = bootinfo.manifest.get_option_storage_window() {
+        let mut flash_persist = FlashOptionsPersistence::new(VerifiedFlash::new(spiflash), storage_window);
+        flash_persist.load_options(&mut opts).unwrap();
+        Some(flash_persist)
+    } else {
+        warn!("No option storage region: disable persistent storage");
+        None
+    };
+
     let mut startup_report = ReportString::new();
 
-    psram_memtest(&mut startup_report, &mut timer);
-    spiflash_memtest(&mut startup_report, &mut timer);
+    let gate = DiagnosticGate::new(opts.diag.mode.value == DiagMode::Full);
+    gate.run(&mut startup_report, "PSRAM memtest", opts.diag.psram.value == RunSkip::Skip,
+             |s| psram_memtest(s, &mut timer));
+    gate.run(&mut startup_report, "SPIFLASH memtest", opts.diag.spiflash.value == RunSkip::Skip,
+             |s| spiflash_memtest(s, &mut timer));
     tusb322_id_test(&mut startup_report, &mut i2cdev);
     print_touch_err(&mut startup_report, &pmod);
     eeprom_id_test(&mut startup_report, &mut i2cdev1);
-    edid_test(&mut startup_report, &mut i2cdev);
+    i2c_bus_map(&mut startup_report, "i2c0", &mut i2cdev);
+    i2c_bus_map(&mut startup_report, "i2c1", &mut i2cdev1);
+    gate.run(&mut startup_report, "EDID", opts.diag.edid.value == RunSkip::Skip,
+             |s| edid_test(s, &mut i2cdev));
 
     timer.disable();
     timer.delay_ns(0);
 
-
-    let mut opts = Opts::default();
     let cal_default = DefaultCalibrationConstants::from_array(
         &PMOD_DEFAULT_CAL, pmod.f_bits());
     if let Some(cal_constants) = CalibrationConstants::from_eeprom(&mut i2cdev1) {
@@ -467,10 +758,23 @@ fn main() -> ! {
     let mut last_hpd = display.get_hpd();
 
     let mut benchmark_rng = Rng::with_seed(0);
-
-    use tiliqua_hal::cy8cmbr3xxx::Cy8cmbr3108Driver;
+    let mut benchmark_last_log_ms = 0u64;
+
+    let mut siggen = SignalGenerator::new(SIGGEN_FS);
+    let mut cal_writer = CalibrationWriter::new();
+    let mut wizard = Wizard::new();
+    let mut last_siggen_output = SiggenOutput::default();
+    // Jack state when the loopback test last ran, and its per-channel result.
+    let mut loopback_result: Option<(u8, [bool; 4])> = None;
+    // Last PSRAM bandwidth sweep, if any.
+    let mut sweep_report = ReportString::new();
+
+    use tiliqua_hal::cy8cmbr3xxx::{Cy8cmbr3108Driver, SensorCounts};
     let i2cdev_cy8 = I2c1::new(unsafe { pac::I2C1::steal() } );
     let mut cy8 = Cy8cmbr3108Driver::new(i2cdev_cy8, &TOUCH_SENSOR_ORDER);
+    // Touch sensor counts by jack, and which of them to update next.
+    let mut touch_counts = [SensorCounts::default(); 8];
+    let mut touch_phase = 0usize;
 
     let mut last_jack = pmod.jack();
 
@@ -490,7 +794,11 @@ fn main() -> ! {
         let h_active = display.size().width;
         let v_active = display.size().height;
 
+        let mut scanout = ScanoutMeter::new();
+
         loop {
+            scanout.poll(display.scanout_stats(), timer.uptime_ms());
+
             let dvi_hpd = display.get_hpd();
             if last_hpd != dvi_hpd {
                 info!("dvi_hpd: display hotplug! new state: {}", dvi_hpd);
@@ -502,19 +810,48 @@ fn main() -> ! {
             }
             last_jack = pmod.jack();
 
-            let (opts, commit_to_eeprom) = critical_section::with(|cs| {
+            let (opts, digit_edit, commit_to_eeprom, save_opts, sweep, loopback, bw_sweep,
+                 wizard_next, wizard_restart) = critical_section::with(|cs| {
                 let mut app = app.borrow_ref_mut(cs);
                 let commit_to_eeprom = app.ui.opts.autocal.write.poll();
-                (app.ui.opts.clone(), commit_to_eeprom)
+                let save_opts = app.ui.opts.diag.save_opts.poll();
+                let bw_sweep = app.ui.opts.diag.bw_sweep.poll();
+                let sweep = app.ui.opts.autocal.sweep.poll();
+                let cables_patched = app.ui.opts.autocal.cables.value == LoopbackCables::Patched;
+                app.ui.opts.autocal.loopback.set_enabled(cables_patched);
+                let loopback = app.ui.opts.autocal.loopback.poll() && cables_patched;
+                let wizard_next = app.ui.opts.wizard.next.poll();
+                let wizard_restart = app.ui.opts.wizard.restart.poll();
+                (app.ui.opts.clone(), app.ui.digit_edit(), commit_to_eeprom, save_opts, sweep, loopback, bw_sweep,
+                 wizard_next, wizard_restart)
             });
 
+            if bw_sweep {
+                sweep_report.clear();
+                psram_sweep(&mut sweep_report, &timer);
+            }
+
+            if save_opts {
+                if let Some(ref mut flash_persist) = flash_persist_opt {
+                    flash_persist.save_options(&opts).unwrap();
+                }
+            }
+
             let counts_per_v = pmod.counts_per_v();
-            let stimulus_raw = counts_per_v * opts.autocal.volts.value as i32;
+            let stimulus_raw = if opts.tracker.page.value == Page::Wizard {
+                wizard.stimulus(counts_per_v)
+            } else {
+                counts_per_v * opts.autocal.volts.value as i32
+            };
 
             draw::draw_options(&mut display, &opts, h_active/2-30, 70,
                                hue).ok();
+            if let Some(edit) = digit_edit {
+                draw::draw_spinbox(&mut display, h_active/2+118, 46, &edit, hue).ok();
+            }
             draw::draw_name(&mut display, h_active/2, 30, hue,
-                            &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline).ok();
+                            &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline,
+                            bootinfo.manifest.built_unix).ok();
 
             if opts.tracker.page.value == Page::Report {
                 let mut status_report = ReportString::new();
@@ -528,12 +865,28 @@ fn main() -> ! {
                         });
                         print_die_temperature(&mut status_report, &dtr);
                         print_psram_stats(&mut status_report, &psram);
+                        if let Some((jack, pass)) = loopback_result {
+                            print_loopback(&mut status_report, jack, pass);
+                        }
                         write!(&mut status_report, "dvi_hpd [active={}]\r\n", dvi_hpd).ok();
+                        print_scanout(&mut status_report, &scanout, &modeline);
                         write!(&mut status_report, "ex0={:08b} ex1={:08b}\r\n",
                                gpio0.input().read().bits(),
                                gpio1.input().read().bits()).ok();
                         &status_report
                     }
+                    ReportPage::Bandwidth => {
+                        if sweep_report.is_empty() {
+                            write!(&mut status_report, "run diag/bw-sweep first\r\n").ok();
+                            &status_report
+                        } else {
+                            &sweep_report
+                        }
+                    }
+                    ReportPage::Log => {
+                        print_recent_log(&mut status_report);
+                        &status_report
+                    }
                 };
                 if let Some(ref help) = bootinfo.manifest.help {
                     draw::draw_tiliqua(&mut display, (h_active/2-80) as i32, (v_active/2-250) as i32, hue,
@@ -549,13 +902,39 @@ fn main() -> ! {
                 ).draw(&mut display).ok();
             }
 
-            if opts.tracker.page.value == Page::Autocal {
+            if opts.tracker.page.value == Page::Wizard {
+                let (progress, prompt) = wizard.prompt();
+                draw::draw_cal_wizard(&mut display, h_active/2, v_active/2-170, hue,
+                                      progress, prompt).ok();
+            }
+
+            if opts.tracker.page.value == Page::Autocal ||
+               opts.tracker.page.value == Page::Wizard {
                 pmod.registers.sample_o0().write(|w| unsafe { w.sample().bits(stimulus_raw as u32) } );
                 pmod.registers.sample_o1().write(|w| unsafe { w.sample().bits(stimulus_raw as u32) } );
                 pmod.registers.sample_o2().write(|w| unsafe { w.sample().bits(stimulus_raw as u32) } );
                 pmod.registers.sample_o3().write(|w| unsafe { w.sample().bits(stimulus_raw as u32) } );
             }
 
+            if opts.tracker.page.value == Page::Siggen {
+                let output = opts.siggen.output.value;
+                if output != last_siggen_output {
+                    // Don't leave the previous output stuck at some level.
+                    siggen.set_amplitude(0);
+                    siggen.waveform = Waveform::Dc;
+                    siggen_burst(&mut siggen, &pmod, &timer, last_siggen_output);
+                    last_siggen_output = output;
+                }
+                siggen.waveform = opts.siggen.wave.value;
+                siggen.set_frequency(opts.siggen.freq.value as u32);
+                siggen.set_amplitude(if opts.siggen.enabled.value == StopRun::Run {
+                    counts_per_v * opts.siggen.level.value as i32 / 1000
+                } else {
+                    0
+                });
+                siggen_burst(&mut siggen, &pmod, &timer, output);
+            }
+
             if opts.tracker.page.value == Page::Benchmark {
                 let fps = {
                     // TODO: use the dedicated timer instead of abusing the PSRAM stats
@@ -567,7 +946,12 @@ fn main() -> ! {
                 };
                 let mut ops_per_loop = 0u32;
                 if opts.benchmark.enabled.value == StopRun::Run {
-                    use options::BenchmarkType;
+                    use options::{BenchmarkType, Batching};
+                    if opts.benchmark.batch.value == Batching::On {
+                        display.begin_batch();
+                    }
+                    // Benchmark text is placed right up to the edges.
+                    let mut display = ClippedDrawTarget::active_area(&mut display);
                     match opts.benchmark.test_type.value {
                         BenchmarkType::Lines => {
                             ops_per_loop = 150;
@@ -585,10 +969,51 @@ fn main() -> ! {
                             ops_per_loop = 1;
                             draw::draw_benchmark_unicode(&mut display, ops_per_loop, &mut benchmark_rng).ok();
                         },
+                        BenchmarkType::Fill => {
+                            ops_per_loop = 10;
+                            draw::draw_benchmark_fill(&mut display, ops_per_loop, &mut benchmark_rng).ok();
+                        },
                     }
                 }
+                display.end_batch();
                 draw::draw_benchmark_stats(&mut display, h_active/2-50, v_active-50, hue,
                                            fps, fps*ops_per_loop).ok();
+                // Also on serial, to compare batching on/off from a capture.
+                let now_ms = timer.uptime_ms();
+                if ops_per_loop != 0 && now_ms - benchmark_last_log_ms >= 1000 {
+                    let test_type: &'static str = opts.benchmark.test_type.value.into();
+                    let batch: &'static str = opts.benchmark.batch.value.into();
+                    info!("benchmark: {} batch={} ops/sec={}", test_type, batch, fps*ops_per_loop);
+                    benchmark_last_log_ms = now_ms;
+                }
+            }
+
+            if opts.tracker.page.value == Page::Touch {
+                if opts.touch.scan.value == StopRun::Run {
+                    // Raw count and baseline are only reported for one sensor at
+                    // a time, after the next scan once it is selected. So give each
+                    // sensor a frame to settle before recording it.
+                    let jack = (touch_phase / 2) % 8;
+                    let sensor = TOUCH_SENSOR_ORDER[jack];
+                    if touch_phase % 2 == 0 {
+                        cy8.read_sensor_raw(sensor).ok();
+                    } else if let (Ok(raw), Ok(baseline)) = (cy8.read_sensor_raw(sensor),
+                                                             cy8.read_sensor_baseline(sensor)) {
+                        touch_counts[jack].raw = raw;
+                        touch_counts[jack].baseline = baseline;
+                    }
+                    touch_phase = touch_phase.wrapping_add(1);
+                    for (jack, sensor) in TOUCH_SENSOR_ORDER.iter().enumerate() {
+                        if let Ok(diff) = cy8.read_sensor_diff(*sensor) {
+                            touch_counts[jack].diff = diff;
+                        }
+                    }
+                }
+                let thresholds = TOUCH_SENSOR_ORDER.map(|sensor| cy8.finger_threshold(sensor));
+                // Bars run past the right edge on narrow displays.
+                draw::draw_touch_counts(&mut ClippedDrawTarget::active_area(&mut display),
+                                        h_active/2-230, v_active/2-100, hue,
+                                        &touch_counts, &thresholds).ok();
             }
 
             //
@@ -623,7 +1048,42 @@ fn main() -> ! {
                 },
                 &cal_default
             );
-            constants.write_to_pmod(&mut pmod);
+            // Don't stall the UI waiting on 8 calibration writes every frame.
+            cal_writer.update(constants.pmod_constants(pmod.f_bits()));
+            cal_writer.poll(&mut pmod);
+
+            if sweep {
+                info!("autocal/sweep: start");
+                match dac_sweep(&constants, &mut siggen, &pmod, &timer) {
+                    Some(fitted) => {
+                        critical_section::with(|cs| {
+                            push_to_opts(&fitted, &mut app.borrow_ref_mut(cs).ui.opts, &cal_default);
+                        });
+                    }
+                    None => warn!("autocal/sweep: fit failed, are outputs looped back to inputs?"),
+                }
+            }
+
+            if wizard_restart {
+                wizard = Wizard::new();
+            }
+
+            if wizard_next {
+                // Outputs have been driven at the stimulus for this step
+                // since it was shown, so the inputs have settled.
+                let readings = average_inputs(&pmod);
+                if let Some(solved) = wizard.press(&constants, counts_per_v, readings) {
+                    critical_section::with(|cs| {
+                        push_to_opts(solved, &mut app.borrow_ref_mut(cs).ui.opts, &cal_default);
+                        solved.write_to_eeprom(&mut i2cdev1);
+                    });
+                }
+            }
+
+            if loopback {
+                let stimulus = LOOPBACK_VOLTS.map(|v| counts_per_v * v);
+                loopback_result = Some((pmod.jack(), audio_loopback_test(&pmod, stimulus)));
+            }
 
             if commit_to_eeprom {
                 critical_section::with(|_| {
@@ -631,7 +1091,10 @@ fn main() -> ! {
                 });
             }
 
-            if opts.tracker.page.value != Page::Report && opts.tracker.page.value != Page::Benchmark {
+            if opts.tracker.page.value != Page::Report &&
+               opts.tracker.page.value != Page::Benchmark &&
+               opts.tracker.page.value != Page::Siggen &&
+               opts.tracker.page.value != Page::Touch {
                 draw::draw_cal(&mut display, h_active/2-128, v_active/2-128, hue,
                                &[stimulus_raw, stimulus_raw, stimulus_raw, stimulus_raw],
                                &pmod.sample_i(), counts_per_v).ok();
@@ -2,15 +2,21 @@ use opts::*;
 use strum_macros::{EnumIter, IntoStaticStr};
 use serde_derive::{Serialize, Deserialize};
 
+use tiliqua_lib::siggen::Waveform;
+
 #[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
 #[strum(serialize_all = "SCREAMING-KEBAB-CASE")]
 pub enum Page {
     #[default]
     Report,
+    Diag,
     Autocal,
+    Wizard,
     TweakAdc,
     TweakDac,
+    Siggen,
     Benchmark,
+    Touch,
 }
 
 #[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
@@ -29,6 +35,27 @@ pub enum ReportPage {
     Startup,
     #[default]
     Status,
+    /// Most recent log lines, for debugging without a serial adapter.
+    Log,
+    /// Results of the last `bw-sweep`.
+    Bandwidth,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum DiagMode {
+    /// Run every diagnostic, ignoring the skip flags.
+    #[default]
+    Full,
+    Custom,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum RunSkip {
+    #[default]
+    Run,
+    Skip,
 }
 
 #[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
@@ -39,10 +66,31 @@ pub enum StopRun {
     Run,
 }
 
+/// The loopback test drives every output, so it waits on the user
+/// confirming each output is patched to the input of the same number.
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum LoopbackCables {
+    #[default]
+    NotPatched,
+    Patched,
+}
+
 int_params!(RefVoltageParams<i8>     { step: 1, min: -10, max: 10 });
 int_params!(CalTweakerParams<i16>    { step: 1, min: -256, max: 256 });
+int_params!(SiggenFreqParams<u16>    { step: 10, min: 10, max: 10000, format: IntFormat::Scaled { divisor: 1, precision: 0, suffix: "Hz" } });
+int_params!(SiggenLevelParams<i16>   { step: 100, min: -8000, max: 8000, format: IntFormat::Scaled { divisor: 1000, precision: 1, suffix: "V" } });
 
-button_params!(OneShotButtonParams { mode: ButtonMode::OneShot });
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum SiggenOutput {
+    #[default]
+    All,
+    Out0,
+    Out1,
+    Out2,
+    Out3,
+}
 
 #[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
 #[strum(serialize_all = "kebab-case")]
@@ -52,6 +100,17 @@ pub enum BenchmarkType {
     Text,
     Pixels,
     Unicode,
+    Fill,
+}
+
+/// Whether benchmark draws are batched (see `DMAFramebuffer0::begin_batch`),
+/// to compare ops/sec with and without batching.
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Batching {
+    #[default]
+    Off,
+    On,
 }
 
 #[derive(OptionPage, Clone)]
@@ -60,6 +119,22 @@ pub struct ReportOpts {
     pub page: EnumOption<ReportPage>,
 }
 
+#[derive(OptionPage, Clone)]
+pub struct DiagOpts {
+    #[option]
+    pub mode: EnumOption<DiagMode>,
+    #[option]
+    pub psram: EnumOption<RunSkip>,
+    #[option]
+    pub spiflash: EnumOption<RunSkip>,
+    #[option]
+    pub edid: EnumOption<RunSkip>,
+    #[option]
+    pub bw_sweep: ActionOption,
+    #[option(false)]
+    pub save_opts: ActionOption,
+}
+
 #[derive(OptionPage, Clone)]
 pub struct AutocalOpts {
     #[option]
@@ -69,7 +144,22 @@ pub struct AutocalOpts {
     #[option]
     pub autozero: EnumOption<StopRun>,
     #[option]
-    pub write: ButtonOption<OneShotButtonParams>,
+    pub sweep: ActionOption,
+    #[option]
+    pub write: ActionOption,
+    #[option]
+    pub cables: EnumOption<LoopbackCables>,
+    #[option]
+    pub loopback: ActionOption,
+}
+
+/// Guided alternative to `AutocalOpts`, see `calibration::Wizard`.
+#[derive(OptionPage, Clone)]
+pub struct WizardOpts {
+    #[option]
+    pub next: ActionOption,
+    #[option]
+    pub restart: ActionOption,
 }
 
 #[derive(OptionPage, Clone)]
@@ -92,12 +182,34 @@ pub struct CalOpts {
     pub scale3: IntOption<CalTweakerParams>,
 }
 
+#[derive(OptionPage, Clone)]
+pub struct SiggenOpts {
+    #[option]
+    pub wave: EnumOption<Waveform>,
+    #[option]
+    pub output: EnumOption<SiggenOutput>,
+    #[option(1000)]
+    pub freq: IntOption<SiggenFreqParams>,
+    #[option(1000)]
+    pub level: IntOption<SiggenLevelParams>,
+    #[option]
+    pub enabled: EnumOption<StopRun>,
+}
+
 #[derive(OptionPage, Clone)]
 pub struct BenchmarkOpts {
     #[option]
     pub test_type: EnumOption<BenchmarkType>,
     #[option]
     pub enabled: EnumOption<StopRun>,
+    #[option]
+    pub batch: EnumOption<Batching>,
+}
+
+#[derive(OptionPage, Clone)]
+pub struct TouchOpts {
+    #[option]
+    pub scan: EnumOption<StopRun>,
 }
 
 #[derive(Options, Clone)]
@@ -105,12 +217,20 @@ pub struct Opts {
     pub tracker: ScreenTracker<Page>,
     #[page(Page::Report)]
     pub report: ReportOpts,
+    #[page(Page::Diag)]
+    pub diag: DiagOpts,
     #[page(Page::Autocal)]
     pub autocal: AutocalOpts,
+    #[page(Page::Wizard)]
+    pub wizard: WizardOpts,
     #[page(Page::TweakAdc)]
     pub caladc: CalOpts,
     #[page(Page::TweakDac)]
     pub caldac: CalOpts,
+    #[page(Page::Siggen)]
+    pub siggen: SiggenOpts,
     #[page(Page::Benchmark)]
     pub benchmark: BenchmarkOpts,
+    #[page(Page::Touch)]
+    pub touch: TouchOpts,
 }
@@ -5,6 +5,23 @@
 Collect some information about Tiliqua health, display it on the video
 output and log it over serial. This is mostly used to check for
 hardware issues and for calibration.
+
+Slow startup diagnostics (PSRAM/SPIFLASH memtests, EDID) can be skipped on
+the DIAG page for faster boots once a unit is known-good. Skip flags only
+apply in 'custom' mode and must be saved with 'save-opts'. The default
+'full' mode always runs everything. Skipped diagnostics show as 'SKIP' in
+the startup report.
+
+DIAG/bw-sweep measures PSRAM throughput across access patterns (sequential,
+strided, random) and burst sizes, shown on the 'bandwidth' report page.
+It runs alongside the framebuffer and audio, so a cell with collapsed
+throughput points at contention in the memory controller.
+
+The SIGGEN page emits test signals (sine, square, noise or DC) on one or
+all outputs, through the calibrated DAC path, for checking downstream gear.
+These samples are written by the CPU at 16kHz, so they are not as clean as
+a gateware oscillator, and the outputs briefly hold their value while the
+screen is redrawn.
 """
 
 import os
@@ -96,4 +113,5 @@ if __name__ == "__main__":
                   argparse_fragment=lambda _: {
                       # direct codec output registers
                       "poke_outputs": True,
-                  })
+                  },
+                  archiver_callback=lambda archiver: archiver.with_option_storage())
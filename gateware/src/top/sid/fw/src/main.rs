@@ -15,6 +15,7 @@ use tiliqua_lib::color::HI8;
 use pac::constants::*;
 use tiliqua_hal::pmod::EurorackPmod;
 use tiliqua_hal::persist::Persist;
+use tiliqua_hal::spiflash::VerifiedFlash;
 
 use tiliqua_hal::embedded_graphics::{
     prelude::*,
@@ -213,7 +214,7 @@ fn main() -> ! {
 
     let mut opts = options::Opts::default();
     let mut flash_persist_opt = if let Some(storage_window) = bootinfo.manifest.get_option_storage_window() {
-        let mut flash_persist = FlashOptionsPersistence::new(spiflash, storage_window);
+        let mut flash_persist = FlashOptionsPersistence::new(VerifiedFlash::new(spiflash), storage_window);
         flash_persist.load_options(&mut opts).unwrap();
         Some(flash_persist)
     } else {
@@ -244,7 +245,10 @@ fn main() -> ! {
         let h_active = display.size().width;
         let v_active = display.size().height;
 
+        handlers::WATCHDOG.start(handlers::WATCHDOG_TIMEOUT_MS);
+
         loop {
+            handlers::WATCHDOG.feed();
             let (opts, save_opts, wipe_opts) = critical_section::with(|cs| {
                 let mut app = app.borrow_ref_mut(cs);
                 let save_opts = app.ui.opts.misc.save_opts.poll();
@@ -275,7 +279,8 @@ fn main() -> ! {
                 persist.set_persistence(64);
                 draw::draw_options(&mut display, &opts, h_active/2-30, v_active-100, hue).ok();
                 draw::draw_name(&mut display, h_active/2, v_active-50, hue,
-                                &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline).ok();
+                                &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline,
+                                bootinfo.manifest.built_unix).ok();
                 draw::draw_help_page(&mut display,
                     MODULE_DOCSTRING,
                     bootinfo.manifest.help.as_ref(),
@@ -287,7 +292,8 @@ fn main() -> ! {
                 persist.set_persistence(15);
                 draw::draw_options(&mut display, &opts, 100, v_active/2, hue).ok();
                 draw::draw_name(&mut display, h_active/2, v_active-50, hue,
-                                &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline).ok();
+                                &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline,
+                                bootinfo.manifest.built_unix).ok();
 
                 // Draw SID visualization
                 let hl_wfm: Option<u8> = match opts.tracker.page.value {
@@ -75,19 +75,22 @@ impl ModulationTarget {
     }
 }
 
-
-int_params!(FrequencyParams<u16>    { step: 125, min: 0,      max: 65500 });
 int_params!(FreqOffsetParams<u16>   { step: 10,  min: 500,    max: 2000 });
 int_params!(PulseWidthParams<u16>   { step: 128, min: 0,      max: 4096 });
 int_params!(EnvelopeParams<u8>      { step: 1,   min: 0,      max: 15 });
 int_params!(BinaryParams<u8>        { step: 1,   min: 0,      max: 1 });
 int_params!(CutoffParams<u16>       { step: 100, min: 0,      max: 2000 });
 int_params!(VolumeParams<u8>        { step: 1,   min: 0,      max: 15 });
-int_params!(TriggerLevelParams<i16> { step: 512, min: -16384, max: 16384 });
 int_params!(PositionParams<i16>     { step: 25,  min: -500,   max: 500 });
 int_params!(ScrollParams<u8>        { step: 1,   min: 0,      max: 60 });
 
-button_params!(OneShotButtonParams { mode: ButtonMode::OneShot });
+// SID oscillator frequency register, clocked at ~1MHz (phi2).
+scaled_int_params!(FrequencyParams<u16> { step: 125, min: 0, max: 65500, unit: "Hz",
+                   to_display: |raw: u16| raw as f32 * (1_000_000.0 / 16_777_216.0) });
+// Audio samples are 4 counts/mV.
+scaled_int_params!(TriggerLevelParams<i16> { step: 512, min: -16384, max: 16384, unit: "mV",
+                   to_display: |raw: i16| raw as f32 / 4.0 });
+
 
 #[derive(OptionPage, Clone)]
 pub struct HelpOpts {
@@ -98,15 +101,15 @@ pub struct HelpOpts {
 #[derive(OptionPage, Clone)]
 pub struct MiscOpts {
     #[option(false)]
-    pub save_opts: ButtonOption<OneShotButtonParams>,
+    pub save_opts: ActionOption,
     #[option(false)]
-    pub wipe_opts: ButtonOption<OneShotButtonParams>,
+    pub wipe_opts: ActionOption,
 }
 
 #[derive(OptionPage, Clone)]
 pub struct VoiceOpts {
     #[option(1000)]
-    pub freq: IntOption<FrequencyParams>,
+    pub freq: ScaledIntOption<FrequencyParams>,
     #[option(1000)]
     pub freq_os: IntOption<FreqOffsetParams>,
     #[option(2048)]
@@ -160,7 +163,7 @@ pub struct ScopeOpts {
     #[option]
     pub trig_mode: EnumOption<TriggerMode>,
     #[option]
-    pub trig_lvl: IntOption<TriggerLevelParams>,
+    pub trig_lvl: ScaledIntOption<TriggerLevelParams>,
     #[option(150)]
     pub ypos0: IntOption<PositionParams>,
     #[option(-150)]
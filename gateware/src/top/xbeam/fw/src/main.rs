@@ -13,6 +13,7 @@ use midi_convert::parse::MidiTryParseSlice;
 use tiliqua_fw::*;
 use tiliqua_lib::*;
 use tiliqua_lib::dsp::OnePoleSmoother;
+use tiliqua_lib::meter::LevelMeter;
 use pac::constants::*;
 use tiliqua_lib::calibration::*;
 
@@ -24,11 +25,16 @@ use opts::{Options, OptionTrait};
 use opts::cc_map::{MidiCcMapper, CcMapMode};
 use hal::pca9635::Pca9635Driver;
 use tiliqua_hal::dma_framebuffer::Rotate;
-use tiliqua_hal::tusb322::{TUSB322Driver, TUSB322Mode, AttachedState};
+use tiliqua_hal::tusb322::{TUSB322Driver, TUSB322Mode};
 use tiliqua_hal::persist::Persist;
+use tiliqua_hal::spiflash::VerifiedFlash;
 
 pub const TIMER0_ISR_PERIOD_MS: u32 = 5;
 
+// Input meters take one sample of each input per timer tick.
+const METER_WINDOW_MS: u32 = 100;
+const METER_PEAK_HOLD_MS: u32 = 1000;
+
 fn global_index(opts: &Opts, opt: &dyn OptionTrait) -> usize {
     let key = opt.key().value();
     opts.all().enumerate()
@@ -60,12 +66,14 @@ fn build_cc_mapper(opts: &Opts) -> MidiCcMapper {
     m.add(31, global_index(opts, &opts.delay.delay_y),    CcMapMode::Absolute);
     m.add(32, global_index(opts, &opts.delay.delay_i),    CcMapMode::Absolute);
     m.add(33, global_index(opts, &opts.delay.delay_c),    CcMapMode::Absolute);
-    // Beam page (CC 40-45, CC 41 formerly decay now unused)
+    // Beam page (CC 40-47, CC 41 formerly decay now unused)
     m.add(40, global_index(opts, &opts.beam.persist),     CcMapMode::Absolute);
     m.add(42, global_index(opts, &opts.beam.ui_hue),      CcMapMode::Absolute);
     m.add(43, global_index(opts, &opts.beam.palette),     CcMapMode::Absolute);
     m.add(44, global_index(opts, &opts.beam.grid),        CcMapMode::Absolute);
     m.add(45, global_index(opts, &opts.beam.grid_i),      CcMapMode::Absolute);
+    m.add(46, global_index(opts, &opts.beam.layout),      CcMapMode::Absolute);
+    m.add(47, global_index(opts, &opts.beam.gamma),       CcMapMode::Absolute);
     // Misc page (CC 50-52)
     m.add(50, global_index(opts, &opts.misc.plot_type),   CcMapMode::Absolute);
     m.add(51, global_index(opts, &opts.misc.plot_src),    CcMapMode::Absolute);
@@ -90,6 +98,7 @@ fn build_cc_mapper(opts: &Opts) -> MidiCcMapper {
 struct App {
     ui: ui::UI<Encoder0, EurorackPmod0, I2c0, Opts>,
     cc_mapper: MidiCcMapper,
+    meter: LevelMeter<4>,
 }
 
 impl App {
@@ -100,10 +109,14 @@ impl App {
         let pca9635 = Pca9635Driver::new(i2cdev);
         let pmod = EurorackPmod0::new(peripherals.PMOD0_PERIPH);
         let cc_mapper = build_cc_mapper(&opts);
+        let mut ui = ui::UI::new(opts, TIMER0_ISR_PERIOD_MS,
+                                 encoder, pca9635, pmod);
+        ui.set_digit_edit(true);
         Self {
-            ui: ui::UI::new(opts, TIMER0_ISR_PERIOD_MS,
-                            encoder, pca9635, pmod),
+            ui,
             cc_mapper,
+            meter: LevelMeter::new(METER_WINDOW_MS / TIMER0_ISR_PERIOD_MS,
+                                   METER_PEAK_HOLD_MS / TIMER0_ISR_PERIOD_MS),
         }
     }
 }
@@ -113,6 +126,12 @@ fn timer0_handler(app: &Mutex<RefCell<App>>) {
         let mut app = app.borrow_ref_mut(cs);
         app.ui.update();
 
+        if app.ui.opts.misc.meters.value == Meters::On {
+            // Calibrated input samples, same as the scope sees.
+            let samples = app.ui.pmod.sample_i();
+            app.meter.feed(&samples);
+        }
+
         // Check for TRS MIDI CC traffic
         let xbeam = unsafe { pac::XBEAM_PERIPH::steal() };
         let midi_word = xbeam.midi_read().read().bits();
@@ -200,7 +219,7 @@ fn main() -> ! {
     let mut opts = Opts::default();
     opts.misc.rotation.value = modeline.rotate.clone();
     let mut flash_persist_opt = if let Some(storage_window) = bootinfo.manifest.get_option_storage_window() {
-        let mut flash_persist = FlashOptionsPersistence::new(spiflash, storage_window);
+        let mut flash_persist = FlashOptionsPersistence::new(VerifiedFlash::new(spiflash), storage_window);
         flash_persist.load_options(&mut opts).unwrap();
         Some(flash_persist)
     } else {
@@ -213,6 +232,8 @@ fn main() -> ! {
     //
 
     let mut last_palette = opts.beam.palette.value;
+    let mut last_layout = opts.beam.layout.value;
+    let mut last_gamma = opts.beam.gamma.value;
     let app = Mutex::new(RefCell::new(App::new(opts)));
 
     handler!(timer0 = || timer0_handler(&app));
@@ -242,23 +263,36 @@ fn main() -> ! {
             w.offset_y().bits((dvi_h / 2) as u16)
         });
 
+        handlers::WATCHDOG.start(handlers::WATCHDOG_TIMEOUT_MS);
+
         loop {
+            handlers::WATCHDOG.feed();
 
             let h_active = display.size().width;
             let v_active = display.size().height;
 
-            let (opts, draw_options, save_opts, wipe_opts) = critical_section::with(|cs| {
+            let (opts, draw_options, digit_edit, save_opts, wipe_opts, levels) = critical_section::with(|cs| {
                 let mut app = app.borrow_ref_mut(cs);
                 let save_opts = app.ui.opts.misc.save_opts.poll();
                 let wipe_opts = app.ui.opts.misc.wipe_opts.poll();
-                (app.ui.opts.clone(), app.ui.draw(), save_opts, wipe_opts)
+                // Trigger level does nothing while free-running.
+                let free_running = app.ui.opts.scope2.trig_mode.value == TriggerMode::Always;
+                app.ui.opts.scope2.trig_lvl.set_enabled(!free_running);
+                (app.ui.opts.clone(), app.ui.draw(), app.ui.digit_edit(), save_opts, wipe_opts,
+                 app.meter.levels())
             });
 
             let on_help_page = opts.tracker.page.value == Page::Help;
 
-            if opts.beam.palette.value != last_palette || first {
-                opts.beam.palette.value.write_to_hardware(&mut display);
+            if opts.beam.palette.value != last_palette ||
+               opts.beam.layout.value != last_layout ||
+               opts.beam.gamma.value != last_gamma || first {
+                opts.beam.palette.value.write_to_hardware_with_gamma(
+                    &mut display, opts.beam.layout.value,
+                    opts.beam.gamma.value as f32 / 10.0f32);
                 last_palette = opts.beam.palette.value;
+                last_layout = opts.beam.layout.value;
+                last_gamma = opts.beam.gamma.value;
             }
 
             if draw_options || on_help_page {
@@ -268,8 +302,16 @@ fn main() -> ! {
                     (h_active-200, v_active/2)
                 };
                 draw::draw_options(&mut display, &opts, x, y, opts.beam.ui_hue.value).ok();
+                if let Some(edit) = digit_edit {
+                    draw::draw_spinbox(&mut display, x+148, y-24, &edit, opts.beam.ui_hue.value).ok();
+                }
                 draw::draw_name(&mut display, h_active/2, v_active-50, opts.beam.ui_hue.value,
-                                &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline).ok();
+                                &bootinfo.manifest.name, &bootinfo.manifest.tag, &modeline,
+                                bootinfo.manifest.built_unix).ok();
+            }
+
+            if opts.misc.meters.value == Meters::On && !on_help_page {
+                draw::draw_meters(&mut display, 20, 30, opts.beam.ui_hue.value, &levels).ok();
             }
 
             if on_help_page {
@@ -306,8 +348,9 @@ fn main() -> ! {
             let (ppd_x, ppd_y) = vscope.pixels_per_div();
             vscope.set_xoffset_px(opts.vector.x_offset.value * (ppd_x / 4) as i16);
             vscope.set_yoffset_px(opts.vector.y_offset.value * (ppd_y / 4) as i16);
-            vscope.set_xscale(opts.vector.x_scale.value);
-            vscope.set_yscale(opts.vector.y_scale.value);
+            let in_scale = opts.misc.in_scale.value;
+            vscope.set_xscale(in_scale.adjust_vscale(opts.vector.x_scale.value));
+            vscope.set_yscale(in_scale.adjust_vscale(opts.vector.y_scale.value));
             vscope.set_pscale(opts.vector.i_scale.value);
             vscope.set_intensity(opts.vector.i_offset.value);
             vscope.set_cscale(opts.vector.c_scale.value);
@@ -316,7 +359,7 @@ fn main() -> ! {
             scope.set_hue(opts.scope2.hue.value);
             scope.set_intensity(opts.scope2.intensity.value);
             scope.set_trigger_level(opts.scope2.trig_lvl.value);
-            scope.set_yscale(opts.scope2.yscale.value);
+            scope.set_yscale(in_scale.adjust_vscale(opts.scope2.yscale.value));
             let xscale_bits: u8 = match opts.scope2.xzoom.value {
                 XZoom::Half   => 7,
                 XZoom::Normal => 6,
@@ -339,20 +382,21 @@ fn main() -> ! {
 
             // Only connect USB PHY if the TUSB322 Type-C controller says we are attached.
             // This fixes enumeration issues on some machines when using typec <-> typec cables.
+            // Debounced, so a bouncing CC line doesn't flap the PHY mid-enumeration.
             critical_section::with(|_| {
-                if let Ok(status) = tusb322.read_connection_status_control() {
-                    // Only update on valid reads to reduce risk of unintended toggling mid-stream
-                    let new_state = status.attached_state == AttachedState::AttachedSnk;
-                    if new_state != usb_cc_attached {
-                        info!("USB CC hotplug: {:?}", status);
-                        usb_cc_attached = new_state;
-                    }
+                if let Some(attached) = tusb322.attached_stable(timer.uptime_ms()) {
+                    info!("USB CC hotplug: attached={}", attached);
+                    usb_cc_attached = attached;
                 }
             });
 
             xbeam_mux.flags().write(
                 |w| { w.usb_en().bit(opts.misc.usb_mode.value == USBMode::Enable);
                       w.show_outputs().bit(opts.misc.plot_src.value == PlotSrc::Outputs);
+                      // Swap is applied before the inverts, see top.py.
+                      w.x_invert().bit(opts.vector.x_invert.value == AxisFlip::On);
+                      w.y_invert().bit(opts.vector.y_invert.value == AxisFlip::On);
+                      w.xy_swap().bit(opts.vector.xy_swap.value == AxisFlip::On);
                       w.usb_connect().bit(usb_cc_attached)
                 } );
 
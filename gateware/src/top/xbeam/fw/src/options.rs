@@ -1,7 +1,8 @@
 use opts::*;
 use strum_macros::{EnumIter, IntoStaticStr};
-use tiliqua_lib::palette::ColorPalette;
+use tiliqua_lib::palette::{ColorPalette, PaletteLayout};
 pub use tiliqua_lib::scope::{Timebase, VScale};
+pub use tiliqua_lib::scaling::InputScale;
 use tiliqua_hal::dma_framebuffer::Rotate;
 use tiliqua_pac::constants::AUDIO_FS;
 use serde_derive::{Serialize, Deserialize};
@@ -76,6 +77,22 @@ pub enum CcHighlight {
     On,
 }
 
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Meters {
+    #[default]
+    Off,
+    On,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
+#[strum(serialize_all = "kebab-case")]
+pub enum AxisFlip {
+    #[default]
+    Off,
+    On,
+}
+
 #[derive(Default, Clone, Copy, PartialEq, EnumIter, IntoStaticStr, Serialize, Deserialize)]
 #[strum(serialize_all = "kebab-case")]
 pub enum XZoom {
@@ -96,10 +113,9 @@ int_params!(HueParams<u8>         { step: 1, min: 0, max: 15 });
 int_params!(TriggerLvlParams<i16> { step: 500, min: -16000, max: 16000, format: IntFormat::Scaled { divisor: 4000, precision: 2, suffix: "V" } });
 int_params!(PosParams<i16>       { step: 1, min: -40, max: 40, format: IntFormat::Scaled { divisor: 4, precision: 2, suffix: "d" } });
 int_params!(ScrollParams<u8>      { step: 1, min: 0, max: 125 });
+int_params!(GammaParams<u8>       { step: 1, min: 4, max: 30, format: IntFormat::Scaled { divisor: 10, precision: 1, suffix: "" } });
 int_params!(NChannelsParams<u8>   { step: 1, min: 1, max: 4 });
 
-button_params!(OneShotButtonParams { mode: ButtonMode::OneShot });
-
 #[derive(OptionPage, Clone)]
 pub struct HelpOpts {
     #[option(0)]
@@ -124,6 +140,14 @@ pub struct VectorOpts {
     pub c_offset: IntOption<HueParams>,
     #[option(0)]
     pub c_scale: IntOption<PCScaleParams>,
+    #[option]
+    pub axes: GroupOption,
+    #[option]
+    pub x_invert: EnumOption<AxisFlip>,
+    #[option]
+    pub y_invert: EnumOption<AxisFlip>,
+    #[option]
+    pub xy_swap: EnumOption<AxisFlip>,
 }
 
 #[derive(OptionPage, Clone)]
@@ -150,6 +174,10 @@ pub struct BeamOpts {
     pub grid: EnumOption<GridOverlay>,
     #[option(4)]
     pub grid_i: IntOption<IntensityParams>,
+    #[option]
+    pub layout: EnumOption<PaletteLayout>,
+    #[option(10)]
+    pub gamma: IntOption<GammaParams>,
 }
 
 #[derive(OptionPage, Clone)]
@@ -159,6 +187,8 @@ pub struct MiscOpts {
     #[option]
     pub plot_src: EnumOption<PlotSrc>,
     #[option]
+    pub in_scale: EnumOption<InputScale>,
+    #[option]
     pub usb_mode: EnumOption<USBMode>,
     #[option]
     pub rotation: EnumOption<Rotate>,
@@ -166,10 +196,12 @@ pub struct MiscOpts {
     pub help: EnumOption<HelpPage>,
     #[option]
     pub cc_highlight: EnumOption<CcHighlight>,
+    #[option]
+    pub meters: EnumOption<Meters>,
     #[option(false)]
-    pub save_opts: ButtonOption<OneShotButtonParams>,
+    pub save_opts: ActionOption,
     #[option(false)]
-    pub wipe_opts: ButtonOption<OneShotButtonParams>,
+    pub wipe_opts: ActionOption,
 }
 
 #[derive(OptionPage, Clone)]
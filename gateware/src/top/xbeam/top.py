@@ -98,6 +98,9 @@ can also be used to control most of these through CCs as follows:
         VECTOR  i-scale       25  in2 (beam intensity CV) scale
         VECTOR  c-offset      26  in3 (beam color) offset
         VECTOR  c-scale       27  in4 (beam color CV) scale
+        VECTOR  x-invert       -  mirror the horizontal axis
+        VECTOR  y-invert       -  mirror the vertical axis
+        VECTOR  xy-swap        -  swap in0 and in1 (before inversion)
 
         DELAY   delay-x       30  in0/X channel delayline length
         DELAY   delay-y       31  in1/Y channel delayline length
@@ -110,9 +113,12 @@ can also be used to control most of these through CCs as follows:
         BEAM    palette       43  color palette
         BEAM    grid          44  grid overlay style
         BEAM    grid-i        45  grid overlay intensity
+        BEAM    layout        46  palette hues x intensities (1x256 = smoothest)
+        BEAM    gamma         47  palette intensity gamma (high = darker)
 
         MISC    plot-type     50  vectorscope or oscilloscope
         MISC    plot-src      51  plot inputs or outputs
+        MISC    in-scale       -  input standard (5V, 10V or line level)
         MISC    usb-mode       -  enable/bypass USB audio
         MISC    rotation      52  screen rotation
         MISC    help           -  show/hide leftmost help page
@@ -171,6 +177,9 @@ class XbeamPeripheral(wiring.Component):
         usb_en:   csr.Field(csr.action.W, unsigned(1))
         usb_connect:   csr.Field(csr.action.W, unsigned(1))
         show_outputs: csr.Field(csr.action.W, unsigned(1))
+        x_invert: csr.Field(csr.action.W, unsigned(1))
+        y_invert: csr.Field(csr.action.W, unsigned(1))
+        xy_swap: csr.Field(csr.action.W, unsigned(1))
 
     class Delay(csr.Register, access="w"):
         value:   csr.Field(csr.action.W, unsigned(16))
@@ -191,6 +200,9 @@ class XbeamPeripheral(wiring.Component):
             "usb_en": Out(1),
             "usb_connect": Out(1),
             "show_outputs": Out(1),
+            "x_invert": Out(1),
+            "y_invert": Out(1),
+            "xy_swap": Out(1),
             "bus": In(csr.Signature(addr_width=regs.addr_width, data_width=regs.data_width)),
 
             # Streams in/out of plotting delay lines
@@ -217,6 +229,15 @@ class XbeamPeripheral(wiring.Component):
         with m.If(self._flags.f.show_outputs.w_stb):
             m.d.sync += self.show_outputs.eq(self._flags.f.show_outputs.w_data)
 
+        with m.If(self._flags.f.x_invert.w_stb):
+            m.d.sync += self.x_invert.eq(self._flags.f.x_invert.w_data)
+
+        with m.If(self._flags.f.y_invert.w_stb):
+            m.d.sync += self.y_invert.eq(self._flags.f.y_invert.w_data)
+
+        with m.If(self._flags.f.xy_swap.w_stb):
+            m.d.sync += self.xy_swap.eq(self._flags.f.xy_swap.w_data)
+
         # Tweakable plotting delay lines.
         m.submodules.split4 = split4 = dsp.Split(n_channels=4, source=wiring.flipped(self.delay_i))
         m.submodules.merge4 = merge4 = dsp.Merge(n_channels=4, sink=wiring.flipped(self.delay_o))
@@ -375,10 +396,28 @@ class XbeamSoc(TiliquaSoc):
             wiring.connect(m, up_split4.o[ch], r.i)
             wiring.connect(m, r.o, up_merge4.i[ch])
 
+        # Vectorscope axis transforms. X/Y are swapped first, then each
+        # (displayed) axis is optionally inverted. The vectorscope's own
+        # offset/scale apply after this, and the screen rotation last
+        # of all (by the framebuffer), so e.g. 'x-invert' always mirrors
+        # whatever ends up on the horizontal axis before rotation.
+        def vector_axes(o, i):
+            flags = self.xbeam_periph
+            x = Mux(flags.xy_swap, o.payload[1].as_value(), o.payload[0].as_value())
+            y = Mux(flags.xy_swap, o.payload[0].as_value(), o.payload[1].as_value())
+            # One's complement rather than negation, so full-scale
+            # negative samples can't overflow.
+            return [
+                i.payload[0].as_value().eq(Mux(flags.x_invert, ~x, x)),
+                i.payload[1].as_value().eq(Mux(flags.y_invert, ~y, y)),
+                i.payload[2].eq(o.payload[2]),
+                i.payload[3].eq(o.payload[3]),
+            ]
+
         with m.If(self.scope_periph.soc_en):
             wiring.connect(m, up_merge4.o, self.scope_periph.i)
         with m.Else():
-            wiring.connect(m, up_merge4.o, self.vector_periph.i)
+            dsp.connect_remap(m, up_merge4.o, self.vector_periph.i, vector_axes)
 
         return m
 
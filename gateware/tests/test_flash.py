@@ -10,6 +10,7 @@ from pathlib import Path
 from tiliqua.build.archive import ArchiveBuilder
 from tiliqua.flash import (ArchiveLoader,
                            compute_concrete_regions_to_flash,
+                           slot_image,
                            OpenFPGALoaderCommandSequence)
 from tiliqua.build.types import FirmwareLocation
 from tiliqua.platform import TiliquaRevision
@@ -155,6 +156,32 @@ class TestFlashCommandGenerator(unittest.TestCase):
             # Last command should not have --skip-reset
             self.assertNotIn("--skip-reset", commands[2])
 
+    def test_user_slot_image(self):
+
+        archiver = ArchiveBuilder(
+            build_path=str(self.build_path),
+            name="USER_IMAGE",
+            tag="jkl012",
+            hw_rev=TiliquaRevision.R5
+        ).with_bitstream()                                                         \
+         .with_firmware(str(self.firmware_path), FirmwareLocation.PSRAM, 0x200000) \
+         .with_option_storage()
+
+        archiver.create()
+
+        with ArchiveLoader(archiver.archive_path) as loader:
+            (_concrete_manifest, flashable_regions) = compute_concrete_regions_to_flash(
+                    loader.manifest, slot=2)
+            image = slot_image(flashable_regions, 2, loader.tmpdir)
+
+        # Whole slot, with regions at their offsets from the slot start (0x300000).
+        self.assertEqual(len(image), 0x100000)
+        self.assertEqual(image[:14], b'TEST_BITSTREAM')
+        self.assertEqual(image[0x90000:0x90000+13], b'TEST_FIRMWARE')
+        self.assertEqual(image[0xF0000:0xF0001], b'{')
+        # Option storage is left erased.
+        self.assertTrue(all(b == 0xff for b in image[0xE0000:0xF0000]))
+
     def test_manifest_rust_compatibility(self):
         """Test that a Python-generated manifest can be read by Rust lib.rs."""
 
@@ -93,6 +93,18 @@ class GrainPlayerTests(unittest.TestCase):
                 output_samples.append(sample)
             print(f"loop/halt: {[s.as_float() for s in output_samples]}")
 
+            # LOOP mode with crossfade: the last 2 samples of the grain fade
+            # into the first 2, and later loops start from the 3rd sample.
+            await csr_write("crossfade", {"length": 2, "gain_step": 1 << 15})
+            await csr_write("control", {"gate": 1, "mode": GrainPlayer.Mode.LOOP.value})
+            output_samples = []
+            for _ in range(12):
+                sample = await stream.get(ctx, dut.o)
+                output_samples.append(sample)
+            print(f"loop/crossfade: {[s.as_float() for s in output_samples]}")
+            await csr_write("control", {"gate": 0, "mode": GrainPlayer.Mode.GATE.value})
+            await csr_write("crossfade", {"length": 0, "gain_step": 0})
+
             # GATE mode, half speed
             await csr_write("control", {"gate": 0, "mode": GrainPlayer.Mode.GATE.value})
             await csr_write("speed", {"speed": 0x80})
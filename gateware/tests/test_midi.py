@@ -241,3 +241,66 @@ class MidiTests(unittest.TestCase):
         sim.add_testbench(testbench)
         with sim.write_vcd(vcd_file=open("test_midi_voice_tracker.vcd", "w")):
             sim.run()
+
+    def test_midi_voice_tracker_steal(self):
+
+        dut = midi.MidiVoiceTracker(steal=True)
+
+        def msg(kind, **payload):
+            return {
+                'status': {
+                    'kind': kind,
+                    'nibble': {'channel': 1},
+                },
+                'midi_payload': payload,
+            }
+
+        def note_on(note):
+            return msg(midi.Status.Kind.NOTE_ON, note_on={'note': note, 'velocity': 0x60})
+
+        def note_off(note):
+            return msg(midi.Status.Kind.NOTE_OFF, note_off={'note': note, 'velocity': 0x30})
+
+        def sustain(held):
+            return msg(midi.Status.Kind.CONTROL_CHANGE, control_change={
+                'controller_number': 64, 'data': 127 if held else 0})
+
+        note_range = list(range(40, 48))
+        steal_ix = 5
+
+        async def testbench(ctx):
+            # Occupy every voice slot.
+            for note in note_range:
+                await stream.put(ctx, dut.i, note_on(note))
+                await ctx.tick().repeat(50)
+
+            # Hold the stolen voice with the sustain pedal only.
+            await stream.put(ctx, dut.i, sustain(True))
+            await ctx.tick().repeat(50)
+            await stream.put(ctx, dut.i, note_off(note_range[steal_ix]))
+            await ctx.tick().repeat(50)
+            for n in range(dut.max_voices):
+                self.assertEqual(ctx.get(dut.o[n].gate), 1)
+                self.assertEqual(ctx.get(dut.sustained[n]), int(n == steal_ix))
+
+            # All voices busy: the NOTE_ON takes over the nominated slot.
+            ctx.set(dut.steal_ix, steal_ix)
+            await stream.put(ctx, dut.i, note_on(60))
+            await ctx.tick().repeat(50)
+            for n in range(dut.max_voices):
+                expected = 60 if n == steal_ix else note_range[n]
+                self.assertEqual(ctx.get(dut.o[n].note), expected)
+                self.assertEqual(ctx.get(dut.o[n].gate), 1)
+                self.assertEqual(ctx.get(dut.sustained[n]), 0)
+
+            # Releasing the pedal must not cut the new note off.
+            await stream.put(ctx, dut.i, sustain(False))
+            await ctx.tick().repeat(50)
+            self.assertEqual(ctx.get(dut.o[steal_ix].note), 60)
+            self.assertEqual(ctx.get(dut.o[steal_ix].gate), 1)
+
+        sim = Simulator(dut)
+        sim.add_clock(1e-6)
+        sim.add_testbench(testbench)
+        with sim.write_vcd(vcd_file=open("test_midi_voice_tracker_steal.vcd", "w")):
+            sim.run()
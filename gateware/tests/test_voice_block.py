@@ -51,6 +51,7 @@ class VoiceBlockTests(unittest.TestCase):
             ctx.set(dut.voice_gates[0], 1)
             ctx.set(dut.voice_freq_incs[0], fixed.Const(0.02, shape=ASQ))
             ctx.set(dut.voice_velocity[0], fixed.Const(0.99, shape=EnvUQ))
+            ctx.set(dut.voice_pans[0], -0x8000) # hard left
             await ctx.tick()
 
             # collect some samples
@@ -71,3 +72,60 @@ class VoiceBlockTests(unittest.TestCase):
         sim.add_testbench(testbench)
         with sim.write_vcd(vcd_file=open("test_voice_block.vcd", "w")):
             sim.run()
+
+    def test_adsr_latched_per_note(self):
+        """ADSR changes apply from the next note, not to held voices."""
+
+        N = 2
+        m = Module()
+        m.submodules.dut = dut = dsp.MultiADSR(n=N)
+        EnvUQ = dsp.MultiADSR.EnvUQ
+
+        async def testbench(ctx):
+
+            async def frames(n):
+                # Envelope of each voice, after `n` full frames.
+                for _ in range(n):
+                    levels = []
+                    for _ in range(N):
+                        sample = await stream.get(ctx, dut.o)
+                        levels.append(sample.sample.as_float())
+                return levels
+
+            ctx.set(dut.attack_rate, fixed.Const(0.25, shape=EnvUQ))
+            ctx.set(dut.decay_rate, fixed.Const(0.125, shape=EnvUQ))
+            ctx.set(dut.sustain_level, fixed.Const(0.5, shape=EnvUQ))
+            ctx.set(dut.release_rate, fixed.Const(0.125, shape=EnvUQ))
+            for n in range(N):
+                ctx.set(dut.voice_velocity[n], fixed.Const(0.99, shape=EnvUQ))
+
+            # voice 0 reaches sustain
+            ctx.set(dut.voice_gates[0], 1)
+            levels = await frames(20)
+            self.assertAlmostEqual(levels[0], 0.5*0.99, delta=0.01)
+            self.assertEqual(levels[1], 0.0)
+
+            # new sustain level: held voice 0 is unaffected
+            ctx.set(dut.sustain_level, fixed.Const(0.25, shape=EnvUQ))
+            ctx.set(dut.release_rate, fixed.Const(0.0625, shape=EnvUQ))
+            levels = await frames(20)
+            self.assertAlmostEqual(levels[0], 0.5*0.99, delta=0.01)
+
+            # ... but the next note uses it
+            ctx.set(dut.voice_gates[1], 1)
+            levels = await frames(20)
+            self.assertAlmostEqual(levels[0], 0.5*0.99, delta=0.01)
+            self.assertAlmostEqual(levels[1], 0.25*0.99, delta=0.01)
+
+            # voice 0 releases at its own (faster) rate: 4 frames from 0.5
+            ctx.set(dut.voice_gates[0], 0)
+            levels = await frames(5)
+            self.assertEqual(levels[0], 0.0)
+            self.assertAlmostEqual(levels[1], 0.25*0.99, delta=0.01)
+            self.assertEqual(ctx.get(dut.voice_active[0]), 0)
+
+        sim = Simulator(m)
+        sim.add_clock(1e-6)
+        sim.add_testbench(testbench)
+        with sim.write_vcd(vcd_file=open("test_adsr_latched.vcd", "w")):
+            sim.run()